@@ -0,0 +1,82 @@
+use clap::Command;
+use clap_complete::Shell;
+
+/// Completes `agents://<provider>/<partial>` and `<provider>/<partial>` words with session ids
+/// listed by `xurl __complete-sessions <provider>`, falling back to the generated completion.
+const BASH_SESSIONS: &str = r#"
+_xurl_with_sessions() {
+    local line="${COMP_LINE:0:COMP_POINT}"
+    local word="${line##*[[:space:]]}"
+    local target="${word#agents://}"
+    local provider="${target%%/*}"
+    local partial="${target#*/}"
+    if [[ "$target" == */* && "$partial" != */* && "$provider" =~ ^[a-z]+$ ]]; then
+        local prefix="${word%"$partial"}"
+        local colon_prefix="${word%"${word##*:}"}"
+        local id
+        COMPREPLY=()
+        while IFS= read -r id; do
+            if [[ "$id" == "$partial"* ]]; then
+                COMPREPLY+=("${prefix#"$colon_prefix"}$id")
+            fi
+        done < <(xurl __complete-sessions "$provider" 2>/dev/null)
+        return 0
+    fi
+    _xurl "$@"
+}
+
+if [[ "${BASH_VERSINFO[0]}" -eq 4 && "${BASH_VERSINFO[1]}" -ge 4 || "${BASH_VERSINFO[0]}" -gt 4 ]]; then
+    complete -F _xurl_with_sessions -o nosort -o bashdefault -o default xurl
+else
+    complete -F _xurl_with_sessions -o bashdefault -o default xurl
+fi
+"#;
+
+const ZSH_SESSIONS: &str = r#"
+_xurl_with_sessions() {
+    local word="${words[CURRENT]}"
+    local target="${word#agents://}"
+    local provider="${target%%/*}"
+    local partial="${target#*/}"
+    if [[ "$target" == */* && "$partial" != */* && "$provider" =~ '^[a-z]+$' ]]; then
+        local -a ids
+        ids=(${(f)"$(xurl __complete-sessions "$provider" 2>/dev/null)"})
+        ids=(${(M)ids:#${partial}*})
+        compadd -U -- "${word%$partial}"${^ids}
+        return
+    fi
+    _xurl "$@"
+}
+
+compdef _xurl_with_sessions xurl
+"#;
+
+const FISH_SESSIONS: &str = r#"
+function __xurl_complete_sessions
+    set -l word (commandline -ct)
+    set -l target (string replace -r '^agents://' '' -- $word)
+    string match -qr '^[a-z]+/[^/]*$' -- $target; or return
+    set -l provider (string split -m1 / -- $target)[1]
+    set -l prefix (string replace -r '[^/]*$' '' -- $word)
+    for id in (xurl __complete-sessions $provider 2>/dev/null)
+        echo $prefix$id
+    end
+end
+
+complete -c xurl -a '(__xurl_complete_sessions)'
+"#;
+
+pub(crate) fn render(shell: Shell, mut command: Command) -> String {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, "xurl", &mut script);
+    let mut script = String::from_utf8_lossy(&script).into_owned();
+
+    let sessions = match shell {
+        Shell::Bash => BASH_SESSIONS,
+        Shell::Zsh => ZSH_SESSIONS,
+        Shell::Fish => FISH_SESSIONS,
+        _ => "",
+    };
+    script.push_str(sessions);
+    script
+}
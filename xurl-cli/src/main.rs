@@ -1,42 +1,717 @@
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::process::ExitCode;
-use std::{fs, io};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use std::{fmt, fs, io};
 
-use std::io::{Read, Write};
+use std::io::{BufRead, IsTerminal, Read, Write};
 
-use clap::Parser;
+mod completions;
+
+use clap::{ArgAction, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use tracing_subscriber::EnvFilter;
+use xurl_core::jsonl::corrupt_lines_warning;
+use xurl_core::timestamp::format_duration;
 use xurl_core::uri::{
-    is_uuid_session_id, parse_collection_query_uri, parse_role_query_uri, parse_role_uri,
+    expand_alias, is_alias_name, is_uuid_session_id, parse_collection_query_uri, parse_provider,
+    parse_role_query_uri, parse_role_uri,
 };
 use xurl_core::{
-    AgentsUri, ProviderKind, ProviderRoots, SkillsUri, WriteEventSink, WriteOptions, WriteRequest,
-    WriteResult, XurlError, query_threads, render_skill_head_markdown, render_skill_markdown,
-    render_subagent_view_markdown, render_thread_head_markdown, render_thread_markdown,
-    render_thread_query_head_markdown, render_thread_query_markdown, resolve_skill,
-    resolve_subagent_view, resolve_thread, write_thread,
+    AgentsUri, ApprovalMode, CacheGcOptions, DEFAULT_SUMMARY_TEMPLATE, ExportFormat, FileUri,
+    FindingsFormat, GH_COMMENT_MAX_CHARS, LineRange, MulticastWrite, ProviderCli, ProviderKind,
+    ProviderRoots, RawSlice, RenderOptions, ResolvedThread, SUMMARY_MAX_THREAD_BYTES, ScanOptions,
+    ScrubKind, Scrubber, SkillsUri, SnapshotUri, SubagentListOptions, SubagentSort, ThreadEvent,
+    ThreadEventKind, ThreadEventSink, TimeZone, TimelineFilter, WatchOptions, WriteCommand,
+    WriteEventSink, WriteOptions, WriteRequest, WriteResult, WriteSummaryFormat, XurlConfig,
+    XurlError, blame_commit, compare_thread, dataset_rows, doctor, enrich_frontmatter,
+    expand_latest_alias, expand_prompt_template, expand_session_prefix, export_thread,
+    export_threads_db, fsck_provider, gc_caches, inline_thread, lint_thread, list_session_ids,
+    load_template, locate_thread, multicast_write, query_threads, recent_threads,
+    render_doctor_markdown, render_file_head_markdown, render_findings_json, render_findings_sarif,
+    render_fsck_markdown, render_gh_comment, render_lint_markdown, render_resolution_markdown,
+    render_running_threads_json, render_running_threads_markdown, render_skill_head_markdown,
+    render_skill_markdown, render_snapshot_head_markdown, render_subagent_view_markdown,
+    render_thread_branches_markdown, render_thread_comparison_markdown,
+    render_thread_diff_markdown, render_thread_event_json, render_thread_head_markdown,
+    render_thread_history_markdown, render_thread_links_json, render_thread_links_markdown,
+    render_thread_metadata_head_markdown, render_thread_query_head_markdown,
+    render_thread_query_markdown, render_thread_webhook_json, resolve_file_thread, resolve_skill,
+    resolve_snapshot, resolve_subagent_view, resolve_thread, run_ordered, running_threads,
+    select_frontmatter_fields, snapshot_thread, subagent_view_settled, subagent_view_to_raw_json,
+    summary_prompt, sync_vault, thread_findings, thread_links, thread_query_to_raw_json,
+    thread_user_prompts, watch_threads, workspace_threads, write_dataset_csv,
+    write_dataset_parquet, write_result_summary_json, write_subagent_raw, write_thread,
+    write_thread_markdown, write_thread_raw,
 };
 
+/// Log filter for `-v`; `-vv` and above use `TRACE_LOG_FILTER`.
+const VERBOSE_LOG_FILTER: &str = "xurl_core=debug,xurl=debug";
+const TRACE_LOG_FILTER: &str = "xurl_core=trace,xurl=trace";
+
+/// Idle period `xurl watch --webhook` uses when `--idle` is not given.
+const DEFAULT_WEBHOOK_IDLE: Duration = Duration::from_secs(60);
+/// Longest a webhook POST may take before it is reported as failed.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often `--wait` re-runs subagent discovery when `--poll` is not given.
+const DEFAULT_SUBAGENT_POLL: Duration = Duration::from_secs(5);
+/// How long `--wait` polls before giving up when `--timeout` is not given.
+const DEFAULT_SUBAGENT_WAIT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
 #[derive(Debug, Parser)]
-#[command(name = "xurl", version, about = "Resolve and read code-agent threads")]
+#[command(
+    name = "xurl",
+    version,
+    about = "Resolve and read code-agent threads",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Cli {
-    /// Thread URI like agents://codex/<session_id>, codex/<session_id>, agents://claude/<session_id>, agents://pi/<session_id>/<child_or_entry_id>, or legacy forms like codex://<session_id>
-    uri: String,
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Thread URI like agents://codex/<session_id>, codex/<session_id>, agents://claude/<session_id>, agents://pi/<session_id>/<child_or_entry_id>, legacy forms like codex://<session_id>, or `.` for the latest thread started in the current directory; several URIs are read one after another (with -o, into one file each in the DIR it names)
+    #[arg(required_unless_present = "stdin_uris")]
+    uris: Vec<String>,
+
+    /// Also read URIs from stdin, one per line, like the output of `xurl last`; blank lines and lines starting with `#` are skipped
+    #[arg(long)]
+    stdin_uris: bool,
+
+    /// Read up to N URIs at once when several are given; output keeps their order
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    jobs: usize,
 
     /// Output frontmatter only (header mode)
     #[arg(short = 'I', long)]
     head: bool,
 
-    /// Send write-mode payload data; may be repeated. Prefix with @file or @- for stdin.
+    /// With -I, read only the session header and first records of a thread, or sqlite counts for OpenCode, instead of the whole thread
+    #[arg(long)]
+    fast: bool,
+
+    /// With -I, print only these comma-separated top-level frontmatter keys, like uri,status,subagents; defaults to `[head] fields` in the config file
+    #[arg(long, value_name = "KEYS", value_delimiter = ',')]
+    head_fields: Option<Vec<String>>,
+
+    /// With -I, order the subagents index: started (earliest first), status (active first), duration (longest first), or discovered
+    #[arg(long, value_name = "KEY")]
+    sort: Option<SubagentSort>,
+
+    /// With -I, list only subagents with these comma-separated statuses, like running,errored
+    #[arg(long, value_name = "STATUSES", value_delimiter = ',')]
+    status: Vec<String>,
+
+    /// Print the provider-native JSON/JSONL payload instead of markdown
+    #[arg(long)]
+    raw: bool,
+
+    /// Render every branch of a pi session with its divergence point
+    #[arg(long)]
+    branches: bool,
+
+    /// Render only user messages, assistant messages, or tool calls: user, assistant, or tools
+    #[arg(long, value_name = "KIND")]
+    only: Option<TimelineFilter>,
+
+    /// Render only the messages and tool calls containing TEXT (ignoring case), with surrounding lines
+    #[arg(long, value_name = "TEXT")]
+    grep: Option<String>,
+
+    /// Stop rendering the thread body after this many bytes and note the truncation
+    #[arg(long, value_name = "BYTES")]
+    max_bytes: Option<u64>,
+
+    /// Keep tool outputs over the `[render] elide_bytes` threshold instead of eliding them
+    #[arg(long)]
+    no_elide: bool,
+
+    /// Show each message's timestamp and the time since the previous message, when the provider records them
+    #[arg(long)]
+    timestamps: bool,
+
+    /// Show timestamps in this zone: local, utc (default), an offset like +08:00, or a zone name like Asia/Shanghai
+    #[arg(long, value_name = "ZONE")]
+    timezone: Option<TimeZone>,
+
+    /// Show Claude slash commands, hooks, and system notices in the timeline
+    #[arg(long)]
+    events: bool,
+
+    /// Also render the earlier sessions this thread was resumed or forked from
+    #[arg(long)]
+    with_history: bool,
+
+    /// With a subagent drill-down URI, also list the agents it spawned, down to N levels
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    depth: usize,
+
+    /// Block until a subagent drill-down URI leaves pendingInit/running, then render it
+    #[arg(long)]
+    wait: bool,
+
+    /// How often --wait re-runs subagent discovery, like 5s or 1m
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "wait")]
+    poll: Option<Duration>,
+
+    /// Give up --wait with an error after this long, like 90s or 10m (default 30m)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "wait")]
+    timeout: Option<Duration>,
+
+    /// Accept the leading part of a session id, like codex://019c871c, and resolve it to the one thread it matches
+    #[arg(long)]
+    prefix: bool,
+
+    /// Limit `xurl .` to one provider like codex or claude
+    #[arg(long, value_name = "PROVIDER")]
+    provider: Option<String>,
+
+    /// Read a file:// URI as this provider's thread file, like codex or claude
+    #[arg(long = "as", value_name = "PROVIDER")]
+    file_provider: Option<String>,
+
+    /// Send write-mode payload data; may be repeated. Prefix with @file or @- for stdin, or use thread:<URI> to inline another thread
     #[arg(short = 'd', long = "data", value_name = "DATA")]
     data: Vec<String>,
 
+    /// Never treat piped stdin as a write-mode prompt
+    #[arg(long)]
+    no_stdin: bool,
+
+    /// Retry a write up to N more times, with exponential backoff, when the provider CLI fails before starting a session
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    retries: u32,
+
+    /// Extra system instructions for write mode, as text or @file (same as `?system=`)
+    #[arg(long, value_name = "TEXT")]
+    system: Option<String>,
+
+    /// Fill the write-mode prompt into the template NAME from ~/.xurl/templates/NAME.md, expanding {{data}}, {{file:<path>}}, and {{thread:<uri>}}
+    #[arg(long, value_name = "NAME")]
+    template: Option<String>,
+
+    /// Stop the provider once its reply exceeds this many characters
+    #[arg(long, value_name = "CHARS")]
+    max_output_chars: Option<usize>,
+
+    /// Stop the provider once it has run this long, like 90, 90s, 5m, or 1h
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    max_duration: Option<Duration>,
+
+    /// Print the provider command line, working directory, and environment instead of running it
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Keep the session open and send each stdin line as a follow-up prompt until EOF
+    #[arg(long)]
+    interactive: bool,
+
     /// Write output to a file instead of stdout
     #[arg(short = 'o', long = "output", value_name = "PATH")]
     output: Option<PathBuf>,
+
+    /// Log resolution steps to stderr; repeat (-vv) for trace output. Overrides `XURL_LOG`
+    #[arg(short = 'v', long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Read thread files at most N directory levels below each provider root
+    #[arg(long, value_name = "N", global = true)]
+    max_depth: Option<usize>,
+
+    /// Follow symlinks while walking provider roots for threads
+    #[arg(long, global = true)]
+    follow_symlinks: bool,
+
+    /// Drop `warning:` lines and progress notices from stderr; errors are still printed
+    #[arg(short = 'q', long, global = true)]
+    quiet: bool,
+
+    /// Print the URI of a created or updated thread on stdout, after the reply, instead of on stderr
+    #[arg(long, global = true)]
+    print_uri: bool,
+
+    /// Discard the reply of a write and print only the created or updated thread URI on stdout
+    #[arg(long, global = true)]
+    print_uri_only: bool,
+
+    /// After each write, print a summary of it (uri, provider, duration, bytes streamed, warnings, exit code) to stderr; FORMAT is json
+    #[arg(long, value_name = "FORMAT", global = true)]
+    write_summary: Option<WriteSummaryFormat>,
+
+    /// Append the --write-summary report to PATH instead of printing it to stderr
+    #[arg(long, value_name = "PATH", global = true)]
+    write_summary_file: Option<PathBuf>,
+
+    /// Stop walking a provider root after this long, like 10s, and use the threads found so far
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    scan_timeout: Option<Duration>,
+
+    /// Fail instead of warning when a thread has corrupt lines, unknown record types, or a newer format
+    #[arg(long, global = true)]
+    strict: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum AliasCommand {
+    /// Point NAME at a URI, replacing an earlier alias of the same name
+    Set {
+        /// Alias name of letters, digits, `-`, and `_`, like release-debug
+        name: String,
+
+        /// URI the alias stands for, like agents://codex/<session_id>
+        uri: String,
+    },
+    /// Delete an alias
+    Remove {
+        /// Alias name to delete
+        name: String,
+    },
+    /// Print every alias as `name = uri`
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+enum CacheCommand {
+    /// Delete cache entries older than --max-age, then the oldest ones until the caches fit in --max-size
+    Gc {
+        /// Delete entries not refreshed for this long, like 12h or 30d
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "30d")]
+        max_age: Duration,
+
+        /// Then delete the oldest entries until the caches total at most this much, like 500M or 1G
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_size: Option<u64>,
+
+        /// List what would be deleted without deleting it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Print URIs of the most recently updated threads
+    Last {
+        /// Provider name like codex or claude; all providers when omitted
+        provider: Option<String>,
+
+        /// Number of threads to print
+        #[arg(short = 'n', long = "limit", default_value_t = 1)]
+        limit: usize,
+
+        /// Render the newest thread as markdown instead of printing URIs
+        #[arg(long)]
+        render: bool,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// List sessions that look like they are running right now, with their working directories
+    Ps {
+        /// Provider name like codex or claude; all providers when omitted
+        provider: Option<String>,
+
+        /// Count threads written this recently as running unless they finished, like 90s or 10m
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "10m")]
+        within: Duration,
+
+        /// Print JSON instead of markdown
+        #[arg(long)]
+        json: bool,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Render threads into a markdown vault (like an Obsidian folder), rewriting only changed notes
+    Sync {
+        /// Vault directory; notes go to <DIR>/<provider>/<session_id>.md
+        #[arg(long, value_name = "DIR")]
+        vault: PathBuf,
+
+        /// Only sync threads of this provider, like codex or claude
+        #[arg(long, value_name = "PROVIDER")]
+        provider: Option<String>,
+
+        /// Only sync the most recently updated N threads
+        #[arg(short = 'n', long = "limit", value_name = "N")]
+        limit: Option<usize>,
+    },
+    /// Compare two branches of a pi session after their last common entry
+    Diff {
+        /// First branch URI like agents://pi/<session_id>/<entry_id>
+        left: String,
+
+        /// Second branch URI like agents://pi/<session_id>/<entry_id>
+        right: String,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Print URIs of threads whose tool output mentions a commit
+    Blame {
+        /// Commit-ish resolved in the current git repository, like HEAD or a SHA
+        commit: String,
+
+        /// Provider name like codex or claude; all providers when omitted
+        #[arg(long, value_name = "PROVIDER")]
+        provider: Option<String>,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// List file paths, URLs, and commits referenced by a thread
+    Links {
+        /// Thread URI like agents://codex/<session_id>
+        uri: String,
+
+        /// Print JSON instead of markdown
+        #[arg(long)]
+        json: bool,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Extract file/line/severity findings from a review thread's assistant messages
+    Findings {
+        /// Thread URI like agents://codex/<session_id>
+        uri: String,
+
+        /// Output format: json (default) or sarif
+        #[arg(long, value_name = "FORMAT", default_value = "json")]
+        format: FindingsFormat,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Copy a thread file into ~/.xurl/snapshots and print its snapshot://<sha256> URI
+    Snapshot {
+        /// Thread URI like agents://codex/<session_id>
+        uri: String,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Print a thread's provider-native payload as stored, without rendering
+    Cat {
+        /// Thread, subagent, or snapshot URI, like agents://codex/<session_id>, agents://codex/<session_id>/<agent_id>, or snapshot://<sha256>
+        uri: String,
+
+        /// Print only lines FIRST through LAST, counted from 1, like 1000:2000; either side may be left out
+        #[arg(long, value_name = "FIRST:LAST", conflicts_with = "tail")]
+        lines: Option<LineRange>,
+
+        /// Print only the last N lines
+        #[arg(long, value_name = "N")]
+        tail: Option<usize>,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Print the canonical URI, thread file, and discovery source of a thread without reading it
+    Resolve {
+        /// Thread URI in any accepted form, like codex/latest or codex://<session_id>
+        uri: String,
+
+        /// Accept the leading part of a session id and resolve it to the one thread it matches
+        #[arg(long)]
+        prefix: bool,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Check a thread file for malformed lines, missing timestamps, and orphaned tool outputs
+    Lint {
+        /// Thread URI like agents://codex/<session_id>
+        uri: String,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Check provider data roots, sqlite indexes, CLIs, and credentials
+    Doctor {
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Export a thread as markdown with embedded images and files written next to it
+    Extract {
+        /// Thread URI like agents://claude/<session_id>
+        uri: String,
+
+        /// Directory to write thread.md and attachments/ into
+        #[arg(long, value_name = "DIR")]
+        out: PathBuf,
+    },
+    /// Export a thread for pasting elsewhere, like a collapsible GitHub comment, or threads as a per-message dataset
+    Export {
+        /// Thread URI like agents://claude/<session_id>; csv and parquet also take agents://<provider>
+        uri: String,
+
+        /// Rendition to produce: gh-comment, csv, or parquet
+        #[arg(long, value_name = "FORMAT")]
+        format: ExportFormat,
+
+        /// Write output to a file instead of stdout; gh-comment parts after the first go to <stem>.<n>.<ext>
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Comma-separated values to rewrite into stable placeholders: paths, emails, hostnames
+        #[arg(long, value_name = "KINDS", value_delimiter = ',')]
+        scrub: Vec<ScrubKind>,
+
+        /// Write the placeholder-to-value mapping of --scrub to this JSON file
+        #[arg(long = "scrub-map", value_name = "PATH", requires = "scrub")]
+        scrub_map: Option<PathBuf>,
+
+        /// With csv or parquet and agents://<provider>, read up to N threads at once; rows keep their order
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        jobs: usize,
+    },
+    /// Write threads, messages, and tool calls into a sqlite database for SQL analytics
+    ExportDb {
+        /// Database file to create or refresh
+        #[arg(long, value_name = "PATH")]
+        out: PathBuf,
+
+        /// Only export threads of this provider, like codex or claude
+        #[arg(long, value_name = "PROVIDER")]
+        provider: Option<String>,
+    },
+    /// Re-send the user prompts of a thread, one by one, to a new session
+    Replay {
+        /// Thread URI to read prompts from, like agents://codex/<session_id>
+        uri: String,
+
+        /// Provider or role URI that starts the new session, like agents://claude
+        #[arg(long = "to", value_name = "URI")]
+        to: String,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Summarize a thread by sending it, rendered, to a new provider session
+    Summarize {
+        /// Thread URI to summarize, like agents://codex/<session_id>
+        uri: String,
+
+        /// Provider or role URI that writes the summary, like agents://claude; defaults to the thread's provider
+        #[arg(long, value_name = "URI")]
+        via: Option<String>,
+
+        /// Summary prompt as text or @file, with {{thread}} where the rendered thread goes; may also use {{uri}}, {{provider}}, and {{session_id}}
+        #[arg(long, value_name = "TEMPLATE")]
+        template: Option<String>,
+
+        /// Include at most this many bytes of the rendered thread in the prompt (default 102400)
+        #[arg(long, value_name = "BYTES")]
+        max_bytes: Option<u64>,
+
+        /// Write the summary to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Send one prompt to several providers at once, streaming their replies line by line with labels
+    Multicast {
+        /// Prompt data; may be repeated. Prefix with @file or @- for stdin, or use thread:<URI> to inline another thread
+        #[arg(short = 'd', long = "data", value_name = "DATA", required = true)]
+        data: Vec<String>,
+
+        /// Comma-separated provider, role, or thread URIs to write to, like agents://codex,agents://claude
+        #[arg(
+            long = "to",
+            value_name = "URIS",
+            value_delimiter = ',',
+            required = true
+        )]
+        to: Vec<String>,
+
+        /// Write the labeled replies to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Compare threads side by side: final answers, tool usage, duration, and token usage
+    Compare {
+        /// Two or more thread URIs, like the ones `xurl multicast` prints
+        #[arg(required = true, num_args = 2..)]
+        uris: Vec<String>,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Cross-check sqlite thread indexes against files on disk
+    Fsck {
+        /// Provider to check: codex or opencode
+        #[arg(
+            required_unless_present = "provider_flag",
+            conflicts_with = "provider_flag"
+        )]
+        provider: Option<String>,
+
+        /// Provider to check (same as the positional argument)
+        #[arg(long = "provider", id = "provider_flag", value_name = "PROVIDER")]
+        provider_flag: Option<String>,
+
+        /// Update codex sqlite rows that point at moved rollout files
+        #[arg(long)]
+        fix: bool,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Manage URI aliases stored in the `[aliases]` table of the xurl config
+    Alias {
+        #[command(subcommand)]
+        command: AliasCommand,
+    },
+    /// Manage the caches xurl rebuilds on demand: materialized OpenCode exports and skills repo clones
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Print a JSON line each time a thread is created or updated
+    Watch {
+        /// Provider name like codex or claude; all providers when omitted
+        provider: Option<String>,
+
+        /// Exit after printing this many events
+        #[arg(long, value_name = "N")]
+        count: Option<usize>,
+
+        /// Print a thread_idle event once a changed thread has been quiet this long, like 90s or 5m
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        idle: Option<Duration>,
+
+        /// POST each thread_idle event as JSON to this URL; implies --idle 60s
+        #[arg(long, value_name = "URL")]
+        webhook: Option<String>,
+    },
+    /// Print a shell completion script that also completes session ids
+    Completions {
+        /// Target shell
+        shell: Shell,
+    },
+    /// List session ids for shell completion, newest first
+    #[command(name = "__complete-sessions", hide = true)]
+    CompleteSessions {
+        /// Provider name like codex or claude
+        provider: String,
+    },
+}
+
+/// Where notices, write replies, and write summaries go, from `-q/--quiet`, `--print-uri`,
+/// `--print-uri-only`, and `--write-summary`.
+#[derive(Debug, Clone, Default)]
+struct OutputOptions {
+    quiet: bool,
+    print_uri: bool,
+    discard_reply: bool,
+    write_summary: Option<WriteSummaryFormat>,
+    write_summary_file: Option<PathBuf>,
+}
+
+static OUTPUT_OPTIONS: OnceLock<OutputOptions> = OnceLock::new();
+
+fn output_options() -> &'static OutputOptions {
+    OUTPUT_OPTIONS.get_or_init(OutputOptions::default)
+}
+
+/// The xurl config, loaded once in `main` before anything reads it.
+static CONFIG: OnceLock<XurlConfig> = OnceLock::new();
+
+fn config() -> &'static XurlConfig {
+    CONFIG.get_or_init(XurlConfig::default)
+}
+
+fn quiet() -> bool {
+    output_options().quiet
+}
+
+fn print_uri() -> bool {
+    output_options().print_uri
+}
+
+/// Prints a `warning:` line to stderr unless `-q/--quiet` is set.
+fn warn(warning: impl fmt::Display) {
+    if !quiet() {
+        eprintln!("warning: {warning}");
+    }
+}
+
+/// Prints a progress notice to stderr unless `-q/--quiet` is set.
+fn notice(message: impl fmt::Display) {
+    if !quiet() {
+        eprintln!("{message}");
+    }
+}
+
+/// Reports the thread a write created or updated: on stdout with `--print-uri`, otherwise as
+/// a stderr notice.
+fn report_thread_uri(verb: &str, uri: &str) {
+    if print_uri() {
+        println!("{uri}");
+    } else {
+        notice(format_args!("{verb}: {uri}"));
+    }
+}
+
+/// Reports a finished write for `--write-summary`: appended to `--write-summary-file` when
+/// set, otherwise printed to stderr, even with `-q/--quiet`.
+fn report_write_summary(result: &WriteResult) -> xurl_core::Result<()> {
+    let options = output_options();
+    let Some(WriteSummaryFormat::Json) = options.write_summary else {
+        return Ok(());
+    };
+    let summary = write_result_summary_json(result)?;
+    match &options.write_summary_file {
+        Some(path) => fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(summary.as_bytes()))
+            .map_err(|source| XurlError::Io {
+                path: path.clone(),
+                source,
+            }),
+        None => {
+            eprint!("{summary}");
+            Ok(())
+        }
+    }
 }
 
 fn main() -> ExitCode {
     let cli = Cli::parse();
+    OUTPUT_OPTIONS.get_or_init(|| OutputOptions {
+        quiet: cli.quiet,
+        print_uri: cli.print_uri || cli.print_uri_only,
+        discard_reply: cli.print_uri_only,
+        write_summary: cli.write_summary,
+        write_summary_file: cli.write_summary_file.clone(),
+    });
+    init_logging(cli.verbose);
+    match XurlConfig::load() {
+        Ok(loaded) => {
+            CONFIG.get_or_init(|| loaded);
+        }
+        Err(err) => {
+            eprintln!("error: {}", user_facing_error(&err));
+            return ExitCode::from(1);
+        }
+    }
 
     match run(cli) {
         Ok(()) => ExitCode::SUCCESS,
@@ -47,111 +722,1564 @@ fn main() -> ExitCode {
     }
 }
 
-fn run(cli: Cli) -> xurl_core::Result<()> {
-    let Cli {
-        uri,
-        head,
-        data,
-        output,
-    } = cli;
-    let roots = ProviderRoots::from_env_or_home()?;
-    let output = output.as_deref();
-    if uri.starts_with("skills://") && !data.is_empty() {
+/// Installs a stderr logger when `-v` is given or `XURL_LOG` holds a filter like `xurl_core=debug`.
+fn init_logging(verbose: u8) {
+    let spec = match verbose {
+        0 => match std::env::var("XURL_LOG") {
+            Ok(spec) if !spec.trim().is_empty() => spec,
+            _ => return,
+        },
+        1 => VERBOSE_LOG_FILTER.to_string(),
+        _ => TRACE_LOG_FILTER.to_string(),
+    };
+    let filter = match EnvFilter::try_new(&spec) {
+        Ok(filter) => filter,
+        Err(err) => {
+            warn(format_args!(
+                "ignoring invalid XURL_LOG filter `{spec}`: {err}"
+            ));
+            return;
+        }
+    };
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .without_time()
+        .init();
+}
+
+fn run(cli: Cli) -> xurl_core::Result<()> {
+    let Cli {
+        command,
+        mut uris,
+        stdin_uris,
+        jobs,
+        head,
+        fast,
+        head_fields,
+        sort,
+        status,
+        raw,
+        branches,
+        only,
+        grep,
+        with_history,
+        max_bytes,
+        no_elide,
+        timestamps,
+        timezone,
+        events,
+        depth,
+        wait,
+        poll,
+        timeout,
+        prefix,
+        provider,
+        file_provider,
+        data,
+        no_stdin,
+        retries,
+        system,
+        template,
+        max_output_chars,
+        max_duration,
+        dry_run,
+        interactive,
+        output,
+        verbose: _,
+        max_depth,
+        follow_symlinks,
+        scan_timeout,
+        strict,
+        quiet: _,
+        print_uri,
+        print_uri_only,
+        write_summary,
+        write_summary_file,
+    } = cli;
+    if print_uri_only && (print_uri || output.is_some() || dry_run) {
+        return Err(XurlError::InvalidMode(
+            "--print-uri-only discards the reply; drop --print-uri, -o/--output, and --dry-run"
+                .to_string(),
+        ));
+    }
+    if write_summary_file.is_some() && write_summary.is_none() {
+        return Err(XurlError::InvalidMode(
+            "--write-summary-file requires --write-summary json".to_string(),
+        ));
+    }
+    if fast && !head {
+        return Err(XurlError::InvalidMode(
+            "--fast only applies to header mode; add -I/--head".to_string(),
+        ));
+    }
+    if head_fields.is_some() && !head {
+        return Err(XurlError::InvalidMode(
+            "--head-fields only applies to header mode; add -I/--head".to_string(),
+        ));
+    }
+    if (sort.is_some() || !status.is_empty()) && !head {
+        return Err(XurlError::InvalidMode(
+            "--sort and --status order the subagents index of header mode; add -I/--head"
+                .to_string(),
+        ));
+    }
+    let roots = ProviderRoots {
+        scan: ScanOptions {
+            max_depth,
+            follow_symlinks,
+            timeout: scan_timeout,
+            ignore: config().ignore.clone(),
+        },
+        strict,
+        ..ProviderRoots::from_env_or_home()?
+    };
+    if let Some(command) = command {
+        return run_command(command, &roots);
+    }
+    if stdin_uris {
+        if !data.is_empty() || interactive {
+            return Err(XurlError::InvalidMode(
+                "--stdin-uris only applies to read mode; drop -d/--data and --interactive"
+                    .to_string(),
+            ));
+        }
+        uris.extend(read_stdin_uris()?);
+        if uris.is_empty() {
+            return Err(XurlError::InvalidMode(
+                "--stdin-uris read no URIs from stdin".to_string(),
+            ));
+        }
+    }
+    if jobs == 0 {
+        return Err(XurlError::InvalidMode(
+            "--jobs must be at least 1".to_string(),
+        ));
+    }
+    let uris = uris
+        .iter()
+        .map(|uri| expand_config_alias(uri).map(std::borrow::Cow::into_owned))
+        .collect::<xurl_core::Result<Vec<_>>>()?;
+    let output = output.as_deref();
+    let options = RenderOptions {
+        only,
+        with_history,
+        max_bytes,
+        elide_bytes: if no_elide || !data.is_empty() {
+            None
+        } else {
+            Some(config().render.elide_bytes)
+        },
+        grep,
+        descendant_depth: depth,
+        show_timestamps: timestamps,
+        timezone: timezone.unwrap_or_default(),
+        show_events: events,
+    };
+    if options
+        .grep
+        .as_deref()
+        .is_some_and(|text| text.trim().is_empty())
+    {
+        return Err(XurlError::InvalidMode(
+            "--grep requires text to search for".to_string(),
+        ));
+    }
+    let read_flag = if only.is_some() {
+        Some("--only")
+    } else if options.grep.is_some() {
+        Some("--grep")
+    } else if with_history {
+        Some("--with-history")
+    } else if max_bytes.is_some() {
+        Some("--max-bytes")
+    } else if timestamps {
+        Some("--timestamps")
+    } else if events {
+        Some("--events")
+    } else {
+        None
+    };
+    if let Some(flag) = read_flag
+        && (head || branches || !data.is_empty())
+    {
+        return Err(XurlError::InvalidMode(format!(
+            "{flag} only applies to read mode; drop -I/--head, --branches, and -d/--data"
+        )));
+    }
+    if raw && (head || branches || !data.is_empty() || read_flag.is_some()) {
+        return Err(XurlError::InvalidMode(
+            "--raw prints the payload as stored; drop -I/--head, --branches, --only, --grep, --with-history, --max-bytes, --timestamps, --events, and -d/--data".to_string(),
+        ));
+    }
+    if interactive {
+        if head || raw || branches || read_flag.is_some() {
+            return Err(XurlError::InvalidMode(
+                "--interactive is a write mode; drop -I/--head, --raw, --branches, and read-only flags"
+                    .to_string(),
+            ));
+        }
+        if dry_run {
+            return Err(XurlError::InvalidMode(
+                "--dry-run cannot be combined with --interactive".to_string(),
+            ));
+        }
+        let [uri] = uris.as_slice() else {
+            return Err(XurlError::InvalidMode(
+                "--interactive takes a single URI".to_string(),
+            ));
+        };
+        if uri == "."
+            || ["skills://", "snapshot://", "file://"]
+                .iter()
+                .any(|scheme| uri.starts_with(scheme))
+        {
+            return Err(XurlError::InvalidMode(
+                "--interactive requires a provider, role, or thread URI".to_string(),
+            ));
+        }
+        let flags = WriteFlags {
+            retries,
+            system,
+            max_output_chars,
+            max_duration,
+            dry_run,
+        };
+        return run_interactive(
+            uri,
+            prefix,
+            &data,
+            template.as_deref(),
+            flags,
+            &roots,
+            output,
+        );
+    }
+
+    if depth > 0 && (head || branches || interactive || !data.is_empty()) {
+        return Err(XurlError::InvalidMode(
+            "--depth only applies to reading a subagent URI; drop -I/--head, --branches, --interactive, and -d/--data"
+                .to_string(),
+        ));
+    }
+    if wait && (branches || interactive || !data.is_empty()) {
+        return Err(XurlError::InvalidMode(
+            "--wait only applies to reading a subagent URI; drop --branches, --interactive, and -d/--data"
+                .to_string(),
+        ));
+    }
+    let poll = poll.unwrap_or(DEFAULT_SUBAGENT_POLL);
+    let wait_timeout = timeout.unwrap_or(DEFAULT_SUBAGENT_WAIT_TIMEOUT);
+
+    // Like `-d @-`, but implied when a prompt is piped in to a URI that can only start a new
+    // thread. Thread reads never touch stdin; appending needs an explicit `-d @-`.
+    let piped_prompt = if data.is_empty()
+        && !no_stdin
+        && !stdin_uris
+        && !wait
+        && depth == 0
+        && !head
+        && !raw
+        && !branches
+        && read_flag.is_none()
+        && let [uri] = uris.as_slice()
+        && is_create_target(uri, prefix)
+    {
+        read_piped_prompt()?
+    } else {
+        None
+    };
+    let write_flag = if retries > 0 {
+        Some("--retries")
+    } else if system.is_some() {
+        Some("--system")
+    } else if template.is_some() {
+        Some("--template")
+    } else if max_output_chars.is_some() {
+        Some("--max-output-chars")
+    } else if max_duration.is_some() {
+        Some("--max-duration")
+    } else if dry_run {
+        Some("--dry-run")
+    } else {
+        None
+    };
+    if let Some(flag) = write_flag
+        && data.is_empty()
+        && piped_prompt.is_none()
+    {
+        return Err(XurlError::InvalidMode(format!(
+            "{flag} only applies to write mode (-d/--data, piped stdin, or --interactive)"
+        )));
+    }
+    if data.is_empty() && piped_prompt.is_none() {
+        let head_fields = match head_fields {
+            Some(fields) => fields,
+            None if head => config().head.fields.clone(),
+            None => Vec::new(),
+        };
+        let read = ReadArgs {
+            head,
+            fast,
+            head_fields,
+            subagents: SubagentListOptions {
+                sort: sort.unwrap_or_default(),
+                statuses: status,
+            },
+            raw,
+            branches,
+            depth,
+            wait,
+            poll,
+            wait_timeout,
+            prefix,
+            provider,
+            file_provider: file_provider.as_deref().map(parse_provider).transpose()?,
+            options,
+        };
+        if let [uri] = uris.as_slice()
+            && !stdin_uris
+        {
+            return read_uri(uri, &read, &roots, output);
+        }
+        return read_batch(&uris, &read, &roots, output, jobs);
+    }
+    let [uri] = uris.as_slice() else {
+        return Err(XurlError::InvalidMode(
+            "write mode (-d/--data) takes a single URI".to_string(),
+        ));
+    };
+    if uri == "." {
+        return Err(XurlError::InvalidMode(
+            "write mode (-d/--data) requires a provider or thread URI, not `.`".to_string(),
+        ));
+    }
+    if provider.is_some() {
+        return Err(XurlError::InvalidMode(
+            "--provider is only supported with `xurl .`; put the provider in the URI".to_string(),
+        ));
+    }
+    if file_provider.is_some() || uri.starts_with("file://") {
+        return Err(XurlError::InvalidMode(
+            "write mode (-d/--data) is not supported for file:// URIs".to_string(),
+        ));
+    }
+    if uri.starts_with("skills://") {
+        return Err(XurlError::InvalidMode(
+            "write mode (-d/--data) is not supported for skills:// URIs".to_string(),
+        ));
+    }
+    if uri.starts_with("snapshot://") {
+        return Err(XurlError::InvalidMode(
+            "write mode (-d/--data) is not supported for snapshot:// URIs; continue the thread it was taken from"
+                .to_string(),
+        ));
+    }
+
+    if head {
+        return Err(XurlError::InvalidMode(
+            "head mode (-I/--head) cannot be combined with write mode (-d/--data)".to_string(),
+        ));
+    }
+    if branches {
+        return Err(XurlError::InvalidMode(
+            "--branches cannot be combined with write mode (-d/--data)".to_string(),
+        ));
+    }
+
+    let prompt = match piped_prompt {
+        Some(prompt) => apply_template(template.as_deref(), prompt, &roots)?,
+        None => build_prompt(&data, template.as_deref(), &roots)?,
+    };
+    let mut target = parse_write_target(uri, prefix, &roots)?;
+    WriteFlags {
+        retries,
+        system,
+        max_output_chars,
+        max_duration,
+        dry_run,
+    }
+    .apply(&mut target.options)?;
+    for warning in &target.warnings {
+        warn(warning);
+    }
+    let mut sink = CliWriteSink::new(output, target.action)?;
+    let result = write_thread(
+        target.provider,
+        &roots,
+        &WriteRequest {
+            prompt,
+            session_id: target.session_id,
+            child_id: target.child_id,
+            options: target.options,
+        },
+        &mut sink,
+    )?;
+    sink.finish(&result)?;
+    Ok(())
+}
+
+fn run_command(command: Commands, roots: &ProviderRoots) -> xurl_core::Result<()> {
+    match command {
+        Commands::Last {
+            provider,
+            limit,
+            render,
+            output,
+        } => {
+            let provider = provider.as_deref().map(parse_provider).transpose()?;
+            let limit = if render { 1 } else { limit };
+            let recent = recent_threads(provider, limit, roots)?;
+            for warning in &recent.warnings {
+                warn(warning);
+            }
+            if recent.items.is_empty() {
+                return Err(XurlError::InvalidMode(match provider {
+                    Some(provider) => format!("no threads found for provider={provider}"),
+                    None => "no threads found for any provider".to_string(),
+                }));
+            }
+
+            if render {
+                let uri = AgentsUri::parse(&recent.items[0].uri)?;
+                return write_read_markdown(
+                    &uri,
+                    roots,
+                    &RenderOptions::default(),
+                    output.as_deref(),
+                );
+            }
+            let output_body = recent
+                .items
+                .iter()
+                .map(|item| format!("{}\n", item.uri))
+                .collect::<String>();
+            write_output(output.as_deref(), &output_body)
+        }
+        Commands::Ps {
+            provider,
+            within,
+            json,
+            output,
+        } => {
+            let provider = provider.as_deref().map(parse_provider).transpose()?;
+            let threads = running_threads(provider, within, roots)?;
+            for warning in &threads.warnings {
+                warn(warning);
+            }
+            let output_body = if json {
+                render_running_threads_json(&threads)?
+            } else {
+                render_running_threads_markdown(&threads, &TimeZone::default())?
+            };
+            write_output(output.as_deref(), &output_body)
+        }
+        Commands::Sync {
+            vault,
+            provider,
+            limit,
+        } => {
+            let provider = provider.as_deref().map(parse_provider).transpose()?;
+            let report = sync_vault(&vault, provider, limit.unwrap_or(usize::MAX), roots)?;
+            for warning in &report.warnings {
+                warn(warning);
+            }
+            for path in report.created.iter().chain(&report.updated) {
+                println!("{}", path.display());
+            }
+            notice(format_args!(
+                "synced {}: {} created, {} updated, {} unchanged",
+                vault.display(),
+                report.created.len(),
+                report.updated.len(),
+                report.unchanged
+            ));
+            Ok(())
+        }
+        Commands::Diff {
+            left,
+            right,
+            output,
+        } => {
+            let left = expand_latest_alias(&parse_cli_uri(&left)?, roots)?;
+            let right = expand_latest_alias(&parse_cli_uri(&right)?, roots)?;
+            let markdown = render_thread_diff_markdown(&left, &right, roots)?;
+            write_output(output.as_deref(), &markdown)
+        }
+        Commands::Blame {
+            commit,
+            provider,
+            output,
+        } => {
+            let provider = provider.as_deref().map(parse_provider).transpose()?;
+            let cwd = std::env::current_dir().map_err(|source| XurlError::Io {
+                path: PathBuf::from("."),
+                source,
+            })?;
+            let blame = blame_commit(&commit, provider, &cwd, roots)?;
+            for warning in &blame.warnings {
+                warn(warning);
+            }
+            if blame.items.is_empty() {
+                return Err(XurlError::InvalidMode(format!(
+                    "no threads found for commit={}",
+                    blame.commit
+                )));
+            }
+            let output_body = blame
+                .items
+                .iter()
+                .map(|item| format!("{}\n", item.uri))
+                .collect::<String>();
+            write_output(output.as_deref(), &output_body)
+        }
+        Commands::Links { uri, json, output } => {
+            let uri = expand_latest_alias(&parse_cli_uri(&uri)?, roots)?;
+            if uri.is_collection() {
+                return Err(XurlError::InvalidMode(
+                    "links requires a thread URI: agents://<provider>/<session_id>".to_string(),
+                ));
+            }
+            let resolved = resolve_thread(&uri, roots)?;
+            let links = thread_links(&uri, &resolved)?;
+            let output_body = if json {
+                render_thread_links_json(&links)?
+            } else {
+                render_thread_links_markdown(&links)?
+            };
+            write_output(output.as_deref(), &output_body)
+        }
+        Commands::Findings {
+            uri,
+            format,
+            output,
+        } => {
+            let uri = expand_latest_alias(&parse_cli_uri(&uri)?, roots)?;
+            if uri.is_collection() {
+                return Err(XurlError::InvalidMode(
+                    "findings requires a thread URI: agents://<provider>/<session_id>".to_string(),
+                ));
+            }
+            let resolved = resolve_thread(&uri, roots)?;
+            let findings = thread_findings(&uri, &resolved)?;
+            let output_body = match format {
+                FindingsFormat::Json => render_findings_json(&findings)?,
+                FindingsFormat::Sarif => render_findings_sarif(&findings)?,
+            };
+            write_output(output.as_deref(), &output_body)
+        }
+        Commands::Snapshot { uri, output } => {
+            let uri = expand_latest_alias(&parse_cli_uri(&uri)?, roots)?;
+            if uri.is_collection() || is_subagent_drilldown(&uri) {
+                return Err(XurlError::InvalidMode(
+                    "snapshot requires a main thread URI: agents://<provider>/<session_id>"
+                        .to_string(),
+                ));
+            }
+            let resolved = resolve_thread(&uri, roots)?;
+            let snapshot = snapshot_thread(&uri, &resolved, &roots.snapshots_root)?;
+            write_output(output.as_deref(), &format!("{}\n", snapshot.as_string()))
+        }
+        Commands::Cat {
+            uri,
+            lines,
+            tail,
+            output,
+        } => {
+            let slice = match (lines, tail) {
+                (Some(range), _) => RawSlice::Lines(range),
+                (None, Some(count)) => RawSlice::Tail(count),
+                (None, None) => RawSlice::Whole,
+            };
+            if uri.starts_with("snapshot://") {
+                let snapshot = resolve_snapshot(&SnapshotUri::parse(&uri)?, &roots.snapshots_root)?;
+                return stream_output(output.as_deref(), |mut writer| {
+                    write_thread_raw(&snapshot.thread, slice, &mut writer)
+                });
+            }
+            let uri = expand_latest_alias(&parse_cli_uri(&uri)?, roots)?;
+            if uri.is_collection() {
+                return Err(XurlError::InvalidMode(
+                    "cat requires a thread URI: agents://<provider>/<session_id>".to_string(),
+                ));
+            }
+            if is_subagent_drilldown(&uri) {
+                let view = resolve_subagent_view(&uri, roots, false, 0)?;
+                return stream_output(output.as_deref(), |mut writer| {
+                    write_subagent_raw(&view, slice, &mut writer)
+                });
+            }
+            // A slice only reads its own lines, so the thread is not checked as a whole.
+            let resolved = if matches!(slice, RawSlice::Whole) {
+                resolve_thread(&uri, roots)?
+            } else {
+                locate_thread(&uri, roots)?
+            };
+            stream_output(output.as_deref(), |mut writer| {
+                write_thread_raw(&resolved, slice, &mut writer)
+            })
+        }
+        Commands::Resolve {
+            uri,
+            prefix,
+            output,
+        } => {
+            let uri = parse_thread_uri(&uri, prefix, roots)?;
+            if uri.is_collection() {
+                return Err(XurlError::InvalidMode(
+                    "resolve requires a thread URI: agents://<provider>/<session_id>".to_string(),
+                ));
+            }
+            if is_subagent_drilldown(&uri) {
+                return Err(XurlError::InvalidMode(
+                    "resolve requires a main thread URI: agents://<provider>/<session_id>"
+                        .to_string(),
+                ));
+            }
+            let resolved = resolve_thread(&uri, roots)?;
+            write_output(
+                output.as_deref(),
+                &render_resolution_markdown(&uri, &resolved)?,
+            )
+        }
+        Commands::Lint { uri, output } => {
+            let uri = expand_latest_alias(&parse_cli_uri(&uri)?, roots)?;
+            if uri.is_collection() {
+                return Err(XurlError::InvalidMode(
+                    "lint requires a thread URI: agents://<provider>/<session_id>".to_string(),
+                ));
+            }
+            let resolved = resolve_thread(&uri, roots)?;
+            let report = lint_thread(&uri, &resolved)?;
+            write_output(output.as_deref(), &render_lint_markdown(&report)?)
+        }
+        Commands::Doctor { output } => {
+            write_output(output.as_deref(), &render_doctor_markdown(&doctor(roots))?)
+        }
+        Commands::Extract { uri, out } => {
+            let uri = expand_latest_alias(&parse_cli_uri(&uri)?, roots)?;
+            if uri.is_collection() {
+                return Err(XurlError::InvalidMode(
+                    "extract requires a thread URI: agents://<provider>/<session_id>".to_string(),
+                ));
+            }
+            let resolved = resolve_thread(&uri, roots)?;
+            let export = export_thread(&uri, &resolved, &out)?;
+            println!("{}", export.markdown_path.display());
+            for path in &export.attachment_paths {
+                println!("{}", path.display());
+            }
+            Ok(())
+        }
+        Commands::Export {
+            uri,
+            format,
+            output,
+            scrub,
+            scrub_map,
+            jobs,
+        } => {
+            if jobs == 0 {
+                return Err(XurlError::InvalidMode(
+                    "--jobs must be at least 1".to_string(),
+                ));
+            }
+            let uri = expand_latest_alias(&parse_cli_uri(&uri)?, roots)?;
+            let mut scrubber = Scrubber::new(&scrub);
+            if matches!(format, ExportFormat::Csv | ExportFormat::Parquet) {
+                export_dataset(&uri, format, output.as_deref(), &mut scrubber, jobs, roots)?;
+            } else {
+                if uri.is_collection() {
+                    return Err(XurlError::InvalidMode(
+                        "export requires a thread URI: agents://<provider>/<session_id>"
+                            .to_string(),
+                    ));
+                }
+                let resolved = resolve_thread(&uri, roots)?;
+                let scrubber = (!scrubber.is_empty()).then_some(&mut scrubber);
+                let parts = render_gh_comment(&uri, &resolved, GH_COMMENT_MAX_CHARS, scrubber)?;
+                if let Some(output) = output {
+                    for (idx, part) in parts.iter().enumerate() {
+                        let path = if idx == 0 {
+                            output.clone()
+                        } else {
+                            numbered_path(&output, idx + 1)
+                        };
+                        write_output(Some(&path), part)?;
+                        println!("{}", path.display());
+                    }
+                } else {
+                    print!("{}", parts.join("\n"));
+                }
+            }
+            if let Some(path) = scrub_map {
+                write_output(Some(&path), &scrubber.mapping_json()?)?;
+            }
+            Ok(())
+        }
+        Commands::ExportDb { out, provider } => {
+            let provider = provider.as_deref().map(parse_provider).transpose()?;
+            let report = export_threads_db(&out, provider, roots)?;
+            for warning in &report.warnings {
+                warn(warning);
+            }
+            notice(format_args!(
+                "exported {} threads, {} messages, {} tool calls",
+                report.threads, report.messages, report.tool_calls
+            ));
+            println!("{}", out.display());
+            Ok(())
+        }
+        Commands::Replay { uri, to, output } => {
+            let source = expand_latest_alias(&parse_cli_uri(&uri)?, roots)?;
+            if source.is_collection() {
+                return Err(XurlError::InvalidMode(
+                    "replay requires a thread URI to read prompts from: agents://<provider>/<session_id>"
+                        .to_string(),
+                ));
+            }
+            let resolved = resolve_thread(&source, roots)?;
+            let prompts = thread_user_prompts(&source, &resolved)?;
+            if prompts.is_empty() {
+                return Err(XurlError::InvalidMode(format!(
+                    "no user prompts found to replay in {}",
+                    source.as_agents_string()
+                )));
+            }
+
+            let target = parse_write_target(&to, false, roots)?;
+            if matches!(target.action, WriteAction::Append) {
+                return Err(XurlError::InvalidMode(
+                    "replay --to requires a provider or role URI that starts a new session: agents://<provider>"
+                        .to_string(),
+                ));
+            }
+            for warning in &target.warnings {
+                warn(warning);
+            }
+
+            let mut sink = CliWriteSink::new(output.as_deref(), WriteAction::Create)?;
+            let mut session_id = None;
+            for (idx, prompt) in prompts.into_iter().enumerate() {
+                if idx > 0 {
+                    sink.next_turn()?;
+                }
+                let result = write_thread(
+                    target.provider,
+                    roots,
+                    &WriteRequest {
+                        prompt,
+                        session_id: session_id.clone(),
+                        child_id: None,
+                        options: target.options.clone(),
+                    },
+                    &mut sink,
+                )?;
+                sink.finish(&result)?;
+                session_id = Some(result.session_id);
+            }
+            Ok(())
+        }
+        Commands::Summarize {
+            uri,
+            via,
+            template,
+            max_bytes,
+            output,
+        } => {
+            let source = expand_latest_alias(&parse_cli_uri(&uri)?, roots)?;
+            if source.is_collection() {
+                return Err(XurlError::InvalidMode(
+                    "summarize requires a thread URI: agents://<provider>/<session_id>".to_string(),
+                ));
+            }
+            let template = match template.as_deref() {
+                Some(template) => load_data(template)?,
+                None => DEFAULT_SUMMARY_TEMPLATE.to_string(),
+            };
+            let options = RenderOptions {
+                max_bytes: Some(max_bytes.unwrap_or(SUMMARY_MAX_THREAD_BYTES)),
+                elide_bytes: Some(config().render.elide_bytes),
+                ..RenderOptions::default()
+            };
+            let prompt = summary_prompt(&source, roots, &template, &options)?;
+
+            let via = via.unwrap_or_else(|| format!("agents://{}", source.provider));
+            let target = parse_write_target(&via, false, roots)?;
+            if matches!(target.action, WriteAction::Append) {
+                return Err(XurlError::InvalidMode(
+                    "summarize --via requires a provider or role URI that starts a new session: agents://<provider>"
+                        .to_string(),
+                ));
+            }
+            for warning in &target.warnings {
+                warn(warning);
+            }
+
+            let mut sink = CliWriteSink::new(output.as_deref(), WriteAction::Create)?;
+            let result = write_thread(
+                target.provider,
+                roots,
+                &WriteRequest {
+                    prompt,
+                    session_id: None,
+                    child_id: None,
+                    options: target.options,
+                },
+                &mut sink,
+            )?;
+            sink.finish(&result)
+        }
+        Commands::Multicast { data, to, output } => {
+            let prompt = build_prompt(&data, None, roots)?;
+            let mut targets = Vec::with_capacity(to.len());
+            for uri in to
+                .iter()
+                .map(|uri| uri.trim())
+                .filter(|uri| !uri.is_empty())
+            {
+                let target = parse_write_target(uri, false, roots)?;
+                let label = uri.strip_prefix("agents://").unwrap_or(uri).to_string();
+                for warning in &target.warnings {
+                    warn(format_args!("[{label}] {warning}"));
+                }
+                targets.push((label, target));
+            }
+
+            let out = Mutex::new(CliWriteSink::new(output.as_deref(), WriteAction::Create)?);
+            let mut sinks = targets
+                .iter()
+                .map(|(label, _)| LabeledWriteSink::new(label, &out))
+                .collect::<Vec<_>>();
+            let writes = targets
+                .iter()
+                .zip(sinks.iter_mut())
+                .map(|((_, target), sink)| MulticastWrite {
+                    provider: target.provider,
+                    request: WriteRequest {
+                        prompt: prompt.clone(),
+                        session_id: target.session_id.clone(),
+                        child_id: target.child_id.clone(),
+                        options: target.options.clone(),
+                    },
+                    sink,
+                })
+                .collect::<Vec<_>>();
+            let results = multicast_write(writes, roots);
+
+            let mut failed = 0;
+            for (((label, target), sink), result) in targets.iter().zip(sinks).zip(results) {
+                match result {
+                    Ok(result) => {
+                        sink.finish(&result)?;
+                        for warning in &result.warnings {
+                            warn(format_args!("[{label}] {warning}"));
+                        }
+                        report_write_summary(&result)?;
+                        let verb = match target.action {
+                            WriteAction::Create => "created",
+                            WriteAction::Append => "updated",
+                        };
+                        report_thread_uri(
+                            verb,
+                            &format!("agents://{}/{}", result.provider, result.session_id),
+                        );
+                    }
+                    Err(err) => {
+                        let mut sink = sink;
+                        sink.flush()?;
+                        eprintln!("error: [{label}] {}", user_facing_error(&err));
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(XurlError::InvalidMode(format!(
+                    "{failed} of {} multicast writes failed",
+                    targets.len()
+                )));
+            }
+            Ok(())
+        }
+        Commands::Compare { uris, output } => {
+            let mut threads = Vec::with_capacity(uris.len());
+            for uri in &uris {
+                let uri = expand_latest_alias(&parse_cli_uri(uri)?, roots)?;
+                if uri.is_collection() {
+                    return Err(XurlError::InvalidMode(
+                        "compare requires thread URIs: agents://<provider>/<session_id>"
+                            .to_string(),
+                    ));
+                }
+                let resolved = resolve_thread(&uri, roots)?;
+                threads.push(compare_thread(&uri, &resolved)?);
+            }
+            write_output(
+                output.as_deref(),
+                &render_thread_comparison_markdown(&threads)?,
+            )
+        }
+        Commands::Fsck {
+            provider,
+            provider_flag,
+            fix,
+            output,
+        } => {
+            let provider = parse_provider(&provider.or(provider_flag).unwrap_or_default())?;
+            let report = fsck_provider(provider, roots, fix)?;
+            write_output(output.as_deref(), &render_fsck_markdown(&report)?)
+        }
+        Commands::Alias { command } => run_alias(command),
+        Commands::Cache {
+            command:
+                CacheCommand::Gc {
+                    max_age,
+                    max_size,
+                    dry_run,
+                },
+        } => {
+            let report = gc_caches(
+                roots,
+                &CacheGcOptions {
+                    max_age: Some(max_age),
+                    max_size,
+                    dry_run,
+                },
+            );
+            for warning in &report.warnings {
+                warn(warning);
+            }
+            for path in &report.removed {
+                println!("{}", path.display());
+            }
+            notice(format_args!(
+                "{} {} cache entries ({} bytes), kept {} ({} bytes)",
+                if dry_run { "would delete" } else { "deleted" },
+                report.removed.len(),
+                report.freed_bytes,
+                report.kept,
+                report.kept_bytes
+            ));
+            Ok(())
+        }
+        Commands::Watch {
+            provider,
+            count,
+            idle,
+            webhook,
+        } => {
+            let provider = provider.as_deref().map(parse_provider).transpose()?;
+            if count == Some(0) {
+                return Err(XurlError::InvalidMode(
+                    "--count must be at least 1".to_string(),
+                ));
+            }
+            if let Some(url) = &webhook
+                && !(url.starts_with("http://") || url.starts_with("https://"))
+            {
+                return Err(XurlError::InvalidMode(format!(
+                    "--webhook requires an http:// or https:// URL, got `{url}`"
+                )));
+            }
+            let options = WatchOptions {
+                idle_after: idle.or(webhook.as_ref().map(|_| DEFAULT_WEBHOOK_IDLE)),
+            };
+            watch_threads(
+                provider,
+                roots,
+                &options,
+                &mut CliWatchSink {
+                    remaining: count,
+                    webhook,
+                },
+            )
+        }
+        Commands::Completions { shell } => {
+            write_output(None, &completions::render(shell, Cli::command()))
+        }
+        Commands::CompleteSessions { provider } => {
+            let provider = parse_provider(&provider)?;
+            let ids = list_session_ids(provider, roots)?;
+            let output_body = ids.iter().map(|id| format!("{id}\n")).collect::<String>();
+            write_output(None, &output_body)
+        }
+    }
+}
+
+/// Read-mode flags shared by every URI of a `xurl` invocation.
+struct ReadArgs {
+    head: bool,
+    fast: bool,
+    /// Frontmatter keys `-I` keeps; empty keeps all.
+    head_fields: Vec<String>,
+    subagents: SubagentListOptions,
+    raw: bool,
+    branches: bool,
+    depth: usize,
+    wait: bool,
+    poll: Duration,
+    wait_timeout: Duration,
+    prefix: bool,
+    provider: Option<String>,
+    /// The provider `--as` reads `file://` URIs as.
+    file_provider: Option<ProviderKind>,
+    options: RenderOptions,
+}
+
+/// Reads one URI in the mode `read` selects and writes it to `output`, or stdout.
+fn read_uri(
+    uri: &str,
+    read: &ReadArgs,
+    roots: &ProviderRoots,
+    output: Option<&Path>,
+) -> xurl_core::Result<()> {
+    let options = &read.options;
+    if uri == "." {
+        let uri = latest_workspace_thread(read.provider.as_deref(), roots)?;
+        if read.wait {
+            wait_for_subagent(&uri, roots, read.poll, read.wait_timeout)?;
+        }
+        if read.raw {
+            return write_raw(&uri, roots, read.depth, output);
+        }
+        if read.head {
+            return write_output(output, &read_thread_head(&uri, read, roots)?);
+        }
+        return write_read_markdown(&uri, roots, options, output);
+    }
+    if read.provider.is_some() {
+        return Err(XurlError::InvalidMode(
+            "--provider is only supported with `xurl .`; put the provider in the URI".to_string(),
+        ));
+    }
+    if uri.starts_with("snapshot://") {
+        return read_snapshot(uri, read, roots, output);
+    }
+    if uri.starts_with("file://") {
+        return read_file(uri, read, roots.strict, output);
+    }
+    if read.file_provider.is_some() {
+        return Err(XurlError::InvalidMode(
+            "--as only applies to file:// URIs".to_string(),
+        ));
+    }
+    if uri.starts_with("skills://") {
+        if read.raw {
+            return Err(XurlError::InvalidMode(
+                "--raw is not supported for skills:// URIs".to_string(),
+            ));
+        }
+        let skills_uri = SkillsUri::parse(uri)?;
+        let resolved = resolve_skill(&skills_uri, roots)?;
+        let output_body = if read.head {
+            select_frontmatter_fields(&render_skill_head_markdown(&resolved)?, &read.head_fields)
+        } else {
+            render_skill_markdown(&resolved)
+        };
+        return write_output(output, &output_body);
+    }
+
+    let query = match parse_collection_query_uri(uri)? {
+        Some(query) => Some(query),
+        None if !names_session_prefix(uri, read.prefix) => parse_role_query_uri(uri)?,
+        None => None,
+    };
+    if let Some(query) = query {
+        let result = query_threads(&query, roots)?;
+        if read.raw {
+            return write_output(output, &thread_query_to_raw_json(&result)?);
+        }
+        let output_body = if read.head {
+            select_frontmatter_fields(
+                &render_thread_query_head_markdown(&result, &options.timezone)?,
+                &read.head_fields,
+            )
+        } else {
+            render_thread_query_markdown(&result, &options.timezone)?
+        };
+        return write_output(output, &output_body);
+    }
+
+    let uri = parse_thread_uri(uri, read.prefix, roots)?;
+    if uri.is_collection() {
+        return Err(XurlError::InvalidMode(
+            "read mode requires a thread URI: agents://<provider>/<session_id>".to_string(),
+        ));
+    }
+    if read.wait {
+        wait_for_subagent(&uri, roots, read.poll, read.wait_timeout)?;
+    }
+    if read.raw {
+        return write_raw(&uri, roots, read.depth, output);
+    }
+    if read.head {
+        return write_output(output, &read_thread_head(&uri, read, roots)?);
+    }
+    if read.branches {
+        let resolved = resolve_thread(&uri, roots)?;
+        let body = render_thread_branches_markdown(&uri, &resolved)?;
+        let head = enrich_head(render_thread_head_markdown(
+            &uri,
+            roots,
+            &options.timezone,
+            &read.subagents,
+        )?)?;
+        return write_output(output, &format!("{head}\n{body}"));
+    }
+
+    write_read_markdown(&uri, roots, options, output)
+}
+
+/// Reads a `snapshot://` URI: the copied thread file rendered like the thread it was taken
+/// from, under frontmatter naming the snapshot.
+fn read_snapshot(
+    uri: &str,
+    read: &ReadArgs,
+    roots: &ProviderRoots,
+    output: Option<&Path>,
+) -> xurl_core::Result<()> {
+    reject_live_read_flags(read, "snapshot:// URIs read the copied thread file only")?;
+    let snapshot = resolve_snapshot(&SnapshotUri::parse(uri)?, &roots.snapshots_root)?;
+    let head = render_snapshot_head_markdown(&snapshot)?;
+    write_detached_thread(&snapshot.thread_uri, &snapshot.thread, &head, read, output)
+}
+
+/// Reads a `file://` URI with the provider `--as` names, without looking in any provider root.
+fn read_file(
+    uri: &str,
+    read: &ReadArgs,
+    strict: bool,
+    output: Option<&Path>,
+) -> xurl_core::Result<()> {
+    let Some(provider) = read.file_provider else {
+        return Err(XurlError::InvalidMode(
+            "file:// URIs need the provider that wrote the file; add --as <PROVIDER>, like --as codex"
+                .to_string(),
+        ));
+    };
+    reject_live_read_flags(read, "file:// URIs read the named file only")?;
+    let uri = FileUri::parse(uri)?;
+    let (thread_uri, resolved) = resolve_file_thread(&uri, provider, strict)?;
+    let head = render_file_head_markdown(&uri, &resolved)?;
+    write_detached_thread(&thread_uri, &resolved, &head, read, output)
+}
+
+/// Flags that need the provider's other data, such as sibling sessions or subagent files.
+fn reject_live_read_flags(read: &ReadArgs, reason: &str) -> xurl_core::Result<()> {
+    if read.branches || read.wait || read.depth > 0 || read.options.with_history {
+        return Err(XurlError::InvalidMode(format!(
+            "{reason}; drop --branches, --wait, --depth, and --with-history"
+        )));
+    }
+    Ok(())
+}
+
+/// Renders a thread file found outside the provider roots under `head`, or prints it as stored
+/// with `--raw`. Their heads do not read the thread, so corrupt lines are counted while the body
+/// streams and reported as a warning after it.
+fn write_detached_thread(
+    uri: &AgentsUri,
+    resolved: &ResolvedThread,
+    head: &str,
+    read: &ReadArgs,
+    output: Option<&Path>,
+) -> xurl_core::Result<()> {
+    if read.raw {
+        return stream_output(output, |mut writer| {
+            write_thread_raw(resolved, RawSlice::Whole, &mut writer)
+        });
+    }
+    if read.head {
+        return write_output(output, &select_frontmatter_fields(head, &read.head_fields));
+    }
+    stream_output(output, |mut writer| {
+        writer
+            .write_all(format!("{head}\n").as_bytes())
+            .map_err(|source| output_io_error(output, source))?;
+        let corrupt_lines = write_thread_markdown(uri, resolved, &read.options, &mut writer)?;
+        if corrupt_lines > 0 {
+            warn(corrupt_lines_warning(corrupt_lines));
+        }
+        Ok(())
+    })
+}
+
+/// The `-I` frontmatter of a thread, limited to `--head-fields`; `--fast` reads only what the
+/// thread records about itself.
+fn read_thread_head(
+    uri: &AgentsUri,
+    read: &ReadArgs,
+    roots: &ProviderRoots,
+) -> xurl_core::Result<String> {
+    let zone = &read.options.timezone;
+    let head = enrich_head(if read.fast {
+        render_thread_metadata_head_markdown(uri, roots, zone)?
+    } else {
+        render_thread_head_markdown(uri, roots, zone, &read.subagents)?
+    })?;
+    Ok(select_frontmatter_fields(&head, &read.head_fields))
+}
+
+/// Reads several URIs: into one file each in the directory `output`, or one after another on
+/// stdout, each after a `==> URI <==` line. A URI that fails is reported as a warning and the
+/// rest are still read; the run only fails when every URI does.
+///
+/// With more than one job, URIs are read on `jobs` threads at once. Their stdout renders are
+/// spooled to files first, so bodies still stream instead of being held in memory, and are
+/// printed in the order the URIs were given.
+fn read_batch(
+    uris: &[String],
+    read: &ReadArgs,
+    roots: &ProviderRoots,
+    output: Option<&Path>,
+    jobs: usize,
+) -> xurl_core::Result<()> {
+    // A fresh directory only this run can write to, removed when it is dropped.
+    let spool = (output.is_none() && jobs > 1)
+        .then(|| tempfile::Builder::new().prefix("xurl-batch-").tempdir())
+        .transpose()
+        .map_err(|source| XurlError::Io {
+            path: std::env::temp_dir(),
+            source,
+        })?;
+    let files = match (output, &spool) {
+        (Some(dir), _) => {
+            fs::create_dir_all(dir).map_err(|source| XurlError::Io {
+                path: dir.to_path_buf(),
+                source,
+            })?;
+            batch_file_paths(dir, uris, read.raw)
+        }
+        (None, Some(spool)) => (0..uris.len())
+            .map(|idx| spool.path().join(idx.to_string()))
+            .collect(),
+        (None, None) => Vec::new(),
+    };
+
+    let mut failed = 0;
+    let mut report = |idx: usize, result: xurl_core::Result<()>| match result {
+        Ok(()) => {
+            if let Some(path) = output.and(files.get(idx)) {
+                notice(format_args!("wrote: {}", path.display()));
+            }
+        }
+        Err(err) => {
+            failed += 1;
+            warn(format_args!("{}: {}", uris[idx], user_facing_error(&err)));
+        }
+    };
+    let header = |idx: usize| {
+        if idx > 0 {
+            println!();
+        }
+        println!("==> {} <==", uris[idx]);
+    };
+    if jobs > 1 {
+        run_ordered(
+            uris.len(),
+            jobs,
+            |idx| read_uri(&uris[idx], read, roots, Some(&files[idx])),
+            |idx, result| {
+                let result = match &spool {
+                    Some(_) => {
+                        header(idx);
+                        result.and_then(|()| copy_to_stdout(&files[idx]))
+                    }
+                    None => result,
+                };
+                report(idx, result);
+            },
+        );
+    } else {
+        for (idx, uri) in uris.iter().enumerate() {
+            if output.is_none() {
+                header(idx);
+            }
+            report(
+                idx,
+                read_uri(uri, read, roots, files.get(idx).map(PathBuf::as_path)),
+            );
+        }
+    }
+
+    if failed == uris.len() {
+        return Err(XurlError::InvalidMode(format!(
+            "all {failed} URIs failed to read"
+        )));
+    }
+    if failed > 0 {
+        warn(format_args!(
+            "{failed} of {} URIs failed to read",
+            uris.len()
+        ));
+    }
+    Ok(())
+}
+
+fn copy_to_stdout(path: &Path) -> xurl_core::Result<()> {
+    let mut file = fs::File::open(path).map_err(|source| XurlError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut stdout = io::stdout().lock();
+    io::copy(&mut file, &mut stdout)
+        .map(|_| ())
+        .map_err(|source| output_io_error(None, source))
+}
+
+/// One file per URI in `dir`, with `.2`, `.3`, ... added when URIs map to the same name.
+fn batch_file_paths(dir: &Path, uris: &[String], raw: bool) -> Vec<PathBuf> {
+    let mut written = HashSet::new();
+    uris.iter()
+        .map(|uri| {
+            let path = dir.join(batch_file_name(uri, raw));
+            let mut numbered = path.clone();
+            let mut n = 1;
+            while !written.insert(numbered.clone()) {
+                n += 1;
+                numbered = numbered_path(&path, n);
+            }
+            numbered
+        })
+        .collect()
+}
+
+/// File name for a URI in a batch output directory: the URI without its scheme, with every
+/// character other than letters, digits, `.`, `_`, and `-` replaced by `-`.
+fn batch_file_name(uri: &str, raw: bool) -> String {
+    let target = uri.split_once("://").map_or(uri, |(_, target)| target);
+    let mut name = target
+        .trim_matches('/')
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || matches!(ch, '.' | '_' | '-') {
+                ch
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    if name.is_empty() || name == "." {
+        name = "latest".to_string();
+    }
+    name.push_str(if raw { ".json" } else { ".md" });
+    name
+}
+
+fn latest_workspace_thread(
+    provider: Option<&str>,
+    roots: &ProviderRoots,
+) -> xurl_core::Result<AgentsUri> {
+    let provider = provider.map(parse_provider).transpose()?;
+    let cwd = std::env::current_dir().map_err(|source| XurlError::Io {
+        path: PathBuf::from("."),
+        source,
+    })?;
+    let recent = workspace_threads(provider, &cwd, 1, roots)?;
+    for warning in &recent.warnings {
+        warn(warning);
+    }
+    let Some(item) = recent.items.first() else {
+        return Err(XurlError::InvalidMode(match provider {
+            Some(provider) => format!(
+                "no threads found for provider={provider} in {}",
+                cwd.display()
+            ),
+            None => format!("no threads found in {}", cwd.display()),
+        }));
+    };
+    AgentsUri::parse(&item.uri)
+}
+
+fn write_read_markdown(
+    uri: &AgentsUri,
+    roots: &ProviderRoots,
+    options: &RenderOptions,
+    output: Option<&Path>,
+) -> xurl_core::Result<()> {
+    let is_subagent_drilldown = is_subagent_drilldown(uri);
+    if is_subagent_drilldown
+        && (options.only.is_some()
+            || options.grep.is_some()
+            || options.with_history
+            || options.show_timestamps)
+    {
+        let flag = if options.with_history {
+            "--with-history"
+        } else if options.grep.is_some() {
+            "--grep"
+        } else if options.show_timestamps {
+            "--timestamps"
+        } else {
+            "--only"
+        };
+        return Err(XurlError::InvalidMode(format!(
+            "{flag} requires a main thread URI: agents://<provider>/<session_id>"
+        )));
+    }
+    if !is_subagent_drilldown && options.descendant_depth > 0 {
+        return Err(depth_requires_subagent());
+    }
+    let head = enrich_head(render_thread_head_markdown(
+        uri,
+        roots,
+        &options.timezone,
+        &SubagentListOptions::default(),
+    )?)?;
+    if is_subagent_drilldown {
+        let view = resolve_subagent_view(uri, roots, false, options.descendant_depth)?;
+        let body = render_subagent_view_markdown(&view, &options.timezone);
+        return write_output(output, &format!("{head}\n{body}"));
+    }
+    if options.with_history {
+        let body = render_thread_history_markdown(uri, roots, options)?;
+        return write_output(output, &format!("{head}\n{body}"));
+    }
+
+    // Stream the body so large rollouts are never held in memory as a whole.
+    let resolved = resolve_thread(uri, roots)?;
+    stream_output(output, |mut writer| {
+        writer
+            .write_all(format!("{head}\n").as_bytes())
+            .map_err(|source| output_io_error(output, source))?;
+        // The head already counted the corrupt lines.
+        write_thread_markdown(uri, &resolved, options, &mut writer).map(drop)
+    })
+}
+
+/// Re-runs subagent discovery every `poll` until the drill-down's status is final, failing
+/// with [`XurlError::SubagentWaitTimeout`] once `timeout` has passed.
+fn wait_for_subagent(
+    uri: &AgentsUri,
+    roots: &ProviderRoots,
+    poll: Duration,
+    timeout: Duration,
+) -> xurl_core::Result<()> {
+    if !is_subagent_drilldown(uri) {
         return Err(XurlError::InvalidMode(
-            "write mode (-d/--data) is not supported for skills:// URIs".to_string(),
+            "--wait requires a subagent URI: agents://<provider>/<main_session_id>/<agent_id>"
+                .to_string(),
         ));
     }
-
-    if data.is_empty() {
-        if uri.starts_with("skills://") {
-            let skills_uri = SkillsUri::parse(&uri)?;
-            let resolved = resolve_skill(&skills_uri, &roots)?;
-            let output_body = if head {
-                render_skill_head_markdown(&resolved)
-            } else {
-                render_skill_markdown(&resolved)
-            };
-            return write_output(output, &output_body);
+    let deadline = std::time::Instant::now() + timeout;
+    while !subagent_view_settled(&resolve_subagent_view(uri, roots, false, 0)?) {
+        let now = std::time::Instant::now();
+        if now >= deadline {
+            return Err(XurlError::SubagentWaitTimeout {
+                uri: uri.as_agents_string(),
+                timeout: format_duration(timeout),
+            });
         }
+        std::thread::sleep(poll.min(deadline - now));
+    }
+    Ok(())
+}
 
-        if let Some(query) = parse_collection_query_uri(&uri)? {
-            let result = query_threads(&query, &roots)?;
-            let output_body = if head {
-                render_thread_query_head_markdown(&result)
-            } else {
-                render_thread_query_markdown(&result)
-            };
-            return write_output(output, &output_body);
-        }
+fn depth_requires_subagent() -> XurlError {
+    XurlError::InvalidMode(
+        "--depth requires a subagent URI: agents://<provider>/<main_session_id>/<agent_id>"
+            .to_string(),
+    )
+}
 
-        if let Some(query) = parse_role_query_uri(&uri)? {
-            let result = query_threads(&query, &roots)?;
-            let output_body = if head {
-                render_thread_query_head_markdown(&result)
-            } else {
-                render_thread_query_markdown(&result)
-            };
-            return write_output(output, &output_body);
-        }
+fn is_subagent_drilldown(uri: &AgentsUri) -> bool {
+    match uri.provider {
+        ProviderKind::Codex
+        | ProviderKind::Claude
+        | ProviderKind::Gemini
+        | ProviderKind::Amp
+        | ProviderKind::Opencode => uri.agent_id.is_some(),
+        ProviderKind::Pi => uri.agent_id.as_deref().is_some_and(is_uuid_session_id),
+    }
+}
 
-        let uri = AgentsUri::parse(&uri)?;
-        if uri.is_collection() {
-            return Err(XurlError::InvalidMode(
-                "read mode requires a thread URI: agents://<provider>/<session_id>".to_string(),
-            ));
-        }
-        if head {
-            let head = render_thread_head_markdown(&uri, &roots)?;
-            return write_output(output, &head);
-        }
+/// Prints the thread file as stored, or the subagent view as JSON for drill-down URIs.
+fn write_raw(
+    uri: &AgentsUri,
+    roots: &ProviderRoots,
+    depth: usize,
+    output: Option<&Path>,
+) -> xurl_core::Result<()> {
+    if is_subagent_drilldown(uri) {
+        let view = resolve_subagent_view(uri, roots, false, depth)?;
+        return write_output(output, &subagent_view_to_raw_json(&view)?);
+    }
+    if depth > 0 {
+        return Err(depth_requires_subagent());
+    }
 
-        let is_subagent_drilldown = match uri.provider {
-            xurl_core::ProviderKind::Codex
-            | xurl_core::ProviderKind::Claude
-            | xurl_core::ProviderKind::Gemini
-            | xurl_core::ProviderKind::Amp
-            | xurl_core::ProviderKind::Opencode => uri.agent_id.is_some(),
-            xurl_core::ProviderKind::Pi => uri.agent_id.as_deref().is_some_and(is_uuid_session_id),
-        };
-        let markdown = if is_subagent_drilldown {
-            let head = render_thread_head_markdown(&uri, &roots)?;
-            let view = resolve_subagent_view(&uri, &roots, false)?;
-            let body = render_subagent_view_markdown(&view);
-            format!("{head}\n{body}")
-        } else {
-            let head = render_thread_head_markdown(&uri, &roots)?;
-            let resolved = resolve_thread(&uri, &roots)?;
-            let body = render_thread_markdown(&uri, &resolved)?;
-            format!("{head}\n{body}")
-        };
+    let resolved = resolve_thread(uri, roots)?;
+    stream_output(output, |mut writer| {
+        write_thread_raw(&resolved, RawSlice::Whole, &mut writer)
+    })
+}
+
+/// Runs `write` against a buffered writer for `output`, or stdout when no path is given.
+fn stream_output(
+    output: Option<&Path>,
+    write: impl FnOnce(&mut dyn Write) -> xurl_core::Result<()>,
+) -> xurl_core::Result<()> {
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(io::BufWriter::new(
+            fs::File::create(path).map_err(|source| output_io_error(output, source))?,
+        )),
+        None => Box::new(io::BufWriter::new(io::stdout().lock())),
+    };
+    write(&mut writer)?;
+    writer
+        .flush()
+        .map_err(|source| output_io_error(output, source))
+}
 
-        return write_output(output, &markdown);
+fn output_io_error(output: Option<&Path>, source: io::Error) -> XurlError {
+    XurlError::Io {
+        path: output.unwrap_or(Path::new("<stdout>")).to_path_buf(),
+        source,
     }
+}
 
-    if head {
+fn enrich_head(head: String) -> xurl_core::Result<String> {
+    let enrichers = config().frontmatter_enrichers();
+    let (head, warnings) = enrich_frontmatter(&head, &enrichers)?;
+    for warning in &warnings {
+        warn(warning);
+    }
+    Ok(head)
+}
+
+fn export_dataset(
+    uri: &AgentsUri,
+    format: ExportFormat,
+    output: Option<&Path>,
+    scrubber: &mut Scrubber,
+    jobs: usize,
+    roots: &ProviderRoots,
+) -> xurl_core::Result<()> {
+    if format == ExportFormat::Parquet && output.is_none() {
         return Err(XurlError::InvalidMode(
-            "head mode (-I/--head) cannot be combined with write mode (-d/--data)".to_string(),
+            "--format parquet requires -o <PATH>".to_string(),
         ));
     }
-
-    let prompt = build_prompt(&data)?;
-    let target = parse_write_target(&uri)?;
-    for warning in &target.warnings {
-        eprintln!("warning: {warning}");
+    let (mut rows, warnings) = dataset_rows(uri, jobs, roots)?;
+    if !scrubber.is_empty() {
+        for row in &mut rows {
+            row.text = scrubber.scrub(&row.text);
+        }
     }
-    let mut sink = CliWriteSink::new(output, target.action)?;
-    let result = write_thread(
-        target.provider,
-        &roots,
-        &WriteRequest {
-            prompt,
-            session_id: target.session_id,
-            options: target.options,
-        },
-        &mut sink,
-    )?;
-    sink.finish(&result)?;
-    Ok(())
+    for warning in &warnings {
+        warn(warning);
+    }
+    if let Some(path) = output.filter(|_| format == ExportFormat::Parquet) {
+        write_dataset_parquet(&rows, path)?;
+        println!("{}", path.display());
+        return Ok(());
+    }
+    let mut csv = Vec::new();
+    write_dataset_csv(&rows, &mut csv)
+        .map_err(|err| XurlError::Serialization(format!("failed writing csv: {err}")))?;
+    write_output(output, &String::from_utf8_lossy(&csv))
 }
 
 fn write_output(path: Option<&Path>, content: &str) -> xurl_core::Result<()> {
@@ -167,6 +2295,19 @@ fn write_output(path: Option<&Path>, content: &str) -> xurl_core::Result<()> {
     Ok(())
 }
 
+/// `dir/comment.md` with `n` = 2 becomes `dir/comment.2.md`.
+fn numbered_path(path: &Path, n: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}.{n}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.{n}"),
+    };
+    path.with_file_name(name)
+}
+
 #[derive(Debug, Clone, Copy)]
 enum WriteAction {
     Create,
@@ -177,36 +2318,127 @@ enum WriteAction {
 struct WriteTarget {
     provider: ProviderKind,
     session_id: Option<String>,
+    child_id: Option<String>,
     action: WriteAction,
     options: WriteOptions,
     warnings: Vec<String>,
 }
 
-fn parse_write_target(input: &str) -> xurl_core::Result<WriteTarget> {
-    if let Some(role_uri) = parse_role_uri(input)? {
-        let (options, warnings) = build_write_options(role_uri.query, Some(role_uri.role));
+fn run_alias(command: AliasCommand) -> xurl_core::Result<()> {
+    let path = XurlConfig::path()?;
+    match command {
+        AliasCommand::Set { name, uri } => {
+            let is_subcommand = Cli::command()
+                .get_subcommands()
+                .any(|subcommand| subcommand.get_name() == name);
+            if !is_alias_name(&name) || is_subcommand {
+                return Err(XurlError::InvalidMode(format!(
+                    "invalid alias name `{name}`; use letters, digits, `-`, and `_`, and avoid provider and subcommand names"
+                )));
+            }
+            // Aliases of aliases are not followed, so the target must be a plain agents URI.
+            let is_agents_uri = !is_alias_name(&uri)
+                && !uri.starts_with("alias/")
+                && !uri.starts_with("agents://alias/")
+                && (AgentsUri::parse(&uri).is_ok()
+                    || parse_role_uri(&uri).is_ok_and(|role| role.is_some()));
+            if !is_agents_uri {
+                return Err(XurlError::InvalidMode(format!(
+                    "alias `{name}` must point at an agents URI like agents://codex/<session_id>, not `{uri}`"
+                )));
+            }
+            XurlConfig::set_alias(&path, &name, &uri)
+        }
+        AliasCommand::Remove { name } => {
+            if XurlConfig::remove_alias(&path, &name)? {
+                Ok(())
+            } else {
+                Err(XurlError::InvalidMode(format!(
+                    "alias `{name}` is not set in {}",
+                    path.display()
+                )))
+            }
+        }
+        AliasCommand::List => {
+            let output_body = config()
+                .aliases
+                .iter()
+                .map(|(name, uri)| format!("{name} = {uri}\n"))
+                .collect::<String>();
+            write_output(None, &output_body)
+        }
+    }
+}
+
+/// Expands a config alias given on the command line to the URI it stands for.
+fn expand_config_alias(input: &str) -> xurl_core::Result<std::borrow::Cow<'_, str>> {
+    expand_alias(input, &config().aliases)
+}
+
+/// Parses an agents URI given on the command line, after expanding a config alias.
+fn parse_cli_uri(input: &str) -> xurl_core::Result<AgentsUri> {
+    AgentsUri::parse(&expand_config_alias(input)?)
+}
+
+/// Parses a thread URI and expands config aliases, `latest` aliases and, with `--prefix`,
+/// session id prefixes.
+fn parse_thread_uri(
+    input: &str,
+    prefix: bool,
+    roots: &ProviderRoots,
+) -> xurl_core::Result<AgentsUri> {
+    let input = &*expand_config_alias(input)?;
+    if !prefix {
+        return expand_latest_alias(&AgentsUri::parse(input)?, roots);
+    }
+    let uri = expand_latest_alias(&AgentsUri::parse_prefix(input)?, roots)?;
+    expand_session_prefix(&uri, roots)
+}
+
+/// With `--prefix`, a segment that reads as a session id prefix names a thread, not a role.
+fn names_session_prefix(input: &str, prefix: bool) -> bool {
+    prefix && AgentsUri::parse_prefix(input).is_ok_and(|uri| !uri.is_collection())
+}
+
+/// Whether writing to `input` creates a thread: a provider collection like `agents://codex`,
+/// or a role URI like `agents://codex/reviewer`.
+fn is_create_target(input: &str, prefix: bool) -> bool {
+    if names_session_prefix(input, prefix) {
+        return false;
+    }
+    parse_role_uri(input).is_ok_and(|role| role.is_some())
+        || AgentsUri::parse(input).is_ok_and(|uri| uri.is_collection())
+}
+
+fn parse_write_target(
+    input: &str,
+    prefix: bool,
+    roots: &ProviderRoots,
+) -> xurl_core::Result<WriteTarget> {
+    let input = &*expand_config_alias(input)?;
+    if !names_session_prefix(input, prefix)
+        && let Some(role_uri) = parse_role_uri(input)?
+    {
+        let (options, warnings) = build_write_options(role_uri.query, Some(role_uri.role))?;
+        check_write_model(role_uri.provider, &options)?;
         return Ok(WriteTarget {
             provider: role_uri.provider,
             session_id: None,
+            child_id: None,
             action: WriteAction::Create,
             options,
             warnings,
         });
     }
 
-    let uri = AgentsUri::parse(input)?;
-    if uri.agent_id.is_some() {
-        return Err(XurlError::InvalidMode(
-            "write mode only supports main thread URIs: agents://<provider>/<session_id>"
-                .to_string(),
-        ));
-    }
+    let uri = parse_thread_uri(input, prefix, roots)?;
     let action = if uri.is_collection() {
         WriteAction::Create
     } else {
         WriteAction::Append
     };
-    let (options, warnings) = build_write_options(uri.query, None);
+    let (options, warnings) = build_write_options(uri.query, None)?;
+    check_write_model(uri.provider, &options)?;
 
     let session_id = if uri.session_id.is_empty() {
         None
@@ -217,25 +2449,272 @@ fn parse_write_target(input: &str) -> xurl_core::Result<WriteTarget> {
     Ok(WriteTarget {
         provider: uri.provider,
         session_id,
+        child_id: uri.agent_id,
         action,
         options,
         warnings,
     })
 }
 
+/// Validates `?model=` against the provider's `[models]` allowlist from config.
+fn check_write_model(provider: ProviderKind, options: &WriteOptions) -> xurl_core::Result<()> {
+    match options.model.as_deref() {
+        Some(model) => config().check_model(provider, model),
+        None => Ok(()),
+    }
+}
+
+/// Write-mode flags that are not part of the URI.
+#[derive(Debug, Clone)]
+struct WriteFlags {
+    retries: u32,
+    system: Option<String>,
+    max_output_chars: Option<usize>,
+    max_duration: Option<Duration>,
+    dry_run: bool,
+}
+
+impl WriteFlags {
+    fn apply(self, options: &mut WriteOptions) -> xurl_core::Result<()> {
+        options.retries = self.retries;
+        if let Some(system) = self.system.as_deref() {
+            options.system = Some(load_data(system)?);
+        }
+        options.max_output_chars = self.max_output_chars;
+        options.max_duration = self.max_duration;
+        options.dry_run = self.dry_run;
+        Ok(())
+    }
+}
+
+/// Parses `--max-duration`, `--idle`, and `--max-age` values: plain seconds or a number with
+/// an `s`, `m`, `h`, or `d` suffix.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = match value.char_indices().find(|(_, ch)| !ch.is_ascii_digit()) {
+        Some((idx, _)) => value.split_at(idx),
+        None => (value, "s"),
+    };
+    let number = number.parse::<u64>().map_err(|_| {
+        format!("invalid duration `{value}`; use seconds like 90 or 90s, 5m, 1h, 30d")
+    })?;
+    let seconds = match unit {
+        "s" => Some(number),
+        "m" => number.checked_mul(60),
+        "h" => number.checked_mul(3600),
+        "d" => number.checked_mul(86_400),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        format!("invalid duration `{value}`; use seconds like 90 or 90s, 5m, 1h, 30d")
+    })?;
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Parses `--max-size` values: plain bytes or a number with a `K`, `M`, or `G` suffix
+/// (powers of 1024).
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let invalid = || format!("invalid size `{value}`; use bytes like 4096 or 512K, 500M, 1G");
+    let (number, unit) = match value.char_indices().find(|(_, ch)| !ch.is_ascii_digit()) {
+        Some((idx, _)) => value.split_at(idx),
+        None => (value, ""),
+    };
+    let number = number.parse::<u64>().map_err(|_| invalid())?;
+    match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => Some(number),
+        "K" => number.checked_mul(1 << 10),
+        "M" => number.checked_mul(1 << 20),
+        "G" => number.checked_mul(1 << 30),
+        _ => None,
+    }
+    .ok_or_else(invalid)
+}
+
+/// Sends the `-d` prompt (or the first stdin line), then every further non-blank stdin line
+/// to the same session until EOF. `--template` fills only the first prompt.
+fn run_interactive(
+    uri: &str,
+    prefix: bool,
+    data: &[String],
+    template: Option<&str>,
+    flags: WriteFlags,
+    roots: &ProviderRoots,
+    output: Option<&Path>,
+) -> xurl_core::Result<()> {
+    let mut target = parse_write_target(uri, prefix, roots)?;
+    flags.apply(&mut target.options)?;
+    for warning in &target.warnings {
+        warn(warning);
+    }
+    // Read `-d` payloads before locking stdin for the prompt loop, since `-d @-` reads it too.
+    let first_prompt = if data.is_empty() {
+        None
+    } else {
+        Some(build_prompt(data, template, roots)?)
+    };
+
+    let stdin = io::stdin();
+    let show_prompt = stdin.is_terminal();
+    let mut lines = stdin.lock().lines();
+    let mut next_prompt = || -> xurl_core::Result<Option<String>> {
+        loop {
+            if show_prompt {
+                eprint!("> ");
+            }
+            let Some(line) = lines.next() else {
+                return Ok(None);
+            };
+            let line = line.map_err(|source| XurlError::Io {
+                path: PathBuf::from("<stdin>"),
+                source,
+            })?;
+            if !line.trim().is_empty() {
+                return Ok(Some(line));
+            }
+        }
+    };
+
+    let mut prompt = match first_prompt {
+        Some(prompt) => Some(prompt),
+        None => next_prompt()?
+            .map(|line| apply_template(template, line, roots))
+            .transpose()?,
+    };
+    let mut sink = CliWriteSink::new(output, target.action)?;
+    let mut session_id = target.session_id;
+    let mut child_id = target.child_id;
+    let mut turn = 0;
+    while let Some(text) = prompt {
+        if turn > 0 {
+            sink.next_turn()?;
+        }
+        let result = write_thread(
+            target.provider,
+            roots,
+            &WriteRequest {
+                prompt: text,
+                session_id: session_id.clone(),
+                child_id: child_id.take(),
+                options: target.options.clone(),
+            },
+            &mut sink,
+        )?;
+        sink.finish(&result)?;
+        if show_prompt {
+            eprintln!();
+        }
+        session_id = Some(result.session_id);
+        turn += 1;
+        prompt = next_prompt()?;
+    }
+    Ok(())
+}
+
 fn build_write_options(
     params: Vec<(String, Option<String>)>,
     role: Option<String>,
-) -> (WriteOptions, Vec<String>) {
-    (WriteOptions { params, role }, Vec::new())
+) -> xurl_core::Result<(WriteOptions, Vec<String>)> {
+    let mut warnings = Vec::new();
+    let mut system = None;
+    let mut model = None;
+    let mut approval = None;
+    let mut passthrough = Vec::with_capacity(params.len());
+    for (key, value) in params {
+        if key != "system" && key != "model" && key != "approval" {
+            passthrough.push((key, value));
+            continue;
+        }
+        let Some(value) = value.filter(|value| !value.is_empty()) else {
+            warnings.push(format!("ignored query parameter `{key}` without a value"));
+            continue;
+        };
+        if key == "model" {
+            model = Some(value);
+        } else if key == "approval" {
+            approval = Some(value.parse::<ApprovalMode>()?);
+        } else {
+            // `?system=@file.md` reads the file, like `-d @file`.
+            system = Some(load_data(&value)?);
+        }
+    }
+
+    Ok((
+        WriteOptions {
+            params: passthrough,
+            role,
+            system,
+            model,
+            approval,
+            ..WriteOptions::default()
+        },
+        warnings,
+    ))
 }
 
-fn build_prompt(data: &[String]) -> xurl_core::Result<String> {
+fn build_prompt(
+    data: &[String],
+    template: Option<&str>,
+    roots: &ProviderRoots,
+) -> xurl_core::Result<String> {
     let mut chunks = Vec::with_capacity(data.len());
     for raw in data {
-        chunks.push(load_data(raw)?);
+        chunks.push(match thread_reference(raw) {
+            Some(uri) => inline_thread(uri, roots)?,
+            None => load_data(raw)?,
+        });
+    }
+    apply_template(template, chunks.join("\n"), roots)
+}
+
+/// The URI of a `-d thread:<URI>` payload. Text with whitespace after `thread:` is a prompt.
+fn thread_reference(raw: &str) -> Option<&str> {
+    raw.strip_prefix("thread:")
+        .filter(|uri| !uri.is_empty() && !uri.contains(char::is_whitespace))
+}
+
+/// Fills `prompt` into the `--template` named `template` as its `{{data}}`.
+fn apply_template(
+    template: Option<&str>,
+    prompt: String,
+    roots: &ProviderRoots,
+) -> xurl_core::Result<String> {
+    match template {
+        Some(name) => expand_prompt_template(&load_template(name)?, &prompt, roots),
+        None => Ok(prompt),
+    }
+}
+
+/// Returns stdin as the prompt when it is redirected and holds more than whitespace.
+fn read_piped_prompt() -> xurl_core::Result<Option<String>> {
+    let mut stdin = io::stdin();
+    if stdin.is_terminal() {
+        return Ok(None);
     }
-    Ok(chunks.join("\n"))
+    let mut input = String::new();
+    stdin
+        .read_to_string(&mut input)
+        .map_err(|source| XurlError::Io {
+            path: PathBuf::from("<stdin>"),
+            source,
+        })?;
+    Ok((!input.trim().is_empty()).then_some(input))
+}
+
+fn read_stdin_uris() -> xurl_core::Result<Vec<String>> {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|source| XurlError::Io {
+            path: PathBuf::from("<stdin>"),
+            source,
+        })?;
+    Ok(input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(ToString::to_string)
+        .collect())
 }
 
 fn load_data(raw: &str) -> xurl_core::Result<String> {
@@ -263,11 +2742,67 @@ enum WriteDestination {
     File { path: PathBuf, file: fs::File },
 }
 
+struct CliWatchSink {
+    remaining: Option<usize>,
+    webhook: Option<String>,
+}
+
+impl CliWatchSink {
+    /// Posts an idle event to the webhook. A failed delivery is a warning, so one
+    /// unreachable endpoint does not stop the watch.
+    fn post_webhook(&self, event: &ThreadEvent) -> xurl_core::Result<()> {
+        let Some(url) = &self.webhook else {
+            return Ok(());
+        };
+        if event.event != ThreadEventKind::ThreadIdle {
+            return Ok(());
+        }
+        let body = render_thread_webhook_json(event)?;
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .timeout_global(Some(WEBHOOK_TIMEOUT))
+            .build()
+            .into();
+        if let Err(err) = agent
+            .post(url)
+            .header("Content-Type", "application/json")
+            .send(&body)
+        {
+            warn(format_args!(
+                "webhook POST to {url} failed for {}: {err}",
+                event.uri
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl ThreadEventSink for CliWatchSink {
+    fn on_warning(&mut self, warning: &str) {
+        warn(warning);
+    }
+
+    fn on_event(&mut self, event: &ThreadEvent) -> xurl_core::Result<bool> {
+        // Flush each line so scripts reading the pipe see events as they happen.
+        let mut stdout = io::stdout().lock();
+        stdout
+            .write_all(render_thread_event_json(event)?.as_bytes())
+            .and_then(|()| stdout.flush())
+            .map_err(|source| output_io_error(None, source))?;
+        self.post_webhook(event)?;
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= 1;
+            return Ok(*remaining > 0);
+        }
+        Ok(true)
+    }
+}
+
 struct CliWriteSink {
     destination: WriteDestination,
     action: WriteAction,
     uri_emitted: bool,
     text_emitted: bool,
+    at_line_start: bool,
 }
 
 impl CliWriteSink {
@@ -290,6 +2825,7 @@ impl CliWriteSink {
             action,
             uri_emitted: false,
             text_emitted: false,
+            at_line_start: true,
         })
     }
 
@@ -297,16 +2833,19 @@ impl CliWriteSink {
         if self.uri_emitted {
             return;
         }
+        self.uri_emitted = true;
+        if print_uri() {
+            return;
+        }
         let verb = match self.action {
             WriteAction::Create => "created",
             WriteAction::Append => "updated",
         };
-        eprintln!("{verb}: agents://{provider}/{session_id}");
-        self.uri_emitted = true;
+        notice(format_args!("{verb}: agents://{provider}/{session_id}"));
     }
 
     fn write_delta(&mut self, text: &str) -> xurl_core::Result<()> {
-        if text.is_empty() {
+        if text.is_empty() || output_options().discard_reply {
             return Ok(());
         }
 
@@ -337,12 +2876,27 @@ impl CliWriteSink {
             }
         }
         self.text_emitted = true;
+        self.at_line_start = text.ends_with('\n');
+        Ok(())
+    }
+
+    /// Prepares the sink for a follow-up prompt appended to the session it just reported.
+    fn next_turn(&mut self) -> xurl_core::Result<()> {
+        if self.text_emitted {
+            self.write_delta("\n\n")?;
+        }
+        self.action = WriteAction::Append;
+        self.uri_emitted = false;
+        self.text_emitted = false;
         Ok(())
     }
 
     fn finish(&mut self, result: &WriteResult) -> xurl_core::Result<()> {
         for warning in &result.warnings {
-            eprintln!("warning: {warning}");
+            warn(warning);
+        }
+        if let Some(command) = &result.dry_run {
+            return self.write_delta(&render_write_command(command)?);
         }
         self.emit_uri_once(result.provider, &result.session_id);
         if !self.text_emitted
@@ -350,6 +2904,17 @@ impl CliWriteSink {
         {
             self.write_delta(text)?;
         }
+        report_write_summary(result)?;
+        if print_uri() {
+            let separator = match &self.destination {
+                WriteDestination::Stdout if self.text_emitted && !self.at_line_start => "\n",
+                _ => "",
+            };
+            println!(
+                "{separator}agents://{}/{}",
+                result.provider, result.session_id
+            );
+        }
         Ok(())
     }
 }
@@ -369,43 +2934,142 @@ impl WriteEventSink for CliWriteSink {
     }
 }
 
-fn user_facing_error(err: &XurlError) -> String {
-    match err {
-        XurlError::CommandNotFound { command } if command.contains("amp") => format!(
-            "{err}\nhint: write mode needs Amp CLI; run `amp --version`, install Amp CLI if missing, then run `amp login`."
-        ),
-        XurlError::CommandNotFound { command } if command.contains("codex") => format!(
-            "{err}\nhint: write mode needs Codex CLI; run `codex --version`, install Codex CLI if missing, then run `codex login`."
-        ),
-        XurlError::CommandNotFound { command } if command.contains("claude") => format!(
-            "{err}\nhint: write mode needs Claude CLI; run `claude --version`, install Claude Code if missing, then authenticate."
-        ),
-        XurlError::CommandNotFound { command } if command.contains("gemini") => format!(
-            "{err}\nhint: write mode needs Gemini CLI; run `gemini --version`, install Gemini CLI if missing, then authenticate."
-        ),
-        XurlError::CommandNotFound { command } if command.contains("pi") => format!(
-            "{err}\nhint: write mode needs pi CLI; run `pi --version`, install pi if missing, then configure provider credentials."
-        ),
-        XurlError::CommandNotFound { command } if command.contains("opencode") => format!(
-            "{err}\nhint: write mode needs OpenCode CLI; run `opencode --version`, install OpenCode if missing, then configure providers/models."
-        ),
-        XurlError::CommandFailed { command, .. } if command.contains("amp") => {
-            format!("{err}\nhint: verify authentication with `amp login` and retry.")
+/// Streams one multicast write's reply into a shared sink, one `[label] ` prefixed line at a
+/// time, so concurrent replies interleave by whole lines.
+struct LabeledWriteSink<'a> {
+    label: &'a str,
+    out: &'a Mutex<CliWriteSink>,
+    pending: String,
+    text_emitted: bool,
+}
+
+impl<'a> LabeledWriteSink<'a> {
+    fn new(label: &'a str, out: &'a Mutex<CliWriteSink>) -> Self {
+        Self {
+            label,
+            out,
+            pending: String::new(),
+            text_emitted: false,
+        }
+    }
+
+    /// Writes the complete lines buffered so far, or everything when `flush` is set.
+    fn write_lines(&mut self, text: &str, flush: bool) -> xurl_core::Result<()> {
+        self.pending.push_str(text);
+        let end = if flush {
+            self.pending.len()
+        } else {
+            self.pending.rfind('\n').map_or(0, |idx| idx + 1)
+        };
+        if end == 0 {
+            return Ok(());
         }
-        XurlError::CommandFailed { command, .. } if command.contains("codex") => {
-            format!("{err}\nhint: verify authentication with `codex login` and retry.")
+        let mut labeled = String::new();
+        for line in self.pending[..end].lines() {
+            labeled.push_str(&format!("[{}] {line}\n", self.label));
         }
-        XurlError::CommandFailed { command, .. } if command.contains("claude") => format!(
-            "{err}\nhint: verify authentication with `claude auth` (or your configured login flow) and retry."
-        ),
-        XurlError::CommandFailed { command, .. } if command.contains("gemini") => format!(
-            "{err}\nhint: verify Gemini authentication/configuration and retry the command directly once."
-        ),
-        XurlError::CommandFailed { command, .. } if command.contains("pi") => format!(
-            "{err}\nhint: verify pi provider/model credentials and retry with `pi -p \"hello\" --mode json`."
+        self.pending.drain(..end);
+        let mut out = self
+            .out
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        out.write_delta(&labeled)
+    }
+
+    fn flush(&mut self) -> xurl_core::Result<()> {
+        self.write_lines("", true)
+    }
+
+    /// Writes the final reply when nothing streamed, then any unterminated last line.
+    fn finish(mut self, result: &WriteResult) -> xurl_core::Result<()> {
+        if !self.text_emitted
+            && let Some(text) = result.final_text.as_deref()
+        {
+            self.pending.push_str(text);
+        }
+        self.flush()
+    }
+}
+
+impl WriteEventSink for LabeledWriteSink<'_> {
+    fn on_session_ready(&mut self, _: ProviderKind, _: &str) -> xurl_core::Result<()> {
+        Ok(())
+    }
+
+    fn on_text_delta(&mut self, text: &str) -> xurl_core::Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.text_emitted = true;
+        self.write_lines(text, false)
+    }
+}
+
+/// Formats a `--dry-run` command as `command:`, `cwd:`, and `env:` lines.
+fn render_write_command(command: &WriteCommand) -> xurl_core::Result<String> {
+    let cwd = match &command.cwd {
+        Some(cwd) => cwd.clone(),
+        None => std::env::current_dir().map_err(|source| XurlError::Io {
+            path: PathBuf::from("."),
+            source,
+        })?,
+    };
+    let mut output = format!(
+        "command: {}\ncwd: {}\n",
+        std::iter::once(&command.program)
+            .chain(&command.args)
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" "),
+        cwd.display()
+    );
+    if command.env.is_empty() {
+        output.push_str("env: inherited, no overrides\n");
+    }
+    for (key, value) in &command.env {
+        output.push_str(&format!("env: {key}={}\n", shell_quote(value)));
+    }
+    Ok(output)
+}
+
+/// Quotes `arg` for a POSIX shell unless it only holds characters that need no quoting.
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || "-_./=:,@%+".contains(ch));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Finds the provider CLI a failed command belongs to by comparing the command's binary file
+/// name with each provider's configured bin, so directories in the path never match.
+fn provider_cli_for(command: &str) -> Option<ProviderCli> {
+    let binary = Path::new(command.split_whitespace().next()?).file_name()?;
+    ProviderKind::ALL
+        .into_iter()
+        .find(|provider| Path::new(&ProviderCli::bin(*provider)).file_name() == Some(binary))
+        .map(ProviderCli::of)
+}
+
+fn user_facing_error(err: &XurlError) -> String {
+    match err {
+        XurlError::CommandFailed { command, .. } if command.contains("/api/threads/") => format!(
+            "{err}\nhint: verify XURL_AMP_API_TOKEN and that the thread exists on the Amp server."
         ),
-        XurlError::CommandFailed { command, .. } if command.contains("opencode") => format!(
-            "{err}\nhint: verify OpenCode provider/model configuration and retry with `opencode run \"hello\" --format json`."
+        XurlError::CommandNotFound { command } => match provider_cli_for(command) {
+            Some(cli) => format!("{err}\nhint: write mode needs {}; {}.", cli.name, cli.setup),
+            None => err.to_string(),
+        },
+        XurlError::CommandFailed { command, .. } => match provider_cli_for(command) {
+            Some(cli) => format!("{err}\nhint: {}.", cli.auth),
+            None => err.to_string(),
+        },
+        XurlError::GitCommandFailed { command, .. } if command.contains("rev-parse") => format!(
+            "{err}\nhint: run `xurl blame` inside the git repository that contains the commit."
         ),
         XurlError::SkillSelectionRequired { candidates, .. } => format!(
             "{err}\nhint: choose one candidate URI and retry:\n{}",
@@ -415,9 +3079,20 @@ fn user_facing_error(err: &XurlError) -> String {
                 .collect::<Vec<_>>()
                 .join("\n")
         ),
+        XurlError::AmbiguousSessionPrefix { candidates, .. } => format!(
+            "{err}\nhint: use a longer prefix or one of these URIs:\n{}",
+            candidates
+                .iter()
+                .map(|candidate| format!("- {candidate}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
         XurlError::SkillNotFound { .. } => {
             format!("{err}\nhint: verify the skill name/path and retry the skills:// URI.")
         }
+        XurlError::SnapshotNotFound { .. } => format!(
+            "{err}\nhint: snapshots are read from XURL_SNAPSHOTS_ROOT (default ~/.xurl/snapshots); take one with `xurl snapshot <URI>`."
+        ),
         _ => err.to_string(),
     }
 }
@@ -7,488 +7,36 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 use rusqlite::{Connection, params};
 use tempfile::tempdir;
+use xurl_core::frontmatter::parse_frontmatter;
+use xurl_test_support::amp::{
+    setup_amp_subagent_tree, setup_amp_subagent_tree_missing_role, setup_amp_tree,
+};
+use xurl_test_support::claude::setup_claude_subagent_tree;
+use xurl_test_support::codex::{
+    setup_codex_role_configs, setup_codex_role_query_tree, setup_codex_subagent_tree,
+    setup_codex_subagent_tree_with_sqlite_missing_threads, setup_codex_tree,
+    setup_codex_tree_with_sqlite_missing_threads,
+};
+use xurl_test_support::gemini::{
+    setup_gemini_subagent_tree, setup_gemini_subagent_tree_with_ndjson_logs, setup_gemini_tree,
+};
+use xurl_test_support::ids::{
+    AMP_SESSION_ID, AMP_SUBAGENT_ID, CLAUDE_AGENT_ID, CLAUDE_SESSION_ID, GEMINI_CHILD_SESSION_ID,
+    GEMINI_MISSING_CHILD_SESSION_ID, GEMINI_SESSION_ID, OPENCODE_CHILD_EMPTY_SESSION_ID,
+    OPENCODE_CHILD_SESSION_ID, OPENCODE_MAIN_SESSION_ID, PI_CHILD_SESSION_ID, PI_ENTRY_ID,
+    PI_MISSING_CHILD_SESSION_ID, PI_SESSION_ID, SESSION_ID, SUBAGENT_ID,
+};
+use xurl_test_support::opencode::setup_opencode_subagent_tree;
+use xurl_test_support::pi::{setup_pi_tree, setup_pi_tree_with_child_sessions};
+use xurl_test_support::{ClaudeTreeBuilder, CodexTreeBuilder, Message};
 
-const SESSION_ID: &str = "019c871c-b1f9-7f60-9c4f-87ed09f13592";
-const SUBAGENT_ID: &str = "019c87fb-38b9-7843-92b1-832f02598495";
 const REAL_FIXTURE_MAIN_ID: &str = "55fe4488-c6bd-46fa-9390-dab3b8860b95";
 const REAL_FIXTURE_AGENT_ID: &str = "29bf19c3-b83e-401d-8f38-5660b7f67152";
-const AMP_SESSION_ID: &str = "T-019c0797-c402-7389-bd80-d785c98df295";
-const AMP_SUBAGENT_ID: &str = "T-1abc0797-c402-7389-bd80-d785c98df295";
-const GEMINI_SESSION_ID: &str = "29d207db-ca7e-40ba-87f7-e14c9de60613";
-const GEMINI_CHILD_SESSION_ID: &str = "2b112c8a-d80a-4cff-9c8a-6f3e6fbaf7fb";
-const GEMINI_MISSING_CHILD_SESSION_ID: &str = "62f9f98d-c578-4d3a-b4bf-3aaed19889d6";
 const GEMINI_REAL_SESSION_ID: &str = "da2ab190-85f8-4d5c-bcce-8292921a33bf";
-const PI_SESSION_ID: &str = "12cb4c19-2774-4de4-a0d0-9fa32fbae29f";
-const PI_ENTRY_ID: &str = "d1b2c3d4";
-const PI_CHILD_SESSION_ID: &str = "72b3a4a8-4f08-40af-8d7f-8b2c77584e89";
-const PI_MISSING_CHILD_SESSION_ID: &str = "b200f2f0-5291-4b89-a1e7-7c6a95f11011";
 const PI_REAL_SESSION_ID: &str = "bc6ea3d9-0e40-4942-a490-3e0aa7f125de";
-const CLAUDE_SESSION_ID: &str = "2823d1df-720a-4c31-ac55-ae8ba726721f";
-const CLAUDE_AGENT_ID: &str = "acompact-69d537";
 const CLAUDE_REAL_MAIN_ID: &str = "b90fc33d-33cb-4027-8558-119e2b56c74e";
 const CLAUDE_REAL_AGENT_ID: &str = "a4f21c7";
 const OPENCODE_REAL_SESSION_ID: &str = "ses_7v2md9kx3c1p";
-const OPENCODE_MAIN_SESSION_ID: &str = "ses_5x7md9kx3c1p";
-const OPENCODE_CHILD_SESSION_ID: &str = "ses_5x7md9kx3c2p";
-const OPENCODE_CHILD_EMPTY_SESSION_ID: &str = "ses_5x7md9kx3c3p";
-
-fn setup_codex_tree() -> tempfile::TempDir {
-    let temp = tempdir().expect("tempdir");
-    let thread_path = temp.path().join(format!(
-        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
-    ));
-    fs::create_dir_all(thread_path.parent().expect("parent")).expect("mkdir");
-    fs::write(
-        &thread_path,
-        "{\"type\":\"response_item\",\"payload\":{\"type\":\"message\",\"role\":\"user\",\"content\":[{\"type\":\"input_text\",\"text\":\"hello\"}]}}\n{\"type\":\"response_item\",\"payload\":{\"type\":\"message\",\"role\":\"assistant\",\"content\":[{\"type\":\"output_text\",\"text\":\"world\"}]}}\n",
-    )
-    .expect("write");
-
-    temp
-}
-
-fn setup_codex_tree_with_sqlite_missing_threads() -> tempfile::TempDir {
-    let temp = setup_codex_tree();
-    fs::write(temp.path().join("state.sqlite"), "").expect("write sqlite");
-    temp
-}
-
-fn setup_codex_role_query_tree() -> tempfile::TempDir {
-    let temp = tempdir().expect("tempdir");
-    let thread_path = temp.path().join(format!(
-        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
-    ));
-    fs::create_dir_all(thread_path.parent().expect("parent")).expect("mkdir");
-    fs::write(
-        &thread_path,
-        "{\"type\":\"response_item\",\"payload\":{\"type\":\"message\",\"role\":\"user\",\"content\":[{\"type\":\"input_text\",\"text\":\"run reviewer role\"}]}}\n{\"type\":\"response_item\",\"payload\":{\"type\":\"message\",\"role\":\"assistant\",\"content\":[{\"type\":\"output_text\",\"text\":\"reviewer done\"}]}}\n",
-    )
-    .expect("write");
-    temp
-}
-
-fn setup_codex_role_configs(root: &Path) {
-    fs::write(
-        root.join("config.toml"),
-        r#"
-[agents.reviewer]
-description = "Find issues."
-config_file = "agents/reviewer.toml"
-model_reasoning_effort = "high"
-developer_instructions = "Focus on high priority issues."
-"#,
-    )
-    .expect("write config");
-
-    let role_dir = root.join("agents");
-    fs::create_dir_all(&role_dir).expect("mkdir");
-    fs::write(
-        role_dir.join("reviewer.toml"),
-        r#"
-model = "gpt-5.3-codex"
-"#,
-    )
-    .expect("write role config");
-}
-
-fn setup_amp_tree() -> tempfile::TempDir {
-    let temp = tempdir().expect("tempdir");
-    let thread_path = temp
-        .path()
-        .join(format!("amp/threads/{AMP_SESSION_ID}.json"));
-    fs::create_dir_all(thread_path.parent().expect("parent")).expect("mkdir");
-    fs::write(
-        &thread_path,
-        r#"{"id":"T-019c0797-c402-7389-bd80-d785c98df295","messages":[{"role":"user","content":[{"type":"text","text":"hello"}]},{"role":"assistant","content":[{"type":"thinking","thinking":"analyze"},{"type":"text","text":"world"}]}]}"#,
-    )
-    .expect("write");
-    temp
-}
-
-fn setup_amp_subagent_tree_with_role(main_role: Option<&str>) -> tempfile::TempDir {
-    let temp = tempdir().expect("tempdir");
-    let main_path = temp
-        .path()
-        .join(format!("amp/threads/{AMP_SESSION_ID}.json"));
-    fs::create_dir_all(main_path.parent().expect("parent")).expect("mkdir");
-    let role_field = main_role
-        .map(|role| format!(r#","role":"{role}""#))
-        .unwrap_or_default();
-    fs::write(
-        &main_path,
-        format!(
-            r#"{{"id":"{AMP_SESSION_ID}","status":"running","updatedAt":"2026-02-23T00:00:03Z","messages":[{{"role":"user","timestamp":"2026-02-23T00:00:00Z","content":[{{"type":"text","text":"main task"}}]}}],"relationships":[{{"type":"handoff","threadID":"{AMP_SUBAGENT_ID}"{role_field},"timestamp":"2026-02-23T00:00:02Z"}}]}}"#
-        ),
-    )
-    .expect("write main");
-
-    let child_path = temp
-        .path()
-        .join(format!("amp/threads/{AMP_SUBAGENT_ID}.json"));
-    fs::create_dir_all(child_path.parent().expect("parent")).expect("mkdir");
-    fs::write(
-        &child_path,
-        format!(
-            r#"{{"id":"{AMP_SUBAGENT_ID}","status":"completed","lastUpdated":"2026-02-23T00:00:14Z","messages":[{{"role":"user","timestamp":"2026-02-23T00:00:11Z","content":[{{"type":"text","text":"hello child"}}]}},{{"role":"assistant","timestamp":"2026-02-23T00:00:12Z","content":[{{"type":"text","text":"done child"}}]}}],"relationships":[{{"type":"handoff","threadID":"{AMP_SESSION_ID}","role":"child","timestamp":"2026-02-23T00:00:12Z"}}]}}"#
-        ),
-    )
-    .expect("write child");
-
-    temp
-}
-
-fn setup_amp_subagent_tree() -> tempfile::TempDir {
-    setup_amp_subagent_tree_with_role(Some("parent"))
-}
-
-fn setup_amp_subagent_tree_missing_role() -> tempfile::TempDir {
-    setup_amp_subagent_tree_with_role(None)
-}
-
-fn setup_codex_subagent_tree() -> tempfile::TempDir {
-    let temp = tempdir().expect("tempdir");
-    let main_thread_path = temp.path().join(format!(
-        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
-    ));
-    fs::create_dir_all(main_thread_path.parent().expect("parent")).expect("mkdir");
-    fs::write(
-        &main_thread_path,
-        format!(
-            "{{\"timestamp\":\"2026-02-23T00:00:00Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call\",\"name\":\"spawn_agent\",\"arguments\":\"{{}}\",\"call_id\":\"call_spawn\"}}}}\n{{\"timestamp\":\"2026-02-23T00:00:01Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call_output\",\"call_id\":\"call_spawn\",\"output\":\"{{\\\"agent_id\\\":\\\"{SUBAGENT_ID}\\\"}}\"}}}}\n{{\"timestamp\":\"2026-02-23T00:00:02Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call\",\"name\":\"wait\",\"arguments\":\"{{\\\"ids\\\":[\\\"{SUBAGENT_ID}\\\"],\\\"timeout_ms\\\":120000}}\",\"call_id\":\"call_wait\"}}}}\n{{\"timestamp\":\"2026-02-23T00:00:03Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call_output\",\"call_id\":\"call_wait\",\"output\":\"{{\\\"status\\\":{{\\\"running\\\":\\\"in progress\\\"}},\\\"timed_out\\\":false}}\"}}}}\n{{\"timestamp\":\"2026-02-23T00:00:04Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call\",\"name\":\"close_agent\",\"arguments\":\"{{\\\"id\\\":\\\"{SUBAGENT_ID}\\\"}}\",\"call_id\":\"call_close\"}}}}\n{{\"timestamp\":\"2026-02-23T00:00:05Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call_output\",\"call_id\":\"call_close\",\"output\":\"{{\\\"status\\\":{{\\\"completed\\\":\\\"done\\\"}}}}\"}}}}\n"
-        ),
-    )
-    .expect("write main");
-
-    let child_thread_path = temp.path().join(format!(
-        "sessions/2026/02/23/rollout-2026-02-23T04-49-10-{SUBAGENT_ID}.jsonl"
-    ));
-    fs::create_dir_all(child_thread_path.parent().expect("parent")).expect("mkdir");
-    fs::write(
-        &child_thread_path,
-        format!(
-            "{{\"timestamp\":\"2026-02-23T00:00:10Z\",\"type\":\"session_meta\",\"payload\":{{\"id\":\"{SUBAGENT_ID}\",\"source\":{{\"subagent\":{{\"thread_spawn\":{{\"parent_thread_id\":\"{SESSION_ID}\",\"depth\":1}}}}}}}}}}\n{{\"timestamp\":\"2026-02-23T00:00:11Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"message\",\"role\":\"user\",\"content\":[{{\"type\":\"input_text\",\"text\":\"hello child\"}}]}}}}\n{{\"timestamp\":\"2026-02-23T00:00:12Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"message\",\"role\":\"assistant\",\"content\":[{{\"type\":\"output_text\",\"text\":\"done child\"}}]}}}}\n"
-        ),
-    )
-    .expect("write child");
-
-    temp
-}
-
-fn setup_codex_subagent_tree_with_sqlite_missing_threads() -> tempfile::TempDir {
-    let temp = setup_codex_subagent_tree();
-    fs::write(temp.path().join("state.sqlite"), "").expect("write sqlite");
-    temp
-}
-
-fn setup_claude_subagent_tree() -> tempfile::TempDir {
-    let temp = tempdir().expect("tempdir");
-    let project = temp.path().join("projects/project-subagent");
-    fs::create_dir_all(&project).expect("mkdir");
-
-    let main_thread = project.join(format!("{CLAUDE_SESSION_ID}.jsonl"));
-    fs::write(
-        &main_thread,
-        format!(
-            "{{\"timestamp\":\"2026-02-23T00:00:00Z\",\"type\":\"user\",\"sessionId\":\"{CLAUDE_SESSION_ID}\",\"message\":{{\"role\":\"user\",\"content\":\"root thread\"}}}}\n"
-        ),
-    )
-    .expect("write main");
-
-    let subagents_dir = project.join(CLAUDE_SESSION_ID).join("subagents");
-    fs::create_dir_all(&subagents_dir).expect("mkdir");
-    let agent_thread = subagents_dir.join(format!("agent-{CLAUDE_AGENT_ID}.jsonl"));
-    fs::write(
-        &agent_thread,
-        format!(
-            "{{\"timestamp\":\"2026-02-23T00:00:10Z\",\"type\":\"user\",\"sessionId\":\"{CLAUDE_SESSION_ID}\",\"isSidechain\":true,\"agentId\":\"{CLAUDE_AGENT_ID}\",\"message\":{{\"role\":\"user\",\"content\":\"agent task\"}}}}\n{{\"timestamp\":\"2026-02-23T00:00:11Z\",\"type\":\"assistant\",\"sessionId\":\"{CLAUDE_SESSION_ID}\",\"isSidechain\":true,\"agentId\":\"{CLAUDE_AGENT_ID}\",\"message\":{{\"role\":\"assistant\",\"content\":\"agent done\"}}}}\n"
-        ),
-    )
-    .expect("write agent");
-
-    temp
-}
-
-fn setup_gemini_tree() -> tempfile::TempDir {
-    let temp = tempdir().expect("tempdir");
-    let thread_path = temp.path().join(
-        ".gemini/tmp/0c0d7b04c22749f3687ea60b66949fd32bcea2551d4349bf72346a9ccc9a9ba4/chats/session-2026-01-08T11-55-29-29d207db.json",
-    );
-    fs::create_dir_all(thread_path.parent().expect("parent")).expect("mkdir");
-    fs::write(
-        &thread_path,
-        format!(
-            r#"{{
-  "sessionId": "{GEMINI_SESSION_ID}",
-  "projectHash": "0c0d7b04c22749f3687ea60b66949fd32bcea2551d4349bf72346a9ccc9a9ba4",
-  "startTime": "2026-01-08T11:55:12.379Z",
-  "lastUpdated": "2026-01-08T12:31:14.881Z",
-  "messages": [
-    {{ "type": "info", "content": "ignored" }},
-    {{ "type": "user", "content": "hello" }},
-    {{ "type": "gemini", "content": "world" }}
-  ]
-}}"#
-        ),
-    )
-    .expect("write");
-    temp
-}
-
-fn setup_gemini_subagent_tree() -> tempfile::TempDir {
-    let temp = tempdir().expect("tempdir");
-    let project_hash = "0c0d7b04c22749f3687ea60b66949fd32bcea2551d4349bf72346a9ccc9a9ba4";
-    let project_root = temp.path().join(format!(".gemini/tmp/{project_hash}"));
-    let chats_dir = project_root.join("chats");
-    fs::create_dir_all(&chats_dir).expect("mkdir chats");
-
-    let main_chat_path = chats_dir.join("session-2026-01-08T11-55-main.json");
-    fs::write(
-        &main_chat_path,
-        format!(
-            r#"{{
-  "sessionId": "{GEMINI_SESSION_ID}",
-  "projectHash": "{project_hash}",
-  "startTime": "2026-01-08T11:55:12.379Z",
-  "lastUpdated": "2026-01-08T12:31:14.881Z",
-  "messages": [
-    {{ "type": "user", "content": "hello main" }},
-    {{ "type": "gemini", "content": "main done" }}
-  ]
-}}"#
-        ),
-    )
-    .expect("write main chat");
-
-    let child_chat_path = chats_dir.join("session-2026-01-08T12-12-child.json");
-    fs::write(
-        &child_chat_path,
-        format!(
-            r#"{{
-  "sessionId": "{GEMINI_CHILD_SESSION_ID}",
-  "parentSessionId": "{GEMINI_SESSION_ID}",
-  "projectHash": "{project_hash}",
-  "startTime": "2026-01-08T12:12:00.000Z",
-  "lastUpdated": "2026-01-08T12:20:00.000Z",
-  "messages": [
-    {{ "type": "user", "content": "/resume" }},
-    {{ "type": "gemini", "content": "child done" }}
-  ]
-}}"#
-        ),
-    )
-    .expect("write child chat");
-
-    let logs_path = project_root.join("logs.json");
-    fs::write(
-        &logs_path,
-        format!(
-            r#"[
-  {{
-    "sessionId": "{GEMINI_SESSION_ID}",
-    "messageId": 0,
-    "type": "user",
-    "message": "hello main",
-    "timestamp": "2026-01-08T11:59:09.195Z"
-  }},
-  {{
-    "sessionId": "{GEMINI_MISSING_CHILD_SESSION_ID}",
-    "messageId": 0,
-    "type": "user",
-    "message": "/resume",
-    "timestamp": "2026-01-08T12:00:09.195Z"
-  }},
-  {{
-    "sessionId": "{GEMINI_CHILD_SESSION_ID}",
-    "messageId": 0,
-    "type": "user",
-    "message": "/resume",
-    "timestamp": "2026-01-08T12:11:44.907Z"
-  }}
-]"#
-        ),
-    )
-    .expect("write logs");
-
-    temp
-}
-
-fn setup_gemini_subagent_tree_with_ndjson_logs() -> tempfile::TempDir {
-    let temp = setup_gemini_subagent_tree();
-    let project_hash = "0c0d7b04c22749f3687ea60b66949fd32bcea2551d4349bf72346a9ccc9a9ba4";
-    let logs_path = temp
-        .path()
-        .join(format!(".gemini/tmp/{project_hash}/logs.json"));
-    fs::write(
-        &logs_path,
-        format!(
-            r#"{{"sessionId":"{GEMINI_SESSION_ID}","messageId":0,"type":"user","message":"hello main","timestamp":"2026-01-08T11:59:09.195Z"}}
-{{"sessionId":"{GEMINI_MISSING_CHILD_SESSION_ID}","messageId":0,"type":"user","message":"/resume","timestamp":"2026-01-08T12:00:09.195Z"}}
-{{"sessionId":"{GEMINI_CHILD_SESSION_ID}","messageId":0,"type":"user","message":"/resume","timestamp":"2026-01-08T12:11:44.907Z"}}"#
-        ),
-    )
-    .expect("write ndjson logs");
-
-    temp
-}
-
-fn setup_pi_tree() -> tempfile::TempDir {
-    let temp = tempdir().expect("tempdir");
-    let thread_path = temp.path().join(
-        "agent/sessions/--Users-xuanwo-Code-pi-project--/2026-02-23T13-00-12-780Z_12cb4c19-2774-4de4-a0d0-9fa32fbae29f.jsonl",
-    );
-    fs::create_dir_all(thread_path.parent().expect("parent")).expect("mkdir");
-    fs::write(
-        &thread_path,
-        format!(
-            "{{\"type\":\"session\",\"version\":3,\"id\":\"{PI_SESSION_ID}\",\"timestamp\":\"2026-02-23T13:00:12.780Z\",\"cwd\":\"/tmp/project\"}}\n{{\"type\":\"message\",\"id\":\"a1b2c3d4\",\"parentId\":null,\"timestamp\":\"2026-02-23T13:00:13.000Z\",\"message\":{{\"role\":\"user\",\"content\":[{{\"type\":\"text\",\"text\":\"root\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"b1b2c3d4\",\"parentId\":\"a1b2c3d4\",\"timestamp\":\"2026-02-23T13:00:14.000Z\",\"message\":{{\"role\":\"assistant\",\"content\":[{{\"type\":\"text\",\"text\":\"root done\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"c1b2c3d4\",\"parentId\":\"b1b2c3d4\",\"timestamp\":\"2026-02-23T13:00:15.000Z\",\"message\":{{\"role\":\"user\",\"content\":[{{\"type\":\"text\",\"text\":\"branch one\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"d1b2c3d4\",\"parentId\":\"c1b2c3d4\",\"timestamp\":\"2026-02-23T13:00:16.000Z\",\"message\":{{\"role\":\"assistant\",\"content\":[{{\"type\":\"text\",\"text\":\"branch one done\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"e1b2c3d4\",\"parentId\":\"b1b2c3d4\",\"timestamp\":\"2026-02-23T13:00:17.000Z\",\"message\":{{\"role\":\"user\",\"content\":[{{\"type\":\"text\",\"text\":\"branch two\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"f1b2c3d4\",\"parentId\":\"e1b2c3d4\",\"timestamp\":\"2026-02-23T13:00:18.000Z\",\"message\":{{\"role\":\"assistant\",\"content\":[{{\"type\":\"text\",\"text\":\"branch two done\"}}]}}}}\n"
-        ),
-    )
-    .expect("write");
-    temp
-}
-
-fn setup_pi_tree_with_child_sessions() -> tempfile::TempDir {
-    let temp = tempdir().expect("tempdir");
-    let main_thread_path = temp.path().join(
-        "agent/sessions/--Users-xuanwo-Code-pi-project--/2026-02-23T13-00-12-780Z_12cb4c19-2774-4de4-a0d0-9fa32fbae29f.jsonl",
-    );
-    fs::create_dir_all(main_thread_path.parent().expect("parent")).expect("mkdir");
-    fs::write(
-        &main_thread_path,
-        format!(
-            "{{\"type\":\"session\",\"version\":3,\"id\":\"{PI_SESSION_ID}\",\"timestamp\":\"2026-02-23T13:00:12.780Z\",\"cwd\":\"/tmp/project\",\"childSessionIds\":[\"{PI_CHILD_SESSION_ID}\",\"{PI_MISSING_CHILD_SESSION_ID}\"]}}\n{{\"type\":\"message\",\"id\":\"a1b2c3d4\",\"parentId\":null,\"timestamp\":\"2026-02-23T13:00:13.000Z\",\"message\":{{\"role\":\"user\",\"content\":[{{\"type\":\"text\",\"text\":\"root\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"b1b2c3d4\",\"parentId\":\"a1b2c3d4\",\"timestamp\":\"2026-02-23T13:00:14.000Z\",\"message\":{{\"role\":\"assistant\",\"content\":[{{\"type\":\"text\",\"text\":\"root done\"}}]}}}}\n"
-        ),
-    )
-    .expect("write main");
-
-    let child_thread_path = temp.path().join(format!(
-        "agent/sessions/--Users-xuanwo-Code-pi-project--/2026-02-23T13-10-12-780Z_{PI_CHILD_SESSION_ID}.jsonl"
-    ));
-    fs::create_dir_all(child_thread_path.parent().expect("parent")).expect("mkdir");
-    fs::write(
-        &child_thread_path,
-        format!(
-            "{{\"type\":\"session\",\"version\":3,\"id\":\"{PI_CHILD_SESSION_ID}\",\"timestamp\":\"2026-02-23T13:10:12.780Z\",\"cwd\":\"/tmp/project\",\"parent_session_id\":\"{PI_SESSION_ID}\"}}\n{{\"type\":\"message\",\"id\":\"b1c2d3e4\",\"parentId\":null,\"timestamp\":\"2026-02-23T13:10:13.000Z\",\"message\":{{\"role\":\"user\",\"content\":[{{\"type\":\"text\",\"text\":\"child prompt\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"c1d2e3f4\",\"parentId\":\"b1c2d3e4\",\"timestamp\":\"2026-02-23T13:10:14.000Z\",\"message\":{{\"role\":\"assistant\",\"content\":[{{\"type\":\"text\",\"text\":\"child done\"}}]}}}}\n"
-        ),
-    )
-    .expect("write child");
-    temp
-}
-
-fn setup_opencode_subagent_tree() -> tempfile::TempDir {
-    let temp = tempdir().expect("tempdir");
-    let opencode_root = temp.path().join("opencode");
-    fs::create_dir_all(&opencode_root).expect("mkdir");
-    let db_path = opencode_root.join("opencode.db");
-
-    let conn = Connection::open(&db_path).expect("open sqlite");
-    conn.execute_batch(
-        "
-        CREATE TABLE session (
-            id TEXT PRIMARY KEY,
-            parent_id TEXT
-        );
-        CREATE TABLE message (
-            id TEXT PRIMARY KEY,
-            session_id TEXT NOT NULL,
-            time_created INTEGER NOT NULL,
-            data TEXT NOT NULL
-        );
-        CREATE TABLE part (
-            id TEXT PRIMARY KEY,
-            message_id TEXT NOT NULL,
-            session_id TEXT NOT NULL,
-            time_created INTEGER NOT NULL,
-            data TEXT NOT NULL
-        );
-        ",
-    )
-    .expect("create schema");
-
-    conn.execute(
-        "INSERT INTO session (id, parent_id) VALUES (?1, NULL)",
-        [OPENCODE_MAIN_SESSION_ID],
-    )
-    .expect("insert main session");
-    conn.execute(
-        "INSERT INTO session (id, parent_id) VALUES (?1, ?2)",
-        params![OPENCODE_CHILD_SESSION_ID, OPENCODE_MAIN_SESSION_ID],
-    )
-    .expect("insert child session");
-    conn.execute(
-        "INSERT INTO session (id, parent_id) VALUES (?1, ?2)",
-        params![OPENCODE_CHILD_EMPTY_SESSION_ID, OPENCODE_MAIN_SESSION_ID],
-    )
-    .expect("insert empty child session");
-
-    conn.execute(
-        "INSERT INTO message (id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4)",
-        params![
-            "main_msg_1",
-            OPENCODE_MAIN_SESSION_ID,
-            1_i64,
-            r#"{"role":"user","time":{"created":1}}"#
-        ],
-    )
-    .expect("insert main user");
-    conn.execute(
-        "INSERT INTO part (id, message_id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![
-            "main_part_1",
-            "main_msg_1",
-            OPENCODE_MAIN_SESSION_ID,
-            1_i64,
-            r#"{"type":"text","text":"main root prompt"}"#
-        ],
-    )
-    .expect("insert main user part");
-
-    conn.execute(
-        "INSERT INTO message (id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4)",
-        params![
-            "child_msg_1",
-            OPENCODE_CHILD_SESSION_ID,
-            2_i64,
-            r#"{"role":"user","time":{"created":2}}"#
-        ],
-    )
-    .expect("insert child user");
-    conn.execute(
-        "INSERT INTO part (id, message_id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![
-            "child_part_1",
-            "child_msg_1",
-            OPENCODE_CHILD_SESSION_ID,
-            2_i64,
-            r#"{"type":"text","text":"child asks for help"}"#
-        ],
-    )
-    .expect("insert child user part");
-
-    conn.execute(
-        "INSERT INTO message (id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4)",
-        params![
-            "child_msg_2",
-            OPENCODE_CHILD_SESSION_ID,
-            3_i64,
-            r#"{"role":"assistant","time":{"created":3,"completed":4}}"#
-        ],
-    )
-    .expect("insert child assistant");
-    conn.execute(
-        "INSERT INTO part (id, message_id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![
-            "child_part_2",
-            "child_msg_2",
-            OPENCODE_CHILD_SESSION_ID,
-            3_i64,
-            r#"{"type":"text","text":"child completed"}"#
-        ],
-    )
-    .expect("insert child assistant part");
-
-    temp
-}
 
 fn codex_real_fixture_root() -> PathBuf {
     PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/codex_real_sanitized")
@@ -744,14 +292,52 @@ fn default_outputs_markdown() {
         .assert()
         .success()
         .stdout(predicate::str::contains("---\n"))
-        .stdout(predicate::str::contains("uri: 'agents://codex/"))
-        .stdout(predicate::str::contains("thread_source: '"))
+        .stdout(predicate::str::contains("uri: agents://codex/"))
+        .stdout(predicate::str::contains("thread_source: "))
         .stdout(predicate::str::contains("# Thread"))
         .stdout(predicate::str::contains("## Timeline"))
         .stdout(predicate::str::contains("## 1. User"))
         .stdout(predicate::str::contains("hello"));
 }
 
+#[test]
+fn verbose_logs_resolution_steps_to_stderr() {
+    let temp = setup_codex_tree_with_sqlite_missing_threads();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .env_remove("XURL_LOG")
+        .arg(codex_uri())
+        .arg("-v")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("## 1. User"))
+        .stdout(predicate::str::contains("DEBUG").not())
+        .stderr(predicate::str::contains("state_dbs=1"))
+        .stderr(predicate::str::contains("codex sqlite index unreadable"))
+        .stderr(predicate::str::contains("candidates=1"))
+        .stderr(predicate::str::contains("source=codex:sessions"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .env("XURL_LOG", "xurl_core=debug")
+        .arg(codex_uri())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("source=codex:sessions"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .env_remove("XURL_LOG")
+        .arg(codex_uri())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("DEBUG").not());
+}
+
 #[test]
 fn output_flag_writes_markdown_to_file() {
     let temp = setup_codex_tree();
@@ -802,7 +388,7 @@ fn agents_uri_outputs_markdown() {
         .assert()
         .success()
         .stdout(predicate::str::contains(format!(
-            "uri: 'agents://codex/{SESSION_ID}'"
+            "uri: agents://codex/{SESSION_ID}"
         )))
         .stdout(predicate::str::contains("## 1. User"))
         .stdout(predicate::str::contains("hello"));
@@ -819,7 +405,7 @@ fn shorthand_uri_outputs_markdown() {
         .assert()
         .success()
         .stdout(predicate::str::contains(format!(
-            "uri: 'agents://codex/{SESSION_ID}'"
+            "uri: agents://codex/{SESSION_ID}"
         )))
         .stdout(predicate::str::contains("## 1. User"))
         .stdout(predicate::str::contains("hello"));
@@ -849,11 +435,11 @@ fn skills_local_head_outputs_frontmatter() {
         .arg("skills://xurl")
         .assert()
         .success()
-        .stdout(predicate::str::contains("kind: 'skill'"))
-        .stdout(predicate::str::contains("provider: 'skills'"))
-        .stdout(predicate::str::contains("source_kind: 'local'"))
-        .stdout(predicate::str::contains("source: '"))
-        .stdout(predicate::str::contains("resolved_path: 'xurl/SKILL.md'"));
+        .stdout(predicate::str::contains("kind: skill"))
+        .stdout(predicate::str::contains("provider: skills"))
+        .stdout(predicate::str::contains("source_kind: local"))
+        .stdout(predicate::str::contains("source: "))
+        .stdout(predicate::str::contains("resolved_path: xurl/SKILL.md"));
 }
 
 #[test]
@@ -931,8 +517,12 @@ fn skills_github_reports_candidate_uris_when_ambiguous() {
 }
 
 #[test]
-fn raw_flag_is_rejected() {
+fn raw_flag_outputs_provider_native_payload() {
     let temp = setup_codex_tree();
+    let thread_path = temp.path().join(format!(
+        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+    ));
+    let expected = fs::read_to_string(thread_path).expect("read rollout");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("CODEX_HOME", temp.path())
@@ -940,29 +530,286 @@ fn raw_flag_is_rejected() {
         .arg(codex_uri())
         .arg("--raw")
         .assert()
-        .failure()
-        .stderr(predicate::str::contains("unexpected argument '--raw'"));
-}
-
-#[test]
-fn amp_collection_query_outputs_markdown() {
-    let temp = setup_amp_tree();
+        .success()
+        .stdout(expected);
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("XDG_DATA_HOME", temp.path())
-        .arg("agents://amp?q=world&limit=1")
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("agents://codex?q=hello&limit=1")
+        .arg("--raw")
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Threads"))
-        .stdout(predicate::str::contains("- Limit: `1`"))
+        .stdout(predicate::str::starts_with("{\n  \"query\": {"))
+        .stdout(predicate::str::contains("\"provider\": \"codex\""))
         .stdout(predicate::str::contains(format!(
-            "agents://amp/{AMP_SESSION_ID}"
+            "\"thread_id\": \"{SESSION_ID}\""
         )))
-        .stdout(predicate::str::contains("- Match:"));
-}
+        .stdout(predicate::str::contains("# Threads").not());
 
-#[test]
-fn codex_collection_query_outputs_markdown() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(codex_uri())
+        .args(["--raw", "-I"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--raw prints the payload as stored",
+        ));
+}
+
+#[test]
+fn raw_flag_outputs_subagent_view_json() {
+    let temp = setup_codex_subagent_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(codex_subagent_uri())
+        .arg("--raw")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"kind\": \"detail\""))
+        .stdout(predicate::str::contains(format!(
+            "\"agent_id\": \"{SUBAGENT_ID}\""
+        )))
+        .stdout(predicate::str::contains("# Subagent Thread").not());
+}
+
+#[test]
+fn cat_streams_thread_and_subagent_files_as_stored() {
+    let temp = setup_codex_subagent_tree();
+    let read = |name: &str| {
+        fs::read_to_string(temp.path().join(format!("sessions/2026/02/23/{name}")))
+            .expect("read rollout")
+    };
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .args(["cat", &codex_uri()])
+        .assert()
+        .success()
+        .stdout(read(&format!(
+            "rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+        )));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .args(["cat", &codex_subagent_uri()])
+        .assert()
+        .success()
+        .stdout(read(&format!(
+            "rollout-2026-02-23T04-49-10-{SUBAGENT_ID}.jsonl"
+        )));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .args(["cat", "agents://codex"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cat requires a thread URI"));
+}
+
+#[test]
+fn cat_lines_and_tail_print_a_slice_of_the_file() {
+    let temp = setup_codex_subagent_tree();
+    let rollout = fs::read_to_string(temp.path().join(format!(
+        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+    )))
+    .expect("read rollout");
+    let lines = rollout.split_inclusive('\n').collect::<Vec<_>>();
+    let cat = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+        cmd.env("CODEX_HOME", temp.path())
+            .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+            .args(["cat", &codex_uri()])
+            .args(args)
+            .assert()
+    };
+
+    cat(&["--lines", "2:3"])
+        .success()
+        .stdout(lines[1..3].concat());
+    cat(&["--lines", "5:"])
+        .success()
+        .stdout(lines[4..].concat());
+    cat(&["--lines", "4"])
+        .success()
+        .stdout(lines[3].to_string());
+    cat(&["--tail", "2"])
+        .success()
+        .stdout(lines[lines.len() - 2..].concat());
+    cat(&["--tail", "100"]).success().stdout(rollout.clone());
+    cat(&["--lines", "3:2"])
+        .failure()
+        .stderr(predicate::str::contains("invalid --lines value: 3:2"));
+    cat(&["--lines", "1:2", "--tail", "1"])
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn cat_slice_never_reads_lines_outside_it() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[Message::user("first prompt")])
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .with_event(r#"{"timestamp":"2026-02-23T04:48:52Z","type":"response_item","payl"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"last reply"}]}}"#)
+        .build();
+    let cat = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+        cmd.env("CODEX_HOME", temp.path())
+            .args(["cat", &codex_uri(), "--strict"])
+            .args(args)
+            .assert()
+    };
+
+    // `--strict` rejects the corrupt third line only when something reads it.
+    cat(&[])
+        .failure()
+        .stderr(predicate::str::contains("line 3"));
+    cat(&["--lines", "1:2"])
+        .success()
+        .stdout(predicate::str::contains("first prompt"));
+    cat(&["--tail", "1"])
+        .success()
+        .stdout(predicate::str::contains("last reply"));
+}
+
+#[test]
+fn snapshot_is_readable_after_the_thread_is_deleted() {
+    let temp = setup_codex_tree();
+    let snapshots = temp.path().join("snapshots");
+    let thread_path = temp.path().join(format!(
+        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+    ));
+    let rollout = fs::read_to_string(&thread_path).expect("read rollout");
+    let xurl = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+        cmd.env("CODEX_HOME", temp.path())
+            .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+            .env("XURL_SNAPSHOTS_ROOT", &snapshots);
+        cmd
+    };
+
+    let output = xurl()
+        .args(["snapshot", &codex_uri()])
+        .output()
+        .expect("run xurl");
+    assert!(output.status.success());
+    let snapshot = String::from_utf8(output.stdout).expect("utf8");
+    let snapshot = snapshot.trim();
+    assert!(snapshot.starts_with("snapshot://"), "{snapshot}");
+    xurl()
+        .args(["snapshot", &codex_uri()])
+        .assert()
+        .success()
+        .stdout(format!("{snapshot}\n"));
+    fs::remove_file(&thread_path).expect("remove rollout");
+
+    xurl()
+        .arg(snapshot)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("uri: {snapshot}")))
+        .stdout(predicate::str::contains(format!(
+            "thread_uri: agents://codex/{SESSION_ID}"
+        )))
+        .stdout(predicate::str::contains("## 1. User"));
+    xurl()
+        .args([snapshot, "-I"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("created_at: "))
+        .stdout(predicate::str::contains("## 1. User").not());
+    xurl()
+        .args(["cat", snapshot])
+        .assert()
+        .success()
+        .stdout(rollout);
+    xurl()
+        .arg(format!("snapshot://{}", "0".repeat(64)))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("snapshot not found"))
+        .stderr(predicate::str::contains("xurl snapshot <URI>"));
+}
+
+#[test]
+fn file_uri_reads_a_rollout_outside_the_provider_roots() {
+    let temp = setup_codex_tree();
+    let artifact = tempdir().expect("tempdir");
+    let file = artifact
+        .path()
+        .join(format!("rollout copy {SESSION_ID}.jsonl"));
+    fs::copy(
+        temp.path().join(format!(
+            "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+        )),
+        &file,
+    )
+    .expect("copy rollout");
+    let uri = format!("file://{}", file.display()).replace(' ', "%20");
+    let xurl = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+        cmd.env("CODEX_HOME", artifact.path().join("missing-codex"))
+            .env("CLAUDE_CONFIG_DIR", artifact.path().join("missing-claude"));
+        cmd
+    };
+
+    xurl()
+        .args([&uri, "--as", "codex"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("provider: codex"))
+        .stdout(predicate::str::contains(format!(
+            "session_id: {SESSION_ID}"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "path: {}",
+            file.display()
+        )))
+        .stdout(predicate::str::contains("## 1. User"));
+    xurl()
+        .args([&uri, "--as", "codex", "-I"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("raw_bytes: "))
+        .stdout(predicate::str::contains("## 1. User").not());
+    xurl()
+        .arg(&uri)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("add --as <PROVIDER>"));
+    xurl()
+        .args([&codex_uri(), "--as", "codex"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--as only applies to file:// URIs",
+        ));
+}
+
+#[test]
+fn amp_collection_query_outputs_markdown() {
+    let temp = setup_amp_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .arg("agents://amp?q=world&limit=1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Threads"))
+        .stdout(predicate::str::contains("- Limit: `1`"))
+        .stdout(predicate::str::contains(format!(
+            "agents://amp/{AMP_SESSION_ID}"
+        )))
+        .stdout(predicate::str::contains("- Match:"));
+}
+
+#[test]
+fn codex_collection_query_outputs_markdown() {
     let temp = setup_codex_tree();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
@@ -1044,6 +891,202 @@ fn claude_collection_query_outputs_markdown() {
         .stdout(predicate::str::contains("- Match:"));
 }
 
+#[test]
+fn ps_lists_recently_written_threads_that_have_not_finished() {
+    let running_id = "3823d1df-720a-4c31-ac55-ae8ba726721f";
+    let stale_id = "4823d1df-720a-4c31-ac55-ae8ba726721f";
+    let user = [Message::user("fix the build")];
+    let tool_use = r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"cargo build"}}]}}"#;
+    let temp = ClaudeTreeBuilder::new()
+        .with_project("-Users-alice-repo")
+        .with_cwd("/Users/alice/repo")
+        .with_thread(CLAUDE_SESSION_ID, &user)
+        .with_event(tool_use)
+        .with_event(r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Fixed"}]}}"#)
+        .with_thread(running_id, &user)
+        .with_event(tool_use)
+        .with_thread(stale_id, &user)
+        .with_event(tool_use)
+        .build();
+    fs::File::options()
+        .write(true)
+        .open(
+            temp.path()
+                .join(format!("projects/-Users-alice-repo/{stale_id}.jsonl")),
+        )
+        .expect("open")
+        .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(3600))
+        .expect("set mtime");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg("ps")
+        .arg("claude")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("threads: 1"))
+        .stdout(predicate::str::contains(format!(
+            "## 1. `agents://claude/{running_id}`\n\n- Provider: `claude`\n- Status: `running`\n- Workdir: `/Users/alice/repo`\n"
+        )))
+        .stdout(predicate::str::contains(stale_id).not())
+        .stdout(predicate::str::contains(CLAUDE_SESSION_ID).not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg("ps")
+        .arg("claude")
+        .arg("--within")
+        .arg("2h")
+        .arg("--json")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "\"uri\": \"agents://claude/{stale_id}\""
+        )))
+        .stdout(predicate::str::contains("\"status\": \"running\""))
+        .stdout(predicate::str::contains("\"pid\": null"));
+}
+
+#[test]
+fn claude_collection_query_filters_by_status() {
+    let running_id = "3823d1df-720a-4c31-ac55-ae8ba726721f";
+    let user = [Message::user("fix the build")];
+    let tool_use = r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"cargo build"}}]}}"#;
+    let temp = ClaudeTreeBuilder::new()
+        .with_project("-Users-alice-repo")
+        .with_thread(CLAUDE_SESSION_ID, &user)
+        .with_event(tool_use)
+        .with_event(r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Fixed"}]}}"#)
+        .with_thread(running_id, &user)
+        .with_event(tool_use)
+        .build();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg("agents://claude?status=running")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("- Status: `running`"))
+        .stdout(predicate::str::contains(format!(
+            "agents://claude/{running_id}"
+        )))
+        .stdout(predicate::str::contains(CLAUDE_SESSION_ID).not())
+        .stdout(predicate::str::contains("- Matched: `1`"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg(format!("agents://claude/{CLAUDE_SESSION_ID}"))
+        .arg("-I")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("status: completed\n"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg("agents://claude?status=stuck")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid status=stuck; expected running, completed, failed, or unknown",
+        ));
+}
+
+#[test]
+fn claude_collection_query_filters_by_workdir_and_repo() {
+    let temp = tempdir().expect("tempdir");
+    let repo = temp.path().join("widgets");
+    let other = temp.path().join("scratch");
+    fs::create_dir_all(repo.join(".git")).expect("mkdir");
+    fs::create_dir_all(repo.join("src")).expect("mkdir");
+    fs::create_dir_all(&other).expect("mkdir");
+    fs::write(
+        repo.join(".git/config"),
+        "[remote \"origin\"]\n\turl = git@github.com:acme/widgets.git\n",
+    )
+    .expect("write config");
+
+    let other_session_id = "3823d1df-720a-4c31-ac55-ae8ba726721f";
+    ClaudeTreeBuilder::new()
+        .with_project("-tmp-mixed")
+        .with_cwd(&repo.join("src").display().to_string())
+        .with_thread(CLAUDE_SESSION_ID, &[Message::user("fix it")])
+        .with_cwd(&other.display().to_string())
+        .with_thread(other_session_id, &[Message::user("fix it")])
+        .write_to(temp.path());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg(format!("agents://claude?workdir={}", repo.display()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "- Workdir: `{}`",
+            repo.display()
+        )))
+        .stdout(predicate::str::contains(CLAUDE_SESSION_ID))
+        .stdout(predicate::str::contains(other_session_id).not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg("agents://claude?repo=Acme/widgets")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("- Repo: `Acme/widgets`"))
+        .stdout(predicate::str::contains(CLAUDE_SESSION_ID))
+        .stdout(predicate::str::contains(other_session_id).not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg("agents://claude?repo=acme/gadgets")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("- Matched: `0`"));
+}
+
+#[test]
+fn claude_collection_query_filters_by_project() {
+    let temp = tempdir().expect("tempdir");
+    let other_session_id = "3823d1df-720a-4c31-ac55-ae8ba726721f";
+    for (dir, session_id) in [
+        ("-Users-alice-my-repo", CLAUDE_SESSION_ID),
+        ("-Users-alice-other", other_session_id),
+    ] {
+        let project = temp.path().join("projects").join(dir);
+        fs::create_dir_all(&project).expect("mkdir");
+        fs::write(
+            project.join(format!("{session_id}.jsonl")),
+            format!(
+                "{{\"type\":\"user\",\"sessionId\":\"{session_id}\",\"message\":{{\"role\":\"user\",\"content\":\"hello\"}}}}\n"
+            ),
+        )
+        .expect("write");
+    }
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg("agents://claude?project=%2FUsers%2Falice%2Fmy_repo%2F")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("project: /Users/alice/my_repo/"))
+        .stdout(predicate::str::contains(format!(
+            "agents://claude/{CLAUDE_SESSION_ID}"
+        )))
+        .stdout(predicate::str::contains(other_session_id).not());
+}
+
+#[test]
+fn project_query_is_ignored_for_other_providers() {
+    let temp = setup_codex_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("agents://codex?project=%2Ftmp")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ignored query parameter: project"))
+        .stdout(predicate::str::contains(SESSION_ID));
+}
+
 #[test]
 fn gemini_collection_query_outputs_markdown() {
     let temp = setup_gemini_tree();
@@ -1106,1141 +1149,4317 @@ fn collection_query_not_found_outputs_empty_list() {
 }
 
 #[test]
-fn head_flag_outputs_frontmatter_only() {
-    let temp = setup_codex_tree();
+fn collection_query_sort_relevance_ranks_by_score() {
+    let dense_id = "019c871c-0000-7000-8000-000000000001";
+    let sparse_id = "019c871c-0000-7000-8000-000000000002";
+    let temp = CodexTreeBuilder::new()
+        .with_thread(
+            sparse_id,
+            &[
+                Message::user("deploy the service and then write a long report about it"),
+                Message::assistant("done with the report, nothing else to add here"),
+            ],
+        )
+        .with_thread(
+            dense_id,
+            &[
+                Message::user("deploy failed"),
+                Message::assistant("retry the deploy"),
+            ],
+        )
+        .build();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    let output = cmd
+        .env("CODEX_HOME", temp.path())
+        .arg("agents://codex?q=deploy&sort=relevance")
+        .output()
+        .expect("run xurl");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf8");
+    assert!(stdout.contains("sort: relevance\n"), "{stdout}");
+    assert!(stdout.contains("- Sort: `relevance`\n"), "{stdout}");
+    assert!(stdout.contains("- Score: `"), "{stdout}");
+    let dense = stdout
+        .find(&format!("## 1. `agents://codex/{dense_id}`"))
+        .expect("dense thread ranks first");
+    let sparse = stdout
+        .find(&format!("## 2. `agents://codex/{sparse_id}`"))
+        .expect("sparse thread ranks second");
+    assert!(dense < sparse);
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("CODEX_HOME", temp.path())
-        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(codex_uri())
-        .arg("-I")
+        .arg("agents://codex?sort=relevance")
         .assert()
         .success()
-        .stdout(predicate::str::contains("---\n"))
-        .stdout(predicate::str::contains("mode: 'subagent_index'"))
-        .stdout(predicate::str::contains("subagents:"))
-        .stdout(predicate::str::contains("# Thread").not());
+        .stdout(predicate::str::contains(
+            "sort=relevance requires words in q to rank; sorted by recency",
+        ))
+        .stdout(predicate::str::contains("- Score:").not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("agents://codex?q=deploy&sort=newest")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid sort=newest; expected relevance or recency",
+        ));
 }
 
 #[test]
-fn codex_subagent_head_outputs_header_only() {
-    let temp = setup_codex_subagent_tree();
+fn collection_query_cursor_pages_through_matches() {
+    let ids = [
+        "019c871c-0000-7000-8000-000000000001",
+        "019c871c-0000-7000-8000-000000000002",
+        "019c871c-0000-7000-8000-000000000003",
+    ];
+    let mut builder = CodexTreeBuilder::new();
+    for id in ids {
+        builder = builder.with_thread(id, &[Message::user("deploy the service")]);
+    }
+    let temp = builder.build();
+    let query = |uri: &str| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+        let output = cmd
+            .env("CODEX_HOME", temp.path())
+            .arg(uri)
+            .output()
+            .expect("run xurl");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).expect("utf8")
+    };
+
+    let first = query("agents://codex?q=deploy&limit=2");
+    assert!(first.contains("## 2. `"), "{first}");
+    let head = parse_frontmatter(&first)
+        .expect("valid frontmatter")
+        .expect("frontmatter");
+    assert_eq!(head["limit"], 2, "{first}");
+    assert_eq!(head["threads"].as_array().map(Vec::len), Some(2), "{first}");
+    let cursor = head["next_cursor"]
+        .as_str()
+        .expect("first page has a next cursor")
+        .to_string();
+    assert!(
+        first.contains(&format!("- Next Cursor: `{cursor}`")),
+        "{first}"
+    );
+
+    let second = query(&format!("agents://codex?q=deploy&limit=2&cursor={cursor}"));
+    assert!(second.contains("offset: 2\n"), "{second}");
+    assert!(second.contains("## 3. `"), "{second}");
+    assert!(!second.contains("next_cursor:"), "{second}");
+    assert!(
+        ids.iter()
+            .all(|id| first.contains(id) != second.contains(id)),
+        "pages must not overlap and must cover every match"
+    );
+    let by_offset = query("agents://codex?q=deploy&limit=2&offset=2");
+    assert!(
+        ids.iter()
+            .all(|id| second.contains(id) == by_offset.contains(id))
+    );
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("CODEX_HOME", temp.path())
-        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(codex_subagent_uri())
-        .arg("--head")
+        .arg(format!("agents://codex?q=service&limit=2&cursor={cursor}"))
         .assert()
-        .success()
-        .stdout(predicate::str::contains("mode: 'subagent_detail'"))
-        .stdout(predicate::str::contains(format!(
-            "agent_id: '{SUBAGENT_ID}'"
-        )))
-        .stdout(predicate::str::contains("status:"))
-        .stdout(predicate::str::contains("# Subagent Thread").not());
+        .failure()
+        .stderr(predicate::str::contains("does not belong to this query"));
 }
 
 #[test]
-fn codex_deeplink_outputs_markdown() {
-    let temp = setup_codex_tree();
+fn collection_query_supports_field_and_regex_terms() {
+    let tool_id = "019c871c-0000-7000-8000-000000000001";
+    let chat_id = "019c871c-0000-7000-8000-000000000002";
+    let temp = CodexTreeBuilder::new()
+        .with_thread(tool_id, &[Message::user("look at the deadlock")])
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call","name":"read_file","arguments":"{\"path\":\"src/sched/mod.rs\"}","call_id":"call_1"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"fn tick() {}"}}"#)
+        .with_thread(
+            chat_id,
+            &[
+                Message::user("what is a deadline?"),
+                Message::assistant("A deadlock is different."),
+            ],
+        )
+        .with_session_meta("/work/app", "2026-02-23T04:49:50Z")
+        .build();
+    let query = |uri: &str| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+        let output = cmd
+            .env("CODEX_HOME", temp.path())
+            .arg(uri)
+            .output()
+            .expect("run xurl");
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).expect("utf8")
+    };
+    let found = |stdout: &str| {
+        [tool_id, chat_id]
+            .into_iter()
+            .filter(|id| stdout.contains(&format!("thread_id: {id}")))
+            .collect::<Vec<_>>()
+    };
+
+    let stdout = query("agents://codex?q=role:user+deadlock");
+    assert_eq!(found(&stdout), vec![tool_id], "{stdout}");
+    assert!(stdout.contains("matched_preview: look at the deadlock"));
+    let stdout = query("agents://codex?q=tool:read_file+path:src/**");
+    assert_eq!(found(&stdout), vec![tool_id], "{stdout}");
+    let stdout = query("agents://codex?q=path:lib/**");
+    assert_eq!(found(&stdout), Vec::<&str>::new(), "{stdout}");
+    let stdout = query("agents://codex?q=role:user+/dead(line|lock)/");
+    assert_eq!(found(&stdout), vec![tool_id, chat_id], "{stdout}");
+    assert!(!stdout.contains("score:"), "{stdout}");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("CODEX_HOME", temp.path())
-        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(codex_deeplink_uri())
+        .arg(format!("agents://codex/{tool_id}"))
+        .args(["--grep", "tool:read_file"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Thread"))
-        .stdout(predicate::str::contains("## 1. User"))
-        .stdout(predicate::str::contains("hello"));
-}
+        .stdout(predicate::str::contains(
+            "## Tool Call Matches\n\n### 1. Tool: read_file\n\n```text\n1: read_file\n",
+        ))
+        .stdout(predicate::str::contains("Timeline Matches").not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("agents://codex?q=role:system")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid search term `role:system`: expected role:user or role:assistant",
+        ));
+}
 
 #[test]
-fn agents_codex_deeplink_outputs_markdown() {
+fn last_outputs_most_recent_thread_uri() {
     let temp = setup_codex_tree();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("CODEX_HOME", temp.path())
-        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(agents_codex_deeplink_uri())
+        .arg("last")
+        .arg("codex")
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Thread"))
-        .stdout(predicate::str::contains("## 1. User"))
-        .stdout(predicate::str::contains("hello"));
+        .stdout(format!("agents://codex/{SESSION_ID}\n"));
 }
 
 #[test]
-fn codex_subagent_outputs_markdown_view() {
-    let temp = setup_codex_subagent_tree();
-    let main_uri = agents_uri("codex", SESSION_ID);
-    let subagent_uri = agents_child_uri("codex", SESSION_ID, SUBAGENT_ID);
+fn last_scans_all_providers_when_provider_is_omitted() {
+    let temp = setup_codex_tree();
+    let home = tempdir().expect("tempdir");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("CODEX_HOME", temp.path())
-        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(codex_subagent_uri())
+    cmd.env("HOME", home.path())
+        .env_remove("XDG_DATA_HOME")
+        .env_remove("CLAUDE_CONFIG_DIR")
+        .env_remove("GEMINI_CLI_HOME")
+        .env_remove("PI_CODING_AGENT_DIR")
+        .env("CODEX_HOME", temp.path())
+        .arg("last")
+        .arg("-n")
+        .arg("5")
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Subagent Thread"))
-        .stdout(predicate::str::contains(format!(
-            "- Main Thread: `{main_uri}`"
-        )))
-        .stdout(predicate::str::contains(format!(
-            "- Subagent Thread: `{subagent_uri}`"
-        )))
-        .stdout(predicate::str::contains("## Lifecycle (Parent Thread)"))
-        .stdout(predicate::str::contains("## Thread Excerpt (Child Thread)"));
+        .stdout(format!("agents://codex/{SESSION_ID}\n"));
 }
 
 #[test]
-fn agents_codex_subagent_outputs_markdown_view() {
-    let temp = setup_codex_subagent_tree();
-    let main_uri = agents_uri("codex", SESSION_ID);
-    let subagent_uri = agents_child_uri("codex", SESSION_ID, SUBAGENT_ID);
+fn sync_writes_vault_notes_and_rewrites_only_changed_threads() {
+    let temp = setup_codex_tree();
+    let vault = tempdir().expect("tempdir");
+    let note = vault.path().join(format!("codex/{SESSION_ID}.md"));
+    let sync = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+        cmd.env("CODEX_HOME", temp.path())
+            .args(["sync", "--provider", "codex", "--vault"])
+            .arg(vault.path())
+            .assert()
+            .success()
+    };
+
+    sync()
+        .stdout(format!("{}\n", note.display()))
+        .stderr(predicate::str::contains(
+            "1 created, 0 updated, 0 unchanged",
+        ));
+    let first = fs::read_to_string(&note).expect("read note");
+    assert!(first.starts_with(&format!("---\nuri: agents://codex/{SESSION_ID}\n")));
+    assert!(first.contains("## 1. User\n\nhello"));
+
+    sync().stdout("").stderr(predicate::str::contains(
+        "0 created, 0 updated, 1 unchanged",
+    ));
+
+    CodexTreeBuilder::new()
+        .with_thread(
+            SESSION_ID,
+            &[
+                Message::user("hello"),
+                Message::assistant("world"),
+                Message::user("one more thing"),
+            ],
+        )
+        .write_to(temp.path());
+
+    sync().stderr(predicate::str::contains(
+        "0 created, 1 updated, 0 unchanged",
+    ));
+    assert!(
+        fs::read_to_string(&note)
+            .expect("read note")
+            .contains("one more thing")
+    );
+}
+
+#[test]
+fn export_db_writes_threads_messages_and_tool_calls() {
+    let temp = setup_codex_tree();
+    let out = tempdir().expect("tempdir");
+    let db = out.path().join("threads.db");
+    let export = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+        cmd.env("CODEX_HOME", temp.path())
+            .args(["export-db", "--provider", "codex", "--out"])
+            .arg(&db)
+            .assert()
+            .success()
+            .stdout(format!("{}\n", db.display()))
+            .stderr(predicate::str::contains(
+                "exported 1 threads, 2 messages, 0 tool calls",
+            ));
+    };
+
+    // A second export refreshes the thread's rows instead of duplicating them.
+    export();
+    export();
+
+    let conn = Connection::open(&db).expect("open db");
+    let threads = conn
+        .query_row("SELECT uri, provider, session_id FROM threads", [], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .expect("thread row");
+    assert_eq!(
+        threads,
+        (
+            agents_codex_uri(),
+            "codex".to_string(),
+            SESSION_ID.to_string()
+        )
+    );
+    let mut stmt = conn
+        .prepare("SELECT seq, role, text FROM messages WHERE thread_uri = ?1 ORDER BY seq")
+        .expect("prepare");
+    let messages = stmt
+        .query_map(params![agents_codex_uri()], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .expect("query")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("rows");
+    assert_eq!(
+        messages,
+        vec![
+            (1, "user".to_string(), "hello".to_string()),
+            (2, "assistant".to_string(), "world".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn last_render_outputs_newest_thread_markdown() {
+    let temp = setup_codex_tree();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("CODEX_HOME", temp.path())
         .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(agents_codex_subagent_uri())
+        .arg("last")
+        .arg("codex")
+        .arg("--render")
         .assert()
         .success()
         .stdout(predicate::str::contains(format!(
-            "- Main Thread: `{main_uri}`"
+            "uri: agents://codex/{SESSION_ID}"
         )))
-        .stdout(predicate::str::contains(format!(
-            "- Subagent Thread: `{subagent_uri}`"
-        )));
+        .stdout(predicate::str::contains("## 1. User"))
+        .stdout(predicate::str::contains("hello"));
 }
 
 #[test]
-fn codex_outputs_no_warning_text_for_markdown() {
-    let temp = setup_codex_tree_with_sqlite_missing_threads();
+fn last_returns_error_when_provider_has_no_threads() {
+    let temp = tempdir().expect("tempdir");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("CODEX_HOME", temp.path())
-        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(codex_uri())
+        .arg("last")
+        .arg("codex")
         .assert()
-        .success()
-        .stderr(predicate::str::contains("warning:").not());
+        .failure()
+        .stderr(predicate::str::contains(
+            "no threads found for provider=codex",
+        ));
 }
 
 #[test]
-fn codex_subagent_outputs_no_warning_text_for_markdown() {
-    let temp = setup_codex_subagent_tree_with_sqlite_missing_threads();
+fn last_rejects_unknown_provider() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.arg("last")
+        .arg("unknown")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error:"));
+}
+
+#[test]
+fn latest_alias_reads_most_recent_thread() {
+    let temp = setup_codex_tree();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("CODEX_HOME", temp.path())
         .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(codex_subagent_uri())
+        .arg("agents://codex/@latest")
         .assert()
         .success()
-        .stderr(predicate::str::contains("warning:").not());
+        .stdout(predicate::str::contains(format!(
+            "uri: agents://codex/{SESSION_ID}"
+        )))
+        .stdout(predicate::str::contains("hello"));
 }
 
 #[test]
-fn codex_real_fixture_head_includes_subagents() {
-    let fixture_root = codex_real_fixture_root();
-    assert!(fixture_root.exists(), "fixture root must exist");
-    let subagent_uri = agents_child_uri("codex", REAL_FIXTURE_MAIN_ID, REAL_FIXTURE_AGENT_ID);
+fn latest_alias_with_offset_selects_previous_thread() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(
+            SESSION_ID,
+            &[Message::user("hello"), Message::assistant("world")],
+        )
+        .with_thread(SUBAGENT_ID, &[Message::user("older")])
+        .with_session_meta("/work/app", "2026-02-22T04:48:50Z")
+        .build();
+    let older_path = temp.path().join(format!(
+        "sessions/2026/02/22/rollout-2026-02-22T04-48-50-{SUBAGENT_ID}.jsonl"
+    ));
+    let older = fs::File::options()
+        .write(true)
+        .open(&older_path)
+        .expect("open");
+    older
+        .set_modified(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(60))
+        .expect("set mtime");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("CODEX_HOME", fixture_root)
-        .env("CLAUDE_CONFIG_DIR", "/tmp/missing-claude")
-        .arg(format!("codex://{REAL_FIXTURE_MAIN_ID}"))
-        .arg("--head")
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("-I")
+        .arg("codex/@latest~1")
         .assert()
         .success()
-        .stdout(predicate::str::contains("mode: 'subagent_index'"))
-        .stdout(predicate::str::contains("subagents:"))
-        .stdout(predicate::str::contains(subagent_uri))
-        .stdout(predicate::str::contains("# Subagent Status").not());
+        .stdout(predicate::str::contains(format!(
+            "uri: agents://codex/{SUBAGENT_ID}"
+        )));
 }
 
 #[test]
-fn codex_real_fixture_subagent_detail_outputs_markdown() {
-    let fixture_root = codex_real_fixture_root();
-    assert!(fixture_root.exists(), "fixture root must exist");
+fn latest_shortcut_filters_by_cwd() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[Message::user("hello")])
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .with_thread(SUBAGENT_ID, &[Message::user("hello")])
+        .with_session_meta("/work/lib", "2026-02-23T04:48:50Z")
+        .build();
+    for (session_id, mtime) in [(SESSION_ID, 120), (SUBAGENT_ID, 60)] {
+        let path = temp.path().join(format!(
+            "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{session_id}.jsonl"
+        ));
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .expect("open")
+            .set_modified(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(mtime))
+            .expect("set mtime");
+    }
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("CODEX_HOME", fixture_root)
-        .env("CLAUDE_CONFIG_DIR", "/tmp/missing-claude")
-        .arg(format!(
-            "codex://{REAL_FIXTURE_MAIN_ID}/{REAL_FIXTURE_AGENT_ID}"
-        ))
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("-I")
+        .arg("agents://codex/latest")
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Subagent Thread"))
-        .stdout(predicate::str::contains("## Lifecycle (Parent Thread)"));
-}
+        .stdout(predicate::str::contains(format!(
+            "uri: agents://codex/{SESSION_ID}"
+        )));
 
-#[test]
-fn list_flag_is_rejected() {
-    let temp = setup_codex_subagent_tree();
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("-I")
+        .arg("codex/@last?cwd=%2Fwork%2Flib")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "uri: agents://codex/{SUBAGENT_ID}"
+        )));
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("CODEX_HOME", temp.path())
-        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(codex_subagent_uri())
-        .arg("--list")
+        .arg("-I")
+        .arg("codex/latest?cwd=%2Fwork%2Fother")
         .assert()
         .failure()
-        .stderr(predicate::str::contains("unexpected argument '--list'"));
+        .stderr(predicate::str::contains(
+            "thread not found for provider=codex session_id=latest",
+        ));
 }
 
 #[test]
-fn missing_thread_returns_non_zero() {
+fn workspace_dot_renders_latest_thread_started_in_current_dir() {
     let temp = tempdir().expect("tempdir");
+    let work = fs::canonicalize(temp.path()).expect("canonicalize");
+    let app = work.join("app");
+    let lib = work.join("lib");
+    fs::create_dir_all(&app).expect("mkdir app");
+    fs::create_dir_all(&lib).expect("mkdir lib");
+    let codex_home = work.join("codex");
+    let mut builder = CodexTreeBuilder::new();
+    for (session_id, cwd) in [(SESSION_ID, &app), (SUBAGENT_ID, &lib)] {
+        builder = builder
+            .with_thread(
+                session_id,
+                &[Message::user(format!("hello from {session_id}"))],
+            )
+            .with_session_meta(&cwd.display().to_string(), "2026-02-23T04:48:50Z");
+    }
+    builder.write_to(&codex_home);
+    for (session_id, mtime) in [(SESSION_ID, 60), (SUBAGENT_ID, 120)] {
+        let path = codex_home.join(format!(
+            "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{session_id}.jsonl"
+        ));
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .expect("open")
+            .set_modified(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(mtime))
+            .expect("set mtime");
+    }
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("CODEX_HOME", temp.path())
-        .env("CLAUDE_CONFIG_DIR", temp.path())
-        .arg(codex_uri())
+    cmd.env("CODEX_HOME", &codex_home)
+        .current_dir(&app)
+        .arg(".")
+        .arg("--provider")
+        .arg("codex")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "uri: agents://codex/{SESSION_ID}"
+        )))
+        .stdout(predicate::str::contains(format!("hello from {SESSION_ID}")));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", &codex_home)
+        .current_dir(&lib)
+        .arg("-I")
+        .arg(".")
+        .arg("--provider")
+        .arg("codex")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "uri: agents://codex/{SUBAGENT_ID}"
+        )));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", &codex_home)
+        .current_dir(&work)
+        .arg(".")
+        .arg("--provider")
+        .arg("claude")
+        .env("CLAUDE_CONFIG_DIR", work.join("missing-claude"))
         .assert()
         .failure()
-        .stderr(predicate::str::contains("thread not found"));
+        .stderr(predicate::str::contains(format!(
+            "no threads found for provider=claude in {}",
+            work.display()
+        )));
 }
 
 #[test]
-fn amp_outputs_markdown() {
-    let temp = setup_amp_tree();
-
+fn provider_flag_requires_workspace_dot() {
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("XDG_DATA_HOME", temp.path())
-        .env("CODEX_HOME", temp.path().join("missing-codex"))
-        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(amp_uri())
+    cmd.arg("agents://codex/latest")
+        .arg("--provider")
+        .arg("codex")
         .assert()
-        .success()
-        .stdout(predicate::str::contains("# Thread"))
-        .stdout(predicate::str::contains("## 1. User"))
-        .stdout(predicate::str::contains("hello"))
-        .stdout(predicate::str::contains("analyze"))
-        .stdout(predicate::str::contains("world"));
+        .failure()
+        .stderr(predicate::str::contains(
+            "--provider is only supported with `xurl .`",
+        ));
 }
 
 #[test]
-fn amp_head_outputs_subagent_index() {
-    let temp = setup_amp_subagent_tree();
-    let subagent_uri = agents_child_uri("amp", AMP_SESSION_ID, AMP_SUBAGENT_ID);
+fn blame_prints_threads_whose_tool_output_mentions_commit() {
+    let repo = tempdir().expect("tempdir");
+    let commit = |message: &str| {
+        fs::write(repo.path().join("notes.txt"), message).expect("write");
+        run_git_cmd(&["add".to_string(), ".".to_string()], repo.path());
+        run_git_cmd(
+            &[
+                "-c".to_string(),
+                "user.name=Test".to_string(),
+                "-c".to_string(),
+                "user.email=test@example.com".to_string(),
+                "commit".to_string(),
+                "-m".to_string(),
+                message.to_string(),
+            ],
+            repo.path(),
+        );
+        let output = std::process::Command::new("git")
+            .current_dir(repo.path())
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .expect("rev-parse");
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+    run_git_cmd(&["init".to_string()], repo.path());
+    let sha = commit("add notes");
+
+    let mut builder = CodexTreeBuilder::new();
+    for (session_id, output) in [
+        (SESSION_ID, format!("[main {}] add notes", &sha[..7])),
+        (SUBAGENT_ID, "nothing to commit".to_string()),
+    ] {
+        builder = builder
+            .with_thread(session_id, &[])
+            .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+            .with_event(r#"{"type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"git\",\"commit\"]}","call_id":"call_1"}}"#)
+            .with_event(format!(
+                r#"{{"type":"response_item","payload":{{"type":"function_call_output","call_id":"call_1","output":"{output}"}}}}"#
+            ));
+    }
+    let codex_home = builder.build();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("XDG_DATA_HOME", temp.path())
-        .env("CODEX_HOME", temp.path().join("missing-codex"))
-        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(agents_uri("amp", AMP_SESSION_ID))
-        .arg("--head")
+    cmd.env("CODEX_HOME", codex_home.path())
+        .current_dir(repo.path())
+        .arg("blame")
+        .arg("HEAD")
+        .arg("--provider")
+        .arg("codex")
         .assert()
         .success()
-        .stdout(predicate::str::contains("mode: 'subagent_index'"))
-        .stdout(predicate::str::contains("subagents:"))
-        .stdout(predicate::str::contains(subagent_uri))
-        .stdout(predicate::str::contains("# Subagent Status").not());
-}
+        .stdout(format!("agents://codex/{SESSION_ID}\n"));
 
-#[test]
-fn amp_head_discovery_supports_missing_role_fallback() {
-    let temp = setup_amp_subagent_tree_missing_role();
-    let subagent_uri = agents_child_uri("amp", AMP_SESSION_ID, AMP_SUBAGENT_ID);
+    let newer = commit("edit notes");
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", codex_home.path())
+        .current_dir(repo.path())
+        .arg("blame")
+        .arg("HEAD")
+        .arg("--provider")
+        .arg("codex")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "no threads found for commit={newer}"
+        )));
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("XDG_DATA_HOME", temp.path())
-        .env("CODEX_HOME", temp.path().join("missing-codex"))
-        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(agents_uri("amp", AMP_SESSION_ID))
-        .arg("--head")
+    cmd.env("CODEX_HOME", codex_home.path())
+        .current_dir(repo.path())
+        .arg("blame")
+        .arg("no-such-ref")
         .assert()
-        .success()
-        .stdout(predicate::str::contains("mode: 'subagent_index'"))
-        .stdout(predicate::str::contains("subagents:"))
-        .stdout(predicate::str::contains(subagent_uri));
+        .failure()
+        .stderr(predicate::str::contains("git command failed"))
+        .stderr(predicate::str::contains(
+            "hint: run `xurl blame` inside the git repository",
+        ));
 }
 
 #[test]
-fn amp_subagent_head_outputs_header_only() {
-    let temp = setup_amp_subagent_tree();
+fn links_lists_files_urls_and_commits() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .with_event(r#"{"type":"response_item","payload":{"type":"custom_tool_call","name":"apply_patch","input":"*** Begin Patch\n*** Update File: src/lib.rs\n*** End Patch","call_id":"call_1"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"custom_tool_call_output","call_id":"call_1","output":"Success"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"git\",\"commit\",\"-m\",\"fix\"]}","call_id":"call_2"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call_output","call_id":"call_2","output":"[main 1a2b3c4] fix"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"Done, see https://example.com/pr/1."}]}}"#)
+        .build();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("XDG_DATA_HOME", temp.path())
-        .env("CODEX_HOME", temp.path().join("missing-codex"))
-        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(amp_subagent_uri())
-        .arg("--head")
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("links")
+        .arg(agents_codex_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: links"))
+        .stdout(predicate::str::contains("## Files\n\n- `src/lib.rs`\n"))
+        .stdout(predicate::str::contains(
+            "## URLs\n\n- `https://example.com/pr/1`\n",
+        ))
+        .stdout(predicate::str::contains("## Commits\n\n- `1a2b3c4`\n"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("links")
+        .arg("--json")
+        .arg(agents_codex_uri())
         .assert()
         .success()
-        .stdout(predicate::str::contains("mode: 'subagent_detail'"))
         .stdout(predicate::str::contains(format!(
-            "agent_id: '{AMP_SUBAGENT_ID}'"
+            "\"uri\": \"agents://codex/{SESSION_ID}\""
         )))
-        .stdout(predicate::str::contains("status:"))
-        .stdout(predicate::str::contains("# Subagent Thread").not());
+        .stdout(predicate::str::contains(
+            "\"files\": [\n    \"src/lib.rs\"\n  ]",
+        ))
+        .stdout(predicate::str::contains(
+            "\"commits\": [\n    \"1a2b3c4\"\n  ]",
+        ));
 }
 
 #[test]
-fn amp_subagent_outputs_markdown_view() {
-    let temp = setup_amp_subagent_tree();
-    let main_uri = agents_uri("amp", AMP_SESSION_ID);
-    let subagent_uri = agents_child_uri("amp", AMP_SESSION_ID, AMP_SUBAGENT_ID);
+fn findings_extracts_review_findings_as_json_and_sarif() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"review the diff"}]}}"#)
+        .with_event(r###"{"type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"## Critical\n\n- src/lib.rs:42 unwraps a user-supplied value.\n\n## Nits\n\n- README.md line 3 has a typo."}]}}"###)
+        .build();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("XDG_DATA_HOME", temp.path())
-        .env("CODEX_HOME", temp.path().join("missing-codex"))
-        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
-        .arg(agents_amp_subagent_uri())
+    cmd.env("CODEX_HOME", temp.path())
+        .args(["findings", &agents_codex_uri()])
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Subagent Thread"))
-        .stdout(predicate::str::contains(format!(
-            "- Main Thread: `{main_uri}`"
-        )))
-        .stdout(predicate::str::contains(format!(
-            "- Subagent Thread: `{subagent_uri}`"
-        )))
-        .stdout(predicate::str::contains("- Relation: `validated`"))
-        .stdout(predicate::str::contains("## Lifecycle (Parent Thread)"))
-        .stdout(predicate::str::contains("## Thread Excerpt (Child Thread)"));
+        .stdout(predicate::str::contains(
+            "\"path\": \"src/lib.rs\",\n      \"line\": 42,\n      \"severity\": \"error\"",
+        ))
+        .stdout(predicate::str::contains(
+            "\"path\": \"README.md\",\n      \"line\": 3,\n      \"severity\": \"note\"",
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .args(["findings", &agents_codex_uri(), "--format", "sarif"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"version\": \"2.1.0\""))
+        .stdout(predicate::str::contains("\"level\": \"error\""))
+        .stdout(predicate::str::contains("\"uri\": \"src/lib.rs\""))
+        .stdout(predicate::str::contains("\"startLine\": 42"));
 }
 
 #[test]
-fn gemini_outputs_markdown() {
-    let temp = setup_gemini_tree();
+fn resolve_prints_canonical_uri_and_thread_file() {
+    let temp = setup_codex_tree();
+    let expected_path = temp.path().join(format!(
+        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+    ));
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("GEMINI_CLI_HOME", temp.path())
-        .arg(gemini_uri())
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("resolve")
+        .arg(codex_uri())
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Thread"))
-        .stdout(predicate::str::contains("## 1. User"))
-        .stdout(predicate::str::contains("hello"))
-        .stdout(predicate::str::contains("world"));
+        .stdout(predicate::str::contains(format!(
+            "uri: agents://codex/{SESSION_ID}\n"
+        )))
+        .stdout(predicate::str::contains("mode: resolve\n"))
+        .stdout(predicate::str::contains(format!(
+            "path: {}\n",
+            expected_path.display()
+        )))
+        .stdout(predicate::str::contains("source: codex:sessions\n"))
+        .stdout(predicate::str::contains("candidate_count: 1\n"))
+        .stdout(predicate::str::contains("# Thread").not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("resolve")
+        .arg(format!("agents://codex/{SESSION_ID}/{SUBAGENT_ID}"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "resolve requires a main thread URI",
+        ));
 }
 
+#[cfg(unix)]
 #[test]
-fn gemini_head_outputs_subagent_discovery() {
-    let temp = setup_gemini_subagent_tree();
-    let main_uri = agents_uri("gemini", GEMINI_SESSION_ID);
-    let child_uri = agents_child_uri("gemini", GEMINI_SESSION_ID, GEMINI_CHILD_SESSION_ID);
-    let missing_uri =
-        agents_child_uri("gemini", GEMINI_SESSION_ID, GEMINI_MISSING_CHILD_SESSION_ID);
+fn cache_gc_prunes_old_entries_then_the_oldest_until_under_max_size() {
+    let temp = tempdir().expect("tempdir");
+    let day = std::time::Duration::from_secs(24 * 3600);
+    let age = |path: &Path, by: std::time::Duration| {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(std::time::SystemTime::now() - by))
+            .expect("set mtime");
+    };
+    let exports = temp.path().join("tmp/xurl-opencode/0123456789abcdef");
+    fs::create_dir_all(&exports).expect("mkdir");
+    for (name, days) in [("stale.jsonl", 45), ("recent.jsonl", 3), ("fresh.jsonl", 1)] {
+        fs::write(exports.join(name), "x".repeat(100)).expect("write");
+        age(&exports.join(name), days * day);
+    }
+    let clone = temp.path().join("cache/github.com__Xuanwo__old");
+    fs::create_dir_all(&clone).expect("mkdir");
+    fs::write(clone.join("SKILL.md"), "x".repeat(50)).expect("write");
+    age(&clone.join("SKILL.md"), 60 * day);
+
+    let gc = |args: &[&str]| {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+        cmd.env("TMPDIR", temp.path().join("tmp"))
+            .env("XURL_SKILLS_CACHE_ROOT", temp.path().join("cache"))
+            .args(["cache", "gc"])
+            .args(args)
+            .assert()
+            .success()
+    };
+
+    gc(&["--dry-run"])
+        .stdout(format!(
+            "{}\n{}\n",
+            clone.display(),
+            exports.join("stale.jsonl").display()
+        ))
+        .stderr("would delete 2 cache entries (150 bytes), kept 2 (200 bytes)\n");
+    assert!(clone.exists());
+
+    gc(&["--max-size", "100"])
+        .stdout(predicate::str::contains("recent.jsonl"))
+        .stderr("deleted 3 cache entries (250 bytes), kept 1 (100 bytes)\n");
+    assert!(!clone.exists());
+    assert!(!exports.join("stale.jsonl").exists());
+    assert!(exports.join("fresh.jsonl").exists());
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("GEMINI_CLI_HOME", temp.path())
-        .arg(main_uri)
-        .arg("--head")
+    cmd.args(["cache", "gc", "--max-size", "lots"])
         .assert()
-        .success()
-        .stdout(predicate::str::contains("mode: 'subagent_index'"))
-        .stdout(predicate::str::contains("subagents:"))
-        .stdout(predicate::str::contains(child_uri))
-        .stdout(predicate::str::contains(missing_uri))
-        .stdout(predicate::str::contains("status: 'notFound'"))
-        .stdout(predicate::str::contains("warnings:"));
+        .failure()
+        .stderr(predicate::str::contains("invalid size `lots`"));
 }
 
 #[test]
-fn gemini_head_outputs_subagent_discovery_from_ndjson_logs() {
-    let temp = setup_gemini_subagent_tree_with_ndjson_logs();
-    let main_uri = agents_uri("gemini", GEMINI_SESSION_ID);
-    let child_uri = agents_child_uri("gemini", GEMINI_SESSION_ID, GEMINI_CHILD_SESSION_ID);
-    let missing_uri =
-        agents_child_uri("gemini", GEMINI_SESSION_ID, GEMINI_MISSING_CHILD_SESSION_ID);
+fn alias_set_resolves_through_config_and_remove_drops_it() {
+    let temp = setup_codex_tree();
+    let config = temp.path().join("xurl/config.toml");
+    let xurl = || {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+        cmd.env("CODEX_HOME", temp.path())
+            .env("XURL_CONFIG", &config);
+        cmd
+    };
+
+    xurl()
+        .args(["alias", "set", "release-debug"])
+        .arg(codex_uri())
+        .assert()
+        .success();
+    xurl()
+        .args(["alias", "list"])
+        .assert()
+        .success()
+        .stdout(format!("release-debug = {}\n", codex_uri()));
 
-    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("GEMINI_CLI_HOME", temp.path())
-        .arg(main_uri)
-        .arg("--head")
+    xurl()
+        .arg("release-debug")
         .assert()
         .success()
-        .stdout(predicate::str::contains("mode: 'subagent_index'"))
-        .stdout(predicate::str::contains("subagents:"))
-        .stdout(predicate::str::contains(child_uri))
-        .stdout(predicate::str::contains(missing_uri))
-        .stdout(predicate::str::contains("status: 'notFound'"));
+        .stdout(predicate::str::contains("# Thread"));
+    xurl()
+        .args(["resolve", "agents://alias/release-debug"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "uri: agents://codex/{SESSION_ID}\n"
+        )));
+
+    xurl()
+        .args(["alias", "set", "codex"])
+        .arg(codex_uri())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid alias name `codex`"));
+    xurl()
+        .args(["alias", "set", "again", "release-debug"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("must point at an agents URI"));
+
+    xurl()
+        .args(["alias", "remove", "release-debug"])
+        .assert()
+        .success();
+    xurl()
+        .args(["resolve", "agents://alias/release-debug"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown alias `release-debug`"));
 }
 
 #[test]
-fn gemini_subagent_outputs_markdown_view() {
-    let temp = setup_gemini_subagent_tree();
-    let main_uri = agents_uri("gemini", GEMINI_SESSION_ID);
-    let subagent_uri = agents_child_uri("gemini", GEMINI_SESSION_ID, GEMINI_CHILD_SESSION_ID);
+fn prefix_resolves_unique_session_id_prefix() {
+    let sibling_id = "019c871c-0000-7000-8000-000000000001";
+    let temp = CodexTreeBuilder::new()
+        .with_thread(
+            SESSION_ID,
+            &[Message::user("hello"), Message::assistant("world")],
+        )
+        .with_thread(sibling_id, &[Message::user("sibling prompt")])
+        .build();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("GEMINI_CLI_HOME", temp.path())
-        .arg(agents_gemini_subagent_uri())
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("--prefix")
+        .arg("codex://019C871C-B1")
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Subagent Thread"))
         .stdout(predicate::str::contains(format!(
-            "- Main Thread: `{main_uri}`"
+            "uri: agents://codex/{SESSION_ID}"
         )))
+        .stdout(predicate::str::contains("world"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("resolve")
+        .arg("--prefix")
+        .arg("codex/019c871c-00")
+        .assert()
+        .success()
         .stdout(predicate::str::contains(format!(
-            "- Subagent Thread: `{subagent_uri}`"
+            "uri: agents://codex/{sibling_id}"
+        )));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("--prefix")
+        .arg("codex://019c871c")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "multiple threads matched session id prefix for provider=codex prefix=019c871c",
+        ))
+        .stderr(predicate::str::contains(format!(
+            "- agents://codex/{SESSION_ID}"
         )))
-        .stdout(predicate::str::contains("## Thread Excerpt (Child Thread)"));
+        .stderr(predicate::str::contains(format!(
+            "- agents://codex/{sibling_id}"
+        )));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("--prefix")
+        .arg("codex://019c0000")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "thread not found for provider=codex session_id=019c0000",
+        ));
+
+    // Without --prefix the segment is read as a role name.
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("codex://019c871c")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("role: 019c871c"));
 }
 
 #[test]
-fn gemini_missing_subagent_outputs_not_found_markdown() {
-    let temp = setup_gemini_subagent_tree();
+fn lint_reports_issues_with_line_numbers() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .with_event(r#"{"timestamp":"2026-02-23T04:48:51Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"hello"}]}}"#)
+        .with_event(r#"{"type":"response_item","payload":"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call_output","call_id":"call_9","output":"lost"}}"#)
+        .build();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("GEMINI_CLI_HOME", temp.path())
-        .arg(gemini_missing_subagent_uri())
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("lint")
+        .arg(agents_codex_uri())
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Subagent Thread"))
+        .stdout(predicate::str::contains("mode: lint"))
+        .stdout(predicate::str::contains("records: 4"))
+        .stdout(predicate::str::contains("issues: 3"))
+        .stdout(predicate::str::contains("- line 3 `malformed_json`: "))
         .stdout(predicate::str::contains(
-            "- Status: `notFound` (`inferred`)",
+            "- line 4 `missing_timestamp`: `response_item` record has no `timestamp`\n",
         ))
         .stdout(predicate::str::contains(
-            "_No child thread messages found._",
+            "- line 4 `orphan_tool_output`: tool output for `call_9` has no earlier tool call\n",
         ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("lint")
+        .arg("agents://codex")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("lint requires a thread URI"));
 }
 
 #[test]
-fn pi_outputs_markdown_from_latest_leaf() {
-    let temp = setup_pi_tree();
+fn read_skips_corrupt_lines_and_counts_them() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .with_event(r#"{"timestamp":"2026-02-23T04:48:51Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"before corruption"}]}}"#)
+        .with_event(r#"{"timestamp":"2026-02-23T04:48:52Z","type":"response_item","payl"#)
+        .with_event("\u{0}garbage")
+        .with_event(r#"{"timestamp":"2026-02-23T04:48:53Z","type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"after corruption"}]}}"#)
+        .build();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
-        .arg(pi_uri())
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Thread"))
-        .stdout(predicate::str::contains("## Timeline"))
-        .stdout(predicate::str::contains("root"))
-        .stdout(predicate::str::contains("branch two done"));
+        .stdout(predicate::str::contains("corrupt_lines: 2\n"))
+        .stdout(predicate::str::contains(
+            "corrupt_lines: 2 (skipped records that are not valid JSON)",
+        ))
+        .stdout(predicate::str::contains("before corruption"))
+        .stdout(predicate::str::contains("after corruption"));
 }
 
 #[test]
-fn pi_entry_outputs_markdown_from_requested_leaf() {
-    let temp = setup_pi_tree();
+fn file_read_reports_corrupt_lines_after_the_body() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[Message::user("before corruption")])
+        .with_event(r#"{"timestamp":"2026-02-23T04:48:52Z","type":"response_item","payl"#)
+        .build();
+    let uri = format!(
+        "file://{}",
+        temp.path()
+            .join(format!(
+                "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+            ))
+            .display()
+    );
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
-        .arg(pi_entry_uri())
+    cmd.args([&uri, "--as", "codex"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Thread"))
-        .stdout(predicate::str::contains("branch one done"))
-        .stdout(predicate::str::contains("branch two done").not());
+        .stdout(predicate::str::contains("before corruption"))
+        .stdout(predicate::str::contains("corrupt_lines").not())
+        .stderr(predicate::str::contains(
+            "warning: corrupt_lines: 1 (skipped records that are not valid JSON)",
+        ));
 }
 
 #[test]
-fn pi_head_outputs_entries() {
-    let temp = setup_pi_tree();
+fn strict_turns_parse_warnings_into_errors() {
+    let clean = setup_codex_tree();
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", clean.path())
+        .arg(agents_codex_uri())
+        .arg("--strict")
+        .assert()
+        .success();
 
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[Message::user("hello")])
+        .with_event(r#"{"type":"response_item","payl"#)
+        .with_event(r#"{"type":"world_state"}"#)
+        .build();
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
-        .arg(pi_uri())
-        .arg("--head")
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("--strict")
         .assert()
-        .success()
-        .stdout(predicate::str::contains("mode: 'pi_entry_index'"))
-        .stdout(predicate::str::contains("entries:"))
-        .stdout(predicate::str::contains(format!(
-            "uri: 'agents://pi/{PI_SESSION_ID}/a1b2c3d4'"
-        )))
-        .stdout(predicate::str::contains("is_leaf: true"));
+        .failure()
+        .stderr(predicate::str::contains("strict mode: "))
+        .stderr(predicate::str::contains("EOF while parsing"))
+        .stderr(predicate::str::contains(
+            "`world_state` records are not rendered",
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .args(["export", &agents_codex_uri(), "--format", "csv", "--strict"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("strict mode: "));
 }
 
 #[test]
-fn pi_head_outputs_entries_and_child_sessions() {
-    let temp = setup_pi_tree_with_child_sessions();
+fn read_warns_about_newer_rollout_formats() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_event(format!(
+            r#"{{"type":"session_meta","payload":{{"id":"{SESSION_ID}","cli_version":"1.0.0"}}}}"#
+        ))
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"hello"}]}}"#)
+        .build();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
-        .arg(pi_uri())
-        .arg("--head")
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
         .assert()
         .success()
-        .stdout(predicate::str::contains("mode: 'pi_entry_index'"))
-        .stdout(predicate::str::contains("entries:"))
-        .stdout(predicate::str::contains("subagents:"))
-        .stdout(predicate::str::contains(format!(
-            "uri: 'agents://pi/{PI_SESSION_ID}/{PI_CHILD_SESSION_ID}'"
-        )))
-        .stdout(predicate::str::contains(format!(
-            "uri: 'agents://pi/{PI_SESSION_ID}/{PI_MISSING_CHILD_SESSION_ID}'"
-        )))
-        .stdout(predicate::str::contains("status: 'completed'"))
-        .stdout(predicate::str::contains("status: 'notFound'"))
-        .stdout(predicate::str::contains("warnings:"));
+        .stdout(predicate::str::contains(
+            "codex format v1.0.0 not fully supported (newest known: v0)",
+        ))
+        .stdout(predicate::str::contains("hello"));
 }
 
+#[cfg(unix)]
 #[test]
-fn pi_child_session_outputs_subagent_markdown_view() {
-    let temp = setup_pi_tree_with_child_sessions();
-    let main_uri = agents_uri("pi", PI_SESSION_ID);
-    let child_uri = agents_child_uri("pi", PI_SESSION_ID, PI_CHILD_SESSION_ID);
+fn doctor_reports_provider_health_matrix() {
+    let temp = tempdir().expect("tempdir");
+    let codex_home = temp.path().join("codex");
+    fs::create_dir_all(&codex_home).expect("mkdir codex");
+    let mock = setup_mock_bins(&[(
+        "codex",
+        r#"if [ "$1" = "--version" ]; then echo "codex-cli 1.2.3"; exit 0; fi
+if [ "$1" = "login" ]; then echo "Logged in using ChatGPT"; exit 0; fi
+exit 1"#,
+    )]);
+    let missing_bin = temp.path().join("missing-bin");
+    // `[bins]` points codex at the mock; the claude entry loses to `XURL_CLAUDE_BIN`.
+    let config = temp.path().join("config.toml");
+    fs::write(
+        &config,
+        format!(
+            "[bins]\ncodex = \"{}\"\nclaude = \"{}\"\n",
+            mock.path().join("codex").display(),
+            mock.path().join("codex").display()
+        ),
+    )
+    .expect("write config");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
-        .arg(&child_uri)
+    cmd.env("CODEX_HOME", &codex_home)
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("claude"))
+        .env("GEMINI_CLI_HOME", temp.path().join("gemini-home"))
+        .env("PI_CODING_AGENT_DIR", temp.path().join("pi"))
+        .env("XDG_DATA_HOME", temp.path().join("data"))
+        .env("XURL_CONFIG", &config)
+        .env_remove("XURL_CODEX_BIN")
+        .env("XURL_CLAUDE_BIN", &missing_bin)
+        .env("XURL_AMP_BIN", &missing_bin)
+        .env("XURL_GEMINI_BIN", &missing_bin)
+        .env("XURL_PI_BIN", &missing_bin)
+        .env("XURL_OPENCODE_BIN", &missing_bin)
+        .env_remove("ANTHROPIC_API_KEY")
+        .env_remove("CLAUDE_CODE_OAUTH_TOKEN")
+        .arg("doctor")
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Subagent Thread"))
+        .stdout(predicate::str::contains("mode: doctor"))
+        .stdout(predicate::str::contains(
+            "| codex | ok | warn | ok | ok | yes | yes |",
+        ))
+        .stdout(predicate::str::contains(
+            "| claude | missing | - | missing | warn | yes | no |",
+        ))
         .stdout(predicate::str::contains(format!(
-            "- Main Thread: `{main_uri}`"
+            "- Binary: `{}` (from [bins] codex)\n- CLI: codex-cli 1.2.3",
+            mock.path().join("codex").display()
         )))
         .stdout(predicate::str::contains(format!(
-            "- Subagent Thread: `{child_uri}`"
+            "- Binary: `{}` (from XURL_CLAUDE_BIN)",
+            missing_bin.display()
         )))
-        .stdout(predicate::str::contains("child done"))
-        .stdout(predicate::str::contains("## Thread Excerpt (Child Thread)"));
+        .stdout(predicate::str::contains("- Auth: Logged in using ChatGPT"))
+        .stdout(predicate::str::contains(
+            "- Hint: write mode needs Claude CLI; run `claude --version`",
+        ))
+        .stdout(predicate::str::contains("- Index: opencode.db not found"));
 }
 
 #[test]
-fn pi_child_session_head_outputs_subagent_detail() {
-    let temp = setup_pi_tree_with_child_sessions();
+fn extract_writes_attachments_and_links_them() {
+    let temp = tempdir().expect("tempdir");
+    let project = temp.path().join("projects/project-images");
+    fs::create_dir_all(&project).expect("mkdir");
+    fs::write(
+        project.join(format!("{CLAUDE_SESSION_ID}.jsonl")),
+        format!(
+            r#"{{"type":"user","sessionId":"{CLAUDE_SESSION_ID}","message":{{"role":"user","content":[{{"type":"text","text":"what is in this picture?"}},{{"type":"image","source":{{"type":"base64","media_type":"image/png","data":"iVBORw0KGgo="}}}}]}}}}"#
+        ),
+    )
+    .expect("write");
+    let out = temp.path().join("export");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
-        .arg(pi_child_session_uri())
-        .arg("--head")
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg("extract")
+        .arg(format!("agents://claude/{CLAUDE_SESSION_ID}"))
+        .arg("--out")
+        .arg(&out)
         .assert()
         .success()
-        .stdout(predicate::str::contains("mode: 'subagent_detail'"))
-        .stdout(predicate::str::contains(format!(
-            "agent_id: '{PI_CHILD_SESSION_ID}'"
-        )))
-        .stdout(predicate::str::contains("status: 'completed'"))
-        .stdout(predicate::str::contains("# Subagent Thread").not());
+        .stdout(predicate::str::contains("thread.md"))
+        .stdout(predicate::str::contains("1.png"));
+
+    let markdown = fs::read_to_string(out.join("thread.md")).expect("read markdown");
+    assert!(markdown.contains("what is in this picture?\n\n![image 1](attachments/1.png)"));
+    assert_eq!(
+        fs::read(out.join("attachments/1.png")).expect("read image"),
+        b"\x89PNG\r\n\x1a\n"
+    );
 }
 
 #[test]
-fn pi_missing_child_session_head_reports_not_found_with_evidence() {
-    let temp = setup_pi_tree_with_child_sessions();
+fn export_gh_comment_splits_long_threads_into_collapsed_parts() {
+    let long_reply = "word ".repeat(8_000);
+    let temp = CodexTreeBuilder::new()
+        .with_thread(
+            SESSION_ID,
+            &[
+                Message::user("write a long report"),
+                Message::assistant(long_reply.clone()),
+                Message::assistant(long_reply.clone()),
+                Message::assistant(long_reply),
+            ],
+        )
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .build();
+    let out = temp.path().join("comment.md");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
-        .arg(pi_missing_child_session_uri())
-        .arg("--head")
+    cmd.env("CODEX_HOME", temp.path())
+        .args([
+            "export",
+            &agents_codex_uri(),
+            "--format",
+            "gh-comment",
+            "-o",
+        ])
+        .arg(&out)
         .assert()
         .success()
-        .stdout(predicate::str::contains("mode: 'subagent_detail'"))
-        .stdout(predicate::str::contains(format!(
-            "agent_id: '{PI_MISSING_CHILD_SESSION_ID}'"
-        )))
-        .stdout(predicate::str::contains("status: 'notFound'"))
-        .stdout(predicate::str::contains("warnings:"))
-        .stdout(predicate::str::contains(
-            "relation hint references child_session_id",
+        .stdout(predicate::str::contains("comment.md"))
+        .stdout(predicate::str::contains("comment.2.md"));
+
+    let first = fs::read_to_string(&out).expect("read part 1");
+    let second = fs::read_to_string(temp.path().join("comment.2.md")).expect("read part 2");
+    for part in [&first, &second] {
+        assert!(part.chars().count() <= 65_536);
+        assert!(part.contains("<details>\n<summary>4 timeline entries, part "));
+        assert!(part.trim_end().ends_with("</details>"));
+    }
+    assert!(first.contains(&format!(
+        "**Agent thread** `{}` (part 1 of",
+        agents_codex_uri()
+    )));
+    assert!(first.contains("## 1. User\n\nwrite a long report"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .args(["export", &agents_codex_uri(), "--format", "html"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unsupported --format value: html; expected gh-comment",
         ));
 }
 
 #[test]
-fn pi_head_entry_outputs_header_only() {
-    let temp = setup_pi_tree();
+fn export_scrub_rewrites_identifying_values_and_writes_the_mapping() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(
+            SESSION_ID,
+            &[
+                Message::user("fix /home/alice/app/main.rs and mail alice@example.com"),
+                Message::assistant("pushed to https://git.example.com/app for alice"),
+            ],
+        )
+        .build();
+    let map = temp.path().join("scrub.json");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
-        .arg(pi_entry_uri())
-        .arg("--head")
+    cmd.env("CODEX_HOME", temp.path())
+        .env("HOME", temp.path())
+        .args([
+            "export",
+            &agents_codex_uri(),
+            "--format",
+            "gh-comment",
+            "--scrub",
+            "paths,emails,hostnames",
+            "--scrub-map",
+        ])
+        .arg(&map)
         .assert()
         .success()
-        .stdout(predicate::str::contains("mode: 'pi_entry'"))
-        .stdout(predicate::str::contains(format!(
-            "entry_id: '{PI_ENTRY_ID}'"
-        )))
-        .stdout(predicate::str::contains("# Thread").not());
+        .stdout(predicate::str::contains(
+            "fix /home/[user-1]/app/main.rs and mail [email-1]",
+        ))
+        .stdout(predicate::str::contains(
+            "pushed to https://[host-1]/app for [user-1]",
+        ))
+        .stdout(predicate::str::contains("alice").not())
+        .stdout(predicate::str::contains("example.com").not());
+
+    let mapping = fs::read_to_string(&map).expect("read mapping");
+    assert!(mapping.contains(r#""[user-1]": "alice""#), "{mapping}");
+    assert!(
+        mapping.contains(r#""[email-1]": "alice@example.com""#),
+        "{mapping}"
+    );
+    assert!(
+        mapping.contains(r#""[host-1]": "git.example.com""#),
+        "{mapping}"
+    );
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .args([
+            "export",
+            &agents_codex_uri(),
+            "--format",
+            "csv",
+            "--scrub",
+            "names",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unsupported --scrub value: names; expected paths, emails, or hostnames",
+        ));
 }
 
 #[test]
-fn pi_real_fixture_outputs_markdown() {
-    let fixture_root = pi_real_fixture_root();
-    assert!(fixture_root.exists(), "fixture root must exist");
+fn export_csv_writes_one_row_per_message() {
+    let temp = setup_codex_tree();
+    let expected_rows = |stdout: &str| {
+        let lines = stdout.split("\r\n").collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "provider,session_id,uri,seq,role,timestamp,thread_input_tokens,thread_output_tokens,text"
+        );
+        let prefix = format!("codex,{SESSION_ID},{},", agents_codex_uri());
+        assert!(
+            lines[1].starts_with(&format!("{prefix}1,user,")),
+            "{stdout}"
+        );
+        assert!(lines[1].ends_with(",hello"), "{stdout}");
+        assert!(
+            lines[2].starts_with(&format!("{prefix}2,assistant,")),
+            "{stdout}"
+        );
+        assert!(lines[2].ends_with(",world"), "{stdout}");
+        assert_eq!(lines[3], "");
+    };
+
+    for uri in [agents_codex_uri(), "agents://codex".to_string()] {
+        let output = Command::new(assert_cmd::cargo::cargo_bin!("xurl"))
+            .env("CODEX_HOME", temp.path())
+            .args(["export", &uri, "--format", "csv"])
+            .output()
+            .expect("run");
+        assert!(output.status.success(), "{output:?}");
+        expected_rows(&String::from_utf8(output.stdout).expect("utf8"));
+    }
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("PI_CODING_AGENT_DIR", fixture_root)
-        .arg(pi_real_uri())
+    cmd.env("CODEX_HOME", temp.path())
+        .args(["export", "agents://codex", "--format", "parquet"])
         .assert()
-        .success()
-        .stdout(predicate::str::contains("# Thread"))
-        .stdout(predicate::str::contains("## 1. User"))
-        .stdout(predicate::str::contains("## 2. Assistant"));
+        .failure()
+        .stderr(predicate::str::contains(
+            "--format parquet requires -o <PATH>",
+        ));
 }
 
 #[test]
-fn claude_subagent_outputs_markdown_view() {
-    let temp = setup_claude_subagent_tree();
-    let main_uri = agents_uri("claude", CLAUDE_SESSION_ID);
-    let subagent_uri = agents_child_uri("claude", CLAUDE_SESSION_ID, CLAUDE_AGENT_ID);
+fn export_csv_jobs_keep_the_serial_row_order() {
+    let mut builder = CodexTreeBuilder::new();
+    for idx in 0..6 {
+        let messages = (0..=idx)
+            .map(|turn| Message::user(format!("thread {idx} turn {turn}")))
+            .collect::<Vec<_>>();
+        builder = builder
+            .with_thread(
+                &format!("019c871c-b1f9-7f60-9c4f-87ed09f1a{idx:03}"),
+                &messages,
+            )
+            .with_session_meta("/work/app", &format!("2026-02-23T04:48:5{idx}Z"));
+    }
+    let temp = builder.build();
+
+    let export = |jobs: &str| {
+        let output = Command::new(assert_cmd::cargo::cargo_bin!("xurl"))
+            .env("CODEX_HOME", temp.path())
+            .args([
+                "export",
+                "agents://codex",
+                "--format",
+                "csv",
+                "--jobs",
+                jobs,
+            ])
+            .output()
+            .expect("run");
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).expect("utf8")
+    };
+    let serial = export("1");
+    assert_eq!(serial.matches("\r\n").count(), 1 + 21, "{serial}");
+    assert_eq!(export("4"), serial);
+}
+
+#[test]
+fn max_depth_bounds_discovery_and_reports_skipped_directories() {
+    let temp = setup_codex_tree();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
-        .env("CODEX_HOME", temp.path().join("missing-codex"))
-        .arg(claude_subagent_uri())
+    cmd.env("CODEX_HOME", temp.path())
+        .args(["agents://codex", "--max-depth", "2"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Subagent Thread"))
-        .stdout(predicate::str::contains(format!(
-            "- Main Thread: `{main_uri}`"
-        )))
-        .stdout(predicate::str::contains(format!(
-            "- Subagent Thread: `{subagent_uri}`"
-        )))
-        .stdout(predicate::str::contains("## Agent Status Summary"));
+        .stdout(predicate::str::contains("_No threads found._"))
+        .stdout(predicate::str::contains("deeper than --max-depth 2"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .args(["agents://codex", "--max-depth", "8"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(agents_codex_uri()))
+        .stdout(predicate::str::contains("--max-depth").not());
 }
 
 #[test]
-fn claude_real_fixture_head_includes_subagents() {
-    let fixture_root = claude_real_fixture_root();
-    assert!(fixture_root.exists(), "fixture root must exist");
-    let subagent_uri = agents_child_uri("claude", CLAUDE_REAL_MAIN_ID, CLAUDE_REAL_AGENT_ID);
+fn ignore_globs_hide_threads_from_discovery() {
+    let temp = setup_codex_tree();
+    let config = temp.path().join("config.toml");
+    fs::write(&config, "ignore = [\"**/2026/02/**\"]\n").expect("write config");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("CLAUDE_CONFIG_DIR", fixture_root)
-        .env("CODEX_HOME", "/tmp/missing-codex")
-        .arg(claude_real_uri())
-        .arg("--head")
+    cmd.env("CODEX_HOME", temp.path())
+        .env("XURL_CONFIG", &config)
+        .arg("agents://codex")
         .assert()
         .success()
-        .stdout(predicate::str::contains("mode: 'subagent_index'"))
-        .stdout(predicate::str::contains("subagents:"))
-        .stdout(predicate::str::contains(subagent_uri))
-        .stdout(predicate::str::contains("# Subagent Status").not());
+        .stdout(predicate::str::contains("_No threads found._"));
+
+    fs::remove_file(&config).expect("remove config");
+    fs::write(temp.path().join("sessions/.xurlignore"), "2026/\n").expect("write ignore");
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("XURL_CONFIG", &config)
+        .arg("agents://codex")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("_No threads found._"));
 }
 
 #[test]
-fn claude_real_fixture_subagent_detail_outputs_markdown() {
-    let fixture_root = claude_real_fixture_root();
-    assert!(fixture_root.exists(), "fixture root must exist");
+fn only_filters_timeline_entries() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"list the files"}]}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"ls\"]}","call_id":"call_1"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"README.md"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"There is one file."}]}}"#)
+        .build();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("CLAUDE_CONFIG_DIR", fixture_root)
-        .env("CODEX_HOME", "/tmp/missing-codex")
-        .arg(claude_real_subagent_uri())
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("--only")
+        .arg("user")
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Subagent Thread"))
-        .stdout(predicate::str::contains("## Thread Excerpt (Child Thread)"));
+        .stdout(predicate::str::contains("## 1. User\n\nlist the files"))
+        .stdout(predicate::str::contains("There is one file.").not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("--only")
+        .arg("tools")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("## 1. Tool: shell"))
+        .stdout(predicate::str::contains(
+            "Output:\n\n```text\nREADME.md\n```",
+        ))
+        .stdout(predicate::str::contains("list the files").not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("--only")
+        .arg("system")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unsupported --only value: system"));
 }
 
 #[test]
-fn opencode_subagent_head_includes_subagents_and_warnings() {
-    let temp = setup_opencode_subagent_tree();
-    let child_uri = agents_child_uri(
-        "opencode",
-        OPENCODE_MAIN_SESSION_ID,
-        OPENCODE_CHILD_SESSION_ID,
-    );
-    let empty_child_uri = agents_child_uri(
-        "opencode",
-        OPENCODE_MAIN_SESSION_ID,
-        OPENCODE_CHILD_EMPTY_SESSION_ID,
-    );
+fn grep_renders_matching_entries_only() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"run the tests"}]}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"cargo\",\"test\"]}","call_id":"call_1"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"running 3 tests\nthread 'tick' panicked: panic in scheduler\nerror: test failed"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"The scheduler test fails.\nIt hits a Panic in scheduler on tick."}]}}"#)
+        .build();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("XDG_DATA_HOME", temp.path())
-        .arg(agents_uri("opencode", OPENCODE_MAIN_SESSION_ID))
-        .arg("--head")
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .args(["--grep", "panic in scheduler"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("mode: 'subagent_index'"))
-        .stdout(predicate::str::contains("subagents:"))
-        .stdout(predicate::str::contains(child_uri))
-        .stdout(predicate::str::contains(empty_child_uri))
-        .stdout(predicate::str::contains("status: 'completed'"))
-        .stdout(predicate::str::contains("status: 'pendingInit'"))
-        .stdout(predicate::str::contains("warnings:"))
-        .stdout(predicate::str::contains(format!(
-            "child session_id={OPENCODE_CHILD_EMPTY_SESSION_ID} has no materialized messages in sqlite"
-        )));
+        .stdout(predicate::str::contains(
+            "### 2. Assistant\n\n```text\n1- The scheduler test fails.\n2: It hits a Panic in scheduler on tick.\n```",
+        ))
+        .stdout(predicate::str::contains("### 1. Tool: shell"))
+        .stdout(predicate::str::contains(
+            ": thread 'tick' panicked: panic in scheduler\n",
+        ))
+        .stdout(predicate::str::contains("run the tests").not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .args(["--grep", "panic in scheduler", "--only", "assistant"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("### 2. Assistant"))
+        .stdout(predicate::str::contains("Tool Call Matches").not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .args(["--grep", "panic", "-I"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--grep only applies to read mode"));
 }
 
 #[test]
-fn opencode_subagent_outputs_markdown_view() {
-    let temp = setup_opencode_subagent_tree();
-    let main_uri = agents_uri("opencode", OPENCODE_MAIN_SESSION_ID);
-    let subagent_uri = agents_child_uri(
-        "opencode",
-        OPENCODE_MAIN_SESSION_ID,
-        OPENCODE_CHILD_SESSION_ID,
-    );
+fn large_tool_outputs_are_elided_unless_no_elide() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"cat\",\"build.log\"]}","call_id":"call_1"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"0123456789abcdef"}}"#)
+        .build();
+    let config = temp.path().join("config.toml");
+    fs::write(&config, "[render]\nelide_bytes = 8\n").expect("write config");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("XDG_DATA_HOME", temp.path())
-        .arg(&subagent_uri)
+    cmd.env("CODEX_HOME", temp.path())
+        .env("XURL_CONFIG", &config)
+        .arg(agents_codex_uri())
+        .arg("--only")
+        .arg("tools")
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Subagent Thread"))
-        .stdout(predicate::str::contains(format!(
-            "- Main Thread: `{main_uri}`"
-        )))
-        .stdout(predicate::str::contains(format!(
-            "- Subagent Thread: `{subagent_uri}`"
-        )))
         .stdout(predicate::str::contains(
-            "- Status: `completed` (`child_rollout`)",
+            "Output: [output elided: 16B, sha256=9f9f5111f7b27a781f1f1ddde5ebc2dd2b796bfc7365c9c28b548e564176929f]",
         ))
+        .stdout(predicate::str::contains("0123456789abcdef").not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("XURL_CONFIG", &config)
+        .arg(agents_codex_uri())
+        .arg("--only")
+        .arg("tools")
+        .arg("--no-elide")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("```text\n0123456789abcdef\n```"));
+}
+
+#[test]
+fn max_bytes_truncates_rendered_thread() {
+    let temp = setup_codex_tree();
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("--max-bytes")
+        .arg("30")
+        .assert()
+        .success()
         .stdout(predicate::str::contains(
-            "- Evidence: opencode sqlite relation validated via session.parent_id",
+            "# Thread\n\n## Timeline\n\n## 1. U",
         ))
-        .stdout(predicate::str::contains("child completed"));
+        .stdout(predicate::str::contains(
+            "_Output truncated at 30 bytes; rerun with a larger --max-bytes to see the rest._",
+        ));
 }
 
 #[test]
-fn opencode_subagent_not_found_outputs_markdown_view() {
-    let temp = setup_opencode_subagent_tree();
-    let missing_child = "ses_5x7md9kx3c9p";
-    let missing_uri = agents_child_uri("opencode", OPENCODE_MAIN_SESSION_ID, missing_child);
+fn events_show_claude_slash_commands_and_hooks() {
+    let temp = ClaudeTreeBuilder::new()
+        .with_project("-Users-alice-repo")
+        .with_thread(
+            CLAUDE_SESSION_ID,
+            &[Message::user(
+                "<command-name>/review</command-name>\n<command-message>review</command-message>\n<command-args>src/lib.rs</command-args>",
+            )],
+        )
+        .with_event(r#"{"type":"system","content":"Stop [./check.sh] failed: tests red","hookEvent":"Stop"}"#)
+        .with_event(r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Fixed"}]}}"#)
+        .build();
+    let uri = agents_uri("claude", CLAUDE_SESSION_ID);
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("XDG_DATA_HOME", temp.path())
-        .arg(&missing_uri)
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg(&uri)
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Subagent Thread"))
-        .stdout(predicate::str::contains(format!(
-            "- Subagent Thread: `{missing_uri}`"
-        )))
-        .stdout(predicate::str::contains("- Status: `notFound` (`inferred`)"))
-        .stdout(predicate::str::contains("_No child thread messages found._"))
-        .stdout(predicate::str::contains(format!(
-            "agent not found for main_session_id={OPENCODE_MAIN_SESSION_ID} agent_id={missing_child}"
-        )));
+        .stdout(predicate::str::contains("Hook:").not())
+        .stdout(predicate::str::contains("## 2. Assistant\n\nFixed"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg(&uri)
+        .arg("--events")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "## 1. Command: /review\n\nsrc/lib.rs",
+        ))
+        .stdout(predicate::str::contains(
+            "## 2. Hook: Stop\n\nStop [./check.sh] failed: tests red",
+        ))
+        .stdout(predicate::str::contains("## 3. Assistant\n\nFixed"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg(&uri)
+        .arg("--events")
+        .arg("--raw")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--raw prints the payload as stored",
+        ));
 }
 
 #[test]
-fn gemini_real_fixture_outputs_markdown() {
-    let fixture_root = gemini_real_fixture_root();
-    assert!(fixture_root.exists(), "fixture root must exist");
+fn timestamps_label_messages_with_turn_durations_and_head_shows_duration() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_event(r#"{"timestamp":"2026-02-23T04:48:50.120Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"hello"}]}}"#)
+        .with_event(r#"{"timestamp":"2026-02-23T04:49:32.500Z","type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"world"}]}}"#)
+        .with_event(r#"{"timestamp":"2026-02-23T04:51:00.120Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"again"}]}}"#)
+        .build();
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("GEMINI_CLI_HOME", fixture_root)
-        .arg(gemini_real_uri())
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("--timestamps")
         .assert()
         .success()
-        .stdout(predicate::str::contains("# Thread"))
-        .stdout(predicate::str::contains("## 1. User"));
-}
+        .stdout(predicate::str::contains(
+            "## 1. User @ 2026-02-23T04:48:50Z\n\nhello",
+        ))
+        .stdout(predicate::str::contains(
+            "## 2. Assistant @ 2026-02-23T04:49:32Z (+42s)\n\nworld",
+        ))
+        .stdout(predicate::str::contains(
+            "## 3. User @ 2026-02-23T04:51:00Z (+1m27s)\n\nagain",
+        ))
+        .stdout(predicate::str::contains("duration: 2m10s"));
 
-#[test]
-fn opencode_real_fixture_outputs_markdown() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("--timestamps")
+        .arg("--timezone")
+        .arg("+08:00")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "## 2. Assistant @ 2026-02-23T12:49:32+08:00 (+42s)",
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("--timezone")
+        .arg("Mars/Olympus_Mons")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unknown timezone: Mars/Olympus_Mons",
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("## 2. Assistant\n\nworld"));
+}
+
+#[test]
+fn with_history_renders_forked_codex_sessions_in_order() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SUBAGENT_ID, &[Message::user("first question")])
+        .with_session_meta("/work/app", "2026-02-23T04:00:00Z")
+        .with_thread(SESSION_ID, &[])
+        .with_event(format!(
+            r#"{{"type":"session_meta","payload":{{"id":"{SESSION_ID}","forked_from_id":"{SUBAGENT_ID}"}}}}"#
+        ))
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"follow-up question"}]}}"#)
+        .build();
+
+    let output = Command::new(assert_cmd::cargo::cargo_bin!("xurl"))
+        .env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("--with-history")
+        .output()
+        .expect("run");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let first = stdout
+        .find(&format!(
+            "## Session 1 of 2: `agents://codex/{SUBAGENT_ID}`"
+        ))
+        .expect("first session");
+    let second = stdout
+        .find(&format!("## Session 2 of 2: `agents://codex/{SESSION_ID}`"))
+        .expect("second session");
+    assert!(first < second);
+    assert!(stdout.contains(&format!("resumed from `agents://codex/{SUBAGENT_ID}`")));
+    assert!(stdout[first..second].contains("first question"));
+    assert!(stdout[second..].contains("follow-up question"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("first question").not());
+}
+
+#[test]
+fn claude_thread_lists_files_changed() {
+    let temp = tempdir().expect("tempdir");
+    let project = temp.path().join("projects/project-edits");
+    fs::create_dir_all(&project).expect("mkdir");
+    let lines = [
+        r#"{"type":"user","message":{"role":"user","content":"add docs"}}"#,
+        r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"toolu_1","name":"Write","input":{"file_path":"/repo/docs/guide.md","content":"hi"}}]}}"#,
+        r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","content":"File created successfully at: /repo/docs/guide.md"}]}}"#,
+        r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"toolu_2","name":"Edit","input":{"file_path":"/repo/README.md","old_string":"a","new_string":"b"}}]}}"#,
+        r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"toolu_3","name":"Edit","input":{"file_path":"/repo/README.md","old_string":"b","new_string":"c"}}]}}"#,
+        r#"{"type":"assistant","message":{"role":"assistant","content":"done"}}"#,
+    ];
+    fs::write(
+        project.join(format!("{CLAUDE_SESSION_ID}.jsonl")),
+        lines.join("\n"),
+    )
+    .expect("write");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg(format!("agents://claude/{CLAUDE_SESSION_ID}"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "files_changed:\n- path: /repo/docs/guide.md\n  kind: created\n  edits: 1\n- path: /repo/README.md\n  kind: modified\n  edits: 2\n",
+        ))
+        .stdout(predicate::str::contains(
+            "## Files Changed\n\n- `/repo/docs/guide.md`: created (1 edit)\n- `/repo/README.md`: modified (2 edits)\n\n## Timeline",
+        ));
+}
+
+#[test]
+fn codex_head_summarizes_mcp_server_usage() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"check the page"}]}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call","name":"mcp__playwright__navigate","arguments":"{\"url\":\"https://example.com\"}","call_id":"call_1"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"loaded"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call","name":"mcp__playwright__click","arguments":"{}","call_id":"call_2"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{}","call_id":"call_3"}}"#)
+        .build();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("-I")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "mcp_servers:\n- server: playwright\n  calls: 2\n",
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("--only")
+        .arg("tools")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mcp:playwright/navigate"));
+}
+
+#[test]
+fn head_shows_workdir_and_its_git_branch() {
+    let temp = tempdir().expect("tempdir");
+    let repo = temp.path().join("repo");
+    fs::create_dir_all(repo.join(".git")).expect("mkdir");
+    fs::create_dir_all(repo.join("crates/core")).expect("mkdir");
+    fs::write(repo.join(".git/HEAD"), "ref: refs/heads/fix-parser\n").expect("write HEAD");
+    fs::write(
+        repo.join(".git/config"),
+        "[remote \"origin\"]\n\turl = https://github.com/acme/widgets.git\n",
+    )
+    .expect("write config");
+    let workdir = repo.join("crates/core");
+
+    CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[Message::user("fix the parser")])
+        .with_session_meta(&workdir.display().to_string(), "2026-02-23T04:48:50Z")
+        .write_to(temp.path());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("-I")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "workdir: {}\nrepo: https://github.com/acme/widgets.git\nbranch: fix-parser\n",
+            workdir.display()
+        )));
+}
+
+#[test]
+fn head_counts_messages_tool_calls_and_raw_bytes() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[Message::user("list files")])
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call","name":"shell","call_id":"c1","arguments":"{\"command\":[\"ls\"]}"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call_output","call_id":"c1","output":"a.txt"}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"One file: a.txt"}]}}"#)
+        .build();
+    let rollout = fs::read_to_string(temp.path().join(format!(
+        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+    )))
+    .expect("read rollout");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("-I")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "message_count: 2\ntool_call_count: 1\nraw_bytes: {}\n",
+            rollout.len()
+        )));
+}
+
+#[test]
+fn head_fields_limit_frontmatter_keys_from_flag_or_config() {
+    let temp = setup_codex_tree();
+    let config = temp.path().join("config.toml");
+    fs::write(&config, "[head]\nfields = [\"provider\"]\n").expect("write config");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("XURL_CONFIG", &config)
+        .args([
+            agents_codex_uri().as_str(),
+            "-I",
+            "--head-fields",
+            "session_id,uri",
+        ])
+        .assert()
+        .success()
+        .stdout(format!(
+            "---\nuri: {}\nsession_id: {SESSION_ID}\n---\n",
+            agents_codex_uri()
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("XURL_CONFIG", &config)
+        .args([agents_codex_uri().as_str(), "-I"])
+        .assert()
+        .success()
+        .stdout("---\nprovider: codex\n---\n");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .args([agents_codex_uri().as_str(), "--head-fields", "uri"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--head-fields only applies to header mode",
+        ));
+}
+
+#[test]
+fn fast_head_reads_only_the_session_header() {
+    let mut builder = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_event(format!(
+            r#"{{"type":"session_meta","payload":{{"id":"{SESSION_ID}","timestamp":"2026-02-23T04:48:50Z","cwd":"/work/app","cli_version":"0.104.0","git":{{"branch":"main"}}}}}}"#
+        ))
+        .with_event(r#"{"type":"turn_context","payload":{"cwd":"/work/app","model":"gpt-5.3-codex"}}"#);
+    // Far past the header: a full read would report this file change.
+    for idx in 0..500 {
+        builder = builder.with_event(format!(
+            r#"{{"type":"response_item","payload":{{"type":"message","role":"user","content":[{{"type":"input_text","text":"step {idx}"}}]}}}}"#
+        ));
+    }
+    let temp = builder
+        .with_event(r#"{"type":"response_item","payload":{"type":"function_call","name":"apply_patch","call_id":"c1","arguments":"*** Begin Patch\n*** Add File: late.txt\n+x\n*** End Patch"}}"#)
+        .build();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .args([agents_codex_uri().as_str(), "-I", "--fast"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: metadata\n"))
+        .stdout(predicate::str::contains(
+            "started_at: 2026-02-23T04:48:50Z\nworkdir: /work/app\nmodel: gpt-5.3-codex\ncli_version: 0.104.0\nbranch: main\n",
+        ))
+        .stdout(predicate::str::contains("late.txt").not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .args([agents_codex_uri().as_str(), "-I"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("late.txt"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .args([agents_codex_uri().as_str(), "--fast"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--fast only applies to header mode",
+        ));
+}
+
+#[test]
+fn latest_alias_out_of_range_returns_not_found() {
+    let temp = setup_codex_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("agents://codex/@latest~1")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "thread not found for provider=codex session_id=@latest~1",
+        ));
+}
+
+#[test]
+fn completions_bash_includes_session_completion() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.arg("completions")
+        .arg("bash")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("_xurl()"))
+        .stdout(predicate::str::contains("xurl __complete-sessions"))
+        .stdout(predicate::str::contains("complete -F _xurl_with_sessions"));
+}
+
+#[test]
+fn completions_fish_includes_session_completion() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.arg("completions")
+        .arg("fish")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("complete -c xurl"))
+        .stdout(predicate::str::contains("__xurl_complete_sessions"));
+}
+
+#[test]
+fn complete_sessions_lists_session_ids() {
+    let temp = setup_codex_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("__complete-sessions")
+        .arg("codex")
+        .assert()
+        .success()
+        .stdout(format!("{SESSION_ID}\n"));
+}
+
+#[test]
+fn complete_sessions_is_hidden_from_help() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("completions"))
+        .stdout(predicate::str::contains("__complete-sessions").not());
+}
+
+#[test]
+fn fsck_codex_reports_moved_rollout_orphan_and_empty_session() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(
+            SESSION_ID,
+            &[Message::user("hello"), Message::assistant("world")],
+        )
+        .with_thread(SUBAGENT_ID, &[])
+        .with_event(r#"{"type":"session_meta","payload":{}}"#)
+        .build();
+    let moved_path = temp.path().join(format!(
+        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+    ));
+    let stale_path = temp.path().join(format!(
+        "sessions/2026/02/22/rollout-2026-02-22T04-48-50-{SESSION_ID}.jsonl"
+    ));
+
+    let conn = Connection::open(temp.path().join("state_5.sqlite")).expect("open sqlite");
+    conn.execute_batch(
+        "CREATE TABLE threads (id TEXT PRIMARY KEY, rollout_path TEXT NOT NULL, archived INTEGER NOT NULL DEFAULT 0);",
+    )
+    .expect("create schema");
+    conn.execute(
+        "INSERT INTO threads (id, rollout_path, archived) VALUES (?1, ?2, 0)",
+        params![SESSION_ID, stale_path.display().to_string()],
+    )
+    .expect("insert thread");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("fsck")
+        .arg("--provider")
+        .arg("codex")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: fsck"))
+        .stdout(predicate::str::contains("indexed_threads: 1"))
+        .stdout(predicate::str::contains("files_on_disk: 2"))
+        .stdout(predicate::str::contains("issues: 3"))
+        .stdout(predicate::str::contains("## Repair Plan"))
+        .stdout(predicate::str::contains(format!(
+            "`missing_rollout` `{SESSION_ID}`"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "update threads.rollout_path to {}",
+            moved_path.display()
+        )))
+        .stdout(predicate::str::contains(format!(
+            "`orphan_file` `{SUBAGENT_ID}`"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "`empty_session` `{SUBAGENT_ID}`"
+        )));
+}
+
+#[test]
+fn fsck_codex_fix_updates_moved_rollout_path() {
+    let temp = setup_codex_tree();
+    let moved_path = temp.path().join(format!(
+        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+    ));
+    let stale_path = temp.path().join(format!(
+        "sessions/2026/02/22/rollout-2026-02-22T04-48-50-{SESSION_ID}.jsonl"
+    ));
+    let db_path = temp.path().join("state_5.sqlite");
+    let conn = Connection::open(&db_path).expect("open sqlite");
+    conn.execute_batch(
+        "CREATE TABLE threads (id TEXT PRIMARY KEY, rollout_path TEXT NOT NULL, archived INTEGER NOT NULL DEFAULT 0);",
+    )
+    .expect("create schema");
+    conn.execute(
+        "INSERT INTO threads (id, rollout_path, archived) VALUES (?1, ?2, 0)",
+        params![SESSION_ID, stale_path.display().to_string()],
+    )
+    .expect("insert thread");
+    drop(conn);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("fsck")
+        .arg("codex")
+        .arg("--fix")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("fixed: 1"))
+        .stdout(predicate::str::contains("- Fixed: `yes`"));
+
+    let conn = Connection::open(&db_path).expect("open sqlite");
+    let rollout_path = conn
+        .query_row(
+            "SELECT rollout_path FROM threads WHERE id = ?1",
+            params![SESSION_ID],
+            |row| row.get::<_, String>(0),
+        )
+        .expect("query thread");
+    assert_eq!(rollout_path, moved_path.display().to_string());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("fsck")
+        .arg("codex")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("_No issues found._"));
+}
+
+#[test]
+fn fsck_fix_rejects_opencode() {
+    let temp = setup_opencode_subagent_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .arg("fsck")
+        .arg("opencode")
+        .arg("--fix")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "error: invalid mode: fsck --fix only supports codex",
+        ));
+}
+
+#[test]
+fn fsck_codex_warns_when_sqlite_index_missing() {
+    let temp = setup_codex_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("fsck")
+        .arg("--provider")
+        .arg("codex")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("no sqlite thread index found"))
+        .stdout(predicate::str::contains("_No issues found._"));
+}
+
+#[test]
+fn fsck_opencode_reports_empty_session() {
+    let temp = setup_opencode_subagent_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .arg("fsck")
+        .arg("--provider")
+        .arg("opencode")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("provider: opencode"))
+        .stdout(predicate::str::contains(format!(
+            "`empty_session` `{OPENCODE_CHILD_EMPTY_SESSION_ID}`"
+        )))
+        .stdout(predicate::str::contains("files_on_disk").not());
+}
+
+#[test]
+fn fsck_rejects_unsupported_provider() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.arg("fsck")
+        .arg("--provider")
+        .arg("claude")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "error: provider does not support fsck: claude",
+        ));
+}
+
+#[test]
+fn head_flag_outputs_frontmatter_only() {
+    let temp = setup_codex_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(codex_uri())
+        .arg("-I")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("---\n"))
+        .stdout(predicate::str::contains("mode: subagent_index"))
+        .stdout(predicate::str::contains("subagents:"))
+        .stdout(predicate::str::contains("# Thread").not());
+}
+
+#[test]
+fn several_uris_are_read_in_turn_and_fail_only_when_all_fail() {
+    let temp = setup_codex_tree();
+    let missing = "agents://codex/019c871c-b1f9-7f60-9c4f-87ed09f1aaaa";
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg(missing)
+        .arg("-I")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "==> {} <==\n---\n",
+            agents_codex_uri()
+        )))
+        .stdout(predicate::str::contains(format!("\n==> {missing} <==\n")))
+        .stderr(predicate::str::contains(format!(
+            "warning: {missing}: thread not found"
+        )))
+        .stderr(predicate::str::contains(
+            "warning: 1 of 2 URIs failed to read",
+        ));
+
+    let out = temp.path().join("out");
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg(format!("codex/{SESSION_ID}"))
+        .arg("-o")
+        .arg(&out)
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+    for name in [
+        format!("codex-{SESSION_ID}.md"),
+        format!("codex-{SESSION_ID}.2.md"),
+    ] {
+        let body = fs::read_to_string(out.join(&name)).expect("batch file");
+        assert!(body.contains("# Thread"), "{name}: {body}");
+    }
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(missing)
+        .arg("agents://codex/019c871c-b1f9-7f60-9c4f-87ed09f1bbbb")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("all 2 URIs failed to read"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg(missing)
+        .arg("-d")
+        .arg("hi")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "write mode (-d/--data) takes a single URI",
+        ));
+}
+
+#[test]
+fn stdin_uris_are_read_in_order_with_parallel_jobs() {
+    let temp = setup_codex_tree();
+    let missing = "agents://codex/019c871c-b1f9-7f60-9c4f-87ed09f1aaaa";
+    let stdin = format!(
+        "# from xurl last\n{}\n\n{missing}\ncodex/{SESSION_ID}\n",
+        agents_codex_uri()
+    );
+
+    let output = Command::new(assert_cmd::cargo::cargo_bin!("xurl"))
+        .env("CODEX_HOME", temp.path())
+        .arg("--stdin-uris")
+        .arg("--jobs")
+        .arg("2")
+        .arg("-I")
+        .write_stdin(stdin)
+        .output()
+        .expect("run xurl");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf8");
+    let headers = stdout
+        .lines()
+        .filter(|line| line.starts_with("==> "))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        headers,
+        vec![
+            format!("==> {} <==", agents_codex_uri()),
+            format!("==> {missing} <=="),
+            format!("==> codex/{SESSION_ID} <=="),
+        ]
+    );
+    assert_eq!(stdout.matches("resolution:").count(), 2, "{stdout}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("1 of 3 URIs failed to read"),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("--stdin-uris")
+        .write_stdin("# nothing here\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--stdin-uris read no URIs from stdin",
+        ));
+}
+
+#[test]
+fn head_reports_how_the_thread_was_resolved() {
+    let temp = setup_codex_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(codex_uri())
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "resolution:\n  source: codex:sessions\n  strategy: filename\n  candidates: 1\n  elapsed_ms: ",
+        ))
+        .stdout(predicate::str::contains("duplicates_skipped").not());
+}
+
+#[test]
+fn codex_compacted_thread_renders_context_summary() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_event(r#"{"type":"compacted","payload":{"message":"Earlier: user asked for hello."}}"#)
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"world"}]}}"#)
+        .build();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(codex_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("compacted: true\n"))
+        .stdout(predicate::str::contains(
+            "## 1. Context Summary\n\nEarlier: user asked for hello.",
+        ))
+        .stdout(predicate::str::contains("## 2. Assistant"));
+
+    let temp = setup_codex_tree();
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(codex_uri())
+        .arg("-I")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("compacted:").not());
+}
+
+#[cfg(unix)]
+#[test]
+fn head_applies_configured_frontmatter_enrichers() {
+    let temp = setup_codex_tree();
+    let mock = setup_mock_bins(&[
+        (
+            "ticket-enricher",
+            r#"grep -q "\"provider\":\"codex\"" && echo '{"ticket":"JIRA-42"}'"#,
+        ),
+        ("provider-enricher", r#"echo '{"provider":"other"}'"#),
+        ("broken-enricher", "echo boom >&2\nexit 3"),
+    ]);
+    let config = temp.path().join("config.toml");
+    fs::write(
+        &config,
+        format!(
+            "[[enrichers]]\ncommand = '{0}/ticket-enricher'\n\n[[enrichers]]\ncommand = '{0}/provider-enricher'\n\n[[enrichers]]\ncommand = '{0}/broken-enricher'\n",
+            mock.path().display()
+        ),
+    )
+    .expect("write config");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .env("XURL_CONFIG", &config)
+        .arg(codex_uri())
+        .arg("-I")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ticket: JIRA-42\n---\n"))
+        .stdout(predicate::str::contains("provider: codex"))
+        .stdout(predicate::str::contains("provider: other").not())
+        .stderr(predicate::str::contains(
+            "warning: frontmatter enricher field ignored because it already exists: provider",
+        ))
+        .stderr(predicate::str::contains(
+            "warning: frontmatter enricher failed: command failed:",
+        ));
+}
+
+#[test]
+fn invalid_config_returns_error() {
+    let temp = setup_codex_tree();
+    let config = temp.path().join("config.toml");
+    fs::write(&config, "enrichers = 'nope'\n").expect("write config");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .env("XURL_CONFIG", &config)
+        .arg(codex_uri())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error: invalid config"));
+
+    // A malformed `ignore` list is reported, not silently dropped, even when nothing else
+    // in the command reads the config.
+    fs::write(&config, "ignore = 'nope'\n").expect("write config");
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .env("XURL_CONFIG", &config)
+        .arg(codex_uri())
+        .arg("--no-elide")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error: invalid config"));
+}
+
+#[test]
+fn codex_subagent_head_outputs_header_only() {
+    let temp = setup_codex_subagent_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(codex_subagent_uri())
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: subagent_detail"))
+        .stdout(predicate::str::contains(format!("agent_id: {SUBAGENT_ID}")))
+        .stdout(predicate::str::contains("status:"))
+        .stdout(predicate::str::contains("# Subagent Thread").not());
+}
+
+#[test]
+fn codex_deeplink_outputs_markdown() {
+    let temp = setup_codex_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(codex_deeplink_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Thread"))
+        .stdout(predicate::str::contains("## 1. User"))
+        .stdout(predicate::str::contains("hello"));
+}
+
+#[test]
+fn agents_codex_deeplink_outputs_markdown() {
+    let temp = setup_codex_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(agents_codex_deeplink_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Thread"))
+        .stdout(predicate::str::contains("## 1. User"))
+        .stdout(predicate::str::contains("hello"));
+}
+
+#[test]
+fn codex_subagent_outputs_markdown_view() {
+    let temp = setup_codex_subagent_tree();
+    let main_uri = agents_uri("codex", SESSION_ID);
+    let subagent_uri = agents_child_uri("codex", SESSION_ID, SUBAGENT_ID);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(codex_subagent_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Subagent Thread"))
+        .stdout(predicate::str::contains(format!(
+            "- Main Thread: `{main_uri}`"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "- Subagent Thread: `{subagent_uri}`"
+        )))
+        .stdout(predicate::str::contains("## Lifecycle (Parent Thread)"))
+        .stdout(predicate::str::contains("## Thread Excerpt (Child Thread)"));
+}
+
+#[test]
+fn agents_codex_subagent_outputs_markdown_view() {
+    let temp = setup_codex_subagent_tree();
+    let main_uri = agents_uri("codex", SESSION_ID);
+    let subagent_uri = agents_child_uri("codex", SESSION_ID, SUBAGENT_ID);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(agents_codex_subagent_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "- Main Thread: `{main_uri}`"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "- Subagent Thread: `{subagent_uri}`"
+        )));
+}
+
+#[test]
+fn codex_outputs_no_warning_text_for_markdown() {
+    let temp = setup_codex_tree_with_sqlite_missing_threads();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(codex_uri())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("warning:").not());
+}
+
+#[test]
+fn codex_subagent_outputs_no_warning_text_for_markdown() {
+    let temp = setup_codex_subagent_tree_with_sqlite_missing_threads();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(codex_subagent_uri())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("warning:").not());
+}
+
+#[test]
+fn codex_real_fixture_head_includes_subagents() {
+    let fixture_root = codex_real_fixture_root();
+    assert!(fixture_root.exists(), "fixture root must exist");
+    let subagent_uri = agents_child_uri("codex", REAL_FIXTURE_MAIN_ID, REAL_FIXTURE_AGENT_ID);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", fixture_root)
+        .env("CLAUDE_CONFIG_DIR", "/tmp/missing-claude")
+        .arg(format!("codex://{REAL_FIXTURE_MAIN_ID}"))
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: subagent_index"))
+        .stdout(predicate::str::contains("subagents:"))
+        .stdout(predicate::str::contains(subagent_uri))
+        .stdout(predicate::str::contains("# Subagent Status").not());
+}
+
+#[test]
+fn codex_real_fixture_subagent_detail_outputs_markdown() {
+    let fixture_root = codex_real_fixture_root();
+    assert!(fixture_root.exists(), "fixture root must exist");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", fixture_root)
+        .env("CLAUDE_CONFIG_DIR", "/tmp/missing-claude")
+        .arg(format!(
+            "codex://{REAL_FIXTURE_MAIN_ID}/{REAL_FIXTURE_AGENT_ID}"
+        ))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Subagent Thread"))
+        .stdout(predicate::str::contains("## Lifecycle (Parent Thread)"));
+}
+
+#[test]
+fn list_flag_is_rejected() {
+    let temp = setup_codex_subagent_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(codex_subagent_uri())
+        .arg("--list")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unexpected argument '--list'"));
+}
+
+#[test]
+fn missing_thread_returns_non_zero() {
+    let temp = tempdir().expect("tempdir");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .env("CLAUDE_CONFIG_DIR", temp.path())
+        .arg(codex_uri())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("thread not found"));
+}
+
+#[test]
+fn amp_outputs_markdown() {
+    let temp = setup_amp_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .env("CODEX_HOME", temp.path().join("missing-codex"))
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(amp_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Thread"))
+        .stdout(predicate::str::contains("## 1. User"))
+        .stdout(predicate::str::contains("hello"))
+        .stdout(predicate::str::contains("analyze"))
+        .stdout(predicate::str::contains("world"));
+}
+
+#[cfg(unix)]
+#[test]
+fn amp_fetches_missing_thread_from_api_and_caches_it() {
+    let temp = tempdir().expect("tempdir");
+    let mock = setup_mock_bins(&[(
+        "curl",
+        r#"grep -q "Authorization: Bearer amp-token" || exit 22
+case "$*" in
+  *"https://amp.test/api/threads/T-019c0797-c402-7389-bd80-d785c98df295"*) ;;
+  *) exit 22 ;;
+esac
+echo '{"id":"T-019c0797-c402-7389-bd80-d785c98df295","messages":[{"role":"user","content":[{"type":"text","text":"from api"}]}]}'"#,
+    )]);
+    let cache_root = temp.path().join("amp-cache");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .env("XURL_AMP_CACHE_ROOT", &cache_root)
+        .env("XURL_AMP_API_TOKEN", "amp-token")
+        .env("XURL_AMP_API_URL", "https://amp.test/")
+        .env("XURL_CURL_BIN", mock.path().join("curl"))
+        .arg(amp_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("from api"));
+    assert!(
+        cache_root
+            .join(format!("threads/{AMP_SESSION_ID}.json"))
+            .exists()
+    );
+
+    // Cached threads resolve without the token.
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .env("XURL_AMP_CACHE_ROOT", &cache_root)
+        .env_remove("XURL_AMP_API_TOKEN")
+        .arg(amp_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("from api"));
+}
+
+#[test]
+fn amp_missing_thread_without_token_returns_not_found() {
+    let temp = tempdir().expect("tempdir");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .env("XURL_AMP_CACHE_ROOT", temp.path().join("amp-cache"))
+        .env_remove("XURL_AMP_API_TOKEN")
+        .arg(amp_uri())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "thread not found for provider=amp",
+        ));
+}
+
+#[test]
+fn amp_head_outputs_subagent_index() {
+    let temp = setup_amp_subagent_tree();
+    let subagent_uri = agents_child_uri("amp", AMP_SESSION_ID, AMP_SUBAGENT_ID);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .env("CODEX_HOME", temp.path().join("missing-codex"))
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(agents_uri("amp", AMP_SESSION_ID))
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: subagent_index"))
+        .stdout(predicate::str::contains("subagents:"))
+        .stdout(predicate::str::contains(format!(
+            "related:\n- uri: agents://amp/{AMP_SUBAGENT_ID}\n  type: handoff\n"
+        )))
+        .stdout(predicate::str::contains(subagent_uri))
+        .stdout(predicate::str::contains("# Subagent Status").not());
+}
+
+#[test]
+fn amp_head_discovery_supports_missing_role_fallback() {
+    let temp = setup_amp_subagent_tree_missing_role();
+    let subagent_uri = agents_child_uri("amp", AMP_SESSION_ID, AMP_SUBAGENT_ID);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .env("CODEX_HOME", temp.path().join("missing-codex"))
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(agents_uri("amp", AMP_SESSION_ID))
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: subagent_index"))
+        .stdout(predicate::str::contains("subagents:"))
+        .stdout(predicate::str::contains(subagent_uri));
+}
+
+#[test]
+fn amp_subagent_head_outputs_header_only() {
+    let temp = setup_amp_subagent_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .env("CODEX_HOME", temp.path().join("missing-codex"))
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(amp_subagent_uri())
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: subagent_detail"))
+        .stdout(predicate::str::contains(format!(
+            "agent_id: {AMP_SUBAGENT_ID}"
+        )))
+        .stdout(predicate::str::contains("status:"))
+        .stdout(predicate::str::contains("# Subagent Thread").not());
+}
+
+#[test]
+fn amp_subagent_outputs_markdown_view() {
+    let temp = setup_amp_subagent_tree();
+    let main_uri = agents_uri("amp", AMP_SESSION_ID);
+    let subagent_uri = agents_child_uri("amp", AMP_SESSION_ID, AMP_SUBAGENT_ID);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .env("CODEX_HOME", temp.path().join("missing-codex"))
+        .env("CLAUDE_CONFIG_DIR", temp.path().join("missing-claude"))
+        .arg(agents_amp_subagent_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Subagent Thread"))
+        .stdout(predicate::str::contains(format!(
+            "- Main Thread: `{main_uri}`"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "- Subagent Thread: `{subagent_uri}`"
+        )))
+        .stdout(predicate::str::contains("- Relation: `validated`"))
+        .stdout(predicate::str::contains("## Lifecycle (Parent Thread)"))
+        .stdout(predicate::str::contains("## Thread Excerpt (Child Thread)"));
+}
+
+#[test]
+fn gemini_outputs_markdown() {
+    let temp = setup_gemini_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("GEMINI_CLI_HOME", temp.path())
+        .arg(gemini_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Thread"))
+        .stdout(predicate::str::contains("## 1. User"))
+        .stdout(predicate::str::contains("hello"))
+        .stdout(predicate::str::contains("world"));
+}
+
+#[test]
+fn gemini_head_outputs_subagent_discovery() {
+    let temp = setup_gemini_subagent_tree();
+    let main_uri = agents_uri("gemini", GEMINI_SESSION_ID);
+    let child_uri = agents_child_uri("gemini", GEMINI_SESSION_ID, GEMINI_CHILD_SESSION_ID);
+    let missing_uri =
+        agents_child_uri("gemini", GEMINI_SESSION_ID, GEMINI_MISSING_CHILD_SESSION_ID);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("GEMINI_CLI_HOME", temp.path())
+        .arg(main_uri)
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: subagent_index"))
+        .stdout(predicate::str::contains("subagents:"))
+        .stdout(predicate::str::contains(child_uri))
+        .stdout(predicate::str::contains(missing_uri))
+        .stdout(predicate::str::contains("status: notFound"))
+        .stdout(predicate::str::contains("warnings:"));
+}
+
+#[test]
+fn gemini_head_outputs_subagent_discovery_from_ndjson_logs() {
+    let temp = setup_gemini_subagent_tree_with_ndjson_logs();
+    let main_uri = agents_uri("gemini", GEMINI_SESSION_ID);
+    let child_uri = agents_child_uri("gemini", GEMINI_SESSION_ID, GEMINI_CHILD_SESSION_ID);
+    let missing_uri =
+        agents_child_uri("gemini", GEMINI_SESSION_ID, GEMINI_MISSING_CHILD_SESSION_ID);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("GEMINI_CLI_HOME", temp.path())
+        .arg(main_uri)
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: subagent_index"))
+        .stdout(predicate::str::contains("subagents:"))
+        .stdout(predicate::str::contains(child_uri))
+        .stdout(predicate::str::contains(missing_uri))
+        .stdout(predicate::str::contains("status: notFound"));
+}
+
+#[test]
+fn gemini_subagent_outputs_markdown_view() {
+    let temp = setup_gemini_subagent_tree();
+    let main_uri = agents_uri("gemini", GEMINI_SESSION_ID);
+    let subagent_uri = agents_child_uri("gemini", GEMINI_SESSION_ID, GEMINI_CHILD_SESSION_ID);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("GEMINI_CLI_HOME", temp.path())
+        .arg(agents_gemini_subagent_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Subagent Thread"))
+        .stdout(predicate::str::contains(format!(
+            "- Main Thread: `{main_uri}`"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "- Subagent Thread: `{subagent_uri}`"
+        )))
+        .stdout(predicate::str::contains("## Thread Excerpt (Child Thread)"));
+}
+
+#[test]
+fn gemini_missing_subagent_outputs_not_found_markdown() {
+    let temp = setup_gemini_subagent_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("GEMINI_CLI_HOME", temp.path())
+        .arg(gemini_missing_subagent_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Subagent Thread"))
+        .stdout(predicate::str::contains(
+            "- Status: `notFound` (`inferred`)",
+        ))
+        .stdout(predicate::str::contains(
+            "_No child thread messages found._",
+        ));
+}
+
+#[test]
+fn pi_outputs_markdown_from_latest_leaf() {
+    let temp = setup_pi_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg(pi_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Thread"))
+        .stdout(predicate::str::contains("## Timeline"))
+        .stdout(predicate::str::contains("root"))
+        .stdout(predicate::str::contains("branch two done"));
+}
+
+#[test]
+fn pi_entry_outputs_markdown_from_requested_leaf() {
+    let temp = setup_pi_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg(pi_entry_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Thread"))
+        .stdout(predicate::str::contains("branch one done"))
+        .stdout(predicate::str::contains("branch two done").not());
+}
+
+#[test]
+fn pi_branches_renders_every_leaf_with_divergence_point() {
+    let temp = setup_pi_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg(pi_uri())
+        .arg("--branches")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: pi_entry_index"))
+        .stdout(predicate::str::contains(format!(
+            "## Branch 1: `agents://pi/{PI_SESSION_ID}/f1b2c3d4`\n\n- Diverges after: `root`"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "## Branch 2: `agents://pi/{PI_SESSION_ID}/d1b2c3d4`\n\n- Diverges after: `agents://pi/{PI_SESSION_ID}/b1b2c3d4`\n\n### 3. User\n\nbranch one\n\n### 4. Assistant\n\nbranch one done"
+        )));
+}
+
+#[test]
+fn pi_branches_rejects_other_providers() {
+    let temp = setup_codex_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(codex_uri())
+        .arg("--branches")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "error: invalid mode: branch listing requires a pi main thread URI",
+        ));
+}
+
+#[test]
+fn diff_compares_pi_branches_after_common_ancestor() {
+    let temp = setup_pi_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg("diff")
+        .arg(pi_entry_uri())
+        .arg(format!("pi://{PI_SESSION_ID}/f1b2c3d4"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: pi_branch_diff"))
+        .stdout(predicate::str::contains(format!(
+            "- Common ancestor: `agents://pi/{PI_SESSION_ID}/b1b2c3d4` (2 shared entries)"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "## Only in A: `agents://pi/{PI_SESSION_ID}/{PI_ENTRY_ID}`\n\n### 3. User\n\nbranch one\n\n### 4. Assistant\n\nbranch one done"
+        )))
+        .stdout(predicate::str::contains(
+            "### 3. User\n\nbranch two\n\n### 4. Assistant\n\nbranch two done",
+        ))
+        .stdout(predicate::str::contains("root done").not());
+}
+
+#[test]
+fn diff_rejects_branches_of_different_sessions() {
+    let temp = setup_pi_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg("diff")
+        .arg(pi_entry_uri())
+        .arg(codex_uri())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "error: invalid mode: diff requires two pi entry URIs of the same session",
+        ));
+}
+
+#[test]
+fn pi_head_outputs_entries() {
+    let temp = setup_pi_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg(pi_uri())
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: pi_entry_index"))
+        .stdout(predicate::str::contains("entries:"))
+        .stdout(predicate::str::contains(format!(
+            "uri: agents://pi/{PI_SESSION_ID}/a1b2c3d4"
+        )))
+        .stdout(predicate::str::contains("is_leaf: true"));
+}
+
+#[test]
+fn pi_head_outputs_entries_and_child_sessions() {
+    let temp = setup_pi_tree_with_child_sessions();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg(pi_uri())
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: pi_entry_index"))
+        .stdout(predicate::str::contains("entries:"))
+        .stdout(predicate::str::contains("subagents:"))
+        .stdout(predicate::str::contains(format!(
+            "uri: agents://pi/{PI_SESSION_ID}/{PI_CHILD_SESSION_ID}"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "uri: agents://pi/{PI_SESSION_ID}/{PI_MISSING_CHILD_SESSION_ID}"
+        )))
+        .stdout(predicate::str::contains("status: completed"))
+        .stdout(predicate::str::contains("status: notFound"))
+        .stdout(predicate::str::contains("warnings:"));
+}
+
+#[test]
+fn head_status_filter_limits_the_subagent_index() {
+    let temp = setup_pi_tree_with_child_sessions();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .args([
+            pi_uri().as_str(),
+            "-I",
+            "--status",
+            "notFound",
+            "--sort",
+            "started",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "uri: agents://pi/{PI_SESSION_ID}/{PI_MISSING_CHILD_SESSION_ID}"
+        )))
+        .stdout(
+            predicate::str::contains(format!(
+                "agents://pi/{PI_SESSION_ID}/{PI_CHILD_SESSION_ID}\n  status:"
+            ))
+            .not(),
+        )
+        .stdout(predicate::str::contains(format!(
+            "subagent_totals:\n  count: 2\n  by_status:\n    completed: 1\n    notFound: 1\n  duration: 1s\n  longest:\n    agent_id: {PI_CHILD_SESSION_ID}\n"
+        )));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .args([pi_uri().as_str(), "--sort", "status"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("add -I/--head"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .args([pi_uri().as_str(), "-I", "--sort", "size"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unsupported --sort value: size"));
+}
+
+#[test]
+fn pi_child_session_outputs_subagent_markdown_view() {
+    let temp = setup_pi_tree_with_child_sessions();
+    let main_uri = agents_uri("pi", PI_SESSION_ID);
+    let child_uri = agents_child_uri("pi", PI_SESSION_ID, PI_CHILD_SESSION_ID);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg(&child_uri)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Subagent Thread"))
+        .stdout(predicate::str::contains(format!(
+            "- Main Thread: `{main_uri}`"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "- Subagent Thread: `{child_uri}`"
+        )))
+        .stdout(predicate::str::contains("child done"))
+        .stdout(predicate::str::contains("## Thread Excerpt (Child Thread)"));
+}
+
+#[test]
+fn pi_child_session_head_outputs_subagent_detail() {
+    let temp = setup_pi_tree_with_child_sessions();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg(pi_child_session_uri())
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: subagent_detail"))
+        .stdout(predicate::str::contains(format!(
+            "agent_id: {PI_CHILD_SESSION_ID}"
+        )))
+        .stdout(predicate::str::contains("status: completed"))
+        .stdout(predicate::str::contains("# Subagent Thread").not());
+}
+
+#[test]
+fn pi_missing_child_session_head_reports_not_found_with_evidence() {
+    let temp = setup_pi_tree_with_child_sessions();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg(pi_missing_child_session_uri())
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: subagent_detail"))
+        .stdout(predicate::str::contains(format!(
+            "agent_id: {PI_MISSING_CHILD_SESSION_ID}"
+        )))
+        .stdout(predicate::str::contains("status: notFound"))
+        .stdout(predicate::str::contains("warnings:"))
+        .stdout(predicate::str::contains(
+            "relation hint references child_session_id",
+        ));
+}
+
+#[test]
+fn pi_head_entry_outputs_header_only() {
+    let temp = setup_pi_tree();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg(pi_entry_uri())
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: pi_entry"))
+        .stdout(predicate::str::contains(format!("entry_id: {PI_ENTRY_ID}")))
+        .stdout(predicate::str::contains("# Thread").not());
+}
+
+#[test]
+fn pi_real_fixture_outputs_markdown() {
+    let fixture_root = pi_real_fixture_root();
+    assert!(fixture_root.exists(), "fixture root must exist");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PI_CODING_AGENT_DIR", fixture_root)
+        .arg(pi_real_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Thread"))
+        .stdout(predicate::str::contains("## 1. User"))
+        .stdout(predicate::str::contains("## 2. Assistant"));
+}
+
+#[test]
+fn claude_subagent_outputs_markdown_view() {
+    let temp = setup_claude_subagent_tree();
+    let main_uri = agents_uri("claude", CLAUDE_SESSION_ID);
+    let subagent_uri = agents_child_uri("claude", CLAUDE_SESSION_ID, CLAUDE_AGENT_ID);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", temp.path())
+        .env("CODEX_HOME", temp.path().join("missing-codex"))
+        .arg(claude_subagent_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Subagent Thread"))
+        .stdout(predicate::str::contains(format!(
+            "- Main Thread: `{main_uri}`"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "- Subagent Thread: `{subagent_uri}`"
+        )))
+        .stdout(predicate::str::contains("## Agent Status Summary"));
+}
+
+#[test]
+fn claude_real_fixture_head_includes_subagents() {
+    let fixture_root = claude_real_fixture_root();
+    assert!(fixture_root.exists(), "fixture root must exist");
+    let subagent_uri = agents_child_uri("claude", CLAUDE_REAL_MAIN_ID, CLAUDE_REAL_AGENT_ID);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", fixture_root)
+        .env("CODEX_HOME", "/tmp/missing-codex")
+        .arg(claude_real_uri())
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: subagent_index"))
+        .stdout(predicate::str::contains("subagents:"))
+        .stdout(predicate::str::contains(subagent_uri))
+        .stdout(predicate::str::contains("# Subagent Status").not());
+}
+
+#[test]
+fn claude_real_fixture_subagent_detail_outputs_markdown() {
+    let fixture_root = claude_real_fixture_root();
+    assert!(fixture_root.exists(), "fixture root must exist");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CLAUDE_CONFIG_DIR", fixture_root)
+        .env("CODEX_HOME", "/tmp/missing-codex")
+        .arg(claude_real_subagent_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Subagent Thread"))
+        .stdout(predicate::str::contains("## Thread Excerpt (Child Thread)"));
+}
+
+#[test]
+fn opencode_subagent_head_includes_subagents_and_warnings() {
+    let temp = setup_opencode_subagent_tree();
+    let child_uri = agents_child_uri(
+        "opencode",
+        OPENCODE_MAIN_SESSION_ID,
+        OPENCODE_CHILD_SESSION_ID,
+    );
+    let empty_child_uri = agents_child_uri(
+        "opencode",
+        OPENCODE_MAIN_SESSION_ID,
+        OPENCODE_CHILD_EMPTY_SESSION_ID,
+    );
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .arg(agents_uri("opencode", OPENCODE_MAIN_SESSION_ID))
+        .arg("--head")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: subagent_index"))
+        .stdout(predicate::str::contains("subagents:"))
+        .stdout(predicate::str::contains(child_uri))
+        .stdout(predicate::str::contains(empty_child_uri))
+        .stdout(predicate::str::contains("status: completed"))
+        .stdout(predicate::str::contains("status: pendingInit"))
+        .stdout(predicate::str::contains("warnings:"))
+        .stdout(predicate::str::contains(format!(
+            "child session_id={OPENCODE_CHILD_EMPTY_SESSION_ID} has no materialized messages in sqlite"
+        )));
+}
+
+#[test]
+fn opencode_subagent_outputs_markdown_view() {
+    let temp = setup_opencode_subagent_tree();
+    let main_uri = agents_uri("opencode", OPENCODE_MAIN_SESSION_ID);
+    let subagent_uri = agents_child_uri(
+        "opencode",
+        OPENCODE_MAIN_SESSION_ID,
+        OPENCODE_CHILD_SESSION_ID,
+    );
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .arg(&subagent_uri)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Subagent Thread"))
+        .stdout(predicate::str::contains(format!(
+            "- Main Thread: `{main_uri}`"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "- Subagent Thread: `{subagent_uri}`"
+        )))
+        .stdout(predicate::str::contains(
+            "- Status: `completed` (`child_rollout`)",
+        ))
+        .stdout(predicate::str::contains(
+            "- Evidence: opencode sqlite relation validated via session.parent_id",
+        ))
+        .stdout(predicate::str::contains("child completed"));
+}
+
+#[test]
+fn opencode_subagent_not_found_outputs_markdown_view() {
+    let temp = setup_opencode_subagent_tree();
+    let missing_child = "ses_5x7md9kx3c9p";
+    let missing_uri = agents_child_uri("opencode", OPENCODE_MAIN_SESSION_ID, missing_child);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XDG_DATA_HOME", temp.path())
+        .arg(&missing_uri)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Subagent Thread"))
+        .stdout(predicate::str::contains(format!(
+            "- Subagent Thread: `{missing_uri}`"
+        )))
+        .stdout(predicate::str::contains("- Status: `notFound` (`inferred`)"))
+        .stdout(predicate::str::contains("_No child thread messages found._"))
+        .stdout(predicate::str::contains(format!(
+            "agent not found for main_session_id={OPENCODE_MAIN_SESSION_ID} agent_id={missing_child}"
+        )));
+}
+
+#[test]
+fn gemini_real_fixture_outputs_markdown() {
+    let fixture_root = gemini_real_fixture_root();
+    assert!(fixture_root.exists(), "fixture root must exist");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("GEMINI_CLI_HOME", fixture_root)
+        .arg(gemini_real_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Thread"))
+        .stdout(predicate::str::contains("## 1. User"));
+}
+
+#[test]
+fn opencode_real_fixture_outputs_markdown() {
     let fixture_root = opencode_real_fixture_root();
     assert!(fixture_root.exists(), "fixture root must exist");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("XDG_DATA_HOME", fixture_root)
-        .arg(opencode_real_uri())
+    cmd.env("XDG_DATA_HOME", fixture_root)
+        .arg(opencode_real_uri())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Thread"))
+        .stdout(predicate::str::contains("## 1. User"));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_create_streams_output_and_prints_uri() {
+    let mock = setup_mock_bins(&[(
+        "codex",
+        r#"
+if [ "$1" = "exec" ] && [ "$2" = "--json" ]; then
+  echo '{"type":"thread.started","thread_id":"11111111-1111-4111-8111-111111111111"}'
+  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"hello from create"}}'
+  exit 0
+fi
+echo "unexpected args: $*" >&2
+exit 7
+"#,
+    )]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("agents://codex")
+        .arg("-d")
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("hello from create"))
+        .stderr(predicate::str::contains(
+            "created: agents://codex/11111111-1111-4111-8111-111111111111",
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .args(["agents://codex", "-d", "hello", "-q", "--print-uri"])
+        .assert()
+        .success()
+        .stdout("hello from create\nagents://codex/11111111-1111-4111-8111-111111111111\n")
+        .stderr("");
+}
+
+#[cfg(unix)]
+#[test]
+fn write_print_uri_only_prints_nothing_but_the_uri() {
+    let mock = setup_mock_bins(&[(
+        "codex",
+        r#"
+if [ "$1" = "exec" ] && [ "$2" = "--json" ]; then
+  echo '{"type":"thread.started","thread_id":"11111111-1111-4111-8111-111111111111"}'
+  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"hello from create"}}'
+  exit 0
+fi
+echo "unexpected args: $*" >&2
+exit 7
+"#,
+    )]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .args(["agents://codex", "-d", "hello", "--print-uri-only"])
+        .assert()
+        .success()
+        .stdout("agents://codex/11111111-1111-4111-8111-111111111111\n")
+        .stderr("");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .args([
+            "agents://codex",
+            "-d",
+            "hello",
+            "--print-uri-only",
+            "--dry-run",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--print-uri-only discards the reply; drop --print-uri",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_summary_reports_each_write_as_json() {
+    let mock = setup_mock_bins(&[(
+        "codex",
+        r#"
+if [ "$1" = "exec" ] && [ "$2" = "--json" ]; then
+  echo '{"type":"thread.started","thread_id":"11111111-1111-4111-8111-111111111111"}'
+  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"hello from create"}}'
+  exit 0
+fi
+echo "unexpected args: $*" >&2
+exit 7
+"#,
+    )]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .args(["agents://codex", "-d", "hello", "-q", "--write-summary", "json"])
+        .assert()
+        .success()
+        .stdout("hello from create")
+        .stderr(predicate::str::starts_with(
+            "{\"bytes_streamed\":17,\"duration_ms\":",
+        ))
+        .stderr(predicate::str::contains(
+            "\"exit_code\":0,\"provider\":\"codex\",\"session_id\":\"11111111-1111-4111-8111-111111111111\",\"truncated\":false,\"uri\":\"agents://codex/11111111-1111-4111-8111-111111111111\",\"warnings\":[]}\n",
+        ));
+
+    let temp = tempdir().expect("tempdir");
+    let summary_path = temp.path().join("summary.jsonl");
+    for _ in 0..2 {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+        cmd.env("PATH", path_with_mock(mock.path()))
+            .args(["agents://codex", "-d", "hello", "--write-summary", "json"])
+            .arg("--write-summary-file")
+            .arg(&summary_path)
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("duration_ms").not());
+    }
+    let summaries = fs::read_to_string(&summary_path).expect("read summary");
+    assert_eq!(summaries.lines().count(), 2, "{summaries}");
+    assert!(
+        summaries
+            .lines()
+            .all(|line| line.contains("\"exit_code\":0"))
+    );
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .args(["agents://codex", "-d", "hello", "--write-summary", "yaml"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unsupported --write-summary value: yaml; expected json",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_create_supports_shorthand_collection_uri() {
+    let mock = setup_mock_bins(&[(
+        "codex",
+        r#"
+if [ "$1" = "exec" ] && [ "$2" = "--json" ]; then
+  echo '{"type":"thread.started","thread_id":"11111111-1111-4111-8111-111111111111"}'
+  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"hello from create"}}'
+  exit 0
+fi
+echo "unexpected args: $*" >&2
+exit 7
+"#,
+    )]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("codex")
+        .arg("-d")
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("hello from create"))
+        .stderr(predicate::str::contains(
+            "created: agents://codex/11111111-1111-4111-8111-111111111111",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_create_with_codex_role_loads_role_config() {
+    let mock = setup_mock_bins(&[(
+        "codex",
+        r#"
+if [ "$1" != "exec" ] || [ "$2" != "--json" ]; then
+  echo "unexpected args: $*" >&2
+  exit 7
+fi
+seen_model=0
+seen_effort=0
+seen_instructions=0
+seen_prompt=0
+while [ "$#" -gt 0 ]; do
+  case "$1" in
+    --config)
+      shift
+      if [ "$1" = "model=gpt-5.3-codex" ]; then
+        seen_model=1
+      fi
+      if [ "$1" = "model_reasoning_effort=high" ]; then
+        seen_effort=1
+      fi
+      if [ "$1" = "developer_instructions=Focus on high priority issues." ]; then
+        seen_instructions=1
+      fi
+      ;;
+    hello)
+      seen_prompt=1
+      ;;
+  esac
+  shift
+done
+[ "$seen_model" -eq 1 ] || exit 8
+[ "$seen_effort" -eq 1 ] || exit 9
+[ "$seen_instructions" -eq 1 ] || exit 10
+[ "$seen_prompt" -eq 1 ] || exit 11
+echo '{"type":"thread.started","thread_id":"12345678-1111-4111-8111-111111111111"}'
+echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"role create ok"}}'
+"#,
+    )]);
+    setup_codex_role_configs(mock.path());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("CODEX_HOME", mock.path())
+        .arg("agents://codex/reviewer")
+        .arg("-d")
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("role create ok"))
+        .stderr(predicate::str::contains(
+            "created: agents://codex/12345678-1111-4111-8111-111111111111",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_append_uses_resume_and_prints_updated_uri() {
+    let mock = setup_mock_bins(&[(
+        "codex",
+        r#"
+if [ "$1" = "exec" ] && [ "$2" = "resume" ] && [ "$3" = "--json" ]; then
+  echo "{\"type\":\"thread.started\",\"thread_id\":\"$4\"}"
+  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"hello from append"}}'
+  exit 0
+fi
+echo "unexpected args: $*" >&2
+exit 7
+"#,
+    )]);
+    let target = "agents://codex/22222222-2222-4222-8222-222222222222";
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg(target)
+        .arg("--data")
+        .arg("continue")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("hello from append"))
+        .stderr(predicate::str::contains(
+            "updated: agents://codex/22222222-2222-4222-8222-222222222222",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_create_passthroughs_all_query_options_without_normalization() {
+    let workdir_text = "/tmp/workdir".to_string();
+    let add_dir_a_text = "/tmp/add-a".to_string();
+    let add_dir_b_text = "/tmp/add-b".to_string();
+    let script = format!(
+        r#"
+if [ "$1" != "exec" ] || [ "$2" != "--json" ]; then
+  echo "unexpected args: $*" >&2
+  exit 7
+fi
+found_workdir=0
+found_model=0
+found_flag=0
+count_add_dir=0
+count_json=0
+count_json_with_value=0
+prompt_seen=0
+while [ "$#" -gt 0 ]; do
+  case "$1" in
+    --workdir)
+      shift
+      [ "$1" = "{workdir_text}" ] || exit 9
+      found_workdir=1
+      ;;
+    --add_dir)
+      shift
+      if [ "$1" = "{add_dir_a_text}" ] || [ "$1" = "{add_dir_b_text}" ]; then
+        count_add_dir=$((count_add_dir + 1))
+      else
+        echo "unexpected add dir: $1" >&2
+        exit 10
+      fi
+      ;;
+    --config)
+      shift
+      [ "$1" = "model=gpt-5" ] || exit 11
+      found_model=1
+      ;;
+    --flag)
+      found_flag=1
+      ;;
+    --json)
+      count_json=$((count_json + 1))
+      if [ "$2" = "1" ]; then
+        shift
+        count_json_with_value=$((count_json_with_value + 1))
+      fi
+      ;;
+    hello)
+      prompt_seen=1
+      ;;
+  esac
+  shift
+done
+if [ "$found_workdir" -ne 1 ] || [ "$count_add_dir" -ne 2 ] || [ "$found_model" -ne 1 ] || [ "$found_flag" -ne 1 ] || [ "$count_json" -ne 2 ] || [ "$count_json_with_value" -ne 1 ] || [ "$prompt_seen" -ne 1 ]; then
+  echo "missing expected flags" >&2
+  exit 12
+fi
+echo '{{"type":"thread.started","thread_id":"66666666-6666-4666-8666-666666666666"}}'
+echo '{{"type":"item.completed","item":{{"id":"item_1","type":"agent_message","text":"query options ok"}}}}'
+"#,
+    );
+    let mock = setup_mock_bins(&[("codex", script.as_str())]);
+
+    let target = format!(
+        "agents://codex?workdir={}&add_dir={}&add_dir={}&model=gpt-5&flag&json=1",
+        encode_query_component(&workdir_text),
+        encode_query_component(&add_dir_a_text),
+        encode_query_component(&add_dir_b_text),
+    );
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg(target)
+        .arg("-d")
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("query options ok"))
+        .stderr(predicate::str::contains("reserved by xurl").not())
+        .stderr(predicate::str::contains(
+            "created: agents://codex/66666666-6666-4666-8666-666666666666",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_append_passthroughs_query_options() {
+    let target_session = "22222222-2222-4222-8222-222222222222";
+    let script = format!(
+        r#"
+if [ "$1" != "exec" ] || [ "$2" != "resume" ] || [ "$3" != "--json" ]; then
+  echo "unexpected args: $*" >&2
+  exit 7
+fi
+count_workdir=0
+found_flag=0
+found_prompt=0
+while [ "$#" -gt 0 ]; do
+  case "$1" in
+    --workdir)
+      shift
+      if [ "$1" = "/tmp/a" ] || [ "$1" = "/tmp/b" ]; then
+        count_workdir=$((count_workdir + 1))
+      else
+        exit 8
+      fi
+      ;;
+    --flag)
+      found_flag=1
+      ;;
+    "{target_session}")
+      ;;
+    continue)
+      found_prompt=1
+      ;;
+  esac
+  shift
+done
+[ "$count_workdir" -eq 2 ] || exit 9
+[ "$found_flag" -eq 1 ] || exit 10
+[ "$found_prompt" -eq 1 ] || exit 11
+echo '{{"type":"thread.started","thread_id":"{target_session}"}}'
+echo '{{"type":"item.completed","item":{{"id":"item_1","type":"agent_message","text":"append passthrough query"}}}}'
+"#,
+    );
+    let mock = setup_mock_bins(&[("codex", script.as_str())]);
+    let target = format!(
+        "agents://codex/{target_session}?workdir={}&workdir={}&flag",
+        encode_query_component("/tmp/a"),
+        encode_query_component("/tmp/b"),
+    );
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg(target)
+        .arg("--data")
+        .arg("continue")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("append passthrough query"))
+        .stderr(predicate::str::contains("ignored query parameter").not())
+        .stderr(predicate::str::contains(format!(
+            "updated: agents://codex/{target_session}",
+        )));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_amp_passthroughs_workdir_and_add_dir_query_parameters() {
+    let workdir_text = "/tmp/amp-workdir".to_string();
+    let add_dir_text = "/tmp/amp-add".to_string();
+    let script = format!(
+        r#"
+if [ "$1" != "-x" ] || [ "$2" != "hello" ] || [ "$3" != "--stream-json" ]; then
+  echo "unexpected args: $*" >&2
+  exit 7
+fi
+seen_workdir=0
+seen_add_dir=0
+seen_foo=0
+while [ "$#" -gt 0 ]; do
+  case "$1" in
+    --workdir)
+      shift
+      [ "$1" = "{workdir_text}" ] || exit 8
+      seen_workdir=1
+      ;;
+    --add_dir)
+      shift
+      [ "$1" = "{add_dir_text}" ] || exit 9
+      seen_add_dir=1
+      ;;
+    --foo)
+      shift
+      [ "$1" = "bar" ] || exit 10
+      seen_foo=1
+      ;;
+    *)
+      ;;
+  esac
+  shift
+done
+[ "$seen_workdir" -eq 1 ] || exit 11
+[ "$seen_add_dir" -eq 1 ] || exit 12
+[ "$seen_foo" -eq 1 ] || exit 13
+echo '{{"type":"system","subtype":"init","session_id":"T-77777777-7777-4777-8777-777777777777"}}'
+echo '{{"type":"assistant","session_id":"T-77777777-7777-4777-8777-777777777777","message":{{"content":[{{"type":"text","text":"passthrough-ok"}}]}}}}'
+echo '{{"type":"result","subtype":"success","session_id":"T-77777777-7777-4777-8777-777777777777","result":"ok"}}'
+"#,
+    );
+    let mock = setup_mock_bins(&[("amp", script.as_str())]);
+    let target = format!(
+        "agents://amp?workdir={}&add_dir={}&foo=bar",
+        encode_query_component(&workdir_text),
+        encode_query_component(&add_dir_text),
+    );
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg(target)
+        .arg("-d")
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("passthrough-ok"))
+        .stderr(predicate::str::contains("ignored query parameter `add_dir`").not())
+        .stderr(predicate::str::contains(
+            "created: agents://amp/T-77777777-7777-4777-8777-777777777777",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_data_file_and_stdin_are_supported() {
+    let mock = setup_mock_bins(&[(
+        "codex",
+        r#"
+if [ "$1" != "exec" ] || [ "$2" != "--json" ]; then
+  echo "unexpected args: $*" >&2
+  exit 7
+fi
+if [ "$3" = "from-file" ]; then
+  echo '{"type":"thread.started","thread_id":"33333333-3333-4333-8333-333333333333"}'
+  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"file-ok"}}'
+  exit 0
+fi
+if [ "$3" = "from-stdin" ]; then
+  echo '{"type":"thread.started","thread_id":"44444444-4444-4444-8444-444444444444"}'
+  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"stdin-ok"}}'
+  exit 0
+fi
+echo "unexpected prompt: $3" >&2
+exit 8
+"#,
+    )]);
+
+    let prompt_file_dir = tempdir().expect("tempdir");
+    let prompt_file = prompt_file_dir.path().join("prompt.txt");
+    fs::write(&prompt_file, "from-file").expect("write prompt");
+
+    let mut from_file = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    from_file
+        .env("PATH", path_with_mock(mock.path()))
+        .arg("agents://codex")
+        .arg("-d")
+        .arg(format!("@{}", prompt_file.display()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("file-ok"));
+
+    let mut from_stdin = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    from_stdin
+        .env("PATH", path_with_mock(mock.path()))
+        .arg("agents://codex")
+        .arg("-d")
+        .arg("@-")
+        .write_stdin("from-stdin")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("stdin-ok"));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_reads_prompt_from_piped_stdin() {
+    let temp = setup_codex_tree();
+    let mock = setup_mock_bins(&[(
+        "codex",
+        r#"
+if [ "$1" = "exec" ] && [ "$2" = "--json" ] && [ "$3" = "piped prompt" ]; then
+  echo '{"type":"thread.started","thread_id":"55555555-5555-4555-8555-555555555555"}'
+  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"piped-ok"}}'
+  exit 0
+fi
+echo "unexpected args: $*" >&2
+exit 7
+"#,
+    )]);
+
+    let mut piped = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    piped
+        .env("PATH", path_with_mock(mock.path()))
+        .env("CODEX_HOME", temp.path())
+        .arg("agents://codex")
+        .write_stdin("piped prompt")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("piped-ok"))
+        .stderr(predicate::str::contains(
+            "created: agents://codex/55555555-5555-4555-8555-555555555555",
+        ));
+
+    let mut opted_out = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    opted_out
+        .env("PATH", path_with_mock(mock.path()))
+        .env("CODEX_HOME", temp.path())
+        .arg("agents://codex")
+        .arg("--no-stdin")
+        .write_stdin("piped prompt")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Threads"))
+        .stdout(predicate::str::contains("piped-ok").not());
+
+    let mut blank = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    blank
+        .env("PATH", path_with_mock(mock.path()))
+        .env("CODEX_HOME", temp.path())
+        .arg("agents://codex")
+        .write_stdin("\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Threads"));
+}
+
+#[cfg(unix)]
+#[test]
+fn piped_stdin_never_turns_a_thread_read_into_a_write() {
+    let temp = setup_codex_tree();
+    let mock = setup_mock_bins(&[("codex", "echo called >> \"$0.calls\"\nexit 7\n")]);
+    let rollout = temp.path().join(format!(
+        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+    ));
+    let before = fs::read_to_string(&rollout).expect("read rollout");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("CODEX_HOME", temp.path())
+        .arg(codex_uri())
+        .write_stdin("please do not send me")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# Thread"))
+        .stderr(predicate::str::contains("updated:").not());
+
+    assert!(
+        !mock.path().join("codex.calls").exists(),
+        "the provider CLI must not run for a read"
+    );
+    assert_eq!(fs::read_to_string(&rollout).expect("read rollout"), before);
+}
+
+#[cfg(unix)]
+#[test]
+fn interactive_write_resumes_session_for_each_stdin_line() {
+    let mock = setup_mock_bins(&[(
+        "claude",
+        r#"
+last=""
+resume=""
+while [ "$#" -gt 0 ]; do
+  case "$1" in
+    --resume) shift; resume="$1" ;;
+  esac
+  last="$1"
+  shift
+done
+case "$last" in
+  "first prompt") [ -z "$resume" ] || exit 8 ;;
+  "second prompt") [ "$resume" = "aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa" ] || exit 9 ;;
+  *) echo "unexpected prompt: $last" >&2; exit 7 ;;
+esac
+echo '{"type":"system","subtype":"init","session_id":"aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa"}'
+echo "{\"type\":\"assistant\",\"session_id\":\"aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa\",\"message\":{\"content\":[{\"type\":\"text\",\"text\":\"reply to $last\"}]}}"
+"#,
+    )]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("agents://claude")
+        .arg("--interactive")
+        .write_stdin("first prompt\n\nsecond prompt\n")
+        .assert()
+        .success()
+        .stdout("reply to first prompt\n\nreply to second prompt")
+        .stderr(predicate::str::contains(
+            "created: agents://claude/aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa",
+        ))
+        .stderr(predicate::str::contains(
+            "updated: agents://claude/aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa",
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("agents://claude")
+        .args(["--interactive", "-I"])
         .assert()
-        .success()
-        .stdout(predicate::str::contains("# Thread"))
-        .stdout(predicate::str::contains("## 1. User"));
+        .failure()
+        .stderr(predicate::str::contains("--interactive is a write mode"));
 }
 
 #[cfg(unix)]
 #[test]
-fn write_create_streams_output_and_prints_uri() {
+fn write_retries_transient_failures_before_session_start() {
+    let state = tempdir().expect("tempdir");
+    let counter = state.path().join("attempts");
     let mock = setup_mock_bins(&[(
         "codex",
-        r#"
-if [ "$1" = "exec" ] && [ "$2" = "--json" ]; then
-  echo '{"type":"thread.started","thread_id":"11111111-1111-4111-8111-111111111111"}'
-  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"hello from create"}}'
-  exit 0
+        &format!(
+            r#"
+count=$(cat "{counter}" 2>/dev/null || echo 0)
+count=$((count + 1))
+echo "$count" > "{counter}"
+if [ "$count" -lt 2 ]; then
+  echo "rate limited" >&2
+  exit 1
 fi
-echo "unexpected args: $*" >&2
-exit 7
+echo '{{"type":"thread.started","thread_id":"66666666-6666-4666-8666-666666666666"}}'
+echo '{{"type":"item.completed","item":{{"id":"item_1","type":"agent_message","text":"retry-ok"}}}}'
 "#,
+            counter = counter.display()
+        ),
     )]);
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("PATH", path_with_mock(mock.path()))
         .arg("agents://codex")
-        .arg("-d")
-        .arg("hello")
+        .args(["-d", "hello", "--retries", "2"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("hello from create"))
+        .stdout(predicate::str::contains("retry-ok"))
+        .stderr(predicate::str::contains("warning: attempt 1 of 3 failed"))
+        .stderr(predicate::str::contains("rate limited"))
         .stderr(predicate::str::contains(
-            "created: agents://codex/11111111-1111-4111-8111-111111111111",
+            "warning: write succeeded on attempt 2 of 3",
         ));
+
+    fs::write(&counter, "-5").expect("reset counter");
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("agents://codex")
+        .args(["-d", "hello"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("rate limited"));
+    assert_eq!(
+        fs::read_to_string(&counter).expect("read counter").trim(),
+        "-4"
+    );
 }
 
 #[cfg(unix)]
 #[test]
-fn write_create_supports_shorthand_collection_uri() {
+fn write_budget_stops_runaway_provider() {
     let mock = setup_mock_bins(&[(
         "codex",
         r#"
-if [ "$1" = "exec" ] && [ "$2" = "--json" ]; then
-  echo '{"type":"thread.started","thread_id":"11111111-1111-4111-8111-111111111111"}'
-  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"hello from create"}}'
-  exit 0
+echo '{"type":"thread.started","thread_id":"77777777-7777-4777-8777-777777777777"}'
+if [ "$3" = "long" ]; then
+  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"abcdefghij"}}'
 fi
-echo "unexpected args: $*" >&2
-exit 7
+exec sleep 30
 "#,
     )]);
 
+    let started = std::time::Instant::now();
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("PATH", path_with_mock(mock.path()))
-        .arg("codex")
-        .arg("-d")
-        .arg("hello")
+        .arg("agents://codex")
+        .args(["-d", "long", "--max-output-chars", "4"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("hello from create"))
+        .stdout("abcd")
         .stderr(predicate::str::contains(
-            "created: agents://codex/11111111-1111-4111-8111-111111111111",
+            "warning: write stopped early: output exceeded 4 chars (--max-output-chars)",
+        ))
+        .stderr(predicate::str::contains(
+            "created: agents://codex/77777777-7777-4777-8777-777777777777",
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("agents://codex")
+        .args(["-d", "quiet", "--max-duration", "1s"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "warning: write stopped early: provider ran longer than 1s (--max-duration)",
         ));
+    assert!(started.elapsed() < std::time::Duration::from_secs(20));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.arg("agents://codex")
+        .args(["--max-duration", "soon"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid duration `soon`"));
 }
 
 #[cfg(unix)]
 #[test]
-fn write_create_with_codex_role_loads_role_config() {
-    let mock = setup_mock_bins(&[(
-        "codex",
-        r#"
-if [ "$1" != "exec" ] || [ "$2" != "--json" ]; then
-  echo "unexpected args: $*" >&2
-  exit 7
-fi
-seen_model=0
-seen_effort=0
-seen_instructions=0
-seen_prompt=0
+fn write_maps_system_prompt_to_provider_options() {
+    let mock = setup_mock_bins(&[
+        (
+            "claude",
+            r#"
+system=""
+last=""
 while [ "$#" -gt 0 ]; do
   case "$1" in
-    --config)
-      shift
-      if [ "$1" = "model=gpt-5.3-codex" ]; then
-        seen_model=1
-      fi
-      if [ "$1" = "model_reasoning_effort=high" ]; then
-        seen_effort=1
-      fi
-      if [ "$1" = "developer_instructions=Focus on high priority issues." ]; then
-        seen_instructions=1
-      fi
-      ;;
-    hello)
-      seen_prompt=1
-      ;;
+    --append-system-prompt) shift; system="$1" ;;
   esac
+  last="$1"
   shift
 done
-[ "$seen_model" -eq 1 ] || exit 8
-[ "$seen_effort" -eq 1 ] || exit 9
-[ "$seen_instructions" -eq 1 ] || exit 10
-[ "$seen_prompt" -eq 1 ] || exit 11
-echo '{"type":"thread.started","thread_id":"12345678-1111-4111-8111-111111111111"}'
-echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"role create ok"}}'
+[ "$system" = "be terse" ] || { echo "unexpected system: $system" >&2; exit 7; }
+echo '{"type":"system","subtype":"init","session_id":"bbbbbbbb-bbbb-4bbb-8bbb-bbbbbbbbbbbb"}'
+echo '{"type":"assistant","session_id":"bbbbbbbb-bbbb-4bbb-8bbb-bbbbbbbbbbbb","message":{"content":[{"type":"text","text":"claude-system-ok"}]}}'
 "#,
-    )]);
-    setup_codex_role_configs(mock.path());
+        ),
+        (
+            "codex",
+            r#"
+case "$*" in
+  *"--config developer_instructions=cite files"*) ;;
+  *) echo "unexpected args: $*" >&2; exit 7 ;;
+esac
+echo '{"type":"thread.started","thread_id":"88888888-8888-4888-8888-888888888888"}'
+echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"codex-system-ok"}}'
+"#,
+        ),
+        (
+            "amp",
+            r#"
+case "$*" in
+  *--system*) echo "system leaked: $*" >&2; exit 7 ;;
+esac
+echo '{"type":"system","subtype":"init","session_id":"T-99999999-9999-4999-8999-999999999999"}'
+echo '{"type":"assistant","message":{"content":[{"type":"text","text":"amp-ok"}]}}'
+"#,
+        ),
+    ]);
+    let prompt_dir = tempdir().expect("tempdir");
+    let system_file = prompt_dir.path().join("system.md");
+    fs::write(&system_file, "be terse").expect("write system prompt");
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("PATH", path_with_mock(mock.path()))
-        .env("CODEX_HOME", mock.path())
-        .arg("agents://codex/reviewer")
-        .arg("-d")
-        .arg("hello")
+        .arg(format!("agents://claude?system=@{}", system_file.display()))
+        .args(["-d", "hello"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("role create ok"))
-        .stderr(predicate::str::contains(
-            "created: agents://codex/12345678-1111-4111-8111-111111111111",
-        ));
-}
+        .stdout(predicate::str::contains("claude-system-ok"));
 
-#[cfg(unix)]
-#[test]
-fn write_append_uses_resume_and_prints_updated_uri() {
-    let mock = setup_mock_bins(&[(
-        "codex",
-        r#"
-if [ "$1" = "exec" ] && [ "$2" = "resume" ] && [ "$3" = "--json" ]; then
-  echo "{\"type\":\"thread.started\",\"thread_id\":\"$4\"}"
-  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"hello from append"}}'
-  exit 0
-fi
-echo "unexpected args: $*" >&2
-exit 7
-"#,
-    )]);
-    let target = "agents://codex/22222222-2222-4222-8222-222222222222";
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("agents://codex")
+        .args(["-d", "hello", "--system", "cite files"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("codex-system-ok"));
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("PATH", path_with_mock(mock.path()))
-        .arg(target)
-        .arg("--data")
-        .arg("continue")
+        .arg("agents://amp?system=be%20terse")
+        .args(["-d", "hello"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("hello from append"))
         .stderr(predicate::str::contains(
-            "updated: agents://codex/22222222-2222-4222-8222-222222222222",
+            "warning: ignored system prompt: provider `amp` has no system prompt option",
         ));
 }
 
 #[cfg(unix)]
 #[test]
-fn write_create_passthroughs_all_query_options_without_normalization() {
-    let workdir_text = "/tmp/workdir".to_string();
-    let add_dir_a_text = "/tmp/add-a".to_string();
-    let add_dir_b_text = "/tmp/add-b".to_string();
-    let script = format!(
-        r#"
-if [ "$1" != "exec" ] || [ "$2" != "--json" ]; then
-  echo "unexpected args: $*" >&2
-  exit 7
-fi
-found_workdir=0
-found_model=0
-found_flag=0
-count_add_dir=0
-count_json=0
-count_json_with_value=0
-prompt_seen=0
-while [ "$#" -gt 0 ]; do
-  case "$1" in
-    --workdir)
-      shift
-      [ "$1" = "{workdir_text}" ] || exit 9
-      found_workdir=1
-      ;;
-    --add_dir)
-      shift
-      if [ "$1" = "{add_dir_a_text}" ] || [ "$1" = "{add_dir_b_text}" ]; then
-        count_add_dir=$((count_add_dir + 1))
-      else
-        echo "unexpected add dir: $1" >&2
-        exit 10
-      fi
-      ;;
-    --model)
-      shift
-      [ "$1" = "gpt-5" ] || exit 11
-      found_model=1
-      ;;
-    --flag)
-      found_flag=1
-      ;;
-    --json)
-      count_json=$((count_json + 1))
-      if [ "$2" = "1" ]; then
-        shift
-        count_json_with_value=$((count_json_with_value + 1))
-      fi
-      ;;
-    hello)
-      prompt_seen=1
-      ;;
-  esac
-  shift
-done
-if [ "$found_workdir" -ne 1 ] || [ "$count_add_dir" -ne 2 ] || [ "$found_model" -ne 1 ] || [ "$found_flag" -ne 1 ] || [ "$count_json" -ne 2 ] || [ "$count_json_with_value" -ne 1 ] || [ "$prompt_seen" -ne 1 ]; then
-  echo "missing expected flags" >&2
-  exit 12
-fi
-echo '{{"type":"thread.started","thread_id":"66666666-6666-4666-8666-666666666666"}}'
-echo '{{"type":"item.completed","item":{{"id":"item_1","type":"agent_message","text":"query options ok"}}}}'
+fn write_maps_model_to_provider_flags() {
+    let mock = setup_mock_bins(&[
+        (
+            "claude",
+            r#"
+case "$*" in
+  *"--model opus"*) ;;
+  *) echo "unexpected args: $*" >&2; exit 7 ;;
+esac
+echo '{"type":"system","subtype":"init","session_id":"bbbbbbbb-bbbb-4bbb-8bbb-bbbbbbbbbbbb"}'
+echo '{"type":"assistant","session_id":"bbbbbbbb-bbbb-4bbb-8bbb-bbbbbbbbbbbb","message":{"content":[{"type":"text","text":"claude-model-ok"}]}}'
 "#,
-    );
-    let mock = setup_mock_bins(&[("codex", script.as_str())]);
+        ),
+        (
+            "codex",
+            r#"
+case "$*" in
+  *"--config model=gpt-5"*) ;;
+  *) echo "unexpected args: $*" >&2; exit 7 ;;
+esac
+echo '{"type":"thread.started","thread_id":"88888888-8888-4888-8888-888888888888"}'
+echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"codex-model-ok"}}'
+"#,
+        ),
+    ]);
+    let config_dir = tempdir().expect("tempdir");
+    let config = config_dir.path().join("config.toml");
+    fs::write(&config, "[models]\ncodex = [\"gpt-5\", \"o3\"]\n").expect("write config");
 
-    let target = format!(
-        "agents://codex?workdir={}&add_dir={}&add_dir={}&model=gpt-5&flag&json=1",
-        encode_query_component(&workdir_text),
-        encode_query_component(&add_dir_a_text),
-        encode_query_component(&add_dir_b_text),
-    );
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("PATH", path_with_mock(mock.path()))
-        .arg(target)
-        .arg("-d")
-        .arg("hello")
+        .env("XURL_CONFIG", &config)
+        .arg("agents://claude?model=opus")
+        .args(["-d", "hello"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("query options ok"))
-        .stderr(predicate::str::contains("reserved by xurl").not())
+        .stdout(predicate::str::contains("claude-model-ok"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("XURL_CONFIG", &config)
+        .arg("agents://codex?model=gpt-5")
+        .args(["-d", "hello"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("codex-model-ok"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("XURL_CONFIG", &config)
+        .arg("agents://codex?model=gpt-4")
+        .args(["-d", "hello"])
+        .assert()
+        .failure()
         .stderr(predicate::str::contains(
-            "created: agents://codex/66666666-6666-4666-8666-666666666666",
+            "model `gpt-4` is not allowed for provider codex; [models] codex allows: gpt-5, o3",
         ));
 }
 
 #[cfg(unix)]
 #[test]
-fn write_append_passthroughs_query_options() {
-    let target_session = "22222222-2222-4222-8222-222222222222";
-    let script = format!(
+fn write_records_provider_stream_and_replays_it() {
+    let mock = setup_mock_bins(&[(
+        "codex",
         r#"
-if [ "$1" != "exec" ] || [ "$2" != "resume" ] || [ "$3" != "--json" ]; then
-  echo "unexpected args: $*" >&2
-  exit 7
-fi
-count_workdir=0
-found_flag=0
-found_prompt=0
-while [ "$#" -gt 0 ]; do
-  case "$1" in
-    --workdir)
-      shift
-      if [ "$1" = "/tmp/a" ] || [ "$1" = "/tmp/b" ]; then
-        count_workdir=$((count_workdir + 1))
-      else
-        exit 8
-      fi
-      ;;
-    --flag)
-      found_flag=1
-      ;;
-    "{target_session}")
-      ;;
-    continue)
-      found_prompt=1
-      ;;
-  esac
-  shift
-done
-[ "$count_workdir" -eq 2 ] || exit 9
-[ "$found_flag" -eq 1 ] || exit 10
-[ "$found_prompt" -eq 1 ] || exit 11
-echo '{{"type":"thread.started","thread_id":"{target_session}"}}'
-echo '{{"type":"item.completed","item":{{"id":"item_1","type":"agent_message","text":"append passthrough query"}}}}'
+echo '{"type":"thread.started","thread_id":"77777777-7777-4777-8777-777777777777"}'
+echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"recorded reply"}}'
 "#,
+    )]);
+    let record_dir = tempdir().expect("tempdir");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("XURL_RECORD_DIR", record_dir.path())
+        .arg("agents://codex")
+        .args(["-d", "hello"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("recorded reply"));
+
+    let recordings = fs::read_dir(record_dir.path())
+        .expect("read record dir")
+        .map(|entry| entry.expect("entry").path())
+        .collect::<Vec<_>>();
+    assert_eq!(recordings.len(), 1);
+    let recording = &recordings[0];
+    assert!(
+        recording
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("codex-") && name.ends_with(".jsonl"))
     );
-    let mock = setup_mock_bins(&[("codex", script.as_str())]);
-    let target = format!(
-        "agents://codex/{target_session}?workdir={}&workdir={}&flag",
-        encode_query_component("/tmp/a"),
-        encode_query_component("/tmp/b"),
+    assert!(
+        fs::read_to_string(recording)
+            .expect("read recording")
+            .contains("\"thread_id\":\"77777777-7777-4777-8777-777777777777\"")
     );
 
+    let empty_path = tempdir().expect("tempdir");
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    cmd.env("PATH", path_with_mock(mock.path()))
-        .arg(target)
-        .arg("--data")
-        .arg("continue")
+    cmd.env("PATH", empty_path.path())
+        .env("XURL_REPLAY_FILE", recording)
+        .arg("agents://codex")
+        .args(["-d", "hello"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("append passthrough query"))
-        .stderr(predicate::str::contains("ignored query parameter").not())
-        .stderr(predicate::str::contains(format!(
-            "updated: agents://codex/{target_session}",
-        )));
+        .stdout(predicate::str::contains("recorded reply"))
+        .stderr(predicate::str::contains(
+            "created: agents://codex/77777777-7777-4777-8777-777777777777",
+        ));
 }
 
 #[cfg(unix)]
 #[test]
-fn write_amp_passthroughs_workdir_and_add_dir_query_parameters() {
-    let workdir_text = "/tmp/amp-workdir".to_string();
-    let add_dir_text = "/tmp/amp-add".to_string();
-    let script = format!(
-        r#"
-if [ "$1" != "-x" ] || [ "$2" != "hello" ] || [ "$3" != "--stream-json" ]; then
-  echo "unexpected args: $*" >&2
-  exit 7
-fi
-seen_workdir=0
-seen_add_dir=0
-seen_foo=0
-while [ "$#" -gt 0 ]; do
-  case "$1" in
-    --workdir)
-      shift
-      [ "$1" = "{workdir_text}" ] || exit 8
-      seen_workdir=1
-      ;;
-    --add_dir)
-      shift
-      [ "$1" = "{add_dir_text}" ] || exit 9
-      seen_add_dir=1
-      ;;
-    --foo)
-      shift
-      [ "$1" = "bar" ] || exit 10
-      seen_foo=1
-      ;;
-    *)
-      ;;
-  esac
-  shift
-done
-[ "$seen_workdir" -eq 1 ] || exit 11
-[ "$seen_add_dir" -eq 1 ] || exit 12
-[ "$seen_foo" -eq 1 ] || exit 13
-echo '{{"type":"system","subtype":"init","session_id":"T-77777777-7777-4777-8777-777777777777"}}'
-echo '{{"type":"assistant","session_id":"T-77777777-7777-4777-8777-777777777777","message":{{"content":[{{"type":"text","text":"passthrough-ok"}}]}}}}'
-echo '{{"type":"result","subtype":"success","session_id":"T-77777777-7777-4777-8777-777777777777","result":"ok"}}'
+fn dry_run_prints_provider_command_without_spawning() {
+    let mock = setup_mock_bins(&[
+        (
+            "codex",
+            r#"
+echo "should not run" >&2
+exit 99
 "#,
-    );
-    let mock = setup_mock_bins(&[("amp", script.as_str())]);
-    let target = format!(
-        "agents://amp?workdir={}&add_dir={}&foo=bar",
-        encode_query_component(&workdir_text),
-        encode_query_component(&add_dir_text),
-    );
+        ),
+        (
+            "gemini",
+            r#"
+echo "should not run" >&2
+exit 99
+"#,
+        ),
+    ]);
 
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
     cmd.env("PATH", path_with_mock(mock.path()))
-        .arg(target)
-        .arg("-d")
-        .arg("hello")
+        .arg("agents://codex?model=gpt-5&flag")
+        .args(["-d", "it's done", "--dry-run"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("passthrough-ok"))
-        .stderr(predicate::str::contains("ignored query parameter `add_dir`").not())
+        .stdout(predicate::str::contains(
+            r"command: codex exec --json --flag --config model=gpt-5 'it'\''s done'",
+        ))
+        .stdout(predicate::str::contains("\ncwd: /"))
+        .stdout(predicate::str::contains("env: inherited, no overrides"))
+        .stderr(predicate::str::contains("should not run").not())
+        .stderr(predicate::str::contains("created:").not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("agents://gemini")
+        .args(["-d", "hello", "--system", "be terse", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("command: gemini "))
+        .stdout(predicate::str::contains(
+            "env: GEMINI_SYSTEM_MD='<temporary file holding the system prompt>'",
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg(codex_uri())
+        .arg("--dry-run")
+        .assert()
+        .failure()
         .stderr(predicate::str::contains(
-            "created: agents://amp/T-77777777-7777-4777-8777-777777777777",
+            "--dry-run only applies to write mode",
         ));
 }
 
 #[cfg(unix)]
 #[test]
-fn write_data_file_and_stdin_are_supported() {
-    let mock = setup_mock_bins(&[(
-        "codex",
-        r#"
-if [ "$1" != "exec" ] || [ "$2" != "--json" ]; then
-  echo "unexpected args: $*" >&2
-  exit 7
-fi
-if [ "$3" = "from-file" ]; then
-  echo '{"type":"thread.started","thread_id":"33333333-3333-4333-8333-333333333333"}'
-  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"file-ok"}}'
-  exit 0
-fi
-if [ "$3" = "from-stdin" ]; then
-  echo '{"type":"thread.started","thread_id":"44444444-4444-4444-8444-444444444444"}'
-  echo '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"stdin-ok"}}'
-  exit 0
-fi
-echo "unexpected prompt: $3" >&2
-exit 8
-"#,
-    )]);
+fn write_approval_maps_to_each_provider_flag() {
+    let mock = setup_mock_bins(&[
+        ("codex", "exit 99\n"),
+        ("claude", "exit 99\n"),
+        ("opencode", "exit 99\n"),
+    ]);
 
-    let prompt_file_dir = tempdir().expect("tempdir");
-    let prompt_file = prompt_file_dir.path().join("prompt.txt");
-    fs::write(&prompt_file, "from-file").expect("write prompt");
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("agents://codex?approval=never")
+        .args(["-d", "hello", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "command: codex exec --json --config sandbox_mode=read-only hello",
+        ));
 
-    let mut from_file = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    from_file
-        .env("PATH", path_with_mock(mock.path()))
-        .arg("agents://codex")
-        .arg("-d")
-        .arg(format!("@{}", prompt_file.display()))
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("agents://claude?approval=auto")
+        .args(["-d", "hello", "--dry-run"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("file-ok"));
+        .stdout(predicate::str::contains(
+            "--permission-mode bypassPermissions",
+        ));
 
-    let mut from_stdin = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
-    from_stdin
-        .env("PATH", path_with_mock(mock.path()))
-        .arg("agents://codex")
-        .arg("-d")
-        .arg("@-")
-        .write_stdin("from-stdin")
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("agents://opencode?approval=on-request")
+        .args(["-d", "hello", "--dry-run"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("stdin-ok"));
+        .stdout(predicate::str::contains("approval").not())
+        .stderr(predicate::str::contains(
+            "warning: ignored approval=on-request: provider `opencode` takes tool permissions",
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("agents://claude?approval=sometimes")
+        .args(["-d", "hello", "--dry-run"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unsupported approval value: sometimes; expected never, on-request, or auto",
+        ));
 }
 
 #[cfg(unix)]
@@ -2292,6 +5511,20 @@ fn write_command_not_found_has_hint() {
         .stderr(predicate::str::contains("hint: write mode needs Codex CLI"));
 }
 
+#[cfg(unix)]
+#[test]
+fn write_command_not_found_hint_matches_the_binary_name() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", "")
+        .env("XURL_CODEX_BIN", "/home/sample/bin/codex")
+        .arg("agents://codex")
+        .arg("-d")
+        .arg("hello")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("hint: write mode needs Codex CLI"));
+}
+
 #[cfg(unix)]
 #[test]
 fn write_amp_create_stream_json_path_works() {
@@ -2376,6 +5609,73 @@ exit 7
         ));
 }
 
+#[cfg(unix)]
+#[test]
+fn write_gemini_append_resumes_from_project_root() {
+    let temp = setup_gemini_tree();
+    let project_root = temp.path().join("repo");
+    fs::create_dir_all(&project_root).expect("mkdir");
+    fs::write(
+        temp.path().join(
+            ".gemini/tmp/0c0d7b04c22749f3687ea60b66949fd32bcea2551d4349bf72346a9ccc9a9ba4/.project_root",
+        ),
+        project_root.display().to_string(),
+    )
+    .expect("write project root");
+    let mock = setup_mock_bins(&[(
+        "gemini",
+        &format!(
+            r#"
+if [ "$(pwd -P)" != "$(cd '{}' && pwd -P)" ]; then
+  echo "unexpected cwd: $(pwd)" >&2
+  exit 8
+fi
+if [ "$5" = "--resume" ] && [ "$6" = "{GEMINI_SESSION_ID}" ]; then
+  echo '{{"type":"init","session_id":"{GEMINI_SESSION_ID}"}}'
+  echo '{{"type":"message","role":"assistant","content":"resumed","delta":true}}'
+  echo '{{"type":"result","status":"success"}}'
+  exit 0
+fi
+echo "unexpected args: $*" >&2
+exit 7
+"#,
+            project_root.display()
+        ),
+    )]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("GEMINI_CLI_HOME", temp.path())
+        .arg(format!("agents://gemini/{GEMINI_SESSION_ID}"))
+        .arg("-d")
+        .arg("continue")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("resumed"))
+        .stderr(predicate::str::contains(format!(
+            "updated: agents://gemini/{GEMINI_SESSION_ID}"
+        )));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_gemini_append_rejects_unknown_session() {
+    let temp = tempdir().expect("tempdir");
+    let mock = setup_mock_bins(&[("gemini", "echo \"should not run\" >&2\nexit 99")]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("GEMINI_CLI_HOME", temp.path())
+        .arg(format!("agents://gemini/{GEMINI_SESSION_ID}"))
+        .arg("-d")
+        .arg("continue")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "thread not found for provider=gemini session_id={GEMINI_SESSION_ID}"
+        )));
+}
+
 #[cfg(unix)]
 #[test]
 fn write_gemini_role_uri_is_rejected_with_clear_error() {
@@ -2420,12 +5720,93 @@ exit 7
     cmd.env("PATH", path_with_mock(mock.path()))
         .arg("agents://pi")
         .arg("-d")
-        .arg("hello")
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("hello from pi"))
+        .stderr(predicate::str::contains(
+            "created: agents://pi/aaaaaaaa-1111-4222-8333-bbbbbbbbbbbb",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_pi_child_session_uri_continues_child_session() {
+    let temp = setup_pi_tree_with_child_sessions();
+    let mock = setup_mock_bins(&[(
+        "pi",
+        &format!(
+            r#"
+case "$2" in
+  *_{PI_CHILD_SESSION_ID}.jsonl)
+    if [ "$1" = "--session" ] && [ "$3" = "-p" ]; then
+      echo '{{"type":"session","id":"{PI_CHILD_SESSION_ID}"}}'
+      echo '{{"type":"message_update","assistantMessageEvent":{{"type":"text_delta","delta":"child reply"}}}}'
+      exit 0
+    fi
+    ;;
+esac
+echo "unexpected args: $*" >&2
+exit 7
+"#
+        ),
+    )]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg(pi_child_session_uri())
+        .arg("-d")
+        .arg("continue")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("child reply"))
+        .stderr(predicate::str::contains(format!(
+            "updated: agents://pi/{PI_CHILD_SESSION_ID}"
+        )));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_pi_entry_uri_requires_latest_entry() {
+    let temp = setup_pi_tree();
+    let mock = setup_mock_bins(&[(
+        "pi",
+        &format!(
+            r#"
+if [ "$1" = "--session" ] && [ "$3" = "-p" ]; then
+  echo '{{"type":"session","id":"{PI_SESSION_ID}"}}'
+  echo '{{"type":"message_update","assistantMessageEvent":{{"type":"text_delta","delta":"leaf reply"}}}}'
+  exit 0
+fi
+echo "unexpected args: $*" >&2
+exit 7
+"#
+        ),
+    )]);
+
+    let mut leaf_cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    leaf_cmd
+        .env("PATH", path_with_mock(mock.path()))
+        .env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg(agents_child_uri("pi", PI_SESSION_ID, "f1b2c3d4"))
+        .arg("-d")
+        .arg("continue")
         .assert()
         .success()
-        .stdout(predicate::str::contains("hello from pi"))
+        .stdout(predicate::str::contains("leaf reply"));
+
+    let mut branch_cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    branch_cmd
+        .env("PATH", path_with_mock(mock.path()))
+        .env("PI_CODING_AGENT_DIR", temp.path().join("agent"))
+        .arg(pi_entry_uri())
+        .arg("-d")
+        .arg("continue")
+        .assert()
+        .failure()
         .stderr(predicate::str::contains(
-            "created: agents://pi/aaaaaaaa-1111-4222-8333-bbbbbbbbbbbb",
+            "entry_id=d1b2c3d4 is not the latest entry (f1b2c3d4)",
         ));
 }
 
@@ -2541,6 +5922,357 @@ exit 7
         ));
 }
 
+#[cfg(unix)]
+#[test]
+fn replay_sends_user_prompts_to_a_new_session() {
+    let temp = CodexTreeBuilder::new()
+        .with_thread(
+            SESSION_ID,
+            &[
+                Message::user("<environment_context>cwd</environment_context>"),
+                Message::user("first prompt"),
+                Message::assistant("ok"),
+                Message::user("second prompt"),
+            ],
+        )
+        .with_session_meta("/work/app", "2026-02-23T04:48:50Z")
+        .build();
+
+    let mock = setup_mock_bins(&[(
+        "claude",
+        r#"
+last=""
+resume=""
+while [ "$#" -gt 0 ]; do
+  case "$1" in
+    --resume) shift; resume="$1" ;;
+  esac
+  last="$1"
+  shift
+done
+case "$last" in
+  "first prompt") [ -z "$resume" ] || exit 8 ;;
+  "second prompt") [ "$resume" = "aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa" ] || exit 9 ;;
+  *) echo "unexpected prompt: $last" >&2; exit 7 ;;
+esac
+echo '{"type":"system","subtype":"init","session_id":"aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa"}'
+echo "{\"type\":\"assistant\",\"session_id\":\"aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa\",\"message\":{\"content\":[{\"type\":\"text\",\"text\":\"reply to $last\"}]}}"
+"#,
+    )]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("CODEX_HOME", temp.path())
+        .arg("replay")
+        .arg(agents_codex_uri())
+        .arg("--to")
+        .arg("agents://claude")
+        .assert()
+        .success()
+        .stdout("reply to first prompt\n\nreply to second prompt")
+        .stderr(predicate::str::contains(
+            "created: agents://claude/aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa",
+        ))
+        .stderr(predicate::str::contains(
+            "updated: agents://claude/aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa",
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("replay")
+        .arg(agents_codex_uri())
+        .arg("--to")
+        .arg("agents://claude/2823d1df-720a-4c31-ac55-ae8ba726721f")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "replay --to requires a provider or role URI that starts a new session",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn summarize_sends_rendered_thread_through_template_to_via_provider() {
+    let temp = setup_codex_tree();
+    let mock = setup_mock_bins(&[(
+        "claude",
+        r###"
+last=""
+for arg in "$@"; do last="$arg"; done
+case "$last" in
+  "Summarize the agent conversation below (agents://codex/"*"## 1. User"*"hello"*"## 2. Assistant"*"world"*) summary="default summary" ;;
+  "Brief codex: # Thread"*"hello"*) summary="template summary" ;;
+  *) echo "unexpected prompt: $last" >&2; exit 7 ;;
+esac
+echo '{"type":"system","subtype":"init","session_id":"aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa"}'
+echo "{\"type\":\"assistant\",\"session_id\":\"aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa\",\"message\":{\"content\":[{\"type\":\"text\",\"text\":\"$summary\"}]}}"
+"###,
+    )]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("CODEX_HOME", temp.path())
+        .arg("summarize")
+        .arg(agents_codex_uri())
+        .arg("--via")
+        .arg("agents://claude")
+        .assert()
+        .success()
+        .stdout("default summary")
+        .stderr(predicate::str::contains(
+            "created: agents://claude/aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa",
+        ));
+
+    let summary = temp.path().join("summary.md");
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("CODEX_HOME", temp.path())
+        .arg("summarize")
+        .arg(agents_codex_uri())
+        .arg("--via")
+        .arg("agents://claude")
+        .arg("--template")
+        .arg("Brief {{provider}}: {{thread}}")
+        .arg("-o")
+        .arg(&summary)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&summary).expect("summary"),
+        "template summary"
+    );
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("summarize")
+        .arg(agents_codex_uri())
+        .arg("--template")
+        .arg("Summarize {{url}}")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "summary template must include {{thread}}",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_template_expands_data_file_and_thread_placeholders() {
+    let temp = setup_codex_tree();
+    let config_dir = tempdir().expect("tempdir");
+    let templates = config_dir.path().join("templates");
+    fs::create_dir_all(&templates).expect("mkdir");
+    let diff = config_dir.path().join("change.diff");
+    fs::write(&diff, "+fn added() {}\n").expect("write diff");
+    fs::write(
+        templates.join("review.md"),
+        format!(
+            "Review this diff. {{{{data}}}}\n{{{{file:{}}}}}\nContext: {{{{thread:{}}}}}\n",
+            diff.display(),
+            agents_codex_uri()
+        ),
+    )
+    .expect("write template");
+    let mock = setup_mock_bins(&[(
+        "claude",
+        r###"
+last=""
+for arg in "$@"; do last="$arg"; done
+case "$last" in
+  "Review this diff. Focus on naming."*"+fn added() {}"*"Context: # Thread"*"hello"*) reply="reviewed" ;;
+  *) echo "unexpected prompt: $last" >&2; exit 7 ;;
+esac
+echo '{"type":"system","subtype":"init","session_id":"aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa"}'
+echo "{\"type\":\"assistant\",\"session_id\":\"aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa\",\"message\":{\"content\":[{\"type\":\"text\",\"text\":\"$reply\"}]}}"
+"###,
+    )]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("CODEX_HOME", temp.path())
+        .env("XURL_CONFIG", config_dir.path().join("config.toml"))
+        .arg("agents://claude")
+        .arg("--template")
+        .arg("review")
+        .arg("-d")
+        .arg("Focus on naming.")
+        .assert()
+        .success()
+        .stdout("reviewed");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("XURL_CONFIG", config_dir.path().join("config.toml"))
+        .arg("agents://claude")
+        .arg("--template")
+        .arg("missing")
+        .arg("-d")
+        .arg("x")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("template `missing` not found"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg(agents_codex_uri())
+        .arg("--template")
+        .arg("review")
+        .arg("--no-stdin")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--template only applies to write mode",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn write_data_thread_reference_inlines_rendered_thread() {
+    let temp = setup_codex_tree();
+    let mock = setup_mock_bins(&[(
+        "claude",
+        r###"
+last=""
+for arg in "$@"; do last="$arg"; done
+case "$last" in
+  "Continue from what this agent did:
+# Thread"*"## 1. User"*"hello"*"## 2. Assistant"*"world"*) reply="continued" ;;
+  *) echo "unexpected prompt: $last" >&2; exit 7 ;;
+esac
+echo '{"type":"system","subtype":"init","session_id":"aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa"}'
+echo "{\"type\":\"assistant\",\"session_id\":\"aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa\",\"message\":{\"content\":[{\"type\":\"text\",\"text\":\"$reply\"}]}}"
+"###,
+    )]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("CODEX_HOME", temp.path())
+        .arg("agents://claude")
+        .arg("-d")
+        .arg("Continue from what this agent did:")
+        .arg("-d")
+        .arg(format!("thread:{}", agents_codex_uri()))
+        .assert()
+        .success()
+        .stdout("continued");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .env("CODEX_HOME", temp.path())
+        .arg("agents://claude")
+        .arg("-d")
+        .arg("thread: what happened?")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unexpected prompt: thread: what happened?",
+        ));
+}
+
+#[test]
+fn compare_reports_answers_tools_durations_and_tokens_side_by_side() {
+    const OTHER_SESSION_ID: &str = "019c871c-b1f9-7f60-9c4f-87ed09f1aaaa";
+    let first = [
+        r#"{"timestamp":"2026-02-23T04:48:50Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"fix flaky test"}]}}"#,
+        r#"{"timestamp":"2026-02-23T04:49:00Z","type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"cargo\",\"test\"]}","call_id":"call_1"}}"#,
+        r#"{"timestamp":"2026-02-23T04:49:10Z","type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"cargo\",\"test\"]}","call_id":"call_2"}}"#,
+        r#"{"timestamp":"2026-02-23T04:49:20Z","type":"event_msg","payload":{"type":"token_count","info":{"total_token_usage":{"input_tokens":1200,"output_tokens":340}}}}"#,
+        r#"{"timestamp":"2026-02-23T04:50:00Z","type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"Added a retry to the flaky test."}]}}"#,
+    ];
+    let second = [
+        r#"{"timestamp":"2026-02-23T04:48:50Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"fix flaky test"}]}}"#,
+        r#"{"timestamp":"2026-02-23T04:49:05Z","type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"The test races on a shared port."}]}}"#,
+    ];
+    let mut builder = CodexTreeBuilder::new();
+    for (session_id, lines) in [(SESSION_ID, &first[..]), (OTHER_SESSION_ID, &second[..])] {
+        builder = builder.with_thread(session_id, &[]);
+        for line in lines {
+            builder = builder.with_event(*line);
+        }
+    }
+    let temp = builder.build();
+    let other_uri = format!("agents://codex/{OTHER_SESSION_ID}");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("compare")
+        .arg(agents_codex_uri())
+        .arg(format!("codex/{OTHER_SESSION_ID}"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("mode: compare"))
+        .stdout(predicate::str::contains(format!(
+            "| | `{}` | `{other_uri}` |\n| --- | --- | --- |\n| Provider | codex | codex |\n| Duration | 1m10s | 15s |\n| Input tokens | 1200 | - |\n| Output tokens | 340 | - |\n| Tool calls | 2 | 0 |",
+            agents_codex_uri()
+        )))
+        .stdout(predicate::str::contains("| `shell` | 2 | 0 |"))
+        .stdout(predicate::str::contains(format!(
+            "### `{}`\n\nAdded a retry to the flaky test.\n\n### `{other_uri}`\n\nThe test races on a shared port.\n",
+            agents_codex_uri()
+        )));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("CODEX_HOME", temp.path())
+        .arg("compare")
+        .arg(agents_codex_uri())
+        .assert()
+        .failure();
+}
+
+#[cfg(unix)]
+#[test]
+fn multicast_writes_to_every_target_with_labeled_lines() {
+    let mock = setup_mock_bins(&[
+        (
+            "codex",
+            r#"
+echo '{"type":"thread.started","thread_id":"11111111-1111-4111-8111-111111111111"}'
+printf '%s\n' '{"type":"item.completed","item":{"id":"item_1","type":"agent_message","text":"codex line one\ncodex line two"}}'
+"#,
+        ),
+        (
+            "claude",
+            r#"
+echo '{"type":"system","subtype":"init","session_id":"aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa"}'
+echo '{"type":"assistant","session_id":"aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa","message":{"content":[{"type":"text","text":"claude reply"}]}}'
+"#,
+        ),
+        ("gemini", "echo 'quota exceeded' >&2\nexit 3"),
+    ]);
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("multicast")
+        .arg("-d")
+        .arg("fix flaky test")
+        .arg("--to")
+        .arg("agents://codex,agents://claude")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "[codex] codex line one\n[codex] codex line two\n",
+        ))
+        .stdout(predicate::str::contains("[claude] claude reply\n"))
+        .stderr(predicate::str::contains(
+            "created: agents://codex/11111111-1111-4111-8111-111111111111\ncreated: agents://claude/aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa",
+        ));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("xurl"));
+    cmd.env("PATH", path_with_mock(mock.path()))
+        .arg("multicast")
+        .arg("-d")
+        .arg("fix flaky test")
+        .arg("--to")
+        .arg("agents://gemini,agents://claude")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("[claude] claude reply"))
+        .stderr(predicate::str::contains("error: [gemini]"))
+        .stderr(predicate::str::contains(
+            "created: agents://claude/aaaaaaaa-aaaa-4aaa-8aaa-aaaaaaaaaaaa",
+        ))
+        .stderr(predicate::str::contains("1 of 2 multicast writes failed"));
+}
+
 #[cfg(unix)]
 #[test]
 fn write_claude_role_uri_sets_agent_flag() {
@@ -2616,3 +6348,255 @@ exit 7
     let written = fs::read_to_string(output).expect("read output");
     assert_eq!(written, "file target");
 }
+
+#[test]
+fn watch_prints_a_json_line_when_a_thread_is_updated() {
+    let temp = setup_codex_tree();
+    let rollout = temp.path().join(format!(
+        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+    ));
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin!("xurl"))
+        .env("CODEX_HOME", temp.path())
+        .args(["watch", "codex", "--count", "1"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("spawn watch");
+
+    // The rollout exists before watching starts, so whenever the watcher notices the
+    // appends it must report an update, never a creation.
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(20);
+    while child.try_wait().expect("poll watch").is_none() {
+        if std::time::Instant::now() > deadline {
+            child.kill().expect("kill watch");
+            panic!("watch did not report the update");
+        }
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(&rollout)
+            .expect("open rollout");
+        std::io::Write::write_all(&mut file, b"\n").expect("append");
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+
+    let output = child.wait_with_output().expect("watch output");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).expect("utf8"),
+        format!(
+            "{{\"event\":\"thread_updated\",\"provider\":\"codex\",\"uri\":\"agents://codex/{SESSION_ID}\"}}\n"
+        )
+    );
+}
+
+#[test]
+fn watch_webhook_posts_idle_event_with_last_message() {
+    let temp = setup_codex_tree();
+    let rollout = temp.path().join(format!(
+        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+    ));
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+    let url = format!("http://{}/hook", listener.local_addr().expect("addr"));
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().expect("accept");
+        let mut reader = std::io::BufReader::new(stream);
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            std::io::BufRead::read_line(&mut reader, &mut line).expect("read header");
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                content_length = value.trim().parse().expect("content length");
+            }
+        }
+        let mut body = vec![0; content_length];
+        std::io::Read::read_exact(&mut reader, &mut body).expect("read body");
+        std::io::Write::write_all(
+            reader.get_mut(),
+            b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )
+        .expect("respond");
+        String::from_utf8(body).expect("utf8 body")
+    });
+
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin!("xurl"))
+        .env("CODEX_HOME", temp.path())
+        .args(["watch", "codex", "--idle", "1s", "--webhook"])
+        .arg(&url)
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("spawn watch");
+    let stdout = child.stdout.take().expect("stdout");
+    let (lines_tx, lines_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::BufRead::lines(std::io::BufReader::new(stdout)) {
+            let _ = lines_tx.send(line.expect("read line"));
+        }
+    });
+
+    // Append until the update is reported, then stop so the thread can go idle. Appends that
+    // were already written when the first update was seen may be reported as more updates.
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(20);
+    let updated = loop {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "watch did not report the update"
+        );
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(&rollout)
+            .expect("open rollout");
+        std::io::Write::write_all(&mut file, b"\n").expect("append");
+        if let Ok(line) = lines_rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            break line;
+        }
+    };
+    assert!(
+        updated.contains("\"event\":\"thread_updated\""),
+        "{updated}"
+    );
+
+    let idle = loop {
+        let line = lines_rx
+            .recv_timeout(std::time::Duration::from_secs(20))
+            .expect("idle event");
+        if !line.contains("\"event\":\"thread_updated\"") {
+            break line;
+        }
+    };
+    assert!(idle.contains("\"event\":\"thread_idle\""), "{idle}");
+    assert!(idle.contains("\"last_message\":"), "{idle}");
+    let body = server.join().expect("webhook server");
+    assert!(body.contains("\"event\":\"thread_idle\""), "{body}");
+    assert!(
+        body.contains(&format!(
+            "\"text\":\"codex thread agents://codex/{SESSION_ID} went idle: "
+        )),
+        "{body}"
+    );
+    child.kill().expect("kill watch");
+    child.wait().expect("wait watch");
+}
+
+#[test]
+fn wait_blocks_until_subagent_status_is_final() {
+    let temp = running_codex_subagent_tree();
+
+    Command::new(assert_cmd::cargo::cargo_bin!("xurl"))
+        .env("CODEX_HOME", temp.path())
+        .arg(codex_uri())
+        .arg("--wait")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--wait requires a subagent URI"));
+
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin!("xurl"))
+        .env("CODEX_HOME", temp.path())
+        .arg(format!("agents://codex/{SESSION_ID}/{SUBAGENT_ID}"))
+        .args(["--wait", "--poll", "1s"])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("spawn wait");
+    std::thread::sleep(std::time::Duration::from_millis(1500));
+    assert!(
+        child.try_wait().expect("poll wait").is_none(),
+        "--wait returned while the subagent was running"
+    );
+
+    // Build the closed main thread elsewhere and rename it in, so a poll never reads it half
+    // written.
+    let closed = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_spawned_agent(SUBAGENT_ID)
+        .with_closed_agent(SUBAGENT_ID, "completed", "done")
+        .build();
+    let main_rollout =
+        format!("sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl");
+    fs::rename(
+        closed.path().join(&main_rollout),
+        temp.path().join(&main_rollout),
+    )
+    .expect("replace main");
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(20);
+    while child.try_wait().expect("poll wait").is_none() {
+        if std::time::Instant::now() > deadline {
+            child.kill().expect("kill wait");
+            panic!("--wait did not return after the subagent completed");
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    let output = child.wait_with_output().expect("wait output");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf8");
+    assert!(stdout.contains("status: completed"), "{stdout}");
+}
+
+#[test]
+fn wait_fails_once_its_timeout_expires() {
+    let temp = running_codex_subagent_tree();
+
+    Command::new(assert_cmd::cargo::cargo_bin!("xurl"))
+        .env("CODEX_HOME", temp.path())
+        .arg(format!("agents://codex/{SESSION_ID}/{SUBAGENT_ID}"))
+        .args(["--wait", "--poll", "1s", "--timeout", "2s"])
+        .timeout(std::time::Duration::from_secs(20))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "timed out after 2s waiting for agents://codex/{SESSION_ID}/{SUBAGENT_ID}"
+        )));
+}
+
+/// A main thread that spawned `SUBAGENT_ID` and has not closed it yet.
+fn running_codex_subagent_tree() -> tempfile::TempDir {
+    CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_spawned_agent(SUBAGENT_ID)
+        .with_thread(SUBAGENT_ID, &[Message::user("hello child")])
+        .with_parent(SESSION_ID)
+        .build()
+}
+
+#[test]
+fn subagent_depth_lists_descendant_agents() {
+    let grandchild_id = "019c8800-0000-7000-8000-000000000001";
+    let temp = CodexTreeBuilder::new()
+        .with_thread(SESSION_ID, &[])
+        .with_spawned_agent(SUBAGENT_ID)
+        .with_closed_agent(SUBAGENT_ID, "completed", "done")
+        .with_thread(SUBAGENT_ID, &[Message::user("hello child")])
+        .with_parent(SESSION_ID)
+        .with_spawned_agent(grandchild_id)
+        .with_thread(grandchild_id, &[Message::user("hello grandchild")])
+        .with_parent(SUBAGENT_ID)
+        .build();
+    let subagent_uri = format!("agents://codex/{SESSION_ID}/{SUBAGENT_ID}");
+
+    Command::new(assert_cmd::cargo::cargo_bin!("xurl"))
+        .env("CODEX_HOME", temp.path())
+        .arg(&subagent_uri)
+        .args(["--depth", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "## Descendant Agents\n\n- `agents://codex/{SUBAGENT_ID}/{grandchild_id}` `running`"
+        )));
+    Command::new(assert_cmd::cargo::cargo_bin!("xurl"))
+        .env("CODEX_HOME", temp.path())
+        .arg(&subagent_uri)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("## Descendant Agents").not());
+    Command::new(assert_cmd::cargo::cargo_bin!("xurl"))
+        .env("CODEX_HOME", temp.path())
+        .arg(codex_uri())
+        .args(["--depth", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--depth requires a subagent URI"));
+}
@@ -0,0 +1,153 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{Value, json};
+
+/// Directory, relative to the exported markdown, that attachments are written to.
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+/// A decoded piece of embedded binary content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub file_name: String,
+    pub media_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Replaces base64 content embedded in `value` with markdown text parts that link to the
+/// decoded file under [`ATTACHMENTS_DIR`], and returns the decoded files.
+///
+/// Recognizes Claude/Amp `source: {type: "base64", ...}` blocks, Codex `input_image` data
+/// URIs, Gemini `inlineData` parts, and any other `data:<mime>;base64,` string. Content that
+/// does not decode is left in place.
+pub fn extract_attachments(value: &mut Value, attachments: &mut Vec<Attachment>) {
+    if let Some((media_type, data)) = embedded_content(value)
+        && let Ok(bytes) = STANDARD.decode(data.trim())
+    {
+        *value = json!({ "type": "text", "text": push_attachment(attachments, media_type, bytes) });
+        return;
+    }
+
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                extract_attachments(item, attachments);
+            }
+        }
+        Value::Object(map) => {
+            for item in map.values_mut() {
+                extract_attachments(item, attachments);
+            }
+        }
+        Value::String(text) => {
+            if let Some((media_type, data)) = parse_data_uri(text)
+                && let Ok(bytes) = STANDARD.decode(data.trim())
+            {
+                *text = push_attachment(attachments, media_type.to_string(), bytes);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn embedded_content(value: &Value) -> Option<(String, String)> {
+    if let Some(source) = value.get("source")
+        && source.get("type").and_then(Value::as_str) == Some("base64")
+    {
+        let media_type = source
+            .get("media_type")
+            .or_else(|| source.get("mediaType"))
+            .and_then(Value::as_str)
+            .unwrap_or("application/octet-stream");
+        let data = source.get("data").and_then(Value::as_str)?;
+        return Some((media_type.to_string(), data.to_string()));
+    }
+
+    if let Some(inline) = value.get("inlineData") {
+        let media_type = inline
+            .get("mimeType")
+            .and_then(Value::as_str)
+            .unwrap_or("application/octet-stream");
+        let data = inline.get("data").and_then(Value::as_str)?;
+        return Some((media_type.to_string(), data.to_string()));
+    }
+
+    if value.get("type").and_then(Value::as_str) == Some("input_image") {
+        let (media_type, data) = parse_data_uri(value.get("image_url")?.as_str()?)?;
+        return Some((media_type.to_string(), data.to_string()));
+    }
+
+    None
+}
+
+fn parse_data_uri(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix("data:")?;
+    let (header, data) = rest.split_once(',')?;
+    let media_type = header.strip_suffix(";base64")?;
+    Some((media_type, data))
+}
+
+fn push_attachment(
+    attachments: &mut Vec<Attachment>,
+    media_type: String,
+    bytes: Vec<u8>,
+) -> String {
+    let index = attachments.len() + 1;
+    let file_name = format!("{index}.{}", extension(&media_type));
+    let link = format!("{ATTACHMENTS_DIR}/{file_name}");
+    let text = if media_type.starts_with("image/") {
+        format!("![image {index}]({link})")
+    } else {
+        format!("[attachment {index}: {media_type}]({link})")
+    };
+    attachments.push(Attachment {
+        file_name,
+        media_type,
+        bytes,
+    });
+    text
+}
+
+fn extension(media_type: &str) -> &'static str {
+    match media_type {
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        "application/pdf" => "pdf",
+        "text/plain" => "txt",
+        "application/json" => "json",
+        _ => "bin",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::extract_attachments;
+
+    #[test]
+    fn replaces_base64_blocks_and_data_uris_with_links() {
+        let mut value = json!({
+            "message": {"content": [
+                {"type": "text", "text": "see screenshot"},
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "aGVsbG8="}},
+                {"type": "input_image", "image_url": "data:image/jpeg;base64,d29ybGQ="},
+                {"type": "document", "source": {"type": "base64", "media_type": "application/pdf", "data": "not base64!"}}
+            ]}
+        });
+        let mut attachments = Vec::new();
+        extract_attachments(&mut value, &mut attachments);
+
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0].file_name, "1.png");
+        assert_eq!(attachments[0].bytes, b"hello");
+        assert_eq!(attachments[1].file_name, "2.jpg");
+        assert_eq!(attachments[1].bytes, b"world");
+        let content = &value["message"]["content"];
+        assert_eq!(content[1]["text"], "![image 1](attachments/1.png)");
+        assert_eq!(content[2]["text"], "![image 2](attachments/2.jpg)");
+        assert_eq!(content[3]["source"]["data"], "not base64!");
+    }
+}
@@ -0,0 +1,184 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use walkdir::WalkDir;
+
+use crate::model::{CacheGcOptions, CacheGcReport};
+use crate::provider::ProviderRoots;
+use crate::provider::opencode::materialized_root;
+
+/// One unit `xurl cache gc` keeps or deletes as a whole.
+struct CacheEntry {
+    path: PathBuf,
+    is_dir: bool,
+    bytes: u64,
+    /// Newest modification time of the entry's files: when it was last refreshed.
+    modified: SystemTime,
+}
+
+/// Prunes the caches xurl rebuilds on demand: JSONL exports materialized from OpenCode's
+/// sqlite database, and repositories cloned for `skills://` URIs.
+///
+/// Entries older than `max_age` are deleted first; then, oldest first, entries are deleted
+/// until the remaining ones fit in `max_size`. With `dry_run` nothing is deleted and the
+/// report lists what would have been. Entries that cannot be read or deleted are reported as
+/// warnings and kept.
+pub fn gc_caches(roots: &ProviderRoots, options: &CacheGcOptions) -> CacheGcReport {
+    let mut report = CacheGcReport::default();
+    let mut entries = Vec::new();
+    collect_files(&materialized_root(), &mut entries, &mut report.warnings);
+    collect_dirs(&roots.skills_cache_root, &mut entries, &mut report.warnings);
+    entries.sort_by_key(|entry| entry.modified);
+
+    let now = SystemTime::now();
+    let mut total = entries.iter().map(|entry| entry.bytes).sum::<u64>();
+    for entry in entries {
+        let age = now.duration_since(entry.modified).unwrap_or_default();
+        let expired = options.max_age.is_some_and(|max_age| age > max_age);
+        let oversized = options.max_size.is_some_and(|max_size| total > max_size);
+        if !(expired || oversized) {
+            report.kept += 1;
+            report.kept_bytes += entry.bytes;
+            continue;
+        }
+        if !options.dry_run
+            && let Err(err) = remove_entry(&entry)
+        {
+            report
+                .warnings
+                .push(format!("could not delete {}: {err}", entry.path.display()));
+            report.kept += 1;
+            report.kept_bytes += entry.bytes;
+            continue;
+        }
+        total -= entry.bytes;
+        report.freed_bytes += entry.bytes;
+        report.removed.push(entry.path);
+    }
+    report
+}
+
+/// Every file below `root` is an entry of its own.
+fn collect_files(root: &Path, entries: &mut Vec<CacheEntry>, warnings: &mut Vec<String>) {
+    if !root.exists() {
+        return;
+    }
+    for entry in WalkDir::new(root) {
+        match entry.and_then(|entry| Ok((entry.metadata()?, entry))) {
+            Ok((metadata, entry)) if metadata.is_file() => entries.push(CacheEntry {
+                path: entry.into_path(),
+                is_dir: false,
+                bytes: metadata.len(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            }),
+            Ok(_) => {}
+            Err(err) => warnings.push(format!("skipped cache entry: {err}")),
+        }
+    }
+}
+
+/// Every directory right below `root` is an entry, sized and dated by the files inside it.
+fn collect_dirs(root: &Path, entries: &mut Vec<CacheEntry>, warnings: &mut Vec<String>) {
+    let Ok(dirs) = fs::read_dir(root) else {
+        return;
+    };
+    for dir in dirs.filter_map(std::result::Result::ok) {
+        let path = dir.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let mut bytes = 0;
+        let mut modified = None::<SystemTime>;
+        for file in WalkDir::new(&path) {
+            let metadata = match file.and_then(|file| file.metadata()) {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    warnings.push(format!("skipped cache entry: {err}"));
+                    continue;
+                }
+            };
+            if metadata.is_file() {
+                bytes += metadata.len();
+                if let Ok(file_modified) = metadata.modified() {
+                    modified = modified.max(Some(file_modified));
+                }
+            }
+        }
+        // An empty directory is dated by the directory itself.
+        let modified = modified
+            .or_else(|| dir.metadata().and_then(|metadata| metadata.modified()).ok())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        entries.push(CacheEntry {
+            path,
+            is_dir: true,
+            bytes,
+            modified,
+        });
+    }
+}
+
+fn remove_entry(entry: &CacheEntry) -> std::io::Result<()> {
+    if entry.is_dir {
+        return fs::remove_dir_all(&entry.path);
+    }
+    fs::remove_file(&entry.path)?;
+    // Drops the per-root directory once its last export is gone.
+    if let Some(parent) = entry.path.parent() {
+        let _ = fs::remove_dir(parent);
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    use tempfile::tempdir;
+
+    use super::{CacheEntry, collect_dirs};
+
+    fn touch(path: &Path, bytes: usize, age: Duration) {
+        fs::write(path, vec![b'x'; bytes]).expect("write");
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(SystemTime::now() - age))
+            .expect("set mtime");
+    }
+
+    #[test]
+    fn skills_clones_are_dated_by_their_newest_file() {
+        let temp = tempdir().expect("tempdir");
+        let day = Duration::from_secs(24 * 3600);
+        for (repo, age) in [("old", 40 * day), ("fresh", day)] {
+            let dir = temp.path().join(repo);
+            fs::create_dir_all(dir.join(".git")).expect("mkdir");
+            touch(&dir.join("SKILL.md"), 10, 60 * day);
+            touch(&dir.join(".git/FETCH_HEAD"), 5, age);
+        }
+
+        let mut entries = Vec::<CacheEntry>::new();
+        let mut warnings = Vec::new();
+        collect_dirs(temp.path(), &mut entries, &mut warnings);
+        entries.sort_by_key(|entry| entry.modified);
+
+        assert!(warnings.is_empty(), "{warnings:?}");
+        let names = entries
+            .iter()
+            .map(|entry| entry.path.file_name().expect("name").to_string_lossy())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["old", "fresh"]);
+        assert!(
+            entries
+                .iter()
+                .all(|entry| entry.bytes == 15 && entry.is_dir)
+        );
+        let age = SystemTime::now()
+            .duration_since(entries[1].modified)
+            .expect("past");
+        assert!(age >= day && age < 2 * day, "{age:?}");
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{Result, XurlError};
+use crate::frontmatter::{CommandEnricher, FrontmatterEnricher};
+use crate::model::{ProviderKind, ResolvedBin};
+
+/// User configuration loaded from `~/.xurl/config.toml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct XurlConfig {
+    /// External commands that add fields to thread frontmatter, run in order.
+    pub enrichers: Vec<EnricherConfig>,
+    /// How thread bodies are rendered.
+    pub render: RenderConfig,
+    /// Header mode (`-I`) settings.
+    pub head: HeadConfig,
+    /// `[models]` allowlists keyed by provider name; providers without a list accept any model.
+    pub models: BTreeMap<String, Vec<String>>,
+    /// `[aliases]` table mapping a name to the URI it stands for.
+    pub aliases: BTreeMap<String, String>,
+    /// `[bins]` paths to provider CLIs (and `curl`) keyed by name; `XURL_<NAME>_BIN` wins.
+    pub bins: BTreeMap<String, String>,
+    /// Path globs like `**/node_modules/**` that scans of provider roots skip.
+    pub ignore: Vec<String>,
+}
+
+/// `[render]` settings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RenderConfig {
+    /// Tool outputs larger than this many bytes are replaced with a size and sha256 note.
+    pub elide_bytes: u64,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            elide_bytes: DEFAULT_ELIDE_BYTES,
+        }
+    }
+}
+
+/// `[head]` settings.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HeadConfig {
+    /// Top-level frontmatter keys `-I` prints when `--head-fields` is not given; empty prints
+    /// every key.
+    pub fields: Vec<String>,
+}
+
+pub const DEFAULT_ELIDE_BYTES: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnricherConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl XurlConfig {
+    /// Loads the config file, returning the default config when it does not exist.
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::path()?)
+    }
+
+    pub fn path() -> Result<PathBuf> {
+        // Precedence:
+        // 1) XURL_CONFIG
+        // 2) ~/.xurl/config.toml
+        if let Some(path) = env::var_os("XURL_CONFIG").filter(|path| !path.is_empty()) {
+            return Ok(PathBuf::from(path));
+        }
+        let home = dirs::home_dir().ok_or(XurlError::HomeDirectoryNotFound)?;
+        Ok(home.join(".xurl/config.toml"))
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let raw = match fs::read_to_string(path) {
+            Ok(raw) => raw,
+            Err(source) if source.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(source) => {
+                return Err(XurlError::Io {
+                    path: path.to_path_buf(),
+                    source,
+                });
+            }
+        };
+        toml::from_str(&raw).map_err(|err| XurlError::InvalidConfig {
+            path: path.to_path_buf(),
+            message: err.message().to_string(),
+        })
+    }
+
+    /// Sets `[aliases] <name> = "<uri>"` in the config file at `path`, replacing an earlier
+    /// value and keeping the rest of the file as written.
+    pub fn set_alias(path: &Path, name: &str, uri: &str) -> Result<()> {
+        let raw = Self::read_for_edit(path)?;
+        let (edited, _) = edit_alias(&raw, name, Some(uri));
+        Self::write_edited(path, &edited)
+    }
+
+    /// Removes `name` from `[aliases]`; returns whether it was set.
+    pub fn remove_alias(path: &Path, name: &str) -> Result<bool> {
+        let raw = Self::read_for_edit(path)?;
+        let (edited, found) = edit_alias(&raw, name, None);
+        if found {
+            Self::write_edited(path, &edited)?;
+        }
+        Ok(found)
+    }
+
+    /// Reads the config file for an in-place edit, refusing to touch a file that does not parse.
+    fn read_for_edit(path: &Path) -> Result<String> {
+        Self::load_from(path)?;
+        match fs::read_to_string(path) {
+            Ok(raw) => Ok(raw),
+            Err(source) if source.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+            Err(source) => Err(XurlError::Io {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+
+    fn write_edited(path: &Path, raw: &str) -> Result<()> {
+        toml::from_str::<Self>(raw).map_err(|err| XurlError::InvalidConfig {
+            path: path.to_path_buf(),
+            message: err.message().to_string(),
+        })?;
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent).map_err(|source| XurlError::Io {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+        }
+        fs::write(path, raw).map_err(|source| XurlError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Resolves the binary xurl runs for `name`, a provider or `curl`: the `XURL_<NAME>_BIN`
+    /// environment variable, then `[bins] <name>` in the config file, then `name` on `PATH`.
+    /// A config file that does not load counts as empty.
+    pub fn resolve_bin(name: &str) -> ResolvedBin {
+        let var = format!("XURL_{}_BIN", name.to_ascii_uppercase());
+        if let Some(path) = env::var(&var).ok().filter(|path| !path.is_empty()) {
+            return ResolvedBin { path, source: var };
+        }
+        if let Some(path) = Self::load()
+            .ok()
+            .and_then(|config| config.bins.get(name).cloned())
+            .filter(|path| !path.is_empty())
+        {
+            return ResolvedBin {
+                path,
+                source: format!("[bins] {name}"),
+            };
+        }
+        ResolvedBin {
+            path: name.to_string(),
+            source: "PATH".to_string(),
+        }
+    }
+
+    /// Rejects a write-mode `model` missing from the provider's `[models]` allowlist.
+    pub fn check_model(&self, provider: ProviderKind, model: &str) -> Result<()> {
+        let Some(allowed) = self.models.get(&provider.to_string()) else {
+            return Ok(());
+        };
+        if allowed.iter().any(|candidate| candidate == model) {
+            return Ok(());
+        }
+        Err(XurlError::InvalidMode(format!(
+            "model `{model}` is not allowed for provider {provider}; [models] {provider} allows: {}",
+            allowed.join(", ")
+        )))
+    }
+
+    pub fn frontmatter_enrichers(&self) -> Vec<Box<dyn FrontmatterEnricher>> {
+        self.enrichers
+            .iter()
+            .map(|enricher| {
+                Box::new(CommandEnricher::new(
+                    enricher.command.clone(),
+                    enricher.args.clone(),
+                )) as Box<dyn FrontmatterEnricher>
+            })
+            .collect()
+    }
+}
+
+/// Sets (`Some`) or removes (`None`) one key of the `[aliases]` table in `raw`, line by line so
+/// comments and the other tables stay as written. Returns the new text and whether the key
+/// existed.
+fn edit_alias(raw: &str, name: &str, uri: Option<&str>) -> (String, bool) {
+    let mut lines = raw.lines().map(str::to_string).collect::<Vec<_>>();
+    let entry = uri.map(|uri| {
+        format!(
+            "{name} = \"{}\"",
+            uri.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+    });
+
+    let Some(start) = lines.iter().position(|line| line.trim() == "[aliases]") else {
+        let Some(entry) = entry else {
+            return (raw.to_string(), false);
+        };
+        let mut edited = raw.trim_end().to_string();
+        if !edited.is_empty() {
+            edited.push_str("\n\n");
+        }
+        edited.push_str(&format!("[aliases]\n{entry}\n"));
+        return (edited, false);
+    };
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .map_or(lines.len(), |offset| start + 1 + offset);
+    let existing = (start + 1..end).find(|&idx| {
+        lines[idx]
+            .split_once('=')
+            .is_some_and(|(key, _)| key.trim().trim_matches('"') == name)
+    });
+
+    match (existing, entry) {
+        (Some(idx), Some(entry)) => lines[idx] = entry,
+        (Some(idx), None) => {
+            lines.remove(idx);
+        }
+        (None, Some(entry)) => {
+            let last = (start..end)
+                .rev()
+                .find(|&idx| !lines[idx].trim().is_empty())
+                .unwrap_or(start);
+            lines.insert(last + 1, entry);
+        }
+        (None, None) => {}
+    }
+    let mut edited = lines.join("\n");
+    edited.push('\n');
+    (edited, existing.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::{DEFAULT_ELIDE_BYTES, XurlConfig};
+    use crate::error::XurlError;
+    use crate::model::ProviderKind;
+
+    #[test]
+    fn load_from_missing_file_returns_default() {
+        let temp = tempdir().expect("tempdir");
+        let config = XurlConfig::load_from(&temp.path().join("config.toml")).expect("load");
+        assert!(config.enrichers.is_empty());
+        assert_eq!(config.render.elide_bytes, DEFAULT_ELIDE_BYTES);
+    }
+
+    #[test]
+    fn load_from_reads_enrichers_and_rejects_invalid_toml() {
+        let temp = tempdir().expect("tempdir");
+        let path = temp.path().join("config.toml");
+        fs::write(
+            &path,
+            "[[enrichers]]\ncommand = \"jira-from-branch\"\nargs = [\"--prefix\", \"JIRA\"]\n",
+        )
+        .expect("write");
+        let config = XurlConfig::load_from(&path).expect("load");
+        assert_eq!(config.enrichers.len(), 1);
+        assert_eq!(config.enrichers[0].command, "jira-from-branch");
+        assert_eq!(config.enrichers[0].args, vec!["--prefix", "JIRA"]);
+
+        fs::write(&path, "[render]\nelide_bytes = 1024\n").expect("write");
+        let config = XurlConfig::load_from(&path).expect("load");
+        assert_eq!(config.render.elide_bytes, 1024);
+        assert!(config.head.fields.is_empty());
+
+        fs::write(&path, "[head]\nfields = [\"uri\", \"status\"]\n").expect("write");
+        let config = XurlConfig::load_from(&path).expect("load");
+        assert_eq!(config.head.fields, vec!["uri", "status"]);
+
+        fs::write(&path, "[models]\ncodex = [\"gpt-5\", \"gpt-5-codex\"]\n").expect("write");
+        let config = XurlConfig::load_from(&path).expect("load");
+        assert!(config.check_model(ProviderKind::Codex, "gpt-5").is_ok());
+        assert!(config.check_model(ProviderKind::Claude, "opus").is_ok());
+        let err = config
+            .check_model(ProviderKind::Codex, "gpt-4o")
+            .expect_err("not allowlisted");
+        assert!(err.to_string().contains("gpt-5, gpt-5-codex"));
+
+        fs::write(&path, "[[enrichers]]\nargs = []\n").expect("write");
+        let err = XurlConfig::load_from(&path).expect_err("missing command");
+        assert!(matches!(err, XurlError::InvalidConfig { .. }));
+    }
+
+    #[test]
+    fn set_and_remove_alias_keep_the_rest_of_the_file() {
+        let temp = tempdir().expect("tempdir");
+        let path = temp.path().join("xurl/config.toml");
+
+        XurlConfig::set_alias(&path, "first", "agents://codex/a").expect("set on missing file");
+        fs::write(
+            &path,
+            format!(
+                "# team config\n[render]\nelide_bytes = 1024\n\n{}",
+                fs::read_to_string(&path).expect("read")
+            ),
+        )
+        .expect("write");
+
+        XurlConfig::set_alias(&path, "release-debug", "agents://codex/b?model=\"x\"")
+            .expect("add alias");
+        XurlConfig::set_alias(&path, "first", "agents://claude/c").expect("replace alias");
+        let raw = fs::read_to_string(&path).expect("read");
+        assert!(raw.starts_with("# team config\n[render]\nelide_bytes = 1024\n"));
+        let config = XurlConfig::load_from(&path).expect("load");
+        assert_eq!(config.render.elide_bytes, 1024);
+        assert_eq!(config.aliases["first"], "agents://claude/c");
+        assert_eq!(
+            config.aliases["release-debug"],
+            "agents://codex/b?model=\"x\""
+        );
+
+        assert!(XurlConfig::remove_alias(&path, "first").expect("remove"));
+        assert!(!XurlConfig::remove_alias(&path, "first").expect("remove again"));
+        let config = XurlConfig::load_from(&path).expect("load");
+        assert_eq!(config.aliases.len(), 1);
+    }
+}
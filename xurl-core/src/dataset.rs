@@ -0,0 +1,292 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::error::{Result, XurlError};
+use crate::model::MessageRow;
+use crate::parallel::run_ordered;
+use crate::provider::ProviderRoots;
+use crate::render;
+use crate::service::{read_thread_raw, recent_threads, resolve_thread};
+use crate::uri::AgentsUri;
+use crate::usage;
+
+/// Columns of a dataset export, in order.
+pub const DATASET_COLUMNS: [&str; 9] = [
+    "provider",
+    "session_id",
+    "uri",
+    "seq",
+    "role",
+    "timestamp",
+    "thread_input_tokens",
+    "thread_output_tokens",
+    "text",
+];
+
+/// One row per message of the thread at `uri`, or of every main thread of the provider when
+/// `uri` is a collection like `agents://codex`, reading up to `jobs` of them at once. Rows
+/// stay in thread order. Threads in a collection that fail to resolve or parse are reported
+/// as warnings and left out, unless strict parsing failed them.
+pub fn dataset_rows(
+    uri: &AgentsUri,
+    jobs: usize,
+    roots: &ProviderRoots,
+) -> Result<(Vec<MessageRow>, Vec<String>)> {
+    if !uri.is_collection() {
+        return Ok((thread_rows(uri, roots)?, Vec::new()));
+    }
+    let recent = recent_threads(Some(uri.provider), usize::MAX, roots)?;
+    let threads = recent
+        .items
+        .iter()
+        .filter_map(|item| AgentsUri::parse(&item.uri).ok())
+        .filter(|thread| thread.agent_id.is_none())
+        .collect::<Vec<_>>();
+    let mut warnings = recent.warnings;
+    let mut rows = Vec::new();
+    let mut strict_error = None;
+    run_ordered(
+        threads.len(),
+        jobs.max(1),
+        |idx| thread_rows(&threads[idx], roots),
+        |idx, result| match result {
+            Ok(thread_rows) => rows.extend(thread_rows),
+            Err(err @ XurlError::StrictParse { .. }) => {
+                strict_error.get_or_insert(err);
+            }
+            Err(err) => warnings.push(format!(
+                "skipped {}: {err}",
+                threads[idx].as_agents_string()
+            )),
+        },
+    );
+    match strict_error {
+        Some(err) => Err(err),
+        None => Ok((rows, warnings)),
+    }
+}
+
+/// Token usage is a thread total on every row: providers record usage per turn or per
+/// thread rather than per message.
+fn thread_rows(uri: &AgentsUri, roots: &ProviderRoots) -> Result<Vec<MessageRow>> {
+    let resolved = resolve_thread(uri, roots)?;
+    let raw = read_thread_raw(&resolved.path)?;
+    let tokens = usage::token_usage(uri.provider, &raw);
+    let key = uri.as_agents_string();
+    Ok(
+        render::extract_messages(uri.provider, &resolved.path, &raw)?
+            .into_iter()
+            .enumerate()
+            .map(|(idx, message)| MessageRow {
+                provider: uri.provider,
+                session_id: uri.session_id.clone(),
+                uri: key.clone(),
+                seq: idx as u64 + 1,
+                role: message.role,
+                timestamp: message.timestamp,
+                thread_input_tokens: tokens.map(|tokens| tokens.input_tokens),
+                thread_output_tokens: tokens.map(|tokens| tokens.output_tokens),
+                text: message.text,
+            })
+            .collect(),
+    )
+}
+
+/// Writes `rows` as RFC 4180 CSV with a header line.
+pub fn write_dataset_csv<W: Write>(rows: &[MessageRow], out: &mut W) -> io::Result<()> {
+    write!(out, "{}\r\n", DATASET_COLUMNS.join(","))?;
+    let optional = |value: Option<u64>| value.map(|value| value.to_string()).unwrap_or_default();
+    for row in rows {
+        let fields = [
+            row.provider.to_string(),
+            row.session_id.clone(),
+            row.uri.clone(),
+            row.seq.to_string(),
+            row.role.to_string(),
+            row.timestamp.clone().unwrap_or_default(),
+            optional(row.thread_input_tokens),
+            optional(row.thread_output_tokens),
+            row.text.clone(),
+        ];
+        let line = fields
+            .iter()
+            .map(|field| csv_field(field))
+            .collect::<Vec<_>>()
+            .join(",");
+        out.write_all(line.as_bytes())?;
+        out.write_all(b"\r\n")?;
+    }
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes `rows` to a parquet file at `path`, in one row group.
+#[cfg(feature = "parquet")]
+pub fn write_dataset_parquet(rows: &[MessageRow], path: &Path) -> Result<()> {
+    use std::fs;
+    use std::sync::Arc;
+
+    use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+
+    const SCHEMA: &str = "
+message xurl_messages {
+    REQUIRED BYTE_ARRAY provider (UTF8);
+    REQUIRED BYTE_ARRAY session_id (UTF8);
+    REQUIRED BYTE_ARRAY uri (UTF8);
+    REQUIRED INT64 seq;
+    REQUIRED BYTE_ARRAY role (UTF8);
+    OPTIONAL BYTE_ARRAY timestamp (UTF8);
+    OPTIONAL INT64 thread_input_tokens;
+    OPTIONAL INT64 thread_output_tokens;
+    REQUIRED BYTE_ARRAY text (UTF8);
+}
+";
+
+    let parquet_err = |err: parquet::errors::ParquetError| {
+        XurlError::Serialization(format!("failed writing parquet {}: {err}", path.display()))
+    };
+    let strings = |values: Vec<String>| {
+        values
+            .into_iter()
+            .map(|value| ByteArray::from(value.into_bytes()))
+            .collect::<Vec<_>>()
+    };
+    let optional_strings = |values: Vec<Option<String>>| {
+        let levels = values
+            .iter()
+            .map(|value| i16::from(value.is_some()))
+            .collect::<Vec<_>>();
+        (strings(values.into_iter().flatten().collect()), levels)
+    };
+    let optional_ints = |values: Vec<Option<u64>>| {
+        let levels = values
+            .iter()
+            .map(|value| i16::from(value.is_some()))
+            .collect::<Vec<_>>();
+        let values = values
+            .into_iter()
+            .flatten()
+            .map(|value| i64::try_from(value).unwrap_or(i64::MAX))
+            .collect::<Vec<_>>();
+        (values, levels)
+    };
+
+    let schema = Arc::new(parse_message_type(SCHEMA).map_err(parquet_err)?);
+    let file = fs::File::create(path).map_err(|source| XurlError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut writer =
+        SerializedFileWriter::new(file, schema, Arc::new(WriterProperties::builder().build()))
+            .map_err(parquet_err)?;
+    let mut row_group = writer.next_row_group().map_err(parquet_err)?;
+    let mut column_idx = 0;
+    while let Some(mut column) = row_group.next_column().map_err(parquet_err)? {
+        match DATASET_COLUMNS[column_idx] {
+            "seq" => {
+                let values = rows
+                    .iter()
+                    .map(|row| i64::try_from(row.seq).unwrap_or(i64::MAX))
+                    .collect::<Vec<_>>();
+                column
+                    .typed::<Int64Type>()
+                    .write_batch(&values, None, None)
+                    .map_err(parquet_err)?;
+            }
+            name @ ("thread_input_tokens" | "thread_output_tokens") => {
+                let (values, levels) = optional_ints(
+                    rows.iter()
+                        .map(|row| {
+                            if name == "thread_input_tokens" {
+                                row.thread_input_tokens
+                            } else {
+                                row.thread_output_tokens
+                            }
+                        })
+                        .collect(),
+                );
+                column
+                    .typed::<Int64Type>()
+                    .write_batch(&values, Some(&levels), None)
+                    .map_err(parquet_err)?;
+            }
+            "timestamp" => {
+                let (values, levels) =
+                    optional_strings(rows.iter().map(|row| row.timestamp.clone()).collect());
+                column
+                    .typed::<ByteArrayType>()
+                    .write_batch(&values, Some(&levels), None)
+                    .map_err(parquet_err)?;
+            }
+            name => {
+                let values = strings(
+                    rows.iter()
+                        .map(|row| match name {
+                            "provider" => row.provider.to_string(),
+                            "session_id" => row.session_id.clone(),
+                            "uri" => row.uri.clone(),
+                            "role" => row.role.to_string(),
+                            _ => row.text.clone(),
+                        })
+                        .collect(),
+                );
+                column
+                    .typed::<ByteArrayType>()
+                    .write_batch(&values, None, None)
+                    .map_err(parquet_err)?;
+            }
+        }
+        column.close().map_err(parquet_err)?;
+        column_idx += 1;
+    }
+    row_group.close().map_err(parquet_err)?;
+    writer.close().map_err(parquet_err)?;
+    Ok(())
+}
+
+/// Parquet support is compiled in with the `parquet` feature.
+#[cfg(not(feature = "parquet"))]
+pub fn write_dataset_parquet(_rows: &[MessageRow], _path: &Path) -> Result<()> {
+    Err(XurlError::InvalidMode(
+        "--format parquet requires xurl built with the `parquet` feature: cargo install xurl-cli --features parquet"
+            .to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_dataset_csv;
+    use crate::model::{MessageRole, MessageRow, ProviderKind};
+
+    #[test]
+    fn write_dataset_csv_quotes_fields_that_need_it() {
+        let rows = vec![MessageRow {
+            provider: ProviderKind::Claude,
+            session_id: "s1".to_string(),
+            uri: "agents://claude/s1".to_string(),
+            seq: 1,
+            role: MessageRole::Assistant,
+            timestamp: None,
+            thread_input_tokens: Some(12),
+            thread_output_tokens: None,
+            text: "say \"hi\",\nthen stop".to_string(),
+        }];
+        let mut out = Vec::new();
+        write_dataset_csv(&rows, &mut out).expect("write");
+        assert_eq!(
+            String::from_utf8(out).expect("utf8"),
+            "provider,session_id,uri,seq,role,timestamp,thread_input_tokens,thread_output_tokens,text\r\n\
+             claude,s1,agents://claude/s1,1,assistant,,12,,\"say \"\"hi\"\",\nthen stop\"\r\n"
+        );
+    }
+}
@@ -28,6 +28,9 @@ pub enum XurlError {
     #[error("provider does not support write mode: {0}")]
     UnsupportedProviderWrite(String),
 
+    #[error("provider does not support fsck: {0}")]
+    UnsupportedFsckProvider(String),
+
     #[error("command not found: {command}")]
     CommandNotFound { command: String },
 
@@ -44,6 +47,9 @@ pub enum XurlError {
     #[error("serialization error: {0}")]
     Serialization(String),
 
+    #[error("invalid config {path}: {message}")]
+    InvalidConfig { path: PathBuf, message: String },
+
     #[error("cannot determine home directory")]
     HomeDirectoryNotFound,
 
@@ -54,9 +60,22 @@ pub enum XurlError {
         searched_roots: Vec<PathBuf>,
     },
 
+    #[error("multiple threads matched session id prefix for provider={provider} prefix={prefix}")]
+    AmbiguousSessionPrefix {
+        provider: String,
+        prefix: String,
+        candidates: Vec<String>,
+    },
+
     #[error("skill not found for uri={uri}")]
     SkillNotFound { uri: String },
 
+    #[error("snapshot not found for uri={uri} in {root}")]
+    SnapshotNotFound { uri: String, root: PathBuf },
+
+    #[error("invalid snapshot manifest {path}: {message}")]
+    InvalidSnapshotManifest { path: PathBuf, message: String },
+
     #[error("multiple skills matched for uri={uri}; choose one of: {candidates:?}")]
     SkillSelectionRequired {
         uri: String,
@@ -103,6 +122,12 @@ pub enum XurlError {
         source: rusqlite::Error,
     },
 
+    #[error("timed out after {timeout} waiting for {uri} to leave pendingInit/running")]
+    SubagentWaitTimeout { uri: String, timeout: String },
+
+    #[error("strict mode: {uri} has parse warnings: {}", problems.join("; "))]
+    StrictParse { uri: String, problems: Vec<String> },
+
     #[error("invalid json line in {path} at line {line}: {source}")]
     InvalidJsonLine {
         path: PathBuf,
@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use rusqlite::{Connection, params};
+
+use crate::error::{Result, XurlError};
+use crate::model::{ExportDbReport, ProviderKind, ThreadQueryItem};
+use crate::provider::ProviderRoots;
+use crate::render;
+use crate::service::{read_thread_raw, recent_threads, resolve_thread};
+use crate::tools;
+use crate::uri::AgentsUri;
+
+/// Tables `xurl export-db` writes. Rows of a thread are keyed by its `agents://` URI, and
+/// `seq` is the 1-based position of a message or tool call in its thread.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS threads (
+    uri TEXT PRIMARY KEY,
+    provider TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    thread_source TEXT NOT NULL,
+    updated_at TEXT
+);
+CREATE TABLE IF NOT EXISTS messages (
+    thread_uri TEXT NOT NULL REFERENCES threads(uri),
+    seq INTEGER NOT NULL,
+    role TEXT NOT NULL,
+    text TEXT NOT NULL,
+    timestamp TEXT,
+    PRIMARY KEY (thread_uri, seq)
+);
+CREATE TABLE IF NOT EXISTS tool_calls (
+    thread_uri TEXT NOT NULL REFERENCES threads(uri),
+    seq INTEGER NOT NULL,
+    call_id TEXT,
+    name TEXT NOT NULL,
+    input TEXT NOT NULL,
+    output TEXT,
+    PRIMARY KEY (thread_uri, seq)
+);
+";
+
+/// Writes the main threads of `provider` (every provider when `None`) with their messages and
+/// tool calls into the sqlite database at `out`, creating it and its tables when missing.
+///
+/// Re-exporting into an existing database replaces the rows of every exported thread, so the
+/// file can be refreshed in place. A thread that fails to resolve or parse is reported as a
+/// warning and left out.
+pub fn export_threads_db(
+    out: &Path,
+    provider: Option<ProviderKind>,
+    roots: &ProviderRoots,
+) -> Result<ExportDbReport> {
+    let sqlite_err = |source| XurlError::Sqlite {
+        path: out.to_path_buf(),
+        source,
+    };
+    let mut conn = Connection::open(out).map_err(sqlite_err)?;
+    conn.execute_batch(SCHEMA).map_err(sqlite_err)?;
+
+    let recent = recent_threads(provider, usize::MAX, roots)?;
+    let mut report = ExportDbReport {
+        warnings: recent.warnings,
+        ..ExportDbReport::default()
+    };
+    let tx = conn.transaction().map_err(sqlite_err)?;
+    for item in &recent.items {
+        let Ok(uri) = AgentsUri::parse(&item.uri) else {
+            continue;
+        };
+        if uri.agent_id.is_some() {
+            continue;
+        }
+        match export_thread_rows(&tx, out, &uri, item, roots) {
+            Ok((messages, tool_calls)) => {
+                report.threads += 1;
+                report.messages += messages;
+                report.tool_calls += tool_calls;
+            }
+            Err(err @ (XurlError::Sqlite { .. } | XurlError::StrictParse { .. })) => {
+                return Err(err);
+            }
+            Err(err) => report
+                .warnings
+                .push(format!("skipped {}: {err}", uri.as_agents_string())),
+        }
+    }
+    tx.commit().map_err(sqlite_err)?;
+    Ok(report)
+}
+
+/// Replaces the rows of one thread and returns how many messages and tool calls it wrote.
+fn export_thread_rows(
+    conn: &Connection,
+    out: &Path,
+    uri: &AgentsUri,
+    item: &ThreadQueryItem,
+    roots: &ProviderRoots,
+) -> Result<(usize, usize)> {
+    let resolved = resolve_thread(uri, roots)?;
+    let raw = read_thread_raw(&resolved.path)?;
+    let messages = render::extract_messages(uri.provider, &resolved.path, &raw)?;
+    let calls = tools::extract_tool_calls(uri.provider, &resolved.path, &raw)?;
+
+    let key = uri.as_agents_string();
+    let sqlite_err = |source| XurlError::Sqlite {
+        path: out.to_path_buf(),
+        source,
+    };
+    for table in ["messages", "tool_calls", "threads"] {
+        let column = if table == "threads" {
+            "uri"
+        } else {
+            "thread_uri"
+        };
+        conn.execute(&format!("DELETE FROM {table} WHERE {column} = ?1"), [&key])
+            .map_err(sqlite_err)?;
+    }
+    conn.execute(
+        "INSERT INTO threads (uri, provider, session_id, thread_source, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            key,
+            uri.provider.to_string(),
+            uri.session_id,
+            resolved.path.display().to_string(),
+            item.updated_at,
+        ],
+    )
+    .map_err(sqlite_err)?;
+    for (idx, message) in messages.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO messages (thread_uri, seq, role, text, timestamp) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                key,
+                idx + 1,
+                message.role.to_string(),
+                message.text,
+                message.timestamp,
+            ],
+        )
+        .map_err(sqlite_err)?;
+    }
+    for (idx, call) in calls.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO tool_calls (thread_uri, seq, call_id, name, input, output) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                key,
+                idx + 1,
+                call.id,
+                call.name,
+                call.input.to_string(),
+                call.output,
+            ],
+        )
+        .map_err(sqlite_err)?;
+    }
+    Ok((messages.len(), calls.len()))
+}
@@ -0,0 +1,167 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::model::{Finding, FindingSeverity, MessageRole, ThreadMessage};
+
+// `src/lib.rs:42`, `src/lib.rs:42-50`, `src/lib.rs line 42`, and `src/lib.rs#L42`.
+static LOCATION_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?P<path>(?:[\w.\-]+/)*[\w\-]+(?:\.[\w\-]+)*\.[A-Za-z][A-Za-z0-9]*)`?(?::(?P<colon>\d+)|,?\s+lines?\s+(?P<word>\d+)|#L(?P<anchor>\d+))",
+    )
+    .expect("valid regex")
+});
+static URL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\w+://\S+").expect("valid regex"));
+static LIST_MARKER_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(?:[-*+]|\d+[.)])\s+").expect("valid regex"));
+
+const ERROR_WORDS: &[&str] = &[
+    "blocker", "critical", "severe", "high", "major", "p0", "p1", "bug", "error",
+];
+const WARNING_WORDS: &[&str] = &["medium", "moderate", "p2", "warning"];
+const NOTE_WORDS: &[&str] = &[
+    "low",
+    "minor",
+    "p3",
+    "nit",
+    "nits",
+    "nitpick",
+    "suggestion",
+    "style",
+    "info",
+];
+
+/// Pulls review findings out of assistant messages.
+///
+/// A finding is a list item, heading, or severity-tagged line that names a file with a line
+/// number. Its severity comes from words like `critical`, `P2`, or `nit` on the line, then
+/// from the nearest heading above it, and is otherwise `warning`. A location reported more
+/// than once keeps its last message, since reviews tend to restate findings in the final
+/// summary.
+pub(crate) fn extract_findings(messages: &[ThreadMessage]) -> Vec<Finding> {
+    let mut findings = Vec::<Finding>::new();
+    for message in messages
+        .iter()
+        .filter(|message| message.role == MessageRole::Assistant)
+    {
+        let mut section = None;
+        let mut in_code = false;
+        for line in message.text.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with("```") {
+                in_code = !in_code;
+                continue;
+            }
+            if in_code || trimmed.is_empty() {
+                continue;
+            }
+            let heading =
+                trimmed.starts_with('#') || (trimmed.starts_with("**") && trimmed.ends_with("**"));
+            let severity = severity_of(trimmed);
+            let Some((path, line_no)) = location(trimmed) else {
+                if heading {
+                    section = severity;
+                }
+                continue;
+            };
+            let list_item = LIST_MARKER_RE.is_match(trimmed);
+            if !list_item && !heading && severity.is_none() {
+                continue;
+            }
+            let finding = Finding {
+                path,
+                line: line_no,
+                severity: severity.or(section).unwrap_or(FindingSeverity::Warning),
+                message: LIST_MARKER_RE.replace(trimmed, "").into_owned(),
+            };
+            findings
+                .retain(|existing| existing.path != finding.path || existing.line != finding.line);
+            findings.push(finding);
+        }
+    }
+    findings
+}
+
+fn location(line: &str) -> Option<(String, u64)> {
+    // Hosts and paths inside URLs, like `https://example.com:8080/a.rs`, are not locations.
+    let line = URL_RE.replace_all(line, "");
+    LOCATION_RE.captures_iter(&line).find_map(|captures| {
+        let path = captures.name("path")?;
+        let line_no = ["colon", "word", "anchor"]
+            .iter()
+            .find_map(|name| captures.name(name))?
+            .as_str()
+            .parse::<u64>()
+            .ok()
+            .filter(|line_no| *line_no > 0)?;
+        let path = path.as_str().trim_start_matches("./");
+        Some((path.to_string(), line_no))
+    })
+}
+
+fn severity_of(line: &str) -> Option<FindingSeverity> {
+    let lowercase = line.to_lowercase();
+    let words = lowercase
+        .split(|ch: char| !ch.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    let has = |candidates: &[&str]| words.iter().any(|word| candidates.contains(word));
+    if has(ERROR_WORDS) {
+        Some(FindingSeverity::Error)
+    } else if has(WARNING_WORDS) {
+        Some(FindingSeverity::Warning)
+    } else if has(NOTE_WORDS) {
+        Some(FindingSeverity::Note)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extract_findings;
+    use crate::model::{FindingSeverity, MessageRole, ThreadMessage};
+
+    fn message(role: MessageRole, text: &str) -> ThreadMessage {
+        ThreadMessage {
+            role,
+            text: text.to_string(),
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn extract_findings_reads_locations_and_severities_from_review_text() {
+        let messages = vec![
+            message(MessageRole::User, "- review src/user.rs:1 please"),
+            message(
+                MessageRole::Assistant,
+                "Looking at src/lib.rs:10 first.\n\n- src/lib.rs:10 may panic",
+            ),
+            message(
+                MessageRole::Assistant,
+                concat!(
+                    "## High\n\n",
+                    "- `src/lib.rs:10` unwraps a user-supplied value and panics.\n",
+                    "- [P2] src/parse.rs line 7 ignores trailing input.\n\n",
+                    "## Nits\n\n",
+                    "1. ./docs/guide.md:3 has a typo.\n",
+                    "- See https://example.com:8080/a.rs:9 for context.\n\n",
+                    "```text\n- src/ignored.rs:1 inside a code block\n```\n",
+                ),
+            ),
+        ];
+
+        let findings = extract_findings(&messages)
+            .into_iter()
+            .map(|finding| (finding.path, finding.line, finding.severity))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            findings,
+            vec![
+                ("src/lib.rs".to_string(), 10, FindingSeverity::Error),
+                ("src/parse.rs".to_string(), 7, FindingSeverity::Warning),
+                ("docs/guide.md".to_string(), 3, FindingSeverity::Note),
+            ]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::io::BufRead;
+
+use serde_json::Value;
+
+use crate::jsonl;
+use crate::model::ProviderKind;
+
+/// Lines read from the start of a rollout while looking for its format version.
+const FORMAT_PROBE_LINES: usize = 20;
+
+/// The newest major version of each provider's on-disk format that the readers understand.
+///
+/// Codex and Claude stamp rollouts with the CLI version that wrote them (`cli_version` in the
+/// `session_meta` payload, `version` on every Claude entry); pi writes a format `version` in
+/// its `session` header. Providers missing from the table are not versioned.
+pub const FORMAT_COMPATIBILITY: &[(ProviderKind, u64)] = &[
+    (ProviderKind::Codex, 0),
+    (ProviderKind::Claude, 2),
+    (ProviderKind::Pi, 3),
+];
+
+/// The format version a rollout declares near its start, when the provider records one.
+pub fn detect_format_version<R: BufRead>(provider: ProviderKind, reader: R) -> Option<String> {
+    reader
+        .lines()
+        .map_while(std::result::Result::ok)
+        .take(FORMAT_PROBE_LINES)
+        .filter_map(|line| jsonl::parse_thread_line(&line))
+        .find_map(|value| declared_version(provider, &value))
+}
+
+fn declared_version(provider: ProviderKind, value: &Value) -> Option<String> {
+    let version = match provider {
+        ProviderKind::Codex => {
+            if value.get("type").and_then(Value::as_str) != Some("session_meta") {
+                return None;
+            }
+            value.pointer("/payload/cli_version")?
+        }
+        ProviderKind::Claude => value.get("version")?,
+        ProviderKind::Pi => {
+            if value.get("type").and_then(Value::as_str) != Some("session") {
+                return None;
+            }
+            value.get("version")?
+        }
+        ProviderKind::Amp | ProviderKind::Gemini | ProviderKind::Opencode => return None,
+    };
+    match version {
+        Value::String(version) => Some(version.clone()),
+        Value::Number(version) => Some(version.to_string()),
+        _ => None,
+    }
+}
+
+/// A warning for a format newer than [`FORMAT_COMPATIBILITY`] lists. Versions that do not
+/// start with a number, like redacted ones, are left alone.
+pub fn format_version_warning(provider: ProviderKind, version: &str) -> Option<String> {
+    let newest = FORMAT_COMPATIBILITY
+        .iter()
+        .find(|(kind, _)| *kind == provider)
+        .map(|(_, newest)| *newest)?;
+    let major = version
+        .trim_start_matches('v')
+        .split(['.', '-', '+'])
+        .next()?
+        .parse::<u64>()
+        .ok()?;
+    (major > newest).then(|| {
+        format!(
+            "{provider} format v{version} not fully supported (newest known: v{newest}); entries xurl does not recognize are left out"
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_format_version, format_version_warning};
+    use crate::model::ProviderKind;
+
+    #[test]
+    fn newer_formats_than_the_table_lists_are_flagged() {
+        let codex =
+            "{\"type\":\"session_meta\",\"payload\":{\"id\":\"s1\",\"cli_version\":\"1.2.0\"}}\n";
+        let version = detect_format_version(ProviderKind::Codex, codex.as_bytes());
+        assert_eq!(version.as_deref(), Some("1.2.0"));
+        assert_eq!(
+            format_version_warning(ProviderKind::Codex, "1.2.0").as_deref(),
+            Some(
+                "codex format v1.2.0 not fully supported (newest known: v0); entries xurl does not recognize are left out"
+            )
+        );
+        assert_eq!(format_version_warning(ProviderKind::Codex, "0.104.0"), None);
+
+        let pi = "{\"type\":\"session\",\"version\":3,\"id\":\"s1\"}\n";
+        let version = detect_format_version(ProviderKind::Pi, pi.as_bytes());
+        assert_eq!(version.as_deref(), Some("3"));
+        assert_eq!(format_version_warning(ProviderKind::Pi, "3"), None);
+        assert!(format_version_warning(ProviderKind::Pi, "4").is_some());
+
+        let claude = "{\"type\":\"user\",\"version\":\"txt_3be394b4\"}\n";
+        let version = detect_format_version(ProviderKind::Claude, claude.as_bytes());
+        assert_eq!(version.as_deref(), Some("txt_3be394b4"));
+        assert_eq!(
+            format_version_warning(ProviderKind::Claude, "txt_3be394b4"),
+            None
+        );
+        assert_eq!(format_version_warning(ProviderKind::Gemini, "9"), None);
+    }
+}
@@ -0,0 +1,301 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::error::{Result, XurlError};
+
+/// Adds fields to a rendered frontmatter head.
+///
+/// Enrichers receive the head document as JSON and return the fields to add. Fields that
+/// already exist in the head are never overwritten.
+pub trait FrontmatterEnricher {
+    fn enrich(&self, head: &Value) -> Result<Map<String, Value>>;
+}
+
+impl<F> FrontmatterEnricher for F
+where
+    F: Fn(&Value) -> Result<Map<String, Value>>,
+{
+    fn enrich(&self, head: &Value) -> Result<Map<String, Value>> {
+        self(head)
+    }
+}
+
+/// Runs an external command with the head JSON on stdin and reads a JSON object from stdout.
+#[derive(Debug, Clone)]
+pub struct CommandEnricher {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandEnricher {
+    pub fn new(program: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            program: program.into(),
+            args,
+        }
+    }
+
+    fn command_line(&self) -> String {
+        std::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl FrontmatterEnricher for CommandEnricher {
+    fn enrich(&self, head: &Value) -> Result<Map<String, Value>> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|source| {
+                if source.kind() == std::io::ErrorKind::NotFound {
+                    XurlError::CommandNotFound {
+                        command: self.program.clone(),
+                    }
+                } else {
+                    XurlError::Io {
+                        path: PathBuf::from(&self.program),
+                        source,
+                    }
+                }
+            })?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            let input = serde_json::to_vec(head)
+                .map_err(|err| XurlError::Serialization(err.to_string()))?;
+            // The command may exit without reading stdin; its exit status decides the outcome.
+            let _ = stdin.write_all(&input);
+        }
+
+        let output = child.wait_with_output().map_err(|source| XurlError::Io {
+            path: PathBuf::from(&self.program),
+            source,
+        })?;
+        if !output.status.success() {
+            return Err(XurlError::CommandFailed {
+                command: self.command_line(),
+                code: output.status.code(),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+
+        match serde_json::from_slice::<Value>(&output.stdout) {
+            Ok(Value::Object(fields)) => Ok(fields),
+            Ok(_) => Err(XurlError::Serialization(format!(
+                "enricher `{}` must print a JSON object",
+                self.command_line()
+            ))),
+            Err(err) => Err(XurlError::Serialization(format!(
+                "enricher `{}` printed invalid JSON: {err}",
+                self.command_line()
+            ))),
+        }
+    }
+}
+
+/// Parses the leading `---` frontmatter block of `markdown` into JSON.
+pub fn parse_frontmatter(markdown: &str) -> Result<Option<Value>> {
+    let Some((yaml, _)) = split_frontmatter(markdown) else {
+        return Ok(None);
+    };
+    serde_yaml::from_str::<Value>(yaml)
+        .map(Some)
+        .map_err(|err| XurlError::Serialization(format!("invalid frontmatter: {err}")))
+}
+
+/// Appends `fields`, a struct or map, to a frontmatter head being built: one `key: value` line
+/// per field, nested values below their key. A value with every field skipped adds nothing.
+pub(crate) fn push_yaml_fields(output: &mut String, fields: &impl Serialize) -> Result<()> {
+    let yaml = serde_yaml::to_string(fields)
+        .map_err(|err| XurlError::Serialization(format!("failed to write frontmatter: {err}")))?;
+    if yaml != "{}\n" {
+        output.push_str(&yaml);
+    }
+    Ok(())
+}
+
+/// Runs `enrichers` in order over the frontmatter of `markdown` and appends the fields they add.
+///
+/// Enricher failures and conflicting fields are reported as warnings instead of errors so a
+/// broken hook never blocks reading a thread.
+pub fn enrich_frontmatter(
+    markdown: &str,
+    enrichers: &[Box<dyn FrontmatterEnricher>],
+) -> Result<(String, Vec<String>)> {
+    if enrichers.is_empty() {
+        return Ok((markdown.to_string(), Vec::new()));
+    }
+    let Some((yaml, rest)) = split_frontmatter(markdown) else {
+        return Ok((markdown.to_string(), Vec::new()));
+    };
+    let Some(Value::Object(mut head)) = parse_frontmatter(markdown)? else {
+        return Ok((markdown.to_string(), Vec::new()));
+    };
+
+    let mut added = Map::new();
+    let mut warnings = Vec::new();
+    for enricher in enrichers {
+        let fields = match enricher.enrich(&Value::Object(head.clone())) {
+            Ok(fields) => fields,
+            Err(err) => {
+                warnings.push(format!("frontmatter enricher failed: {err}"));
+                continue;
+            }
+        };
+        for (key, value) in fields {
+            if head.contains_key(&key) {
+                warnings.push(format!(
+                    "frontmatter enricher field ignored because it already exists: {key}"
+                ));
+                continue;
+            }
+            head.insert(key.clone(), value.clone());
+            added.insert(key, value);
+        }
+    }
+
+    if added.is_empty() {
+        return Ok((markdown.to_string(), warnings));
+    }
+
+    let fields =
+        serde_yaml::to_string(&added).map_err(|err| XurlError::Serialization(err.to_string()))?;
+    Ok((format!("---\n{yaml}{fields}---\n{rest}"), warnings))
+}
+
+/// Keeps only the top-level frontmatter keys named in `fields`, with their nested lines, in the
+/// order the frontmatter lists them. Names missing from the frontmatter are ignored, and an
+/// empty `fields` keeps everything.
+pub fn select_frontmatter_fields(markdown: &str, fields: &[String]) -> String {
+    if fields.is_empty() {
+        return markdown.to_string();
+    }
+    let Some((yaml, rest)) = split_frontmatter(markdown) else {
+        return markdown.to_string();
+    };
+    let mut selected = String::new();
+    let mut keep = false;
+    for line in yaml.lines() {
+        // Nested lines are indented; block sequences may start at column 0 under their key.
+        if !line.starts_with([' ', '-']) {
+            keep = line
+                .split_once(':')
+                .is_some_and(|(key, _)| fields.iter().any(|field| field == key));
+        }
+        if keep {
+            selected.push_str(line);
+            selected.push('\n');
+        }
+    }
+    format!("---\n{selected}---\n{rest}")
+}
+
+fn split_frontmatter(markdown: &str) -> Option<(&str, &str)> {
+    let rest = markdown.strip_prefix("---\n")?;
+    if let Some(body) = rest.strip_prefix("---\n") {
+        return Some(("", body));
+    }
+    let end = rest.find("\n---\n")?;
+    Some((&rest[..=end], &rest[end + 5..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Map, Value, json};
+
+    use super::{
+        FrontmatterEnricher, enrich_frontmatter, parse_frontmatter, select_frontmatter_fields,
+    };
+    use crate::error::{Result, XurlError};
+
+    const HEAD: &str = "---\nuri: 'agents://codex/abc'\nprovider: 'codex'\nbranch: 'feat/JIRA-42-fix'\nsubagents:\n  - agent_id: 'a'\n    status: 'done'\n---\n\n# Thread\n";
+
+    fn fields(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => unreachable!("fields must be an object"),
+        }
+    }
+
+    #[test]
+    fn parse_frontmatter_reads_nested_head() {
+        let head = parse_frontmatter(HEAD)
+            .expect("parse")
+            .expect("frontmatter");
+        assert_eq!(head["provider"], "codex");
+        assert_eq!(head["subagents"][0]["status"], "done");
+        assert!(parse_frontmatter("# no head\n").expect("parse").is_none());
+    }
+
+    #[test]
+    fn enrich_frontmatter_appends_fields_before_closing_fence() {
+        let jira = |head: &Value| -> Result<Map<String, Value>> {
+            let branch = head["branch"].as_str().unwrap_or_default();
+            let ticket = branch
+                .split(['/', '-'])
+                .collect::<Vec<_>>()
+                .windows(2)
+                .find(|pair| pair[0] == "JIRA")
+                .map(|pair| format!("JIRA-{}", pair[1]));
+            Ok(fields(json!({ "jira": ticket })))
+        };
+        let enrichers: Vec<Box<dyn FrontmatterEnricher>> = vec![Box::new(jira)];
+
+        let (output, warnings) = enrich_frontmatter(HEAD, &enrichers).expect("enrich");
+        assert!(warnings.is_empty());
+        assert!(output.contains("    status: 'done'\njira: JIRA-42\n---\n\n# Thread\n"));
+        let head = parse_frontmatter(&output)
+            .expect("parse")
+            .expect("frontmatter");
+        assert_eq!(head["jira"], "JIRA-42");
+    }
+
+    #[test]
+    fn enrich_frontmatter_keeps_existing_fields_and_reports_failures() {
+        let overwrite = |_: &Value| -> Result<Map<String, Value>> {
+            Ok(fields(json!({ "provider": "other", "team": "infra" })))
+        };
+        let failing = |_: &Value| -> Result<Map<String, Value>> {
+            Err(XurlError::Serialization("boom".to_string()))
+        };
+        let enrichers: Vec<Box<dyn FrontmatterEnricher>> =
+            vec![Box::new(overwrite), Box::new(failing)];
+
+        let (output, warnings) = enrich_frontmatter(HEAD, &enrichers).expect("enrich");
+        assert!(output.contains("provider: 'codex'\n"));
+        assert!(output.contains("team: infra\n"));
+        assert_eq!(
+            warnings,
+            vec![
+                "frontmatter enricher field ignored because it already exists: provider"
+                    .to_string(),
+                "frontmatter enricher failed: serialization error: boom".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn select_frontmatter_fields_keeps_named_keys_with_nested_lines() {
+        let fields = ["subagents", "uri", "missing"].map(str::to_string);
+        assert_eq!(
+            select_frontmatter_fields(HEAD, &fields),
+            "---\nuri: 'agents://codex/abc'\nsubagents:\n  - agent_id: 'a'\n    status: 'done'\n---\n\n# Thread\n"
+        );
+        assert_eq!(
+            select_frontmatter_fields(
+                "---\nteam:\n- infra\nuri: 'x'\n---\n",
+                &["team".to_string()]
+            ),
+            "---\nteam:\n- infra\n---\n"
+        );
+        assert_eq!(select_frontmatter_fields(HEAD, &[]), HEAD);
+    }
+}
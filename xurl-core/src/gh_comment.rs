@@ -0,0 +1,205 @@
+use crate::error::Result;
+use crate::model::{RenderOptions, ResolvedThread};
+use crate::scrub::Scrubber;
+use crate::service::render_thread_markdown;
+use crate::uri::AgentsUri;
+
+/// Characters GitHub accepts in one issue or pull request comment.
+pub const GH_COMMENT_MAX_CHARS: usize = 65_536;
+
+/// Tool outputs larger than this many bytes are replaced with their size and sha256 in
+/// comment exports.
+pub const GH_COMMENT_ELIDE_BYTES: u64 = 2 * 1024;
+
+/// Characters of every part kept free for the header and the `<details>` wrapper.
+const WRAPPER_RESERVE: usize = 512;
+
+/// Renders the thread at `uri` as one or more GitHub comment bodies, each under `max_chars`
+/// characters, with the timeline folded into a `<details>` block. With a `scrubber`, the
+/// body is scrubbed before it is split.
+pub fn render_gh_comment(
+    uri: &AgentsUri,
+    resolved: &ResolvedThread,
+    max_chars: usize,
+    scrubber: Option<&mut Scrubber>,
+) -> Result<Vec<String>> {
+    let options = RenderOptions {
+        elide_bytes: Some(GH_COMMENT_ELIDE_BYTES),
+        ..RenderOptions::default()
+    };
+    let mut body = render_thread_markdown(uri, resolved, &options)?;
+    if let Some(scrubber) = scrubber {
+        body = scrubber.scrub(&body);
+    }
+    Ok(split_gh_comment(uri, &body, max_chars))
+}
+
+/// Wraps `body` into comment parts. Parts break between timeline entries where possible;
+/// an entry too large for one part is split between lines, closing and reopening any code
+/// fence it was in.
+pub fn split_gh_comment(uri: &AgentsUri, body: &str, max_chars: usize) -> Vec<String> {
+    let budget = max_chars
+        .saturating_sub(WRAPPER_RESERVE)
+        .max(WRAPPER_RESERVE);
+    let entries = body.lines().filter(|line| is_entry_heading(line)).count();
+    let chunks = pack_chunks(body, budget);
+    let total = chunks.len();
+    let uri = uri.as_agents_string();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let (marker, summary) = if total == 1 {
+                (String::new(), format!("{entries} timeline entries"))
+            } else {
+                (
+                    format!(" (part {} of {total})", idx + 1),
+                    format!("{entries} timeline entries, part {} of {total}", idx + 1),
+                )
+            };
+            format!(
+                "<!-- xurl: {uri} part {}/{total} -->\n**Agent thread** `{uri}`{marker}\n\n<details>\n<summary>{summary}</summary>\n\n{}\n\n</details>\n",
+                idx + 1,
+                chunk.trim_end(),
+            )
+        })
+        .collect()
+}
+
+fn is_entry_heading(line: &str) -> bool {
+    line.strip_prefix("## ")
+        .and_then(|rest| rest.split_once(". "))
+        .is_some_and(|(number, _)| number.parse::<usize>().is_ok())
+}
+
+fn pack_chunks(body: &str, budget: usize) -> Vec<String> {
+    let mut sections = Vec::<String>::new();
+    for line in body.lines() {
+        if line.starts_with("## ") || sections.is_empty() {
+            sections.push(String::new());
+        }
+        let section = sections.last_mut().expect("a section was pushed");
+        section.push_str(line);
+        section.push('\n');
+    }
+
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for section in sections {
+        let size = section.chars().count();
+        if size <= budget {
+            if current.chars().count() + size > budget {
+                chunks.push(std::mem::take(&mut current));
+            }
+            current.push_str(&section);
+            continue;
+        }
+        if !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+        }
+        split_section(&section, budget, &mut chunks, &mut current);
+    }
+    if !current.is_empty() || chunks.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Splits an oversized section between lines into `chunks`, leaving the tail in `current`.
+fn split_section(section: &str, budget: usize, chunks: &mut Vec<String>, current: &mut String) {
+    const FENCE_CLOSE: &str = "```\n";
+    let mut fence = None::<String>;
+    let mut size = 0usize;
+    for line in section.lines() {
+        let pieces = hard_wrap(line, budget / 2);
+        for piece in pieces {
+            let cost = piece.chars().count() + 1;
+            let reserve = if fence.is_some() {
+                FENCE_CLOSE.len()
+            } else {
+                0
+            };
+            if size > 0 && size + cost + reserve > budget {
+                if fence.is_some() {
+                    current.push_str(FENCE_CLOSE);
+                }
+                chunks.push(std::mem::take(current));
+                size = 0;
+                if let Some(opener) = &fence {
+                    current.push_str(opener);
+                    current.push('\n');
+                    size = opener.chars().count() + 1;
+                }
+            }
+            current.push_str(&piece);
+            current.push('\n');
+            size += cost;
+        }
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            fence = match fence {
+                Some(_) => None,
+                None => Some(trimmed.to_string()),
+            };
+        }
+    }
+}
+
+/// Breaks a line longer than `width` characters into pieces of at most `width` characters.
+fn hard_wrap(line: &str, width: usize) -> Vec<String> {
+    let chars = line.chars().collect::<Vec<_>>();
+    if chars.len() <= width {
+        return vec![line.to_string()];
+    }
+    chars
+        .chunks(width)
+        .map(|piece| piece.iter().collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GH_COMMENT_MAX_CHARS, split_gh_comment};
+    use crate::uri::AgentsUri;
+
+    #[test]
+    fn split_keeps_small_threads_in_one_collapsed_comment() {
+        let uri =
+            AgentsUri::parse("agents://codex/019c871c-b1f9-7f60-9c4f-87ed09f13592").expect("parse");
+        let parts = split_gh_comment(
+            &uri,
+            "# Thread\n\n## Timeline\n\n## 1. User\n\nhi\n\n## 2. Assistant\n\nhello\n",
+            GH_COMMENT_MAX_CHARS,
+        );
+        assert_eq!(parts.len(), 1);
+        assert!(parts[0].contains("<summary>2 timeline entries</summary>"));
+        assert!(parts[0].contains("## 2. Assistant\n\nhello\n\n</details>\n"));
+        assert!(!parts[0].contains("part 1 of"));
+    }
+
+    #[test]
+    fn split_breaks_between_entries_and_reopens_code_fences() {
+        let uri = AgentsUri::parse("agents://claude/2823d1df-720a-4c31-ac55-ae8ba726721f")
+            .expect("parse");
+        let output = (0..400)
+            .map(|idx| format!("line {idx:04} of tool output"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let body = format!(
+            "# Thread\n\n## Timeline\n\n## 1. User\n\nrun it\n\n## 2. Tool: Bash\n\n```text\n{output}\n```\n\n## 3. Assistant\n\ndone\n"
+        );
+
+        let parts = split_gh_comment(&uri, &body, 4_000);
+        assert!(parts.len() > 2, "{}", parts.len());
+        for (idx, part) in parts.iter().enumerate() {
+            assert!(part.chars().count() <= 4_000);
+            assert!(part.contains(&format!("part {} of {}", idx + 1, parts.len())));
+            assert_eq!(part.matches("```").count() % 2, 0, "{part}");
+        }
+        assert!(parts[1].contains("```text\nline "));
+        assert!(parts.last().expect("last").contains("## 3. Assistant"));
+        let joined = parts.join("");
+        assert!(joined.contains("line 0000 of tool output"));
+        assert!(joined.contains("line 0399 of tool output"));
+    }
+}
@@ -0,0 +1,235 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::error::{Result, XurlError};
+
+static COMMIT_TOKEN_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b[0-9a-f]{7,40}\b").expect("valid regex"));
+
+/// Resolves `commit_ish` to a full commit SHA in the repository containing `repo`.
+pub fn resolve_commit(repo: &Path, commit_ish: &str) -> Result<String> {
+    let rev = format!("{commit_ish}^{{commit}}");
+    let sha = run_git(
+        [
+            OsStr::new("rev-parse"),
+            OsStr::new("--verify"),
+            OsStr::new(&rev),
+        ],
+        repo,
+    )?;
+    Ok(sha.trim().to_string())
+}
+
+/// Whether `text` contains `sha` or an abbreviation of it (at least seven hex digits).
+pub(crate) fn mentions_commit(text: &str, sha: &str) -> bool {
+    COMMIT_TOKEN_RE
+        .find_iter(text)
+        .any(|token| sha.starts_with(token.as_str()))
+}
+
+/// The git repository a directory belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RepoInfo {
+    /// The work tree root.
+    pub(crate) root: PathBuf,
+    /// The URL of the `origin` remote.
+    pub(crate) origin: Option<String>,
+    /// The checked out branch, `None` on a detached HEAD.
+    pub(crate) branch: Option<String>,
+}
+
+/// Finds the repository containing `dir` by reading its `.git` metadata directly, so thread
+/// heads do not spawn `git`. Linked worktrees, whose `.git` is a `gitdir:` file, read the
+/// remote from the main repository's config.
+pub(crate) fn repo_info(dir: &Path) -> Option<RepoInfo> {
+    let (root, git_dir) = dir.ancestors().find_map(|ancestor| {
+        let dot_git = ancestor.join(".git");
+        if dot_git.is_dir() {
+            return Some((ancestor.to_path_buf(), dot_git));
+        }
+        let linked = std::fs::read_to_string(&dot_git).ok()?;
+        let git_dir = Path::new(linked.strip_prefix("gitdir:")?.trim());
+        Some((ancestor.to_path_buf(), ancestor.join(git_dir)))
+    })?;
+    let common_dir = std::fs::read_to_string(git_dir.join("commondir"))
+        .map_or_else(|_| git_dir.clone(), |common| git_dir.join(common.trim()));
+    let branch = std::fs::read_to_string(git_dir.join("HEAD"))
+        .ok()
+        .and_then(|head| {
+            head.trim()
+                .strip_prefix("ref: refs/heads/")
+                .map(ToString::to_string)
+        });
+    let origin = std::fs::read_to_string(common_dir.join("config"))
+        .ok()
+        .and_then(|config| origin_url(&config));
+    Some(RepoInfo {
+        root,
+        origin,
+        branch,
+    })
+}
+
+/// The `url` of the `[remote "origin"]` section of a git config file.
+fn origin_url(config: &str) -> Option<String> {
+    let mut in_origin = false;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_origin = line == r#"[remote "origin"]"#;
+            continue;
+        }
+        if in_origin
+            && let Some((key, value)) = line.split_once('=')
+            && key.trim() == "url"
+        {
+            return Some(value.trim().to_string());
+        }
+    }
+    None
+}
+
+/// Whether the remote URL `origin` points at `repo`: another remote URL, or the `owner/name`
+/// path at the end of it. Trailing `.git` and letter case are ignored, so SSH and HTTPS remotes
+/// of one repository both match.
+pub(crate) fn origin_matches(origin: &str, repo: &str) -> bool {
+    let normalize = |url: &str| {
+        let url = url.trim().trim_end_matches('/').to_ascii_lowercase();
+        url.strip_suffix(".git")
+            .map_or_else(|| url.clone(), ToString::to_string)
+    };
+    let origin = normalize(origin);
+    let repo = normalize(repo);
+    origin == repo
+        || origin
+            .strip_suffix(repo.as_str())
+            .is_some_and(|prefix| prefix.ends_with(['/', ':']))
+}
+
+pub(crate) fn run_git<const N: usize>(args: [&OsStr; N], cwd: &Path) -> Result<String> {
+    let output = Command::new("git")
+        .current_dir(cwd)
+        .args(args)
+        .output()
+        .map_err(|source| {
+            if source.kind() == std::io::ErrorKind::NotFound {
+                XurlError::CommandNotFound {
+                    command: "git".to_string(),
+                }
+            } else {
+                XurlError::Io {
+                    path: PathBuf::from("git"),
+                    source,
+                }
+            }
+        })?;
+
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+    }
+
+    let command = format!(
+        "git {}",
+        args.iter()
+            .map(|item| item.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(XurlError::GitCommandFailed {
+        command,
+        code: output.status.code(),
+        stderr,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::{mentions_commit, origin_matches, repo_info};
+
+    #[test]
+    fn mentions_commit_matches_abbreviated_sha() {
+        let sha = "1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b";
+        assert!(mentions_commit("[main 1a2b3c4] fix parser", sha));
+        assert!(mentions_commit(&format!("commit {sha}\nAuthor: a"), sha));
+        assert!(!mentions_commit("[main 1a2b3c] too short", sha));
+        assert!(!mentions_commit("[main 1a2b3c5] other", sha));
+        assert!(!mentions_commit("x1a2b3c4d", sha));
+    }
+
+    #[test]
+    fn origin_matches_owner_name_across_remote_forms() {
+        assert!(origin_matches(
+            "git@github.com:Xuanwo/turl.git",
+            "xuanwo/turl"
+        ));
+        assert!(origin_matches(
+            "https://github.com/Xuanwo/turl",
+            "Xuanwo/turl"
+        ));
+        assert!(origin_matches(
+            "https://github.com/Xuanwo/turl.git",
+            "https://github.com/xuanwo/turl"
+        ));
+        assert!(!origin_matches(
+            "https://github.com/Xuanwo/turl2",
+            "Xuanwo/turl"
+        ));
+        assert!(!origin_matches(
+            "https://github.com/other/Xuanwo/turl-x",
+            "Xuanwo/turl"
+        ));
+        assert!(!origin_matches(
+            "https://github.com/someXuanwo/turl",
+            "Xuanwo/turl"
+        ));
+    }
+
+    #[test]
+    fn repo_info_reads_branch_and_origin_from_git_metadata() {
+        let temp = tempdir().expect("tempdir");
+        let repo = temp.path().join("repo");
+        fs::create_dir_all(repo.join(".git/worktrees/feature")).expect("mkdir");
+        fs::create_dir_all(repo.join("src")).expect("mkdir");
+        fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main\n").expect("write");
+        fs::write(
+            repo.join(".git/config"),
+            "[core]\n\tbare = false\n[remote \"upstream\"]\n\turl = https://example.com/up.git\n[remote \"origin\"]\n\turl = git@example.com:me/repo.git\n",
+        )
+        .expect("write");
+
+        let info = repo_info(&repo.join("src")).expect("repo");
+        assert_eq!(info.root, repo);
+        assert_eq!(info.branch.as_deref(), Some("main"));
+        assert_eq!(info.origin.as_deref(), Some("git@example.com:me/repo.git"));
+
+        let worktree = temp.path().join("feature");
+        fs::create_dir_all(&worktree).expect("mkdir");
+        let linked = repo.join(".git/worktrees/feature");
+        fs::write(
+            worktree.join(".git"),
+            format!("gitdir: {}\n", linked.display()),
+        )
+        .expect("write");
+        fs::write(linked.join("commondir"), "../..\n").expect("write");
+        fs::write(
+            linked.join("HEAD"),
+            "1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b\n",
+        )
+        .expect("write");
+
+        let info = repo_info(&worktree).expect("worktree");
+        assert_eq!(info.root, worktree);
+        assert_eq!(info.branch, None);
+        assert_eq!(info.origin.as_deref(), Some("git@example.com:me/repo.git"));
+
+        assert_eq!(repo_info(temp.path()), None);
+    }
+}
@@ -1,6 +1,7 @@
 use std::io::BufRead;
 use std::path::Path;
 
+use serde::de::IgnoredAny;
 use serde_json::Value;
 
 use crate::error::{Result, XurlError};
@@ -20,6 +21,34 @@ pub fn parse_json_line(path: &Path, line_no: usize, line: &str) -> Result<Option
     Ok(Some(value))
 }
 
+/// Parses a thread file line, treating lines that are not valid JSON like blank ones.
+///
+/// Thread readers skip corrupt lines instead of failing the whole thread, and count them while
+/// they stream the thread so the skip shows up as a warning.
+pub fn parse_thread_line(line: &str) -> Option<Value> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    serde_json::from_str(trimmed).ok()
+}
+
+/// Counts the non-blank lines that are not valid JSON.
+pub fn count_corrupt_lines<R: BufRead>(reader: R) -> usize {
+    reader
+        .lines()
+        .map_while(std::result::Result::ok)
+        .filter(|line| {
+            let trimmed = line.trim();
+            !trimmed.is_empty() && serde_json::from_str::<IgnoredAny>(trimmed).is_err()
+        })
+        .count()
+}
+
+pub fn corrupt_lines_warning(count: usize) -> String {
+    format!("corrupt_lines: {count} (skipped records that are not valid JSON)")
+}
+
 pub fn parse_jsonl_reader<R, F>(path: &Path, mut reader: R, mut on_value: F) -> Result<()>
 where
     R: BufRead,
@@ -48,3 +77,27 @@ where
 
     Ok(())
 }
+
+/// Like [`parse_jsonl_reader`], but skips lines that are not valid JSON.
+pub fn parse_thread_jsonl_reader<R, F>(path: &Path, mut reader: R, mut on_value: F) -> Result<()>
+where
+    R: BufRead,
+    F: FnMut(Value) -> Result<()>,
+{
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let bytes = reader
+            .read_line(&mut line)
+            .map_err(|source| XurlError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        if bytes == 0 {
+            return Ok(());
+        }
+        if let Some(value) = parse_thread_line(&line) {
+            on_value(value)?;
+        }
+    }
+}
@@ -1,23 +1,98 @@
+pub mod attachments;
+pub mod cache;
+pub mod config;
+pub mod dataset;
 pub mod error;
+pub mod export_db;
+pub mod findings;
+pub mod format;
+pub mod frontmatter;
+pub mod gh_comment;
+pub mod git;
 pub mod jsonl;
+pub mod links;
+pub mod lint;
+pub(crate) mod metadata;
 pub mod model;
+pub mod parallel;
+pub mod process;
 pub mod provider;
+pub mod query;
+pub mod rank;
 pub mod render;
+pub mod scan;
+pub mod scrub;
 pub mod service;
+pub mod snapshot;
+pub mod status;
+pub mod summarize;
+pub mod template;
+pub mod timestamp;
+pub mod tools;
 pub mod uri;
+pub mod usage;
+pub mod vault;
+pub mod watch;
 
+pub use cache::gc_caches;
+pub use config::{DEFAULT_ELIDE_BYTES, EnricherConfig, HeadConfig, RenderConfig, XurlConfig};
+pub use dataset::{DATASET_COLUMNS, dataset_rows, write_dataset_csv, write_dataset_parquet};
 pub use error::{Result, XurlError};
+pub use export_db::export_threads_db;
+pub use frontmatter::{
+    CommandEnricher, FrontmatterEnricher, enrich_frontmatter, parse_frontmatter,
+    select_frontmatter_fields,
+};
+pub use gh_comment::{GH_COMMENT_MAX_CHARS, render_gh_comment};
 pub use model::{
-    MessageRole, PiEntryListView, ProviderKind, ResolutionMeta, ResolvedSkill, ResolvedThread,
-    SkillResolutionMeta, SkillsSourceKind, SubagentDetailView, SubagentListView, SubagentView,
-    ThreadMessage, ThreadQuery, ThreadQueryItem, ThreadQueryResult, WriteOptions, WriteRequest,
-    WriteResult,
+    ApprovalMode, CacheGcOptions, CacheGcReport, CommitBlame, CommitBlameItem, DoctorReport,
+    ExportDbReport, ExportFormat, FileChange, FileChangeKind, Finding, FindingSeverity,
+    FindingsFormat, FsckIssue, FsckIssueKind, FsckReport, HealthCheck, HealthStatus, LineRange,
+    LintIssue, LintIssueKind, LintReport, McpServerUsage, MessageRole, MessageRow, PiEntryListView,
+    ProviderHealth, ProviderKind, QuerySort, RawSlice, RecentThreads, RenderOptions,
+    ResolutionMeta, ResolvedBin, ResolvedSkill, ResolvedThread, RunningThread, RunningThreads,
+    ScrubKind, SkillResolutionMeta, SkillsSourceKind, SubagentDescendant, SubagentDetailView,
+    SubagentListOptions, SubagentListView, SubagentLongest, SubagentSort, SubagentTotals,
+    SubagentView, ThreadComparison, ThreadEvent, ThreadEventKind, ThreadExport, ThreadFindings,
+    ThreadLinks, ThreadMessage, ThreadQuery, ThreadQueryItem, ThreadQueryResult,
+    ThreadRelationship, ThreadStatus, TimelineFilter, TokenUsage, ToolCall, VaultSyncReport,
+    WriteCommand, WriteOptions, WriteRequest, WriteResult, WriteSummaryFormat,
 };
-pub use provider::{ProviderRoots, WriteEventSink};
+pub use parallel::run_ordered;
+pub use provider::{ProviderCli, ProviderRoots, WriteEventSink};
+pub use scan::ScanOptions;
+pub use scrub::Scrubber;
 pub use service::{
-    query_threads, render_skill_head_markdown, render_skill_markdown,
-    render_subagent_view_markdown, render_thread_head_markdown, render_thread_markdown,
-    render_thread_query_head_markdown, render_thread_query_markdown, resolve_skill,
-    resolve_subagent_view, resolve_thread, write_thread,
+    MulticastWrite, blame_commit, compare_thread, doctor, expand_latest_alias,
+    expand_session_prefix, export_thread, fsck_provider, lint_thread, list_session_ids,
+    locate_thread, multicast_write, query_threads, recent_threads, render_doctor_markdown,
+    render_file_head_markdown, render_findings_json, render_findings_sarif, render_fsck_markdown,
+    render_lint_markdown, render_resolution_markdown, render_running_threads_json,
+    render_running_threads_markdown, render_skill_head_markdown, render_skill_markdown,
+    render_subagent_view_markdown, render_thread_branches_markdown,
+    render_thread_comparison_markdown, render_thread_diff_markdown, render_thread_head_markdown,
+    render_thread_history_markdown, render_thread_links_json, render_thread_links_markdown,
+    render_thread_markdown, render_thread_metadata_head_markdown,
+    render_thread_query_head_markdown, render_thread_query_markdown, resolve_file_thread,
+    resolve_skill, resolve_subagent_view, resolve_thread, resolve_thread_chain, running_threads,
+    subagent_view_settled, subagent_view_to_raw_json, thread_findings, thread_links,
+    thread_query_to_raw_json, thread_user_prompts, workspace_threads, write_result_summary_json,
+    write_subagent_raw, write_thread, write_thread_markdown, write_thread_raw,
+};
+pub use snapshot::{
+    ResolvedSnapshot, SnapshotManifest, render_snapshot_head_markdown, resolve_snapshot,
+    snapshot_thread,
+};
+pub use status::thread_status;
+pub use summarize::{DEFAULT_SUMMARY_TEMPLATE, SUMMARY_MAX_THREAD_BYTES, summary_prompt};
+pub use template::{
+    INLINE_THREAD_MAX_BYTES, expand_prompt_template, inline_thread, load_template, templates_dir,
+};
+pub use timestamp::TimeZone;
+pub use tools::{extract_tool_calls, files_changed, mcp_server_usage};
+pub use uri::{AgentsUri, FileUri, SkillsUri, SnapshotUri};
+pub use vault::{sync_vault, vault_note_path};
+pub use watch::{
+    ThreadEventSink, ThreadWatcher, WatchOptions, render_thread_event_json,
+    render_thread_webhook_json, watch_threads,
 };
-pub use uri::{AgentsUri, SkillsUri};
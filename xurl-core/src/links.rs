@@ -0,0 +1,118 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::model::{ThreadMessage, ToolCall};
+use crate::tools;
+
+static URL_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"https?://[^\s<>"'`)\]}]+"#).expect("valid regex"));
+static BACKTICK_PATH_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"`((?:~|\.{1,2})?/?[\w.\-]+(?:/[\w.\-]+)+/?)`").expect("valid regex"));
+// `[main 1a2b3c4] message` from `git commit`, and `commit <sha>` from `git log` / `git show`.
+static COMMIT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\[[^\]\s]+(?: \(root-commit\))? ([0-9a-f]{7,40})\]|\bcommit ([0-9a-f]{7,40})\b")
+        .expect("valid regex")
+});
+
+/// File paths, URLs, and commits referenced by a thread, each in first-seen order.
+#[derive(Debug, Default)]
+pub(crate) struct Links {
+    pub(crate) files: Vec<String>,
+    pub(crate) urls: Vec<String>,
+    pub(crate) commits: Vec<String>,
+}
+
+impl Links {
+    fn push_text(&mut self, text: &str) {
+        for url in URL_RE.find_iter(text) {
+            let url = url
+                .as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?']);
+            push_unique(&mut self.urls, url);
+        }
+        for captures in COMMIT_RE.captures_iter(text) {
+            if let Some(sha) = captures.get(1).or_else(|| captures.get(2)) {
+                push_unique(&mut self.commits, sha.as_str());
+            }
+        }
+    }
+}
+
+/// Collects links from tool inputs and outputs first, then from message text.
+///
+/// Files come from tool arguments and patch headers; message text only contributes
+/// backtick-quoted paths, since bare words are too ambiguous to treat as paths.
+pub(crate) fn collect_links(messages: &[ThreadMessage], calls: &[ToolCall]) -> Links {
+    let mut links = Links::default();
+    for call in calls {
+        for path in tools::input_paths(call) {
+            push_unique(&mut links.files, &path);
+        }
+        tools::for_each_string(&call.input, &mut |text| links.push_text(text));
+        if let Some(output) = &call.output {
+            links.push_text(output);
+        }
+    }
+    for message in messages {
+        for captures in BACKTICK_PATH_RE.captures_iter(&message.text) {
+            push_unique(&mut links.files, &captures[1]);
+        }
+        links.push_text(&message.text);
+    }
+    links
+}
+
+fn push_unique(values: &mut Vec<String>, value: &str) {
+    if !value.is_empty() && !values.iter().any(|existing| existing == value) {
+        values.push(value.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::collect_links;
+    use crate::model::{MessageRole, ThreadMessage, ToolCall};
+
+    #[test]
+    fn collect_links_reads_tool_calls_and_messages() {
+        let calls = vec![
+            ToolCall {
+                id: Some("call_1".to_string()),
+                name: "apply_patch".to_string(),
+                input: json!(
+                    "*** Begin Patch\n*** Update File: src/lib.rs\n@@\n*** Add File: docs/new.md\n*** End Patch"
+                ),
+                output: Some("Success".to_string()),
+            },
+            ToolCall {
+                id: Some("call_2".to_string()),
+                name: "Read".to_string(),
+                input: json!({"file_path": "/repo/Cargo.toml"}),
+                output: None,
+            },
+            ToolCall {
+                id: Some("call_3".to_string()),
+                name: "shell".to_string(),
+                input: json!({"command": ["git", "commit", "-m", "fix"]}),
+                output: Some("[main 1a2b3c4] fix\n 1 file changed".to_string()),
+            },
+        ];
+        let messages = vec![ThreadMessage {
+            role: MessageRole::Assistant,
+            text:
+                "Updated `src/lib.rs` and `README.md`, see https://github.com/Xuanwo/xurl/pull/1."
+                    .to_string(),
+            timestamp: None,
+        }];
+
+        let links = collect_links(&messages, &calls);
+        assert_eq!(
+            links.files,
+            vec!["src/lib.rs", "docs/new.md", "/repo/Cargo.toml"]
+        );
+        assert_eq!(links.urls, vec!["https://github.com/Xuanwo/xurl/pull/1"]);
+        assert_eq!(links.commits, vec!["1a2b3c4"]);
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+use crate::model::{LintIssue, LintIssueKind, ProviderKind};
+
+/// Checks a thread payload against the shape its provider writes, without stopping at the
+/// first problem.
+///
+/// Line-based rollouts report issues with 1-based line numbers. Amp and Gemini threads are a
+/// single JSON document, so only a parse error carries a line; other issues name the message
+/// index instead. Returns the number of records checked with the issues found.
+pub fn lint_thread_payload(provider: ProviderKind, raw: &str) -> (usize, Vec<LintIssue>) {
+    match provider {
+        ProviderKind::Amp | ProviderKind::Gemini => lint_json_document(provider, raw),
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Pi | ProviderKind::Opencode => {
+            lint_jsonl(provider, raw)
+        }
+    }
+}
+
+fn lint_jsonl(provider: ProviderKind, raw: &str) -> (usize, Vec<LintIssue>) {
+    let mut records = 0;
+    let mut issues = Vec::new();
+    let mut call_ids = HashSet::new();
+    let mut unknown_types = HashSet::new();
+    for (idx, line) in raw.lines().enumerate() {
+        let line_no = idx + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records += 1;
+        let value = match serde_json::from_str::<Value>(line.trim()) {
+            Ok(value) => value,
+            Err(err) => {
+                issues.push(issue(
+                    LintIssueKind::MalformedJson,
+                    Some(line_no),
+                    err.to_string(),
+                ));
+                continue;
+            }
+        };
+
+        if let Some(record_type) = value.get("type").and_then(Value::as_str)
+            && known_record_types(provider).is_some_and(|known| !known.contains(&record_type))
+            && unknown_types.insert(record_type.to_string())
+        {
+            issues.push(issue(
+                LintIssueKind::UnknownRecordType,
+                Some(line_no),
+                format!("`{record_type}` records are not rendered"),
+            ));
+        }
+
+        if requires_timestamp(provider, &value) && value.get("timestamp").is_none() {
+            let record_type = value
+                .get("type")
+                .and_then(Value::as_str)
+                .unwrap_or("record");
+            issues.push(issue(
+                LintIssueKind::MissingTimestamp,
+                Some(line_no),
+                format!("`{record_type}` record has no `timestamp`"),
+            ));
+        }
+
+        let (calls, outputs) = tool_ids(provider, &value);
+        call_ids.extend(calls);
+        for id in outputs {
+            if !call_ids.contains(&id) {
+                issues.push(issue(
+                    LintIssueKind::OrphanToolOutput,
+                    Some(line_no),
+                    format!("tool output for `{id}` has no earlier tool call"),
+                ));
+            }
+        }
+    }
+    (records, issues)
+}
+
+fn lint_json_document(provider: ProviderKind, raw: &str) -> (usize, Vec<LintIssue>) {
+    let value = match serde_json::from_str::<Value>(raw) {
+        Ok(value) => value,
+        Err(err) => {
+            return (
+                0,
+                vec![issue(
+                    LintIssueKind::MalformedJson,
+                    Some(err.line()),
+                    err.to_string(),
+                )],
+            );
+        }
+    };
+
+    let messages = value
+        .get("messages")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let mut issues = Vec::new();
+    let mut call_ids = HashSet::new();
+    for (idx, message) in messages.iter().enumerate() {
+        if provider == ProviderKind::Gemini && message.get("timestamp").is_none() {
+            issues.push(issue(
+                LintIssueKind::MissingTimestamp,
+                None,
+                format!("messages[{idx}] has no `timestamp`"),
+            ));
+        }
+        if provider != ProviderKind::Amp {
+            continue;
+        }
+        let items = message.get("content").and_then(Value::as_array);
+        for item in items.into_iter().flatten() {
+            match item.get("type").and_then(Value::as_str) {
+                Some("tool_use") => {
+                    if let Some(id) = item.get("id").and_then(Value::as_str) {
+                        call_ids.insert(id.to_string());
+                    }
+                }
+                Some("tool_result") => {
+                    if let Some(id) = item.get("toolUseID").and_then(Value::as_str)
+                        && !call_ids.contains(id)
+                    {
+                        issues.push(issue(
+                            LintIssueKind::OrphanToolOutput,
+                            None,
+                            format!(
+                                "messages[{idx}] has a tool result for `{id}` with no earlier tool call"
+                            ),
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    (messages.len(), issues)
+}
+
+/// Top-level record `type`s the readers know, for providers with a fixed set. Only the first
+/// record of an unknown type is reported.
+fn known_record_types(provider: ProviderKind) -> Option<&'static [&'static str]> {
+    match provider {
+        ProviderKind::Codex => Some(&[
+            "session_meta",
+            "response_item",
+            "event_msg",
+            "turn_context",
+            "compacted",
+        ]),
+        ProviderKind::Claude => Some(&[
+            "user",
+            "assistant",
+            "system",
+            "command",
+            "hook",
+            "summary",
+            "file-history-snapshot",
+            "queue-operation",
+            "progress",
+        ]),
+        ProviderKind::Pi => Some(&[
+            "session",
+            "message",
+            "compaction",
+            "branch_summary",
+            "model_change",
+            "thinking_level_change",
+            "custom",
+            "custom_message",
+            "label",
+        ]),
+        ProviderKind::Opencode | ProviderKind::Amp | ProviderKind::Gemini => None,
+    }
+}
+
+/// Whether the provider stamps this kind of record with a top-level `timestamp`.
+fn requires_timestamp(provider: ProviderKind, value: &Value) -> bool {
+    match provider {
+        ProviderKind::Codex | ProviderKind::Pi => true,
+        ProviderKind::Claude => matches!(
+            value.get("type").and_then(Value::as_str),
+            Some("user" | "assistant" | "system")
+        ),
+        ProviderKind::Opencode | ProviderKind::Amp | ProviderKind::Gemini => false,
+    }
+}
+
+/// Returns the tool call ids a record introduces and the call ids its tool outputs refer to.
+fn tool_ids(provider: ProviderKind, value: &Value) -> (Vec<String>, Vec<String>) {
+    let mut calls = Vec::new();
+    let mut outputs = Vec::new();
+    let id_of =
+        |value: &Value, key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+    match provider {
+        ProviderKind::Codex => {
+            if let Some(payload) = value.get("payload")
+                && value.get("type").and_then(Value::as_str) == Some("response_item")
+            {
+                match payload.get("type").and_then(Value::as_str) {
+                    Some("function_call" | "custom_tool_call" | "local_shell_call") => {
+                        calls.extend(id_of(payload, "call_id"));
+                    }
+                    Some(
+                        "function_call_output"
+                        | "custom_tool_call_output"
+                        | "local_shell_call_output",
+                    ) => outputs.extend(id_of(payload, "call_id")),
+                    _ => {}
+                }
+            }
+        }
+        ProviderKind::Claude => {
+            let items = value
+                .get("message")
+                .and_then(|message| message.get("content"))
+                .and_then(Value::as_array);
+            for item in items.into_iter().flatten() {
+                match item.get("type").and_then(Value::as_str) {
+                    Some("tool_use") => calls.extend(id_of(item, "id")),
+                    Some("tool_result") => outputs.extend(id_of(item, "tool_use_id")),
+                    _ => {}
+                }
+            }
+        }
+        ProviderKind::Pi => {
+            if let Some(message) = value.get("message") {
+                if message.get("role").and_then(Value::as_str) == Some("toolResult") {
+                    outputs.extend(id_of(message, "toolCallId"));
+                }
+                let items = message.get("content").and_then(Value::as_array);
+                for item in items.into_iter().flatten() {
+                    if item.get("type").and_then(Value::as_str) == Some("toolCall") {
+                        calls.extend(id_of(item, "id"));
+                    }
+                }
+            }
+        }
+        // OpenCode keeps a call and its output in the same part.
+        ProviderKind::Opencode | ProviderKind::Amp | ProviderKind::Gemini => {}
+    }
+    (calls, outputs)
+}
+
+fn issue(kind: LintIssueKind, line: Option<usize>, message: String) -> LintIssue {
+    LintIssue {
+        kind,
+        line,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lint_thread_payload;
+    use crate::model::{LintIssueKind, ProviderKind};
+
+    #[test]
+    fn lint_reports_malformed_lines_missing_timestamps_and_orphan_outputs() {
+        let raw = concat!(
+            r#"{"timestamp":"2026-02-23T00:00:00Z","type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{}","call_id":"call_1"}}"#,
+            "\n",
+            r#"{"timestamp":"2026-02-23T00:00:01Z","type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"ok"}}"#,
+            "\n",
+            "{\"type\":\"response_item\",\n",
+            "\n",
+            r#"{"type":"response_item","payload":{"type":"function_call_output","call_id":"call_9","output":"lost"}}"#,
+            "\n",
+            r#"{"timestamp":"2026-02-23T00:00:02Z","type":"world_state","payload":{}}"#,
+            "\n",
+            r#"{"timestamp":"2026-02-23T00:00:03Z","type":"world_state","payload":{}}"#,
+            "\n",
+        );
+
+        let (records, issues) = lint_thread_payload(ProviderKind::Codex, raw);
+        assert_eq!(records, 6);
+        let found = issues
+            .iter()
+            .map(|issue| (issue.kind, issue.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (LintIssueKind::MalformedJson, Some(3)),
+                (LintIssueKind::MissingTimestamp, Some(5)),
+                (LintIssueKind::OrphanToolOutput, Some(5)),
+                (LintIssueKind::UnknownRecordType, Some(6)),
+            ]
+        );
+        assert!(issues[2].message.contains("call_9"));
+    }
+
+    #[test]
+    fn lint_reports_json_document_parse_error_line() {
+        let (records, issues) = lint_thread_payload(
+            ProviderKind::Gemini,
+            "{\n  \"messages\": [\n    oops\n  ]\n}",
+        );
+        assert_eq!(records, 0);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, LintIssueKind::MalformedJson);
+        assert_eq!(issues[0].line, Some(3));
+    }
+}
@@ -0,0 +1,164 @@
+use std::io::BufRead;
+
+use serde_json::Value;
+
+use crate::jsonl;
+use crate::model::ProviderKind;
+
+/// Lines read before giving up on fields a thread never recorded. Every provider writes its
+/// session header and first model choice well within them.
+const METADATA_LINES: usize = 200;
+
+/// Session facts recorded at the start of a thread file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ThreadMetadata {
+    pub(crate) started_at: Option<String>,
+    pub(crate) workdir: Option<String>,
+    pub(crate) model: Option<String>,
+    pub(crate) cli_version: Option<String>,
+    pub(crate) branch: Option<String>,
+}
+
+impl ThreadMetadata {
+    fn is_complete(&self, provider: ProviderKind) -> bool {
+        let common = self.started_at.is_some() && self.workdir.is_some() && self.model.is_some();
+        match provider {
+            // pi headers carry no CLI version or branch.
+            ProviderKind::Pi => common,
+            _ => common && self.cli_version.is_some() && self.branch.is_some(),
+        }
+    }
+}
+
+/// Reads the session header and first model choice of a Codex, Claude, or pi thread, and
+/// stops as soon as it has them, so the cost does not grow with the thread. Fields a thread
+/// does not record within its first lines are left `None`; other providers record nothing.
+pub(crate) fn thread_metadata<R: BufRead>(provider: ProviderKind, reader: R) -> ThreadMetadata {
+    let absorb = match provider {
+        ProviderKind::Codex => absorb_codex,
+        ProviderKind::Claude => absorb_claude,
+        ProviderKind::Pi => absorb_pi,
+        ProviderKind::Amp | ProviderKind::Gemini | ProviderKind::Opencode => {
+            return ThreadMetadata::default();
+        }
+    };
+    let mut metadata = ThreadMetadata::default();
+    for line in reader
+        .lines()
+        .take(METADATA_LINES)
+        .map_while(std::result::Result::ok)
+    {
+        if let Some(value) = jsonl::parse_thread_line(&line) {
+            absorb(&mut metadata, &value);
+        }
+        if metadata.is_complete(provider) {
+            break;
+        }
+    }
+    metadata
+}
+
+/// Keeps the first non-empty value seen for a field.
+fn fill(field: &mut Option<String>, value: Option<&Value>) {
+    if field.is_none()
+        && let Some(value) = value
+            .and_then(Value::as_str)
+            .filter(|value| !value.is_empty())
+    {
+        *field = Some(value.to_string());
+    }
+}
+
+fn absorb_codex(metadata: &mut ThreadMetadata, value: &Value) {
+    let Some(payload) = value.get("payload") else {
+        return;
+    };
+    match value.get("type").and_then(Value::as_str) {
+        Some("session_meta") => {
+            fill(&mut metadata.started_at, payload.get("timestamp"));
+            fill(&mut metadata.workdir, payload.get("cwd"));
+            fill(&mut metadata.cli_version, payload.get("cli_version"));
+            fill(&mut metadata.branch, payload.pointer("/git/branch"));
+        }
+        Some("turn_context") => {
+            fill(&mut metadata.workdir, payload.get("cwd"));
+            fill(&mut metadata.model, payload.get("model"));
+        }
+        _ => {}
+    }
+}
+
+fn absorb_claude(metadata: &mut ThreadMetadata, value: &Value) {
+    if !matches!(
+        value.get("type").and_then(Value::as_str),
+        Some("user" | "assistant")
+    ) {
+        return;
+    }
+    fill(&mut metadata.started_at, value.get("timestamp"));
+    fill(&mut metadata.workdir, value.get("cwd"));
+    fill(&mut metadata.cli_version, value.get("version"));
+    fill(&mut metadata.branch, value.get("gitBranch"));
+    fill(&mut metadata.model, value.pointer("/message/model"));
+}
+
+fn absorb_pi(metadata: &mut ThreadMetadata, value: &Value) {
+    match value.get("type").and_then(Value::as_str) {
+        Some("session") => {
+            fill(&mut metadata.started_at, value.get("timestamp"));
+            fill(&mut metadata.workdir, value.get("cwd"));
+        }
+        Some("model_change") => fill(&mut metadata.model, value.get("modelId")),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ThreadMetadata, thread_metadata};
+    use crate::model::ProviderKind;
+
+    #[test]
+    fn metadata_comes_from_the_first_records_only() {
+        let codex = [
+            r#"{"type":"session_meta","payload":{"id":"s1","timestamp":"2026-02-23T04:48:50Z","cwd":"/work/app","cli_version":"0.104.0","git":{"branch":"main"}}}"#,
+            r#"{"type":"turn_context","payload":{"cwd":"/work/app","model":"gpt-5.3-codex"}}"#,
+            r#"{"type":"turn_context","payload":{"cwd":"/elsewhere","model":"later-model"}}"#,
+        ];
+        assert_eq!(
+            thread_metadata(ProviderKind::Codex, codex.join("\n").as_bytes()),
+            ThreadMetadata {
+                started_at: Some("2026-02-23T04:48:50Z".to_string()),
+                workdir: Some("/work/app".to_string()),
+                model: Some("gpt-5.3-codex".to_string()),
+                cli_version: Some("0.104.0".to_string()),
+                branch: Some("main".to_string()),
+            }
+        );
+
+        let claude = [
+            r#"{"type":"summary","summary":"Fix build"}"#,
+            r#"{"type":"user","timestamp":"2026-02-23T04:48:50Z","cwd":"/work/app","version":"2.1.3","gitBranch":"fix","message":{"role":"user","content":"fix"}}"#,
+            r#"{"type":"assistant","timestamp":"2026-02-23T04:49:00Z","message":{"role":"assistant","model":"claude-sonnet-4-5","content":[]}}"#,
+        ];
+        let metadata = thread_metadata(ProviderKind::Claude, claude.join("\n").as_bytes());
+        assert_eq!(metadata.started_at.as_deref(), Some("2026-02-23T04:48:50Z"));
+        assert_eq!(metadata.model.as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!(metadata.branch.as_deref(), Some("fix"));
+
+        let pi = [
+            r#"{"type":"session","version":3,"id":"s1","timestamp":"2026-02-23T13:20:05.148Z","cwd":"/work/app"}"#,
+            "not json",
+            r#"{"type":"model_change","id":"a1","provider":"openai-codex","modelId":"gpt-5.3-codex"}"#,
+        ];
+        let metadata = thread_metadata(ProviderKind::Pi, pi.join("\n").as_bytes());
+        assert_eq!(metadata.workdir.as_deref(), Some("/work/app"));
+        assert_eq!(metadata.model.as_deref(), Some("gpt-5.3-codex"));
+        assert_eq!(metadata.cli_version, None);
+
+        assert_eq!(
+            thread_metadata(ProviderKind::Gemini, codex.join("\n").as_bytes()),
+            ThreadMetadata::default()
+        );
+    }
+}
@@ -1,9 +1,16 @@
+use std::collections::BTreeMap;
 use std::fmt;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 
 use serde::Serialize;
 
+use crate::error::XurlError;
+use crate::timestamp::TimeZone;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ProviderKind {
     Amp,
     Codex,
@@ -13,6 +20,17 @@ pub enum ProviderKind {
     Opencode,
 }
 
+impl ProviderKind {
+    pub const ALL: [Self; 6] = [
+        Self::Amp,
+        Self::Codex,
+        Self::Claude,
+        Self::Gemini,
+        Self::Pi,
+        Self::Opencode,
+    ];
+}
+
 impl fmt::Display for ProviderKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -31,6 +49,13 @@ pub struct ResolutionMeta {
     pub source: String,
     pub candidate_count: usize,
     pub warnings: Vec<String>,
+    /// Records that are not valid JSON and that readers skip.
+    pub corrupt_lines: usize,
+    /// Candidates left out of `candidate_count` because they are byte-identical copies of
+    /// another candidate.
+    pub duplicates_skipped: usize,
+    /// Time spent finding the thread file, filled in by `resolve_thread`.
+    pub elapsed: Duration,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -78,6 +103,8 @@ pub struct ResolvedSkill {
 pub struct WriteRequest {
     pub prompt: String,
     pub session_id: Option<String>,
+    /// Child session or entry id under `session_id`; only pi accepts write targets below a main thread.
+    pub child_id: Option<String>,
     pub options: WriteOptions,
 }
 
@@ -87,12 +114,63 @@ pub struct WriteResult {
     pub session_id: String,
     pub final_text: Option<String>,
     pub warnings: Vec<String>,
+    /// Set when the provider process was killed for exceeding `max_output_chars` or
+    /// `max_duration`.
+    pub truncated: bool,
+    /// The command that would have run; set instead of spawning it when
+    /// `WriteOptions::dry_run` is on.
+    pub dry_run: Option<WriteCommand>,
+    /// Exit code of the provider process; `None` for dry runs and when a signal ended it.
+    pub exit_code: Option<i32>,
+    /// Wall-clock time of the write, retries included.
+    pub duration: Duration,
+    /// UTF-8 bytes of reply text streamed to the sink, retries included.
+    pub bytes_streamed: u64,
+}
+
+/// A provider CLI invocation as write mode spawns it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Working directory; `None` inherits xurl's own.
+    pub cwd: Option<PathBuf>,
+    /// Variables set on top of the inherited environment.
+    pub env: Vec<(String, String)>,
+}
+
+impl WriteCommand {
+    pub fn new(program: String, args: &[String]) -> Self {
+        Self {
+            program,
+            args: args.to_vec(),
+            cwd: None,
+            env: Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct WriteOptions {
     pub params: Vec<(String, Option<String>)>,
     pub role: Option<String>,
+    /// Extra attempts, with exponential backoff, when the provider CLI exits
+    /// non-zero before reporting a session id.
+    pub retries: u32,
+    /// Extra system instructions, mapped to each provider's own mechanism.
+    pub system: Option<String>,
+    /// Model name, mapped to each provider's own model flag or config override.
+    pub model: Option<String>,
+    /// How much the agent may do without approval, mapped to each provider's own
+    /// sandbox or permission flag.
+    pub approval: Option<ApprovalMode>,
+    /// Kill the provider process once this many reply characters were streamed.
+    pub max_output_chars: Option<usize>,
+    /// Kill the provider process once it has run this long.
+    pub max_duration: Option<Duration>,
+    /// Build the provider command but return it in `WriteResult::dry_run` instead of
+    /// spawning it.
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
@@ -110,10 +188,302 @@ impl fmt::Display for MessageRole {
     }
 }
 
+/// What a write-mode agent may do without a human approving it (`approval=` in write URIs).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApprovalMode {
+    /// Read only: file edits and commands that need approval are refused.
+    Never,
+    /// File edits in the workspace are approved; anything else needs approval, which a
+    /// non-interactive run refuses.
+    OnRequest,
+    /// Everything is approved, without a sandbox.
+    Auto,
+}
+
+impl fmt::Display for ApprovalMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Never => write!(f, "never"),
+            Self::OnRequest => write!(f, "on-request"),
+            Self::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+impl FromStr for ApprovalMode {
+    type Err = XurlError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "never" => Ok(Self::Never),
+            "on-request" => Ok(Self::OnRequest),
+            "auto" => Ok(Self::Auto),
+            _ => Err(XurlError::InvalidMode(format!(
+                "unsupported approval value: {value}; expected never, on-request, or auto"
+            ))),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ThreadMessage {
     pub role: MessageRole,
     pub text: String,
+    /// When the provider recorded the message, as an RFC 3339 timestamp.
+    pub timestamp: Option<String>,
+}
+
+/// How serious a review finding is; the names match SARIF result levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingSeverity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for FindingSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+            Self::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A file and line an agent's review flagged, with the line of the review that said so.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub path: String,
+    pub line: u64,
+    pub severity: FindingSeverity,
+    pub message: String,
+}
+
+/// Review findings extracted from a thread's assistant messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ThreadFindings {
+    pub uri: String,
+    pub findings: Vec<Finding>,
+}
+
+/// What `xurl findings` prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingsFormat {
+    Json,
+    /// A SARIF 2.1.0 log, for code-scanning uploads.
+    Sarif,
+}
+
+impl FromStr for FindingsFormat {
+    type Err = XurlError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(Self::Json),
+            "sarif" => Ok(Self::Sarif),
+            _ => Err(XurlError::InvalidMode(format!(
+                "unsupported --format value: {value}; expected json or sarif"
+            ))),
+        }
+    }
+}
+
+/// How `--write-summary` reports a finished write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteSummaryFormat {
+    Json,
+}
+
+impl FromStr for WriteSummaryFormat {
+    type Err = XurlError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(Self::Json),
+            _ => Err(XurlError::InvalidMode(format!(
+                "unsupported --write-summary value: {value}; expected json"
+            ))),
+        }
+    }
+}
+
+/// A kind of identifying value `xurl export --scrub` rewrites into placeholders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrubKind {
+    /// Home directories like `/home/alice`, and the user names found in them.
+    Paths,
+    Emails,
+    /// URL hosts and internal names like `build01.internal`.
+    Hostnames,
+}
+
+impl FromStr for ScrubKind {
+    type Err = XurlError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "paths" => Ok(Self::Paths),
+            "emails" => Ok(Self::Emails),
+            "hostnames" => Ok(Self::Hostnames),
+            _ => Err(XurlError::InvalidMode(format!(
+                "unsupported --scrub value: {value}; expected paths, emails, or hostnames"
+            ))),
+        }
+    }
+}
+
+/// The rendition `xurl export` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportFormat {
+    /// Collapsible markdown split into parts that fit GitHub's comment limit.
+    GhComment,
+    /// One CSV row per message.
+    Csv,
+    /// One parquet row per message; needs the `parquet` feature.
+    Parquet,
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::GhComment => write!(f, "gh-comment"),
+            Self::Csv => write!(f, "csv"),
+            Self::Parquet => write!(f, "parquet"),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = XurlError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "gh-comment" => Ok(Self::GhComment),
+            "csv" => Ok(Self::Csv),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(XurlError::InvalidMode(format!(
+                "unsupported --format value: {value}; expected gh-comment, csv, or parquet"
+            ))),
+        }
+    }
+}
+
+/// Which timeline entries `--only` keeps in a rendered thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineFilter {
+    User,
+    Assistant,
+    Tools,
+}
+
+impl fmt::Display for TimelineFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User => write!(f, "user"),
+            Self::Assistant => write!(f, "assistant"),
+            Self::Tools => write!(f, "tools"),
+        }
+    }
+}
+
+impl FromStr for TimelineFilter {
+    type Err = XurlError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "user" => Ok(Self::User),
+            "assistant" => Ok(Self::Assistant),
+            "tools" => Ok(Self::Tools),
+            _ => Err(XurlError::InvalidMode(format!(
+                "unsupported --only value: {value}; expected user, assistant, or tools"
+            ))),
+        }
+    }
+}
+
+/// Options that shape how a thread body is rendered.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Keep only user messages, assistant messages, or tool calls in the timeline.
+    pub only: Option<TimelineFilter>,
+    /// Render earlier sessions this thread was resumed or forked from.
+    pub with_history: bool,
+    /// Stop rendering after this many bytes of body and append a truncation notice.
+    pub max_bytes: Option<u64>,
+    /// Replace tool outputs larger than this many bytes with their size and sha256.
+    pub elide_bytes: Option<u64>,
+    /// Render only the timeline entries and tool calls containing this text, with context.
+    pub grep: Option<String>,
+    /// Levels of descendant agents a subagent drill-down lists; 0 lists none.
+    pub descendant_depth: usize,
+    /// Show each message's timestamp and the time since the previous message.
+    pub show_timestamps: bool,
+    /// The zone `show_timestamps` shows timestamps in.
+    pub timezone: TimeZone,
+    /// Show Claude slash commands, hooks, and system notices as timeline entries.
+    pub show_events: bool,
+}
+
+/// A tool invocation recorded in a thread, with its output when the thread recorded one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ToolCall {
+    pub id: Option<String>,
+    pub name: String,
+    pub input: serde_json::Value,
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+impl fmt::Display for FileChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Created => write!(f, "created"),
+            Self::Modified => write!(f, "modified"),
+            Self::Deleted => write!(f, "deleted"),
+        }
+    }
+}
+
+/// A file changed by edit, write, or patch tool calls during a thread.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: FileChangeKind,
+    /// Number of tool calls that touched the file.
+    pub edits: usize,
+}
+
+/// Tool calls a thread made through one MCP server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct McpServerUsage {
+    pub server: String,
+    pub calls: usize,
+}
+
+/// A link from an Amp thread to another thread, from its `relationships` list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ThreadRelationship {
+    /// Relationship type as Amp records it, like `handoff` or `fork`.
+    pub kind: String,
+    /// This thread's side of the relationship, like `parent` or `child`, when recorded.
+    pub role: Option<String>,
+    pub uri: String,
+    pub timestamp: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -122,6 +492,8 @@ pub struct SubagentQuery {
     pub main_thread_id: String,
     pub agent_id: Option<String>,
     pub list: bool,
+    /// Levels of descendant agents a drill-down lists below the child; 0 lists none.
+    pub depth: usize,
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
@@ -159,10 +531,24 @@ pub struct SubagentDetailView {
     pub status_source: String,
     pub child_thread: Option<SubagentThreadRef>,
     pub excerpt: Vec<SubagentExcerptMessage>,
+    /// Agents the child spawned, and theirs, depth first, down to `query.depth` levels.
+    pub descendants: Vec<SubagentDescendant>,
     #[serde(skip_serializing)]
     pub warnings: Vec<String>,
 }
 
+/// A subagent spawned below a drilled-down child thread.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubagentDescendant {
+    /// 1 for agents the child spawned, 2 for the agents those spawned, and so on.
+    pub depth: usize,
+    pub parent_thread_id: String,
+    pub agent_id: String,
+    pub uri: String,
+    pub status: String,
+    pub status_source: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct SubagentListItem {
     pub agent_id: String,
@@ -173,10 +559,123 @@ pub struct SubagentListItem {
     pub child_thread: Option<SubagentThreadRef>,
 }
 
+/// How `--sort` orders a subagent index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubagentSort {
+    /// The order the provider's records list the children in.
+    #[default]
+    Discovered,
+    /// Earliest first message first.
+    Started,
+    /// Active children first: running, pendingInit, errored, completed, shutdown, notFound.
+    Status,
+    /// Longest first.
+    Duration,
+}
+
+impl fmt::Display for SubagentSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Discovered => write!(f, "discovered"),
+            Self::Started => write!(f, "started"),
+            Self::Status => write!(f, "status"),
+            Self::Duration => write!(f, "duration"),
+        }
+    }
+}
+
+impl FromStr for SubagentSort {
+    type Err = XurlError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "discovered" => Ok(Self::Discovered),
+            "started" => Ok(Self::Started),
+            "status" => Ok(Self::Status),
+            "duration" => Ok(Self::Duration),
+            _ => Err(XurlError::InvalidMode(format!(
+                "unsupported --sort value: {value}; expected started, status, duration, or discovered"
+            ))),
+        }
+    }
+}
+
+/// Options that shape the `subagents` index of a main thread's frontmatter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubagentListOptions {
+    pub sort: SubagentSort,
+    /// Keep only children with one of these statuses; empty keeps all.
+    pub statuses: Vec<String>,
+}
+
+/// The part of a stored thread file `xurl cat` prints.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RawSlice {
+    #[default]
+    Whole,
+    Lines(LineRange),
+    /// The last N lines.
+    Tail(usize),
+}
+
+/// Lines `first` through `last` of a file, counted from 1 and inclusive; `last: None` runs to
+/// the end of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub first: usize,
+    pub last: Option<usize>,
+}
+
+impl FromStr for LineRange {
+    type Err = XurlError;
+
+    /// Parses `FIRST:LAST`; either side may be left out (`1000:`, `:2000`), and a single
+    /// number selects that one line.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            XurlError::InvalidMode(format!(
+                "invalid --lines value: {value}; expected FIRST:LAST, like 1000:2000"
+            ))
+        };
+        let (first, last) = value.split_once(':').unwrap_or((value, value));
+        let first = match first.trim() {
+            "" => 1,
+            first => first.parse::<usize>().map_err(|_| invalid())?,
+        };
+        let last = match last.trim() {
+            "" => None,
+            last => Some(last.parse::<usize>().map_err(|_| invalid())?),
+        };
+        if first == 0 || last.is_some_and(|last| last < first) {
+            return Err(invalid());
+        }
+        Ok(Self { first, last })
+    }
+}
+
+/// Totals over every child of a subagent index.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SubagentTotals {
+    /// How many children have each status.
+    pub by_status: BTreeMap<String, usize>,
+    /// Summed run time of the children, from each one's first to last timestamped message.
+    pub duration_ms: u64,
+    pub longest: Option<SubagentLongest>,
+}
+
+/// The child with the longest run time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubagentLongest {
+    pub agent_id: String,
+    pub duration_ms: u64,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct SubagentListView {
     pub query: SubagentQuery,
     pub agents: Vec<SubagentListItem>,
+    pub totals: SubagentTotals,
     #[serde(skip_serializing)]
     pub warnings: Vec<String>,
 }
@@ -220,22 +719,376 @@ pub struct ThreadQuery {
     pub role: Option<String>,
     pub q: Option<String>,
     pub limit: usize,
+    pub sort: QuerySort,
+    /// Number of matches to skip before the page starts.
+    pub offset: usize,
+    /// `next_cursor` of an earlier page of the same query; decoded into an offset.
+    pub cursor: Option<String>,
+    /// Project directory filter; only Claude groups sessions by project.
+    pub project: Option<String>,
+    /// Keep only threads in this status.
+    pub status: Option<ThreadStatus>,
+    /// Keep only threads whose recorded working directory is this directory or below it.
+    pub workdir: Option<String>,
+    /// Keep only threads whose working directory is a checkout of this repository, given as
+    /// `owner/name` or a remote URL.
+    pub repo: Option<String>,
     pub ignored_params: Vec<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// Whether a main thread is still running, inferred from the last records its provider wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadStatus {
+    Running,
+    Completed,
+    Failed,
+    Unknown,
+}
+
+impl fmt::Display for ThreadStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Running => write!(f, "running"),
+            Self::Completed => write!(f, "completed"),
+            Self::Failed => write!(f, "failed"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Order of thread query results; `Relevance` only applies when the query has `q`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuerySort {
+    #[default]
+    Recency,
+    Relevance,
+}
+
+impl fmt::Display for QuerySort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Recency => write!(f, "recency"),
+            Self::Relevance => write!(f, "relevance"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct ThreadQueryItem {
     pub thread_id: String,
     pub uri: String,
     pub thread_source: String,
     pub updated_at: Option<String>,
     pub matched_preview: Option<String>,
+    /// BM25 relevance of the thread to `q` within this result set; `None` without `q`.
+    pub score: Option<f64>,
+    /// Set on query results; `None` in recent-thread listings, which skip reading threads.
+    pub status: Option<ThreadStatus>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct ThreadQueryResult {
     pub query: ThreadQuery,
     pub items: Vec<ThreadQueryItem>,
+    /// Cursor for the page after this one; `None` on the last page.
+    pub next_cursor: Option<String>,
+    #[serde(skip_serializing)]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecentThreads {
+    pub provider: Option<ProviderKind>,
+    pub items: Vec<ThreadQueryItem>,
+    #[serde(skip_serializing)]
+    pub warnings: Vec<String>,
+}
+
+/// A thread `xurl ps` considers running right now.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RunningThread {
+    pub uri: String,
+    pub provider: ProviderKind,
+    pub status: ThreadStatus,
+    /// Working directory the thread recorded, when it recorded one.
+    pub cwd: Option<String>,
+    pub updated_at: Option<String>,
+    /// Provider CLI process running in `cwd`, when one was found.
+    pub pid: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RunningThreads {
+    pub items: Vec<RunningThread>,
+    #[serde(skip_serializing)]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadEventKind {
+    /// A main thread that did not exist when watching started.
+    ThreadCreated,
+    /// A main thread whose stored payload changed.
+    ThreadUpdated,
+    /// A thread that changed while watching and has since stayed unchanged for the idle
+    /// period, which usually means the agent finished its turn.
+    ThreadIdle,
+}
+
+/// One line of `xurl watch` output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ThreadEvent {
+    pub event: ThreadEventKind,
+    pub provider: ProviderKind,
+    pub uri: String,
+    /// Start of the thread's last message; only set on `thread_idle` events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsckIssueKind {
+    MissingRollout,
+    OrphanFile,
+    EmptySession,
+    OrphanMessage,
+}
+
+impl fmt::Display for FsckIssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingRollout => write!(f, "missing_rollout"),
+            Self::OrphanFile => write!(f, "orphan_file"),
+            Self::EmptySession => write!(f, "empty_session"),
+            Self::OrphanMessage => write!(f, "orphan_message"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FsckIssue {
+    pub kind: FsckIssueKind,
+    pub session_id: String,
+    pub source: String,
+    pub repair: String,
+    /// Set when `fsck --fix` already applied the repair.
+    pub fixed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FsckReport {
+    pub provider: ProviderKind,
+    pub indexes: Vec<PathBuf>,
+    pub indexed_threads: usize,
+    pub files_on_disk: Option<usize>,
+    pub issues: Vec<FsckIssue>,
+    #[serde(skip_serializing)]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintIssueKind {
+    MalformedJson,
+    MissingTimestamp,
+    OrphanToolOutput,
+    UnknownRecordType,
+}
+
+impl fmt::Display for LintIssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MalformedJson => write!(f, "malformed_json"),
+            Self::MissingTimestamp => write!(f, "missing_timestamp"),
+            Self::OrphanToolOutput => write!(f, "orphan_tool_output"),
+            Self::UnknownRecordType => write!(f, "unknown_record_type"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintIssue {
+    pub kind: LintIssueKind,
+    /// 1-based line in the thread file, when the issue maps to one.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintReport {
+    pub uri: String,
+    pub provider: ProviderKind,
+    pub path: PathBuf,
+    pub records: usize,
+    pub issues: Vec<LintIssue>,
+}
+
+/// One message of a dataset export (`xurl export --format csv|parquet`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageRow {
+    pub provider: ProviderKind,
+    pub session_id: String,
+    pub uri: String,
+    /// 1-based position of the message in its thread.
+    pub seq: u64,
+    pub role: MessageRole,
+    pub timestamp: Option<String>,
+    /// Token usage of the whole thread, repeated on each of its rows.
+    pub thread_input_tokens: Option<u64>,
+    pub thread_output_tokens: Option<u64>,
+    pub text: String,
+}
+
+/// How many rows `xurl export-db` wrote.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportDbReport {
+    pub threads: usize,
+    pub messages: usize,
+    pub tool_calls: usize,
+    pub warnings: Vec<String>,
+}
+
+/// Limits `xurl cache gc` prunes the caches down to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheGcOptions {
+    /// Delete entries not refreshed for longer than this.
+    pub max_age: Option<Duration>,
+    /// Then delete the oldest entries until the rest total at most this many bytes.
+    pub max_size: Option<u64>,
+    /// Report what would be deleted without deleting it.
+    pub dry_run: bool,
+}
+
+/// What `xurl cache gc` deleted, or would delete with `dry_run`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheGcReport {
+    pub removed: Vec<PathBuf>,
+    pub freed_bytes: u64,
+    pub kept: usize,
+    pub kept_bytes: u64,
+    pub warnings: Vec<String>,
+}
+
+/// What `xurl sync` did to the notes of a vault.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VaultSyncReport {
+    pub created: Vec<PathBuf>,
+    pub updated: Vec<PathBuf>,
+    /// Notes already up to date with their thread.
+    pub unchanged: usize,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommitBlameItem {
+    pub uri: String,
+    /// Name of the first tool whose output mentions the commit.
+    pub tool: String,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommitBlame {
+    pub commit: String,
+    pub items: Vec<CommitBlameItem>,
     #[serde(skip_serializing)]
     pub warnings: Vec<String>,
 }
+
+/// Outcome of one `xurl doctor` check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    /// Could not be confirmed, or works in a degraded way.
+    Warn,
+    Missing,
+    Error,
+}
+
+impl fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            Self::Ok => "ok",
+            Self::Warn => "warn",
+            Self::Missing => "missing",
+            Self::Error => "error",
+        };
+        write!(f, "{value}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HealthCheck {
+    pub status: HealthStatus,
+    pub detail: String,
+}
+
+/// A CLI binary and the setting it came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResolvedBin {
+    pub path: String,
+    /// `XURL_<NAME>_BIN`, `[bins] <name>`, or `PATH`.
+    pub source: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProviderHealth {
+    pub provider: ProviderKind,
+    pub root: HealthCheck,
+    /// Sqlite indexes the provider keeps; `None` when it has none.
+    pub index: Option<HealthCheck>,
+    /// The binary write mode runs.
+    pub bin: ResolvedBin,
+    pub cli: HealthCheck,
+    pub auth: HealthCheck,
+    pub role_write: bool,
+    pub fsck: bool,
+    /// Suggested fixes for the checks that did not pass.
+    pub hints: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DoctorReport {
+    pub providers: Vec<ProviderHealth>,
+}
+
+/// Files written by exporting a thread with its embedded attachments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadExport {
+    pub markdown_path: PathBuf,
+    pub attachment_paths: Vec<PathBuf>,
+}
+
+/// Tokens a provider reported for a thread, summed over its turns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// One thread's column of an `xurl compare` report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadComparison {
+    pub uri: String,
+    pub provider: ProviderKind,
+    /// The last assistant message, if the thread has one.
+    pub final_answer: Option<String>,
+    /// Tool call counts by tool name, most used first.
+    pub tool_calls: Vec<(String, usize)>,
+    pub duration: Option<Duration>,
+    pub tokens: Option<TokenUsage>,
+}
+
+/// File paths, URLs, and commits referenced by a thread's tool calls and messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ThreadLinks {
+    pub uri: String,
+    pub files: Vec<String>,
+    pub urls: Vec<String>,
+    pub commits: Vec<String>,
+}
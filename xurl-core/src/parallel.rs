@@ -0,0 +1,41 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+/// Runs `work` for every index below `count` on up to `jobs` threads, and hands each result to
+/// `done` in index order, as soon as it and every earlier result are ready. Results that finish
+/// early wait in a buffer keyed by index, so output does not depend on thread scheduling.
+pub fn run_ordered<T: Send>(
+    count: usize,
+    jobs: usize,
+    work: impl Fn(usize) -> T + Sync,
+    mut done: impl FnMut(usize, T),
+) {
+    let next = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..jobs.min(count) {
+            let sender = sender.clone();
+            let (next, work) = (&next, &work);
+            scope.spawn(move || {
+                loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    if idx >= count || sender.send((idx, work(idx))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut ready = 0;
+        for (idx, value) in receiver {
+            pending.insert(idx, value);
+            while let Some(value) = pending.remove(&ready) {
+                done(ready, value);
+                ready += 1;
+            }
+        }
+    });
+}
@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+
+use crate::model::ProviderKind;
+
+/// A provider CLI process, with the directory it runs in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AgentProcess {
+    pub(crate) pid: u32,
+    pub(crate) provider: ProviderKind,
+    pub(crate) cwd: PathBuf,
+}
+
+/// Provider CLI processes of the current user that `/proc` shows. Other platforms have no
+/// process table xurl reads, so they list none.
+#[cfg(target_os = "linux")]
+pub(crate) fn agent_processes() -> Vec<AgentProcess> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(std::result::Result::ok)
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            let provider = cmdline_provider(&std::fs::read(entry.path().join("cmdline")).ok()?)?;
+            // Fails for processes of other users.
+            let cwd = std::fs::read_link(entry.path().join("cwd")).ok()?;
+            Some(AgentProcess { pid, provider, cwd })
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn agent_processes() -> Vec<AgentProcess> {
+    Vec::new()
+}
+
+/// The provider whose CLI a NUL-separated command line runs: the program itself, or the
+/// script an interpreter like `node` runs.
+fn cmdline_provider(cmdline: &[u8]) -> Option<ProviderKind> {
+    cmdline.split(|byte| *byte == 0).take(2).find_map(|arg| {
+        let arg = String::from_utf8_lossy(arg);
+        let name = Path::new(arg.as_ref()).file_name()?.to_str()?;
+        ProviderKind::ALL
+            .iter()
+            .copied()
+            .find(|provider| provider.to_string() == name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cmdline_provider;
+    use crate::model::ProviderKind;
+
+    #[test]
+    fn cmdline_names_the_provider_cli_or_its_script() {
+        assert_eq!(
+            cmdline_provider(b"/usr/local/bin/codex\0exec\0--json\0"),
+            Some(ProviderKind::Codex)
+        );
+        assert_eq!(
+            cmdline_provider(b"node\0/home/alice/.npm/bin/claude\0--resume\0"),
+            Some(ProviderKind::Claude)
+        );
+        assert_eq!(cmdline_provider(b"vim\0codex.md\0"), None);
+        assert_eq!(cmdline_provider(b"bash\0-c\0codex\0"), None);
+    }
+}
@@ -1,50 +1,177 @@
-use std::io::{BufReader, Read};
+use std::fs;
+use std::io::{BufReader, Read, Write};
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::time::Duration;
 
+use crate::config::XurlConfig;
 use crate::error::{Result, XurlError};
 use crate::jsonl;
-use crate::model::{ProviderKind, ResolutionMeta, ResolvedThread, WriteRequest, WriteResult};
-use crate::provider::{Provider, WriteEventSink, append_passthrough_args};
+use crate::model::{
+    ApprovalMode, ProviderKind, ResolutionMeta, ResolvedThread, WriteCommand, WriteRequest,
+    WriteResult,
+};
+use crate::provider::budget::WriteBudget;
+use crate::provider::process::{SpawnedWrite, start_write_command};
+use crate::provider::{
+    Provider, WriteEventSink, append_passthrough_args, dry_run_result,
+    push_unsupported_approval_warning, push_unsupported_system_warning,
+};
 use serde_json::Value;
+use tracing::debug;
 
 #[derive(Debug, Clone)]
 pub struct AmpProvider {
     root: PathBuf,
+    cache_root: Option<PathBuf>,
 }
 
 impl AmpProvider {
     pub fn new(root: impl Into<PathBuf>) -> Self {
-        Self { root: root.into() }
+        Self {
+            root: root.into(),
+            cache_root: None,
+        }
+    }
+
+    /// Enables the `XURL_AMP_API_TOKEN` network fallback, caching downloaded threads here.
+    pub fn with_cache_root(mut self, cache_root: impl Into<PathBuf>) -> Self {
+        self.cache_root = Some(cache_root.into());
+        self
     }
 
     fn threads_root(&self) -> PathBuf {
         self.root.join("threads")
     }
 
-    fn amp_bin() -> String {
-        std::env::var("XURL_AMP_BIN").unwrap_or_else(|_| "amp".to_string())
+    fn api_token() -> Option<String> {
+        std::env::var("XURL_AMP_API_TOKEN")
+            .ok()
+            .filter(|token| !token.trim().is_empty())
+    }
+
+    fn api_url() -> String {
+        std::env::var("XURL_AMP_API_URL").unwrap_or_else(|_| "https://ampcode.com".to_string())
+    }
+
+    fn curl_bin() -> String {
+        XurlConfig::resolve_bin("curl").path
     }
 
-    fn spawn_amp_command(args: &[String]) -> Result<std::process::Child> {
-        let bin = Self::amp_bin();
-        let mut command = Command::new(&bin);
-        command
-            .args(args)
-            .stdin(Stdio::null())
+    /// Downloads a thread from the Amp server API with the curl at `bin`. The token is passed to
+    /// curl on stdin so it never shows up in the process list.
+    fn fetch_thread(bin: &str, session_id: &str, token: &str) -> Result<Vec<u8>> {
+        let url = format!(
+            "{}/api/threads/{session_id}",
+            Self::api_url().trim_end_matches('/')
+        );
+        let args = [
+            "--fail".to_string(),
+            "--silent".to_string(),
+            "--show-error".to_string(),
+            "--location".to_string(),
+            "--header".to_string(),
+            "@-".to_string(),
+            url,
+        ];
+        let mut child = Command::new(bin)
+            .args(&args)
+            .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-        command.spawn().map_err(|source| {
-            if source.kind() == std::io::ErrorKind::NotFound {
-                XurlError::CommandNotFound { command: bin }
-            } else {
-                XurlError::Io {
-                    path: PathBuf::from(bin),
-                    source,
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|source| {
+                if source.kind() == std::io::ErrorKind::NotFound {
+                    XurlError::CommandNotFound {
+                        command: bin.to_string(),
+                    }
+                } else {
+                    XurlError::Io {
+                        path: PathBuf::from(bin),
+                        source,
+                    }
                 }
-            }
-        })
+            })?;
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = writeln!(stdin, "Authorization: Bearer {}", token.trim());
+        }
+        let output = child.wait_with_output().map_err(|source| XurlError::Io {
+            path: PathBuf::from(bin),
+            source,
+        })?;
+        if !output.status.success() {
+            return Err(XurlError::CommandFailed {
+                command: format!("{bin} {}", args.join(" ")),
+                code: output.status.code(),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+
+        match serde_json::from_slice::<Value>(&output.stdout) {
+            Ok(Value::Object(_)) => Ok(output.stdout),
+            _ => Err(XurlError::WriteProtocol(format!(
+                "amp api returned a non-thread response for session_id={session_id}"
+            ))),
+        }
+    }
+
+    fn resolve_from_cache(&self, session_id: &str) -> Result<Option<ResolvedThread>> {
+        let Some(cache_root) = &self.cache_root else {
+            return Ok(None);
+        };
+        let path = cache_root
+            .join("threads")
+            .join(format!("{session_id}.json"));
+        if path.exists() {
+            debug!(path = %path.display(), "amp thread found in cache");
+            return Ok(Some(Self::resolved(session_id, path, "amp:cache")));
+        }
+
+        let Some(token) = Self::api_token() else {
+            debug!("amp thread not cached and no API token set; skipping network fallback");
+            return Ok(None);
+        };
+        Self::download_thread(session_id, path, &token, &Self::curl_bin()).map(Some)
+    }
+
+    /// Fetches `session_id` with the curl at `curl_bin` and caches it at `path`.
+    fn download_thread(
+        session_id: &str,
+        path: PathBuf,
+        token: &str,
+        curl_bin: &str,
+    ) -> Result<ResolvedThread> {
+        debug!("fetching amp thread {session_id} from the API");
+        let body = Self::fetch_thread(curl_bin, session_id, token)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|source| XurlError::Io {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+        }
+        write_cached(&path, &body)?;
+        Ok(Self::resolved(session_id, path, "amp:api"))
+    }
+
+    fn resolved(session_id: &str, path: PathBuf, source: &str) -> ResolvedThread {
+        ResolvedThread {
+            provider: ProviderKind::Amp,
+            session_id: session_id.to_string(),
+            path,
+            metadata: ResolutionMeta {
+                source: source.to_string(),
+                candidate_count: 1,
+                warnings: Vec::new(),
+                corrupt_lines: 0,
+                duplicates_skipped: 0,
+                elapsed: Duration::ZERO,
+            },
+        }
+    }
+
+    pub(crate) fn amp_bin() -> String {
+        XurlConfig::resolve_bin("amp").path
     }
 
     fn extract_assistant_text(value: &Value) -> Option<String> {
@@ -71,19 +198,28 @@ impl AmpProvider {
         sink: &mut dyn WriteEventSink,
         warnings: Vec<String>,
     ) -> Result<WriteResult> {
-        let mut child = Self::spawn_amp_command(args)?;
-        let stdout = child.stdout.take().ok_or_else(|| {
-            XurlError::WriteProtocol("amp stdout pipe is unavailable".to_string())
-        })?;
-        let stderr = child.stderr.take().ok_or_else(|| {
-            XurlError::WriteProtocol("amp stderr pipe is unavailable".to_string())
-        })?;
+        let command = WriteCommand::new(Self::amp_bin(), args);
+        if req.options.dry_run {
+            return Ok(dry_run_result(
+                ProviderKind::Amp,
+                req.session_id.clone(),
+                command,
+                warnings,
+            ));
+        }
+        let SpawnedWrite {
+            process,
+            stdout,
+            stderr,
+        } = start_write_command(ProviderKind::Amp, &command)?;
         let stderr_handle = std::thread::spawn(move || {
             let mut reader = BufReader::new(stderr);
             let mut content = String::new();
             let _ = reader.read_to_string(&mut content);
             content
         });
+        let mut budget = WriteBudget::start(process, &req.options);
+        let sink = &mut budget.sink(sink);
 
         let mut session_id = req.session_id.clone();
         let mut final_text = None::<String>;
@@ -150,12 +286,12 @@ impl AmpProvider {
             Ok(())
         })?;
 
-        let status = child.wait().map_err(|source| XurlError::Io {
+        let status = budget.wait().map_err(|source| XurlError::Io {
             path: PathBuf::from(Self::amp_bin()),
             source,
         })?;
         let stderr_content = stderr_handle.join().unwrap_or_default();
-        if !status.success() {
+        if !status.success() && !budget.stopped() {
             return Err(XurlError::CommandFailed {
                 command: format!("{} {}", Self::amp_bin(), args.join(" ")),
                 code: status.code(),
@@ -177,12 +313,19 @@ impl AmpProvider {
             ));
         };
 
-        Ok(WriteResult {
+        let mut result = WriteResult {
             provider: ProviderKind::Amp,
             session_id,
             final_text,
             warnings,
-        })
+            truncated: false,
+            dry_run: None,
+            exit_code: status.code(),
+            duration: Duration::ZERO,
+            bytes_streamed: 0,
+        };
+        budget.finish(&mut result);
+        Ok(result)
     }
 }
 
@@ -195,23 +338,27 @@ impl Provider for AmpProvider {
         let threads_root = self.threads_root();
         let path = threads_root.join(format!("{session_id}.json"));
 
-        if !path.exists() {
-            return Err(XurlError::ThreadNotFound {
-                provider: ProviderKind::Amp.to_string(),
-                session_id: session_id.to_string(),
-                searched_roots: vec![threads_root],
-            });
+        if path.exists() {
+            debug!(path = %path.display(), "amp thread found in local threads root");
+            return Ok(Self::resolved(session_id, path, "amp:threads"));
         }
+        debug!(
+            root = %threads_root.display(),
+            "amp thread {session_id} not in local threads root; trying cache"
+        );
 
-        Ok(ResolvedThread {
-            provider: ProviderKind::Amp,
+        if let Some(resolved) = self.resolve_from_cache(session_id)? {
+            return Ok(resolved);
+        }
+
+        let mut searched_roots = vec![threads_root];
+        if let Some(cache_root) = &self.cache_root {
+            searched_roots.push(cache_root.join("threads"));
+        }
+        Err(XurlError::ThreadNotFound {
+            provider: ProviderKind::Amp.to_string(),
             session_id: session_id.to_string(),
-            path,
-            metadata: ResolutionMeta {
-                source: "amp:threads".to_string(),
-                candidate_count: 1,
-                warnings: Vec::new(),
-            },
+            searched_roots,
         })
     }
 
@@ -222,7 +369,22 @@ impl Provider for AmpProvider {
                 ProviderKind::Amp
             )));
         }
-        let warnings = Vec::new();
+        let mut warnings = Vec::new();
+        push_unsupported_system_warning(&mut warnings, &req.options, ProviderKind::Amp);
+        if let Some(model) = req.options.model.as_deref() {
+            warnings.push(format!(
+                "ignored model `{model}`: provider `{}` picks its model from the agent mode",
+                ProviderKind::Amp
+            ));
+        }
+        if req.options.approval != Some(ApprovalMode::Auto) {
+            push_unsupported_approval_warning(
+                &mut warnings,
+                &req.options,
+                ProviderKind::Amp,
+                "only has --dangerously-allow-all (approval=auto)",
+            );
+        }
         let mut args = Vec::new();
         if let Some(session_id) = req.session_id.as_deref() {
             args.push("threads".to_string());
@@ -236,11 +398,30 @@ impl Provider for AmpProvider {
             args.push(req.prompt.clone());
             args.push("--stream-json".to_string());
         }
+        if req.options.approval == Some(ApprovalMode::Auto) {
+            args.push("--dangerously-allow-all".to_string());
+        }
         append_passthrough_args(&mut args, &req.options.params);
         self.run_write(&args, req, sink, warnings)
     }
 }
 
+/// Writes a downloaded thread next to `path` and renames it into place, so a reader never sees
+/// a partly written cache file.
+fn write_cached(path: &Path, body: &[u8]) -> Result<()> {
+    let temp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    let written = fs::write(&temp_path, body)
+        .and_then(|()| fs::rename(&temp_path, path))
+        .map_err(|source| XurlError::Io {
+            path: path.to_path_buf(),
+            source,
+        });
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -266,6 +447,88 @@ mod tests {
         assert_eq!(resolved.metadata.source, "amp:threads");
     }
 
+    #[test]
+    fn resolves_from_api_cache_when_not_on_disk() {
+        let temp = tempdir().expect("tempdir");
+        let cache = temp.path().join("cache");
+        fs::create_dir_all(cache.join("threads")).expect("mkdir");
+        let path = cache
+            .join("threads")
+            .join("T-019c0797-c402-7389-bd80-d785c98df295.json");
+        fs::write(&path, "{\"messages\":[]}").expect("write");
+
+        let provider = AmpProvider::new(temp.path().join("amp")).with_cache_root(&cache);
+        let resolved = provider
+            .resolve("T-019c0797-c402-7389-bd80-d785c98df295")
+            .expect("resolve should succeed");
+        assert_eq!(resolved.path, path);
+        assert_eq!(resolved.metadata.source, "amp:cache");
+    }
+
+    /// Writes a `curl` stand-in that drains the header on stdin and prints `body`.
+    #[cfg(unix)]
+    fn fake_curl(dir: &std::path::Path, body: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join("curl");
+        fs::write(
+            &path,
+            format!("#!/bin/sh\ncat >/dev/null\nprintf '%s' '{body}'\n"),
+        )
+        .expect("write curl");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).expect("chmod curl");
+        path.display().to_string()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn downloads_thread_from_api_into_cache() {
+        let temp = tempdir().expect("tempdir");
+        let curl = fake_curl(temp.path(), "{\"messages\":[]}");
+        let threads = temp.path().join("cache/threads");
+        fs::create_dir_all(&threads).expect("mkdir");
+        let path = threads.join("T-019c0797-c402-7389-bd80-d785c98df295.json");
+
+        let resolved = AmpProvider::download_thread(
+            "T-019c0797-c402-7389-bd80-d785c98df295",
+            path.clone(),
+            "amp-token",
+            &curl,
+        )
+        .expect("download should succeed");
+        assert_eq!(resolved.path, path);
+        assert_eq!(resolved.metadata.source, "amp:api");
+        assert_eq!(
+            fs::read_to_string(&path).expect("read cache"),
+            "{\"messages\":[]}"
+        );
+        assert_eq!(fs::read_dir(&threads).expect("list cache").count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_non_object_api_response() {
+        let temp = tempdir().expect("tempdir");
+        let curl = fake_curl(temp.path(), "[]");
+        let path = temp
+            .path()
+            .join("cache/threads/T-019c0797-c402-7389-bd80-d785c98df295.json");
+
+        let err = AmpProvider::download_thread(
+            "T-019c0797-c402-7389-bd80-d785c98df295",
+            path.clone(),
+            "amp-token",
+            &curl,
+        )
+        .expect_err("a list is not a thread");
+        assert!(
+            err.to_string()
+                .contains("amp api returned a non-thread response"),
+            "{err}"
+        );
+        assert!(!path.exists());
+    }
+
     #[test]
     fn missing_thread_returns_not_found() {
         let temp = tempdir().expect("tempdir");
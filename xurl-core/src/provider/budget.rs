@@ -0,0 +1,154 @@
+use std::process::ExitStatus;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+
+use crate::error::Result;
+use crate::model::{ProviderKind, WriteOptions, WriteResult};
+use crate::provider::WriteEventSink;
+use crate::provider::process::WriteProcess;
+
+/// Enforces `WriteOptions::max_output_chars` and `max_duration` on a running provider
+/// process, killing it once either limit is exceeded.
+pub(crate) struct WriteBudget {
+    child: Arc<Mutex<WriteProcess>>,
+    stop_reason: Arc<Mutex<Option<String>>>,
+    cancel: Option<Sender<()>>,
+    watchdog: Option<JoinHandle<()>>,
+    max_output_chars: Option<usize>,
+    emitted_chars: usize,
+}
+
+impl WriteBudget {
+    /// Takes ownership of `child` (after its pipes were taken) and starts the duration watchdog.
+    pub(crate) fn start(child: WriteProcess, options: &WriteOptions) -> Self {
+        let child = Arc::new(Mutex::new(child));
+        let stop_reason = Arc::new(Mutex::new(None));
+        let (cancel, watchdog) = match options.max_duration {
+            Some(limit) => {
+                let (cancel, cancelled) = mpsc::channel::<()>();
+                let child = Arc::clone(&child);
+                let stop_reason = Arc::clone(&stop_reason);
+                let watchdog = std::thread::spawn(move || {
+                    if cancelled.recv_timeout(limit) == Err(RecvTimeoutError::Timeout) {
+                        stop(
+                            &child,
+                            &stop_reason,
+                            format!(
+                                "provider ran longer than {}s (--max-duration)",
+                                limit.as_secs_f64()
+                            ),
+                        );
+                    }
+                });
+                (Some(cancel), Some(watchdog))
+            }
+            None => (None, None),
+        };
+
+        Self {
+            child,
+            stop_reason,
+            cancel,
+            watchdog,
+            max_output_chars: options.max_output_chars,
+            emitted_chars: 0,
+        }
+    }
+
+    /// Wraps `sink` so text deltas count against the output limit.
+    pub(crate) fn sink<'a>(&'a mut self, sink: &'a mut dyn WriteEventSink) -> BudgetSink<'a> {
+        BudgetSink {
+            inner: sink,
+            budget: self,
+        }
+    }
+
+    /// Stops the watchdog and waits for the provider process to exit.
+    pub(crate) fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        self.cancel.take();
+        if let Some(watchdog) = self.watchdog.take() {
+            let _ = watchdog.join();
+        }
+        self.child
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .wait()
+    }
+
+    /// Whether the process was killed for exceeding a limit; its exit status is then expected
+    /// to be a failure.
+    pub(crate) fn stopped(&self) -> bool {
+        self.stop_reason
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+
+    /// Marks `result` as truncated when a limit was hit and trims `final_text` to the
+    /// output limit.
+    pub(crate) fn finish(&self, result: &mut WriteResult) {
+        let Some(reason) = self
+            .stop_reason
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+        else {
+            return;
+        };
+        result.truncated = true;
+        result
+            .warnings
+            .push(format!("write stopped early: {reason}"));
+        if let (Some(limit), Some(text)) = (self.max_output_chars, result.final_text.as_mut())
+            && let Some((end, _)) = text.char_indices().nth(limit)
+        {
+            text.truncate(end);
+        }
+    }
+}
+
+fn stop(child: &Mutex<WriteProcess>, stop_reason: &Mutex<Option<String>>, reason: String) {
+    stop_reason
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get_or_insert(reason);
+    let _ = child.lock().unwrap_or_else(PoisonError::into_inner).kill();
+}
+
+pub(crate) struct BudgetSink<'a> {
+    inner: &'a mut dyn WriteEventSink,
+    budget: &'a mut WriteBudget,
+}
+
+impl WriteEventSink for BudgetSink<'_> {
+    fn on_session_ready(&mut self, provider: ProviderKind, session_id: &str) -> Result<()> {
+        self.inner.on_session_ready(provider, session_id)
+    }
+
+    fn on_text_delta(&mut self, text: &str) -> Result<()> {
+        if self.budget.stopped() {
+            return Ok(());
+        }
+        let Some(limit) = self.budget.max_output_chars else {
+            return self.inner.on_text_delta(text);
+        };
+
+        let remaining = limit.saturating_sub(self.budget.emitted_chars);
+        match text.char_indices().nth(remaining) {
+            Some((end, _)) => {
+                self.budget.emitted_chars = limit;
+                stop(
+                    &self.budget.child,
+                    &self.budget.stop_reason,
+                    format!("output exceeded {limit} chars (--max-output-chars)"),
+                );
+                self.inner.on_text_delta(&text[..end])
+            }
+            None => {
+                self.budget.emitted_chars += text.chars().count();
+                self.inner.on_text_delta(text)
+            }
+        }
+    }
+}
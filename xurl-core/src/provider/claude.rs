@@ -1,20 +1,28 @@
 use std::cmp::Reverse;
+use std::collections::HashSet;
 use std::fs;
 use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use serde::Deserialize;
 use serde_json::Value;
-use walkdir::WalkDir;
+use tracing::debug;
 
+use crate::config::XurlConfig;
 use crate::error::{Result, XurlError};
 use crate::jsonl;
-use crate::model::{ProviderKind, ResolutionMeta, ResolvedThread, WriteRequest, WriteResult};
+use crate::model::{
+    ApprovalMode, ProviderKind, ResolutionMeta, ResolvedThread, WriteCommand, WriteRequest,
+    WriteResult,
+};
+use crate::provider::budget::WriteBudget;
+use crate::provider::process::{SpawnedWrite, start_write_command};
 use crate::provider::{
-    Provider, WriteEventSink, append_passthrough_args, append_passthrough_args_excluding,
+    Provider, WriteEventSink, append_model_arg, append_passthrough_args,
+    append_passthrough_args_excluding, dry_run_result, skip_identical_candidates,
 };
+use crate::scan::{ScanOptions, scan_files};
 
 #[derive(Debug, Deserialize)]
 struct SessionsIndex {
@@ -33,18 +41,31 @@ struct SessionIndexEntry {
 #[derive(Debug, Clone)]
 pub struct ClaudeProvider {
     root: PathBuf,
+    scan: ScanOptions,
 }
 
 impl ClaudeProvider {
     pub fn new(root: impl Into<PathBuf>) -> Self {
-        Self { root: root.into() }
+        Self {
+            root: root.into(),
+            scan: ScanOptions::default(),
+        }
+    }
+
+    /// Bounds the walks that look for thread files when no index has the session.
+    #[must_use]
+    pub fn with_scan_options(mut self, scan: &ScanOptions) -> Self {
+        self.scan = scan.clone();
+        self
     }
 
     fn projects_root(&self) -> PathBuf {
         self.root.join("projects")
     }
 
-    fn choose_latest(paths: Vec<PathBuf>) -> Option<(PathBuf, usize)> {
+    /// The newest candidate, how many distinct candidates there were, and how many
+    /// byte-identical copies were skipped.
+    fn choose_latest(paths: Vec<PathBuf>) -> Option<(PathBuf, usize, usize)> {
         if paths.is_empty() {
             return None;
         }
@@ -59,19 +80,27 @@ impl ClaudeProvider {
             })
             .collect::<Vec<_>>();
         scored.sort_by_key(|(_, modified)| Reverse(*modified));
-        let count = scored.len();
-        scored.into_iter().next().map(|(path, _)| (path, count))
+        let (paths, duplicates_skipped) =
+            skip_identical_candidates(scored.into_iter().map(|(path, _)| path).collect());
+        let count = paths.len();
+        paths
+            .into_iter()
+            .next()
+            .map(|path| (path, count, duplicates_skipped))
     }
 
-    fn find_from_sessions_index(projects_root: &Path, session_id: &str) -> Vec<PathBuf> {
+    fn find_from_sessions_index(
+        projects_root: &Path,
+        scan: &ScanOptions,
+        session_id: &str,
+        warnings: &mut Vec<String>,
+    ) -> Vec<PathBuf> {
         if !projects_root.exists() {
             return Vec::new();
         }
 
-        WalkDir::new(projects_root)
+        scan_files(projects_root, scan, warnings)
             .into_iter()
-            .filter_map(std::result::Result::ok)
-            .filter(|entry| entry.file_type().is_file())
             .filter(|entry| entry.file_name() == "sessions-index.json")
             .filter_map(|entry| fs::read_to_string(entry.path()).ok())
             .filter_map(|content| serde_json::from_str::<SessionsIndex>(&content).ok())
@@ -88,16 +117,19 @@ impl ClaudeProvider {
             .collect()
     }
 
-    fn find_by_filename(projects_root: &Path, session_id: &str) -> Vec<PathBuf> {
+    fn find_by_filename(
+        projects_root: &Path,
+        scan: &ScanOptions,
+        session_id: &str,
+        warnings: &mut Vec<String>,
+    ) -> Vec<PathBuf> {
         if !projects_root.exists() {
             return Vec::new();
         }
 
         let needle = format!("{session_id}.jsonl");
-        WalkDir::new(projects_root)
+        scan_files(projects_root, scan, warnings)
             .into_iter()
-            .filter_map(std::result::Result::ok)
-            .filter(|entry| entry.file_type().is_file())
             .map(|entry| entry.into_path())
             .filter(|path| {
                 path.file_name()
@@ -131,15 +163,18 @@ impl ClaudeProvider {
         false
     }
 
-    fn find_by_header_scan(projects_root: &Path, session_id: &str) -> Vec<PathBuf> {
+    fn find_by_header_scan(
+        projects_root: &Path,
+        scan: &ScanOptions,
+        session_id: &str,
+        warnings: &mut Vec<String>,
+    ) -> Vec<PathBuf> {
         if !projects_root.exists() {
             return Vec::new();
         }
 
-        WalkDir::new(projects_root)
+        scan_files(projects_root, scan, warnings)
             .into_iter()
-            .filter_map(std::result::Result::ok)
-            .filter(|entry| entry.file_type().is_file())
             .map(|entry| entry.into_path())
             .filter(|path| {
                 path.extension()
@@ -154,12 +189,20 @@ impl ClaudeProvider {
         session_id: &str,
         selected: PathBuf,
         count: usize,
+        duplicates_skipped: usize,
+        mut warnings: Vec<String>,
         source: &str,
     ) -> ResolvedThread {
+        // The lookups walk the same root, so they report the same skipped subtrees.
+        let mut seen = HashSet::new();
+        warnings.retain(|warning| seen.insert(warning.clone()));
         let mut metadata = ResolutionMeta {
             source: source.to_string(),
             candidate_count: count,
-            warnings: Vec::new(),
+            warnings,
+            corrupt_lines: 0,
+            duplicates_skipped,
+            elapsed: Duration::ZERO,
         };
 
         if count > 1 {
@@ -177,28 +220,8 @@ impl ClaudeProvider {
         }
     }
 
-    fn claude_bin() -> String {
-        std::env::var("XURL_CLAUDE_BIN").unwrap_or_else(|_| "claude".to_string())
-    }
-
-    fn spawn_claude_command(args: &[String]) -> Result<std::process::Child> {
-        let bin = Self::claude_bin();
-        let mut command = Command::new(&bin);
-        command
-            .args(args)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-        command.spawn().map_err(|source| {
-            if source.kind() == std::io::ErrorKind::NotFound {
-                XurlError::CommandNotFound { command: bin }
-            } else {
-                XurlError::Io {
-                    path: PathBuf::from(bin),
-                    source,
-                }
-            }
-        })
+    pub(crate) fn claude_bin() -> String {
+        XurlConfig::resolve_bin("claude").path
     }
 
     fn extract_assistant_text(value: &Value) -> Option<String> {
@@ -225,19 +248,28 @@ impl ClaudeProvider {
         sink: &mut dyn WriteEventSink,
         warnings: Vec<String>,
     ) -> Result<WriteResult> {
-        let mut child = Self::spawn_claude_command(args)?;
-        let stdout = child.stdout.take().ok_or_else(|| {
-            XurlError::WriteProtocol("claude stdout pipe is unavailable".to_string())
-        })?;
-        let stderr = child.stderr.take().ok_or_else(|| {
-            XurlError::WriteProtocol("claude stderr pipe is unavailable".to_string())
-        })?;
+        let command = WriteCommand::new(Self::claude_bin(), args);
+        if req.options.dry_run {
+            return Ok(dry_run_result(
+                ProviderKind::Claude,
+                req.session_id.clone(),
+                command,
+                warnings,
+            ));
+        }
+        let SpawnedWrite {
+            process,
+            stdout,
+            stderr,
+        } = start_write_command(ProviderKind::Claude, &command)?;
         let stderr_handle = std::thread::spawn(move || {
             let mut reader = BufReader::new(stderr);
             let mut content = String::new();
             let _ = reader.read_to_string(&mut content);
             content
         });
+        let mut budget = WriteBudget::start(process, &req.options);
+        let sink = &mut budget.sink(sink);
 
         let mut session_id = req.session_id.clone();
         let mut final_text = None::<String>;
@@ -288,13 +320,13 @@ impl ClaudeProvider {
             Ok(())
         })?;
 
-        let status = child.wait().map_err(|source| XurlError::Io {
+        let status = budget.wait().map_err(|source| XurlError::Io {
             path: PathBuf::from(Self::claude_bin()),
             source,
         })?;
         let stderr_content = stderr_handle.join().unwrap_or_default();
 
-        if !status.success() {
+        if !status.success() && !budget.stopped() {
             return Err(XurlError::CommandFailed {
                 command: format!("{} {}", Self::claude_bin(), args.join(" ")),
                 code: status.code(),
@@ -310,12 +342,19 @@ impl ClaudeProvider {
             ));
         };
 
-        Ok(WriteResult {
+        let mut result = WriteResult {
             provider: ProviderKind::Claude,
             session_id,
             final_text,
             warnings,
-        })
+            truncated: false,
+            dry_run: None,
+            exit_code: status.code(),
+            duration: Duration::ZERO,
+            bytes_streamed: 0,
+        };
+        budget.finish(&mut result);
+        Ok(result)
     }
 }
 
@@ -326,33 +365,56 @@ impl Provider for ClaudeProvider {
 
     fn resolve(&self, session_id: &str) -> Result<ResolvedThread> {
         let projects = self.projects_root();
+        debug!(root = %projects.display(), "resolving claude thread {session_id}");
 
-        let index_hits = Self::find_from_sessions_index(&projects, session_id);
-        if let Some((selected, count)) = Self::choose_latest(index_hits) {
+        let mut warnings = Vec::new();
+        let index_hits =
+            Self::find_from_sessions_index(&projects, &self.scan, session_id, &mut warnings);
+        debug!(
+            candidates = index_hits.len(),
+            "claude sessions-index lookup finished"
+        );
+        if let Some((selected, count, duplicates_skipped)) = Self::choose_latest(index_hits) {
             return Ok(Self::make_resolved(
                 session_id,
                 selected,
                 count,
+                duplicates_skipped,
+                warnings,
                 "claude:sessions-index",
             ));
         }
 
-        let filename_hits = Self::find_by_filename(&projects, session_id);
-        if let Some((selected, count)) = Self::choose_latest(filename_hits) {
+        let filename_hits =
+            Self::find_by_filename(&projects, &self.scan, session_id, &mut warnings);
+        debug!(
+            candidates = filename_hits.len(),
+            "claude filename scan finished"
+        );
+        if let Some((selected, count, duplicates_skipped)) = Self::choose_latest(filename_hits) {
             return Ok(Self::make_resolved(
                 session_id,
                 selected,
                 count,
+                duplicates_skipped,
+                warnings,
                 "claude:filename",
             ));
         }
 
-        let scanned_hits = Self::find_by_header_scan(&projects, session_id);
-        if let Some((selected, count)) = Self::choose_latest(scanned_hits) {
+        let scanned_hits =
+            Self::find_by_header_scan(&projects, &self.scan, session_id, &mut warnings);
+        debug!(
+            candidates = scanned_hits.len(),
+            "claude header scan finished"
+        );
+        if let Some((selected, count, duplicates_skipped)) = Self::choose_latest(scanned_hits) {
             return Ok(Self::make_resolved(
                 session_id,
                 selected,
                 count,
+                duplicates_skipped,
+                warnings,
                 "claude:header-scan",
             ));
         }
@@ -385,6 +447,22 @@ impl Provider for ClaudeProvider {
         } else {
             append_passthrough_args(&mut args, &req.options.params);
         }
+        append_model_arg(&mut args, &req.options, "--model");
+        if let Some(approval) = req.options.approval {
+            args.push("--permission-mode".to_string());
+            args.push(
+                match approval {
+                    ApprovalMode::Never => "dontAsk",
+                    ApprovalMode::OnRequest => "acceptEdits",
+                    ApprovalMode::Auto => "bypassPermissions",
+                }
+                .to_string(),
+            );
+        }
+        if let Some(system) = req.options.system.as_deref() {
+            args.push("--append-system-prompt".to_string());
+            args.push(system.to_string());
+        }
         if let Some(session_id) = req.session_id.as_deref() {
             args.push("--resume".to_string());
             args.push(session_id.to_string());
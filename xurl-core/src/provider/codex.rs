@@ -3,34 +3,53 @@ use std::collections::BTreeMap;
 use std::fs;
 use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use rusqlite::{Connection, OpenFlags, OptionalExtension};
 use serde_json::Value;
 use toml::Table as TomlTable;
 use toml::Value as TomlValue;
-use walkdir::WalkDir;
+use tracing::debug;
 
+use crate::config::XurlConfig;
 use crate::error::{Result, XurlError};
 use crate::jsonl;
-use crate::model::{ProviderKind, ResolutionMeta, ResolvedThread, WriteRequest, WriteResult};
-use crate::provider::{Provider, WriteEventSink, append_passthrough_args};
+use crate::model::{
+    ApprovalMode, ProviderKind, ResolutionMeta, ResolvedThread, WriteCommand, WriteRequest,
+    WriteResult,
+};
+use crate::provider::budget::WriteBudget;
+use crate::provider::process::{SpawnedWrite, start_write_command};
+use crate::provider::{
+    Provider, WriteEventSink, append_passthrough_args, dry_run_result, skip_identical_candidates,
+};
+use crate::scan::{ScanOptions, scan_files};
 
 #[derive(Debug, Clone)]
 pub struct CodexProvider {
     root: PathBuf,
+    scan: ScanOptions,
 }
 
 #[derive(Debug, Clone)]
-struct SqliteThreadRecord {
-    rollout_path: PathBuf,
-    archived: bool,
+pub(crate) struct SqliteThreadRecord {
+    pub(crate) rollout_path: PathBuf,
+    pub(crate) archived: bool,
 }
 
 impl CodexProvider {
     pub fn new(root: impl Into<PathBuf>) -> Self {
-        Self { root: root.into() }
+        Self {
+            root: root.into(),
+            scan: ScanOptions::default(),
+        }
+    }
+
+    /// Bounds the walks that look for thread files when no index has the session.
+    #[must_use]
+    pub fn with_scan_options(mut self, scan: &ScanOptions) -> Self {
+        self.scan = scan.clone();
+        self
     }
 
     fn sessions_root(&self) -> PathBuf {
@@ -41,7 +60,7 @@ impl CodexProvider {
         self.root.join("archived_sessions")
     }
 
-    fn state_db_paths(&self) -> Vec<PathBuf> {
+    pub(crate) fn state_db_paths(&self) -> Vec<PathBuf> {
         let mut paths = if let Ok(entries) = fs::read_dir(&self.root) {
             entries
                 .filter_map(std::result::Result::ok)
@@ -98,6 +117,37 @@ impl CodexProvider {
         Ok(row)
     }
 
+    pub(crate) fn list_thread_records(
+        db_path: &Path,
+    ) -> std::result::Result<Vec<(String, SqliteThreadRecord)>, rusqlite::Error> {
+        let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut stmt =
+            conn.prepare("SELECT id, rollout_path, archived FROM threads ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                SqliteThreadRecord {
+                    rollout_path: PathBuf::from(row.get::<_, String>(1)?),
+                    archived: row.get::<_, i64>(2)? != 0,
+                },
+            ))
+        })?;
+        rows.collect()
+    }
+
+    /// Points the `threads` row of `session_id` at a rollout file that was moved on disk.
+    pub(crate) fn update_rollout_path(
+        db_path: &Path,
+        session_id: &str,
+        rollout_path: &Path,
+    ) -> std::result::Result<usize, rusqlite::Error> {
+        let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+        conn.execute(
+            "UPDATE threads SET rollout_path = ?1 WHERE id = ?2",
+            (rollout_path.to_string_lossy(), session_id),
+        )
+    }
+
     fn lookup_thread_from_state_db(
         state_dbs: &[PathBuf],
         session_id: &str,
@@ -105,28 +155,42 @@ impl CodexProvider {
     ) -> Option<SqliteThreadRecord> {
         for db_path in state_dbs {
             match Self::query_thread_record(db_path, session_id) {
-                Ok(Some(record)) => return Some(record),
-                Ok(None) => continue,
-                Err(err) => warnings.push(format!(
-                    "failed reading sqlite thread index {}: {err}",
-                    db_path.display()
-                )),
+                Ok(Some(record)) => {
+                    debug!(
+                        db = %db_path.display(),
+                        rollout = %record.rollout_path.display(),
+                        archived = record.archived,
+                        "codex sqlite index hit"
+                    );
+                    return Some(record);
+                }
+                Ok(None) => debug!(db = %db_path.display(), "codex sqlite index miss"),
+                Err(err) => {
+                    debug!(db = %db_path.display(), error = %err, "codex sqlite index unreadable");
+                    warnings.push(format!(
+                        "failed reading sqlite thread index {}: {err}",
+                        db_path.display()
+                    ));
+                }
             }
         }
 
         None
     }
 
-    fn find_candidates(root: &Path, session_id: &str) -> Vec<PathBuf> {
+    fn find_candidates(
+        root: &Path,
+        scan: &ScanOptions,
+        session_id: &str,
+        warnings: &mut Vec<String>,
+    ) -> Vec<PathBuf> {
         let needle = format!("{session_id}.jsonl");
         if !root.exists() {
             return Vec::new();
         }
 
-        WalkDir::new(root)
+        scan_files(root, scan, warnings)
             .into_iter()
-            .filter_map(std::result::Result::ok)
-            .filter(|entry| entry.file_type().is_file())
             .map(|entry| entry.into_path())
             .filter(|path| {
                 path.file_name()
@@ -136,7 +200,9 @@ impl CodexProvider {
             .collect()
     }
 
-    fn choose_latest(paths: Vec<PathBuf>) -> Option<(PathBuf, usize)> {
+    /// The newest candidate, how many distinct candidates there were, and how many
+    /// byte-identical copies were skipped.
+    fn choose_latest(paths: Vec<PathBuf>) -> Option<(PathBuf, usize, usize)> {
         if paths.is_empty() {
             return None;
         }
@@ -152,12 +218,17 @@ impl CodexProvider {
             .collect::<Vec<_>>();
 
         scored.sort_by_key(|(_, modified)| Reverse(*modified));
-        let count = scored.len();
-        scored.into_iter().next().map(|(path, _)| (path, count))
+        let (paths, duplicates_skipped) =
+            skip_identical_candidates(scored.into_iter().map(|(path, _)| path).collect());
+        let count = paths.len();
+        paths
+            .into_iter()
+            .next()
+            .map(|path| (path, count, duplicates_skipped))
     }
 
-    fn codex_bin() -> String {
-        std::env::var("XURL_CODEX_BIN").unwrap_or_else(|_| "codex".to_string())
+    pub(crate) fn codex_bin() -> String {
+        XurlConfig::resolve_bin("codex").path
     }
 
     fn config_path(&self) -> PathBuf {
@@ -255,26 +326,6 @@ impl CodexProvider {
         }
     }
 
-    fn spawn_codex_command(args: &[String]) -> Result<std::process::Child> {
-        let bin = Self::codex_bin();
-        let mut command = Command::new(&bin);
-        command
-            .args(args)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-        command.spawn().map_err(|source| {
-            if source.kind() == std::io::ErrorKind::NotFound {
-                XurlError::CommandNotFound { command: bin }
-            } else {
-                XurlError::Io {
-                    path: PathBuf::from(bin),
-                    source,
-                }
-            }
-        })
-    }
-
     fn run_write(
         &self,
         args: &[String],
@@ -282,19 +333,28 @@ impl CodexProvider {
         sink: &mut dyn WriteEventSink,
         warnings: Vec<String>,
     ) -> Result<WriteResult> {
-        let mut child = Self::spawn_codex_command(args)?;
-        let stdout = child.stdout.take().ok_or_else(|| {
-            XurlError::WriteProtocol("codex stdout pipe is unavailable".to_string())
-        })?;
-        let stderr = child.stderr.take().ok_or_else(|| {
-            XurlError::WriteProtocol("codex stderr pipe is unavailable".to_string())
-        })?;
+        let command = WriteCommand::new(Self::codex_bin(), args);
+        if req.options.dry_run {
+            return Ok(dry_run_result(
+                ProviderKind::Codex,
+                req.session_id.clone(),
+                command,
+                warnings,
+            ));
+        }
+        let SpawnedWrite {
+            process,
+            stdout,
+            stderr,
+        } = start_write_command(ProviderKind::Codex, &command)?;
         let stderr_handle = std::thread::spawn(move || {
             let mut reader = BufReader::new(stderr);
             let mut content = String::new();
             let _ = reader.read_to_string(&mut content);
             content
         });
+        let mut budget = WriteBudget::start(process, &req.options);
+        let sink = &mut budget.sink(sink);
 
         let mut session_id = req.session_id.clone();
         let mut final_text = None::<String>;
@@ -331,13 +391,13 @@ impl CodexProvider {
             Ok(())
         })?;
 
-        let status = child.wait().map_err(|source| XurlError::Io {
+        let status = budget.wait().map_err(|source| XurlError::Io {
             path: PathBuf::from(Self::codex_bin()),
             source,
         })?;
         let stderr_content = stderr_handle.join().unwrap_or_default();
 
-        if !status.success() {
+        if !status.success() && !budget.stopped() {
             return Err(XurlError::CommandFailed {
                 command: format!("{} {}", Self::codex_bin(), args.join(" ")),
                 code: status.code(),
@@ -353,12 +413,19 @@ impl CodexProvider {
             ));
         };
 
-        Ok(WriteResult {
+        let mut result = WriteResult {
             provider: ProviderKind::Codex,
             session_id,
             final_text,
             warnings,
-        })
+            truncated: false,
+            dry_run: None,
+            exit_code: status.code(),
+            duration: Duration::ZERO,
+            bytes_streamed: 0,
+        };
+        budget.finish(&mut result);
+        Ok(result)
     }
 }
 
@@ -371,6 +438,12 @@ impl Provider for CodexProvider {
         let sessions = self.sessions_root();
         let archived = self.archived_root();
         let state_dbs = self.state_db_paths();
+        debug!(
+            sessions = %sessions.display(),
+            archived = %archived.display(),
+            state_dbs = state_dbs.len(),
+            "resolving codex thread {session_id}"
+        );
         let mut warnings = Vec::new();
         let sqlite_record =
             Self::lookup_thread_from_state_db(&state_dbs, session_id, &mut warnings);
@@ -385,6 +458,9 @@ impl Provider for CodexProvider {
                         source: "codex:sqlite:sessions".to_string(),
                         candidate_count: 1,
                         warnings,
+                        corrupt_lines: 0,
+                        duplicates_skipped: 0,
+                        elapsed: Duration::ZERO,
                     },
                 });
             }
@@ -395,8 +471,15 @@ impl Provider for CodexProvider {
             ));
         }
 
-        let active_candidates = Self::find_candidates(&sessions, session_id);
-        if let Some((selected, count)) = Self::choose_latest(active_candidates) {
+        debug!(root = %sessions.display(), "scanning codex sessions root");
+        let active_candidates =
+            Self::find_candidates(&sessions, &self.scan, session_id, &mut warnings);
+        debug!(
+            candidates = active_candidates.len(),
+            "codex sessions scan finished"
+        );
+        if let Some((selected, count, duplicates_skipped)) = Self::choose_latest(active_candidates)
+        {
             if count > 1 {
                 warnings.push(format!(
                     "multiple matches found ({count}) for session_id={session_id}; selected latest: {}",
@@ -408,6 +491,9 @@ impl Provider for CodexProvider {
                 source: "codex:sessions".to_string(),
                 candidate_count: count,
                 warnings,
+                corrupt_lines: 0,
+                duplicates_skipped,
+                elapsed: Duration::ZERO,
             };
 
             return Ok(ResolvedThread {
@@ -428,6 +514,9 @@ impl Provider for CodexProvider {
                         source: "codex:sqlite:archived_sessions".to_string(),
                         candidate_count: 1,
                         warnings,
+                        corrupt_lines: 0,
+                        duplicates_skipped: 0,
+                        elapsed: Duration::ZERO,
                     },
                 });
             }
@@ -438,8 +527,16 @@ impl Provider for CodexProvider {
             ));
         }
 
-        let archived_candidates = Self::find_candidates(&archived, session_id);
-        if let Some((selected, count)) = Self::choose_latest(archived_candidates) {
+        let archived_candidates =
+            Self::find_candidates(&archived, &self.scan, session_id, &mut warnings);
+        debug!(
+            root = %archived.display(),
+            candidates = archived_candidates.len(),
+            "codex archived sessions scan finished"
+        );
+        if let Some((selected, count, duplicates_skipped)) =
+            Self::choose_latest(archived_candidates)
+        {
             if count > 1 {
                 warnings.push(format!(
                     "multiple archived matches found ({count}) for session_id={session_id}; selected latest: {}",
@@ -451,6 +548,9 @@ impl Provider for CodexProvider {
                 source: "codex:archived_sessions".to_string(),
                 candidate_count: count,
                 warnings,
+                corrupt_lines: 0,
+                duplicates_skipped,
+                elapsed: Duration::ZERO,
             };
 
             return Ok(ResolvedThread {
@@ -473,11 +573,36 @@ impl Provider for CodexProvider {
 
     fn write(&self, req: &WriteRequest, sink: &mut dyn WriteEventSink) -> Result<WriteResult> {
         let warnings = Vec::new();
-        let role_overrides = if let Some(role) = req.options.role.as_deref() {
+        let mut role_overrides = if let Some(role) = req.options.role.as_deref() {
             self.load_role_overrides(role)?
         } else {
             Vec::new()
         };
+        if let Some(model) = req.options.model.as_deref() {
+            role_overrides.retain(|(key, _)| key != "model");
+            role_overrides.push(("model".to_string(), model.to_string()));
+        }
+        if let Some(approval) = req.options.approval {
+            let sandbox = match approval {
+                ApprovalMode::Never => "read-only",
+                ApprovalMode::OnRequest => "workspace-write",
+                ApprovalMode::Auto => "danger-full-access",
+            };
+            role_overrides.retain(|(key, _)| key != "sandbox_mode");
+            role_overrides.push(("sandbox_mode".to_string(), sandbox.to_string()));
+        }
+        if let Some(system) = req.options.system.as_deref() {
+            // Extend the role's developer instructions rather than replacing them.
+            match role_overrides
+                .iter_mut()
+                .find(|(key, _)| key == "developer_instructions")
+            {
+                Some((_, value)) => *value = format!("{value}\n\n{system}"),
+                None => {
+                    role_overrides.push(("developer_instructions".to_string(), system.to_string()));
+                }
+            }
+        }
         let mut args = Vec::new();
         args.push("exec".to_string());
 
@@ -2,24 +2,45 @@ use std::cmp::Reverse;
 use std::fs;
 use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use serde_json::Value;
-use walkdir::WalkDir;
+use sha2::{Digest, Sha256};
+use tracing::debug;
 
+use crate::config::XurlConfig;
 use crate::error::{Result, XurlError};
-use crate::model::{ProviderKind, ResolutionMeta, ResolvedThread, WriteRequest, WriteResult};
-use crate::provider::{Provider, WriteEventSink, append_passthrough_args};
+use crate::model::{
+    ApprovalMode, ProviderKind, ResolutionMeta, ResolvedThread, WriteCommand, WriteRequest,
+    WriteResult,
+};
+use crate::provider::budget::WriteBudget;
+use crate::provider::process::{SpawnedWrite, start_write_command};
+use crate::provider::{
+    Provider, WriteEventSink, append_model_arg, append_passthrough_args, dry_run_result,
+    skip_identical_candidates,
+};
+use crate::scan::{ScanOptions, scan_files};
 
 #[derive(Debug, Clone)]
 pub struct GeminiProvider {
     root: PathBuf,
+    scan: ScanOptions,
 }
 
 impl GeminiProvider {
     pub fn new(root: impl Into<PathBuf>) -> Self {
-        Self { root: root.into() }
+        Self {
+            root: root.into(),
+            scan: ScanOptions::default(),
+        }
+    }
+
+    /// Bounds the walks that look for thread files when no index has the session.
+    #[must_use]
+    pub fn with_scan_options(mut self, scan: &ScanOptions) -> Self {
+        self.scan = scan.clone();
+        self
     }
 
     fn tmp_root(&self) -> PathBuf {
@@ -56,22 +77,27 @@ impl GeminiProvider {
             .is_some_and(|id| id.eq_ignore_ascii_case(session_id))
     }
 
-    fn find_candidates(tmp_root: &Path, session_id: &str) -> Vec<PathBuf> {
+    fn find_candidates(
+        tmp_root: &Path,
+        scan: &ScanOptions,
+        session_id: &str,
+        warnings: &mut Vec<String>,
+    ) -> Vec<PathBuf> {
         if !tmp_root.exists() {
             return Vec::new();
         }
 
-        WalkDir::new(tmp_root)
+        scan_files(tmp_root, scan, warnings)
             .into_iter()
-            .filter_map(std::result::Result::ok)
-            .filter(|entry| entry.file_type().is_file())
             .map(|entry| entry.into_path())
             .filter(|path| Self::is_session_file(path))
             .filter(|path| Self::has_session_id(path, session_id))
             .collect()
     }
 
-    fn choose_latest(paths: Vec<PathBuf>) -> Option<(PathBuf, usize)> {
+    /// The newest candidate, how many distinct candidates there were, and how many
+    /// byte-identical copies were skipped.
+    fn choose_latest(paths: Vec<PathBuf>) -> Option<(PathBuf, usize, usize)> {
         if paths.is_empty() {
             return None;
         }
@@ -87,57 +113,99 @@ impl GeminiProvider {
             .collect::<Vec<_>>();
 
         scored.sort_by_key(|(_, modified)| Reverse(*modified));
-        let count = scored.len();
-        scored.into_iter().next().map(|(path, _)| (path, count))
+        let (paths, duplicates_skipped) =
+            skip_identical_candidates(scored.into_iter().map(|(path, _)| path).collect());
+        let count = paths.len();
+        paths
+            .into_iter()
+            .next()
+            .map(|path| (path, count, duplicates_skipped))
     }
 
-    fn gemini_bin() -> String {
-        std::env::var("XURL_GEMINI_BIN").unwrap_or_else(|_| "gemini".to_string())
+    pub(crate) fn gemini_bin() -> String {
+        XurlConfig::resolve_bin("gemini").path
     }
 
-    fn spawn_gemini_command(args: &[String]) -> Result<std::process::Child> {
-        let bin = Self::gemini_bin();
-        let mut command = Command::new(&bin);
-        command
-            .args(args)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-        command.spawn().map_err(|source| {
-            if source.kind() == std::io::ErrorKind::NotFound {
-                XurlError::CommandNotFound { command: bin }
-            } else {
-                XurlError::Io {
-                    path: PathBuf::from(bin),
-                    source,
-                }
-            }
-        })
+    /// Gemini only resumes sessions of the project it is started in, so appends run from the
+    /// project root recorded next to the session's `chats` directory when there is one.
+    pub(crate) fn session_project_root(session_path: &Path) -> Option<PathBuf> {
+        let project_dir = session_path.parent()?.parent()?;
+        let raw = fs::read_to_string(project_dir.join(".project_root")).ok()?;
+        let root = PathBuf::from(raw.trim());
+        root.is_dir().then_some(root)
+    }
+
+    /// Returns the `projectHash` directory a session file lives under (`tmp/<hash>/chats/`).
+    pub(crate) fn session_project_hash(session_path: &Path) -> Option<String> {
+        let project_dir = session_path.parent()?.parent()?;
+        project_dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(ToString::to_string)
+    }
+
+    /// Gemini names project directories after the sha256 of the project root path.
+    pub(crate) fn project_hash(project_root: &Path) -> String {
+        format!(
+            "{:x}",
+            Sha256::digest(project_root.to_string_lossy().as_bytes())
+        )
     }
 
     fn run_write(
         &self,
         args: &[String],
+        cwd: Option<&Path>,
         req: &WriteRequest,
         sink: &mut dyn WriteEventSink,
         warnings: Vec<String>,
     ) -> Result<WriteResult> {
-        let mut child = Self::spawn_gemini_command(args)?;
-        let stdout = child.stdout.take().ok_or_else(|| {
-            XurlError::WriteProtocol("gemini stdout pipe is unavailable".to_string())
-        })?;
-        let stderr = child.stderr.take().ok_or_else(|| {
-            XurlError::WriteProtocol("gemini stderr pipe is unavailable".to_string())
-        })?;
+        let mut command = WriteCommand::new(Self::gemini_bin(), args);
+        command.cwd = cwd.map(Path::to_path_buf);
+        if req.options.dry_run {
+            if req.options.system.is_some() {
+                command.env.push((
+                    "GEMINI_SYSTEM_MD".to_string(),
+                    "<temporary file holding the system prompt>".to_string(),
+                ));
+            }
+            return Ok(dry_run_result(
+                ProviderKind::Gemini,
+                req.session_id.clone(),
+                command,
+                warnings,
+            ));
+        }
+        // Gemini reads a system prompt only from the file named by `GEMINI_SYSTEM_MD`.
+        let system_file = req
+            .options
+            .system
+            .as_deref()
+            .map(SystemPromptFile::create)
+            .transpose()?;
+        if let Some(file) = &system_file {
+            command.env.push((
+                "GEMINI_SYSTEM_MD".to_string(),
+                file.0.to_string_lossy().into_owned(),
+            ));
+        }
+        let SpawnedWrite {
+            process,
+            stdout,
+            stderr,
+        } = start_write_command(ProviderKind::Gemini, &command)?;
         let stderr_handle = std::thread::spawn(move || {
             let mut reader = BufReader::new(stderr);
             let mut content = String::new();
             let _ = reader.read_to_string(&mut content);
             content
         });
+        let mut budget = WriteBudget::start(process, &req.options);
+        let sink = &mut budget.sink(sink);
 
         let stream_path = Path::new("<gemini:stdout>");
-        let mut session_id = req.session_id.clone();
+        // Start empty so resumed sessions are announced as soon as the stream confirms them.
+        let mut session_id = None::<String>;
         let mut final_text = None::<String>;
         let mut streamed_text = String::new();
         let mut stream_error = None::<String>;
@@ -199,12 +267,12 @@ impl GeminiProvider {
             }
         }
 
-        let status = child.wait().map_err(|source| XurlError::Io {
+        let status = budget.wait().map_err(|source| XurlError::Io {
             path: PathBuf::from(Self::gemini_bin()),
             source,
         })?;
         let stderr_content = stderr_handle.join().unwrap_or_default();
-        if !status.success() {
+        if !status.success() && !budget.stopped() {
             return Err(XurlError::CommandFailed {
                 command: format!("{} {}", Self::gemini_bin(), args.join(" ")),
                 code: status.code(),
@@ -224,7 +292,7 @@ impl GeminiProvider {
             )));
         }
 
-        let session_id = if let Some(session_id) = session_id {
+        let session_id = if let Some(session_id) = session_id.or_else(|| req.session_id.clone()) {
             session_id
         } else {
             return Err(XurlError::WriteProtocol(
@@ -232,12 +300,46 @@ impl GeminiProvider {
             ));
         };
 
-        Ok(WriteResult {
+        let mut result = WriteResult {
             provider: ProviderKind::Gemini,
             session_id,
             final_text,
             warnings,
-        })
+            truncated: false,
+            dry_run: None,
+            exit_code: status.code(),
+            duration: Duration::ZERO,
+            bytes_streamed: 0,
+        };
+        budget.finish(&mut result);
+        Ok(result)
+    }
+}
+
+/// Temporary system prompt file, removed once the write finishes.
+struct SystemPromptFile(PathBuf);
+
+impl SystemPromptFile {
+    fn create(system: &str) -> Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "xurl-gemini-system-{}-{}.md",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+        ));
+        fs::write(&path, system).map_err(|source| XurlError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for SystemPromptFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
     }
 }
 
@@ -248,13 +350,22 @@ impl Provider for GeminiProvider {
 
     fn resolve(&self, session_id: &str) -> Result<ResolvedThread> {
         let tmp_root = self.tmp_root();
-        let candidates = Self::find_candidates(&tmp_root, session_id);
+        let mut warnings = Vec::new();
+        let candidates = Self::find_candidates(&tmp_root, &self.scan, session_id, &mut warnings);
+        debug!(
+            root = %tmp_root.display(),
+            candidates = candidates.len(),
+            "gemini scan finished for {session_id}"
+        );
 
-        if let Some((selected, count)) = Self::choose_latest(candidates) {
+        if let Some((selected, count, duplicates_skipped)) = Self::choose_latest(candidates) {
             let mut metadata = ResolutionMeta {
                 source: "gemini:chats".to_string(),
                 candidate_count: count,
-                warnings: Vec::new(),
+                warnings,
+                corrupt_lines: 0,
+                duplicates_skipped,
+                elapsed: Duration::ZERO,
             };
 
             if count > 1 {
@@ -286,7 +397,7 @@ impl Provider for GeminiProvider {
                 ProviderKind::Gemini
             )));
         }
-        let warnings = Vec::new();
+        let mut warnings = Vec::new();
         let mut args = vec![
             "-p".to_string(),
             req.prompt.clone(),
@@ -294,12 +405,35 @@ impl Provider for GeminiProvider {
             "stream-json".to_string(),
         ];
         append_passthrough_args(&mut args, &req.options.params);
+        append_model_arg(&mut args, &req.options, "-m");
+        match req.options.approval {
+            Some(ApprovalMode::Never) => {
+                // Non-interactive gemini refuses tools that would ask, but it has no
+                // read-only mode: tools it never asks about still run.
+                args.push("--approval-mode".to_string());
+                args.push("default".to_string());
+                warnings.push(
+                    "approval=never: gemini still runs tools it does not ask about; it has no read-only mode"
+                        .to_string(),
+                );
+            }
+            Some(ApprovalMode::OnRequest) => {
+                args.push("--approval-mode".to_string());
+                args.push("auto_edit".to_string());
+            }
+            Some(ApprovalMode::Auto) => args.push("--yolo".to_string()),
+            None => {}
+        }
         if let Some(session_id) = req.session_id.as_deref() {
+            // Fail before spawning gemini: it silently starts a new session for unknown ids.
+            let resolved = self.resolve(session_id)?;
+            warnings.extend(resolved.metadata.warnings);
+            let cwd = Self::session_project_root(&resolved.path);
             args.push("--resume".to_string());
             args.push(session_id.to_string());
-            self.run_write(&args, req, sink, warnings)
+            self.run_write(&args, cwd.as_deref(), req, sink, warnings)
         } else {
-            self.run_write(&args, req, sink, warnings)
+            self.run_write(&args, None, req, sink, warnings)
         }
     }
 }
@@ -401,6 +535,39 @@ mod tests {
         assert!(first.exists());
     }
 
+    #[test]
+    fn skips_byte_identical_copies_without_warning() {
+        let temp = tempdir().expect("tempdir");
+        let session_id = "29d207db-ca7e-40ba-87f7-e14c9de60613";
+
+        let original = write_session(
+            temp.path(),
+            "hash-a",
+            "session-2026-01-08T11-55-29-29d207db.json",
+            session_id,
+            "same",
+        );
+        let backup = write_session(
+            temp.path(),
+            "hash-a",
+            "session-2026-01-08T11-55-29-29d207db.backup.json",
+            session_id,
+            "same",
+        );
+        assert_eq!(
+            fs::read(&original).expect("read"),
+            fs::read(&backup).expect("read")
+        );
+
+        let provider = GeminiProvider::new(temp.path());
+        let resolved = provider
+            .resolve(session_id)
+            .expect("resolve should succeed");
+        assert_eq!(resolved.metadata.candidate_count, 1);
+        assert_eq!(resolved.metadata.duplicates_skipped, 1);
+        assert!(resolved.metadata.warnings.is_empty());
+    }
+
     #[test]
     fn missing_thread_returns_not_found() {
         let temp = tempdir().expect("tempdir");
@@ -410,4 +577,20 @@ mod tests {
             .expect_err("must fail");
         assert!(format!("{err}").contains("thread not found"));
     }
+
+    #[test]
+    fn project_hash_matches_session_directory_name() {
+        let hash = GeminiProvider::project_hash(Path::new("/Users/alice/repo"));
+        assert_eq!(
+            hash,
+            "61716daa82182a39249b43721b4a171063eb9a93539014ebe7577499d368cdca"
+        );
+        let session = Path::new("/home/.gemini/tmp")
+            .join(&hash)
+            .join("chats/session-2026-01-08T11-55-29-29d207db.json");
+        assert_eq!(
+            GeminiProvider::session_project_hash(&session).as_deref(),
+            Some(hash.as_str())
+        );
+    }
 }
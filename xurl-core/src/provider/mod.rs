@@ -1,19 +1,53 @@
-use std::env;
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs, io};
 
 use dirs::home_dir;
+use sha2::{Digest, Sha256};
 
+use crate::config::XurlConfig;
 use crate::error::{Result, XurlError};
-use crate::model::{ProviderKind, ResolvedThread, WriteRequest, WriteResult};
+use crate::model::{
+    ProviderKind, ResolvedBin, ResolvedThread, WriteCommand, WriteOptions, WriteRequest,
+    WriteResult,
+};
+use crate::scan::ScanOptions;
 
 pub mod amp;
+pub(crate) mod budget;
 pub mod claude;
 pub mod codex;
 pub mod gemini;
 pub mod opencode;
 pub mod pi;
+pub(crate) mod process;
 pub mod skills;
 
+/// Drops candidates whose bytes match an earlier candidate, keeping the first of each, and
+/// returns how many were dropped. Copies of one session in several scanned roots, like an
+/// rsync backup, are then not reported as conflicting matches.
+pub(crate) fn skip_identical_candidates(paths: Vec<PathBuf>) -> (Vec<PathBuf>, usize) {
+    if paths.len() < 2 {
+        return (paths, 0);
+    }
+    let total = paths.len();
+    let mut seen = HashSet::new();
+    let kept = paths
+        .into_iter()
+        .filter(|path| content_digest(path).is_none_or(|digest| seen.insert(digest)))
+        .collect::<Vec<_>>();
+    let skipped = total - kept.len();
+    (kept, skipped)
+}
+
+fn content_digest(path: &Path) -> Option<(u64, Vec<u8>)> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut hasher = Sha256::new();
+    let len = io::copy(&mut file, &mut hasher).ok()?;
+    Some((len, hasher.finalize().to_vec()))
+}
+
 pub(crate) fn append_passthrough_args(args: &mut Vec<String>, params: &[(String, Option<String>)]) {
     append_passthrough_args_excluding(args, params, &[]);
 }
@@ -39,6 +73,120 @@ pub(crate) fn append_passthrough_args_excluding(
     excluded
 }
 
+/// Appends `flag <model>` when a normalized model was requested.
+pub(crate) fn append_model_arg(args: &mut Vec<String>, options: &WriteOptions, flag: &str) {
+    if let Some(model) = options.model.as_deref() {
+        args.push(flag.to_string());
+        args.push(model.to_string());
+    }
+}
+
+/// The result of a dry run: the command that would have been spawned.
+pub(crate) fn dry_run_result(
+    provider: ProviderKind,
+    session_id: Option<String>,
+    command: WriteCommand,
+    warnings: Vec<String>,
+) -> WriteResult {
+    WriteResult {
+        provider,
+        session_id: session_id.unwrap_or_default(),
+        final_text: None,
+        warnings,
+        truncated: false,
+        dry_run: Some(command),
+        exit_code: None,
+        duration: Duration::ZERO,
+        bytes_streamed: 0,
+    }
+}
+
+/// Warns that `options.approval` is dropped, and why `provider` cannot honor it.
+pub(crate) fn push_unsupported_approval_warning(
+    warnings: &mut Vec<String>,
+    options: &WriteOptions,
+    provider: ProviderKind,
+    reason: &str,
+) {
+    if let Some(approval) = options.approval {
+        warnings.push(format!(
+            "ignored approval={approval}: provider `{provider}` {reason}"
+        ));
+    }
+}
+
+/// Warns that `options.system` is dropped because `provider` has no system prompt option.
+pub(crate) fn push_unsupported_system_warning(
+    warnings: &mut Vec<String>,
+    options: &WriteOptions,
+    provider: ProviderKind,
+) {
+    if options.system.is_some() {
+        warnings.push(format!(
+            "ignored system prompt: provider `{provider}` has no system prompt option"
+        ));
+    }
+}
+
+/// The command-line tool a provider writes through, with setup guidance shared by error hints
+/// and `xurl doctor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProviderCli {
+    /// Display name like `Codex CLI`.
+    pub name: &'static str,
+    /// How to check the install and log in, like "run `codex --version`, ...".
+    pub setup: &'static str,
+    /// What to do when the CLI runs but fails, like "verify authentication with ...".
+    pub auth: &'static str,
+}
+
+impl ProviderCli {
+    pub fn of(provider: ProviderKind) -> Self {
+        match provider {
+            ProviderKind::Amp => Self {
+                name: "Amp CLI",
+                setup: "run `amp --version`, install Amp CLI if missing, then run `amp login`",
+                auth: "verify authentication with `amp login` and retry",
+            },
+            ProviderKind::Codex => Self {
+                name: "Codex CLI",
+                setup: "run `codex --version`, install Codex CLI if missing, then run `codex login`",
+                auth: "verify authentication with `codex login` and retry",
+            },
+            ProviderKind::Claude => Self {
+                name: "Claude CLI",
+                setup: "run `claude --version`, install Claude Code if missing, then authenticate",
+                auth: "verify authentication with `claude auth` (or your configured login flow) and retry",
+            },
+            ProviderKind::Gemini => Self {
+                name: "Gemini CLI",
+                setup: "run `gemini --version`, install Gemini CLI if missing, then authenticate",
+                auth: "verify Gemini authentication/configuration and retry the command directly once",
+            },
+            ProviderKind::Pi => Self {
+                name: "pi CLI",
+                setup: "run `pi --version`, install pi if missing, then configure provider credentials",
+                auth: "verify pi provider/model credentials and retry with `pi -p \"hello\" --mode json`",
+            },
+            ProviderKind::Opencode => Self {
+                name: "OpenCode CLI",
+                setup: "run `opencode --version`, install OpenCode if missing, then configure providers/models",
+                auth: "verify OpenCode provider/model configuration and retry with `opencode run \"hello\" --format json`",
+            },
+        }
+    }
+
+    /// Binary that write mode runs, honoring `XURL_<PROVIDER>_BIN` and `[bins]` overrides.
+    pub fn bin(provider: ProviderKind) -> String {
+        Self::resolve_bin(provider).path
+    }
+
+    /// Same as [`ProviderCli::bin`], with the setting the binary came from.
+    pub fn resolve_bin(provider: ProviderKind) -> ResolvedBin {
+        XurlConfig::resolve_bin(&provider.to_string())
+    }
+}
+
 pub trait WriteEventSink {
     fn on_session_ready(&mut self, provider: ProviderKind, session_id: &str) -> Result<()>;
     fn on_text_delta(&mut self, text: &str) -> Result<()>;
@@ -56,6 +204,7 @@ pub trait Provider {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ProviderRoots {
     pub amp_root: PathBuf,
+    pub amp_cache_root: PathBuf,
     pub codex_root: PathBuf,
     pub claude_root: PathBuf,
     pub gemini_root: PathBuf,
@@ -63,6 +212,12 @@ pub struct ProviderRoots {
     pub opencode_root: PathBuf,
     pub skills_root: PathBuf,
     pub skills_cache_root: PathBuf,
+    pub snapshots_root: PathBuf,
+    /// Bounds on the walks that look for thread files under the roots; unbounded by default.
+    pub scan: ScanOptions,
+    /// Fail resolving a thread with parse warnings: lines that are not valid JSON, record types
+    /// the readers skip, or a format newer than xurl knows.
+    pub strict: bool,
 }
 
 impl ProviderRoots {
@@ -117,6 +272,14 @@ impl ProviderRoots {
             .map(|path| path.join("opencode"))
             .unwrap_or_else(|| home.join(".local/share/opencode"));
 
+        // Precedence:
+        // 1) XURL_AMP_CACHE_ROOT
+        // 2) ~/.xurl/amp
+        let amp_cache_root = env::var_os("XURL_AMP_CACHE_ROOT")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".xurl/amp"));
+
         // Precedence:
         // 1) XURL_SKILLS_ROOT
         // 2) ~/.agents/skills
@@ -133,8 +296,17 @@ impl ProviderRoots {
             .map(PathBuf::from)
             .unwrap_or_else(|| home.join(".xurl/skills"));
 
+        // Precedence:
+        // 1) XURL_SNAPSHOTS_ROOT
+        // 2) ~/.xurl/snapshots
+        let snapshots_root = env::var_os("XURL_SNAPSHOTS_ROOT")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".xurl/snapshots"));
+
         Ok(Self {
             amp_root,
+            amp_cache_root,
             codex_root,
             claude_root,
             gemini_root,
@@ -142,6 +314,9 @@ impl ProviderRoots {
             opencode_root,
             skills_root,
             skills_cache_root,
+            snapshots_root,
+            scan: ScanOptions::default(),
+            strict: false,
         })
     }
 }
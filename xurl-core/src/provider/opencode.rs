@@ -1,19 +1,30 @@
 use std::collections::HashMap;
 use std::fs;
-use std::hash::{Hash, Hasher};
 use std::io::{BufRead, BufReader, Read};
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use rusqlite::{Connection, OpenFlags};
 use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use tracing::debug;
 
+use crate::config::XurlConfig;
 use crate::error::{Result, XurlError};
-use crate::model::{ProviderKind, ResolutionMeta, ResolvedThread, WriteRequest, WriteResult};
+use crate::model::{
+    ProviderKind, ResolutionMeta, ResolvedThread, WriteCommand, WriteRequest, WriteResult,
+};
+use crate::provider::budget::WriteBudget;
+use crate::provider::process::{SpawnedWrite, start_write_command};
 use crate::provider::{
-    Provider, WriteEventSink, append_passthrough_args, append_passthrough_args_excluding,
+    Provider, WriteEventSink, append_model_arg, append_passthrough_args,
+    append_passthrough_args_excluding, dry_run_result, push_unsupported_approval_warning,
+    push_unsupported_system_warning,
 };
 
+/// Messages of a session in order, and the parts of each message keyed by message id.
+type SessionRows = (Vec<(String, Value)>, HashMap<String, Vec<Value>>);
+
 #[derive(Debug, Clone)]
 pub struct OpencodeProvider {
     root: PathBuf,
@@ -28,13 +39,16 @@ impl OpencodeProvider {
         self.root.join("opencode.db")
     }
 
+    /// Where the JSONL export of `session_id` is kept, keyed by a hash of the root so that
+    /// every xurl build and process agrees on it.
     fn materialized_path(&self, session_id: &str) -> PathBuf {
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        self.root.hash(&mut hasher);
-        let root_key = format!("{:016x}", hasher.finish());
+        let digest = Sha256::digest(self.root.as_os_str().as_encoded_bytes());
+        let root_key = digest[..8]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
 
-        std::env::temp_dir()
-            .join("xurl-opencode")
+        materialized_root()
             .join(root_key)
             .join(format!("{session_id}.jsonl"))
     }
@@ -147,28 +161,56 @@ impl OpencodeProvider {
         output
     }
 
-    fn opencode_bin() -> String {
-        std::env::var("XURL_OPENCODE_BIN").unwrap_or_else(|_| "opencode".to_string())
+    /// Messages and parts of `session_id` from `opencode.db`; `None` when the database or
+    /// the session row is missing.
+    fn read_db(&self, session_id: &str, warnings: &mut Vec<String>) -> Result<Option<SessionRows>> {
+        let db_path = self.db_path();
+        if !db_path.exists() {
+            debug!("opencode database does not exist");
+            return Ok(None);
+        }
+        let sqlite_err = |source| XurlError::Sqlite {
+            path: db_path.clone(),
+            source,
+        };
+
+        let conn = Connection::open_with_flags(&db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(sqlite_err)?;
+        if !Self::session_exists(&conn, session_id).map_err(sqlite_err)? {
+            return Ok(None);
+        }
+        let messages = Self::fetch_messages(&conn, session_id, warnings).map_err(sqlite_err)?;
+        let parts = Self::fetch_parts(&conn, session_id, warnings).map_err(sqlite_err)?;
+        Ok(Some((messages, parts)))
+    }
+
+    fn storage_root(&self) -> PathBuf {
+        self.root.join("storage")
     }
 
-    fn spawn_opencode_command(args: &[String]) -> Result<std::process::Child> {
-        let bin = Self::opencode_bin();
-        let mut command = Command::new(&bin);
-        command
-            .args(args)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-        command.spawn().map_err(|source| {
-            if source.kind() == std::io::ErrorKind::NotFound {
-                XurlError::CommandNotFound { command: bin }
-            } else {
-                XurlError::Io {
-                    path: PathBuf::from(bin),
-                    source,
-                }
+    /// Messages and parts of `session_id` from the JSON files newer OpenCode versions keep
+    /// under `storage/`: `message/<session_id>/<message_id>.json` and
+    /// `part/<message_id>/<part_id>.json`. Both are empty when the session has no files.
+    fn read_storage(&self, session_id: &str, warnings: &mut Vec<String>) -> SessionRows {
+        let storage = self.storage_root();
+        let mut messages = read_json_dir(&storage.join("message").join(session_id), warnings);
+        messages.sort_by_key(|(id, message)| (message_created(message), id.clone()));
+
+        let mut parts = HashMap::new();
+        for (id, _) in &messages {
+            let message_parts = read_json_dir(&storage.join("part").join(id), warnings);
+            if !message_parts.is_empty() {
+                parts.insert(
+                    id.clone(),
+                    message_parts.into_iter().map(|(_, part)| part).collect(),
+                );
             }
-        })
+        }
+        (messages, parts)
+    }
+
+    pub(crate) fn opencode_bin() -> String {
+        XurlConfig::resolve_bin("opencode").path
     }
 
     fn collect_text(value: Option<&Value>) -> String {
@@ -262,19 +304,28 @@ impl OpencodeProvider {
         sink: &mut dyn WriteEventSink,
         warnings: Vec<String>,
     ) -> Result<WriteResult> {
-        let mut child = Self::spawn_opencode_command(args)?;
-        let stdout = child.stdout.take().ok_or_else(|| {
-            XurlError::WriteProtocol("opencode stdout pipe is unavailable".to_string())
-        })?;
-        let stderr = child.stderr.take().ok_or_else(|| {
-            XurlError::WriteProtocol("opencode stderr pipe is unavailable".to_string())
-        })?;
+        let command = WriteCommand::new(Self::opencode_bin(), args);
+        if req.options.dry_run {
+            return Ok(dry_run_result(
+                ProviderKind::Opencode,
+                req.session_id.clone(),
+                command,
+                warnings,
+            ));
+        }
+        let SpawnedWrite {
+            process,
+            stdout,
+            stderr,
+        } = start_write_command(ProviderKind::Opencode, &command)?;
         let stderr_handle = std::thread::spawn(move || {
             let mut reader = BufReader::new(stderr);
             let mut content = String::new();
             let _ = reader.read_to_string(&mut content);
             content
         });
+        let mut budget = WriteBudget::start(process, &req.options);
+        let sink = &mut budget.sink(sink);
 
         let stream_path = PathBuf::from("<opencode:stdout>");
         let mut session_id = req.session_id.clone();
@@ -337,12 +388,12 @@ impl OpencodeProvider {
             }
         }
 
-        let status = child.wait().map_err(|source| XurlError::Io {
+        let status = budget.wait().map_err(|source| XurlError::Io {
             path: PathBuf::from(Self::opencode_bin()),
             source,
         })?;
         let stderr_content = stderr_handle.join().unwrap_or_default();
-        if !status.success() {
+        if !status.success() && !budget.stopped() {
             return Err(XurlError::CommandFailed {
                 command: format!("{} {}", Self::opencode_bin(), args.join(" ")),
                 code: status.code(),
@@ -370,12 +421,19 @@ impl OpencodeProvider {
             ));
         };
 
-        Ok(WriteResult {
+        let mut result = WriteResult {
             provider: ProviderKind::Opencode,
             session_id,
             final_text,
             warnings,
-        })
+            truncated: false,
+            dry_run: None,
+            exit_code: status.code(),
+            duration: Duration::ZERO,
+            bytes_streamed: 0,
+        };
+        budget.finish(&mut result);
+        Ok(result)
     }
 }
 
@@ -386,46 +444,44 @@ impl Provider for OpencodeProvider {
 
     fn resolve(&self, session_id: &str) -> Result<ResolvedThread> {
         let db_path = self.db_path();
-        if !db_path.exists() {
-            return Err(XurlError::ThreadNotFound {
-                provider: ProviderKind::Opencode.to_string(),
-                session_id: session_id.to_string(),
-                searched_roots: vec![db_path],
-            });
-        }
-
-        let conn = Connection::open_with_flags(&db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
-            .map_err(|source| XurlError::Sqlite {
-                path: db_path.clone(),
-                source,
-            })?;
-
-        if !Self::session_exists(&conn, session_id).map_err(|source| XurlError::Sqlite {
-            path: db_path.clone(),
-            source,
-        })? {
-            return Err(XurlError::ThreadNotFound {
-                provider: ProviderKind::Opencode.to_string(),
-                session_id: session_id.to_string(),
-                searched_roots: vec![db_path],
-            });
-        }
-
+        debug!(db = %db_path.display(), "resolving opencode thread {session_id}");
         let mut warnings = Vec::new();
-        let messages =
-            Self::fetch_messages(&conn, session_id, &mut warnings).map_err(|source| {
-                XurlError::Sqlite {
-                    path: db_path.clone(),
-                    source,
+        let db_rows = self.read_db(session_id, &mut warnings)?;
+        let (storage_messages, storage_parts) = self.read_storage(session_id, &mut warnings);
+        // Every warning so far is a message or part whose payload failed to parse.
+        let corrupt_lines = warnings.len();
+
+        let (messages, parts, source) = match db_rows {
+            Some((mut messages, mut parts)) => {
+                let filled =
+                    merge_storage_rows(&mut messages, &mut parts, storage_messages, storage_parts);
+                if filled > 0 {
+                    warnings.push(format!(
+                        "opencode.db lacks {filled} messages or parts of this session; filled them in from {}",
+                        self.storage_root().display()
+                    ));
                 }
-            })?;
-        let parts = Self::fetch_parts(&conn, session_id, &mut warnings).map_err(|source| {
-            XurlError::Sqlite {
-                path: db_path.clone(),
-                source,
+                (messages, parts, "opencode:sqlite")
             }
-        })?;
+            None if storage_messages.is_empty() => {
+                return Err(XurlError::ThreadNotFound {
+                    provider: ProviderKind::Opencode.to_string(),
+                    session_id: session_id.to_string(),
+                    searched_roots: vec![
+                        db_path,
+                        self.storage_root().join("message").join(session_id),
+                    ],
+                });
+            }
+            None => (storage_messages, storage_parts, "opencode:storage"),
+        };
 
+        debug!(
+            messages = messages.len(),
+            parts = parts.len(),
+            source,
+            "opencode rows loaded"
+        );
         let raw = Self::render_jsonl(session_id, messages, parts);
         let path = self.materialized_path(session_id);
 
@@ -436,25 +492,32 @@ impl Provider for OpencodeProvider {
             })?;
         }
 
-        fs::write(&path, raw).map_err(|source| XurlError::Io {
-            path: path.clone(),
-            source,
-        })?;
+        write_materialized(&path, &raw)?;
 
         Ok(ResolvedThread {
             provider: ProviderKind::Opencode,
             session_id: session_id.to_string(),
             path,
             metadata: ResolutionMeta {
-                source: "opencode:sqlite".to_string(),
+                source: source.to_string(),
                 candidate_count: 1,
+                corrupt_lines,
                 warnings,
+                duplicates_skipped: 0,
+                elapsed: Duration::ZERO,
             },
         })
     }
 
     fn write(&self, req: &WriteRequest, sink: &mut dyn WriteEventSink) -> Result<WriteResult> {
         let mut warnings = Vec::new();
+        push_unsupported_system_warning(&mut warnings, &req.options, ProviderKind::Opencode);
+        push_unsupported_approval_warning(
+            &mut warnings,
+            &req.options,
+            ProviderKind::Opencode,
+            "takes tool permissions from its `permission` config",
+        );
         let mut args = vec!["run".to_string(), req.prompt.clone()];
         if let Some(session_id) = req.session_id.as_deref() {
             args.push("--session".to_string());
@@ -479,12 +542,126 @@ impl Provider for OpencodeProvider {
         } else {
             append_passthrough_args(&mut args, &req.options.params);
         }
+        append_model_arg(&mut args, &req.options, "-m");
         self.run_write(&args, req, sink, warnings)
     }
 }
 
+/// Adds the messages, and the parts of messages, that `storage/` has beyond the database rows
+/// of a session, as when OpenCode wrote parts only as files or the database copy was cut
+/// short. Returns how many messages and parts were added.
+fn merge_storage_rows(
+    messages: &mut Vec<(String, Value)>,
+    parts: &mut HashMap<String, Vec<Value>>,
+    storage_messages: Vec<(String, Value)>,
+    mut storage_parts: HashMap<String, Vec<Value>>,
+) -> usize {
+    let mut filled = 0;
+    let mut added_messages = false;
+    for (id, message) in storage_messages {
+        if let Some(extra) = storage_parts.remove(&id) {
+            let existing = parts.entry(id.clone()).or_default();
+            if extra.len() > existing.len() {
+                filled += extra.len() - existing.len();
+                *existing = extra;
+            }
+        }
+        if !messages.iter().any(|(known, _)| *known == id) {
+            messages.push((id, message));
+            filled += 1;
+            added_messages = true;
+        }
+    }
+    if added_messages {
+        messages.sort_by_key(|(id, message)| (message_created(message), id.clone()));
+    }
+    filled
+}
+
+/// `time.created` of a message; messages without one sort last.
+fn message_created(message: &Value) -> i64 {
+    message
+        .pointer("/time/created")
+        .and_then(Value::as_i64)
+        .unwrap_or(i64::MAX)
+}
+
+/// The `*.json` files of `dir` by file name, each keyed by its file stem.
+fn read_json_dir(dir: &Path, warnings: &mut Vec<String>) -> Vec<(String, Value)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files = entries
+        .filter_map(std::result::Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let mut values = Vec::with_capacity(files.len());
+    for path in files {
+        let Some(id) = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+        let parsed = fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|raw| serde_json::from_str::<Value>(&raw).map_err(|err| err.to_string()));
+        match parsed {
+            Ok(value) => values.push((id, value)),
+            Err(err) => warnings.push(format!(
+                "skipped {}: invalid json payload ({err})",
+                path.display()
+            )),
+        }
+    }
+    values
+}
+
+/// Directory holding the JSONL exports materialized from every OpenCode root.
+pub(crate) fn materialized_root() -> PathBuf {
+    std::env::temp_dir().join("xurl-opencode")
+}
+
+/// Replaces the export at `path` with `raw` unless it already holds the same content.
+///
+/// Other xurl processes may be reading or refreshing the same export. Writers take turns on
+/// `<path>.lock`, and the new content is written to a temp file renamed over `path`, so a
+/// reader sees either the old export or the new one, never a partial write.
+fn write_materialized(path: &Path, raw: &str) -> Result<()> {
+    let io_err = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| XurlError::Io { path, source }
+    };
+    let lock_path = path.with_extension("jsonl.lock");
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(io_err(&lock_path))?;
+    lock.lock().map_err(io_err(&lock_path))?;
+
+    let unchanged = fs::read(path)
+        .is_ok_and(|existing| Sha256::digest(existing) == Sha256::digest(raw.as_bytes()));
+    if unchanged {
+        return Ok(());
+    }
+    let temp_path = path.with_extension(format!("jsonl.{}.tmp", std::process::id()));
+    let written = fs::write(&temp_path, raw)
+        .and_then(|()| fs::rename(&temp_path, path))
+        .map_err(io_err(path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::fs;
     use std::path::Path;
 
@@ -599,6 +776,105 @@ mod tests {
         assert!(format!("{err}").contains("thread not found"));
     }
 
+    fn write_storage_json(root: &Path, relative: &str, body: &str) {
+        let path = root.join("storage").join(relative);
+        fs::create_dir_all(path.parent().expect("parent")).expect("mkdir");
+        fs::write(path, body).expect("write");
+    }
+
+    #[test]
+    fn storage_files_fill_in_what_the_db_lacks() {
+        let temp = tempdir().expect("tempdir");
+        let session_id = "ses_43a90e3adffejRgrTdlJa48CtE";
+        write_storage_json(
+            temp.path(),
+            &format!("message/{session_id}/msg_1.json"),
+            r#"{"id":"msg_1","role":"user","time":{"created":1}}"#,
+        );
+        write_storage_json(
+            temp.path(),
+            &format!("message/{session_id}/msg_2.json"),
+            r#"{"id":"msg_2","role":"assistant","time":{"created":2}}"#,
+        );
+        write_storage_json(
+            temp.path(),
+            "part/msg_1/prt_1.json",
+            r#"{"type":"text","text":"hello"}"#,
+        );
+        write_storage_json(
+            temp.path(),
+            "part/msg_2/prt_2.json",
+            r#"{"type":"text","text":"from storage"}"#,
+        );
+        write_storage_json(temp.path(), "part/msg_2/prt_3.json", "{not json");
+
+        let provider = OpencodeProvider::new(temp.path());
+        let resolved = provider.resolve(session_id).expect("resolve from storage");
+        assert_eq!(resolved.metadata.source, "opencode:storage");
+        assert_eq!(resolved.metadata.corrupt_lines, 1);
+        let raw = fs::read_to_string(&resolved.path).expect("read materialized");
+        assert!(raw.contains(r#""text":"hello""#));
+        assert!(raw.contains(r#""text":"from storage""#));
+
+        // The database has the session and its first message, but lost the reply.
+        let conn = prepare_db(&temp.path().join("opencode.db"));
+        conn.execute("INSERT INTO session (id) VALUES (?1)", [session_id])
+            .expect("insert session");
+        conn.execute(
+            "INSERT INTO message (id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                "msg_1",
+                session_id,
+                1_i64,
+                r#"{"role":"user","time":{"created":1}}"#
+            ],
+        )
+        .expect("insert user");
+
+        let resolved = provider.resolve(session_id).expect("resolve merged");
+        assert_eq!(resolved.metadata.source, "opencode:sqlite");
+        assert!(
+            resolved.metadata.warnings.iter().any(|warning| warning
+                .starts_with("opencode.db lacks 3 messages or parts of this session")),
+            "{:?}",
+            resolved.metadata.warnings
+        );
+        let raw = fs::read_to_string(&resolved.path).expect("read materialized");
+        let hello = raw.find(r#""text":"hello""#).expect("user part");
+        let reply = raw.find(r#""text":"from storage""#).expect("reply part");
+        assert!(hello < reply);
+    }
+
+    #[test]
+    fn concurrent_resolves_share_one_complete_export() {
+        let temp = tempdir().expect("tempdir");
+        let conn = prepare_db(&temp.path().join("opencode.db"));
+        let session_id = "ses_43a90e3adffejRgrTdlJa48CtE";
+        conn.execute("INSERT INTO session (id) VALUES (?1)", [session_id])
+            .expect("insert session");
+
+        let provider = OpencodeProvider::new(temp.path());
+        let expected = OpencodeProvider::render_jsonl(session_id, Vec::new(), HashMap::new());
+        let paths = std::thread::scope(|scope| {
+            let handles = (0..8)
+                .map(|_| scope.spawn(|| provider.resolve(session_id).expect("resolve").path))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("join"))
+                .collect::<Vec<_>>()
+        });
+
+        assert!(paths.iter().all(|path| *path == paths[0]));
+        assert_eq!(fs::read_to_string(&paths[0]).expect("read"), expected);
+        let leftovers = fs::read_dir(paths[0].parent().expect("parent"))
+            .expect("read dir")
+            .filter_map(std::result::Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
     #[test]
     fn materialized_paths_are_isolated_by_root() {
         let first_root = tempdir().expect("first tempdir");
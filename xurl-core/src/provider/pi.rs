@@ -2,25 +2,46 @@ use std::cmp::Reverse;
 use std::fs;
 use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use serde_json::Value;
-use walkdir::WalkDir;
+use tracing::debug;
 
+use crate::config::XurlConfig;
 use crate::error::{Result, XurlError};
 use crate::jsonl;
-use crate::model::{ProviderKind, ResolutionMeta, ResolvedThread, WriteRequest, WriteResult};
-use crate::provider::{Provider, WriteEventSink, append_passthrough_args};
+use crate::model::{
+    ApprovalMode, ProviderKind, ResolutionMeta, ResolvedThread, WriteCommand, WriteOptions,
+    WriteRequest, WriteResult,
+};
+use crate::provider::budget::WriteBudget;
+use crate::provider::process::{SpawnedWrite, start_write_command};
+use crate::provider::{
+    Provider, WriteEventSink, append_model_arg, append_passthrough_args, dry_run_result,
+    push_unsupported_approval_warning, push_unsupported_system_warning, skip_identical_candidates,
+};
+use crate::scan::{ScanOptions, scan_files};
+use crate::uri::is_uuid_session_id;
 
 #[derive(Debug, Clone)]
 pub struct PiProvider {
     root: PathBuf,
+    scan: ScanOptions,
 }
 
 impl PiProvider {
     pub fn new(root: impl Into<PathBuf>) -> Self {
-        Self { root: root.into() }
+        Self {
+            root: root.into(),
+            scan: ScanOptions::default(),
+        }
+    }
+
+    /// Bounds the walks that look for thread files when no index has the session.
+    #[must_use]
+    pub fn with_scan_options(mut self, scan: &ScanOptions) -> Self {
+        self.scan = scan.clone();
+        self
     }
 
     fn sessions_root(&self) -> PathBuf {
@@ -54,15 +75,18 @@ impl PiProvider {
                 .is_some_and(|id| id.eq_ignore_ascii_case(session_id))
     }
 
-    fn find_candidates(sessions_root: &Path, session_id: &str) -> Vec<PathBuf> {
+    fn find_candidates(
+        sessions_root: &Path,
+        scan: &ScanOptions,
+        session_id: &str,
+        warnings: &mut Vec<String>,
+    ) -> Vec<PathBuf> {
         if !sessions_root.exists() {
             return Vec::new();
         }
 
-        WalkDir::new(sessions_root)
+        scan_files(sessions_root, scan, warnings)
             .into_iter()
-            .filter_map(std::result::Result::ok)
-            .filter(|entry| entry.file_type().is_file())
             .map(|entry| entry.into_path())
             .filter(|path| {
                 path.extension()
@@ -73,7 +97,9 @@ impl PiProvider {
             .collect()
     }
 
-    fn choose_latest(paths: Vec<PathBuf>) -> Option<(PathBuf, usize)> {
+    /// The newest candidate, how many distinct candidates there were, and how many
+    /// byte-identical copies were skipped.
+    fn choose_latest(paths: Vec<PathBuf>) -> Option<(PathBuf, usize, usize)> {
         if paths.is_empty() {
             return None;
         }
@@ -88,32 +114,53 @@ impl PiProvider {
             })
             .collect::<Vec<_>>();
         scored.sort_by_key(|(_, modified)| Reverse(*modified));
-        let count = scored.len();
-        scored.into_iter().next().map(|(path, _)| (path, count))
+        let (paths, duplicates_skipped) =
+            skip_identical_candidates(scored.into_iter().map(|(path, _)| path).collect());
+        let count = paths.len();
+        paths
+            .into_iter()
+            .next()
+            .map(|path| (path, count, duplicates_skipped))
     }
 
-    fn pi_bin() -> String {
-        std::env::var("XURL_PI_BIN").unwrap_or_else(|_| "pi".to_string())
+    pub(crate) fn pi_bin() -> String {
+        XurlConfig::resolve_bin("pi").path
     }
 
-    fn spawn_pi_command(args: &[String]) -> Result<std::process::Child> {
-        let bin = Self::pi_bin();
-        let mut command = Command::new(&bin);
-        command
-            .args(args)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-        command.spawn().map_err(|source| {
-            if source.kind() == std::io::ErrorKind::NotFound {
-                XurlError::CommandNotFound { command: bin }
-            } else {
-                XurlError::Io {
-                    path: PathBuf::from(bin),
-                    source,
-                }
+    /// pi appends new turns after the last entry in a session file, so an entry id is only a
+    /// valid write target when it is that leaf; other branches live in their own child sessions.
+    fn ensure_latest_entry(path: &Path, session_id: &str, entry_id: &str) -> Result<()> {
+        let file = fs::File::open(path).map_err(|source| XurlError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut found = false;
+        let mut latest = None::<String>;
+        jsonl::parse_jsonl_reader(path, BufReader::new(file), |_, value| {
+            if value.get("type").and_then(Value::as_str) == Some("session") {
+                return Ok(());
             }
-        })
+            if let Some(id) = value.get("id").and_then(Value::as_str) {
+                found |= id.eq_ignore_ascii_case(entry_id);
+                latest = Some(id.to_ascii_lowercase());
+            }
+            Ok(())
+        })?;
+
+        if !found {
+            return Err(XurlError::EntryNotFound {
+                provider: ProviderKind::Pi.to_string(),
+                session_id: session_id.to_string(),
+                entry_id: entry_id.to_string(),
+            });
+        }
+        if latest.as_deref() != Some(entry_id.to_ascii_lowercase().as_str()) {
+            return Err(XurlError::InvalidMode(format!(
+                "pi continues from the latest entry of a session; entry_id={entry_id} is not the latest entry ({}); write to the branch's child session URI instead",
+                latest.unwrap_or_default()
+            )));
+        }
+        Ok(())
     }
 
     fn extract_assistant_text(message: &Value) -> Option<String> {
@@ -146,27 +193,35 @@ impl PiProvider {
     fn run_write(
         &self,
         args: &[String],
-        req: &WriteRequest,
+        target_session_id: Option<String>,
+        options: &WriteOptions,
         sink: &mut dyn WriteEventSink,
         warnings: Vec<String>,
     ) -> Result<WriteResult> {
-        let mut child = Self::spawn_pi_command(args)?;
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or_else(|| XurlError::WriteProtocol("pi stdout pipe is unavailable".to_string()))?;
-        let stderr = child
-            .stderr
-            .take()
-            .ok_or_else(|| XurlError::WriteProtocol("pi stderr pipe is unavailable".to_string()))?;
+        let command = WriteCommand::new(Self::pi_bin(), args);
+        if options.dry_run {
+            return Ok(dry_run_result(
+                ProviderKind::Pi,
+                target_session_id.clone(),
+                command,
+                warnings,
+            ));
+        }
+        let SpawnedWrite {
+            process,
+            stdout,
+            stderr,
+        } = start_write_command(ProviderKind::Pi, &command)?;
         let stderr_handle = std::thread::spawn(move || {
             let mut reader = BufReader::new(stderr);
             let mut content = String::new();
             let _ = reader.read_to_string(&mut content);
             content
         });
+        let mut budget = WriteBudget::start(process, options);
+        let sink = &mut budget.sink(sink);
 
-        let mut session_id = req.session_id.clone();
+        let mut session_id = target_session_id;
         let mut final_text = None::<String>;
         let mut streamed_text = String::new();
         let mut streamed_delta = false;
@@ -224,12 +279,12 @@ impl PiProvider {
             Ok(())
         })?;
 
-        let status = child.wait().map_err(|source| XurlError::Io {
+        let status = budget.wait().map_err(|source| XurlError::Io {
             path: PathBuf::from(Self::pi_bin()),
             source,
         })?;
         let stderr_content = stderr_handle.join().unwrap_or_default();
-        if !status.success() {
+        if !status.success() && !budget.stopped() {
             return Err(XurlError::CommandFailed {
                 command: format!("{} {}", Self::pi_bin(), args.join(" ")),
                 code: status.code(),
@@ -245,12 +300,19 @@ impl PiProvider {
             ));
         };
 
-        Ok(WriteResult {
+        let mut result = WriteResult {
             provider: ProviderKind::Pi,
             session_id,
             final_text,
             warnings,
-        })
+            truncated: false,
+            dry_run: None,
+            exit_code: status.code(),
+            duration: Duration::ZERO,
+            bytes_streamed: 0,
+        };
+        budget.finish(&mut result);
+        Ok(result)
     }
 }
 
@@ -261,13 +323,23 @@ impl Provider for PiProvider {
 
     fn resolve(&self, session_id: &str) -> Result<ResolvedThread> {
         let sessions_root = self.sessions_root();
-        let candidates = Self::find_candidates(&sessions_root, session_id);
+        let mut warnings = Vec::new();
+        let candidates =
+            Self::find_candidates(&sessions_root, &self.scan, session_id, &mut warnings);
+        debug!(
+            root = %sessions_root.display(),
+            candidates = candidates.len(),
+            "pi scan finished for {session_id}"
+        );
 
-        if let Some((selected, count)) = Self::choose_latest(candidates) {
+        if let Some((selected, count, duplicates_skipped)) = Self::choose_latest(candidates) {
             let mut metadata = ResolutionMeta {
                 source: "pi:sessions".to_string(),
                 candidate_count: count,
-                warnings: Vec::new(),
+                warnings,
+                corrupt_lines: 0,
+                duplicates_skipped,
+                elapsed: Duration::ZERO,
             };
 
             if count > 1 {
@@ -299,9 +371,34 @@ impl Provider for PiProvider {
                 ProviderKind::Pi
             )));
         }
-        let warnings = Vec::new();
+        let mut warnings = Vec::new();
+        push_unsupported_system_warning(&mut warnings, &req.options, ProviderKind::Pi);
+        if req.options.approval != Some(ApprovalMode::Auto) {
+            push_unsupported_approval_warning(
+                &mut warnings,
+                &req.options,
+                ProviderKind::Pi,
+                "runs every tool without asking",
+            );
+        }
         let mut args = Vec::new();
-        if let Some(session_id) = req.session_id.as_deref() {
+        let target_session_id = match (req.session_id.as_deref(), req.child_id.as_deref()) {
+            (Some(_), Some(child_id)) if is_uuid_session_id(child_id) => {
+                Some(child_id.to_ascii_lowercase())
+            }
+            (Some(session_id), Some(entry_id)) => {
+                let resolved = self.resolve(session_id)?;
+                Self::ensure_latest_entry(&resolved.path, session_id, entry_id)?;
+                Some(session_id.to_string())
+            }
+            (session_id, None) => session_id.map(str::to_string),
+            (None, Some(_)) => {
+                return Err(XurlError::InvalidMode(
+                    "pi child write target requires a main session id".to_string(),
+                ));
+            }
+        };
+        if let Some(session_id) = target_session_id.as_deref() {
             let resolved = self.resolve(session_id)?;
             let session_path = resolved.path.to_string_lossy().to_string();
             args.push("--session".to_string());
@@ -317,7 +414,8 @@ impl Provider for PiProvider {
             args.push("json".to_string());
         }
         append_passthrough_args(&mut args, &req.options.params);
-        self.run_write(&args, req, sink, warnings)
+        append_model_arg(&mut args, &req.options, "--model");
+        self.run_write(&args, target_session_id, &req.options, sink, warnings)
     }
 }
 
@@ -384,6 +482,9 @@ mod tests {
             "2026-02-23T13-10-12-780Z_12cb4c19-2774-4de4-a0d0-9fa32fbae29f.jsonl",
             session_id,
         );
+        let mut content = fs::read_to_string(&second).expect("read");
+        content.push_str("{\"type\":\"message\",\"id\":\"e5f6a7b8\",\"parentId\":\"a1b2c3d4\",\"timestamp\":\"2026-02-23T13:10:13.000Z\",\"message\":{\"role\":\"user\",\"content\":[{\"type\":\"text\",\"text\":\"again\"}],\"timestamp\":1771852317843}}\n");
+        fs::write(&second, content).expect("write");
 
         let provider = PiProvider::new(temp.path());
         let resolved = provider
@@ -0,0 +1,188 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::debug;
+
+use crate::error::{Result, XurlError};
+use crate::model::{ProviderKind, WriteCommand};
+
+/// When set, the stdout of every provider write is copied to a new file in this directory.
+const RECORD_DIR_ENV: &str = "XURL_RECORD_DIR";
+/// When set, provider writes replay this recorded stdout instead of spawning the provider CLI.
+const REPLAY_FILE_ENV: &str = "XURL_REPLAY_FILE";
+
+/// A provider write in flight: a spawned CLI, or a recorded stream being replayed.
+pub(crate) enum WriteProcess {
+    Child(Child),
+    Replay,
+}
+
+impl WriteProcess {
+    pub(crate) fn kill(&mut self) -> io::Result<()> {
+        match self {
+            Self::Child(child) => child.kill(),
+            Self::Replay => Ok(()),
+        }
+    }
+
+    pub(crate) fn wait(&mut self) -> io::Result<ExitStatus> {
+        match self {
+            Self::Child(child) => child.wait(),
+            Self::Replay => Ok(success_status()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn success_status() -> ExitStatus {
+    std::os::unix::process::ExitStatusExt::from_raw(0)
+}
+
+#[cfg(windows)]
+fn success_status() -> ExitStatus {
+    std::os::windows::process::ExitStatusExt::from_raw(0)
+}
+
+pub(crate) struct SpawnedWrite {
+    pub(crate) process: WriteProcess,
+    pub(crate) stdout: Box<dyn Read + Send>,
+    pub(crate) stderr: Box<dyn Read + Send>,
+}
+
+/// Spawns `command` with stdin closed and stdout/stderr piped, or replays `XURL_REPLAY_FILE`
+/// in its place. Stdout is teed to `XURL_RECORD_DIR` when that is set.
+pub(crate) fn start_write_command(
+    provider: ProviderKind,
+    command: &WriteCommand,
+) -> Result<SpawnedWrite> {
+    let spawned = match env::var_os(REPLAY_FILE_ENV).filter(|path| !path.is_empty()) {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            debug!(path = %path.display(), "replaying recorded {provider} write");
+            let stdout = File::open(&path).map_err(|source| XurlError::Io { path, source })?;
+            SpawnedWrite {
+                process: WriteProcess::Replay,
+                stdout: Box::new(stdout),
+                stderr: Box::new(io::empty()),
+            }
+        }
+        None => spawn_write_command(provider, command)?,
+    };
+
+    match env::var_os(RECORD_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        Some(dir) => {
+            let dir = PathBuf::from(dir);
+            fs::create_dir_all(&dir).map_err(|source| XurlError::Io {
+                path: dir.clone(),
+                source,
+            })?;
+            let millis = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            let (path, file) = create_record_file(&dir, provider, millis)?;
+            debug!(path = %path.display(), "recording {provider} write");
+            Ok(SpawnedWrite {
+                stdout: Box::new(RecordingReader {
+                    inner: spawned.stdout,
+                    file,
+                }),
+                ..spawned
+            })
+        }
+        None => Ok(spawned),
+    }
+}
+
+/// Creates `<provider>-<millis>.jsonl` in `dir`, or `<provider>-<millis>-<n>.jsonl` when writes
+/// that started in the same millisecond already took the earlier names, never truncating one.
+fn create_record_file(dir: &Path, provider: ProviderKind, millis: u128) -> Result<(PathBuf, File)> {
+    for attempt in 0u32.. {
+        let name = match attempt {
+            0 => format!("{provider}-{millis}.jsonl"),
+            _ => format!("{provider}-{millis}-{attempt}.jsonl"),
+        };
+        let path = dir.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(source) if source.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(source) => return Err(XurlError::Io { path, source }),
+        }
+    }
+    unreachable!("record file attempts are unbounded")
+}
+
+fn spawn_write_command(
+    provider: ProviderKind,
+    write_command: &WriteCommand,
+) -> Result<SpawnedWrite> {
+    let bin = &write_command.program;
+    let mut command = Command::new(bin);
+    if let Some(cwd) = &write_command.cwd {
+        command.current_dir(cwd);
+    }
+    command
+        .args(&write_command.args)
+        .envs(write_command.env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = command.spawn().map_err(|source| {
+        if source.kind() == io::ErrorKind::NotFound {
+            XurlError::CommandNotFound {
+                command: bin.clone(),
+            }
+        } else {
+            XurlError::Io {
+                path: PathBuf::from(bin),
+                source,
+            }
+        }
+    })?;
+    let stdout = child.stdout.take().ok_or_else(|| {
+        XurlError::WriteProtocol(format!("{provider} stdout pipe is unavailable"))
+    })?;
+    let stderr = child.stderr.take().ok_or_else(|| {
+        XurlError::WriteProtocol(format!("{provider} stderr pipe is unavailable"))
+    })?;
+    Ok(SpawnedWrite {
+        process: WriteProcess::Child(child),
+        stdout: Box::new(stdout),
+        stderr: Box::new(stderr),
+    })
+}
+
+/// Copies everything read from `inner` into the record file.
+struct RecordingReader {
+    inner: Box<dyn Read + Send>,
+    file: File,
+}
+
+impl Read for RecordingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.file.write_all(&buf[..read])?;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::create_record_file;
+    use crate::model::ProviderKind;
+
+    #[test]
+    fn record_files_from_the_same_millisecond_get_distinct_names() {
+        let temp = tempdir().expect("tempdir");
+        let (first, _) = create_record_file(temp.path(), ProviderKind::Codex, 42).expect("first");
+        let (second, _) = create_record_file(temp.path(), ProviderKind::Codex, 42).expect("second");
+        assert_eq!(first, temp.path().join("codex-42.jsonl"));
+        assert_eq!(second, temp.path().join("codex-42-1.jsonl"));
+    }
+}
@@ -1,11 +1,11 @@
 use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 use walkdir::WalkDir;
 
 use crate::error::{Result, XurlError};
+use crate::git::run_git;
 use crate::model::{ResolvedSkill, SkillResolutionMeta, SkillsSourceKind};
 use crate::uri::SkillsUri;
 
@@ -222,43 +222,6 @@ fn read_skill_file(path: &Path) -> Result<String> {
     })
 }
 
-fn run_git<const N: usize>(args: [&OsStr; N], cwd: &Path) -> Result<String> {
-    let output = Command::new("git")
-        .current_dir(cwd)
-        .args(args)
-        .output()
-        .map_err(|source| {
-            if source.kind() == std::io::ErrorKind::NotFound {
-                XurlError::CommandNotFound {
-                    command: "git".to_string(),
-                }
-            } else {
-                XurlError::Io {
-                    path: PathBuf::from("git"),
-                    source,
-                }
-            }
-        })?;
-
-    if output.status.success() {
-        return Ok(String::from_utf8_lossy(&output.stdout).to_string());
-    }
-
-    let command = format!(
-        "git {}",
-        args.iter()
-            .map(|item| item.to_string_lossy())
-            .collect::<Vec<_>>()
-            .join(" ")
-    );
-    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-    Err(XurlError::GitCommandFailed {
-        command,
-        code: output.status.code(),
-        stderr,
-    })
-}
-
 fn normalize_skill_file_path(path: &str) -> Option<PathBuf> {
     let mut normalized = PathBuf::new();
     for segment in path.split('/') {
@@ -0,0 +1,273 @@
+use regex::Regex;
+
+use crate::error::{Result, XurlError};
+use crate::model::{MessageRole, ToolCall};
+use crate::tools;
+
+/// One term of a `q=` or `--grep` search.
+#[derive(Debug, Clone)]
+pub enum SearchTerm {
+    /// Free words, matched as one case-insensitive phrase; stored lowercase.
+    Phrase(String),
+    /// `/regex/`, matched against the text as written.
+    Regex(Regex),
+    /// `role:user` or `role:assistant`; only messages of that role match.
+    Role(MessageRole),
+    /// `tool:<name>`; only tool calls with that name (ignoring case) match.
+    Tool(String),
+    /// `path:<glob>`; only tool calls whose input names a matching file path match.
+    Path(Regex),
+}
+
+/// A parsed search: every term must match the same message or tool call.
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub terms: Vec<SearchTerm>,
+}
+
+/// A message or tool call a search is matched against. Compact summaries are messages
+/// without a role.
+#[derive(Debug, Clone, Copy)]
+pub enum SearchUnit<'a> {
+    Message {
+        role: Option<MessageRole>,
+        text: &'a str,
+    },
+    ToolCall(&'a ToolCall),
+}
+
+impl SearchQuery {
+    /// Parses whitespace-separated terms. Words that are not `role:`, `tool:`, `path:`, or
+    /// `/regex/` terms are joined, in order, into a single phrase, so a plain `q` keeps
+    /// matching as one phrase. A regex may contain spaces: it runs to the next word ending in
+    /// `/`. A word starting with `/` that is never closed, like `/tmp/out.log`, is a word.
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut terms = Vec::new();
+        let mut phrase = Vec::<&str>::new();
+        let tokens = input.split_whitespace().collect::<Vec<_>>();
+        let mut idx = 0;
+        while idx < tokens.len() {
+            let token = tokens[idx];
+            idx += 1;
+            let regex_end = token
+                .starts_with('/')
+                .then(|| {
+                    (idx - 1..tokens.len()).find(|end| {
+                        let word = tokens[*end];
+                        word.ends_with('/') && (*end >= idx || word.len() > 1)
+                    })
+                })
+                .flatten();
+            if let Some(end) = regex_end {
+                let pattern = tokens[idx - 1..=end].join(" ");
+                let pattern = &pattern[1..pattern.len() - 1];
+                let regex = Regex::new(pattern)
+                    .map_err(|err| invalid_term(&format!("/{pattern}/"), &err.to_string()))?;
+                terms.push(SearchTerm::Regex(regex));
+                idx = end + 1;
+            } else if let Some(role) = token.strip_prefix("role:") {
+                let role = match role.to_ascii_lowercase().as_str() {
+                    "user" => MessageRole::User,
+                    "assistant" => MessageRole::Assistant,
+                    _ => return Err(invalid_term(token, "expected role:user or role:assistant")),
+                };
+                terms.push(SearchTerm::Role(role));
+            } else if let Some(name) = token.strip_prefix("tool:") {
+                if name.is_empty() {
+                    return Err(invalid_term(token, "expected a tool name"));
+                }
+                terms.push(SearchTerm::Tool(name.to_string()));
+            } else if let Some(glob) = token.strip_prefix("path:") {
+                if glob.is_empty() {
+                    return Err(invalid_term(token, "expected a path glob"));
+                }
+                terms.push(SearchTerm::Path(glob_regex(glob)));
+            } else {
+                phrase.push(token);
+            }
+        }
+        if !phrase.is_empty() {
+            terms.insert(0, SearchTerm::Phrase(phrase.join(" ").to_lowercase()));
+        }
+        Ok(Self { terms })
+    }
+
+    /// The phrase, when the query is nothing but a phrase and can be searched for in raw
+    /// thread files.
+    pub fn plain_phrase(&self) -> Option<&str> {
+        match self.terms.as_slice() {
+            [SearchTerm::Phrase(phrase)] => Some(phrase),
+            _ => None,
+        }
+    }
+
+    /// The lowercase phrase of the query, if it has free words.
+    pub fn phrase(&self) -> Option<&str> {
+        self.terms.iter().find_map(|term| match term {
+            SearchTerm::Phrase(phrase) => Some(phrase.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Whether the query has phrase or regex terms, which pick out lines of text.
+    pub fn has_text_terms(&self) -> bool {
+        self.terms
+            .iter()
+            .any(|term| matches!(term, SearchTerm::Phrase(_) | SearchTerm::Regex(_)))
+    }
+
+    pub fn matches(&self, unit: SearchUnit<'_>) -> bool {
+        let text = match unit {
+            SearchUnit::Message { text, .. } => text.to_string(),
+            SearchUnit::ToolCall(call) => tool_call_text(call),
+        };
+        let lowercase = text.to_lowercase();
+        self.terms.iter().all(|term| match term {
+            SearchTerm::Phrase(phrase) => lowercase.contains(phrase.as_str()),
+            SearchTerm::Regex(regex) => regex.is_match(&text),
+            SearchTerm::Role(role) => {
+                matches!(unit, SearchUnit::Message { role: Some(actual), .. } if actual == *role)
+            }
+            SearchTerm::Tool(name) => {
+                matches!(unit, SearchUnit::ToolCall(call) if call.name.eq_ignore_ascii_case(name))
+            }
+            SearchTerm::Path(glob) => match unit {
+                SearchUnit::ToolCall(call) => tools::input_paths(call)
+                    .iter()
+                    .any(|path| glob.is_match(path)),
+                SearchUnit::Message { .. } => false,
+            },
+        })
+    }
+
+    /// Whether one line of text matches any phrase or regex term.
+    pub fn line_matches(&self, line: &str) -> bool {
+        let lowercase = line.to_lowercase();
+        self.terms.iter().any(|term| match term {
+            SearchTerm::Phrase(phrase) => lowercase.contains(phrase.as_str()),
+            SearchTerm::Regex(regex) => regex.is_match(line),
+            SearchTerm::Role(_) | SearchTerm::Tool(_) | SearchTerm::Path(_) => false,
+        })
+    }
+}
+
+/// The text of a tool call that searches look at: its name, pretty input, and output.
+pub fn tool_call_text(call: &ToolCall) -> String {
+    let mut text = format!("{}\n", call.name);
+    if !call.input.is_null() {
+        text.push_str(
+            &serde_json::to_string_pretty(&call.input).unwrap_or_else(|_| call.input.to_string()),
+        );
+        text.push('\n');
+    }
+    if let Some(output) = &call.output {
+        text.push_str(output);
+    }
+    text
+}
+
+/// Turns a path glob into a regex matching whole paths or their trailing components, so
+/// `src/**` matches both `src/main.rs` and `/repo/src/main.rs`. `**` crosses directories,
+/// `*` and `?` do not.
+pub(crate) fn glob_regex(glob: &str) -> Regex {
+    let mut pattern = String::from("(?:^|/)");
+    let mut chars = glob.trim_start_matches("./").chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                pattern.push_str(".*");
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            _ => pattern.push_str(&regex::escape(&ch.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).expect("escaped glob is a valid regex")
+}
+
+fn invalid_term(term: &str, reason: &str) -> XurlError {
+    XurlError::InvalidMode(format!("invalid search term `{term}`: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{SearchQuery, SearchTerm, SearchUnit};
+    use crate::model::{MessageRole, ToolCall};
+
+    #[test]
+    fn parse_builds_terms_and_keeps_free_words_as_one_phrase() {
+        let query = SearchQuery::parse("Spawn role:user /dead(lock|line) here/ agent tool:Bash")
+            .expect("parse");
+        assert!(matches!(
+            query.terms.as_slice(),
+            [
+                SearchTerm::Phrase(phrase),
+                SearchTerm::Role(MessageRole::User),
+                SearchTerm::Regex(regex),
+                SearchTerm::Tool(tool),
+            ] if phrase == "spawn agent" && regex.as_str() == "dead(lock|line) here" && tool == "Bash"
+        ));
+        assert_eq!(
+            SearchQuery::parse("spawn agent")
+                .expect("parse")
+                .plain_phrase(),
+            Some("spawn agent")
+        );
+        assert_eq!(
+            SearchQuery::parse("/tmp/out.log")
+                .expect("parse")
+                .plain_phrase(),
+            Some("/tmp/out.log")
+        );
+
+        for (input, reason) in [
+            ("role:system", "expected role:user or role:assistant"),
+            ("/(/", "invalid search term `/(/`"),
+        ] {
+            let err = SearchQuery::parse(input).expect_err("must fail");
+            assert!(format!("{err}").contains(reason), "{err}");
+        }
+    }
+
+    #[test]
+    fn matches_scopes_terms_to_messages_and_tool_calls() {
+        let call = ToolCall {
+            id: None,
+            name: "Edit".to_string(),
+            input: json!({"file_path": "/repo/src/query/mod.rs", "old_string": "deadlock"}),
+            output: None,
+        };
+        let user = SearchUnit::Message {
+            role: Some(MessageRole::User),
+            text: "fix the deadlock",
+        };
+
+        let query = SearchQuery::parse("path:src/** deadlock").expect("parse");
+        assert!(query.matches(SearchUnit::ToolCall(&call)));
+        assert!(!query.matches(user));
+        assert!(
+            !SearchQuery::parse("path:src/*.rs")
+                .expect("parse")
+                .matches(SearchUnit::ToolCall(&call))
+        );
+        assert!(
+            SearchQuery::parse("tool:edit")
+                .expect("parse")
+                .matches(SearchUnit::ToolCall(&call))
+        );
+        assert!(
+            SearchQuery::parse("role:user /dead\\w+/")
+                .expect("parse")
+                .matches(user)
+        );
+        assert!(
+            !SearchQuery::parse("role:assistant deadlock")
+                .expect("parse")
+                .matches(user)
+        );
+    }
+}
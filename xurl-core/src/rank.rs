@@ -0,0 +1,78 @@
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Scores each document against the whitespace-separated terms of `query` with BM25.
+///
+/// Term frequency counts case-insensitive substring occurrences, matching how `q` filters
+/// threads, so `agent` also counts `agents`. Document frequency and average length come from
+/// `documents` alone, so scores only compare within one result set.
+pub fn bm25_scores(query: &str, documents: &[String]) -> Vec<f64> {
+    let mut terms = query
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    terms.sort();
+    terms.dedup();
+    if terms.is_empty() || documents.is_empty() {
+        return vec![0.0; documents.len()];
+    }
+
+    let documents = documents
+        .iter()
+        .map(|document| document.to_lowercase())
+        .collect::<Vec<_>>();
+    let lengths = documents
+        .iter()
+        .map(|document| document.split_whitespace().count() as f64)
+        .collect::<Vec<_>>();
+    let avg_length = (lengths.iter().sum::<f64>() / documents.len() as f64).max(1.0);
+    let frequencies = documents
+        .iter()
+        .map(|document| {
+            terms
+                .iter()
+                .map(|term| document.matches(term.as_str()).count() as f64)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let total = documents.len() as f64;
+    let idf = (0..terms.len())
+        .map(|idx| {
+            let df = frequencies.iter().filter(|tf| tf[idx] > 0.0).count() as f64;
+            (1.0 + (total - df + 0.5) / (df + 0.5)).ln()
+        })
+        .collect::<Vec<_>>();
+
+    frequencies
+        .iter()
+        .zip(&lengths)
+        .map(|(tf, length)| {
+            let norm = K1 * (1.0 - B + B * length / avg_length);
+            tf.iter()
+                .zip(&idf)
+                .map(|(tf, idf)| idf * tf * (K1 + 1.0) / (tf + norm))
+                .sum()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bm25_scores;
+
+    #[test]
+    fn bm25_prefers_rarer_terms_and_denser_matches() {
+        let documents = [
+            "spawn agent once in a long thread about many other things".to_string(),
+            "spawn agent then spawn agent again".to_string(),
+            "agent without the other term".to_string(),
+        ];
+        let scores = bm25_scores("Spawn agent", &documents);
+        assert!(scores[1] > scores[0], "{scores:?}");
+        assert!(scores[0] > scores[2], "{scores:?}");
+        assert!(scores[2] > 0.0, "{scores:?}");
+
+        assert_eq!(bm25_scores("  ", &documents), vec![0.0; 3]);
+    }
+}
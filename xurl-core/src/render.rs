@@ -1,11 +1,23 @@
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use serde::Serialize;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 
 use crate::error::{Result, XurlError};
+use crate::frontmatter::push_yaml_fields;
 use crate::jsonl;
-use crate::model::{MessageRole, ProviderKind, ThreadMessage};
+use crate::model::{
+    MessageRole, ProviderKind, RenderOptions, ThreadMessage, ThreadRelationship, TimelineFilter,
+    ToolCall,
+};
+use crate::query::{self, SearchQuery, SearchUnit};
+use crate::timestamp::{self, TimeZone};
+use crate::tools;
 use crate::uri::AgentsUri;
 
 const TOOL_TYPES: &[&str] = &[
@@ -17,64 +29,624 @@ const TOOL_TYPES: &[&str] = &[
     "function_response",
 ];
 const COMPACT_PLACEHOLDER: &str = "Context was compacted.";
+/// Lines shown before and after each `--grep` match.
+const GREP_CONTEXT_LINES: usize = 2;
+/// Longest `--grep` line shown before it is cut, so base64 blobs stay readable.
+const GREP_MAX_LINE_BYTES: usize = 400;
 
 enum TimelineEntry {
     Message(ThreadMessage),
-    Compact { summary: Option<String> },
+    Compact {
+        summary: Option<String>,
+    },
+    Summary {
+        text: String,
+    },
+    /// A Claude slash command, hook, or system notice, shown with `RenderOptions::show_events`.
+    Event {
+        title: String,
+        text: String,
+    },
 }
 
-pub fn render_markdown(uri: &AgentsUri, source_path: &Path, raw_jsonl: &str) -> Result<String> {
+impl TimelineEntry {
+    fn title(&self) -> &str {
+        match self {
+            Self::Message(message) => match message.role {
+                MessageRole::User => "User",
+                MessageRole::Assistant => "Assistant",
+            },
+            Self::Compact { .. } => "Context Compacted",
+            Self::Summary { .. } => "Context Summary",
+            Self::Event { title, .. } => title,
+        }
+    }
+
+    fn push_text(&self, output: &mut String) {
+        output.push_str(self.text());
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            Self::Message(message) => message.text.trim(),
+            Self::Compact { summary } => summary.as_deref().unwrap_or(COMPACT_PLACEHOLDER).trim(),
+            Self::Summary { text } | Self::Event { text, .. } => text.trim(),
+        }
+    }
+}
+
+/// The frontmatter of [`render_markdown`].
+#[derive(Serialize)]
+struct RawThreadHead<'a> {
+    uri: String,
+    thread_source: Cow<'a, str>,
+}
+
+pub fn render_markdown(
+    uri: &AgentsUri,
+    source_path: &Path,
+    raw_jsonl: &str,
+    options: &RenderOptions,
+) -> Result<String> {
+    let mut output = String::from("---\n");
+    push_yaml_fields(
+        &mut output,
+        &RawThreadHead {
+            uri: uri.as_agents_string(),
+            thread_source: source_path.to_string_lossy(),
+        },
+    )?;
+    output.push_str("---\n\n");
+
+    let mut body = Vec::new();
+    write_markdown_body(
+        uri,
+        source_path,
+        || Ok(raw_jsonl.as_bytes()),
+        options,
+        &mut body,
+    )?;
+    output.push_str(&String::from_utf8_lossy(&body));
+    Ok(output)
+}
+
+/// Writes the markdown body of a thread (everything after the frontmatter) to `out`.
+///
+/// Codex, Claude, and OpenCode rollouts are read one line at a time through `open`, which is
+/// called once for tool calls and once for the timeline, so entries are never collected in
+/// memory. Amp, Gemini, and pi threads need the whole document and are parsed at once. With
+/// `options.max_bytes` set, output stops at that size and ends with a truncation notice; the
+/// rest of the thread is not read.
+///
+/// Returns how many lines were skipped because they are not valid JSON, counted over the part
+/// of the thread read for the timeline.
+pub fn write_markdown_body<R, W>(
+    uri: &AgentsUri,
+    source_path: &Path,
+    open: impl Fn() -> Result<R>,
+    options: &RenderOptions,
+    out: &mut W,
+) -> Result<usize>
+where
+    R: BufRead,
+    W: Write,
+{
+    let mut out = LimitedWriter::new(out, options.max_bytes);
+    out.push("# Thread\n\n")?;
+
+    if matches!(
+        uri.provider,
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Opencode
+    ) {
+        let mut entries =
+            LineEntries::new(uri.provider, source_path, open()?).with_events(options.show_events);
+        let tool_calls =
+            || tools::extract_tool_calls_from_reader(uri.provider, source_path, open()?);
+        push_body(&mut out, options, entries.by_ref(), tool_calls)?;
+        out.flush()?;
+        return Ok(entries.corrupt_lines);
+    }
+
+    let mut raw = String::new();
+    open()?
+        .read_to_string(&mut raw)
+        .map_err(|source| XurlError::Io {
+            path: source_path.to_path_buf(),
+            source,
+        })?;
     let entries = extract_timeline_entries(
         uri.provider,
         source_path,
-        raw_jsonl,
+        &raw,
         &uri.session_id,
         uri.agent_id.as_deref(),
     )?;
+    let corrupt_lines = if uri.provider == ProviderKind::Pi {
+        jsonl::count_corrupt_lines(raw.as_bytes())
+    } else {
+        0
+    };
+    if uri.provider == ProviderKind::Amp
+        && uri.agent_id.is_none()
+        && options.grep.is_none()
+        && options.only.is_none()
+        && !push_related_threads(&mut out, &amp_relationships(&raw))?
+    {
+        out.flush()?;
+        return Ok(corrupt_lines);
+    }
+    let tool_calls = || tools::extract_tool_calls(uri.provider, source_path, &raw);
+    push_body(&mut out, options, entries.into_iter().map(Ok), tool_calls)?;
+    out.flush()?;
+    Ok(corrupt_lines)
+}
 
-    let mut output = String::new();
-    let thread_uri = uri.as_agents_string();
-    let source = source_path.to_string_lossy();
-    output.push_str("---\n");
-    output.push_str(&format!("uri: '{}'\n", yaml_single_quoted(&thread_uri)));
-    output.push_str(&format!(
-        "thread_source: '{}'\n",
-        yaml_single_quoted(source.as_ref())
-    ));
-    output.push_str("---\n\n");
-    output.push_str("# Thread\n\n");
-    output.push_str("## Timeline\n\n");
+fn push_body<W: Write>(
+    out: &mut LimitedWriter<'_, W>,
+    options: &RenderOptions,
+    entries: impl Iterator<Item = Result<TimelineEntry>>,
+    tool_calls: impl FnOnce() -> Result<Vec<ToolCall>>,
+) -> Result<()> {
+    if let Some(pattern) = options.grep.as_deref() {
+        return push_grep_matches(out, pattern, options.only, entries, tool_calls);
+    }
+    if let Some(filter) = options.only {
+        return push_filtered_timeline(out, filter, options, entries, tool_calls);
+    }
 
-    if entries.is_empty() {
-        output.push_str("_No user/assistant messages or compact events found._\n");
-        return Ok(output);
+    let changes = tools::files_changed(&tool_calls()?);
+    if !changes.is_empty() {
+        let mut section = String::from("## Files Changed\n\n");
+        for change in &changes {
+            let plural = if change.edits == 1 { "" } else { "s" };
+            section.push_str(&format!(
+                "- `{}`: {} ({} edit{plural})\n",
+                change.path, change.kind, change.edits
+            ));
+        }
+        section.push('\n');
+        if !out.push(&section)? {
+            return Ok(());
+        }
     }
 
-    for (idx, entry) in entries.iter().enumerate() {
-        let title = match entry {
-            TimelineEntry::Message(message) => match message.role {
-                MessageRole::User => "User",
-                MessageRole::Assistant => "Assistant",
-            },
-            TimelineEntry::Compact { .. } => "Context Compacted",
+    if !out.push("## Timeline\n\n")? {
+        return Ok(());
+    }
+    let mut clock = TimelineClock::new(options);
+    let mut count = 0usize;
+    for entry in entries {
+        let entry = entry?;
+        count += 1;
+        let label = clock.label(&entry);
+        if !out.push(&numbered_entry(count, &entry, &label))? {
+            return Ok(());
+        }
+    }
+    if count == 0 {
+        out.push("_No user/assistant messages or compact events found._\n")?;
+    }
+    Ok(())
+}
+
+/// Adds a `Related Threads` section when there are relationships; returns `false` once the
+/// output limit is reached.
+fn push_related_threads<W: Write>(
+    out: &mut LimitedWriter<'_, W>,
+    relationships: &[ThreadRelationship],
+) -> Result<bool> {
+    if relationships.is_empty() {
+        return Ok(true);
+    }
+    let mut section = String::from(
+        "## Related Threads
+
+",
+    );
+    for relationship in relationships {
+        let role = relationship
+            .role
+            .as_deref()
+            .map(|role| format!(" ({role})"))
+            .unwrap_or_default();
+        section.push_str(&format!(
+            "- `{}`: {}{role}\n",
+            relationship.uri, relationship.kind
+        ));
+    }
+    section.push('\n');
+    out.push(&section)
+}
+
+/// Every entry of an Amp thread's `relationships` list (handoffs, forks, and any other type)
+/// that names a valid `threadID`, in the order Amp recorded them.
+pub fn amp_relationships(raw: &str) -> Vec<ThreadRelationship> {
+    let Ok(value) = serde_json::from_str::<Value>(raw) else {
+        return Vec::new();
+    };
+    value
+        .get("relationships")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|relationship| {
+            let thread_id = relationship.get("threadID").and_then(Value::as_str)?;
+            let uri = AgentsUri::parse(&format!("agents://amp/{thread_id}")).ok()?;
+            let field = |key: &str| {
+                relationship
+                    .get(key)
+                    .and_then(Value::as_str)
+                    .map(ToString::to_string)
+            };
+            Some(ThreadRelationship {
+                kind: field("type").unwrap_or_else(|| "unknown".to_string()),
+                role: field("role").map(|role| role.to_ascii_lowercase()),
+                uri: uri.as_agents_string(),
+                timestamp: field("timestamp")
+                    .or_else(|| field("updatedAt"))
+                    .or_else(|| field("createdAt")),
+            })
+        })
+        .collect()
+}
+
+fn numbered_entry(number: usize, entry: &TimelineEntry, label: &str) -> String {
+    let mut chunk = format!("## {number}. {}{label}\n\n", entry.title());
+    if !entry.text().is_empty() {
+        entry.push_text(&mut chunk);
+        chunk.push_str("\n\n");
+    }
+    chunk
+}
+
+/// Formats a provider timestamp (RFC 3339, or epoch seconds or milliseconds) as RFC 3339 to
+/// the second in `zone`. Values that are not timestamps are returned as written.
+pub fn format_timestamp(value: &str, zone: &TimeZone) -> String {
+    let millis = timestamp::parse_millis(value).or_else(|| {
+        let value = value.trim();
+        (!value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit()))
+            .then(|| value.parse::<i64>().ok())
+            .flatten()
+            .map(timestamp::epoch_millis)
+    });
+    match millis {
+        Some(millis) => timestamp::format_millis_in(millis, zone),
+        None => value.to_string(),
+    }
+}
+
+/// Heading labels for `RenderOptions::show_timestamps`: a message's timestamp and the time
+/// since the previous timestamped message, like ` @ 2026-02-23T04:48:50Z (+42s)`.
+struct TimelineClock<'a> {
+    /// The zone to show timestamps in; `None` when they are not shown.
+    zone: Option<&'a TimeZone>,
+    previous: Option<i64>,
+}
+
+impl<'a> TimelineClock<'a> {
+    fn new(options: &'a RenderOptions) -> Self {
+        Self {
+            zone: options.show_timestamps.then_some(&options.timezone),
+            previous: None,
+        }
+    }
+
+    /// Returns the label for `entry`, or an empty string when it has no timestamp.
+    fn label(&mut self, entry: &TimelineEntry) -> String {
+        let TimelineEntry::Message(message) = entry else {
+            return String::new();
         };
+        let (Some(zone), Some(millis)) = (
+            self.zone,
+            message
+                .timestamp
+                .as_deref()
+                .and_then(timestamp::parse_millis),
+        ) else {
+            return String::new();
+        };
+        let mut label = format!(" @ {}", timestamp::format_millis_in(millis, zone));
+        if let Some(previous) = self.previous.replace(millis) {
+            let elapsed = Duration::from_millis(u64::try_from(millis - previous).unwrap_or(0));
+            label.push_str(&format!(" (+{})", timestamp::format_duration(elapsed)));
+        }
+        label
+    }
+}
 
-        output.push_str(&format!("## {}. {}\n\n", idx + 1, title));
-        match entry {
-            TimelineEntry::Message(message) => output.push_str(message.text.trim()),
-            TimelineEntry::Compact { summary } => {
-                let summary = summary.as_deref().unwrap_or(COMPACT_PLACEHOLDER);
-                output.push_str(summary.trim());
+fn push_filtered_timeline<W: Write>(
+    out: &mut LimitedWriter<'_, W>,
+    filter: TimelineFilter,
+    options: &RenderOptions,
+    entries: impl Iterator<Item = Result<TimelineEntry>>,
+    tool_calls: impl FnOnce() -> Result<Vec<ToolCall>>,
+) -> Result<()> {
+    if !out.push("## Timeline\n\n")? {
+        return Ok(());
+    }
+    if filter == TimelineFilter::Tools {
+        let calls = tool_calls()?;
+        if calls.is_empty() {
+            out.push("_No tool calls found._\n")?;
+        }
+        for (idx, call) in calls.iter().enumerate() {
+            let mut chunk = format!("## {}. Tool: {}\n\n", idx + 1, call.name);
+            if !call.input.is_null() {
+                let input = serde_json::to_string_pretty(&call.input)
+                    .unwrap_or_else(|_| call.input.to_string());
+                chunk.push_str(&format!("```json\n{input}\n```\n\n"));
+            }
+            if let Some(text) = call
+                .output
+                .as_deref()
+                .filter(|text| !text.trim().is_empty())
+            {
+                match elided_output(text, options.elide_bytes) {
+                    Some(note) => chunk.push_str(&format!("Output: {note}\n\n")),
+                    None => {
+                        chunk.push_str(&format!("Output:\n\n```text\n{}\n```\n\n", text.trim_end()))
+                    }
+                }
+            }
+            if !out.push(&chunk)? {
+                return Ok(());
             }
         }
-        output.push_str("\n\n");
+        return Ok(());
     }
 
-    Ok(output)
+    let role = match filter {
+        TimelineFilter::User => MessageRole::User,
+        _ => MessageRole::Assistant,
+    };
+    let mut clock = TimelineClock::new(options);
+    let mut count = 0usize;
+    for entry in entries {
+        let entry = entry?;
+        // Durations count from the previous message of either role.
+        let label = clock.label(&entry);
+        if !matches!(&entry, TimelineEntry::Message(message) if message.role == role) {
+            continue;
+        }
+        count += 1;
+        if !out.push(&numbered_entry(count, &entry, &label))? {
+            return Ok(());
+        }
+    }
+    if count == 0 {
+        out.push(&format!("_No {filter} messages found._\n"))?;
+    }
+    Ok(())
 }
 
-fn yaml_single_quoted(value: &str) -> String {
-    value.replace('\'', "''")
+/// Renders the timeline entries and tool calls matching the search `pattern` (see
+/// [`SearchQuery::parse`]), each as its matching lines with surrounding context. Entries keep
+/// the number they have in the full timeline, and tool calls the number they have under
+/// `--only tools`, so a match can be found again in the full render. `only` limits which
+/// entries are searched.
+fn push_grep_matches<W: Write>(
+    out: &mut LimitedWriter<'_, W>,
+    pattern: &str,
+    only: Option<TimelineFilter>,
+    entries: impl Iterator<Item = Result<TimelineEntry>>,
+    tool_calls: impl FnOnce() -> Result<Vec<ToolCall>>,
+) -> Result<()> {
+    let search = SearchQuery::parse(pattern)?;
+    let mut matched = false;
+
+    if only != Some(TimelineFilter::Tools) {
+        let mut section_started = false;
+        for (idx, entry) in entries.enumerate() {
+            let entry = entry?;
+            let keep = match only {
+                Some(TimelineFilter::User) => {
+                    matches!(&entry, TimelineEntry::Message(message) if message.role == MessageRole::User)
+                }
+                Some(TimelineFilter::Assistant) => {
+                    matches!(&entry, TimelineEntry::Message(message) if message.role == MessageRole::Assistant)
+                }
+                _ => true,
+            };
+            let role = match &entry {
+                TimelineEntry::Message(message) => Some(message.role),
+                TimelineEntry::Compact { .. }
+                | TimelineEntry::Summary { .. }
+                | TimelineEntry::Event { .. } => None,
+            };
+            let text = entry.text();
+            if !keep || !search.matches(SearchUnit::Message { role, text }) {
+                continue;
+            }
+            let excerpt = grep_excerpt(text, &search);
+            let mut chunk = String::new();
+            if !section_started {
+                chunk.push_str("## Timeline Matches\n\n");
+                section_started = true;
+            }
+            chunk.push_str(&format!(
+                "### {}. {}\n\n```text\n{excerpt}```\n\n",
+                idx + 1,
+                entry.title()
+            ));
+            matched = true;
+            if !out.push(&chunk)? {
+                return Ok(());
+            }
+        }
+    }
+
+    if matches!(only, None | Some(TimelineFilter::Tools)) {
+        let mut section_started = false;
+        for (idx, call) in tool_calls()?.iter().enumerate() {
+            if !search.matches(SearchUnit::ToolCall(call)) {
+                continue;
+            }
+            let excerpt = grep_excerpt(&query::tool_call_text(call), &search);
+            let mut chunk = String::new();
+            if !section_started {
+                chunk.push_str("## Tool Call Matches\n\n");
+                section_started = true;
+            }
+            chunk.push_str(&format!(
+                "### {}. Tool: {}\n\n```text\n{excerpt}```\n\n",
+                idx + 1,
+                call.name
+            ));
+            matched = true;
+            if !out.push(&chunk)? {
+                return Ok(());
+            }
+        }
+    }
+
+    if !matched {
+        out.push(&format!("_No matches for `{pattern}`._\n"))?;
+    }
+    Ok(())
+}
+
+/// Returns the lines of `text` matching the phrase or regex terms of `search` with
+/// `GREP_CONTEXT_LINES` of context, numbered from 1 like `grep -n -C`, with `--` between
+/// separate hunks. A search of only field terms shows the opening lines instead.
+fn grep_excerpt(text: &str, search: &SearchQuery) -> String {
+    let lines = text.lines().collect::<Vec<_>>();
+    if lines.is_empty() {
+        return String::new();
+    }
+    let mut hits = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| search.line_matches(line))
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    if hits.is_empty() {
+        hits.push(0);
+    }
+
+    let mut excerpt = String::new();
+    let mut shown_until = None::<usize>;
+    for hit in hits {
+        let start = hit.saturating_sub(GREP_CONTEXT_LINES);
+        let end = (hit + GREP_CONTEXT_LINES).min(lines.len() - 1);
+        let start = match shown_until {
+            Some(last) if start <= last + 1 => last + 1,
+            Some(_) => {
+                excerpt.push_str("--\n");
+                start
+            }
+            None => start,
+        };
+        for (idx, line) in lines.iter().enumerate().take(end + 1).skip(start) {
+            let separator = if idx == hit { ':' } else { '-' };
+            excerpt.push_str(&format!(
+                "{}{separator} {}\n",
+                idx + 1,
+                truncate_line(line, GREP_MAX_LINE_BYTES)
+            ));
+        }
+        shown_until = Some(shown_until.map_or(end, |last| last.max(end)));
+    }
+    excerpt
+}
+
+fn truncate_line(line: &str, max_bytes: usize) -> String {
+    if line.len() <= max_bytes {
+        return line.to_string();
+    }
+    let mut end = max_bytes;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &line[..end])
+}
+
+/// Describes a tool output that is over `limit` bytes, such as a base64 image or a full build
+/// log, as `[output elided: 1.2MB, sha256=...]` so it can still be told apart from others.
+fn elided_output(text: &str, limit: Option<u64>) -> Option<String> {
+    let size = text.len() as u64;
+    if size <= limit? {
+        return None;
+    }
+    let digest = Sha256::digest(text.as_bytes());
+    Some(format!(
+        "[output elided: {}, sha256={digest:x}]",
+        format_size(size)
+    ))
+}
+
+fn format_size(bytes: u64) -> String {
+    const KB: f64 = 1024.0;
+    let bytes_f = bytes as f64;
+    if bytes_f >= KB * KB {
+        format!("{:.1}MB", bytes_f / (KB * KB))
+    } else if bytes_f >= KB {
+        format!("{:.1}KB", bytes_f / KB)
+    } else {
+        format!("{bytes}B")
+    }
+}
+
+/// Writes rendered chunks until an optional byte limit, then a single truncation notice.
+struct LimitedWriter<'a, W: Write> {
+    out: &'a mut W,
+    limit: Option<u64>,
+    written: u64,
+    truncated: bool,
+}
+
+impl<'a, W: Write> LimitedWriter<'a, W> {
+    fn new(out: &'a mut W, limit: Option<u64>) -> Self {
+        Self {
+            out,
+            limit,
+            written: 0,
+            truncated: false,
+        }
+    }
+
+    /// Returns `false` once the limit is reached; callers should stop rendering.
+    fn push(&mut self, chunk: &str) -> Result<bool> {
+        if self.truncated {
+            return Ok(false);
+        }
+        let Some(limit) = self.limit else {
+            self.write(chunk)?;
+            return Ok(true);
+        };
+
+        let remaining = limit.saturating_sub(self.written);
+        if (chunk.len() as u64) <= remaining {
+            self.write(chunk)?;
+            return Ok(true);
+        }
+
+        let mut end = usize::try_from(remaining).unwrap_or(usize::MAX);
+        while !chunk.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.write(&chunk[..end])?;
+        self.write(&format!(
+            "\n\n_Output truncated at {limit} bytes; rerun with a larger --max-bytes to see the rest._\n"
+        ))?;
+        self.truncated = true;
+        Ok(false)
+    }
+
+    fn write(&mut self, text: &str) -> Result<()> {
+        self.written += text.len() as u64;
+        self.out.write_all(text.as_bytes()).map_err(output_error)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.out.flush().map_err(output_error)
+    }
+}
+
+fn output_error(source: std::io::Error) -> XurlError {
+    XurlError::Io {
+        path: PathBuf::from("<output>"),
+        source,
+    }
 }
 
 pub fn extract_messages(
@@ -87,7 +659,9 @@ pub fn extract_messages(
             .into_iter()
             .filter_map(|entry| match entry {
                 TimelineEntry::Message(message) => Some(message),
-                TimelineEntry::Compact { .. } => None,
+                TimelineEntry::Compact { .. }
+                | TimelineEntry::Summary { .. }
+                | TimelineEntry::Event { .. } => None,
             })
             .collect(),
     )
@@ -100,138 +674,469 @@ fn extract_timeline_entries(
     session_id: &str,
     target_entry_id: Option<&str>,
 ) -> Result<Vec<TimelineEntry>> {
-    if provider == ProviderKind::Amp {
-        return Ok(messages_to_entries(extract_amp_messages(path, raw_jsonl)?));
-    }
-    if provider == ProviderKind::Gemini {
-        return Ok(messages_to_entries(extract_gemini_messages(
+    match provider {
+        ProviderKind::Amp => Ok(messages_to_entries(extract_amp_messages(path, raw_jsonl)?)),
+        ProviderKind::Gemini => Ok(messages_to_entries(extract_gemini_messages(
             path, raw_jsonl,
-        )?));
-    }
-    if provider == ProviderKind::Pi {
-        return extract_pi_entries(path, raw_jsonl, session_id, target_entry_id);
+        )?)),
+        ProviderKind::Pi => extract_pi_entries(raw_jsonl, session_id, target_entry_id),
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Opencode => {
+            LineEntries::new(provider, path, raw_jsonl.as_bytes()).collect()
+        }
     }
+}
 
-    let mut entries = Vec::new();
+/// What [`count_messages`] found while streaming a thread.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageCount {
+    /// User and assistant messages.
+    pub messages: usize,
+    /// Non-blank lines skipped because they are not valid JSON.
+    pub corrupt_lines: usize,
+}
 
-    for (line_idx, line) in raw_jsonl.lines().enumerate() {
-        let line_no = line_idx + 1;
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            continue;
+/// Counts the user and assistant messages of a thread without collecting them, along with the
+/// lines the readers skip as corrupt.
+pub fn count_messages<R: BufRead>(
+    provider: ProviderKind,
+    path: &Path,
+    reader: R,
+) -> Result<MessageCount> {
+    if matches!(
+        provider,
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Opencode
+    ) {
+        let mut entries = LineEntries::new(provider, path, reader);
+        let mut messages = 0usize;
+        for entry in entries.by_ref() {
+            if matches!(entry?, TimelineEntry::Message(_)) {
+                messages += 1;
+            }
         }
+        return Ok(MessageCount {
+            messages,
+            corrupt_lines: entries.corrupt_lines,
+        });
+    }
 
-        let Some(value) = jsonl::parse_json_line(path, line_no, trimmed)? else {
-            continue;
-        };
+    let mut reader = reader;
+    let mut raw = String::new();
+    reader
+        .read_to_string(&mut raw)
+        .map_err(|source| XurlError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    Ok(MessageCount {
+        messages: extract_messages(provider, path, &raw)?.len(),
+        corrupt_lines: if provider == ProviderKind::Pi {
+            jsonl::count_corrupt_lines(raw.as_bytes())
+        } else {
+            0
+        },
+    })
+}
+
+/// Wall-clock time from the first to the last timestamped message of a thread, or `None`
+/// when the provider recorded fewer than two timestamps.
+pub fn thread_duration<R: BufRead>(
+    provider: ProviderKind,
+    path: &Path,
+    reader: R,
+) -> Result<Option<Duration>> {
+    Ok(thread_time_span(provider, path, reader)?
+        .filter(|span| span.stamps > 1)
+        .map(|span| Duration::from_millis(u64::try_from(span.last - span.first).unwrap_or(0))))
+}
 
-        let extracted = match provider {
-            ProviderKind::Amp => None,
-            ProviderKind::Codex => extract_codex_entry(&value),
-            ProviderKind::Claude => extract_claude_entry(&value),
-            ProviderKind::Gemini => None,
-            ProviderKind::Pi => None,
-            ProviderKind::Opencode => extract_opencode_message(&value).map(TimelineEntry::Message),
+/// Epoch milliseconds of the earliest and latest timestamped messages of a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TimeSpan {
+    pub(crate) first: i64,
+    pub(crate) last: i64,
+    /// How many messages carried a timestamp.
+    pub(crate) stamps: usize,
+}
+
+/// The time span of a thread's timestamped messages, or `None` when none has a timestamp.
+pub(crate) fn thread_time_span<R: BufRead>(
+    provider: ProviderKind,
+    path: &Path,
+    reader: R,
+) -> Result<Option<TimeSpan>> {
+    let mut span = None::<TimeSpan>;
+    let mut record = |message: &ThreadMessage| {
+        let Some(millis) = message
+            .timestamp
+            .as_deref()
+            .and_then(timestamp::parse_millis)
+        else {
+            return;
         };
+        span = Some(span.map_or(
+            TimeSpan {
+                first: millis,
+                last: millis,
+                stamps: 1,
+            },
+            |span| TimeSpan {
+                first: span.first.min(millis),
+                last: span.last.max(millis),
+                stamps: span.stamps + 1,
+            },
+        ));
+    };
+
+    if matches!(
+        provider,
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Opencode
+    ) {
+        for entry in LineEntries::new(provider, path, reader) {
+            if let TimelineEntry::Message(message) = entry? {
+                record(&message);
+            }
+        }
+    } else {
+        let mut reader = reader;
+        let mut raw = String::new();
+        reader
+            .read_to_string(&mut raw)
+            .map_err(|source| XurlError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        extract_messages(provider, path, &raw)?
+            .iter()
+            .for_each(&mut record);
+    }
+    Ok(span)
+}
+
+/// Timeline entries of a line-based rollout (Codex, Claude, OpenCode), read one line at a time.
+/// Lines that are not valid JSON are skipped and counted.
+struct LineEntries<'a, R: BufRead> {
+    provider: ProviderKind,
+    path: &'a Path,
+    reader: R,
+    line: String,
+    corrupt_lines: usize,
+    after_compact: bool,
+    events: bool,
+}
 
-        if let Some(entry) = extracted {
-            entries.push(entry);
+impl<'a, R: BufRead> LineEntries<'a, R> {
+    fn new(provider: ProviderKind, path: &'a Path, reader: R) -> Self {
+        Self {
+            provider,
+            path,
+            reader,
+            line: String::new(),
+            corrupt_lines: 0,
+            after_compact: false,
+            events: false,
         }
     }
 
-    Ok(entries)
+    /// Also yields Claude slash commands, hooks, and system notices as `TimelineEntry::Event`.
+    fn with_events(mut self, events: bool) -> Self {
+        self.events = events;
+        self
+    }
+}
+
+impl<R: BufRead> Iterator for LineEntries<'_, R> {
+    type Item = Result<TimelineEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(source) => {
+                    return Some(Err(XurlError::Io {
+                        path: self.path.to_path_buf(),
+                        source,
+                    }));
+                }
+            }
+            let Some(value) = jsonl::parse_thread_line(&self.line) else {
+                if !self.line.trim().is_empty() {
+                    self.corrupt_lines += 1;
+                }
+                continue;
+            };
+            let extracted = match self.provider {
+                ProviderKind::Codex => extract_codex_entry(&value),
+                ProviderKind::Claude if self.events => {
+                    extract_claude_event(&value).or_else(|| extract_claude_entry(&value))
+                }
+                ProviderKind::Claude => extract_claude_entry(&value),
+                ProviderKind::Opencode => {
+                    extract_opencode_message(&value).map(TimelineEntry::Message)
+                }
+                ProviderKind::Amp | ProviderKind::Gemini | ProviderKind::Pi => None,
+            };
+            let Some(entry) = extracted else {
+                continue;
+            };
+
+            // Codex writes a `context_compacted` event next to the `compacted` record.
+            let duplicate_compact =
+                matches!(entry, TimelineEntry::Compact { summary: None }) && self.after_compact;
+            self.after_compact = matches!(
+                entry,
+                TimelineEntry::Compact { .. } | TimelineEntry::Summary { .. }
+            );
+            if !duplicate_compact {
+                return Some(Ok(entry));
+            }
+        }
+    }
 }
 
 fn messages_to_entries(messages: Vec<ThreadMessage>) -> Vec<TimelineEntry> {
     messages.into_iter().map(TimelineEntry::Message).collect()
 }
 
-fn extract_pi_entries(
-    path: &Path,
-    raw_jsonl: &str,
-    session_id: &str,
-    target_entry_id: Option<&str>,
-) -> Result<Vec<TimelineEntry>> {
-    let mut entries_by_id = HashMap::<String, Value>::new();
-    let mut last_entry_id = None::<String>;
+struct PiTree {
+    entries_by_id: HashMap<String, Value>,
+    order: Vec<String>,
+    last_entry_id: Option<String>,
+}
 
-    for (line_idx, line) in raw_jsonl.lines().enumerate() {
-        let line_no = line_idx + 1;
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            continue;
-        }
+impl PiTree {
+    fn parse(raw_jsonl: &str) -> Self {
+        let mut entries_by_id = HashMap::<String, Value>::new();
+        let mut order = Vec::new();
+        let mut last_entry_id = None::<String>;
 
-        let Some(value) = jsonl::parse_json_line(path, line_no, trimmed)? else {
-            continue;
-        };
+        for line in raw_jsonl.lines() {
+            let Some(value) = jsonl::parse_thread_line(line) else {
+                continue;
+            };
 
-        if value.get("type").and_then(Value::as_str) == Some("session") {
-            continue;
+            if value.get("type").and_then(Value::as_str) == Some("session") {
+                continue;
+            }
+
+            let Some(id) = value
+                .get("id")
+                .and_then(Value::as_str)
+                .map(str::to_ascii_lowercase)
+            else {
+                continue;
+            };
+
+            last_entry_id = Some(id.clone());
+            if entries_by_id.insert(id.clone(), value).is_none() {
+                order.push(id);
+            }
+        }
+
+        Self {
+            entries_by_id,
+            order,
+            last_entry_id,
         }
+    }
 
-        let Some(id) = value
-            .get("id")
+    fn parent_id(&self, entry_id: &str) -> Option<String> {
+        self.entries_by_id
+            .get(entry_id)?
+            .get("parentId")
             .and_then(Value::as_str)
             .map(str::to_ascii_lowercase)
-        else {
-            continue;
-        };
+    }
 
-        last_entry_id = Some(id.clone());
-        entries_by_id.insert(id, value);
+    /// Resolves `target_entry_id` (or the last entry in the file) to a known entry id.
+    fn leaf_id(&self, session_id: &str, target_entry_id: Option<&str>) -> Result<String> {
+        let leaf_id = target_entry_id
+            .map(str::to_ascii_lowercase)
+            .or_else(|| self.last_entry_id.clone())
+            .unwrap_or_default();
+
+        if !self.entries_by_id.contains_key(&leaf_id) {
+            return Err(XurlError::EntryNotFound {
+                provider: ProviderKind::Pi.to_string(),
+                session_id: session_id.to_string(),
+                entry_id: leaf_id,
+            });
+        }
+        Ok(leaf_id)
     }
 
-    if entries_by_id.is_empty() {
-        return Ok(Vec::new());
+    /// Entry ids from the root down to `leaf_id`.
+    fn path_to(&self, leaf_id: &str) -> Vec<String> {
+        let mut path_ids = Vec::new();
+        let mut seen = HashSet::new();
+        let mut current = Some(leaf_id.to_string());
+
+        while let Some(entry_id) = current {
+            if !seen.insert(entry_id.clone()) || !self.entries_by_id.contains_key(&entry_id) {
+                break;
+            }
+            current = self.parent_id(&entry_id);
+            path_ids.push(entry_id);
+        }
+
+        path_ids.reverse();
+        path_ids
     }
 
-    let leaf_id = target_entry_id
-        .map(str::to_ascii_lowercase)
-        .or(last_entry_id)
-        .unwrap_or_default();
+    /// Leaf entry ids, the latest branch first and the rest in file order.
+    fn leaves(&self) -> Vec<String> {
+        let parents = self
+            .order
+            .iter()
+            .filter_map(|entry_id| self.parent_id(entry_id))
+            .collect::<HashSet<_>>();
+        let mut leaves = self
+            .order
+            .iter()
+            .filter(|entry_id| !parents.contains(*entry_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        if let Some(last) = &self.last_entry_id
+            && let Some(position) = leaves.iter().position(|leaf| leaf == last)
+        {
+            let latest = leaves.remove(position);
+            leaves.insert(0, latest);
+        }
+        leaves
+    }
 
-    if !entries_by_id.contains_key(&leaf_id) {
-        return Err(XurlError::EntryNotFound {
-            provider: ProviderKind::Pi.to_string(),
-            session_id: session_id.to_string(),
-            entry_id: leaf_id,
-        });
+    /// Numbered timeline entries along `path_ids`, skipping entries listed in `skip`.
+    fn timeline(&self, path_ids: &[String], skip: &HashSet<&str>) -> Vec<(usize, TimelineEntry)> {
+        path_ids
+            .iter()
+            .filter_map(|entry_id| {
+                let entry = extract_pi_entry(self.entries_by_id.get(entry_id)?)?;
+                Some((entry_id, entry))
+            })
+            .enumerate()
+            .filter(|(_, (entry_id, _))| !skip.contains(entry_id.as_str()))
+            .map(|(idx, (_, entry))| (idx + 1, entry))
+            .collect()
+    }
+}
+
+fn extract_pi_entries(
+    raw_jsonl: &str,
+    session_id: &str,
+    target_entry_id: Option<&str>,
+) -> Result<Vec<TimelineEntry>> {
+    let tree = PiTree::parse(raw_jsonl);
+    if tree.order.is_empty() {
+        return Ok(Vec::new());
     }
 
-    let mut path_ids = Vec::new();
-    let mut seen = HashSet::new();
-    let mut current = Some(leaf_id);
+    let leaf_id = tree.leaf_id(session_id, target_entry_id)?;
+    let path_ids = tree.path_to(&leaf_id);
+    Ok(tree
+        .timeline(&path_ids, &HashSet::new())
+        .into_iter()
+        .map(|(_, entry)| entry)
+        .collect())
+}
+
+/// Renders every leaf of a pi session tree. The latest branch is rendered in full; each other
+/// branch only renders the entries after the point where it diverges from earlier branches.
+pub fn render_pi_branches_markdown(uri: &AgentsUri, raw_jsonl: &str) -> Result<String> {
+    let tree = PiTree::parse(raw_jsonl);
+    let session_uri = format!("agents://pi/{}", uri.session_id);
+
+    let mut output = String::new();
+    output.push_str("# Thread Branches\n\n");
 
-    while let Some(entry_id) = current {
-        if !seen.insert(entry_id.clone()) {
-            break;
+    let leaves = tree.leaves();
+    if leaves.is_empty() {
+        output.push_str("_No entries found in this session._\n");
+        return Ok(output);
+    }
+
+    let mut rendered = HashSet::<String>::new();
+    for (index, leaf_id) in leaves.iter().enumerate() {
+        let path_ids = tree.path_to(leaf_id);
+        let divergence = path_ids
+            .iter()
+            .rev()
+            .find(|entry_id| rendered.contains(*entry_id));
+
+        output.push_str(&format!(
+            "## Branch {}: `{session_uri}/{leaf_id}`\n\n",
+            index + 1
+        ));
+        match divergence {
+            Some(entry_id) => {
+                output.push_str(&format!("- Diverges after: `{session_uri}/{entry_id}`\n\n"))
+            }
+            None => output.push_str("- Diverges after: `root`\n\n"),
         }
 
-        let Some(entry) = entries_by_id.get(&entry_id) else {
-            break;
-        };
-        path_ids.push(entry_id);
+        let skip = path_ids
+            .iter()
+            .filter(|entry_id| rendered.contains(*entry_id))
+            .map(String::as_str)
+            .collect::<HashSet<_>>();
+        push_numbered_entries(&mut output, "###", &tree.timeline(&path_ids, &skip));
+        rendered.extend(path_ids);
+    }
 
-        current = entry
-            .get("parentId")
-            .and_then(Value::as_str)
-            .map(str::to_ascii_lowercase);
+    Ok(output)
+}
+
+/// Renders the entries two pi branches do not share, after their last common entry.
+pub fn render_pi_diff_markdown(
+    left: &AgentsUri,
+    right: &AgentsUri,
+    raw_jsonl: &str,
+) -> Result<String> {
+    let tree = PiTree::parse(raw_jsonl);
+    let left_path = tree.path_to(&tree.leaf_id(&left.session_id, left.agent_id.as_deref())?);
+    let right_path = tree.path_to(&tree.leaf_id(&right.session_id, right.agent_id.as_deref())?);
+    let shared = left_path
+        .iter()
+        .zip(&right_path)
+        .take_while(|(left, right)| left == right)
+        .count();
+
+    let mut output = String::new();
+    output.push_str("# Branch Diff\n\n");
+    match shared.checked_sub(1).map(|idx| &left_path[idx]) {
+        Some(entry_id) => output.push_str(&format!(
+            "- Common ancestor: `agents://pi/{}/{entry_id}` ({shared} shared entries)\n\n",
+            left.session_id
+        )),
+        None => output.push_str("- Common ancestor: `root` (0 shared entries)\n\n"),
     }
 
-    path_ids.reverse();
+    for (label, uri, path_ids) in [("A", left, &left_path), ("B", right, &right_path)] {
+        output.push_str(&format!(
+            "## Only in {label}: `{}`\n\n",
+            uri.as_agents_string()
+        ));
+        let skip = path_ids[..shared]
+            .iter()
+            .map(String::as_str)
+            .collect::<HashSet<_>>();
+        push_numbered_entries(&mut output, "###", &tree.timeline(path_ids, &skip));
+    }
 
-    let mut entries = Vec::new();
-    for entry_id in path_ids {
-        let Some(entry) = entries_by_id.get(&entry_id) else {
-            continue;
-        };
-        if let Some(timeline_entry) = extract_pi_entry(entry) {
-            entries.push(timeline_entry);
-        }
+    Ok(output)
+}
+
+fn push_numbered_entries(output: &mut String, heading: &str, entries: &[(usize, TimelineEntry)]) {
+    if entries.is_empty() {
+        output.push_str("_No user/assistant messages or compact events found._\n\n");
+        return;
     }
 
-    Ok(entries)
+    for (number, entry) in entries {
+        output.push_str(&format!("{heading} {number}. {}\n\n", entry.title()));
+        entry.push_text(output);
+        output.push_str("\n\n");
+    }
 }
 
 fn extract_pi_entry(value: &Value) -> Option<TimelineEntry> {
@@ -239,16 +1144,26 @@ fn extract_pi_entry(value: &Value) -> Option<TimelineEntry> {
 
     if entry_type == "message" {
         let message = value.get("message")?;
-        let role = message
-            .get("role")
-            .and_then(Value::as_str)
-            .and_then(parse_role)?;
-        let text = extract_text(message.get("content"));
+        let role = message.get("role").and_then(Value::as_str)?;
+        if role == "toolResult" {
+            return pi_tool_result(message, message.get("content"));
+        }
+        let role = parse_role(role)?;
+        let content = message.get("content");
+        let text = match role {
+            MessageRole::User => extract_text(content),
+            MessageRole::Assistant => extract_pi_assistant_text(content),
+        };
         if text.trim().is_empty() {
-            return None;
+            return pi_tool_result_block(content);
         }
+        let timestamp = value.get("timestamp").and_then(timestamp::from_value);
 
-        return Some(TimelineEntry::Message(ThreadMessage { role, text }));
+        return Some(TimelineEntry::Message(ThreadMessage {
+            role,
+            text,
+            timestamp,
+        }));
     }
 
     if entry_type == "compaction" || entry_type == "branch_summary" {
@@ -262,6 +1177,88 @@ fn extract_pi_entry(value: &Value) -> Option<TimelineEntry> {
     None
 }
 
+/// Pi assistant content: text as written, `thinking` blocks as a quoted section, and each
+/// `toolCall` (or `tool_use`) block as the tool name and its arguments.
+fn extract_pi_assistant_text(content: Option<&Value>) -> String {
+    let Some(items) = content.and_then(Value::as_array) else {
+        return extract_text(content);
+    };
+
+    let mut chunks = Vec::new();
+    for item in items {
+        let field = |name: &str| item.get(name).and_then(Value::as_str).map(str::trim);
+        match field("type") {
+            Some("text") => {
+                if let Some(text) = field("text").filter(|text| !text.is_empty()) {
+                    chunks.push(text.to_string());
+                }
+            }
+            Some("thinking") => {
+                if let Some(thinking) = field("thinking").filter(|text| !text.is_empty()) {
+                    let quoted = thinking
+                        .lines()
+                        .map(|line| format!("> {line}").trim_end().to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    chunks.push(format!("**Thinking**\n\n{quoted}"));
+                }
+            }
+            Some("toolCall" | "tool_use") => {
+                let name = field("name").unwrap_or("tool");
+                let mut chunk = format!("**Tool:** `{name}`");
+                if let Some(arguments) = item
+                    .get("arguments")
+                    .or_else(|| item.get("input"))
+                    .filter(|arguments| !arguments.is_null())
+                {
+                    let arguments = serde_json::to_string_pretty(arguments)
+                        .unwrap_or_else(|_| arguments.to_string());
+                    chunk.push_str(&format!("\n\n```json\n{arguments}\n```"));
+                }
+                chunks.push(chunk);
+            }
+            _ => {}
+        }
+    }
+
+    chunks.join("\n\n")
+}
+
+/// A message that carries only a `tool_result` block, as some pi providers record results.
+fn pi_tool_result_block(content: Option<&Value>) -> Option<TimelineEntry> {
+    let block = content?
+        .as_array()?
+        .iter()
+        .find(|item| item.get("type").and_then(Value::as_str) == Some("tool_result"))?;
+    pi_tool_result(block, block.get("content"))
+}
+
+/// Pi `toolResult` messages become events titled with the tool name, their output fenced.
+fn pi_tool_result(record: &Value, content: Option<&Value>) -> Option<TimelineEntry> {
+    let name = ["toolName", "name"]
+        .iter()
+        .find_map(|key| record.get(*key).and_then(Value::as_str))
+        .unwrap_or("tool");
+    let failed = ["isError", "is_error"]
+        .iter()
+        .any(|key| record.get(*key).and_then(Value::as_bool) == Some(true));
+    let output = match content {
+        Some(Value::String(text)) => text.trim().to_string(),
+        content => extract_text(content),
+    };
+    let title = if failed {
+        format!("Tool Error: {name}")
+    } else {
+        format!("Tool Result: {name}")
+    };
+    let text = if output.is_empty() {
+        String::new()
+    } else {
+        format!("```text\n{output}\n```")
+    };
+    Some(TimelineEntry::Event { title, text })
+}
+
 fn extract_amp_messages(path: &Path, raw_json: &str) -> Result<Vec<ThreadMessage>> {
     let value =
         serde_json::from_str::<Value>(raw_json).map_err(|source| XurlError::InvalidJsonLine {
@@ -289,8 +1286,15 @@ fn extract_amp_messages(path: &Path, raw_json: &str) -> Result<Vec<ThreadMessage
         if text.trim().is_empty() {
             continue;
         }
+        let timestamp = message
+            .pointer("/meta/sentAt")
+            .and_then(timestamp::from_value);
 
-        messages.push(ThreadMessage { role, text });
+        messages.push(ThreadMessage {
+            role,
+            text,
+            timestamp,
+        });
     }
 
     Ok(messages)
@@ -329,8 +1333,13 @@ fn extract_gemini_messages(path: &Path, raw_json: &str) -> Result<Vec<ThreadMess
         if text.trim().is_empty() {
             continue;
         }
+        let timestamp = message.get("timestamp").and_then(timestamp::from_value);
 
-        messages.push(ThreadMessage { role, text });
+        messages.push(ThreadMessage {
+            role,
+            text,
+            timestamp,
+        });
     }
 
     Ok(messages)
@@ -353,7 +1362,11 @@ fn extract_codex_message(value: &Value) -> Option<ThreadMessage> {
             return None;
         }
 
-        return Some(ThreadMessage { role, text });
+        return Some(ThreadMessage {
+            role,
+            text,
+            timestamp: value.get("timestamp").and_then(timestamp::from_value),
+        });
     }
 
     if record_type == "event_msg"
@@ -377,6 +1390,7 @@ fn extract_codex_message(value: &Value) -> Option<ThreadMessage> {
         return Some(ThreadMessage {
             role: MessageRole::Assistant,
             text,
+            timestamp: value.get("timestamp").and_then(timestamp::from_value),
         });
     }
 
@@ -388,6 +1402,10 @@ fn extract_codex_entry(value: &Value) -> Option<TimelineEntry> {
         return Some(TimelineEntry::Message(message));
     }
 
+    if let Some(text) = extract_codex_compacted_summary(value) {
+        return Some(TimelineEntry::Summary { text });
+    }
+
     if is_codex_compact_event(value) {
         return Some(TimelineEntry::Compact { summary: None });
     }
@@ -395,6 +1413,30 @@ fn extract_codex_entry(value: &Value) -> Option<TimelineEntry> {
     None
 }
 
+/// Returns the summary carried by a codex `compacted` record, which replaces the history
+/// before it when the session is resumed.
+fn extract_codex_compacted_summary(value: &Value) -> Option<String> {
+    if value.get("type").and_then(Value::as_str) != Some("compacted") {
+        return None;
+    }
+    value
+        .get("payload")
+        .and_then(|payload| payload.get("message"))
+        .and_then(Value::as_str)
+        .filter(|text| !text.trim().is_empty())
+        .map(ToString::to_string)
+}
+
+/// Whether a codex rollout contains a compaction record.
+pub fn codex_rollout_is_compacted<R: BufRead>(reader: R) -> bool {
+    reader
+        .lines()
+        .map_while(std::result::Result::ok)
+        .filter(|line| line.contains("compacted"))
+        .filter_map(|line| serde_json::from_str::<Value>(line.trim()).ok())
+        .any(|value| is_codex_compact_event(&value))
+}
+
 fn is_codex_compact_event(value: &Value) -> bool {
     let record_type = value.get("type").and_then(Value::as_str);
 
@@ -428,7 +1470,11 @@ fn extract_claude_message(value: &Value) -> Option<ThreadMessage> {
         return None;
     }
 
-    Some(ThreadMessage { role, text })
+    Some(ThreadMessage {
+        role,
+        text,
+        timestamp: value.get("timestamp").and_then(timestamp::from_value),
+    })
 }
 
 fn extract_claude_entry(value: &Value) -> Option<TimelineEntry> {
@@ -444,6 +1490,53 @@ fn extract_claude_entry(value: &Value) -> Option<TimelineEntry> {
     extract_claude_message(value).map(TimelineEntry::Message)
 }
 
+/// Claude's slash commands, hooks, and system notices. A slash command is recorded as a user
+/// message wrapping the command in `<command-name>` and `<command-args>` tags, and its local
+/// output as one wrapped in `<local-command-stdout>`.
+fn extract_claude_event(value: &Value) -> Option<TimelineEntry> {
+    let field = |name: &str| value.get(name).and_then(Value::as_str);
+    let string = |names: &[&str]| names.iter().find_map(|name| field(name));
+    let hook = string(&["hookEvent", "hookName"]);
+    let (title, text) = match (field("type")?, hook) {
+        ("user", _) => {
+            let text = extract_text(value.pointer("/message/content"));
+            if let Some(name) = tagged_text(&text, "command-name") {
+                let args = tagged_text(&text, "command-args").unwrap_or_default();
+                (format!("Command: {name}"), args.to_string())
+            } else {
+                let output = tagged_text(&text, "local-command-stdout")?;
+                ("Command Output".to_string(), output.to_string())
+            }
+        }
+        ("command", _) => {
+            let name = string(&["command", "name"])?;
+            let text = string(&["args", "output", "content"]).unwrap_or_default();
+            (format!("Command: {name}"), text.to_string())
+        }
+        ("hook", _) | ("system", Some(_)) => {
+            let name = hook.or_else(|| field("event")).unwrap_or("hook");
+            let text = string(&["content", "output", "command"]).unwrap_or_default();
+            (format!("Hook: {name}"), text.to_string())
+        }
+        ("system", None) if !is_claude_compact_boundary(value) => {
+            let title = field("subtype").map_or_else(
+                || "System".to_string(),
+                |subtype| format!("System: {subtype}"),
+            );
+            (title, field("content").unwrap_or_default().to_string())
+        }
+        _ => return None,
+    };
+    Some(TimelineEntry::Event { title, text })
+}
+
+/// The trimmed text between `<tag>` and `</tag>`.
+fn tagged_text<'a>(text: &'a str, tag: &str) -> Option<&'a str> {
+    let start = text.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = start + text[start..].find(&format!("</{tag}>"))?;
+    Some(text[start..end].trim())
+}
+
 fn is_claude_compact_boundary(value: &Value) -> bool {
     value.get("type").and_then(Value::as_str) == Some("system")
         && value.get("subtype").and_then(Value::as_str) == Some("compact_boundary")
@@ -496,6 +1589,9 @@ fn extract_opencode_message(value: &Value) -> Option<ThreadMessage> {
     Some(ThreadMessage {
         role,
         text: chunks.join("\n\n"),
+        timestamp: message
+            .pointer("/time/created")
+            .and_then(timestamp::from_value),
     })
 }
 
@@ -605,8 +1701,12 @@ fn extract_text(content: Option<&Value>) -> String {
 mod tests {
     use std::path::Path;
 
-    use crate::model::ProviderKind;
-    use crate::render::{extract_messages, render_markdown};
+    use crate::frontmatter::parse_frontmatter;
+    use crate::model::{ProviderKind, RenderOptions};
+    use crate::render::{
+        amp_relationships, codex_rollout_is_compacted, extract_messages, render_markdown,
+        render_pi_branches_markdown, render_pi_diff_markdown,
+    };
     use crate::uri::AgentsUri;
 
     #[test]
@@ -614,12 +1714,31 @@ mod tests {
         let raw = r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"hello"}]}}"#;
         let uri =
             AgentsUri::parse("codex://019c871c-b1f9-7f60-9c4f-87ed09f13592").expect("parse uri");
-        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw).expect("render");
+        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw, &RenderOptions::default())
+            .expect("render");
 
         assert!(output.starts_with("---\n"));
-        assert!(output.contains("uri: 'agents://codex/019c871c-b1f9-7f60-9c4f-87ed09f13592'"));
-        assert!(output.contains("thread_source: '/tmp/mock'"));
+        assert!(output.contains("uri: agents://codex/019c871c-b1f9-7f60-9c4f-87ed09f13592\n"));
+        assert!(output.contains("thread_source: /tmp/mock\n"));
         assert!(output.contains("## Timeline"));
+
+        for source in [
+            "it's \"quoted\"",
+            "line one\nline two\r\n",
+            "---\n# not a heading",
+            "tab\tback\\slash: #x",
+            "bell\u{7}del\u{7f}c1\u{85}\u{9b}",
+            "ls\u{2028}ps\u{2029}bom\u{feff}",
+            "  padded  ",
+            "中文 🚀",
+        ] {
+            let output = render_markdown(&uri, Path::new(source), raw, &RenderOptions::default())
+                .expect("render");
+            let head = parse_frontmatter(&output)
+                .expect("valid yaml")
+                .expect("frontmatter");
+            assert_eq!(head["thread_source"], source, "{output}");
+        }
     }
 
     #[test]
@@ -670,6 +1789,26 @@ mod tests {
         assert_eq!(messages[1].text, "step by step\n\ndone");
     }
 
+    #[test]
+    fn amp_renders_every_relationship_as_a_related_thread() {
+        let raw = r#"{"id":"T-019c0797-c402-7389-bd80-d785c98df295","messages":[{"role":"user","content":[{"type":"text","text":"hello"}]}],"relationships":[{"type":"handoff","threadID":"T-1e8a2f4c-7d91-4b63-a5f0-3c2d9e8b7a61","role":"Parent","timestamp":"2026-02-23T00:00:02Z"},{"type":"fork","threadID":"T-5c6d7e8f-0a1b-4c2d-8e3f-4a5b6c7d8e9f"},{"type":"handoff","threadID":"not a thread/../x"}]}"#;
+
+        let relationships = amp_relationships(raw);
+        assert_eq!(relationships.len(), 2);
+        assert_eq!(relationships[0].kind, "handoff");
+        assert_eq!(relationships[0].role.as_deref(), Some("parent"));
+        assert_eq!(relationships[1].kind, "fork");
+        assert_eq!(relationships[1].timestamp, None);
+
+        let uri = AgentsUri::parse("agents://amp/T-019c0797-c402-7389-bd80-d785c98df295")
+            .expect("parse uri");
+        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw, &RenderOptions::default())
+            .expect("render");
+        assert!(output.contains(
+            "## Related Threads\n\n- `agents://amp/T-1e8a2f4c-7d91-4b63-a5f0-3c2d9e8b7a61`: handoff (parent)\n- `agents://amp/T-5c6d7e8f-0a1b-4c2d-8e3f-4a5b6c7d8e9f`: fork\n\n## Timeline"
+        ));
+    }
+
     #[test]
     fn gemini_extracts_user_and_assistant_messages() {
         let raw = r#"{"sessionId":"29d207db-ca7e-40ba-87f7-e14c9de60613","messages":[{"type":"info","content":"ignored"},{"type":"user","content":"hello"},{"type":"gemini","content":"world"},{"type":"gemini","content":[{"type":"thinking","text":"step by step"},{"type":"tool_call","name":"list_directory"},{"type":"text","text":"done"}]}]}"#;
@@ -694,7 +1833,8 @@ mod tests {
 {"type":"message","id":"g1b2c3d4","parentId":"f1b2c3d4","timestamp":"2026-02-23T13:00:19.000Z","message":{"role":"assistant","content":[{"type":"text","text":"branch two done"}]}}"#;
 
         let uri = AgentsUri::parse("pi://12cb4c19-2774-4de4-a0d0-9fa32fbae29f").expect("parse uri");
-        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw).expect("render");
+        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw, &RenderOptions::default())
+            .expect("render");
 
         assert!(output.contains("root"));
         assert!(output.contains("branch two"));
@@ -702,6 +1842,24 @@ mod tests {
         assert!(!output.contains("branch one done"));
     }
 
+    #[test]
+    fn pi_renders_tool_calls_results_and_thinking() {
+        let raw = r#"{"type":"session","version":3,"id":"12cb4c19-2774-4de4-a0d0-9fa32fbae29f","timestamp":"2026-02-23T13:00:12.780Z","cwd":"/tmp/project"}
+{"type":"message","id":"a1b2c3d4","parentId":null,"timestamp":"2026-02-23T13:00:13.000Z","message":{"role":"user","content":[{"type":"text","text":"list files"}]}}
+{"type":"message","id":"b1b2c3d4","parentId":"a1b2c3d4","timestamp":"2026-02-23T13:00:14.000Z","message":{"role":"assistant","content":[{"type":"thinking","thinking":"check the tree\nthen answer"},{"type":"toolCall","id":"call_1","name":"bash","arguments":{"command":"ls"}}]}}
+{"type":"message","id":"c1b2c3d4","parentId":"b1b2c3d4","timestamp":"2026-02-23T13:00:15.000Z","message":{"role":"toolResult","toolCallId":"call_1","toolName":"bash","content":[{"type":"text","text":"Cargo.toml\nsrc"}],"isError":false}}
+{"type":"message","id":"d1b2c3d4","parentId":"c1b2c3d4","timestamp":"2026-02-23T13:00:16.000Z","message":{"role":"assistant","content":[{"type":"text","text":"two entries"}]}}"#;
+
+        let uri = AgentsUri::parse("pi://12cb4c19-2774-4de4-a0d0-9fa32fbae29f").expect("parse uri");
+        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw, &RenderOptions::default())
+            .expect("render");
+
+        assert!(output.contains("**Thinking**\n\n> check the tree\n> then answer"));
+        assert!(output.contains("**Tool:** `bash`\n\n```json\n{\n  \"command\": \"ls\"\n}\n```"));
+        assert!(output.contains("## 3. Tool Result: bash\n\n```text\nCargo.toml\nsrc\n```"));
+        assert!(output.contains("## 4. Assistant\n\ntwo entries"));
+    }
+
     #[test]
     fn pi_entry_leaf_renders_requested_branch() {
         let raw = r#"{"type":"session","version":3,"id":"12cb4c19-2774-4de4-a0d0-9fa32fbae29f","timestamp":"2026-02-23T13:00:12.780Z","cwd":"/tmp/project"}
@@ -714,7 +1872,8 @@ mod tests {
 
         let uri = AgentsUri::parse("pi://12cb4c19-2774-4de4-a0d0-9fa32fbae29f/d1b2c3d4")
             .expect("parse uri");
-        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw).expect("render");
+        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw, &RenderOptions::default())
+            .expect("render");
 
         assert!(output.contains("branch one done"));
         assert!(!output.contains("branch two done"));
@@ -732,7 +1891,8 @@ mod tests {
 
         let uri = AgentsUri::parse("pi://12cb4c19-2774-4de4-a0d0-9fa32fbae29f/d1b2c3d4")
             .expect("parse uri");
-        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw).expect("render");
+        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw, &RenderOptions::default())
+            .expect("render");
 
         assert!(output.contains("branch one done"));
         assert!(!output.contains("branch two done"));
@@ -745,10 +1905,49 @@ mod tests {
 
         let uri = AgentsUri::parse("pi://12cb4c19-2774-4de4-a0d0-9fa32fbae29f/deadbeef")
             .expect("parse uri");
-        let err = render_markdown(&uri, Path::new("/tmp/mock"), raw).expect_err("must fail");
+        let err = render_markdown(&uri, Path::new("/tmp/mock"), raw, &RenderOptions::default())
+            .expect_err("must fail");
         assert!(format!("{err}").contains("entry not found"));
     }
 
+    #[test]
+    fn pi_branches_render_each_leaf_once_from_divergence_point() {
+        let raw = r#"{"type":"session","version":3,"id":"12cb4c19-2774-4de4-a0d0-9fa32fbae29f","timestamp":"2026-02-23T13:00:12.780Z","cwd":"/tmp/project"}
+{"type":"message","id":"a1b2c3d4","parentId":null,"timestamp":"2026-02-23T13:00:13.000Z","message":{"role":"user","content":[{"type":"text","text":"root"}]}}
+{"type":"message","id":"b1b2c3d4","parentId":"a1b2c3d4","timestamp":"2026-02-23T13:00:14.000Z","message":{"role":"assistant","content":[{"type":"text","text":"root done"}]}}
+{"type":"message","id":"c1b2c3d4","parentId":"b1b2c3d4","timestamp":"2026-02-23T13:00:15.000Z","message":{"role":"user","content":[{"type":"text","text":"branch one"}]}}
+{"type":"message","id":"d1b2c3d4","parentId":"a1b2c3d4","timestamp":"2026-02-23T13:00:16.000Z","message":{"role":"assistant","content":[{"type":"text","text":"retry"}]}}
+{"type":"message","id":"e1b2c3d4","parentId":"b1b2c3d4","timestamp":"2026-02-23T13:00:17.000Z","message":{"role":"user","content":[{"type":"text","text":"branch two"}]}}"#;
+
+        let uri = AgentsUri::parse("pi://12cb4c19-2774-4de4-a0d0-9fa32fbae29f").expect("parse uri");
+        let output = render_pi_branches_markdown(&uri, raw).expect("render");
+
+        let latest = output
+            .find("## Branch 1: `agents://pi/12cb4c19-2774-4de4-a0d0-9fa32fbae29f/e1b2c3d4`")
+            .expect("latest branch first");
+        let one = output
+            .find("## Branch 2: `agents://pi/12cb4c19-2774-4de4-a0d0-9fa32fbae29f/c1b2c3d4`")
+            .expect("branch one");
+        let retry = output
+            .find("## Branch 3: `agents://pi/12cb4c19-2774-4de4-a0d0-9fa32fbae29f/d1b2c3d4`")
+            .expect("retry branch");
+        assert!(latest < one && one < retry);
+        assert_eq!(output.matches("root done").count(), 1);
+        assert!(output.contains("- Diverges after: `agents://pi/12cb4c19-2774-4de4-a0d0-9fa32fbae29f/a1b2c3d4`\n\n### 2. Assistant\n\nretry"));
+    }
+
+    #[test]
+    fn pi_diff_reports_missing_entry() {
+        let raw = r#"{"type":"message","id":"a1b2c3d4","parentId":null,"message":{"role":"user","content":[{"type":"text","text":"root"}]}}"#;
+        let left = AgentsUri::parse("pi://12cb4c19-2774-4de4-a0d0-9fa32fbae29f/a1b2c3d4")
+            .expect("parse uri");
+        let right = AgentsUri::parse("pi://12cb4c19-2774-4de4-a0d0-9fa32fbae29f/deadbeef")
+            .expect("parse uri");
+
+        let err = render_pi_diff_markdown(&left, &right, raw).expect_err("must fail");
+        assert!(format!("{err}").contains("entry_id=deadbeef"));
+    }
+
     #[test]
     fn codex_renders_compact_events_in_timeline() {
         let raw = r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"hello"}]}}
@@ -757,7 +1956,8 @@ mod tests {
 
         let uri =
             AgentsUri::parse("codex://019c871c-b1f9-7f60-9c4f-87ed09f13592").expect("parse uri");
-        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw).expect("render");
+        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw, &RenderOptions::default())
+            .expect("render");
 
         assert!(output.contains("## 1. User"));
         assert!(output.contains("## 2. Context Compacted"));
@@ -765,6 +1965,80 @@ mod tests {
         assert!(output.contains("## 3. Assistant"));
     }
 
+    #[test]
+    fn codex_renders_compacted_summary_as_context_summary() {
+        let raw = r#"{"type":"compacted","payload":{"message":"User asked for a migration plan; draft is in PLAN.md.","replacement_history":[]}}
+{"type":"event_msg","payload":{"type":"context_compacted"}}
+{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"continue"}]}}"#;
+
+        let uri =
+            AgentsUri::parse("codex://019c871c-b1f9-7f60-9c4f-87ed09f13592").expect("parse uri");
+        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw, &RenderOptions::default())
+            .expect("render");
+
+        assert!(output.contains(
+            "## 1. Context Summary\n\nUser asked for a migration plan; draft is in PLAN.md."
+        ));
+        assert!(!output.contains("Context Compacted"));
+        assert!(output.contains("## 2. User"));
+        assert!(codex_rollout_is_compacted(raw.as_bytes()));
+        assert!(!codex_rollout_is_compacted(
+            r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"compacted"}]}}"#.as_bytes()
+        ));
+    }
+
+    #[test]
+    fn max_bytes_truncates_body_with_notice() {
+        let raw = r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"first prompt"}]}}
+{"type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"second reply that is cut"}]}}
+{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"never rendered"}]}}"#;
+        let uri =
+            AgentsUri::parse("codex://019c871c-b1f9-7f60-9c4f-87ed09f13592").expect("parse uri");
+        let options = RenderOptions {
+            max_bytes: Some(60),
+            ..RenderOptions::default()
+        };
+        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw, &options).expect("render");
+        let body = output.split_once("---\n\n").expect("frontmatter").1;
+
+        assert!(body.contains("## 1. User\n\nfirst prompt"));
+        assert!(!body.contains("never rendered"));
+        assert!(body.ends_with(
+            "\n\n_Output truncated at 60 bytes; rerun with a larger --max-bytes to see the rest._\n"
+        ));
+        let kept = body.split_once("\n\n_Output truncated").expect("notice").0;
+        assert_eq!(kept.len(), 60);
+    }
+
+    #[test]
+    fn grep_renders_matching_entries_with_context() {
+        let raw = r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"why does it crash?"}]}}
+{"type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"one\ntwo\nthree\nPanic in scheduler\nfour\nfive\nsix\nseven\npanic in scheduler again"}]}}
+{"type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":\"cargo test\"}","call_id":"call_1"}}
+{"type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"thread 'main' panicked: panic in scheduler"}}"#;
+        let uri =
+            AgentsUri::parse("codex://019c871c-b1f9-7f60-9c4f-87ed09f13592").expect("parse uri");
+        let options = RenderOptions {
+            grep: Some("panic in SCHEDULER".to_string()),
+            ..RenderOptions::default()
+        };
+        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw, &options).expect("render");
+
+        assert!(!output.contains("why does it crash?"));
+        assert!(output.contains(
+            "## Timeline Matches\n\n### 2. Assistant\n\n```text\n2- two\n3- three\n4: Panic in scheduler\n5- four\n6- five\n7- six\n8- seven\n9: panic in scheduler again\n```"
+        ));
+        assert!(output.contains("## Tool Call Matches\n\n### 1. Tool: shell\n\n"));
+        assert!(output.contains(": thread 'main' panicked: panic in scheduler\n"));
+
+        let options = RenderOptions {
+            grep: Some("not there".to_string()),
+            ..RenderOptions::default()
+        };
+        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw, &options).expect("render");
+        assert!(output.contains("_No matches for `not there`._"));
+    }
+
     #[test]
     fn claude_compact_summary_renders_as_compact_entry() {
         let raw = r#"{"type":"user","isCompactSummary":true,"message":{"role":"user","content":[{"type":"text","text":"Summary: old conversation"}]}}
@@ -772,11 +2046,42 @@ mod tests {
 
         let uri =
             AgentsUri::parse("claude://2823d1df-720a-4c31-ac55-ae8ba726721f").expect("parse uri");
-        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw).expect("render");
+        let output = render_markdown(&uri, Path::new("/tmp/mock"), raw, &RenderOptions::default())
+            .expect("render");
 
         assert!(output.contains("## 1. Context Compacted"));
         assert!(output.contains("Summary: old conversation"));
         assert!(!output.contains("## 1. User"));
         assert!(output.contains("## 2. Assistant"));
     }
+
+    #[test]
+    fn claude_events_render_only_when_shown() {
+        let raw = r#"{"type":"user","message":{"role":"user","content":"<command-name>/compact</command-name>\n<command-message>compact</command-message>\n<command-args>keep the plan</command-args>"}}
+{"type":"system","subtype":"compact_boundary","content":"Conversation compacted"}
+{"type":"system","content":"PostToolUse:Edit [cargo fmt] completed successfully","hookEvent":"PostToolUse"}
+{"type":"system","subtype":"informational","content":"Model switched to sonnet"}
+{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Done"}]}}"#;
+        let uri =
+            AgentsUri::parse("claude://2823d1df-720a-4c31-ac55-ae8ba726721f").expect("parse uri");
+
+        let hidden = render_markdown(&uri, Path::new("/tmp/mock"), raw, &RenderOptions::default())
+            .expect("render");
+        assert!(!hidden.contains("Hook:"));
+        assert!(hidden.contains("## 2. Context Compacted"));
+        assert!(hidden.contains("## 3. Assistant"));
+
+        let options = RenderOptions {
+            show_events: true,
+            ..RenderOptions::default()
+        };
+        let shown = render_markdown(&uri, Path::new("/tmp/mock"), raw, &options).expect("render");
+        assert!(shown.contains("## 1. Command: /compact\n\nkeep the plan\n\n"));
+        assert!(shown.contains("## 2. Context Compacted"));
+        assert!(shown.contains(
+            "## 3. Hook: PostToolUse\n\nPostToolUse:Edit [cargo fmt] completed successfully"
+        ));
+        assert!(shown.contains("## 4. System: informational\n\nModel switched to sonnet"));
+        assert!(shown.contains("## 5. Assistant"));
+    }
 }
@@ -0,0 +1,304 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use walkdir::{DirEntry, WalkDir};
+
+use crate::query::glob_regex;
+use crate::timestamp::format_duration;
+
+/// Per-directory ignore file; its globs apply to the paths below the directory it is in.
+pub const IGNORE_FILE_NAME: &str = ".xurlignore";
+
+/// Bounds on the directory walks that discover thread files under provider roots, carried in
+/// [`ProviderRoots::scan`](crate::ProviderRoots::scan).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Directory levels below a root to descend into; unbounded when `None`.
+    pub max_depth: Option<usize>,
+    /// Descend into symlinked directories and read symlinked files.
+    pub follow_symlinks: bool,
+    /// Stop walking a root after this long and keep the files found so far.
+    ///
+    /// The walk runs on its own thread, which is told to stop when the time runs out and does
+    /// so before its next directory entry. A read that hangs, as on an unresponsive network
+    /// mount, cannot be interrupted, so that thread lives on until the read returns; nothing
+    /// it finds afterwards is used.
+    pub timeout: Option<Duration>,
+    /// Path globs like `**/node_modules/**` to skip, from the `ignore` config key.
+    pub ignore: Vec<String>,
+}
+
+enum ScanEvent {
+    File(DirEntry),
+    Skipped(String),
+}
+
+/// Regular files under `root`, walked within `options`.
+///
+/// Paths matching an `ignore` glob, or a glob in a `.xurlignore` file of a directory above
+/// them, are skipped without a warning, and ignored directories are not walked. Other parts of
+/// the tree left out are reported in `warnings`: unreadable entries, symlinks when they are not
+/// followed, directories below `max_depth`, and whatever the walk had not reached
+/// when the timeout ran out.
+pub(crate) fn scan_files(
+    root: &Path,
+    options: &ScanOptions,
+    warnings: &mut Vec<String>,
+) -> Vec<DirEntry> {
+    let mut files = Vec::new();
+    let Some(timeout) = options.timeout else {
+        walk(root, options, &AtomicBool::new(false), |event| {
+            match event {
+                ScanEvent::File(entry) => files.push(entry),
+                ScanEvent::Skipped(warning) => warnings.push(warning),
+            }
+            true
+        });
+        return files;
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let (walk_root, walk_options, walk_stop) = (root.to_path_buf(), options.clone(), stop.clone());
+    thread::spawn(move || {
+        walk(&walk_root, &walk_options, &walk_stop, |event| {
+            sender.send(event).is_ok()
+        });
+    });
+    let deadline = Instant::now() + timeout;
+    loop {
+        match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(ScanEvent::File(entry)) => files.push(entry),
+            Ok(ScanEvent::Skipped(warning)) => warnings.push(warning),
+            Err(RecvTimeoutError::Timeout) => {
+                stop.store(true, Ordering::Relaxed);
+                warnings.push(format!(
+                    "scan of {} timed out after {}; threads not reached yet are missing",
+                    root.display(),
+                    format_duration(timeout)
+                ));
+                break;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    files
+}
+
+/// Walks `root`, handing each event to `emit` until it returns `false` or `stop` is set.
+fn walk(
+    root: &Path,
+    options: &ScanOptions,
+    stop: &AtomicBool,
+    mut emit: impl FnMut(ScanEvent) -> bool,
+) {
+    let mut walker = WalkDir::new(root).follow_links(options.follow_symlinks);
+    if let Some(depth) = options.max_depth {
+        walker = walker.max_depth(depth);
+    }
+    let mut ignore = IgnoreRules::new(&options.ignore);
+
+    let mut symlinks = Vec::<PathBuf>::new();
+    let mut too_deep = 0usize;
+    let mut entries = walker.into_iter();
+    while let Some(entry) = entries.next() {
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Ok(entry) = &entry {
+            let is_dir = entry.file_type().is_dir();
+            if ignore.is_ignored(entry.path(), is_dir) {
+                if is_dir {
+                    entries.skip_current_dir();
+                }
+                continue;
+            }
+            if is_dir {
+                ignore.read_ignore_file(entry.path());
+            }
+        }
+        let event = match entry {
+            Ok(entry) if entry.file_type().is_file() => ScanEvent::File(entry),
+            Ok(entry) if entry.path_is_symlink() && !options.follow_symlinks => {
+                symlinks.push(entry.into_path());
+                continue;
+            }
+            Ok(entry) => {
+                if entry.file_type().is_dir() && options.max_depth == Some(entry.depth()) {
+                    too_deep += 1;
+                }
+                continue;
+            }
+            Err(err) => ScanEvent::Skipped(format!(
+                "skipped {}: {err}",
+                err.path().unwrap_or(root).display()
+            )),
+        };
+        if !emit(event) {
+            return;
+        }
+    }
+
+    if let Some(first) = symlinks.first() {
+        emit(ScanEvent::Skipped(format!(
+            "skipped {} symlinks under {} (like {}); pass --follow-symlinks to follow them",
+            symlinks.len(),
+            root.display(),
+            first.display()
+        )));
+    }
+    if let Some(depth) = options.max_depth.filter(|_| too_deep > 0) {
+        emit(ScanEvent::Skipped(format!(
+            "skipped {too_deep} directories under {} deeper than --max-depth {depth}",
+            root.display()
+        )));
+    }
+}
+
+/// Globs from the config, plus those of the `.xurlignore` files read so far, each scoped to
+/// the directory its file is in.
+struct IgnoreRules {
+    global: Vec<Regex>,
+    scoped: Vec<(PathBuf, Vec<Regex>)>,
+}
+
+impl IgnoreRules {
+    fn new(globs: &[String]) -> Self {
+        Self {
+            global: globs.iter().map(|glob| glob_regex(glob)).collect(),
+            scoped: Vec::new(),
+        }
+    }
+
+    /// Reads `dir/.xurlignore`: one glob per line, with blank lines and `#` comments skipped.
+    fn read_ignore_file(&mut self, dir: &Path) {
+        let Ok(raw) = fs::read_to_string(dir.join(IGNORE_FILE_NAME)) else {
+            return;
+        };
+        let globs = raw
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(glob_regex)
+            .collect::<Vec<_>>();
+        if !globs.is_empty() {
+            self.scoped.push((dir.to_path_buf(), globs));
+        }
+    }
+
+    /// A directory is also tested with a trailing `/`, so `**/vendor/**` prunes `vendor`
+    /// itself instead of every file below it.
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let matches = |globs: &[Regex], candidate: &str| {
+            globs.iter().any(|glob| {
+                glob.is_match(candidate) || (is_dir && glob.is_match(&format!("{candidate}/")))
+            })
+        };
+        if matches(&self.global, &path.to_string_lossy()) {
+            return true;
+        }
+        self.scoped.iter().any(|(dir, globs)| {
+            path.strip_prefix(dir)
+                .ok()
+                .filter(|relative| !relative.as_os_str().is_empty())
+                .is_some_and(|relative| matches(globs, &format!("/{}", relative.to_string_lossy())))
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    use tempfile::tempdir;
+
+    use super::{ScanOptions, scan_files};
+
+    #[test]
+    fn scan_reports_subtrees_it_leaves_out() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("sessions");
+        fs::create_dir_all(root.join("2026/02/23")).expect("mkdir");
+        fs::write(root.join("top.jsonl"), "{}").expect("write");
+        fs::write(root.join("2026/02/23/deep.jsonl"), "{}").expect("write");
+        let outside = temp.path().join("backup");
+        fs::create_dir_all(&outside).expect("mkdir");
+        fs::write(outside.join("linked.jsonl"), "{}").expect("write");
+        symlink(&outside, root.join("backup")).expect("symlink");
+
+        let names = |options: ScanOptions, warnings: &mut Vec<String>| {
+            let mut names = scan_files(&root, &options, warnings)
+                .into_iter()
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        let mut warnings = Vec::new();
+        let bounded = ScanOptions {
+            max_depth: Some(2),
+            ..ScanOptions::default()
+        };
+        assert_eq!(names(bounded, &mut warnings), vec!["top.jsonl"]);
+        assert_eq!(warnings.len(), 2, "{warnings:?}");
+        assert!(warnings[0].starts_with("skipped 1 symlinks under "));
+        assert!(warnings[0].ends_with("pass --follow-symlinks to follow them"));
+        assert!(warnings[1].contains("skipped 1 directories under "));
+        assert!(warnings[1].ends_with("deeper than --max-depth 2"));
+
+        let mut warnings = Vec::new();
+        let unbounded = ScanOptions {
+            follow_symlinks: true,
+            ..ScanOptions::default()
+        };
+        assert_eq!(
+            names(unbounded, &mut warnings),
+            vec!["deep.jsonl", "linked.jsonl", "top.jsonl"]
+        );
+        assert!(warnings.is_empty(), "{warnings:?}");
+    }
+
+    #[test]
+    fn scan_skips_ignored_paths_without_warnings() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("sessions");
+        for dir in [
+            "2026/02/23",
+            "node_modules/pkg",
+            "backup/2026",
+            "nested/old",
+        ] {
+            fs::create_dir_all(root.join(dir)).expect("mkdir");
+        }
+        fs::write(root.join("2026/02/23/kept.jsonl"), "{}").expect("write");
+        fs::write(root.join("2026/02/23/notes.tmp"), "{}").expect("write");
+        fs::write(root.join("node_modules/pkg/vendored.jsonl"), "{}").expect("write");
+        fs::write(root.join("backup/2026/copy.jsonl"), "{}").expect("write");
+        fs::write(root.join("nested/old/stale.jsonl"), "{}").expect("write");
+        fs::write(root.join("nested/fresh.jsonl"), "{}").expect("write");
+        fs::write(root.join(".xurlignore"), "# copies\n\nbackup/\n*.tmp\n").expect("write");
+        fs::write(root.join("nested/.xurlignore"), "/old/**\n").expect("write");
+
+        let mut warnings = Vec::new();
+        let options = ScanOptions {
+            ignore: vec!["**/node_modules/**".to_string()],
+            ..ScanOptions::default()
+        };
+        let mut names = scan_files(&root, &options, &mut warnings)
+            .into_iter()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name != ".xurlignore")
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["fresh.jsonl", "kept.jsonl"]);
+        assert!(warnings.is_empty(), "{warnings:?}");
+    }
+}
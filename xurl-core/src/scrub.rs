@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+use crate::error::{Result, XurlError};
+use crate::model::ScrubKind;
+
+// `/home/alice`, `/Users/alice`, and `C:\Users\alice`, with the user name captured.
+static HOME_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?P<prefix>/home/|/Users/|\b[A-Za-z]:\\+Users\\+)(?P<user>[A-Za-z0-9._-]+)")
+        .expect("valid regex")
+});
+static EMAIL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\b[A-Z0-9._%+-]+@[A-Z0-9-]+(?:\.[A-Z0-9-]+)*\.[A-Z]{2,}\b")
+        .expect("valid regex")
+});
+// The host of a URL, after any `user@`. Hosts without a dot, like `agents://codex` or
+// `localhost`, are left alone by the caller.
+static URL_HOST_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?P<prefix>\b[A-Za-z][A-Za-z0-9+.-]*://(?:[^@/\s]+@)?)(?P<host>[A-Za-z0-9.-]+)")
+        .expect("valid regex")
+});
+static INTERNAL_HOST_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)\b[a-z0-9-]+(?:\.[a-z0-9-]+)*\.(?:local|internal|lan|corp|intranet|home\.arpa)\b",
+    )
+    .expect("valid regex")
+});
+
+/// Account names too generic to rewrite outside of home paths.
+const GENERIC_USERS: &[&str] = &["admin", "guest", "root", "shared", "user"];
+
+/// Rewrites home paths, user names, emails, and hostnames into placeholders like `[user-1]`,
+/// `[email-1]`, and `[host-1]`.
+///
+/// A value gets the same placeholder every time one scrubber sees it, so one scrubber should
+/// be used for a whole export. User names are learned from home paths, starting with the
+/// current user's, and are then also rewritten where they appear on their own.
+#[derive(Debug, Clone, Default)]
+pub struct Scrubber {
+    kinds: BTreeSet<ScrubKind>,
+    placeholders: BTreeMap<String, String>,
+    users: BTreeSet<String>,
+}
+
+impl Scrubber {
+    pub fn new(kinds: &[ScrubKind]) -> Self {
+        let mut scrubber = Self {
+            kinds: kinds.iter().copied().collect(),
+            ..Self::default()
+        };
+        if scrubber.kinds.contains(&ScrubKind::Paths)
+            && let Some(home) = dirs::home_dir()
+        {
+            scrubber.scrub_homes(&home.display().to_string());
+        }
+        scrubber
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+
+    pub fn scrub(&mut self, text: &str) -> String {
+        let mut text = text.to_string();
+        if self.kinds.contains(&ScrubKind::Emails) {
+            text = EMAIL_RE
+                .replace_all(&text, |captures: &Captures| {
+                    self.placeholder("email", &captures[0])
+                })
+                .into_owned();
+        }
+        if self.kinds.contains(&ScrubKind::Hostnames) {
+            text = URL_HOST_RE
+                .replace_all(&text, |captures: &Captures| {
+                    let host = &captures["host"];
+                    if host.contains('.') {
+                        format!("{}{}", &captures["prefix"], self.placeholder("host", host))
+                    } else {
+                        captures[0].to_string()
+                    }
+                })
+                .into_owned();
+            text = INTERNAL_HOST_RE
+                .replace_all(&text, |captures: &Captures| {
+                    self.placeholder("host", &captures[0])
+                })
+                .into_owned();
+        }
+        if self.kinds.contains(&ScrubKind::Paths) {
+            text = self.scrub_homes(&text);
+            text = self.scrub_users(&text);
+        }
+        text
+    }
+
+    /// Placeholders handed out so far, mapped to the values they replaced.
+    pub fn mapping(&self) -> BTreeMap<String, String> {
+        self.placeholders
+            .iter()
+            .map(|(original, placeholder)| (placeholder.clone(), original.clone()))
+            .collect()
+    }
+
+    /// The `mapping` as a JSON object, for `xurl export --scrub-map`.
+    pub fn mapping_json(&self) -> Result<String> {
+        let mut output = serde_json::to_string_pretty(&self.mapping())
+            .map_err(|err| XurlError::Serialization(err.to_string()))?;
+        output.push('\n');
+        Ok(output)
+    }
+
+    fn scrub_homes(&mut self, text: &str) -> String {
+        HOME_RE
+            .replace_all(text, |captures: &Captures| {
+                let user = &captures["user"];
+                if !GENERIC_USERS.contains(&user) {
+                    self.users.insert(user.to_string());
+                }
+                format!("{}{}", &captures["prefix"], self.placeholder("user", user))
+            })
+            .into_owned()
+    }
+
+    fn scrub_users(&mut self, text: &str) -> String {
+        let names = self
+            .users
+            .iter()
+            .filter(|user| user.len() >= 3)
+            .map(|user| regex::escape(user))
+            .collect::<Vec<_>>();
+        if names.is_empty() {
+            return text.to_string();
+        }
+        let users_re = Regex::new(&format!(r"\b(?:{})\b", names.join("|"))).expect("valid regex");
+        users_re
+            .replace_all(text, |captures: &Captures| {
+                self.placeholder("user", &captures[0])
+            })
+            .into_owned()
+    }
+
+    fn placeholder(&mut self, kind: &str, original: &str) -> String {
+        if let Some(placeholder) = self.placeholders.get(original) {
+            return placeholder.clone();
+        }
+        let prefix = format!("[{kind}-");
+        let next = self
+            .placeholders
+            .values()
+            .filter(|placeholder| placeholder.starts_with(&prefix))
+            .count()
+            + 1;
+        let placeholder = format!("{prefix}{next}]");
+        self.placeholders
+            .insert(original.to_string(), placeholder.clone());
+        placeholder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scrubber;
+    use crate::model::ScrubKind;
+
+    #[test]
+    fn scrub_rewrites_values_into_stable_placeholders() {
+        let mut scrubber = Scrubber::new(&[ScrubKind::Emails, ScrubKind::Hostnames]);
+        scrubber.kinds.insert(ScrubKind::Paths);
+
+        let first = scrubber.scrub(
+            "alice@example.com edited /home/alice/app/main.rs on https://git.corp.example.com/app",
+        );
+        let second = scrubber
+            .scrub("ping alice at alice@example.com or build01.internal; agents://codex is kept");
+        assert_eq!(
+            first,
+            "[email-1] edited /home/[user-1]/app/main.rs on https://[host-1]/app"
+        );
+        assert_eq!(
+            second,
+            "ping [user-1] at [email-1] or [host-2]; agents://codex is kept"
+        );
+        assert_eq!(
+            scrubber.mapping().get("[host-2]").map(String::as_str),
+            Some("build01.internal")
+        );
+        assert_eq!(scrubber.mapping().len(), 4);
+    }
+}
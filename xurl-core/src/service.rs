@@ -1,25 +1,43 @@
 use std::cmp::Reverse;
-use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::time::UNIX_EPOCH;
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use grep::regex::RegexMatcherBuilder;
 use grep::searcher::{BinaryDetection, SearcherBuilder, sinks::Lossy};
 use regex::RegexBuilder;
 use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
 use serde_json::Value;
-use walkdir::WalkDir;
+use sha2::{Digest, Sha256};
+use tracing::debug;
 
+use crate::attachments;
 use crate::error::{Result, XurlError};
+use crate::findings;
+use crate::format;
+use crate::frontmatter::push_yaml_fields;
+use crate::git;
 use crate::jsonl;
+use crate::links;
+use crate::lint;
+use crate::metadata;
 use crate::model::{
-    MessageRole, PiEntryListItem, PiEntryListView, PiEntryQuery, ProviderKind, ResolvedSkill,
-    ResolvedThread, SubagentDetailView, SubagentExcerptMessage, SubagentLifecycleEvent,
-    SubagentListItem, SubagentListView, SubagentQuery, SubagentRelation, SubagentThreadRef,
-    SubagentView, ThreadQuery, ThreadQueryItem, ThreadQueryResult, WriteRequest, WriteResult,
+    CommitBlame, CommitBlameItem, DoctorReport, FileChange, FsckIssue, FsckIssueKind, FsckReport,
+    HealthCheck, HealthStatus, LineRange, LintIssueKind, LintReport, McpServerUsage, MessageRole,
+    PiEntryListItem, PiEntryListView, PiEntryQuery, ProviderHealth, ProviderKind, QuerySort,
+    RawSlice, RecentThreads, RenderOptions, ResolutionMeta, ResolvedSkill, ResolvedThread,
+    RunningThread, RunningThreads, SubagentDescendant, SubagentDetailView, SubagentExcerptMessage,
+    SubagentLifecycleEvent, SubagentListItem, SubagentListOptions, SubagentListView,
+    SubagentLongest, SubagentQuery, SubagentRelation, SubagentSort, SubagentThreadRef,
+    SubagentTotals, SubagentView, ThreadComparison, ThreadExport, ThreadFindings, ThreadLinks,
+    ThreadQuery, ThreadQueryItem, ThreadQueryResult, ThreadStatus, ToolCall, WriteRequest,
+    WriteResult,
 };
+use crate::process;
 use crate::provider::amp::AmpProvider;
 use crate::provider::claude::ClaudeProvider;
 use crate::provider::codex::CodexProvider;
@@ -27,9 +45,16 @@ use crate::provider::gemini::GeminiProvider;
 use crate::provider::opencode::OpencodeProvider;
 use crate::provider::pi::PiProvider;
 use crate::provider::skills::SkillsProvider;
-use crate::provider::{Provider, ProviderRoots, WriteEventSink};
+use crate::provider::{Provider, ProviderCli, ProviderRoots, WriteEventSink};
+use crate::query::{self, SearchQuery, SearchUnit};
+use crate::rank;
 use crate::render;
-use crate::uri::{AgentsUri, SkillsUri, is_uuid_session_id};
+use crate::scan::{ScanOptions, scan_files};
+use crate::status;
+use crate::timestamp::{self, TimeZone};
+use crate::tools;
+use crate::uri::{AgentsUri, FileUri, SkillsUri, is_uuid_session_id};
+use crate::usage;
 
 const STATUS_PENDING_INIT: &str = "pendingInit";
 const STATUS_RUNNING: &str = "running";
@@ -162,52 +187,350 @@ impl Default for PiDiscoveredChild {
     }
 }
 
+/// Finds the thread `uri` names. With [`ProviderRoots::strict`] set, a thread with parse
+/// warnings fails with [`XurlError::StrictParse`].
 pub fn resolve_thread(uri: &AgentsUri, roots: &ProviderRoots) -> Result<ResolvedThread> {
+    let uri = &expand_latest_alias(uri, roots)?;
+    let resolved = locate_thread(uri, roots).map(check_format_version);
+    match &resolved {
+        Ok(resolved) => debug!(
+            source = %resolved.metadata.source,
+            candidates = resolved.metadata.candidate_count,
+            duplicates_skipped = resolved.metadata.duplicates_skipped,
+            path = %resolved.path.display(),
+            "resolved {}",
+            uri.as_agents_string()
+        ),
+        Err(err) => debug!(error = %err, "failed to resolve {}", uri.as_agents_string()),
+    }
+    enforce_strict_parsing(&uri.as_agents_string(), resolved?, roots.strict)
+}
+
+/// Reads the file `uri` names as a `provider` thread, without looking in any provider root.
+/// The session id comes from the file name: its trailing UUID, as in a Codex rollout name,
+/// else the name without its extension. With `strict`, parse warnings fail the read as
+/// [`resolve_thread`] does under [`ProviderRoots::strict`].
+pub fn resolve_file_thread(
+    uri: &FileUri,
+    provider: ProviderKind,
+    strict: bool,
+) -> Result<(AgentsUri, ResolvedThread)> {
+    fs::metadata(&uri.path).map_err(|source| XurlError::Io {
+        path: uri.path.clone(),
+        source,
+    })?;
+    let stem = uri
+        .path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let session_id = stem
+        .len()
+        .checked_sub(36)
+        .and_then(|start| stem.get(start..))
+        .filter(|tail| is_uuid_session_id(tail))
+        .unwrap_or(&stem)
+        .to_string();
+    let resolved = ResolvedThread {
+        provider,
+        session_id: session_id.clone(),
+        path: uri.path.clone(),
+        metadata: ResolutionMeta {
+            source: "file".to_string(),
+            candidate_count: 1,
+            ..ResolutionMeta::default()
+        },
+    };
+    let resolved =
+        enforce_strict_parsing(&uri.as_string(), check_format_version(resolved), strict)?;
+    let thread_uri = AgentsUri {
+        provider,
+        session_id,
+        agent_id: None,
+        query: Vec::new(),
+    };
+    Ok((thread_uri, resolved))
+}
+
+/// The fields of a `file://` head, in the order they are written.
+#[derive(Serialize)]
+struct FileHead<'a> {
+    uri: String,
+    provider: ProviderKind,
+    session_id: &'a str,
+    path: String,
+    raw_bytes: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+/// The frontmatter of a `file://` read: the file and the provider it is read as.
+pub fn render_file_head_markdown(uri: &FileUri, resolved: &ResolvedThread) -> Result<String> {
+    let mut output = String::from("---\n");
+    push_yaml_fields(
+        &mut output,
+        &FileHead {
+            uri: uri.as_string(),
+            provider: resolved.provider,
+            session_id: &resolved.session_id,
+            path: resolved.path.display().to_string(),
+            raw_bytes: fs::metadata(&resolved.path)
+                .map(|metadata| metadata.len())
+                .unwrap_or_default(),
+            warnings: unique_warnings(&resolved.metadata.warnings),
+        },
+    )?;
+    output.push_str("---\n");
+    Ok(output)
+}
+
+fn enforce_strict_parsing(
+    uri: &str,
+    resolved: ResolvedThread,
+    strict: bool,
+) -> Result<ResolvedThread> {
+    if strict {
+        let problems = parse_problems(&resolved)?;
+        if !problems.is_empty() {
+            return Err(XurlError::StrictParse {
+                uri: uri.to_string(),
+                problems,
+            });
+        }
+    }
+    Ok(resolved)
+}
+
+/// Finds the thread file of `uri` without reading any of it: no format version check and no
+/// `--strict` parse check, so callers that only read part of the file never touch the rest.
+/// Unlike [`resolve_thread`], a `latest` alias must already be expanded.
+pub fn locate_thread(uri: &AgentsUri, roots: &ProviderRoots) -> Result<ResolvedThread> {
     let session_id = uri.require_session_id()?;
-    match uri.provider {
-        ProviderKind::Amp => AmpProvider::new(&roots.amp_root).resolve(session_id),
-        ProviderKind::Codex => CodexProvider::new(&roots.codex_root).resolve(session_id),
-        ProviderKind::Claude => ClaudeProvider::new(&roots.claude_root).resolve(session_id),
-        ProviderKind::Gemini => GeminiProvider::new(&roots.gemini_root).resolve(session_id),
-        ProviderKind::Pi => PiProvider::new(&roots.pi_root).resolve(session_id),
+    let started = Instant::now();
+    let resolved = match uri.provider {
+        ProviderKind::Amp => AmpProvider::new(&roots.amp_root)
+            .with_cache_root(&roots.amp_cache_root)
+            .resolve(session_id),
+        ProviderKind::Codex => CodexProvider::new(&roots.codex_root)
+            .with_scan_options(&roots.scan)
+            .resolve(session_id),
+        ProviderKind::Claude => ClaudeProvider::new(&roots.claude_root)
+            .with_scan_options(&roots.scan)
+            .resolve(session_id),
+        ProviderKind::Gemini => GeminiProvider::new(&roots.gemini_root)
+            .with_scan_options(&roots.scan)
+            .resolve(session_id),
+        ProviderKind::Pi => PiProvider::new(&roots.pi_root)
+            .with_scan_options(&roots.scan)
+            .resolve(session_id),
         ProviderKind::Opencode => OpencodeProvider::new(&roots.opencode_root).resolve(session_id),
+    };
+    resolved.map(|mut resolved| {
+        resolved.metadata.elapsed = started.elapsed();
+        resolved
+    })
+}
+
+/// The warnings `--strict` turns into errors.
+fn parse_problems(resolved: &ResolvedThread) -> Result<Vec<String>> {
+    let raw = read_thread_raw(&resolved.path)?;
+    let (_, issues) = lint::lint_thread_payload(resolved.provider, &raw);
+    let mut problems = issues
+        .into_iter()
+        .filter(|issue| {
+            matches!(
+                issue.kind,
+                LintIssueKind::MalformedJson | LintIssueKind::UnknownRecordType
+            )
+        })
+        .map(|issue| match issue.line {
+            Some(line) => format!("line {line}: {}", issue.message),
+            None => issue.message,
+        })
+        .collect::<Vec<_>>();
+    if resolved.provider == ProviderKind::Opencode && resolved.metadata.corrupt_lines > 0 {
+        problems.push(jsonl::corrupt_lines_warning(
+            resolved.metadata.corrupt_lines,
+        ));
+    }
+    problems.extend(
+        format::detect_format_version(resolved.provider, raw.as_bytes())
+            .and_then(|version| format::format_version_warning(resolved.provider, &version)),
+    );
+    Ok(problems)
+}
+
+/// Warns when the rollout declares a format version newer than xurl knows, since readers skip
+/// entries they do not recognize.
+fn check_format_version(mut resolved: ResolvedThread) -> ResolvedThread {
+    if matches!(
+        resolved.provider,
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Pi
+    ) && let Ok(file) = fs::File::open(&resolved.path)
+        && let Some(version) =
+            format::detect_format_version(resolved.provider, BufReader::new(file))
+        && let Some(warning) = format::format_version_warning(resolved.provider, &version)
+    {
+        resolved.metadata.warnings.push(warning);
     }
+    resolved
 }
 
 pub fn resolve_skill(uri: &SkillsUri, roots: &ProviderRoots) -> Result<ResolvedSkill> {
     SkillsProvider::new(&roots.skills_root, &roots.skills_cache_root).resolve(uri)
 }
 
+const WRITE_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const WRITE_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Forwards write events while remembering whether the provider reported a session.
+struct AttemptSink<'a> {
+    inner: &'a mut dyn WriteEventSink,
+    session_ready: bool,
+    bytes_streamed: u64,
+}
+
+impl WriteEventSink for AttemptSink<'_> {
+    fn on_session_ready(&mut self, provider: ProviderKind, session_id: &str) -> Result<()> {
+        self.session_ready = true;
+        self.inner.on_session_ready(provider, session_id)
+    }
+
+    fn on_text_delta(&mut self, text: &str) -> Result<()> {
+        self.bytes_streamed += text.len() as u64;
+        self.inner.on_text_delta(text)
+    }
+}
+
 pub fn write_thread(
     provider: ProviderKind,
     roots: &ProviderRoots,
     req: &WriteRequest,
     sink: &mut dyn WriteEventSink,
+) -> Result<WriteResult> {
+    if req.child_id.is_some() && provider != ProviderKind::Pi {
+        return Err(XurlError::InvalidMode(
+            "write mode only supports main thread URIs: agents://<provider>/<session_id>"
+                .to_string(),
+        ));
+    }
+
+    // Only failures before a session id was reported are retried, so a retry never
+    // forks or duplicates a conversation the provider already started.
+    let attempts = req.options.retries.saturating_add(1);
+    let started = Instant::now();
+    let mut retry_warnings = Vec::new();
+    let mut bytes_streamed = 0;
+    let mut attempt = 1;
+    loop {
+        let mut attempt_sink = AttemptSink {
+            inner: &mut *sink,
+            session_ready: false,
+            bytes_streamed: 0,
+        };
+        let outcome = write_thread_once(provider, roots, req, &mut attempt_sink);
+        bytes_streamed += attempt_sink.bytes_streamed;
+        match outcome {
+            Ok(mut result) => {
+                result.duration = started.elapsed();
+                result.bytes_streamed = bytes_streamed;
+                if !retry_warnings.is_empty() {
+                    retry_warnings.push(format!(
+                        "write succeeded on attempt {attempt} of {attempts}"
+                    ));
+                    retry_warnings.append(&mut result.warnings);
+                    result.warnings = retry_warnings;
+                }
+                return Ok(result);
+            }
+            Err(err @ XurlError::CommandFailed { .. })
+                if attempt < attempts && !attempt_sink.session_ready =>
+            {
+                let delay = WRITE_RETRY_BASE_DELAY
+                    .saturating_mul(2u32.saturating_pow(attempt - 1))
+                    .min(WRITE_RETRY_MAX_DELAY);
+                debug!(attempt, attempts, delay_ms = delay.as_millis(), error = %err, "retrying write");
+                retry_warnings.push(format!(
+                    "attempt {attempt} of {attempts} failed, retried after {}ms: {err}",
+                    delay.as_millis()
+                ));
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// One write of [`multicast_write`]: the provider, its request, and where its events go.
+pub struct MulticastWrite<'a> {
+    pub provider: ProviderKind,
+    pub request: WriteRequest,
+    pub sink: &'a mut (dyn WriteEventSink + Send),
+}
+
+/// Runs every write at once, each on its own thread with its own sink, and returns the
+/// results in the order the writes were given. A failed write does not stop the others.
+pub fn multicast_write(
+    writes: Vec<MulticastWrite<'_>>,
+    roots: &ProviderRoots,
+) -> Vec<Result<WriteResult>> {
+    std::thread::scope(|scope| {
+        let handles = writes
+            .into_iter()
+            .map(|write| {
+                scope.spawn(move || write_thread(write.provider, roots, &write.request, write.sink))
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
+
+fn write_thread_once(
+    provider: ProviderKind,
+    roots: &ProviderRoots,
+    req: &WriteRequest,
+    sink: &mut dyn WriteEventSink,
 ) -> Result<WriteResult> {
     match provider {
         ProviderKind::Amp => AmpProvider::new(&roots.amp_root).write(req, sink),
-        ProviderKind::Codex => CodexProvider::new(&roots.codex_root).write(req, sink),
-        ProviderKind::Claude => ClaudeProvider::new(&roots.claude_root).write(req, sink),
-        ProviderKind::Gemini => GeminiProvider::new(&roots.gemini_root).write(req, sink),
-        ProviderKind::Pi => PiProvider::new(&roots.pi_root).write(req, sink),
+        ProviderKind::Codex => CodexProvider::new(&roots.codex_root)
+            .with_scan_options(&roots.scan)
+            .write(req, sink),
+        ProviderKind::Claude => ClaudeProvider::new(&roots.claude_root)
+            .with_scan_options(&roots.scan)
+            .write(req, sink),
+        ProviderKind::Gemini => GeminiProvider::new(&roots.gemini_root)
+            .with_scan_options(&roots.scan)
+            .write(req, sink),
+        ProviderKind::Pi => PiProvider::new(&roots.pi_root)
+            .with_scan_options(&roots.scan)
+            .write(req, sink),
         ProviderKind::Opencode => OpencodeProvider::new(&roots.opencode_root).write(req, sink),
     }
 }
 
 #[derive(Debug, Clone)]
-enum QuerySearchTarget {
+pub(crate) enum QuerySearchTarget {
     File(PathBuf),
     Text(String),
 }
 
 #[derive(Debug, Clone)]
-struct QueryCandidate {
-    thread_id: String,
-    uri: String,
-    thread_source: String,
-    updated_at: Option<String>,
-    updated_epoch: Option<u64>,
-    search_target: QuerySearchTarget,
+pub(crate) struct QueryCandidate {
+    pub(crate) thread_id: String,
+    pub(crate) uri: String,
+    pub(crate) thread_source: String,
+    pub(crate) updated_at: Option<String>,
+    pub(crate) updated_epoch: Option<u64>,
+    pub(crate) search_target: QuerySearchTarget,
 }
 
 pub fn query_threads(query: &ThreadQuery, roots: &ProviderRoots) -> Result<ThreadQueryResult> {
@@ -216,30 +539,87 @@ pub fn query_threads(query: &ThreadQuery, roots: &ProviderRoots) -> Result<Threa
         .iter()
         .map(|key| format!("ignored query parameter: {key}"))
         .collect::<Vec<_>>();
+    if query.project.is_some() && query.provider != ProviderKind::Claude {
+        warnings.push("ignored query parameter: project".to_string());
+    }
+    let keyword_filter = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    let offset = query_page_offset(query)?;
+    let search = keyword_filter.map(SearchQuery::parse).transpose()?;
+    // Only the free words of `q` are ranked; field and regex terms just filter.
+    let rank_words = search.as_ref().and_then(SearchQuery::phrase);
+    if query.sort == QuerySort::Relevance && rank_words.is_none() {
+        warnings.push("sort=relevance requires words in q to rank; sorted by recency".to_string());
+    }
+
+    let mut candidates = collect_query_candidates(
+        query.provider,
+        roots,
+        &mut warnings,
+        query.q.as_deref().is_some_and(|q| !q.trim().is_empty())
+            || query
+                .role
+                .as_deref()
+                .is_some_and(|role| !role.trim().is_empty()),
+    )?;
+    debug!(
+        provider = %query.provider,
+        candidates = candidates.len(),
+        "collected query candidates"
+    );
 
-    let mut candidates = match query.provider {
-        ProviderKind::Amp => collect_amp_query_candidates(roots, &mut warnings),
-        ProviderKind::Codex => collect_codex_query_candidates(roots, &mut warnings),
-        ProviderKind::Claude => collect_claude_query_candidates(roots, &mut warnings),
-        ProviderKind::Gemini => collect_gemini_query_candidates(roots, &mut warnings),
-        ProviderKind::Pi => collect_pi_query_candidates(roots, &mut warnings),
-        ProviderKind::Opencode => collect_opencode_query_candidates(
-            roots,
-            &mut warnings,
-            query.q.as_deref().is_some_and(|q| !q.trim().is_empty())
-                || query
-                    .role
-                    .as_deref()
-                    .is_some_and(|role| !role.trim().is_empty()),
-        )?,
-    };
+    if query.provider == ProviderKind::Claude
+        && let Some(project) = query.project.as_deref()
+    {
+        let project_dirs = claude_project_dirs(roots, project);
+        candidates.retain(|candidate| {
+            let source = Path::new(&candidate.thread_source);
+            project_dirs.iter().any(|dir| source.starts_with(dir))
+        });
+    }
+    if let Some(workdir) = query.workdir.as_deref() {
+        let workdir = absolute_dir(workdir);
+        candidates.retain(|candidate| {
+            thread_in_dir(
+                query.provider,
+                Path::new(&candidate.thread_source),
+                &candidate.thread_id,
+                &workdir,
+                roots,
+            )
+        });
+    }
+    if let Some(repo) = query.repo.as_deref() {
+        // Sessions of one project share a few directories; read each one's git config once.
+        let mut origins = HashMap::<PathBuf, Option<String>>::new();
+        candidates.retain(|candidate| {
+            let Some(cwd) = thread_cwd(
+                query.provider,
+                Path::new(&candidate.thread_source),
+                &candidate.thread_id,
+                roots,
+            ) else {
+                return false;
+            };
+            origins
+                .entry(cwd)
+                .or_insert_with_key(|cwd| git::repo_info(cwd).and_then(|info| info.origin))
+                .as_deref()
+                .is_some_and(|origin| git::origin_matches(origin, repo))
+        });
+    }
 
-    candidates.sort_by_key(|candidate| Reverse(candidate.updated_epoch.unwrap_or(0)));
+    // Ties break on the URI so pages split the same way on every run.
+    candidates.sort_by(|left, right| {
+        Reverse(left.updated_epoch.unwrap_or(0))
+            .cmp(&Reverse(right.updated_epoch.unwrap_or(0)))
+            .then_with(|| left.uri.cmp(&right.uri))
+    });
 
     if query.limit == 0 {
         return Ok(ThreadQueryResult {
             query: query.clone(),
             items: Vec::new(),
+            next_cursor: None,
             warnings,
         });
     }
@@ -249,10 +629,14 @@ pub fn query_threads(query: &ThreadQuery, roots: &ProviderRoots) -> Result<Threa
         .as_deref()
         .map(str::trim)
         .filter(|q| !q.is_empty());
-    let keyword_filter = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    // Relevance ranks every match, so it cannot stop at the first `limit` recent ones.
+    let rank_all = rank_words.is_some() && query.sort == QuerySort::Relevance;
+    // One match past the page tells whether another page follows.
+    let wanted = offset.saturating_add(query.limit).saturating_add(1);
     let mut items = Vec::new();
+    let mut documents = Vec::new();
     for candidate in &candidates {
-        if items.len() >= query.limit {
+        if !rank_all && items.len() >= wanted {
             break;
         }
 
@@ -264,8 +648,8 @@ pub fn query_threads(query: &ThreadQuery, roots: &ProviderRoots) -> Result<Threa
             }
         }
 
-        let matched_preview = if let Some(keyword_filter) = keyword_filter {
-            let matched_preview = match_candidate_preview(candidate, keyword_filter)?;
+        let matched_preview = if let Some(search) = &search {
+            let matched_preview = match_candidate_search(query.provider, candidate, search, roots)?;
             if matched_preview.is_none() {
                 continue;
             }
@@ -274,142 +658,1986 @@ pub fn query_threads(query: &ThreadQuery, roots: &ProviderRoots) -> Result<Threa
             role_preview
         };
 
+        let status = candidate_status(query.provider, candidate, roots);
+        if query.status.is_some_and(|wanted| wanted != status) {
+            continue;
+        }
+
+        if rank_words.is_some() {
+            documents.push(candidate_search_text(query.provider, candidate)?);
+        }
         items.push(ThreadQueryItem {
             thread_id: candidate.thread_id.clone(),
             uri: candidate.uri.clone(),
             thread_source: candidate.thread_source.clone(),
             updated_at: candidate.updated_at.clone(),
             matched_preview,
+            score: None,
+            status: Some(status),
+        });
+    }
+
+    if let Some(rank_words) = rank_words {
+        for (item, score) in items
+            .iter_mut()
+            .zip(rank::bm25_scores(rank_words, &documents))
+        {
+            item.score = Some(score);
+        }
+    }
+    if rank_all {
+        // Stable, so equal scores keep their recency order.
+        items.sort_by(|left, right| {
+            right
+                .score
+                .unwrap_or(0.0)
+                .total_cmp(&left.score.unwrap_or(0.0))
         });
     }
 
+    let next_offset = offset.saturating_add(query.limit);
+    let next_cursor = (items.len() > next_offset).then(|| query_page_cursor(query, next_offset));
+    let items = items.into_iter().skip(offset).take(query.limit).collect();
+
     Ok(ThreadQueryResult {
-        query: query.clone(),
+        query: ThreadQuery {
+            offset,
+            ..query.clone()
+        },
         items,
+        next_cursor,
         warnings,
     })
 }
 
-pub fn render_thread_query_head_markdown(result: &ThreadQueryResult) -> String {
-    let mut output = String::new();
-    output.push_str("---\n");
-    push_yaml_string(&mut output, "uri", &result.query.uri);
-    push_yaml_string(&mut output, "provider", &result.query.provider.to_string());
-    push_yaml_string(&mut output, "mode", "thread_query");
-    push_yaml_string(&mut output, "limit", &result.query.limit.to_string());
-    if let Some(role) = &result.query.role {
-        push_yaml_string(&mut output, "role", role);
-    }
+/// Reads a query candidate's status from its thread file, or from the export of an OpenCode
+/// session. Threads that cannot be read are `Unknown`.
+fn candidate_status(
+    provider: ProviderKind,
+    candidate: &QueryCandidate,
+    roots: &ProviderRoots,
+) -> ThreadStatus {
+    let path = match &candidate.search_target {
+        QuerySearchTarget::File(path) => path.clone(),
+        QuerySearchTarget::Text(_) => {
+            match AgentsUri::parse(&candidate.uri).and_then(|uri| resolve_thread(&uri, roots)) {
+                Ok(resolved) => resolved.path,
+                Err(_) => return ThreadStatus::Unknown,
+            }
+        }
+    };
+    open_thread_reader(&path).map_or(ThreadStatus::Unknown, |reader| {
+        status::thread_status(provider, reader)
+    })
+}
 
-    if let Some(q) = &result.query.q {
-        push_yaml_string(&mut output, "q", q);
-    }
+/// Builds the cursor for the page of `query` starting at `offset`: the offset plus a
+/// fingerprint of the parameters that pick and order matches, so a cursor is only accepted
+/// by the query it came from. `limit` is left out so page sizes can change between pages.
+fn query_page_cursor(query: &ThreadQuery, offset: usize) -> String {
+    let fingerprint = Sha256::digest(
+        [
+            query.provider.to_string(),
+            query.role.clone().unwrap_or_default(),
+            query.q.clone().unwrap_or_default(),
+            query.sort.to_string(),
+            query.project.clone().unwrap_or_default(),
+            query
+                .status
+                .map(|status| status.to_string())
+                .unwrap_or_default(),
+            query.workdir.clone().unwrap_or_default(),
+            query.repo.clone().unwrap_or_default(),
+        ]
+        .join("\0")
+        .as_bytes(),
+    );
+    format!("{offset}.{}", &format!("{fingerprint:x}")[..12])
+}
 
-    output.push_str("threads:\n");
-    if result.items.is_empty() {
-        output.push_str("  []\n");
-    } else {
-        for item in &result.items {
-            push_yaml_string_with_indent(&mut output, 2, "thread_id", &item.thread_id);
-            push_yaml_string_with_indent(&mut output, 2, "uri", &item.uri);
-            push_yaml_string_with_indent(&mut output, 2, "thread_source", &item.thread_source);
-            if let Some(updated_at) = &item.updated_at {
-                push_yaml_string_with_indent(&mut output, 2, "updated_at", updated_at);
-            }
-            if let Some(matched_preview) = &item.matched_preview {
-                push_yaml_string_with_indent(&mut output, 2, "matched_preview", matched_preview);
-            }
+fn query_page_offset(query: &ThreadQuery) -> Result<usize> {
+    let Some(cursor) = query.cursor.as_deref() else {
+        return Ok(query.offset);
+    };
+    cursor
+        .split_once('.')
+        .and_then(|(offset, _)| offset.parse::<usize>().ok())
+        .filter(|offset| query_page_cursor(query, *offset) == cursor)
+        .ok_or_else(|| {
+            XurlError::InvalidUri(format!(
+                "{} (cursor={cursor} does not belong to this query; start again without cursor)",
+                query.uri
+            ))
+        })
+}
+
+pub fn recent_threads(
+    provider: Option<ProviderKind>,
+    limit: usize,
+    roots: &ProviderRoots,
+) -> Result<RecentThreads> {
+    let providers = provider.map_or_else(|| ProviderKind::ALL.to_vec(), |provider| vec![provider]);
+    let mut warnings = Vec::new();
+    let mut scored = Vec::new();
+    for provider in providers {
+        for candidate in collect_query_candidates(provider, roots, &mut warnings, false)? {
+            // OpenCode stores millisecond timestamps; normalize so providers sort together.
+            let updated_secs = match provider {
+                ProviderKind::Opencode => candidate.updated_epoch.map(|stamp| stamp / 1000),
+                _ => candidate.updated_epoch,
+            };
+            scored.push((updated_secs.unwrap_or(0), candidate));
         }
     }
+    scored.sort_by_key(|(updated_secs, _)| Reverse(*updated_secs));
 
-    render_warnings(&mut output, &result.warnings);
-    output.push_str("---\n");
-    output
+    let items = scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, candidate)| ThreadQueryItem {
+            thread_id: candidate.thread_id,
+            uri: candidate.uri,
+            thread_source: candidate.thread_source,
+            updated_at: candidate.updated_at,
+            matched_preview: None,
+            score: None,
+            status: None,
+        })
+        .collect();
+
+    Ok(RecentThreads {
+        provider,
+        items,
+        warnings,
+    })
 }
 
-pub fn render_thread_query_markdown(result: &ThreadQueryResult) -> String {
-    let mut output = render_thread_query_head_markdown(result);
-    output.push('\n');
-    output.push_str("# Threads\n\n");
-    output.push_str(&format!("- Provider: `{}`\n", result.query.provider));
-    if let Some(role) = &result.query.role {
-        output.push_str(&format!("- Role: `{}`\n", role));
-    } else {
-        output.push_str("- Role: `_none_`\n");
-    }
-    output.push_str(&format!("- Limit: `{}`\n", result.query.limit));
-    if let Some(q) = &result.query.q {
-        output.push_str(&format!("- Query: `{}`\n", q));
-    } else {
-        output.push_str("- Query: `_none_`\n");
-    }
-    output.push_str(&format!("- Matched: `{}`\n\n", result.items.len()));
+/// Lists main threads whose recorded working directory is `cwd` or below it, newest first.
+pub fn workspace_threads(
+    provider: Option<ProviderKind>,
+    cwd: &Path,
+    limit: usize,
+    roots: &ProviderRoots,
+) -> Result<RecentThreads> {
+    let recent = recent_threads(provider, usize::MAX, roots)?;
+    let items = recent
+        .items
+        .into_iter()
+        .filter(|item| {
+            AgentsUri::parse(&item.uri).is_ok_and(|uri| {
+                uri.agent_id.is_none()
+                    && thread_in_dir(
+                        uri.provider,
+                        Path::new(&item.thread_source),
+                        &item.thread_id,
+                        cwd,
+                        roots,
+                    )
+            })
+        })
+        .take(limit)
+        .collect();
 
-    if result.items.is_empty() {
-        output.push_str("_No threads found._\n");
-        return output;
-    }
+    Ok(RecentThreads {
+        provider,
+        items,
+        warnings: recent.warnings,
+    })
+}
 
-    for (index, item) in result.items.iter().enumerate() {
-        output.push_str(&format!("## {}. `{}`\n\n", index + 1, item.uri));
-        output.push_str(&format!("- Thread ID: `{}`\n", item.thread_id));
-        output.push_str(&format!("- Thread Source: `{}`\n", item.thread_source));
-        if let Some(updated_at) = &item.updated_at {
-            output.push_str(&format!("- Updated At: `{}`\n", updated_at));
+/// Lists main threads that look like they are running right now, newest first.
+///
+/// A thread is listed when a provider CLI process runs in the directory the thread recorded
+/// (the newest such thread of that provider gets the process), or when it was written within
+/// `active_within` and its status is `running` or `unknown`. Processes are only detected on
+/// Linux, from `/proc`.
+pub fn running_threads(
+    provider: Option<ProviderKind>,
+    active_within: Duration,
+    roots: &ProviderRoots,
+) -> Result<RunningThreads> {
+    let recent = recent_threads(provider, usize::MAX, roots)?;
+    let mut processes = process::agent_processes();
+    processes.retain(|process| provider.is_none_or(|provider| process.provider == provider));
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| i64::try_from(now.as_millis()).unwrap_or(i64::MAX));
+    let window = i64::try_from(active_within.as_millis()).unwrap_or(i64::MAX);
+
+    let mut items = Vec::new();
+    for item in &recent.items {
+        let Ok(uri) = AgentsUri::parse(&item.uri) else {
+            continue;
+        };
+        if uri.agent_id.is_some() {
+            continue;
         }
-        if let Some(matched_preview) = &item.matched_preview {
-            output.push_str(&format!("- Match: `{}`\n", matched_preview));
+        let active = item
+            .updated_at
+            .as_deref()
+            .and_then(|updated| updated.parse::<i64>().ok())
+            .is_some_and(|updated| now - timestamp::epoch_millis(updated) <= window);
+        let watched = processes
+            .iter()
+            .any(|process| process.provider == uri.provider);
+        if !active && !watched {
+            continue;
         }
-        output.push('\n');
+
+        let cwd = thread_cwd(
+            uri.provider,
+            Path::new(&item.thread_source),
+            &uri.session_id,
+            roots,
+        );
+        let pid = cwd.as_ref().and_then(|cwd| {
+            let idx = processes
+                .iter()
+                .position(|process| process.provider == uri.provider && &process.cwd == cwd)?;
+            Some(processes.remove(idx).pid)
+        });
+        if pid.is_none() && !active {
+            continue;
+        }
+        let path = if uri.provider == ProviderKind::Opencode {
+            resolve_thread(&uri, roots).map(|resolved| resolved.path)
+        } else {
+            Ok(PathBuf::from(&item.thread_source))
+        };
+        let status = path
+            .and_then(|path| open_thread_reader(&path))
+            .map_or(ThreadStatus::Unknown, |reader| {
+                status::thread_status(uri.provider, reader)
+            });
+        if pid.is_none() && matches!(status, ThreadStatus::Completed | ThreadStatus::Failed) {
+            continue;
+        }
+        items.push(RunningThread {
+            uri: item.uri.clone(),
+            provider: uri.provider,
+            status,
+            cwd: cwd.map(|cwd| cwd.display().to_string()),
+            updated_at: item.updated_at.clone(),
+            pid,
+        });
     }
 
-    output
+    Ok(RunningThreads {
+        items,
+        warnings: recent.warnings,
+    })
 }
 
-fn match_candidate_preview(candidate: &QueryCandidate, keyword: &str) -> Result<Option<String>> {
-    match &candidate.search_target {
-        QuerySearchTarget::File(path) => match_first_preview_in_file(path, keyword),
-        QuerySearchTarget::Text(text) => Ok(match_first_preview_in_text(text, keyword)),
-    }
+pub fn render_running_threads_json(threads: &RunningThreads) -> Result<String> {
+    to_raw_json(threads)
 }
 
-fn match_first_preview_in_file(path: &Path, keyword: &str) -> Result<Option<String>> {
-    let mut matcher_builder = RegexMatcherBuilder::new();
-    matcher_builder.fixed_strings(true).case_insensitive(true);
-    let matcher = matcher_builder
-        .build(keyword)
-        .map_err(|err| XurlError::InvalidMode(format!("invalid keyword query: {err}")))?;
-    let mut searcher = SearcherBuilder::new()
-        .binary_detection(BinaryDetection::quit(b'\x00'))
-        .line_number(true)
-        .build();
-    let mut preview = None::<String>;
-    searcher
-        .search_path(
-            &matcher,
-            path,
-            Lossy(|_, line| {
-                let line = line.trim();
-                if line.is_empty() {
-                    return Ok(true);
-                }
-                preview = Some(truncate_preview(line, 160));
-                Ok(false)
-            }),
-        )
-        .map_err(|source| XurlError::Io {
-            path: path.to_path_buf(),
-            source,
-        })?;
-    Ok(preview)
+/// The frontmatter of `xurl ps`.
+#[derive(Serialize)]
+struct RunningThreadsHead {
+    mode: &'static str,
+    threads: usize,
 }
 
-fn match_first_preview_in_text(text: &str, keyword: &str) -> Option<String> {
-    let matcher = RegexBuilder::new(&regex::escape(keyword))
-        .case_insensitive(true)
-        .build()
-        .ok()?;
-    let found = matcher.find(text)?;
+pub fn render_running_threads_markdown(
+    threads: &RunningThreads,
+    zone: &TimeZone,
+) -> Result<String> {
+    let mut output = String::from("---\n");
+    push_yaml_fields(
+        &mut output,
+        &RunningThreadsHead {
+            mode: "ps",
+            threads: threads.items.len(),
+        },
+    )?;
+    output.push_str("---\n\n");
+
+    output.push_str("# Running Threads\n\n");
+    if threads.items.is_empty() {
+        output.push_str("_No running threads found._\n");
+        return Ok(output);
+    }
+    for (index, item) in threads.items.iter().enumerate() {
+        output.push_str(&format!("## {}. `{}`\n\n", index + 1, item.uri));
+        output.push_str(&format!("- Provider: `{}`\n", item.provider));
+        output.push_str(&format!("- Status: `{}`\n", item.status));
+        if let Some(cwd) = &item.cwd {
+            output.push_str(&format!("- Workdir: `{cwd}`\n"));
+        }
+        if let Some(updated_at) = &item.updated_at {
+            output.push_str(&format!(
+                "- Updated At: `{}`\n",
+                render::format_timestamp(updated_at, zone)
+            ));
+        }
+        if let Some(pid) = item.pid {
+            output.push_str(&format!("- PID: `{pid}`\n"));
+        }
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Finds main threads with a tool output that mentions `commit_ish`, newest first.
+///
+/// The commit is resolved in the git repository containing `repo`. Outputs match on the full
+/// SHA or an abbreviation of at least seven digits, such as the `[main 1a2b3c4] ...` line
+/// printed by `git commit`.
+pub fn blame_commit(
+    commit_ish: &str,
+    provider: Option<ProviderKind>,
+    repo: &Path,
+    roots: &ProviderRoots,
+) -> Result<CommitBlame> {
+    let commit = git::resolve_commit(repo, commit_ish)?;
+    let recent = recent_threads(provider, usize::MAX, roots)?;
+    let mut warnings = recent.warnings;
+    let mut items = Vec::new();
+    for item in &recent.items {
+        let Ok(uri) = AgentsUri::parse(&item.uri) else {
+            continue;
+        };
+        if uri.agent_id.is_some() {
+            continue;
+        }
+        let resolved = match resolve_thread(&uri, roots) {
+            Ok(resolved) => resolved,
+            Err(err) => {
+                warnings.push(format!("skipped {}: {err}", item.uri));
+                continue;
+            }
+        };
+        let Ok(raw) = read_thread_raw(&resolved.path) else {
+            continue;
+        };
+        // Cheap pre-filter: every match contains the seven-digit abbreviation.
+        if !raw.contains(&commit[..7]) {
+            continue;
+        }
+        let calls = match tools::extract_tool_calls(uri.provider, &resolved.path, &raw) {
+            Ok(calls) => calls,
+            Err(err) => {
+                warnings.push(format!("skipped {}: {err}", item.uri));
+                continue;
+            }
+        };
+        if let Some(call) = calls.iter().find(|call| {
+            call.output
+                .as_deref()
+                .is_some_and(|output| git::mentions_commit(output, &commit))
+        }) {
+            items.push(CommitBlameItem {
+                uri: item.uri.clone(),
+                tool: call.name.clone(),
+                updated_at: item.updated_at.clone(),
+            });
+        }
+    }
+
+    Ok(CommitBlame {
+        commit,
+        items,
+        warnings,
+    })
+}
+
+/// Rewrites `@latest` / `@latest~N` (and `latest` / `@last`) session aliases to the matching
+/// main thread id. A `cwd` query parameter limits candidates to threads started in that
+/// directory or below it; without a value it uses the current directory.
+pub fn expand_latest_alias(uri: &AgentsUri, roots: &ProviderRoots) -> Result<AgentsUri> {
+    let Some(offset) = uri.latest_offset() else {
+        return Ok(uri.clone());
+    };
+
+    let cwd_filter = latest_alias_cwd_filter(uri)?;
+    let recent = recent_threads(Some(uri.provider), usize::MAX, roots)?;
+    let session_id = main_session_ids(recent.items.iter().filter(|item| {
+        cwd_filter.as_deref().is_none_or(|filter| {
+            thread_in_dir(
+                uri.provider,
+                Path::new(&item.thread_source),
+                &item.thread_id,
+                filter,
+                roots,
+            )
+        })
+    }))
+    .nth(offset)
+    .ok_or_else(|| XurlError::ThreadNotFound {
+        provider: uri.provider.to_string(),
+        session_id: uri.session_id.clone(),
+        searched_roots: vec![provider_root(uri.provider, roots).to_path_buf()],
+    })?;
+    debug!(
+        recent = recent.items.len(),
+        cwd = ?cwd_filter,
+        "expanded {} to session {session_id}",
+        uri.as_agents_string()
+    );
+
+    Ok(AgentsUri {
+        session_id,
+        query: uri
+            .query
+            .iter()
+            .filter(|(key, _)| key != "cwd")
+            .cloned()
+            .collect(),
+        ..uri.clone()
+    })
+}
+
+/// Rewrites a session id prefix from [`AgentsUri::parse_prefix`] to the one main thread id
+/// that starts with it. An id that matches a thread exactly is kept, and full-shape ids are
+/// passed through without listing threads.
+pub fn expand_session_prefix(uri: &AgentsUri, roots: &ProviderRoots) -> Result<AgentsUri> {
+    if uri.is_collection() || uri.latest_offset().is_some() {
+        return Ok(uri.clone());
+    }
+    if uri.provider != ProviderKind::Opencode && uri.has_full_session_id() {
+        return Ok(uri.clone());
+    }
+
+    let candidates = list_session_ids(uri.provider, roots)?
+        .into_iter()
+        .filter(|session_id| session_id.starts_with(&uri.session_id))
+        .collect::<Vec<_>>();
+    debug!(
+        candidates = candidates.len(),
+        "matched session id prefix {}", uri.session_id
+    );
+    let session_id = match candidates.as_slice() {
+        [] if uri.has_full_session_id() => return Ok(uri.clone()),
+        [] => {
+            return Err(XurlError::ThreadNotFound {
+                provider: uri.provider.to_string(),
+                session_id: uri.session_id.clone(),
+                searched_roots: vec![provider_root(uri.provider, roots).to_path_buf()],
+            });
+        }
+        [session_id] => session_id.clone(),
+        _ if candidates.contains(&uri.session_id) => uri.session_id.clone(),
+        _ => {
+            return Err(XurlError::AmbiguousSessionPrefix {
+                provider: uri.provider.to_string(),
+                prefix: uri.session_id.clone(),
+                candidates: candidates
+                    .iter()
+                    .map(|session_id| format!("agents://{}/{session_id}", uri.provider))
+                    .collect(),
+            });
+        }
+    };
+
+    Ok(AgentsUri {
+        session_id,
+        ..uri.clone()
+    })
+}
+
+/// Lists main session ids for a provider, newest first; used by shell completion.
+pub fn list_session_ids(provider: ProviderKind, roots: &ProviderRoots) -> Result<Vec<String>> {
+    let recent = recent_threads(Some(provider), usize::MAX, roots)?;
+    Ok(main_session_ids(recent.items.iter()).collect())
+}
+
+fn main_session_ids<'a>(
+    items: impl Iterator<Item = &'a ThreadQueryItem>,
+) -> impl Iterator<Item = String> {
+    let mut seen = BTreeSet::<String>::new();
+    items
+        .filter_map(|item| AgentsUri::parse(&item.uri).ok())
+        .filter(|candidate| candidate.agent_id.is_none())
+        .map(|candidate| candidate.session_id)
+        .filter(move |session_id| seen.insert(session_id.clone()))
+}
+
+fn latest_alias_cwd_filter(uri: &AgentsUri) -> Result<Option<PathBuf>> {
+    let Some((_, value)) = uri.query.iter().rev().find(|(key, _)| key == "cwd") else {
+        return Ok(None);
+    };
+    let current_dir = std::env::current_dir().map_err(|source| XurlError::Io {
+        path: PathBuf::from("."),
+        source,
+    })?;
+    Ok(Some(match value.as_deref() {
+        Some(value) if !value.is_empty() => current_dir.join(value),
+        _ => current_dir,
+    }))
+}
+
+fn thread_in_dir(
+    provider: ProviderKind,
+    source: &Path,
+    session_id: &str,
+    dir: &Path,
+    roots: &ProviderRoots,
+) -> bool {
+    if let Some(cwd) = thread_cwd(provider, source, session_id, roots) {
+        return cwd.starts_with(dir);
+    }
+    // Gemini only records the sha256 of the project root, so without a `.project_root` file
+    // a session can only match its exact project directory.
+    provider == ProviderKind::Gemini
+        && GeminiProvider::session_project_hash(source)
+            .is_some_and(|hash| hash == GeminiProvider::project_hash(dir))
+}
+
+/// `dir` resolved against the current directory when it is relative.
+fn absolute_dir(dir: &str) -> PathBuf {
+    let path = PathBuf::from(dir);
+    match std::env::current_dir() {
+        Ok(cwd) if path.is_relative() => cwd.join(path),
+        _ => path,
+    }
+}
+
+/// The directory a thread was started in, read from its thread file `source`, or for
+/// OpenCode from the session row of `session_id`.
+fn thread_cwd(
+    provider: ProviderKind,
+    source: &Path,
+    session_id: &str,
+    roots: &ProviderRoots,
+) -> Option<PathBuf> {
+    match provider {
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Pi => extract_jsonl_cwd(source),
+        ProviderKind::Amp => extract_amp_cwd(source),
+        ProviderKind::Opencode => fetch_opencode_session_directory(roots, session_id),
+        ProviderKind::Gemini => GeminiProvider::session_project_root(source),
+    }
+}
+
+fn extract_jsonl_cwd(path: &Path) -> Option<PathBuf> {
+    let file = fs::File::open(path).ok()?;
+    let reader = BufReader::new(file);
+    for line in reader.lines().take(50).map_while(std::result::Result::ok) {
+        let Ok(value) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        // Codex keeps cwd in session_meta/turn_context payloads; Claude and pi put it on the line.
+        let cwd = value
+            .get("cwd")
+            .or_else(|| value.get("payload").and_then(|payload| payload.get("cwd")))
+            .and_then(Value::as_str);
+        if let Some(cwd) = cwd.filter(|cwd| !cwd.is_empty()) {
+            return Some(PathBuf::from(cwd));
+        }
+    }
+    None
+}
+
+fn extract_amp_cwd(path: &Path) -> Option<PathBuf> {
+    let raw = fs::read_to_string(path).ok()?;
+    let value = serde_json::from_str::<Value>(&raw).ok()?;
+    value
+        .pointer("/env/initial/trees")?
+        .as_array()?
+        .iter()
+        .filter_map(|tree| tree.get("uri").and_then(Value::as_str))
+        .find_map(|uri| uri.strip_prefix("file://"))
+        .map(PathBuf::from)
+}
+
+fn fetch_opencode_session_directory(roots: &ProviderRoots, session_id: &str) -> Option<PathBuf> {
+    let conn = open_opencode_read_only_db(&opencode_db_path(roots)).ok()?;
+    conn.query_row(
+        "SELECT directory FROM session WHERE id = ?1 LIMIT 1",
+        [session_id],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .map(PathBuf::from)
+}
+
+pub(crate) fn provider_root(provider: ProviderKind, roots: &ProviderRoots) -> &Path {
+    match provider {
+        ProviderKind::Amp => &roots.amp_root,
+        ProviderKind::Codex => &roots.codex_root,
+        ProviderKind::Claude => &roots.claude_root,
+        ProviderKind::Gemini => &roots.gemini_root,
+        ProviderKind::Pi => &roots.pi_root,
+        ProviderKind::Opencode => &roots.opencode_root,
+    }
+}
+
+pub(crate) fn collect_query_candidates(
+    provider: ProviderKind,
+    roots: &ProviderRoots,
+    warnings: &mut Vec<String>,
+    with_search_text: bool,
+) -> Result<Vec<QueryCandidate>> {
+    Ok(match provider {
+        ProviderKind::Amp => collect_amp_query_candidates(roots, warnings),
+        ProviderKind::Codex => collect_codex_query_candidates(roots, warnings),
+        ProviderKind::Claude => collect_claude_query_candidates(roots, warnings),
+        ProviderKind::Gemini => collect_gemini_query_candidates(roots, warnings),
+        ProviderKind::Pi => collect_pi_query_candidates(roots, warnings),
+        ProviderKind::Opencode => {
+            collect_opencode_query_candidates(roots, warnings, with_search_text)?
+        }
+    })
+}
+
+/// The fields of a thread query head, in the order they are written.
+#[derive(Serialize)]
+struct ThreadQueryHead<'a> {
+    uri: &'a str,
+    provider: ProviderKind,
+    mode: &'static str,
+    limit: usize,
+    sort: String,
+    #[serde(skip_serializing_if = "is_zero")]
+    offset: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    q: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workdir: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repo: Option<&'a str>,
+    threads: Vec<ThreadQueryItemHead<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ThreadQueryItemHead<'a> {
+    thread_id: &'a str,
+    uri: &'a str,
+    thread_source: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    updated_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matched_preview: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+}
+
+pub fn render_thread_query_head_markdown(
+    result: &ThreadQueryResult,
+    zone: &TimeZone,
+) -> Result<String> {
+    let query = &result.query;
+    let threads = result
+        .items
+        .iter()
+        .map(|item| ThreadQueryItemHead {
+            thread_id: &item.thread_id,
+            uri: &item.uri,
+            thread_source: &item.thread_source,
+            updated_at: item
+                .updated_at
+                .as_deref()
+                .map(|updated_at| render::format_timestamp(updated_at, zone)),
+            matched_preview: item.matched_preview.as_deref(),
+            score: item.score.map(|score| (score * 1000.0).round() / 1000.0),
+            status: item.status.map(|status| status.to_string()),
+        })
+        .collect();
+    let mut output = String::from("---\n");
+    push_yaml_fields(
+        &mut output,
+        &ThreadQueryHead {
+            uri: &query.uri,
+            provider: query.provider,
+            mode: "thread_query",
+            limit: query.limit,
+            sort: query.sort.to_string(),
+            offset: query.offset,
+            next_cursor: result.next_cursor.as_deref(),
+            role: query.role.as_deref(),
+            q: query.q.as_deref(),
+            project: query.project.as_deref(),
+            status: query.status.map(|status| status.to_string()),
+            workdir: query.workdir.as_deref(),
+            repo: query.repo.as_deref(),
+            threads,
+            warnings: unique_warnings(&result.warnings),
+        },
+    )?;
+    output.push_str("---\n");
+    Ok(output)
+}
+
+pub fn render_thread_query_markdown(result: &ThreadQueryResult, zone: &TimeZone) -> Result<String> {
+    let mut output = render_thread_query_head_markdown(result, zone)?;
+    output.push('\n');
+    output.push_str("# Threads\n\n");
+    output.push_str(&format!("- Provider: `{}`\n", result.query.provider));
+    if let Some(role) = &result.query.role {
+        output.push_str(&format!("- Role: `{}`\n", role));
+    } else {
+        output.push_str("- Role: `_none_`\n");
+    }
+    output.push_str(&format!("- Limit: `{}`\n", result.query.limit));
+    output.push_str(&format!("- Sort: `{}`\n", result.query.sort));
+    if let Some(q) = &result.query.q {
+        output.push_str(&format!("- Query: `{}`\n", q));
+    } else {
+        output.push_str("- Query: `_none_`\n");
+    }
+    if let Some(project) = &result.query.project {
+        output.push_str(&format!("- Project: `{}`\n", project));
+    }
+    if let Some(status) = result.query.status {
+        output.push_str(&format!("- Status: `{status}`\n"));
+    }
+    if let Some(workdir) = &result.query.workdir {
+        output.push_str(&format!("- Workdir: `{workdir}`\n"));
+    }
+    if let Some(repo) = &result.query.repo {
+        output.push_str(&format!("- Repo: `{repo}`\n"));
+    }
+    output.push_str(&format!("- Matched: `{}`\n", result.items.len()));
+    if let Some(next_cursor) = &result.next_cursor {
+        output.push_str(&format!("- Next Cursor: `{next_cursor}`\n"));
+    }
+    output.push('\n');
+
+    if result.items.is_empty() {
+        output.push_str("_No threads found._\n");
+        return Ok(output);
+    }
+
+    for (index, item) in result.items.iter().enumerate() {
+        output.push_str(&format!(
+            "## {}. `{}`\n\n",
+            result.query.offset + index + 1,
+            item.uri
+        ));
+        output.push_str(&format!("- Thread ID: `{}`\n", item.thread_id));
+        output.push_str(&format!("- Thread Source: `{}`\n", item.thread_source));
+        if let Some(updated_at) = &item.updated_at {
+            output.push_str(&format!(
+                "- Updated At: `{}`\n",
+                render::format_timestamp(updated_at, zone)
+            ));
+        }
+        if let Some(status) = item.status {
+            output.push_str(&format!("- Status: `{status}`\n"));
+        }
+        if let Some(matched_preview) = &item.matched_preview {
+            output.push_str(&format!("- Match: `{}`\n", matched_preview));
+        }
+        if let Some(score) = item.score {
+            output.push_str(&format!("- Score: `{score:.3}`\n"));
+        }
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+/// Cross-checks a provider's sqlite index against files on disk. With `fix`, codex
+/// `threads.rollout_path` rows pointing at moved rollouts are updated to the found location.
+pub fn fsck_provider(
+    provider: ProviderKind,
+    roots: &ProviderRoots,
+    fix: bool,
+) -> Result<FsckReport> {
+    match provider {
+        ProviderKind::Codex => Ok(fsck_codex(roots, fix)),
+        ProviderKind::Opencode if fix => Err(XurlError::InvalidMode(
+            "fsck --fix only supports codex; opencode issues must be repaired by hand".to_string(),
+        )),
+        ProviderKind::Opencode => fsck_opencode(roots),
+        ProviderKind::Amp | ProviderKind::Claude | ProviderKind::Gemini | ProviderKind::Pi => {
+            Err(XurlError::UnsupportedFsckProvider(provider.to_string()))
+        }
+    }
+}
+
+fn fsck_codex(roots: &ProviderRoots, fix: bool) -> FsckReport {
+    let mut warnings = Vec::new();
+    let mut issues = Vec::new();
+    let indexes = CodexProvider::new(&roots.codex_root).state_db_paths();
+
+    let mut rollouts = BTreeMap::<String, Vec<PathBuf>>::new();
+    for root in [
+        roots.codex_root.join("sessions"),
+        roots.codex_root.join("archived_sessions"),
+    ] {
+        if !root.exists() {
+            continue;
+        }
+        for entry in scan_files(&root, &roots.scan, &mut warnings) {
+            let path = entry.into_path();
+            let is_rollout = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("rollout-") && name.ends_with(".jsonl"));
+            if !is_rollout {
+                continue;
+            }
+            match extract_codex_rollout_id(&path) {
+                Some(session_id) => rollouts.entry(session_id).or_default().push(path),
+                None => warnings.push(format!(
+                    "skipped rollout with unknown session id: {}",
+                    path.display()
+                )),
+            }
+        }
+    }
+    let files_on_disk = rollouts.values().map(Vec::len).sum();
+
+    let mut indexed = BTreeSet::<String>::new();
+    let mut indexed_threads = 0;
+    for db_path in &indexes {
+        let records = match CodexProvider::list_thread_records(db_path) {
+            Ok(records) => records,
+            Err(err) => {
+                warnings.push(format!(
+                    "failed reading sqlite thread index {}: {err}",
+                    db_path.display()
+                ));
+                continue;
+            }
+        };
+
+        for (row_id, record) in records {
+            indexed_threads += 1;
+            let session_id = row_id.to_ascii_lowercase();
+            if !record.rollout_path.exists() {
+                let found = rollouts.get(&session_id).and_then(|paths| paths.first());
+                let repair = match found {
+                    Some(found) => format!(
+                        "update threads.rollout_path to {} (rollout was moved)",
+                        found.display()
+                    ),
+                    None => "delete the stale threads row or restore the rollout file".to_string(),
+                };
+                let fixed = match found {
+                    Some(found) if fix => {
+                        match CodexProvider::update_rollout_path(db_path, &row_id, found) {
+                            Ok(_) => true,
+                            Err(err) => {
+                                warnings.push(format!(
+                                    "failed updating sqlite thread index {} for session_id={session_id}: {err}",
+                                    db_path.display()
+                                ));
+                                false
+                            }
+                        }
+                    }
+                    _ => false,
+                };
+                issues.push(FsckIssue {
+                    kind: FsckIssueKind::MissingRollout,
+                    session_id: session_id.clone(),
+                    source: format!(
+                        "{}#threads:{session_id} -> {}",
+                        db_path.display(),
+                        record.rollout_path.display()
+                    ),
+                    repair,
+                    fixed,
+                });
+            }
+            indexed.insert(session_id);
+        }
+    }
+
+    if indexes.is_empty() {
+        warnings.push(format!(
+            "no sqlite thread index found under {}; skipped orphan file checks",
+            roots.codex_root.display()
+        ));
+    }
+
+    for (session_id, paths) in &rollouts {
+        for path in paths {
+            if !indexes.is_empty() && !indexed.contains(session_id) {
+                issues.push(FsckIssue {
+                    kind: FsckIssueKind::OrphanFile,
+                    session_id: session_id.clone(),
+                    source: path.display().to_string(),
+                    repair:
+                        "restart codex to re-index it; xurl still resolves it by filesystem scan"
+                            .to_string(),
+                    fixed: false,
+                });
+            }
+
+            match count_codex_rollout_messages(path) {
+                Ok(0) => issues.push(FsckIssue {
+                    kind: FsckIssueKind::EmptySession,
+                    session_id: session_id.clone(),
+                    source: path.display().to_string(),
+                    repair: "archive or delete the rollout file".to_string(),
+                    fixed: false,
+                }),
+                Ok(_) => {}
+                Err(err) => warnings.push(err.to_string()),
+            }
+        }
+    }
+
+    FsckReport {
+        provider: ProviderKind::Codex,
+        indexes,
+        indexed_threads,
+        files_on_disk: Some(files_on_disk),
+        issues,
+        warnings,
+    }
+}
+
+fn count_codex_rollout_messages(path: &Path) -> Result<usize> {
+    let reader = match open_thread_reader(path) {
+        Ok(reader) => reader,
+        Err(XurlError::EmptyThreadFile { .. }) => return Ok(0),
+        Err(err) => return Err(err),
+    };
+    render::count_messages(ProviderKind::Codex, path, reader).map(|count| count.messages)
+}
+
+fn fsck_opencode(roots: &ProviderRoots) -> Result<FsckReport> {
+    let db_path = opencode_db_path(roots);
+    if !db_path.exists() {
+        return Ok(FsckReport {
+            provider: ProviderKind::Opencode,
+            indexes: Vec::new(),
+            indexed_threads: 0,
+            files_on_disk: None,
+            issues: Vec::new(),
+            warnings: vec![format!(
+                "opencode database not found: {}",
+                db_path.display()
+            )],
+        });
+    }
+
+    let conn = open_opencode_read_only_db(&db_path)?;
+    let sqlite_err = |source| XurlError::Sqlite {
+        path: db_path.clone(),
+        source,
+    };
+
+    let indexed_threads = conn
+        .query_row("SELECT COUNT(*) FROM session", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .map_err(sqlite_err)?;
+
+    let mut issues = Vec::new();
+    let mut empty_stmt = conn
+        .prepare(
+            "SELECT s.id
+             FROM session s
+             WHERE NOT EXISTS (SELECT 1 FROM message m WHERE m.session_id = s.id)
+             ORDER BY s.id",
+        )
+        .map_err(sqlite_err)?;
+    let empty_rows = empty_stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(sqlite_err)?;
+    for row in empty_rows {
+        let session_id = row.map_err(sqlite_err)?;
+        issues.push(FsckIssue {
+            kind: FsckIssueKind::EmptySession,
+            source: format!("{}#session:{session_id}", db_path.display()),
+            session_id,
+            repair: "delete the session row; it has no messages".to_string(),
+            fixed: false,
+        });
+    }
+
+    let mut orphan_stmt = conn
+        .prepare(
+            "SELECT m.session_id, COUNT(*)
+             FROM message m
+             WHERE NOT EXISTS (SELECT 1 FROM session s WHERE s.id = m.session_id)
+             GROUP BY m.session_id
+             ORDER BY m.session_id",
+        )
+        .map_err(sqlite_err)?;
+    let orphan_rows = orphan_stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })
+        .map_err(sqlite_err)?;
+    for row in orphan_rows {
+        let (session_id, count) = row.map_err(sqlite_err)?;
+        issues.push(FsckIssue {
+            kind: FsckIssueKind::OrphanMessage,
+            source: format!(
+                "{}#message:session_id={session_id} ({count} rows)",
+                db_path.display()
+            ),
+            session_id,
+            repair: "delete message and part rows for the missing session".to_string(),
+            fixed: false,
+        });
+    }
+
+    Ok(FsckReport {
+        provider: ProviderKind::Opencode,
+        indexes: vec![db_path.clone()],
+        indexed_threads: usize::try_from(indexed_threads).unwrap_or_default(),
+        files_on_disk: None,
+        issues,
+        warnings: Vec::new(),
+    })
+}
+
+/// Returns the prompts a user typed in a thread, in order.
+///
+/// Context that providers inject as user messages, such as Codex `<environment_context>` and
+/// `AGENTS.md` instructions, is skipped so the prompts can be replayed elsewhere.
+pub fn thread_user_prompts(uri: &AgentsUri, resolved: &ResolvedThread) -> Result<Vec<String>> {
+    let raw = read_thread_raw(&resolved.path)?;
+    let messages = render::extract_messages(uri.provider, &resolved.path, &raw)?;
+    Ok(messages
+        .into_iter()
+        .filter(|message| message.role == MessageRole::User)
+        .map(|message| message.text.trim().to_string())
+        .filter(|text| !text.is_empty() && !is_injected_user_context(text))
+        .collect())
+}
+
+fn is_injected_user_context(text: &str) -> bool {
+    [
+        "<environment_context>",
+        "<user_instructions>",
+        "# AGENTS.md instructions",
+    ]
+    .iter()
+    .any(|prefix| text.starts_with(prefix))
+}
+
+/// Writes a thread to `out_dir/thread.md` with its embedded base64 content (images, documents,
+/// data URIs) decoded into `out_dir/attachments/`, linked from the markdown in their place.
+pub fn export_thread(
+    uri: &AgentsUri,
+    resolved: &ResolvedThread,
+    out_dir: &Path,
+) -> Result<ThreadExport> {
+    if uri.agent_id.is_some() {
+        return Err(XurlError::InvalidMode(
+            "extract requires a main thread URI: agents://<provider>/<session_id>".to_string(),
+        ));
+    }
+
+    let raw = read_thread_raw(&resolved.path)?;
+    let mut found = Vec::new();
+    let rewritten = match uri.provider {
+        ProviderKind::Amp | ProviderKind::Gemini => {
+            let mut value = serde_json::from_str::<Value>(&raw).map_err(|source| {
+                XurlError::InvalidJsonLine {
+                    path: resolved.path.clone(),
+                    line: 1,
+                    source,
+                }
+            })?;
+            attachments::extract_attachments(&mut value, &mut found);
+            value.to_string()
+        }
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Pi | ProviderKind::Opencode => {
+            let mut lines = Vec::new();
+            for line in raw.lines() {
+                match jsonl::parse_thread_line(line) {
+                    Some(mut value) => {
+                        attachments::extract_attachments(&mut value, &mut found);
+                        lines.push(value.to_string());
+                    }
+                    None => lines.push(line.to_string()),
+                }
+            }
+            lines.join("\n")
+        }
+    };
+
+    let write = |path: &Path, bytes: &[u8]| {
+        fs::write(path, bytes).map_err(|source| XurlError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    };
+    let attachments_dir = out_dir.join(attachments::ATTACHMENTS_DIR);
+    let create_dir = if found.is_empty() {
+        out_dir
+    } else {
+        &attachments_dir
+    };
+    fs::create_dir_all(create_dir).map_err(|source| XurlError::Io {
+        path: create_dir.to_path_buf(),
+        source,
+    })?;
+
+    let markdown =
+        render::render_markdown(uri, &resolved.path, &rewritten, &RenderOptions::default())?;
+    let markdown_path = out_dir.join("thread.md");
+    write(&markdown_path, markdown.as_bytes())?;
+
+    let mut attachment_paths = Vec::new();
+    for attachment in &found {
+        let path = attachments_dir.join(&attachment.file_name);
+        write(&path, &attachment.bytes)?;
+        attachment_paths.push(path);
+    }
+
+    Ok(ThreadExport {
+        markdown_path,
+        attachment_paths,
+    })
+}
+
+/// Extracts the file paths, URLs, and commits a thread references.
+pub fn thread_links(uri: &AgentsUri, resolved: &ResolvedThread) -> Result<ThreadLinks> {
+    let raw = read_thread_raw(&resolved.path)?;
+    let messages = render::extract_messages(uri.provider, &resolved.path, &raw)?;
+    let calls = tools::extract_tool_calls(uri.provider, &resolved.path, &raw)?;
+    let links = links::collect_links(&messages, &calls);
+    Ok(ThreadLinks {
+        uri: uri.as_agents_string(),
+        files: links.files,
+        urls: links.urls,
+        commits: links.commits,
+    })
+}
+
+/// Review findings (file, line, severity) that a thread's assistant messages report.
+pub fn thread_findings(uri: &AgentsUri, resolved: &ResolvedThread) -> Result<ThreadFindings> {
+    let raw = read_thread_raw(&resolved.path)?;
+    let messages = render::extract_messages(uri.provider, &resolved.path, &raw)?;
+    Ok(ThreadFindings {
+        uri: uri.as_agents_string(),
+        findings: findings::extract_findings(&messages),
+    })
+}
+
+/// Collects what `xurl compare` shows for one thread: its last assistant message, tool call
+/// counts, wall-clock duration, and token usage.
+pub fn compare_thread(uri: &AgentsUri, resolved: &ResolvedThread) -> Result<ThreadComparison> {
+    let raw = read_thread_raw(&resolved.path)?;
+    let final_answer = render::extract_messages(uri.provider, &resolved.path, &raw)?
+        .into_iter()
+        .rev()
+        .find(|message| message.role == MessageRole::Assistant)
+        .map(|message| message.text);
+    let mut counts = BTreeMap::<String, usize>::new();
+    for call in tools::extract_tool_calls(uri.provider, &resolved.path, &raw)? {
+        *counts.entry(call.name).or_default() += 1;
+    }
+    let mut tool_calls = counts.into_iter().collect::<Vec<_>>();
+    tool_calls.sort_by_key(|(_, count)| Reverse(*count));
+    Ok(ThreadComparison {
+        uri: uri.as_agents_string(),
+        provider: uri.provider,
+        final_answer,
+        tool_calls,
+        duration: render::thread_duration(uri.provider, &resolved.path, raw.as_bytes())?,
+        tokens: usage::token_usage(uri.provider, &raw),
+    })
+}
+
+/// The frontmatter of a thread comparison.
+#[derive(Serialize)]
+struct CompareHead<'a> {
+    mode: &'static str,
+    threads: Vec<&'a str>,
+}
+
+/// Renders threads side by side: a summary table, tool call counts per tool, then each
+/// thread's final answer.
+pub fn render_thread_comparison_markdown(threads: &[ThreadComparison]) -> Result<String> {
+    let mut output = String::from("---\n");
+    push_yaml_fields(
+        &mut output,
+        &CompareHead {
+            mode: "compare",
+            threads: threads.iter().map(|thread| thread.uri.as_str()).collect(),
+        },
+    )?;
+    output.push_str("---\n\n");
+
+    output.push_str("# Thread Comparison\n\n");
+    let header = threads
+        .iter()
+        .map(|thread| format!(" `{}` |", thread.uri))
+        .collect::<String>();
+    let divider = " --- |".repeat(threads.len());
+    let row = |output: &mut String, label: &str, cells: Vec<String>| {
+        output.push_str(&format!("| {label} |"));
+        for cell in cells {
+            output.push_str(&format!(" {cell} |"));
+        }
+        output.push('\n');
+    };
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+
+    output.push_str(&format!("| |{header}\n| --- |{divider}\n"));
+    row(
+        &mut output,
+        "Provider",
+        threads
+            .iter()
+            .map(|thread| thread.provider.to_string())
+            .collect(),
+    );
+    row(
+        &mut output,
+        "Duration",
+        threads
+            .iter()
+            .map(|thread| or_dash(thread.duration.map(timestamp::format_duration)))
+            .collect(),
+    );
+    row(
+        &mut output,
+        "Input tokens",
+        threads
+            .iter()
+            .map(|thread| or_dash(thread.tokens.map(|tokens| tokens.input_tokens.to_string())))
+            .collect(),
+    );
+    row(
+        &mut output,
+        "Output tokens",
+        threads
+            .iter()
+            .map(|thread| or_dash(thread.tokens.map(|tokens| tokens.output_tokens.to_string())))
+            .collect(),
+    );
+    row(
+        &mut output,
+        "Tool calls",
+        threads
+            .iter()
+            .map(|thread| {
+                thread
+                    .tool_calls
+                    .iter()
+                    .map(|(_, count)| count)
+                    .sum::<usize>()
+                    .to_string()
+            })
+            .collect(),
+    );
+    output.push('\n');
+
+    output.push_str("## Tool Usage\n\n");
+    let mut names = Vec::<&str>::new();
+    for thread in threads {
+        for (name, _) in &thread.tool_calls {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+    }
+    if names.is_empty() {
+        output.push_str("_No tool calls._\n\n");
+    } else {
+        output.push_str(&format!("| Tool |{header}\n| --- |{divider}\n"));
+        for name in names {
+            row(
+                &mut output,
+                &format!("`{name}`"),
+                threads
+                    .iter()
+                    .map(|thread| {
+                        thread
+                            .tool_calls
+                            .iter()
+                            .find(|(tool, _)| tool == name)
+                            .map_or(0, |(_, count)| *count)
+                            .to_string()
+                    })
+                    .collect(),
+            );
+        }
+        output.push('\n');
+    }
+
+    output.push_str("## Final Answers\n");
+    for thread in threads {
+        output.push_str(&format!("\n### `{}`\n\n", thread.uri));
+        match thread.final_answer.as_deref() {
+            Some(answer) => {
+                output.push_str(answer.trim_end());
+                output.push('\n');
+            }
+            None => output.push_str("_No assistant message._\n"),
+        }
+    }
+
+    Ok(output)
+}
+
+/// The fields of a `--resolve` head, in the order they are written.
+#[derive(Serialize)]
+struct ResolveHead<'a> {
+    uri: String,
+    provider: ProviderKind,
+    session_id: &'a str,
+    mode: &'static str,
+    path: String,
+    source: &'a str,
+    candidate_count: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    duplicates_skipped: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    corrupt_lines: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+/// Renders how `uri` resolved (canonical URI, thread file, discovery source, and candidate
+/// count) as frontmatter, without reading the thread.
+pub fn render_resolution_markdown(uri: &AgentsUri, resolved: &ResolvedThread) -> Result<String> {
+    let meta = &resolved.metadata;
+    let mut output = String::from("---\n");
+    push_yaml_fields(
+        &mut output,
+        &ResolveHead {
+            uri: uri.as_agents_string(),
+            provider: resolved.provider,
+            session_id: &resolved.session_id,
+            mode: "resolve",
+            path: resolved.path.display().to_string(),
+            source: &meta.source,
+            candidate_count: meta.candidate_count,
+            duplicates_skipped: meta.duplicates_skipped,
+            corrupt_lines: meta.corrupt_lines,
+            warnings: unique_warnings(&meta.warnings),
+        },
+    )?;
+    output.push_str("---\n");
+    Ok(output)
+}
+
+/// Checks the thread file against its provider's record shape and reports every issue found.
+pub fn lint_thread(uri: &AgentsUri, resolved: &ResolvedThread) -> Result<LintReport> {
+    let raw = read_thread_raw(&resolved.path)?;
+    let (records, issues) = lint::lint_thread_payload(uri.provider, &raw);
+    Ok(LintReport {
+        uri: uri.as_agents_string(),
+        provider: uri.provider,
+        path: resolved.path.clone(),
+        records,
+        issues,
+    })
+}
+
+/// The fields of a lint head, in the order they are written.
+#[derive(Serialize)]
+struct LintHead<'a> {
+    uri: &'a str,
+    provider: ProviderKind,
+    mode: &'static str,
+    path: String,
+    records: usize,
+    issues: usize,
+}
+
+pub fn render_lint_markdown(report: &LintReport) -> Result<String> {
+    let mut output = String::from("---\n");
+    push_yaml_fields(
+        &mut output,
+        &LintHead {
+            uri: &report.uri,
+            provider: report.provider,
+            mode: "lint",
+            path: report.path.display().to_string(),
+            records: report.records,
+            issues: report.issues.len(),
+        },
+    )?;
+    output.push_str("---\n\n");
+
+    output.push_str("# Lint\n\n");
+    if report.issues.is_empty() {
+        output.push_str("_No issues found._\n");
+        return Ok(output);
+    }
+
+    output.push_str("## Issues\n\n");
+    for issue in &report.issues {
+        match issue.line {
+            Some(line) => output.push_str(&format!(
+                "- line {line} `{}`: {}\n",
+                issue.kind, issue.message
+            )),
+            None => output.push_str(&format!("- `{}`: {}\n", issue.kind, issue.message)),
+        }
+    }
+
+    Ok(output)
+}
+
+pub fn render_thread_links_json(links: &ThreadLinks) -> Result<String> {
+    to_raw_json(links)
+}
+
+pub fn render_findings_json(findings: &ThreadFindings) -> Result<String> {
+    to_raw_json(findings)
+}
+
+/// Renders findings as a SARIF 2.1.0 log with one run, for code-scanning uploads.
+pub fn render_findings_sarif(findings: &ThreadFindings) -> Result<String> {
+    let results = findings
+        .findings
+        .iter()
+        .map(|finding| {
+            serde_json::json!({
+                "ruleId": "agent-review",
+                "level": finding.severity.to_string(),
+                "message": { "text": finding.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": finding.path },
+                        "region": { "startLine": finding.line },
+                    },
+                }],
+            })
+        })
+        .collect::<Vec<_>>();
+    to_raw_json(&serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "xurl",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [{
+                        "id": "agent-review",
+                        "shortDescription": { "text": "Finding reported in an agent review" },
+                    }],
+                },
+            },
+            "automationDetails": { "id": findings.uri },
+            "results": results,
+        }],
+    }))
+}
+
+/// The fields of a links head: how many of each kind of link the thread mentions.
+#[derive(Serialize)]
+struct LinksHead<'a> {
+    uri: &'a str,
+    mode: &'static str,
+    files: usize,
+    urls: usize,
+    commits: usize,
+}
+
+pub fn render_thread_links_markdown(links: &ThreadLinks) -> Result<String> {
+    let mut output = String::from("---\n");
+    push_yaml_fields(
+        &mut output,
+        &LinksHead {
+            uri: &links.uri,
+            mode: "links",
+            files: links.files.len(),
+            urls: links.urls.len(),
+            commits: links.commits.len(),
+        },
+    )?;
+    output.push_str("---\n\n");
+
+    output.push_str("# Thread Links\n\n");
+    for (heading, values) in [
+        ("Files", &links.files),
+        ("URLs", &links.urls),
+        ("Commits", &links.commits),
+    ] {
+        output.push_str(&format!("## {heading}\n\n"));
+        if values.is_empty() {
+            output.push_str("_None._\n\n");
+            continue;
+        }
+        for value in values {
+            output.push_str(&format!("- `{value}`\n"));
+        }
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+/// The fields of an fsck head, in the order they are written.
+#[derive(Serialize)]
+struct FsckHead {
+    provider: ProviderKind,
+    mode: &'static str,
+    indexes: Vec<String>,
+    indexed_threads: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files_on_disk: Option<usize>,
+    issues: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    fixed: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+pub fn render_fsck_markdown(report: &FsckReport) -> Result<String> {
+    let mut output = String::from("---\n");
+    push_yaml_fields(
+        &mut output,
+        &FsckHead {
+            provider: report.provider,
+            mode: "fsck",
+            indexes: report
+                .indexes
+                .iter()
+                .map(|index| index.display().to_string())
+                .collect(),
+            indexed_threads: report.indexed_threads,
+            files_on_disk: report.files_on_disk,
+            issues: report.issues.len(),
+            fixed: report.issues.iter().filter(|issue| issue.fixed).count(),
+            warnings: unique_warnings(&report.warnings),
+        },
+    )?;
+    output.push_str("---\n\n");
+
+    output.push_str("# Fsck\n\n");
+    output.push_str(&format!("- Provider: `{}`\n", report.provider));
+    output.push_str(&format!(
+        "- Indexed Threads: `{}`\n",
+        report.indexed_threads
+    ));
+    if let Some(files_on_disk) = report.files_on_disk {
+        output.push_str(&format!("- Files On Disk: `{files_on_disk}`\n"));
+    }
+    output.push_str(&format!("- Issues: `{}`\n\n", report.issues.len()));
+
+    if report.issues.is_empty() {
+        output.push_str("_No issues found._\n");
+        return Ok(output);
+    }
+
+    output.push_str("## Repair Plan\n\n");
+    for (index, issue) in report.issues.iter().enumerate() {
+        output.push_str(&format!(
+            "### {}. `{}` `{}`\n\n",
+            index + 1,
+            issue.kind,
+            issue.session_id
+        ));
+        output.push_str(&format!("- Source: `{}`\n", issue.source));
+        output.push_str(&format!("- Repair: {}\n", issue.repair));
+        if issue.fixed {
+            output.push_str("- Fixed: `yes`\n");
+        }
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+/// Checks each provider's data root, sqlite indexes, CLI, and credentials without changing
+/// anything: CLIs are only asked for `--version`, and Codex for `login status`.
+pub fn doctor(roots: &ProviderRoots) -> DoctorReport {
+    DoctorReport {
+        providers: ProviderKind::ALL
+            .into_iter()
+            .map(|provider| provider_health(provider, roots))
+            .collect(),
+    }
+}
+
+fn provider_health(provider: ProviderKind, roots: &ProviderRoots) -> ProviderHealth {
+    let cli = ProviderCli::of(provider);
+    let mut hints = Vec::new();
+
+    let (root_path, root_env) = match provider {
+        ProviderKind::Amp => (&roots.amp_root, "XDG_DATA_HOME"),
+        ProviderKind::Codex => (&roots.codex_root, "CODEX_HOME"),
+        ProviderKind::Claude => (&roots.claude_root, "CLAUDE_CONFIG_DIR"),
+        ProviderKind::Gemini => (&roots.gemini_root, "GEMINI_CLI_HOME"),
+        ProviderKind::Pi => (&roots.pi_root, "PI_CODING_AGENT_DIR"),
+        ProviderKind::Opencode => (&roots.opencode_root, "XDG_DATA_HOME"),
+    };
+    let root = if root_path.is_dir() {
+        health(HealthStatus::Ok, root_path.display().to_string())
+    } else {
+        hints.push(format!(
+            "`{}` does not exist; set {root_env} if {provider} keeps its data elsewhere",
+            root_path.display()
+        ));
+        health(HealthStatus::Missing, root_path.display().to_string())
+    };
+
+    let index = match provider {
+        ProviderKind::Codex => Some(check_sqlite_indexes(
+            &CodexProvider::new(&roots.codex_root).state_db_paths(),
+            HealthStatus::Warn,
+            "no state sqlite index; threads are found by scanning session files",
+        )),
+        ProviderKind::Opencode => {
+            let db_path = opencode_db_path(roots);
+            let paths = if db_path.exists() {
+                vec![db_path]
+            } else {
+                Vec::new()
+            };
+            Some(check_sqlite_indexes(
+                &paths,
+                HealthStatus::Missing,
+                "opencode.db not found",
+            ))
+        }
+        _ => None,
+    };
+    if let Some(index) = &index
+        && index.status == HealthStatus::Error
+    {
+        hints.push(format!(
+            "the sqlite index does not open; `xurl fsck {provider}` shows what it disagrees with"
+        ));
+    }
+
+    let bin = ProviderCli::resolve_bin(provider);
+    let cli_check = probe_cli(&bin.path);
+    if cli_check.status != HealthStatus::Ok {
+        hints.push(format!("write mode needs {}; {}", cli.name, cli.setup));
+    }
+
+    let auth = probe_auth(
+        provider,
+        roots,
+        &bin.path,
+        cli_check.status == HealthStatus::Ok,
+    );
+    if auth.status != HealthStatus::Ok {
+        hints.push(cli.auth.to_string());
+    }
+
+    ProviderHealth {
+        provider,
+        root,
+        index,
+        bin,
+        cli: cli_check,
+        auth,
+        role_write: matches!(
+            provider,
+            ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Opencode
+        ),
+        fsck: matches!(provider, ProviderKind::Codex | ProviderKind::Opencode),
+        hints,
+    }
+}
+
+fn health(status: HealthStatus, detail: impl Into<String>) -> HealthCheck {
+    HealthCheck {
+        status,
+        detail: detail.into(),
+    }
+}
+
+fn check_sqlite_indexes(
+    paths: &[PathBuf],
+    absent: HealthStatus,
+    absent_detail: &str,
+) -> HealthCheck {
+    if paths.is_empty() {
+        return health(absent, absent_detail);
+    }
+    for path in paths {
+        let opened =
+            Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).and_then(|conn| {
+                conn.query_row("PRAGMA schema_version", [], |row| row.get::<_, i64>(0))
+            });
+        if let Err(err) = opened {
+            return health(HealthStatus::Error, format!("{}: {err}", path.display()));
+        }
+    }
+    let detail = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    health(HealthStatus::Ok, detail)
+}
+
+fn probe_cli(bin: &str) -> HealthCheck {
+    match Command::new(bin).arg("--version").output() {
+        Ok(output) if output.status.success() => {
+            let version = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .unwrap_or(bin)
+                .to_string();
+            health(HealthStatus::Ok, version)
+        }
+        Ok(output) => health(
+            HealthStatus::Warn,
+            format!("`{bin} --version` exited with {}", output.status),
+        ),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            health(HealthStatus::Missing, format!("`{bin}` not found on PATH"))
+        }
+        Err(err) => health(HealthStatus::Error, format!("`{bin}`: {err}")),
+    }
+}
+
+/// Looks for credentials without using them. Only Codex has a side-effect-free status command;
+/// for the others an API key variable or the credentials file the CLI writes after login counts.
+fn probe_auth(
+    provider: ProviderKind,
+    roots: &ProviderRoots,
+    bin: &str,
+    cli_ok: bool,
+) -> HealthCheck {
+    if provider == ProviderKind::Codex {
+        if !cli_ok {
+            return health(HealthStatus::Warn, "not checked: CLI unavailable");
+        }
+        return match Command::new(bin).args(["login", "status"]).output() {
+            Ok(output) if output.status.success() => {
+                let text = String::from_utf8_lossy(&output.stdout).into_owned()
+                    + &String::from_utf8_lossy(&output.stderr);
+                let status = text
+                    .lines()
+                    .map(str::trim)
+                    .find(|line| !line.is_empty())
+                    .unwrap_or("logged in")
+                    .to_string();
+                health(HealthStatus::Ok, status)
+            }
+            Ok(_) => health(
+                HealthStatus::Missing,
+                "`codex login status` reports no login",
+            ),
+            Err(err) => health(HealthStatus::Error, format!("`{bin} login status`: {err}")),
+        };
+    }
+
+    let (vars, file): (&[&str], PathBuf) = match provider {
+        ProviderKind::Amp => (&["AMP_API_KEY"], roots.amp_root.join("secrets.json")),
+        ProviderKind::Claude => (
+            &["ANTHROPIC_API_KEY", "CLAUDE_CODE_OAUTH_TOKEN"],
+            roots.claude_root.join(".credentials.json"),
+        ),
+        ProviderKind::Gemini => (
+            &["GEMINI_API_KEY", "GOOGLE_API_KEY"],
+            roots.gemini_root.join("oauth_creds.json"),
+        ),
+        ProviderKind::Pi => (&[], roots.pi_root.join("auth.json")),
+        ProviderKind::Opencode => (&[], roots.opencode_root.join("auth.json")),
+        ProviderKind::Codex => (&["OPENAI_API_KEY"], roots.codex_root.join("auth.json")),
+    };
+    if let Some(var) = vars
+        .iter()
+        .find(|var| std::env::var_os(var).is_some_and(|value| !value.is_empty()))
+    {
+        return health(HealthStatus::Ok, format!("{var} is set"));
+    }
+    if file.is_file() {
+        return health(HealthStatus::Ok, file.display().to_string());
+    }
+    let mut looked_for = vars.iter().map(ToString::to_string).collect::<Vec<_>>();
+    looked_for.push(format!("`{}`", file.display()));
+    health(
+        HealthStatus::Warn,
+        format!(
+            "no {}; the login may live in the system keychain",
+            looked_for.join(" or ")
+        ),
+    )
+}
+
+/// The frontmatter of `xurl doctor`: how many providers were checked and how many need
+/// nothing fixed.
+#[derive(Serialize)]
+struct DoctorHead {
+    mode: &'static str,
+    providers: usize,
+    healthy: usize,
+}
+
+pub fn render_doctor_markdown(report: &DoctorReport) -> Result<String> {
+    let mut output = String::from("---\n");
+    push_yaml_fields(
+        &mut output,
+        &DoctorHead {
+            mode: "doctor",
+            providers: report.providers.len(),
+            healthy: report
+                .providers
+                .iter()
+                .filter(|health| health.hints.is_empty())
+                .count(),
+        },
+    )?;
+    output.push_str("---\n\n");
+
+    output.push_str("# Doctor\n\n");
+    output.push_str("| Provider | Root | Index | CLI | Auth | Role Write | Fsck |\n");
+    output.push_str("| --- | --- | --- | --- | --- | --- | --- |\n");
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    for health in &report.providers {
+        output.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} | {} |\n",
+            health.provider,
+            health.root.status,
+            health
+                .index
+                .as_ref()
+                .map_or_else(|| "-".to_string(), |index| index.status.to_string()),
+            health.cli.status,
+            health.auth.status,
+            yes_no(health.role_write),
+            yes_no(health.fsck),
+        ));
+    }
+
+    for health in &report.providers {
+        output.push_str(&format!("\n## {}\n\n", health.provider));
+        output.push_str(&format!("- Root: {}\n", health.root.detail));
+        if let Some(index) = &health.index {
+            output.push_str(&format!("- Index: {}\n", index.detail));
+        }
+        output.push_str(&format!(
+            "- Binary: `{}` (from {})\n",
+            health.bin.path, health.bin.source
+        ));
+        output.push_str(&format!("- CLI: {}\n", health.cli.detail));
+        output.push_str(&format!("- Auth: {}\n", health.auth.detail));
+        for hint in &health.hints {
+            output.push_str(&format!("- Hint: {hint}\n"));
+        }
+    }
+
+    Ok(output)
+}
+
+fn match_candidate_preview(candidate: &QueryCandidate, keyword: &str) -> Result<Option<String>> {
+    match &candidate.search_target {
+        QuerySearchTarget::File(path) => match_first_preview_in_file(path, keyword),
+        QuerySearchTarget::Text(text) => Ok(match_first_preview_in_text(text, keyword)),
+    }
+}
+
+/// Matches a candidate against a `q` search and returns a preview of the first match.
+///
+/// A plain phrase is searched for in the thread file directly. Field and regex terms need
+/// the thread's messages and tool calls, so the thread is resolved and parsed; a phrase in
+/// the same query still rules out threads cheaply first.
+fn match_candidate_search(
+    provider: ProviderKind,
+    candidate: &QueryCandidate,
+    search: &SearchQuery,
+    roots: &ProviderRoots,
+) -> Result<Option<String>> {
+    if let Some(phrase) = search.plain_phrase() {
+        return match_candidate_preview(candidate, phrase);
+    }
+    if let Some(phrase) = search.phrase()
+        && match_candidate_preview(candidate, phrase)?.is_none()
+    {
+        return Ok(None);
+    }
+
+    let resolved = resolve_thread(&AgentsUri::parse(&candidate.uri)?, roots)?;
+    let bytes = fs::read(&resolved.path).map_err(|source| XurlError::Io {
+        path: resolved.path.clone(),
+        source,
+    })?;
+    let raw = String::from_utf8_lossy(&bytes);
+    let messages = render::extract_messages(provider, &resolved.path, &raw).unwrap_or_default();
+    let calls = tools::extract_tool_calls(provider, &resolved.path, &raw).unwrap_or_default();
+    let preview = |text: &str| {
+        let line = text
+            .lines()
+            .find(|line| search.line_matches(line))
+            .or_else(|| text.lines().find(|line| !line.trim().is_empty()))
+            .unwrap_or_default();
+        truncate_preview(line.trim(), 160)
+    };
+
+    for message in &messages {
+        let unit = SearchUnit::Message {
+            role: Some(message.role),
+            text: &message.text,
+        };
+        if search.matches(unit) {
+            return Ok(Some(preview(&message.text)));
+        }
+    }
+    for call in &calls {
+        if search.matches(SearchUnit::ToolCall(call)) {
+            return Ok(Some(preview(&query::tool_call_text(call))));
+        }
+    }
+    Ok(None)
+}
+
+/// The text a matched thread is ranked on: its message text for file-backed threads, or the
+/// stored search text otherwise.
+fn candidate_search_text(provider: ProviderKind, candidate: &QueryCandidate) -> Result<String> {
+    let path = match &candidate.search_target {
+        QuerySearchTarget::File(path) => path,
+        QuerySearchTarget::Text(text) => return Ok(text.clone()),
+    };
+    let bytes = fs::read(path).map_err(|source| XurlError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let raw = String::from_utf8_lossy(&bytes);
+    match render::extract_messages(provider, path, &raw) {
+        Ok(messages) if !messages.is_empty() => Ok(messages
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")),
+        _ => Ok(raw.into_owned()),
+    }
+}
+
+fn match_first_preview_in_file(path: &Path, keyword: &str) -> Result<Option<String>> {
+    let mut matcher_builder = RegexMatcherBuilder::new();
+    matcher_builder.fixed_strings(true).case_insensitive(true);
+    let matcher = matcher_builder
+        .build(keyword)
+        .map_err(|err| XurlError::InvalidMode(format!("invalid keyword query: {err}")))?;
+    let mut searcher = SearcherBuilder::new()
+        .binary_detection(BinaryDetection::quit(b'\x00'))
+        .line_number(true)
+        .build();
+    let mut preview = None::<String>;
+    searcher
+        .search_path(
+            &matcher,
+            path,
+            Lossy(|_, line| {
+                let line = line.trim();
+                if line.is_empty() {
+                    return Ok(true);
+                }
+                preview = Some(truncate_preview(line, 160));
+                Ok(false)
+            }),
+        )
+        .map_err(|source| XurlError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    Ok(preview)
+}
+
+fn match_first_preview_in_text(text: &str, keyword: &str) -> Option<String> {
+    let matcher = RegexBuilder::new(&regex::escape(keyword))
+        .case_insensitive(true)
+        .build()
+        .ok()?;
+    let found = matcher.find(text)?;
     let line_start = text[..found.start()].rfind('\n').map_or(0, |idx| idx + 1);
     let line_end = text[found.end()..]
         .find('\n')
@@ -422,65 +2650,544 @@ fn match_first_preview_in_text(text: &str, keyword: &str) -> Option<String> {
     }
 }
 
-fn read_thread_raw(path: &Path) -> Result<String> {
-    let bytes = fs::read(path).map_err(|source| XurlError::Io {
-        path: path.to_path_buf(),
-        source,
+pub(crate) fn read_thread_raw(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).map_err(|source| XurlError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    if bytes.is_empty() {
+        return Err(XurlError::EmptyThreadFile {
+            path: path.to_path_buf(),
+        });
+    }
+
+    String::from_utf8(bytes).map_err(|_| XurlError::NonUtf8ThreadFile {
+        path: path.to_path_buf(),
+    })
+}
+
+/// Opens a thread file for line-by-line reading, rejecting empty files like [`read_thread_raw`].
+fn open_thread_reader(path: &Path) -> Result<BufReader<fs::File>> {
+    let io_error = |source| XurlError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let file = fs::File::open(path).map_err(io_error)?;
+    if file.metadata().map_err(io_error)?.len() == 0 {
+        return Err(XurlError::EmptyThreadFile {
+            path: path.to_path_buf(),
+        });
+    }
+    Ok(BufReader::new(file))
+}
+
+pub fn render_thread_markdown(
+    uri: &AgentsUri,
+    resolved: &ResolvedThread,
+    options: &RenderOptions,
+) -> Result<String> {
+    let mut output = Vec::new();
+    write_thread_markdown(uri, resolved, options, &mut output)?;
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Streams the rendered thread body to `out` without loading line-based rollouts into memory.
+/// Returns how many lines were skipped as not valid JSON while streaming.
+pub fn write_thread_markdown<W: Write>(
+    uri: &AgentsUri,
+    resolved: &ResolvedThread,
+    options: &RenderOptions,
+    out: &mut W,
+) -> Result<usize> {
+    render::write_markdown_body(
+        uri,
+        &resolved.path,
+        || open_thread_reader(&resolved.path),
+        options,
+        out,
+    )
+}
+
+/// Copies the provider-native thread payload (JSONL rollout, JSON session, or
+/// the materialized OpenCode export), or the part of it `slice` selects, to `out` byte for
+/// byte.
+pub fn write_thread_raw<W: Write>(
+    resolved: &ResolvedThread,
+    slice: RawSlice,
+    out: &mut W,
+) -> Result<()> {
+    copy_thread_file(&resolved.path, slice, out)
+}
+
+/// Copies the stored thread file of a drilled-down subagent to `out` byte for byte, as
+/// [`write_thread_raw`] does for a main thread.
+pub fn write_subagent_raw<W: Write>(
+    view: &SubagentView,
+    slice: RawSlice,
+    out: &mut W,
+) -> Result<()> {
+    let detail = match view {
+        SubagentView::Detail(detail) => detail,
+        SubagentView::List(_) => {
+            return Err(XurlError::InvalidMode(
+                "a subagent list has no thread file; add the agent id to the URI".to_string(),
+            ));
+        }
+    };
+    let Some(path) = detail
+        .child_thread
+        .as_ref()
+        .and_then(|thread| thread.path.as_deref())
+    else {
+        return Err(XurlError::ThreadNotFound {
+            provider: detail.query.provider.clone(),
+            session_id: detail.query.agent_id.clone().unwrap_or_default(),
+            searched_roots: Vec::new(),
+        });
+    };
+    copy_thread_file(Path::new(path), slice, out)
+}
+
+fn copy_thread_file<W: Write>(path: &Path, slice: RawSlice, out: &mut W) -> Result<()> {
+    let io_error = |source| XurlError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut reader = open_thread_reader(path)?;
+    match slice {
+        RawSlice::Whole => io::copy(&mut reader, out).map(drop),
+        RawSlice::Lines(range) => copy_lines(&mut reader, range, out),
+        RawSlice::Tail(count) => tail_offset(reader.get_mut(), count)
+            .and_then(|start| reader.seek(SeekFrom::Start(start)))
+            .and_then(|_| io::copy(&mut reader, out))
+            .map(drop),
+    }
+    .map_err(io_error)
+}
+
+/// Streams the lines `range` selects, and stops reading once past its last line.
+fn copy_lines<W: Write>(
+    reader: &mut impl BufRead,
+    range: LineRange,
+    out: &mut W,
+) -> io::Result<()> {
+    let mut line = 1;
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        let mut consumed = 0;
+        let mut done = false;
+        while consumed < buffer.len() && !done {
+            let rest = &buffer[consumed..];
+            let (len, ends_line) = rest
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .map_or((rest.len(), false), |index| (index + 1, true));
+            if line >= range.first {
+                out.write_all(&rest[..len])?;
+            }
+            consumed += len;
+            if ends_line {
+                done = range.last == Some(line);
+                line += 1;
+            }
+        }
+        reader.consume(consumed);
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+/// The offset where the last `count` lines of `file` start, found by reading blocks back
+/// from the end so a large file is not read through.
+fn tail_offset(file: &mut fs::File, count: usize) -> io::Result<u64> {
+    const BLOCK: u64 = 64 * 1024;
+    let len = file.metadata()?.len();
+    if count == 0 {
+        return Ok(len);
+    }
+    let mut block = vec![0; usize::try_from(BLOCK).unwrap_or(usize::MAX)];
+    let mut newlines = 0;
+    let mut end = len;
+    while end > 0 {
+        let start = end.saturating_sub(BLOCK);
+        let chunk = &mut block[..usize::try_from(end - start).unwrap_or_default()];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        for (index, byte) in chunk.iter().enumerate().rev() {
+            let offset = start + index as u64;
+            // A newline that ends the file closes the last line rather than starting one.
+            if *byte != b'\n' || offset + 1 == len {
+                continue;
+            }
+            newlines += 1;
+            if newlines == count {
+                return Ok(offset + 1);
+            }
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+/// The `--write-summary json` report of a finished write, as one line of JSON so reports of
+/// several writes can be appended to one file.
+pub fn write_result_summary_json(result: &WriteResult) -> Result<String> {
+    let summary = serde_json::json!({
+        "uri": format!("agents://{}/{}", result.provider, result.session_id),
+        "provider": result.provider,
+        "session_id": result.session_id,
+        "exit_code": result.exit_code,
+        "duration_ms": u64::try_from(result.duration.as_millis()).unwrap_or(u64::MAX),
+        "bytes_streamed": result.bytes_streamed,
+        "truncated": result.truncated,
+        "warnings": result.warnings,
+    });
+    let mut output =
+        serde_json::to_string(&summary).map_err(|err| XurlError::Serialization(err.to_string()))?;
+    output.push('\n');
+    Ok(output)
+}
+
+pub fn subagent_view_to_raw_json(view: &SubagentView) -> Result<String> {
+    to_raw_json(view)
+}
+
+pub fn thread_query_to_raw_json(result: &ThreadQueryResult) -> Result<String> {
+    to_raw_json(result)
+}
+
+fn to_raw_json<T: serde::Serialize>(value: &T) -> Result<String> {
+    let mut output = serde_json::to_string_pretty(value)
+        .map_err(|err| XurlError::Serialization(err.to_string()))?;
+    output.push('\n');
+    Ok(output)
+}
+
+const MAX_THREAD_CHAIN: usize = 64;
+
+/// Follows resume/fork links backwards from `uri` and returns the logical
+/// conversation oldest first, ending with `uri` itself.
+pub fn resolve_thread_chain(uri: &AgentsUri, roots: &ProviderRoots) -> Result<Vec<AgentsUri>> {
+    if uri.agent_id.is_some() {
+        return Err(XurlError::InvalidMode(
+            "thread history requires a main thread URI: agents://<provider>/<session_id>"
+                .to_string(),
+        ));
+    }
+
+    let mut chain = vec![main_thread_uri(&expand_latest_alias(uri, roots)?)];
+    let mut seen = BTreeSet::from([chain[0].session_id.clone()]);
+    while chain.len() < MAX_THREAD_CHAIN {
+        let current = &chain[chain.len() - 1];
+        let resolved = resolve_thread(current, roots)?;
+        let Some(previous) = previous_session_id(current, &resolved.path) else {
+            break;
+        };
+        if !seen.insert(previous.clone()) {
+            break;
+        }
+        let previous = AgentsUri {
+            session_id: previous,
+            ..current.clone()
+        };
+        // A link to a session that is no longer on disk ends the chain.
+        if resolve_thread(&previous, roots).is_err() {
+            break;
+        }
+        chain.push(previous);
+    }
+    chain.reverse();
+    Ok(chain)
+}
+
+pub fn render_thread_history_markdown(
+    uri: &AgentsUri,
+    roots: &ProviderRoots,
+    options: &RenderOptions,
+) -> Result<String> {
+    let chain = resolve_thread_chain(uri, roots)?;
+    let mut output = String::from("# Thread\n\n");
+    for (idx, link) in chain.iter().enumerate() {
+        let resolved = resolve_thread(link, roots)?;
+        let body = render_thread_markdown(link, &resolved, options)?;
+        let body = body.strip_prefix("# Thread\n\n").unwrap_or(&body);
+        let note = if idx == 0 {
+            String::new()
+        } else {
+            format!(", resumed from `{}`", chain[idx - 1].as_agents_string())
+        };
+        output.push_str(&format!(
+            "## Session {} of {}: `{}`\n\nSource: `{}`{note}\n\n",
+            idx + 1,
+            chain.len(),
+            link.as_agents_string(),
+            resolved.path.display(),
+        ));
+        output.push_str(body.trim_end());
+        output.push_str("\n\n");
+    }
+    Ok(output)
+}
+
+fn previous_session_id(uri: &AgentsUri, path: &Path) -> Option<String> {
+    match uri.provider {
+        ProviderKind::Codex => codex_forked_from_id(path),
+        ProviderKind::Claude => claude_previous_session_id(&uri.session_id, path),
+        _ => None,
+    }
+}
+
+fn codex_forked_from_id(path: &Path) -> Option<String> {
+    let file = fs::File::open(path).ok()?;
+    BufReader::new(file)
+        .lines()
+        .take(20)
+        .map_while(std::result::Result::ok)
+        .filter_map(|line| serde_json::from_str::<Value>(&line).ok())
+        .find(|value| value.get("type").and_then(Value::as_str) == Some("session_meta"))?
+        .pointer("/payload/forked_from_id")
+        .and_then(Value::as_str)
+        .filter(|id| is_uuid_session_id(id))
+        .map(ToString::to_string)
+}
+
+/// Claude resumes start a new file whose first entries either still carry the
+/// previous `sessionId` or point at a message uuid that lives in the previous file.
+fn claude_previous_session_id(session_id: &str, path: &Path) -> Option<String> {
+    let raw = fs::read_to_string(path).ok()?;
+    let values = raw
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .collect::<Vec<_>>();
+
+    if let Some(previous) = values.iter().find_map(|value| {
+        value
+            .get("sessionId")
+            .and_then(Value::as_str)
+            .filter(|id| *id != session_id && is_uuid_session_id(id))
+    }) {
+        return Some(previous.to_string());
+    }
+
+    let uuids = values
+        .iter()
+        .filter_map(|value| value.get("uuid").and_then(Value::as_str))
+        .collect::<HashSet<_>>();
+    let dangling = values.iter().find_map(|value| {
+        ["parentUuid", "leafUuid"]
+            .iter()
+            .filter_map(|key| value.get(*key).and_then(Value::as_str))
+            .find(|id| !uuids.contains(id))
     })?;
 
-    if bytes.is_empty() {
-        return Err(XurlError::EmptyThreadFile {
-            path: path.to_path_buf(),
-        });
+    let needle = format!("\"uuid\":\"{dangling}\"");
+    fs::read_dir(path.parent()?)
+        .ok()?
+        .filter_map(std::result::Result::ok)
+        .map(|entry| entry.path())
+        .filter(|candidate| {
+            candidate != path && candidate.extension().is_some_and(|ext| ext == "jsonl")
+        })
+        .find(|candidate| {
+            fs::read_to_string(candidate).is_ok_and(|content| content.contains(&needle))
+        })
+        .and_then(|candidate| candidate.file_stem()?.to_str().map(ToString::to_string))
+        .filter(|id| is_uuid_session_id(id))
+}
+
+pub fn render_thread_branches_markdown(
+    uri: &AgentsUri,
+    resolved: &ResolvedThread,
+) -> Result<String> {
+    if uri.provider != ProviderKind::Pi || uri.agent_id.is_some() {
+        return Err(XurlError::InvalidMode(
+            "branch listing requires a pi main thread URI: agents://pi/<session_id>".to_string(),
+        ));
     }
+    let raw = read_thread_raw(&resolved.path)?;
+    render::render_pi_branches_markdown(uri, &raw)
+}
 
-    String::from_utf8(bytes).map_err(|_| XurlError::NonUtf8ThreadFile {
-        path: path.to_path_buf(),
-    })
+/// The fields of a pi branch diff head, in the order they are written.
+#[derive(Serialize)]
+struct PiBranchDiffHead<'a> {
+    provider: ProviderKind,
+    session_id: &'a str,
+    thread_source: String,
+    mode: &'static str,
+    left_uri: String,
+    right_uri: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
 }
 
-pub fn render_thread_markdown(uri: &AgentsUri, resolved: &ResolvedThread) -> Result<String> {
+pub fn render_thread_diff_markdown(
+    left: &AgentsUri,
+    right: &AgentsUri,
+    roots: &ProviderRoots,
+) -> Result<String> {
+    let is_entry_uri = |uri: &AgentsUri| {
+        uri.provider == ProviderKind::Pi && !uri.agent_id.as_deref().is_some_and(is_uuid_session_id)
+    };
+    if !is_entry_uri(left) || !is_entry_uri(right) || left.session_id != right.session_id {
+        return Err(XurlError::InvalidMode(
+            "diff requires two pi entry URIs of the same session: agents://pi/<session_id>/<entry_id>"
+                .to_string(),
+        ));
+    }
+
+    let resolved = resolve_thread(&main_thread_uri(left), roots)?;
     let raw = read_thread_raw(&resolved.path)?;
-    let markdown = render::render_markdown(uri, &resolved.path, &raw)?;
-    Ok(strip_frontmatter(markdown))
+    let body = render::render_pi_diff_markdown(left, right, &raw)?;
+
+    let mut output = String::from("---\n");
+    push_yaml_fields(
+        &mut output,
+        &PiBranchDiffHead {
+            provider: left.provider,
+            session_id: &left.session_id,
+            thread_source: resolved.path.display().to_string(),
+            mode: "pi_branch_diff",
+            left_uri: left.as_agents_string(),
+            right_uri: right.as_agents_string(),
+            warnings: unique_warnings(&resolved.metadata.warnings),
+        },
+    )?;
+    output.push_str("---\n\n");
+    output.push_str(&body);
+    Ok(output)
 }
 
 pub fn render_skill_markdown(resolved: &ResolvedSkill) -> String {
     resolved.content.clone()
 }
 
-pub fn render_skill_head_markdown(resolved: &ResolvedSkill) -> String {
-    let mut output = String::new();
-    output.push_str("---\n");
-    push_yaml_string(&mut output, "uri", &resolved.uri);
-    push_yaml_string(&mut output, "kind", "skill");
-    push_yaml_string(&mut output, "provider", "skills");
-    push_yaml_string(
+/// The fields of a skill head, in the order they are written.
+#[derive(Serialize)]
+struct SkillHead<'a> {
+    uri: &'a str,
+    kind: &'static str,
+    provider: &'static str,
+    source_kind: String,
+    skill_name: &'a str,
+    source: &'a str,
+    resolved_path: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    candidates: &'a [String],
+}
+
+pub fn render_skill_head_markdown(resolved: &ResolvedSkill) -> Result<String> {
+    let mut output = String::from("---\n");
+    push_yaml_fields(
         &mut output,
-        "source_kind",
-        &resolved.source_kind.to_string(),
-    );
-    push_yaml_string(&mut output, "skill_name", &resolved.skill_name);
-    push_yaml_string(&mut output, "source", &resolved.source);
-    push_yaml_string(&mut output, "resolved_path", &resolved.resolved_path);
-    render_warnings(&mut output, &resolved.metadata.warnings);
-    if !resolved.metadata.candidates.is_empty() {
-        output.push_str("candidates:\n");
-        for candidate in &resolved.metadata.candidates {
-            output.push_str(&format!("  - '{}'\n", yaml_single_quoted(candidate)));
+        &SkillHead {
+            uri: &resolved.uri,
+            kind: "skill",
+            provider: "skills",
+            source_kind: resolved.source_kind.to_string(),
+            skill_name: &resolved.skill_name,
+            source: &resolved.source,
+            resolved_path: &resolved.resolved_path,
+            warnings: unique_warnings(&resolved.metadata.warnings),
+            candidates: &resolved.metadata.candidates,
+        },
+    )?;
+    output.push_str("---\n");
+    Ok(output)
+}
+
+/// The fields every thread head starts with.
+#[derive(Serialize)]
+struct ThreadIdentityHead<'a> {
+    uri: String,
+    provider: ProviderKind,
+    session_id: &'a str,
+}
+
+impl<'a> ThreadIdentityHead<'a> {
+    fn new(uri: &'a AgentsUri) -> Self {
+        Self {
+            uri: uri.as_agents_string(),
+            provider: uri.provider,
+            session_id: &uri.session_id,
         }
     }
-    output.push_str("---\n");
-    output
 }
 
-pub fn render_thread_head_markdown(uri: &AgentsUri, roots: &ProviderRoots) -> Result<String> {
-    let mut output = String::new();
-    output.push_str("---\n");
-    push_yaml_string(&mut output, "uri", &uri.as_agents_string());
-    push_yaml_string(&mut output, "provider", &uri.provider.to_string());
-    push_yaml_string(&mut output, "session_id", &uri.session_id);
+/// The fields of a thread head after its identity, in the order they are written. Main
+/// threads fill in their stats and index; subagent and pi entry URIs what they point at.
+#[derive(Default, Serialize)]
+struct ThreadHead {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_source: Option<String>,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    mode: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    agent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subagent_uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status_source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    child_thread_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    child_thread_source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    child_last_updated_at: Option<String>,
+    #[serde(skip_serializing_if = "is_zero")]
+    corrupt_lines: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workdir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    compacted: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    files_changed: Vec<FileChange>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    mcp_servers: Vec<McpServerUsage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related: Vec<RelatedHead>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolution: Option<ResolutionHead>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entries: Option<Vec<PiEntryHead>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subagents: Option<Vec<SubagentHead>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subagent_totals: Option<SubagentTotalsHead>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
 
+pub fn render_thread_head_markdown(
+    uri: &AgentsUri,
+    roots: &ProviderRoots,
+    zone: &TimeZone,
+    subagents: &SubagentListOptions,
+) -> Result<String> {
+    let mut head = ThreadHead::default();
     match (uri.provider, uri.agent_id.as_deref()) {
         (
             ProviderKind::Amp
@@ -491,42 +3198,42 @@ pub fn render_thread_head_markdown(uri: &AgentsUri, roots: &ProviderRoots) -> Re
             None,
         ) => {
             let resolved_main = resolve_thread(uri, roots)?;
-            push_yaml_string(
-                &mut output,
-                "thread_source",
-                &resolved_main.path.display().to_string(),
-            );
-            push_yaml_string(&mut output, "mode", "subagent_index");
-
-            let view = resolve_subagent_view(uri, roots, true)?;
-            let mut warnings = resolved_main.metadata.warnings.clone();
-
-            if let SubagentView::List(list) = view {
-                render_subagents_head(&mut output, &list);
-                warnings.extend(list.warnings);
+            head.thread_source = Some(resolved_main.path.display().to_string());
+            head.mode = "subagent_index";
+            fill_thread_stats(&mut head, uri, &resolved_main, roots);
+            head.compacted = uri.provider == ProviderKind::Codex
+                && render::codex_rollout_is_compacted(open_thread_reader(&resolved_main.path)?);
+            if uri.provider == ProviderKind::Amp {
+                head.related = related_head(&resolved_main.path, zone);
             }
+            head.resolution = Some(resolution_head(&resolved_main));
 
-            render_warnings(&mut output, &warnings);
+            let view = resolve_subagent_view(uri, roots, true, 0)?;
+            if let SubagentView::List(mut list) = view {
+                arrange_subagent_list(&mut list, uri.provider, subagents)?;
+                fill_subagents_head(&mut head, &list, zone);
+                head.warnings = unique_warnings(&[head.warnings, list.warnings].concat());
+            }
         }
         (ProviderKind::Pi, None) => {
             let resolved = resolve_thread(uri, roots)?;
-            push_yaml_string(
-                &mut output,
-                "thread_source",
-                &resolved.path.display().to_string(),
-            );
-            push_yaml_string(&mut output, "mode", "pi_entry_index");
+            head.thread_source = Some(resolved.path.display().to_string());
+            head.mode = "pi_entry_index";
+            fill_thread_stats(&mut head, uri, &resolved, roots);
+            head.resolution = Some(resolution_head(&resolved));
 
             let list = resolve_pi_entry_list_view(uri, roots)?;
-            render_pi_entries_head(&mut output, &list);
+            head.entries = Some(pi_entries_head(&list));
             let mut warnings = list.warnings;
+            warnings.retain(|warning| !resolved.metadata.warnings.contains(warning));
 
-            if let SubagentView::List(subagents) = resolve_subagent_view(uri, roots, true)? {
-                render_subagents_head(&mut output, &subagents);
-                warnings.extend(subagents.warnings);
+            if let SubagentView::List(mut list) = resolve_subagent_view(uri, roots, true, 0)? {
+                arrange_subagent_list(&mut list, uri.provider, subagents)?;
+                fill_subagents_head(&mut head, &list, zone);
+                warnings.extend(list.warnings);
             }
 
-            render_warnings(&mut output, &warnings);
+            head.warnings = unique_warnings(&[head.warnings, warnings].concat());
         }
         (
             ProviderKind::Amp
@@ -539,240 +3246,691 @@ pub fn render_thread_head_markdown(uri: &AgentsUri, roots: &ProviderRoots) -> Re
             let main_uri = main_thread_uri(uri);
             let resolved_main = resolve_thread(&main_uri, roots)?;
 
-            let view = resolve_subagent_view(uri, roots, false)?;
+            let view = resolve_subagent_view(uri, roots, false, 0)?;
             if let SubagentView::Detail(detail) = view {
-                let thread_source = detail
-                    .child_thread
-                    .as_ref()
-                    .and_then(|thread| thread.path.as_deref())
-                    .map(ToString::to_string)
-                    .unwrap_or_else(|| resolved_main.path.display().to_string());
-                push_yaml_string(&mut output, "thread_source", &thread_source);
-                push_yaml_string(&mut output, "mode", "subagent_detail");
-
                 if let Some(agent_id) = &detail.query.agent_id {
-                    push_yaml_string(&mut output, "agent_id", agent_id);
-                    push_yaml_string(
-                        &mut output,
-                        "subagent_uri",
-                        &agents_thread_uri(
-                            &detail.query.provider,
-                            &detail.query.main_thread_id,
-                            Some(agent_id),
-                        ),
-                    );
-                }
-                push_yaml_string(&mut output, "status", &detail.status);
-                push_yaml_string(&mut output, "status_source", &detail.status_source);
-
-                if let Some(child_thread) = &detail.child_thread {
-                    push_yaml_string(&mut output, "child_thread_id", &child_thread.thread_id);
-                    if let Some(path) = &child_thread.path {
-                        push_yaml_string(&mut output, "child_thread_source", path);
-                    }
-                    if let Some(last_updated_at) = &child_thread.last_updated_at {
-                        push_yaml_string(&mut output, "child_last_updated_at", last_updated_at);
-                    }
+                    head.agent_id = Some(agent_id.clone());
+                    head.subagent_uri = Some(agents_thread_uri(
+                        &detail.query.provider,
+                        &detail.query.main_thread_id,
+                        Some(agent_id),
+                    ));
                 }
-
-                render_warnings(&mut output, &detail.warnings);
+                fill_subagent_detail_head(&mut head, &detail, &resolved_main, zone);
             }
         }
         (ProviderKind::Pi, Some(agent_id)) if is_uuid_session_id(agent_id) => {
             let main_uri = main_thread_uri(uri);
             let resolved_main = resolve_thread(&main_uri, roots)?;
 
-            let view = resolve_subagent_view(uri, roots, false)?;
+            let view = resolve_subagent_view(uri, roots, false, 0)?;
             if let SubagentView::Detail(detail) = view {
-                let thread_source = detail
-                    .child_thread
-                    .as_ref()
-                    .and_then(|thread| thread.path.as_deref())
-                    .map(ToString::to_string)
-                    .unwrap_or_else(|| resolved_main.path.display().to_string());
-                push_yaml_string(&mut output, "thread_source", &thread_source);
-                push_yaml_string(&mut output, "mode", "subagent_detail");
-                push_yaml_string(&mut output, "agent_id", agent_id);
-                push_yaml_string(
-                    &mut output,
-                    "subagent_uri",
-                    &agents_thread_uri("pi", &uri.session_id, Some(agent_id)),
-                );
-                push_yaml_string(&mut output, "status", &detail.status);
-                push_yaml_string(&mut output, "status_source", &detail.status_source);
-
-                if let Some(child_thread) = &detail.child_thread {
-                    push_yaml_string(&mut output, "child_thread_id", &child_thread.thread_id);
-                    if let Some(path) = &child_thread.path {
-                        push_yaml_string(&mut output, "child_thread_source", path);
-                    }
-                    if let Some(last_updated_at) = &child_thread.last_updated_at {
-                        push_yaml_string(&mut output, "child_last_updated_at", last_updated_at);
-                    }
-                }
-
-                render_warnings(&mut output, &detail.warnings);
+                head.agent_id = Some(agent_id.to_string());
+                head.subagent_uri = Some(agents_thread_uri("pi", &uri.session_id, Some(agent_id)));
+                fill_subagent_detail_head(&mut head, &detail, &resolved_main, zone);
             }
         }
         (ProviderKind::Pi, Some(entry_id)) => {
             let resolved = resolve_thread(uri, roots)?;
-            push_yaml_string(
-                &mut output,
-                "thread_source",
-                &resolved.path.display().to_string(),
+            head.thread_source = Some(resolved.path.display().to_string());
+            head.mode = "pi_entry";
+            head.entry_id = Some(entry_id.to_string());
+        }
+    }
+
+    let mut output = String::from("---\n");
+    push_yaml_fields(&mut output, &ThreadIdentityHead::new(uri))?;
+    push_yaml_fields(&mut output, &head)?;
+    output.push_str("---\n");
+    Ok(output)
+}
+
+/// The fields of a `-I --fast` head after its identity, in the order they are written.
+#[derive(Default, Serialize)]
+struct ThreadMetadataHead {
+    thread_source: String,
+    mode: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    messages: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parts: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    updated_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    started_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workdir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cli_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolution: Option<ResolutionHead>,
+}
+
+/// Frontmatter for `-I --fast`: what a main thread records about itself, read without going
+/// through the thread. Codex, Claude, and pi threads are read only up to their session header
+/// and first model choice; OpenCode sessions are summarized by sqlite counts. Other URIs get
+/// the full [`render_thread_head_markdown`].
+pub fn render_thread_metadata_head_markdown(
+    uri: &AgentsUri,
+    roots: &ProviderRoots,
+    zone: &TimeZone,
+) -> Result<String> {
+    if uri.agent_id.is_some() {
+        return render_thread_head_markdown(uri, roots, zone, &SubagentListOptions::default());
+    }
+    let uri = &expand_latest_alias(uri, roots)?;
+    let head = match uri.provider {
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Pi => {
+            let resolved = locate_thread(uri, roots)?;
+            let file_meta = fs::metadata(&resolved.path).ok();
+            let updated_at = file_meta
+                .as_ref()
+                .and_then(|file_meta| file_meta.modified().ok())
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| render::format_timestamp(&modified.as_secs().to_string(), zone));
+            let metadata =
+                metadata::thread_metadata(uri.provider, open_thread_reader(&resolved.path)?);
+            ThreadMetadataHead {
+                thread_source: resolved.path.display().to_string(),
+                mode: "metadata",
+                raw_bytes: file_meta.as_ref().map(fs::Metadata::len),
+                updated_at,
+                started_at: metadata
+                    .started_at
+                    .as_deref()
+                    .map(|started_at| render::format_timestamp(started_at, zone)),
+                workdir: metadata.workdir,
+                model: metadata.model,
+                cli_version: metadata.cli_version,
+                branch: metadata.branch,
+                resolution: Some(resolution_head(&resolved)),
+                ..ThreadMetadataHead::default()
+            }
+        }
+        ProviderKind::Opencode => {
+            let db_path = opencode_db_path(roots);
+            let Some(counts) = opencode_session_counts(&db_path, &uri.session_id) else {
+                return render_thread_head_markdown(
+                    uri,
+                    roots,
+                    zone,
+                    &SubagentListOptions::default(),
+                );
+            };
+            let format_millis = |millis: Option<i64>| {
+                millis.map(|millis| render::format_timestamp(&millis.to_string(), zone))
+            };
+            ThreadMetadataHead {
+                thread_source: format!("{}#session:{}", db_path.display(), uri.session_id),
+                mode: "metadata",
+                messages: Some(counts.messages),
+                parts: Some(counts.parts),
+                updated_at: format_millis(counts.last_message_at),
+                started_at: format_millis(counts.first_message_at),
+                workdir: fetch_opencode_session_directory(roots, &uri.session_id)
+                    .map(|workdir| workdir.display().to_string()),
+                ..ThreadMetadataHead::default()
+            }
+        }
+        ProviderKind::Amp | ProviderKind::Gemini => {
+            return render_thread_head_markdown(uri, roots, zone, &SubagentListOptions::default());
+        }
+    };
+    let mut output = String::from("---\n");
+    push_yaml_fields(&mut output, &ThreadIdentityHead::new(uri))?;
+    push_yaml_fields(&mut output, &head)?;
+    output.push_str("---\n");
+    Ok(output)
+}
+
+/// Row counts and message time range of an OpenCode session.
+struct OpencodeSessionCounts {
+    messages: u64,
+    parts: u64,
+    first_message_at: Option<i64>,
+    last_message_at: Option<i64>,
+}
+
+/// `None` when the database cannot be read or has no such session.
+fn opencode_session_counts(db_path: &Path, session_id: &str) -> Option<OpencodeSessionCounts> {
+    let conn = open_opencode_read_only_db(db_path).ok()?;
+    conn.query_row(
+        "SELECT 1 FROM session WHERE id = ?1 LIMIT 1",
+        [session_id],
+        |_| Ok(()),
+    )
+    .ok()?;
+    let (messages, first_message_at, last_message_at) = conn
+        .query_row(
+            "SELECT COUNT(*), MIN(time_created), MAX(time_created) FROM message WHERE session_id = ?1",
+            [session_id],
+            |row| Ok((row.get::<_, u64>(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .ok()?;
+    let parts = conn
+        .query_row(
+            "SELECT COUNT(*) FROM part WHERE session_id = ?1",
+            [session_id],
+            |row| row.get::<_, u64>(0),
+        )
+        .ok()?;
+    Some(OpencodeSessionCounts {
+        messages,
+        parts,
+        first_message_at,
+        last_message_at,
+    })
+}
+
+/// Whether a subagent view has stopped changing: a drill-down whose status is anything but
+/// `pendingInit` or `running`. List views are always settled.
+pub fn subagent_view_settled(view: &SubagentView) -> bool {
+    match view {
+        SubagentView::List(_) => true,
+        SubagentView::Detail(detail) => {
+            !matches!(detail.status.as_str(), STATUS_PENDING_INIT | STATUS_RUNNING)
+        }
+    }
+}
+
+/// Resolves the subagent index of a main thread (`list`) or one subagent drill-down. A
+/// drill-down also lists the agents its child thread spawned, down to `depth` levels.
+pub fn resolve_subagent_view(
+    uri: &AgentsUri,
+    roots: &ProviderRoots,
+    list: bool,
+    depth: usize,
+) -> Result<SubagentView> {
+    if list && uri.agent_id.is_some() {
+        return Err(XurlError::InvalidMode(
+            "subagent index mode requires agents://<provider>/<main_thread_id>".to_string(),
+        ));
+    }
+
+    if !list && uri.agent_id.is_none() {
+        return Err(XurlError::InvalidMode(
+            "subagent drill-down requires agents://<provider>/<main_thread_id>/<agent_id>"
+                .to_string(),
+        ));
+    }
+
+    let mut view = match uri.provider {
+        ProviderKind::Amp => resolve_amp_subagent_view(uri, roots, list),
+        ProviderKind::Codex => resolve_codex_subagent_view(uri, roots, list),
+        ProviderKind::Claude => resolve_claude_subagent_view(uri, roots, list),
+        ProviderKind::Gemini => resolve_gemini_subagent_view(uri, roots, list),
+        ProviderKind::Pi => resolve_pi_subagent_view(uri, roots, list),
+        ProviderKind::Opencode => resolve_opencode_subagent_view(uri, roots, list),
+    }?;
+    if let SubagentView::List(list) = &mut view {
+        list.totals = subagent_totals(uri.provider, &list.agents);
+    }
+    if let SubagentView::Detail(detail) = &mut view
+        && depth > 0
+    {
+        detail.query.depth = depth;
+        if let Some(child) = &detail.child_thread {
+            let mut visited = HashSet::from([uri.session_id.clone(), child.thread_id.clone()]);
+            collect_subagent_descendants(
+                uri.provider,
+                &child.thread_id,
+                (1, depth),
+                roots,
+                &mut visited,
+                &mut detail.descendants,
+            );
+        }
+    }
+    Ok(view)
+}
+
+/// Counts children by status and sums their run times, read from the child threads'
+/// timestamped messages. Children whose thread is missing or has fewer than two timestamps
+/// add no run time.
+fn subagent_totals(provider: ProviderKind, agents: &[SubagentListItem]) -> SubagentTotals {
+    let mut totals = SubagentTotals::default();
+    for agent in agents {
+        *totals.by_status.entry(agent.status.clone()).or_default() += 1;
+        let Some(path) = agent
+            .child_thread
+            .as_ref()
+            .and_then(|thread| thread.path.as_deref())
+            .map(Path::new)
+        else {
+            continue;
+        };
+        let Ok(Some(duration)) = open_thread_reader(path)
+            .and_then(|reader| render::thread_duration(provider, path, reader))
+        else {
+            continue;
+        };
+        let duration_ms = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+        totals.duration_ms = totals.duration_ms.saturating_add(duration_ms);
+        if totals
+            .longest
+            .as_ref()
+            .is_none_or(|longest| duration_ms > longest.duration_ms)
+        {
+            totals.longest = Some(SubagentLongest {
+                agent_id: agent.agent_id.clone(),
+                duration_ms,
+            });
+        }
+    }
+    totals
+}
+
+/// Appends the subagents `thread_id` spawned, each followed by its own descendants, while
+/// `level` is at most `max_depth`. A thread that cannot be read as a main thread, like a
+/// Claude sidechain, has no descendants.
+fn collect_subagent_descendants(
+    provider: ProviderKind,
+    thread_id: &str,
+    (level, max_depth): (usize, usize),
+    roots: &ProviderRoots,
+    visited: &mut HashSet<String>,
+    descendants: &mut Vec<SubagentDescendant>,
+) {
+    let provider_name = provider.to_string();
+    let Ok(parent) = AgentsUri::parse(&agents_thread_uri(&provider_name, thread_id, None)) else {
+        return;
+    };
+    let Ok(SubagentView::List(list)) = resolve_subagent_view(&parent, roots, true, 0) else {
+        return;
+    };
+    for agent in list.agents {
+        descendants.push(SubagentDescendant {
+            depth: level,
+            parent_thread_id: thread_id.to_string(),
+            uri: agents_thread_uri(&provider_name, thread_id, Some(&agent.agent_id)),
+            agent_id: agent.agent_id,
+            status: agent.status,
+            status_source: agent.status_source,
+        });
+        if level < max_depth
+            && let Some(child) = agent.child_thread
+            && visited.insert(child.thread_id.clone())
+        {
+            collect_subagent_descendants(
+                provider,
+                &child.thread_id,
+                (level + 1, max_depth),
+                roots,
+                visited,
+                descendants,
             );
-            push_yaml_string(&mut output, "mode", "pi_entry");
-            push_yaml_string(&mut output, "entry_id", entry_id);
         }
     }
+}
 
-    output.push_str("---\n");
-    Ok(output)
+fn is_zero(value: &usize) -> bool {
+    *value == 0
 }
 
-pub fn resolve_subagent_view(
+/// The warnings of a head, each once and sorted.
+fn unique_warnings(warnings: &[String]) -> Vec<String> {
+    warnings
+        .iter()
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Fills in what a main thread head says about the thread file itself: corrupt lines, the
+/// wall-clock time between its first and last timestamped messages, its status, its workdir,
+/// its sizes, and the files and MCP servers its tool calls touched.
+fn fill_thread_stats(
+    head: &mut ThreadHead,
     uri: &AgentsUri,
+    resolved: &ResolvedThread,
     roots: &ProviderRoots,
-    list: bool,
-) -> Result<SubagentView> {
-    if list && uri.agent_id.is_some() {
-        return Err(XurlError::InvalidMode(
-            "subagent index mode requires agents://<provider>/<main_thread_id>".to_string(),
-        ));
-    }
-
-    if !list && uri.agent_id.is_none() {
-        return Err(XurlError::InvalidMode(
-            "subagent drill-down requires agents://<provider>/<main_thread_id>/<agent_id>"
-                .to_string(),
-        ));
-    }
-
-    match uri.provider {
-        ProviderKind::Amp => resolve_amp_subagent_view(uri, roots, list),
-        ProviderKind::Codex => resolve_codex_subagent_view(uri, roots, list),
-        ProviderKind::Claude => resolve_claude_subagent_view(uri, roots, list),
-        ProviderKind::Gemini => resolve_gemini_subagent_view(uri, roots, list),
-        ProviderKind::Pi => resolve_pi_subagent_view(uri, roots, list),
-        ProviderKind::Opencode => resolve_opencode_subagent_view(uri, roots, list),
-    }
+) {
+    let (provider, path) = (uri.provider, resolved.path.as_path());
+    if let Ok(Some(duration)) =
+        open_thread_reader(path).and_then(|reader| render::thread_duration(provider, path, reader))
+    {
+        head.duration = Some(timestamp::format_duration(duration));
+    }
+    let status = open_thread_reader(path).map_or(ThreadStatus::Unknown, |reader| {
+        status::thread_status(provider, reader)
+    });
+    head.status = Some(status.to_string());
+    fill_thread_workdir(head, uri, path, roots);
+
+    // Messages and corrupt lines are counted while streaming the file, without rendering it;
+    // OpenCode counts its corrupt rows while materializing the thread instead. `raw_bytes` is
+    // the size of the file as `--raw` prints it.
+    let calls = thread_tool_calls(provider, path);
+    let count = open_thread_reader(path)
+        .and_then(|reader| render::count_messages(provider, path, reader))
+        .ok();
+    head.message_count = count.map(|count| count.messages);
+    let streamed_corrupt_lines = count.map_or(0, |count| count.corrupt_lines);
+    head.corrupt_lines = resolved.metadata.corrupt_lines + streamed_corrupt_lines;
+    if streamed_corrupt_lines > 0 {
+        head.warnings
+            .push(jsonl::corrupt_lines_warning(streamed_corrupt_lines));
+    }
+    head.tool_call_count = Some(calls.len());
+    head.raw_bytes = fs::metadata(path).map(|metadata| metadata.len()).ok();
+    head.files_changed = tools::files_changed(&calls);
+    head.mcp_servers = tools::mcp_server_usage(&calls);
 }
 
-fn push_yaml_string(output: &mut String, key: &str, value: &str) {
-    output.push_str(&format!("{key}: '{}'\n", yaml_single_quoted(value)));
+/// Fills in `workdir`, and `repo` and `branch` when the workdir is in a git repository.
+fn fill_thread_workdir(head: &mut ThreadHead, uri: &AgentsUri, path: &Path, roots: &ProviderRoots) {
+    let Some(workdir) = thread_cwd(uri.provider, path, &uri.session_id, roots) else {
+        return;
+    };
+    head.workdir = Some(workdir.display().to_string());
+    let Some(repo) = git::repo_info(&workdir) else {
+        return;
+    };
+    head.repo = Some(
+        repo.origin
+            .unwrap_or_else(|| repo.root.display().to_string()),
+    );
+    head.branch = repo.branch;
 }
 
-fn yaml_single_quoted(value: &str) -> String {
-    value.replace('\'', "''")
+/// How the thread file was found: the discovery strategy, how many candidates matched, how
+/// long the lookup took, and what it warned about.
+#[derive(Serialize)]
+struct ResolutionHead {
+    source: String,
+    strategy: &'static str,
+    candidates: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    duplicates_skipped: usize,
+    elapsed_ms: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
 }
 
-fn render_warnings(output: &mut String, warnings: &[String]) {
-    let mut unique = BTreeSet::<String>::new();
-    unique.extend(warnings.iter().cloned());
-
-    if unique.is_empty() {
-        return;
+fn resolution_head(resolved: &ResolvedThread) -> ResolutionHead {
+    let meta = &resolved.metadata;
+    ResolutionHead {
+        source: meta.source.clone(),
+        strategy: resolution_strategy(&meta.source),
+        candidates: meta.candidate_count,
+        duplicates_skipped: meta.duplicates_skipped,
+        elapsed_ms: u64::try_from(meta.elapsed.as_millis()).unwrap_or(u64::MAX),
+        warnings: unique_warnings(&meta.warnings),
     }
+}
 
-    output.push_str("warnings:\n");
-    for warning in unique {
-        output.push_str(&format!("  - '{}'\n", yaml_single_quoted(&warning)));
+/// The lookup behind a resolution `source`: an index (`sqlite`, `sessions-index`), a match
+/// on the file name (`filename`), reading file contents (`header-scan`), or a download
+/// (`cache`, `api`).
+fn resolution_strategy(source: &str) -> &'static str {
+    match source {
+        "codex:sqlite:sessions" | "codex:sqlite:archived_sessions" | "opencode:sqlite" => "sqlite",
+        "claude:sessions-index" => "sessions-index",
+        "codex:sessions"
+        | "codex:archived_sessions"
+        | "claude:filename"
+        | "amp:threads"
+        | "opencode:storage" => "filename",
+        "claude:header-scan" | "gemini:chats" | "pi:sessions" => "header-scan",
+        "amp:cache" => "cache",
+        "amp:api" => "api",
+        _ => "other",
     }
 }
 
-fn render_subagents_head(output: &mut String, list: &SubagentListView) {
-    output.push_str("subagents:\n");
-    if list.agents.is_empty() {
-        output.push_str("  []\n");
-        return;
+/// Filters a subagent index to `options.statuses` and orders it by `options.sort`. Sorting is
+/// stable, and children without the sorted value (no timestamped messages, say) go last.
+fn arrange_subagent_list(
+    list: &mut SubagentListView,
+    provider: ProviderKind,
+    options: &SubagentListOptions,
+) -> Result<()> {
+    const STATUS_ORDER: [&str; 6] = [
+        STATUS_RUNNING,
+        STATUS_PENDING_INIT,
+        STATUS_ERRORED,
+        STATUS_COMPLETED,
+        STATUS_SHUTDOWN,
+        STATUS_NOT_FOUND,
+    ];
+    if let Some(status) = options
+        .statuses
+        .iter()
+        .find(|status| !STATUS_ORDER.contains(&status.as_str()))
+    {
+        return Err(XurlError::InvalidMode(format!(
+            "unsupported --status value: {status}; expected {}",
+            STATUS_ORDER.join(", ")
+        )));
+    }
+    if !options.statuses.is_empty() {
+        list.agents
+            .retain(|agent| options.statuses.contains(&agent.status));
     }
 
-    for agent in &list.agents {
-        output.push_str(&format!(
-            "  - agent_id: '{}'\n",
-            yaml_single_quoted(&agent.agent_id)
-        ));
-        output.push_str(&format!(
-            "    uri: '{}'\n",
-            yaml_single_quoted(&agents_thread_uri(
-                &list.query.provider,
-                &list.query.main_thread_id,
-                Some(&agent.agent_id),
-            ))
-        ));
-        push_yaml_string_with_indent(output, 4, "status", &agent.status);
-        push_yaml_string_with_indent(output, 4, "status_source", &agent.status_source);
-        if let Some(last_update) = &agent.last_update {
-            push_yaml_string_with_indent(output, 4, "last_update", last_update);
-        }
-        if let Some(child_thread) = &agent.child_thread
-            && let Some(path) = &child_thread.path
-        {
-            push_yaml_string_with_indent(output, 4, "thread_source", path);
+    match options.sort {
+        SubagentSort::Discovered => {}
+        SubagentSort::Status => list.agents.sort_by_key(|agent| {
+            STATUS_ORDER
+                .iter()
+                .position(|status| *status == agent.status)
+                .unwrap_or(STATUS_ORDER.len())
+        }),
+        SubagentSort::Started | SubagentSort::Duration => {
+            let span = |agent: &SubagentListItem| {
+                let path = Path::new(agent.child_thread.as_ref()?.path.as_deref()?);
+                let reader = open_thread_reader(path).ok()?;
+                render::thread_time_span(provider, path, reader).ok()?
+            };
+            let mut keyed = list
+                .agents
+                .drain(..)
+                .map(|agent| {
+                    let key = span(&agent).map(|span| match options.sort {
+                        SubagentSort::Started => span.first,
+                        _ => span.first - span.last,
+                    });
+                    (key.is_none(), key, agent)
+                })
+                .collect::<Vec<_>>();
+            keyed.sort_by_key(|(missing, key, _)| (*missing, *key));
+            list.agents = keyed.into_iter().map(|(_, _, agent)| agent).collect();
         }
     }
+    Ok(())
+}
+
+/// A child in the `subagents` list of a thread head.
+#[derive(Serialize)]
+struct SubagentHead {
+    agent_id: String,
+    uri: String,
+    status: String,
+    status_source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_update: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_source: Option<String>,
 }
 
-fn render_pi_entries_head(output: &mut String, list: &PiEntryListView) {
-    output.push_str("entries:\n");
-    if list.entries.is_empty() {
-        output.push_str("  []\n");
+/// How many children an index has, per status, their summed run time, and the longest-running
+/// one.
+#[derive(Serialize)]
+struct SubagentTotalsHead {
+    count: usize,
+    by_status: BTreeMap<String, usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    longest: Option<LongestSubagentHead>,
+}
+
+#[derive(Serialize)]
+struct LongestSubagentHead {
+    agent_id: String,
+    uri: String,
+    duration: String,
+}
+
+/// Fills in `subagents`, and `subagent_totals` for an index with children.
+fn fill_subagents_head(head: &mut ThreadHead, list: &SubagentListView, zone: &TimeZone) {
+    let child_uri = |agent_id: &str| {
+        agents_thread_uri(
+            &list.query.provider,
+            &list.query.main_thread_id,
+            Some(agent_id),
+        )
+    };
+    head.subagents = Some(
+        list.agents
+            .iter()
+            .map(|agent| SubagentHead {
+                agent_id: agent.agent_id.clone(),
+                uri: child_uri(&agent.agent_id),
+                status: agent.status.clone(),
+                status_source: agent.status_source.clone(),
+                last_update: agent
+                    .last_update
+                    .as_deref()
+                    .map(|last_update| render::format_timestamp(last_update, zone)),
+                thread_source: agent
+                    .child_thread
+                    .as_ref()
+                    .and_then(|child_thread| child_thread.path.clone()),
+            })
+            .collect(),
+    );
+
+    let totals = &list.totals;
+    if totals.by_status.is_empty() {
         return;
     }
+    let format_duration = |millis| timestamp::format_duration(Duration::from_millis(millis));
+    head.subagent_totals = Some(SubagentTotalsHead {
+        count: totals.by_status.values().sum(),
+        by_status: totals.by_status.clone(),
+        duration: (totals.duration_ms > 0).then(|| format_duration(totals.duration_ms)),
+        longest: totals.longest.as_ref().map(|longest| LongestSubagentHead {
+            agent_id: longest.agent_id.clone(),
+            uri: child_uri(&longest.agent_id),
+            duration: format_duration(longest.duration_ms),
+        }),
+    });
+}
 
-    for entry in &list.entries {
-        output.push_str(&format!(
-            "  - entry_id: '{}'\n",
-            yaml_single_quoted(&entry.entry_id)
-        ));
-        output.push_str(&format!(
-            "    uri: '{}'\n",
-            yaml_single_quoted(&agents_thread_uri(
-                &list.query.provider,
-                &list.query.session_id,
-                Some(&entry.entry_id),
-            ))
-        ));
-        push_yaml_string_with_indent(output, 4, "entry_type", &entry.entry_type);
-        if let Some(parent_id) = &entry.parent_id {
-            push_yaml_string_with_indent(output, 4, "parent_id", parent_id);
-        }
-        if let Some(timestamp) = &entry.timestamp {
-            push_yaml_string_with_indent(output, 4, "timestamp", timestamp);
-        }
-        if let Some(preview) = &entry.preview {
-            push_yaml_string_with_indent(output, 4, "preview", preview);
-        }
-        push_yaml_bool_with_indent(output, 4, "is_leaf", entry.is_leaf);
+/// Fills in the head of a subagent drill-down: where its thread is (the main thread's file
+/// when the child has none of its own), its status, and its child thread.
+fn fill_subagent_detail_head(
+    head: &mut ThreadHead,
+    detail: &SubagentDetailView,
+    resolved_main: &ResolvedThread,
+    zone: &TimeZone,
+) {
+    let child_thread = detail.child_thread.as_ref();
+    head.thread_source = Some(
+        child_thread
+            .and_then(|thread| thread.path.clone())
+            .unwrap_or_else(|| resolved_main.path.display().to_string()),
+    );
+    head.mode = "subagent_detail";
+    head.status = Some(detail.status.clone());
+    head.status_source = Some(detail.status_source.clone());
+    if let Some(child_thread) = child_thread {
+        head.child_thread_id = Some(child_thread.thread_id.clone());
+        head.child_thread_source = child_thread.path.clone();
+        head.child_last_updated_at = child_thread
+            .last_updated_at
+            .as_deref()
+            .map(|last_updated_at| render::format_timestamp(last_updated_at, zone));
     }
+    head.warnings = unique_warnings(&detail.warnings);
 }
 
-fn push_yaml_string_with_indent(output: &mut String, indent: usize, key: &str, value: &str) {
-    output.push_str(&format!(
-        "{}{key}: '{}'\n",
-        " ".repeat(indent),
-        yaml_single_quoted(value)
-    ));
+/// The tool calls of a thread for head summaries. Unreadable or malformed threads have none
+/// here and are left for the body renderer to report, so head mode still succeeds.
+fn thread_tool_calls(provider: ProviderKind, path: &Path) -> Vec<ToolCall> {
+    open_thread_reader(path)
+        .and_then(|reader| tools::extract_tool_calls_from_reader(provider, path, reader))
+        .unwrap_or_default()
 }
 
-fn push_yaml_bool_with_indent(output: &mut String, indent: usize, key: &str, value: bool) {
-    output.push_str(&format!("{}{key}: {value}\n", " ".repeat(indent)));
+/// A relationship in the `related` list of an Amp thread head.
+#[derive(Serialize)]
+struct RelatedHead {
+    uri: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<String>,
 }
 
-fn strip_frontmatter(markdown: String) -> String {
-    let Some(rest) = markdown.strip_prefix("---\n") else {
-        return markdown;
-    };
-    let Some((_, body)) = rest.split_once("\n---\n\n") else {
-        return markdown;
+/// Every relationship of an Amp thread. Unreadable threads are left for the body renderer to
+/// report.
+fn related_head(path: &Path, zone: &TimeZone) -> Vec<RelatedHead> {
+    let Ok(raw) = read_thread_raw(path) else {
+        return Vec::new();
     };
-    body.to_string()
+    render::amp_relationships(&raw)
+        .into_iter()
+        .map(|relationship| RelatedHead {
+            uri: relationship.uri,
+            kind: relationship.kind,
+            role: relationship.role,
+            timestamp: relationship
+                .timestamp
+                .as_deref()
+                .map(|timestamp| render::format_timestamp(timestamp, zone)),
+        })
+        .collect()
+}
+
+/// An entry in the `entries` list of a pi thread head.
+#[derive(Serialize)]
+struct PiEntryHead {
+    entry_id: String,
+    uri: String,
+    entry_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preview: Option<String>,
+    is_leaf: bool,
+}
+
+fn pi_entries_head(list: &PiEntryListView) -> Vec<PiEntryHead> {
+    list.entries
+        .iter()
+        .map(|entry| PiEntryHead {
+            entry_id: entry.entry_id.clone(),
+            uri: agents_thread_uri(
+                &list.query.provider,
+                &list.query.session_id,
+                Some(&entry.entry_id),
+            ),
+            entry_type: entry.entry_type.clone(),
+            parent_id: entry.parent_id.clone(),
+            timestamp: entry.timestamp.clone(),
+            preview: entry.preview.clone(),
+            is_leaf: entry.is_leaf,
+        })
+        .collect()
 }
 
-pub fn render_subagent_view_markdown(view: &SubagentView) -> String {
+pub fn render_subagent_view_markdown(view: &SubagentView, zone: &TimeZone) -> String {
     match view {
-        SubagentView::List(list_view) => render_subagent_list_markdown(list_view),
-        SubagentView::Detail(detail_view) => render_subagent_detail_markdown(detail_view),
+        SubagentView::List(list_view) => render_subagent_list_markdown(list_view, zone),
+        SubagentView::Detail(detail_view) => render_subagent_detail_markdown(detail_view, zone),
     }
 }
 
@@ -794,21 +3952,14 @@ pub fn resolve_pi_entry_list_view(
     let resolved = resolve_thread(uri, roots)?;
     let raw = read_thread_raw(&resolved.path)?;
 
-    let mut warnings = resolved.metadata.warnings;
+    let warnings = resolved.metadata.warnings;
     let mut entries = Vec::<PiEntryListItem>::new();
     let mut parent_ids = BTreeSet::<String>::new();
 
-    for (line_idx, line) in raw.lines().enumerate() {
-        let value = match jsonl::parse_json_line(Path::new("<pi:session>"), line_idx + 1, line) {
-            Ok(Some(value)) => value,
-            Ok(None) => continue,
-            Err(err) => {
-                warnings.push(format!(
-                    "failed to parse pi session line {}: {err}",
-                    line_idx + 1,
-                ));
-                continue;
-            }
+    // Corrupt lines are already counted in the head's `corrupt_lines` warning.
+    for line in raw.lines() {
+        let Some(value) = jsonl::parse_thread_line(line) else {
+            continue;
         };
 
         if value.get("type").and_then(Value::as_str) == Some("session") {
@@ -945,7 +4096,7 @@ fn resolve_pi_subagent_view(
     let resolved_main = resolve_thread(&main_uri, roots)?;
     let mut warnings = resolved_main.metadata.warnings.clone();
 
-    let records = discover_pi_session_records(&roots.pi_root, &mut warnings);
+    let records = discover_pi_session_records(&roots.pi_root, &roots.scan, &mut warnings);
     let main_record = records.get(&uri.session_id);
     let mut discovered = discover_pi_children(&uri.session_id, main_record, &records);
 
@@ -971,6 +4122,7 @@ fn resolve_pi_subagent_view(
 
         return Ok(SubagentView::List(SubagentListView {
             query: make_query(uri, None, true),
+            totals: SubagentTotals::default(),
             agents,
             warnings,
         }));
@@ -1002,6 +4154,7 @@ fn resolve_pi_subagent_view(
             status_source: child.status_source,
             child_thread: child.child_thread,
             excerpt: child.excerpt,
+            descendants: Vec::new(),
             warnings,
         }));
     }
@@ -1027,6 +4180,7 @@ fn resolve_pi_subagent_view(
         status_source: "inferred".to_string(),
         child_thread: None,
         excerpt: Vec::new(),
+        descendants: Vec::new(),
         warnings,
     }))
 }
@@ -1158,6 +4312,7 @@ fn discover_pi_children(
 
 fn discover_pi_session_records(
     pi_root: &Path,
+    scan: &ScanOptions,
     warnings: &mut Vec<String>,
 ) -> BTreeMap<String, PiSessionRecord> {
     let sessions_root = pi_root.join("sessions");
@@ -1166,10 +4321,8 @@ fn discover_pi_session_records(
     }
 
     let mut latest = BTreeMap::<String, (u64, PiSessionRecord)>::new();
-    for entry in WalkDir::new(&sessions_root)
+    for entry in scan_files(&sessions_root, scan, warnings)
         .into_iter()
-        .filter_map(std::result::Result::ok)
-        .filter(|entry| entry.file_type().is_file())
         .filter(|entry| {
             entry
                 .path()
@@ -1385,10 +4538,8 @@ fn normalize_hint_key(key: &str) -> String {
 
 fn collect_uuid_strings(value: &Value, ids: &mut Vec<String>) {
     match value {
-        Value::String(text) => {
-            if is_uuid_session_id(text) {
-                ids.push(text.to_ascii_lowercase());
-            }
+        Value::String(text) if is_uuid_session_id(text) => {
+            ids.push(text.to_ascii_lowercase());
         }
         Value::Array(items) => {
             for item in items {
@@ -1522,6 +4673,7 @@ fn build_amp_list_view(
 
     SubagentListView {
         query: make_query(uri, None, true),
+        totals: SubagentTotals::default(),
         agents,
         warnings,
     }
@@ -1620,6 +4772,7 @@ fn build_amp_detail_view(
         status_source,
         child_thread,
         excerpt,
+        descendants: Vec::new(),
         warnings,
     }
 }
@@ -1630,7 +4783,10 @@ fn analyze_amp_child_thread(
     roots: &ProviderRoots,
     warnings: &mut Vec<String>,
 ) -> Option<AmpChildAnalysis> {
-    let resolved_child = match AmpProvider::new(&roots.amp_root).resolve(child_thread_id) {
+    let resolved_child = match AmpProvider::new(&roots.amp_root)
+        .with_cache_root(&roots.amp_cache_root)
+        .resolve(child_thread_id)
+    {
         Ok(resolved) => resolved,
         Err(err) => {
             warnings.push(format!(
@@ -1958,6 +5114,7 @@ fn build_codex_list_view(
 
     SubagentListView {
         query: make_query(uri, None, true),
+        totals: SubagentTotals::default(),
         agents,
         warnings,
     }
@@ -2033,6 +5190,7 @@ fn build_codex_detail_view(
         status_source,
         child_thread,
         excerpt,
+        descendants: Vec::new(),
         warnings,
     }
 }
@@ -2043,6 +5201,7 @@ fn resolve_codex_child_thread(
     roots: &ProviderRoots,
 ) -> Option<(SubagentThreadRef, Vec<String>, Option<String>)> {
     let resolved = CodexProvider::new(&roots.codex_root)
+        .with_scan_options(&roots.scan)
         .resolve(agent_id)
         .ok()?;
     let raw = read_thread_raw(&resolved.path).ok()?;
@@ -2071,6 +5230,7 @@ fn resolve_codex_child_resolved(
     roots: &ProviderRoots,
 ) -> Option<(ResolvedThread, Vec<String>, SubagentThreadRef)> {
     let resolved = CodexProvider::new(&roots.codex_root)
+        .with_scan_options(&roots.scan)
         .resolve(agent_id)
         .ok()?;
     let raw = read_thread_raw(&resolved.path).ok()?;
@@ -2139,23 +5299,10 @@ fn parse_codex_parent_lifecycle(
     let mut warnings = Vec::new();
     let mut calls: HashMap<String, (String, Value, Option<String>)> = HashMap::new();
 
-    for (line_idx, line) in raw.lines().enumerate() {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
+    // Corrupt lines are already counted in the parent head's `corrupt_lines` warning.
+    for line in raw.lines() {
+        let Some(value) = jsonl::parse_thread_line(line) else {
             continue;
-        }
-
-        let value = match jsonl::parse_json_line(Path::new("<codex:parent>"), line_idx + 1, trimmed)
-        {
-            Ok(Some(value)) => value,
-            Ok(None) => continue,
-            Err(err) => {
-                warnings.push(format!(
-                    "failed to parse parent rollout line {}: {err}",
-                    line_idx + 1
-                ));
-                continue;
-            }
         };
 
         if value.get("type").and_then(Value::as_str) != Some("response_item") {
@@ -2421,6 +5568,7 @@ fn resolve_claude_subagent_view(
     if list {
         return Ok(SubagentView::List(SubagentListView {
             query: make_query(uri, None, true),
+            totals: SubagentTotals::default(),
             agents: records
                 .iter()
                 .map(|record| SubagentListItem {
@@ -2471,6 +5619,7 @@ fn resolve_claude_subagent_view(
                 last_updated_at: record.last_update.clone(),
             }),
             excerpt: record.excerpt,
+            descendants: Vec::new(),
             warnings,
         }));
     }
@@ -2488,6 +5637,7 @@ fn resolve_claude_subagent_view(
         status_source: "inferred".to_string(),
         child_thread: None,
         excerpt: Vec::new(),
+        descendants: Vec::new(),
         warnings,
     }))
 }
@@ -2546,6 +5696,7 @@ fn resolve_gemini_subagent_view(
 
         return Ok(SubagentView::List(SubagentListView {
             query: make_query(uri, None, true),
+            totals: SubagentTotals::default(),
             agents,
             warnings,
         }));
@@ -2629,6 +5780,7 @@ fn resolve_gemini_subagent_view(
         status_source,
         child_thread,
         excerpt,
+        descendants: Vec::new(),
         warnings,
     }))
 }
@@ -3195,6 +6347,7 @@ fn resolve_opencode_subagent_view(
 
         return Ok(SubagentView::List(SubagentListView {
             query: make_query(uri, None, true),
+            totals: SubagentTotals::default(),
             agents,
             warnings,
         }));
@@ -3226,6 +6379,7 @@ fn resolve_opencode_subagent_view(
             status_source: analysis.status_source,
             child_thread: analysis.child_thread,
             excerpt: analysis.excerpt,
+            descendants: Vec::new(),
             warnings,
         }));
     }
@@ -3243,6 +6397,7 @@ fn resolve_opencode_subagent_view(
         status_source: "inferred".to_string(),
         child_thread: None,
         excerpt: Vec::new(),
+        descendants: Vec::new(),
         warnings,
     }))
 }
@@ -3758,6 +6913,7 @@ fn collect_amp_query_candidates(
     collect_simple_file_candidates(
         ProviderKind::Amp,
         &threads_root,
+        &roots.scan,
         |path| {
             path.extension()
                 .and_then(|ext| ext.to_str())
@@ -3780,6 +6936,7 @@ fn collect_codex_query_candidates(
     candidates.extend(collect_simple_file_candidates(
         ProviderKind::Codex,
         &roots.codex_root.join("sessions"),
+        &roots.scan,
         |path| {
             path.file_name()
                 .and_then(|name| name.to_str())
@@ -3791,6 +6948,7 @@ fn collect_codex_query_candidates(
     candidates.extend(collect_simple_file_candidates(
         ProviderKind::Codex,
         &roots.codex_root.join("archived_sessions"),
+        &roots.scan,
         |path| {
             path.file_name()
                 .and_then(|name| name.to_str())
@@ -3812,13 +6970,7 @@ fn collect_claude_query_candidates(
     }
 
     let mut candidates = Vec::new();
-    for entry in WalkDir::new(&projects_root)
-        .into_iter()
-        .filter_map(std::result::Result::ok)
-    {
-        if !entry.file_type().is_file() {
-            continue;
-        }
+    for entry in scan_files(&projects_root, &roots.scan, warnings) {
         let path = entry.into_path();
         if path.file_name().and_then(|name| name.to_str()) == Some("sessions-index.json") {
             continue;
@@ -3840,6 +6992,29 @@ fn collect_claude_query_candidates(
     candidates
 }
 
+/// Maps a project path to the Claude project folders that may hold its sessions.
+///
+/// Claude names each folder after the project path with every non-alphanumeric character
+/// replaced by `-`; older releases only replaced `/`.
+fn claude_project_dirs(roots: &ProviderRoots, project: &str) -> Vec<PathBuf> {
+    let project_path = absolute_dir(project);
+    let project = project_path.to_string_lossy();
+    let project = project.trim_end_matches('/');
+
+    let munged = project
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '-' })
+        .collect::<String>();
+    let legacy = project.replace('/', "-");
+
+    let projects_root = roots.claude_root.join("projects");
+    let mut dirs = vec![projects_root.join(&munged)];
+    if legacy != munged {
+        dirs.push(projects_root.join(legacy));
+    }
+    dirs
+}
+
 fn collect_gemini_query_candidates(
     roots: &ProviderRoots,
     warnings: &mut Vec<String>,
@@ -3850,13 +7025,7 @@ fn collect_gemini_query_candidates(
     }
 
     let mut candidates = Vec::new();
-    for entry in WalkDir::new(&tmp_root)
-        .into_iter()
-        .filter_map(std::result::Result::ok)
-    {
-        if !entry.file_type().is_file() {
-            continue;
-        }
+    for entry in scan_files(&tmp_root, &roots.scan, warnings) {
         let path = entry.into_path();
         let is_session_file = path
             .file_name()
@@ -3926,13 +7095,7 @@ fn collect_pi_query_candidates(
     }
 
     let mut candidates = Vec::new();
-    for entry in WalkDir::new(&sessions_root)
-        .into_iter()
-        .filter_map(std::result::Result::ok)
-    {
-        if !entry.file_type().is_file() {
-            continue;
-        }
+    for entry in scan_files(&sessions_root, &roots.scan, warnings) {
         let path = entry.into_path();
         if path.extension().and_then(|ext| ext.to_str()) != Some("jsonl") {
             continue;
@@ -4094,6 +7257,7 @@ fn fetch_opencode_search_text(
 fn collect_simple_file_candidates<F, G>(
     provider: ProviderKind,
     root: &Path,
+    scan: &ScanOptions,
     path_filter: F,
     thread_id_extractor: G,
     warnings: &mut Vec<String>,
@@ -4107,13 +7271,7 @@ where
     }
 
     let mut candidates = Vec::new();
-    for entry in WalkDir::new(root)
-        .into_iter()
-        .filter_map(std::result::Result::ok)
-    {
-        if !entry.file_type().is_file() {
-            continue;
-        }
+    for entry in scan_files(root, scan, warnings) {
         let path = entry.into_path();
         if !path_filter(&path) {
             continue;
@@ -4253,6 +7411,7 @@ fn make_query(uri: &AgentsUri, agent_id: Option<String>, list: bool) -> Subagent
         main_thread_id: uri.session_id.clone(),
         agent_id,
         list,
+        depth: 0,
     }
 }
 
@@ -4283,7 +7442,7 @@ fn render_preview_text(content: &Value, max_chars: usize) -> String {
     truncate_preview(&text, max_chars)
 }
 
-fn truncate_preview(input: &str, max_chars: usize) -> String {
+pub(crate) fn truncate_preview(input: &str, max_chars: usize) -> String {
     let normalized = input.split_whitespace().collect::<Vec<_>>().join(" ");
     if normalized.chars().count() <= max_chars {
         return normalized;
@@ -4300,13 +7459,37 @@ fn truncate_preview(input: &str, max_chars: usize) -> String {
     out
 }
 
-fn render_subagent_list_markdown(view: &SubagentListView) -> String {
+fn render_subagent_list_markdown(view: &SubagentListView, zone: &TimeZone) -> String {
     let main_thread_uri = agents_thread_uri(&view.query.provider, &view.query.main_thread_id, None);
     let mut output = String::new();
     output.push_str("# Subagent Status\n\n");
     output.push_str(&format!("- Provider: `{}`\n", view.query.provider));
     output.push_str(&format!("- Main Thread: `{}`\n", main_thread_uri));
-    output.push_str("- Mode: `list`\n\n");
+    output.push_str("- Mode: `list`\n");
+    let totals = &view.totals;
+    if !totals.by_status.is_empty() {
+        let counts = totals
+            .by_status
+            .iter()
+            .map(|(status, count)| format!("{count} {status}"))
+            .collect::<Vec<_>>();
+        output.push_str(&format!("- Totals: {}\n", counts.join(", ")));
+    }
+    if totals.duration_ms > 0 {
+        output.push_str(&format!(
+            "- Total Duration: `{}`\n",
+            timestamp::format_duration(Duration::from_millis(totals.duration_ms))
+        ));
+    }
+    if let Some(longest) = &totals.longest {
+        output.push_str(&format!(
+            "- Longest: `{}/{}` (`{}`)\n",
+            main_thread_uri,
+            longest.agent_id,
+            timestamp::format_duration(Duration::from_millis(longest.duration_ms))
+        ));
+    }
+    output.push('\n');
 
     if view.agents.is_empty() {
         output.push_str("_No subagents found for this thread._\n");
@@ -4322,7 +7505,10 @@ fn render_subagent_list_markdown(view: &SubagentListView) -> String {
         ));
         output.push_str(&format!(
             "- Last Update: `{}`\n",
-            agent.last_update.as_deref().unwrap_or("unknown")
+            agent.last_update.as_deref().map_or_else(
+                || "unknown".to_string(),
+                |value| render::format_timestamp(value, zone)
+            )
         ));
         output.push_str(&format!(
             "- Relation: `{}`\n",
@@ -4343,7 +7529,7 @@ fn render_subagent_list_markdown(view: &SubagentListView) -> String {
     output
 }
 
-fn render_subagent_detail_markdown(view: &SubagentDetailView) -> String {
+fn render_subagent_detail_markdown(view: &SubagentDetailView, zone: &TimeZone) -> String {
     let main_thread_uri = agents_thread_uri(&view.query.provider, &view.query.main_thread_id, None);
     let mut output = String::new();
     output.push_str("# Subagent Thread\n\n");
@@ -4377,11 +7563,32 @@ fn render_subagent_detail_markdown(view: &SubagentDetailView) -> String {
             output.push_str(&format!("- Child Path: `{}`\n", path));
         }
         if let Some(last_updated_at) = &thread.last_updated_at {
-            output.push_str(&format!("- Child Last Update: `{}`\n", last_updated_at));
+            output.push_str(&format!(
+                "- Child Last Update: `{}`\n",
+                render::format_timestamp(last_updated_at, zone)
+            ));
         }
     }
     output.push('\n');
 
+    if view.query.depth > 0 {
+        output.push_str("## Descendant Agents\n\n");
+        if view.descendants.is_empty() {
+            output.push_str("_No descendant agents found._\n\n");
+        } else {
+            for descendant in &view.descendants {
+                output.push_str(&format!(
+                    "{}- `{}` `{}` (`{}`)\n",
+                    "  ".repeat(descendant.depth - 1),
+                    descendant.uri,
+                    descendant.status,
+                    descendant.status_source
+                ));
+            }
+            output.push('\n');
+        }
+    }
+
     output.push_str("## Lifecycle (Parent Thread)\n\n");
     if view.lifecycle.is_empty() {
         output.push_str("_No lifecycle events found in parent thread._\n\n");
@@ -4421,7 +7628,134 @@ mod tests {
 
     use tempfile::tempdir;
 
-    use crate::service::{extract_last_timestamp, read_thread_raw};
+    use crate::model::{
+        LineRange, ProviderKind, RawSlice, SubagentListItem, SubagentListOptions, SubagentListView,
+        SubagentQuery, SubagentRelation, SubagentSort, SubagentThreadRef, SubagentTotals,
+    };
+    use crate::service::{
+        arrange_subagent_list, claude_previous_session_id, copy_thread_file,
+        count_codex_rollout_messages, extract_last_timestamp, read_thread_raw,
+    };
+
+    #[test]
+    fn raw_slices_span_read_blocks() {
+        let temp = tempdir().expect("tempdir");
+        let path = temp.path().join("rollout.jsonl");
+        let lines = (1..=10_000)
+            .map(|number| format!("{{\"line\":{number}}}\n"))
+            .collect::<Vec<_>>();
+        fs::write(&path, lines.concat()).expect("write");
+        let slice = |slice: RawSlice| {
+            let mut out = Vec::new();
+            copy_thread_file(&path, slice, &mut out).expect("copy");
+            String::from_utf8(out).expect("utf8")
+        };
+
+        assert_eq!(slice(RawSlice::Tail(3)), lines[9_997..].concat());
+        assert_eq!(slice(RawSlice::Tail(9_000)), lines[1_000..].concat());
+        assert_eq!(
+            slice(RawSlice::Lines(LineRange {
+                first: 5_000,
+                last: Some(5_001),
+            })),
+            lines[4_999..5_001].concat()
+        );
+        assert_eq!(slice(RawSlice::Whole), lines.concat());
+    }
+
+    #[test]
+    fn subagent_list_is_filtered_and_sorted_by_status_or_span() {
+        let temp = tempdir().expect("tempdir");
+        let agent = |id: &str, status: &str, stamps: &[&str]| {
+            let path = temp.path().join(format!("{id}.jsonl"));
+            let lines = stamps
+                .iter()
+                .map(|stamp| {
+                    format!(
+                        r#"{{"type":"user","timestamp":"{stamp}","message":{{"role":"user","content":"go"}}}}"#
+                    )
+                })
+                .collect::<Vec<_>>();
+            fs::write(&path, lines.join("\n")).expect("write");
+            SubagentListItem {
+                agent_id: id.to_string(),
+                status: status.to_string(),
+                status_source: "inferred".to_string(),
+                last_update: None,
+                relation: SubagentRelation::default(),
+                child_thread: Some(SubagentThreadRef {
+                    thread_id: id.to_string(),
+                    path: Some(path.display().to_string()),
+                    last_updated_at: None,
+                }),
+            }
+        };
+        let list = SubagentListView {
+            query: SubagentQuery {
+                provider: "claude".to_string(),
+                main_thread_id: "main".to_string(),
+                agent_id: None,
+                list: true,
+                depth: 0,
+            },
+            agents: vec![
+                agent("quiet", "notFound", &[]),
+                agent(
+                    "short",
+                    "completed",
+                    &["2026-02-23T00:00:05Z", "2026-02-23T00:00:06Z"],
+                ),
+                agent(
+                    "long",
+                    "running",
+                    &["2026-02-23T00:00:09Z", "2026-02-23T00:10:00Z"],
+                ),
+                agent(
+                    "early",
+                    "completed",
+                    &["2026-02-23T00:00:01Z", "2026-02-23T00:00:03Z"],
+                ),
+            ],
+            totals: SubagentTotals::default(),
+            warnings: Vec::new(),
+        };
+        let arranged = |sort, statuses: &[&str]| {
+            let mut list = list.clone();
+            arrange_subagent_list(
+                &mut list,
+                ProviderKind::Claude,
+                &SubagentListOptions {
+                    sort,
+                    statuses: statuses.iter().map(ToString::to_string).collect(),
+                },
+            )
+            .map(|()| {
+                list.agents
+                    .into_iter()
+                    .map(|agent| agent.agent_id)
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            arranged(SubagentSort::Started, &[]).expect("sort"),
+            ["early", "short", "long", "quiet"]
+        );
+        assert_eq!(
+            arranged(SubagentSort::Duration, &[]).expect("sort"),
+            ["long", "early", "short", "quiet"]
+        );
+        assert_eq!(
+            arranged(SubagentSort::Status, &[]).expect("sort"),
+            ["long", "short", "early", "quiet"]
+        );
+        assert_eq!(
+            arranged(SubagentSort::Discovered, &["completed", "notFound"]).expect("filter"),
+            ["quiet", "short", "early"]
+        );
+        let err = arranged(SubagentSort::Discovered, &["done"]).expect_err("unknown status");
+        assert!(err.to_string().contains("unsupported --status value: done"));
+    }
 
     #[test]
     fn empty_file_returns_error() {
@@ -4440,4 +7774,42 @@ mod tests {
         let timestamp = extract_last_timestamp(raw).expect("must extract timestamp");
         assert_eq!(timestamp, "2026-02-23T00:00:02Z");
     }
+
+    #[test]
+    fn empty_codex_rollout_counts_zero_messages() {
+        let temp = tempdir().expect("tempdir");
+        let path = temp.path().join("rollout.jsonl");
+        fs::write(&path, "").expect("write");
+
+        let count = count_codex_rollout_messages(&path).expect("must count");
+        assert_eq!(count, 0);
+    }
+    #[test]
+    fn claude_resume_links_to_file_holding_parent_uuid() {
+        let temp = tempdir().expect("tempdir");
+        let old_id = "11111111-1111-4111-8111-111111111111";
+        let new_id = "22222222-2222-4222-8222-222222222222";
+        fs::write(
+            temp.path().join(format!("{old_id}.jsonl")),
+            format!(r#"{{"type":"user","uuid":"aaaa","parentUuid":null,"sessionId":"{old_id}"}}"#),
+        )
+        .expect("write old");
+        let path = temp.path().join(format!("{new_id}.jsonl"));
+        fs::write(
+            &path,
+            format!(
+                r#"{{"type":"user","uuid":"bbbb","parentUuid":"aaaa","sessionId":"{new_id}"}}"#
+            ),
+        )
+        .expect("write new");
+
+        assert_eq!(
+            claude_previous_session_id(new_id, &path).as_deref(),
+            Some(old_id)
+        );
+        assert_eq!(
+            claude_previous_session_id(old_id, &temp.path().join(format!("{old_id}.jsonl"))),
+            None
+        );
+    }
 }
@@ -0,0 +1,296 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Result, XurlError};
+use crate::frontmatter::push_yaml_fields;
+use crate::model::{ProviderKind, ResolutionMeta, ResolvedThread};
+use crate::timestamp;
+use crate::uri::{AgentsUri, SnapshotUri};
+
+/// The file next to a snapshot's thread copy that records where the copy came from.
+const MANIFEST_FILE_NAME: &str = "snapshot.json";
+
+/// What `xurl snapshot` records about the thread it copied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    /// The `agents://` URI the snapshot was taken from.
+    pub uri: String,
+    /// Name of the thread copy in the snapshot directory, the same as the original file's.
+    pub file_name: String,
+    /// Where the thread file was when the snapshot was taken.
+    pub source_path: String,
+    pub created_at: String,
+}
+
+/// A snapshot read back: the copy, and the thread URI it renders as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedSnapshot {
+    pub uri: SnapshotUri,
+    pub thread_uri: AgentsUri,
+    pub thread: ResolvedThread,
+    pub manifest: SnapshotManifest,
+}
+
+/// Copies the thread file of `uri` into `<root>/<sha256>/`, named as the original, with a
+/// `snapshot.json` manifest beside it. Snapshots are keyed by content, so taking one of an
+/// unchanged thread again returns the existing snapshot.
+///
+/// The file is read once, hashed while it streams into a temporary file in `root`, and then
+/// renamed into place, so a thread that is still being written cannot end up under a hash of
+/// different bytes.
+pub fn snapshot_thread(
+    uri: &AgentsUri,
+    resolved: &ResolvedThread,
+    root: &Path,
+) -> Result<SnapshotUri> {
+    let io_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| XurlError::Io { path, source }
+    };
+    let file_name = resolved.path.file_name().map_or_else(
+        || "thread".to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    fs::create_dir_all(root).map_err(io_error(root))?;
+    let temp_path = root.join(format!(".{file_name}.{}.tmp", std::process::id()));
+    let hash = match copy_and_hash(&resolved.path, &temp_path) {
+        Ok(hash) => hash,
+        Err(err) => {
+            let _ = fs::remove_file(&temp_path);
+            return Err(err);
+        }
+    };
+    let snapshot = SnapshotUri { hash };
+    let dir = root.join(&snapshot.hash);
+    let manifest_path = dir.join(MANIFEST_FILE_NAME);
+    if manifest_path.is_file() {
+        let _ = fs::remove_file(&temp_path);
+        return Ok(snapshot);
+    }
+
+    let copy = dir.join(&file_name);
+    if let Err(err) = fs::create_dir_all(&dir)
+        .map_err(io_error(&dir))
+        .and_then(|()| fs::rename(&temp_path, &copy).map_err(io_error(&copy)))
+    {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let manifest = SnapshotManifest {
+        uri: uri.as_agents_string(),
+        file_name,
+        source_path: resolved.path.display().to_string(),
+        created_at: timestamp::format_millis(i64::try_from(millis).unwrap_or(i64::MAX)),
+    };
+    let body = serde_json::to_string_pretty(&manifest)
+        .map_err(|err| XurlError::Serialization(err.to_string()))?;
+    // The manifest goes last: a snapshot directory without one is an interrupted copy that
+    // the next snapshot of the thread redoes.
+    fs::write(&manifest_path, format!("{body}\n")).map_err(io_error(&manifest_path))?;
+    Ok(snapshot)
+}
+
+/// Finds the thread copy of `uri` under `root`, ready to render like the thread it was taken
+/// from.
+pub fn resolve_snapshot(uri: &SnapshotUri, root: &Path) -> Result<ResolvedSnapshot> {
+    let dir = root.join(&uri.hash);
+    let manifest_path = dir.join(MANIFEST_FILE_NAME);
+    let raw = fs::read_to_string(&manifest_path).map_err(|source| {
+        if source.kind() == io::ErrorKind::NotFound {
+            XurlError::SnapshotNotFound {
+                uri: uri.as_string(),
+                root: root.to_path_buf(),
+            }
+        } else {
+            XurlError::Io {
+                path: manifest_path.clone(),
+                source,
+            }
+        }
+    })?;
+    let manifest = serde_json::from_str::<SnapshotManifest>(&raw).map_err(|source| {
+        XurlError::InvalidJsonLine {
+            path: manifest_path.clone(),
+            line: 1,
+            source,
+        }
+    })?;
+    // The copy must sit in the snapshot directory; a name with separators could point anywhere.
+    if manifest.file_name.is_empty()
+        || manifest.file_name.contains(['/', '\\'])
+        || matches!(manifest.file_name.as_str(), "." | "..")
+    {
+        return Err(XurlError::InvalidSnapshotManifest {
+            path: manifest_path,
+            message: format!(
+                "file_name `{}` must be a plain file name",
+                manifest.file_name
+            ),
+        });
+    }
+    let thread_uri = AgentsUri::parse(&manifest.uri)?;
+    let thread = ResolvedThread {
+        provider: thread_uri.provider,
+        session_id: thread_uri.session_id.clone(),
+        path: dir.join(&manifest.file_name),
+        metadata: ResolutionMeta {
+            source: "snapshot".to_string(),
+            candidate_count: 1,
+            ..ResolutionMeta::default()
+        },
+    };
+    Ok(ResolvedSnapshot {
+        uri: uri.clone(),
+        thread_uri,
+        thread,
+        manifest,
+    })
+}
+
+/// The fields of a snapshot head, in the order they are written.
+#[derive(Serialize)]
+struct SnapshotHead<'a> {
+    uri: String,
+    thread_uri: &'a str,
+    provider: ProviderKind,
+    session_id: &'a str,
+    sha256: &'a str,
+    snapshot_path: String,
+    source_path: &'a str,
+    created_at: &'a str,
+}
+
+/// The frontmatter of a snapshot read: the snapshot and the thread it was taken from.
+pub fn render_snapshot_head_markdown(snapshot: &ResolvedSnapshot) -> Result<String> {
+    let mut output = String::from("---\n");
+    push_yaml_fields(
+        &mut output,
+        &SnapshotHead {
+            uri: snapshot.uri.as_string(),
+            thread_uri: &snapshot.manifest.uri,
+            provider: snapshot.thread.provider,
+            session_id: &snapshot.thread.session_id,
+            sha256: &snapshot.uri.hash,
+            snapshot_path: snapshot.thread.path.display().to_string(),
+            source_path: &snapshot.manifest.source_path,
+            created_at: &snapshot.manifest.created_at,
+        },
+    )?;
+    output.push_str("---\n");
+    Ok(output)
+}
+
+/// Streams `source` into a new file at `target` and returns the SHA-256 of the bytes copied.
+fn copy_and_hash(source: &Path, target: &Path) -> Result<String> {
+    let mut input = fs::File::open(source).map_err(|source_err| XurlError::Io {
+        path: source.to_path_buf(),
+        source: source_err,
+    })?;
+    let target_error = |source| XurlError::Io {
+        path: target.to_path_buf(),
+        source,
+    };
+    let mut output = fs::File::create(target).map_err(target_error)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = input
+            .read(&mut buffer)
+            .map_err(|source_err| XurlError::Io {
+                path: source.to_path_buf(),
+                source: source_err,
+            })?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        output.write_all(&buffer[..read]).map_err(target_error)?;
+    }
+    output.sync_all().map_err(target_error)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use crate::error::XurlError;
+    use crate::model::{ResolutionMeta, ResolvedThread};
+    use crate::snapshot::{resolve_snapshot, snapshot_thread};
+    use crate::uri::{AgentsUri, SnapshotUri};
+
+    #[test]
+    fn snapshots_are_keyed_by_content_and_outlive_the_thread() {
+        let temp = tempdir().expect("tempdir");
+        let thread_path = temp.path().join("rollout-2026-02-23T04-48-50-thread.jsonl");
+        fs::write(&thread_path, "{\"type\":\"session_meta\"}\n").expect("write");
+        let uri = AgentsUri::parse("agents://codex/019c871c-b1f9-7f60-9c4f-87ed09f13592")
+            .expect("parse uri");
+        let resolved = ResolvedThread {
+            provider: uri.provider,
+            session_id: uri.session_id.clone(),
+            path: thread_path.clone(),
+            metadata: ResolutionMeta::default(),
+        };
+        let root = temp.path().join("snapshots");
+
+        let snapshot = snapshot_thread(&uri, &resolved, &root).expect("snapshot");
+        assert_eq!(
+            snapshot_thread(&uri, &resolved, &root).expect("snapshot again"),
+            snapshot
+        );
+        fs::remove_file(&thread_path).expect("remove thread");
+
+        let found = resolve_snapshot(&snapshot, &root).expect("resolve");
+        assert_eq!(found.thread_uri, uri);
+        assert_eq!(
+            fs::read_to_string(&found.thread.path).expect("read copy"),
+            "{\"type\":\"session_meta\"}\n"
+        );
+        assert_eq!(
+            found.thread.path.file_name(),
+            thread_path.file_name(),
+            "the copy keeps the original file name"
+        );
+
+        let leftovers = fs::read_dir(&root)
+            .expect("read root")
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            .count();
+        assert_eq!(leftovers, 0, "the temporary copy is renamed into place");
+
+        let manifest_path = root.join(&snapshot.hash).join("snapshot.json");
+        let manifest = fs::read_to_string(&manifest_path).expect("read manifest");
+        fs::write(
+            &manifest_path,
+            manifest.replace(
+                "rollout-2026-02-23T04-48-50-thread.jsonl",
+                "../../escape.jsonl",
+            ),
+        )
+        .expect("write manifest");
+        assert!(matches!(
+            resolve_snapshot(&snapshot, &root),
+            Err(XurlError::InvalidSnapshotManifest { .. })
+        ));
+
+        let missing = SnapshotUri::parse(&format!("snapshot://{}", "0".repeat(64))).expect("uri");
+        assert!(matches!(
+            resolve_snapshot(&missing, &root),
+            Err(XurlError::SnapshotNotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+use std::io::BufRead;
+
+use serde_json::Value;
+
+use crate::jsonl;
+use crate::model::{ProviderKind, ThreadStatus};
+
+/// Infers whether a main thread is still running, finished, or failed from the last records
+/// its provider wrote.
+///
+/// Codex rollouts decide by their last `task_started`, `task_complete`, `turn_aborted`, or
+/// `error` event; Claude sessions by their last `result` entry or message, where a final
+/// assistant reply without tool calls counts as completed; OpenCode exports by whether their
+/// last message is an assistant message with `time.completed` or an `error`. Amp, Gemini, and pi threads record
+/// none of these and are always `Unknown`, as are threads with no deciding record.
+pub fn thread_status<R: BufRead>(provider: ProviderKind, reader: R) -> ThreadStatus {
+    let decide = match provider {
+        ProviderKind::Codex => codex_status,
+        ProviderKind::Claude => claude_status,
+        ProviderKind::Opencode => opencode_status,
+        ProviderKind::Amp | ProviderKind::Gemini | ProviderKind::Pi => {
+            return ThreadStatus::Unknown;
+        }
+    };
+    reader
+        .lines()
+        .map_while(std::result::Result::ok)
+        .filter_map(|line| jsonl::parse_thread_line(&line))
+        .filter_map(|value| decide(&value))
+        .last()
+        .unwrap_or(ThreadStatus::Unknown)
+}
+
+fn codex_status(value: &Value) -> Option<ThreadStatus> {
+    if value.get("type").and_then(Value::as_str) != Some("event_msg") {
+        return None;
+    }
+    match value.pointer("/payload/type").and_then(Value::as_str)? {
+        "task_started" => Some(ThreadStatus::Running),
+        "task_complete" => Some(ThreadStatus::Completed),
+        "turn_aborted" | "error" => Some(ThreadStatus::Failed),
+        _ => None,
+    }
+}
+
+/// A `result` entry reports how a run ended. Otherwise a user message or tool call means the
+/// agent still had work to do, and an assistant reply without tool calls ends the turn.
+fn claude_status(value: &Value) -> Option<ThreadStatus> {
+    match value.get("type").and_then(Value::as_str)? {
+        "result" => {
+            let is_error = value.get("is_error").and_then(Value::as_bool) == Some(true)
+                || value
+                    .get("subtype")
+                    .and_then(Value::as_str)
+                    .is_some_and(|subtype| subtype.starts_with("error"));
+            Some(if is_error {
+                ThreadStatus::Failed
+            } else {
+                ThreadStatus::Completed
+            })
+        }
+        "user" => Some(ThreadStatus::Running),
+        "assistant" => {
+            if value.get("isApiErrorMessage").and_then(Value::as_bool) == Some(true) {
+                return Some(ThreadStatus::Failed);
+            }
+            let message = value.get("message")?;
+            let calls_tools = message
+                .get("content")
+                .and_then(Value::as_array)
+                .is_some_and(|items| {
+                    items
+                        .iter()
+                        .any(|item| item.get("type").and_then(Value::as_str) == Some("tool_use"))
+                });
+            let stopped = message.get("stop_reason").and_then(Value::as_str);
+            if calls_tools || stopped == Some("tool_use") {
+                Some(ThreadStatus::Running)
+            } else {
+                Some(ThreadStatus::Completed)
+            }
+        }
+        _ => None,
+    }
+}
+
+fn opencode_status(value: &Value) -> Option<ThreadStatus> {
+    if value.get("type").and_then(Value::as_str) != Some("message") {
+        return None;
+    }
+    let message = value.get("message")?;
+    match message.get("role").and_then(Value::as_str)? {
+        "user" => Some(ThreadStatus::Running),
+        "assistant" if message.get("error").is_some_and(|error| !error.is_null()) => {
+            Some(ThreadStatus::Failed)
+        }
+        "assistant" if message.pointer("/time/completed").is_some() => {
+            Some(ThreadStatus::Completed)
+        }
+        "assistant" => Some(ThreadStatus::Running),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::thread_status;
+    use crate::model::{ProviderKind, ThreadStatus};
+
+    #[test]
+    fn status_follows_the_last_deciding_record() {
+        let codex = [
+            r#"{"type":"event_msg","payload":{"type":"task_started"}}"#,
+            r#"{"type":"event_msg","payload":{"type":"task_complete"}}"#,
+            r#"{"type":"event_msg","payload":{"type":"task_started"}}"#,
+            r#"{"type":"event_msg","payload":{"type":"agent_message","message":"working"}}"#,
+        ];
+        let status =
+            |provider, lines: &[&str]| thread_status(provider, lines.join("\n").as_bytes());
+        assert_eq!(status(ProviderKind::Codex, &codex), ThreadStatus::Running);
+        assert_eq!(
+            status(ProviderKind::Codex, &codex[..2]),
+            ThreadStatus::Completed
+        );
+        assert_eq!(
+            status(
+                ProviderKind::Codex,
+                &[
+                    r#"{"type":"event_msg","payload":{"type":"turn_aborted","reason":"interrupted"}}"#
+                ]
+            ),
+            ThreadStatus::Failed
+        );
+
+        let claude = [
+            r#"{"type":"user","message":{"role":"user","content":"fix it"}}"#,
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Bash","input":{}}]}}"#,
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Fixed"}]}}"#,
+            r#"{"type":"result","subtype":"error_max_turns","is_error":true}"#,
+        ];
+        assert_eq!(
+            status(ProviderKind::Claude, &claude[..2]),
+            ThreadStatus::Running
+        );
+        assert_eq!(
+            status(ProviderKind::Claude, &claude[..3]),
+            ThreadStatus::Completed
+        );
+        assert_eq!(status(ProviderKind::Claude, &claude), ThreadStatus::Failed);
+
+        let opencode = [
+            r#"{"type":"message","message":{"role":"user","time":{"created":1}},"parts":[]}"#,
+            r#"{"type":"message","message":{"role":"assistant","time":{"created":2,"completed":3}},"parts":[]}"#,
+            r#"{"type":"message","message":{"role":"assistant","time":{"created":4},"error":{"name":"APIError"}},"parts":[]}"#,
+        ];
+        assert_eq!(
+            status(ProviderKind::Opencode, &opencode[..1]),
+            ThreadStatus::Running
+        );
+        assert_eq!(
+            status(ProviderKind::Opencode, &opencode[..2]),
+            ThreadStatus::Completed
+        );
+        assert_eq!(
+            status(ProviderKind::Opencode, &opencode),
+            ThreadStatus::Failed
+        );
+
+        assert_eq!(status(ProviderKind::Pi, &codex), ThreadStatus::Unknown);
+        assert_eq!(status(ProviderKind::Codex, &[]), ThreadStatus::Unknown);
+    }
+}
@@ -0,0 +1,39 @@
+use crate::error::{Result, XurlError};
+use crate::model::RenderOptions;
+use crate::provider::ProviderRoots;
+use crate::service::{render_thread_markdown, resolve_thread};
+use crate::template::fill_template;
+use crate::uri::AgentsUri;
+
+/// Prompt `xurl summarize` sends when no template is given.
+pub const DEFAULT_SUMMARY_TEMPLATE: &str = "Summarize the agent conversation below ({{uri}}) for someone who has not read it. Cover the goal, what was done, the outcome, and any open questions or follow-ups. Reply with the summary only, in markdown.\n\n{{thread}}\n";
+
+/// Rendered thread bytes a summary prompt includes by default. Provider CLIs take the prompt
+/// as one command-line argument, which Linux caps at 128 KiB.
+pub const SUMMARY_MAX_THREAD_BYTES: u64 = 100 * 1024;
+
+/// Renders the thread at `uri` with `options` and fills `template` with it. The template must
+/// contain `{{thread}}` and may use `{{uri}}`, `{{provider}}`, and `{{session_id}}`.
+pub fn summary_prompt(
+    uri: &AgentsUri,
+    roots: &ProviderRoots,
+    template: &str,
+    options: &RenderOptions,
+) -> Result<String> {
+    if !template.contains("{{thread}}") {
+        return Err(XurlError::InvalidMode(
+            "summary template must include {{thread}} where the rendered thread goes".to_string(),
+        ));
+    }
+    let resolved = resolve_thread(uri, roots)?;
+    let thread = render_thread_markdown(uri, &resolved, options)?;
+    fill_template(
+        template,
+        &[
+            ("uri", &uri.as_agents_string()),
+            ("provider", &uri.provider.to_string()),
+            ("session_id", &uri.session_id),
+            ("thread", thread.trim_end()),
+        ],
+    )
+}
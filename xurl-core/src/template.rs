@@ -0,0 +1,195 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::XurlConfig;
+use crate::error::{Result, XurlError};
+use crate::model::RenderOptions;
+use crate::provider::ProviderRoots;
+use crate::service::{expand_latest_alias, render_thread_markdown, resolve_thread};
+use crate::uri::{AgentsUri, expand_alias, is_alias_name};
+
+/// Rendered bytes each inlined thread keeps, so a few of them still fit in the one
+/// command-line argument a provider CLI takes the prompt as.
+pub const INLINE_THREAD_MAX_BYTES: u64 = 64 * 1024;
+
+/// Directory `--template` names are read from: `templates/` beside the config file, so
+/// `~/.xurl/templates` unless `XURL_CONFIG` points elsewhere.
+pub fn templates_dir() -> Result<PathBuf> {
+    let config = XurlConfig::path()?;
+    Ok(config
+        .parent()
+        .map(|dir| dir.join("templates"))
+        .unwrap_or_else(|| PathBuf::from("templates")))
+}
+
+/// Reads the write-mode template `name` from `<templates_dir>/<name>.md`.
+pub fn load_template(name: &str) -> Result<String> {
+    if !is_alias_name(name) {
+        return Err(XurlError::InvalidMode(format!(
+            "invalid template name `{name}`; use letters, digits, `-`, and `_`"
+        )));
+    }
+    let path = templates_dir()?.join(format!("{name}.md"));
+    match fs::read_to_string(&path) {
+        Ok(template) => Ok(template),
+        Err(source) if source.kind() == std::io::ErrorKind::NotFound => {
+            Err(XurlError::InvalidMode(format!(
+                "template `{name}` not found; create {}",
+                path.display()
+            )))
+        }
+        Err(source) => Err(XurlError::Io { path, source }),
+    }
+}
+
+/// Fills a write-mode template: `{{data}}` becomes the `-d` or piped prompt,
+/// `{{file:<path>}}` the contents of a file, and `{{thread:<uri>}}` the thread rendered as
+/// markdown, like `xurl <uri>` prints it, cut at [`INLINE_THREAD_MAX_BYTES`].
+pub fn expand_prompt_template(template: &str, data: &str, roots: &ProviderRoots) -> Result<String> {
+    expand(template, |name| {
+        if name == "data" {
+            return Ok(Some(data.to_string()));
+        }
+        if let Some(path) = name.strip_prefix("file:") {
+            let path = PathBuf::from(path.trim());
+            return fs::read_to_string(&path)
+                .map(Some)
+                .map_err(|source| XurlError::Io { path, source });
+        }
+        if let Some(uri) = name.strip_prefix("thread:") {
+            return inline_thread(uri.trim(), roots).map(Some);
+        }
+        Ok(None)
+    })
+    .map_err(|err| match err {
+        XurlError::InvalidMode(message) if message.starts_with("unknown template placeholder") => {
+            XurlError::InvalidMode(format!(
+                "{message}; expected {{{{data}}}}, {{{{file:<path>}}}}, or {{{{thread:<uri>}}}}"
+            ))
+        }
+        err => err,
+    })
+}
+
+/// Replaces each `{{name}}` in `template` with its value. A name without a value is an error,
+/// so typos do not reach the provider.
+pub fn fill_template(template: &str, values: &[(&str, &str)]) -> Result<String> {
+    expand(template, |name| {
+        Ok(values
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.to_string()))
+    })
+    .map_err(|err| match err {
+        XurlError::InvalidMode(message) => XurlError::InvalidMode(format!(
+            "{message}; expected one of {}",
+            values
+                .iter()
+                .map(|(name, _)| format!("{{{{{name}}}}}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        err => err,
+    })
+}
+
+/// Replaces each `{{name}}` with what `resolve` returns for it; `None` is an unknown
+/// placeholder. An unclosed `{{` is kept as written.
+fn expand(
+    template: &str,
+    mut resolve: impl FnMut(&str) -> Result<Option<String>>,
+) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + end];
+        let Some(value) = resolve(name)? else {
+            return Err(XurlError::InvalidMode(format!(
+                "unknown template placeholder {{{{{name}}}}}"
+            )));
+        };
+        output.push_str(&rest[..start]);
+        output.push_str(&value);
+        rest = &rest[start + 2 + end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Renders the thread at `input` (a URI, shorthand, or alias) for inlining into a prompt,
+/// eliding large tool outputs and stopping after [`INLINE_THREAD_MAX_BYTES`].
+pub fn inline_thread(input: &str, roots: &ProviderRoots) -> Result<String> {
+    let config = XurlConfig::load()?;
+    let uri = expand_latest_alias(
+        &AgentsUri::parse(&expand_alias(input, &config.aliases)?)?,
+        roots,
+    )?;
+    if uri.is_collection() {
+        return Err(XurlError::InvalidMode(format!(
+            "{{{{thread:{input}}}}} requires a thread URI: agents://<provider>/<session_id>"
+        )));
+    }
+    let options = RenderOptions {
+        elide_bytes: Some(config.render.elide_bytes),
+        max_bytes: Some(INLINE_THREAD_MAX_BYTES),
+        ..RenderOptions::default()
+    };
+    let resolved = resolve_thread(&uri, roots)?;
+    Ok(render_thread_markdown(&uri, &resolved, &options)?
+        .trim_end()
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::{expand_prompt_template, fill_template};
+    use crate::provider::ProviderRoots;
+
+    #[test]
+    fn fill_template_replaces_placeholders_and_rejects_unknown_names() {
+        let filled = fill_template(
+            "Summarize {{uri}}:\n\n{{thread}}\n{{thread}} {{not closed",
+            &[("uri", "agents://codex/abc"), ("thread", "# Thread")],
+        )
+        .expect("fill");
+        assert_eq!(
+            filled,
+            "Summarize agents://codex/abc:\n\n# Thread\n# Thread {{not closed"
+        );
+
+        let err = fill_template("{{url}}", &[("uri", "x")]).expect_err("unknown");
+        assert!(
+            format!("{err}").contains("unknown template placeholder {{url}}"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn prompt_templates_expand_data_and_files() {
+        let temp = tempdir().expect("tempdir");
+        let diff = temp.path().join("change.diff");
+        fs::write(&diff, "-old\n+new\n").expect("write");
+        let roots = ProviderRoots::from_env_or_home().expect("roots");
+
+        let prompt = expand_prompt_template(
+            &format!(
+                "Review this diff. {{{{data}}}}\n\n{{{{file:{}}}}}",
+                diff.display()
+            ),
+            "Focus on naming.",
+            &roots,
+        )
+        .expect("expand");
+        assert_eq!(prompt, "Review this diff. Focus on naming.\n\n-old\n+new\n");
+
+        let err = expand_prompt_template("{{diff}}", "", &roots).expect_err("unknown");
+        assert!(format!("{err}").contains("{{file:<path>}}"), "{err}");
+    }
+}
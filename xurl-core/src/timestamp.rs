@@ -0,0 +1,359 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::error::XurlError;
+
+/// The time zone rendered timestamps are shown in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TimeZone {
+    #[default]
+    Utc,
+    /// A fixed offset from UTC, in seconds.
+    Fixed(i32),
+    /// A zone with daylight-saving rules, from the system time zone database (or the copy
+    /// bundled on Windows).
+    Zone(jiff::tz::TimeZone),
+}
+
+impl TimeZone {
+    /// The zone of this machine: `TZ` when set, otherwise the system setting. Fails rather
+    /// than guessing UTC when neither names a zone.
+    pub fn local() -> Result<Self, XurlError> {
+        jiff::tz::TimeZone::try_system()
+            .map(Self::Zone)
+            .map_err(|err| {
+                XurlError::InvalidMode(format!(
+                    "cannot determine the local timezone: {err}; pass --timezone utc, an offset like +08:00, or a zone name like Asia/Shanghai"
+                ))
+            })
+    }
+
+    /// The UTC offset, in seconds, at `utc_seconds` since the Unix epoch.
+    pub fn offset_at(&self, utc_seconds: i64) -> i32 {
+        match self {
+            Self::Utc => 0,
+            Self::Fixed(offset) => *offset,
+            Self::Zone(zone) => jiff::Timestamp::from_second(utc_seconds)
+                .map_or(0, |at| zone.to_offset(at).seconds()),
+        }
+    }
+}
+
+impl fmt::Display for TimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Utc => write!(f, "utc"),
+            Self::Fixed(offset) => write!(f, "{}", format_offset(*offset)),
+            Self::Zone(zone) => write!(f, "{}", zone.iana_name().unwrap_or("local")),
+        }
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = XurlError;
+
+    /// Accepts `local`, `utc`, an offset like `+08:00` or `-0530`, or an IANA zone name like
+    /// `Asia/Shanghai`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("local") {
+            return Self::local();
+        }
+        if value.eq_ignore_ascii_case("utc") || value.eq_ignore_ascii_case("z") {
+            return Ok(Self::Utc);
+        }
+        if let Some(offset) = parse_offset(value) {
+            return Ok(if offset == 0 {
+                Self::Utc
+            } else {
+                Self::Fixed(offset)
+            });
+        }
+        jiff::tz::TimeZone::get(value).map(Self::Zone).map_err(|_| {
+            XurlError::InvalidMode(format!(
+                "unknown timezone: {value}; expected local, utc, an offset like +08:00, or a zone name like Asia/Shanghai"
+            ))
+        })
+    }
+}
+
+/// Reads a provider timestamp: an RFC 3339 string, or epoch seconds or milliseconds as a
+/// number. Returns it as an RFC 3339 string, keeping provider strings as written.
+pub fn from_value(value: &Value) -> Option<String> {
+    if let Some(text) = value.as_str() {
+        return parse_millis(text).map(|_| text.to_string());
+    }
+    value
+        .as_i64()
+        .map(|number| format_millis(epoch_millis(number)))
+}
+
+/// Reads an epoch number as milliseconds, taking numbers too small to be milliseconds since
+/// 1973 as seconds. Epoch seconds stay below 1e11 until the year 5138.
+pub fn epoch_millis(number: i64) -> i64 {
+    if number.abs() < 100_000_000_000 {
+        number * 1000
+    } else {
+        number
+    }
+}
+
+/// Parses an RFC 3339 timestamp like `2026-02-23T04:48:50.123Z` or
+/// `2026-02-23T12:48:50+08:00` into milliseconds since the Unix epoch.
+pub fn parse_millis(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let (date, rest) = text.split_at_checked(10)?;
+    let rest = rest.strip_prefix(['T', 't', ' '])?;
+    let (year, month, day) = split3(date, '-')?;
+
+    let offset_at = rest
+        .char_indices()
+        .skip(8)
+        .find(|(_, ch)| matches!(ch, 'Z' | 'z' | '+' | '-'))
+        .map_or(rest.len(), |(idx, _)| idx);
+    let (time, offset) = rest.split_at(offset_at);
+    let (clock, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let (hour, minute, second) = split3(clock, ':')?;
+    if hour > 23
+        || minute > 59
+        || second > 60
+        || !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+    {
+        return None;
+    }
+    let millis = fraction
+        .chars()
+        .chain("000".chars())
+        .take(3)
+        .collect::<String>()
+        .parse::<i64>()
+        .ok()?;
+
+    let offset_minutes = match offset {
+        "" | "Z" | "z" => 0,
+        _ => {
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let (hours, minutes) = offset[1..].split_once(':')?;
+            sign * (hours.parse::<i64>().ok()? * 60 + minutes.parse::<i64>().ok()?)
+        }
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second
+        - offset_minutes * 60;
+    Some(seconds * 1000 + millis)
+}
+
+/// Formats milliseconds since the Unix epoch as a UTC RFC 3339 timestamp to the second.
+pub fn format_millis(millis: i64) -> String {
+    format_millis_in(millis, &TimeZone::Utc)
+}
+
+/// Formats milliseconds since the Unix epoch as an RFC 3339 timestamp to the second in
+/// `zone`, with `Z` for a zero offset and `+HH:MM` otherwise.
+pub fn format_millis_in(millis: i64, zone: &TimeZone) -> String {
+    let utc_seconds = millis.div_euclid(1000);
+    let offset = zone.offset_at(utc_seconds);
+    let seconds = utc_seconds + i64::from(offset);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let of_day = seconds.rem_euclid(86_400);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}{}",
+        of_day / 3600,
+        of_day % 3600 / 60,
+        of_day % 60,
+        format_offset(offset)
+    )
+}
+
+/// Formats a duration as `42s`, `3m05s`, or `1h02m03s`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..60 => format!("{seconds}s"),
+        60..3600 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!(
+            "{}h{:02}m{:02}s",
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        ),
+    }
+}
+
+fn format_offset(offset: i32) -> String {
+    if offset == 0 {
+        return "Z".to_string();
+    }
+    let sign = if offset < 0 { '-' } else { '+' };
+    let minutes = offset.unsigned_abs() / 60;
+    format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Parses `+08:00`, `-0530`, or `+8` into seconds east of UTC.
+fn parse_offset(text: &str) -> Option<i32> {
+    let sign = match text.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits = text[1..].replace(':', "");
+    if digits.is_empty() || digits.len() > 4 || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = if digits.len() > 2 {
+        digits.split_at(digits.len() - 2)
+    } else {
+        (digits.as_str(), "0")
+    };
+    let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+    (hours <= 14 && minutes < 60).then_some(sign * (hours * 3600 + minutes * 60))
+}
+
+fn split3(text: &str, separator: char) -> Option<(i64, i64, i64)> {
+    let mut parts = text.split(separator).map(|part| {
+        part.bytes()
+            .all(|byte| byte.is_ascii_digit())
+            .then(|| part.parse::<i64>().ok())
+            .flatten()
+    });
+    let parsed = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(parsed)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::{
+        TimeZone, format_duration, format_millis, format_millis_in, from_value, parse_millis,
+    };
+
+    #[test]
+    fn parses_and_formats_rfc3339_timestamps() {
+        let millis = parse_millis("2026-02-23T04:48:50.123Z").expect("parse");
+        assert_eq!(millis, 1_771_822_130_123);
+        assert_eq!(parse_millis("2026-02-23T12:48:50.123+08:00"), Some(millis));
+        assert_eq!(format_millis(millis), "2026-02-23T04:48:50Z");
+        assert_eq!(parse_millis("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_millis("not a time"), None);
+        assert_eq!(parse_millis("2026-13-01T00:00:00Z"), None);
+        assert_eq!(parse_millis("2026-02-30T00:00:00Z"), None);
+        assert_eq!(parse_millis("2026-04-31T00:00:00Z"), None);
+        assert_eq!(parse_millis("2026-02-29T00:00:00Z"), None);
+        assert!(parse_millis("2024-02-29T00:00:00Z").is_some());
+
+        assert_eq!(
+            from_value(&json!(1_771_822_130_123_i64)).as_deref(),
+            Some("2026-02-23T04:48:50Z")
+        );
+        assert_eq!(
+            from_value(&json!(1_771_822_130)).as_deref(),
+            Some("2026-02-23T04:48:50Z")
+        );
+
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(185)), "3m05s");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h02m03s");
+    }
+
+    #[test]
+    fn zones_apply_fixed_offsets_and_daylight_saving_rules() {
+        let at = |text: &str| parse_millis(text).expect("parse");
+        let winter = at("2026-02-23T04:48:50Z");
+
+        let shanghai = "+08:00".parse::<TimeZone>().expect("offset");
+        assert_eq!(shanghai, TimeZone::Fixed(8 * 3600));
+        assert_eq!(
+            format_millis_in(winter, &shanghai),
+            "2026-02-23T12:48:50+08:00"
+        );
+        assert_eq!(
+            format_millis_in(winter, &"-0530".parse().expect("offset")),
+            "2026-02-22T23:18:50-05:30"
+        );
+        assert_eq!("UTC".parse::<TimeZone>().expect("utc"), TimeZone::Utc);
+        assert_eq!(
+            format_millis_in(winter, &"Asia/Shanghai".parse().expect("zone")),
+            "2026-02-23T12:48:50+08:00"
+        );
+
+        let berlin =
+            TimeZone::Zone(jiff::tz::TimeZone::posix("CET-1CEST,M3.5.0,M10.5.0/3").expect("rule"));
+        assert_eq!(
+            format_millis_in(winter, &berlin),
+            "2026-02-23T05:48:50+01:00"
+        );
+        assert_eq!(
+            format_millis_in(at("2026-03-29T00:59:59Z"), &berlin),
+            "2026-03-29T01:59:59+01:00"
+        );
+        assert_eq!(
+            format_millis_in(at("2026-03-29T01:00:00Z"), &berlin),
+            "2026-03-29T03:00:00+02:00"
+        );
+        assert_eq!(
+            format_millis_in(at("2026-10-25T01:00:00Z"), &berlin),
+            "2026-10-25T02:00:00+01:00"
+        );
+
+        let sydney = TimeZone::Zone(
+            jiff::tz::TimeZone::posix("AEST-10AEDT,M10.1.0,M4.1.0/3").expect("rule"),
+        );
+        assert_eq!(
+            format_millis_in(winter, &sydney),
+            "2026-02-23T15:48:50+11:00"
+        );
+        assert_eq!(
+            format_millis_in(at("2026-07-01T00:00:00Z"), &sydney),
+            "2026-07-01T10:00:00+10:00"
+        );
+
+        let err = "Mars/Olympus_Mons"
+            .parse::<TimeZone>()
+            .expect_err("unknown zone");
+        assert!(format!("{err}").contains("unknown timezone"), "{err}");
+    }
+}
@@ -0,0 +1,828 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::error::{Result, XurlError};
+use crate::jsonl;
+use crate::model::{FileChange, FileChangeKind, McpServerUsage, ProviderKind, ToolCall};
+
+/// Collects the tool calls recorded in a thread in file order, paired with their outputs.
+///
+/// Outputs are matched to calls by the provider's call id; an output whose call is missing is
+/// kept as a call named after the tool the provider recorded, or `unknown`.
+pub fn extract_tool_calls(provider: ProviderKind, path: &Path, raw: &str) -> Result<Vec<ToolCall>> {
+    let mut calls = ToolCalls::default();
+    match provider {
+        ProviderKind::Amp => collect_amp_calls(&parse_json(path, raw)?, &mut calls),
+        ProviderKind::Gemini => collect_gemini_calls(&parse_json(path, raw)?, &mut calls),
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Pi | ProviderKind::Opencode => {
+            return extract_tool_calls_from_reader(provider, path, raw.as_bytes());
+        }
+    }
+    Ok(calls.calls)
+}
+
+/// Same as [`extract_tool_calls`], reading a line-based rollout one line at a time.
+///
+/// Amp and Gemini threads are single JSON documents and are read whole.
+pub fn extract_tool_calls_from_reader<R: BufRead>(
+    provider: ProviderKind,
+    path: &Path,
+    mut reader: R,
+) -> Result<Vec<ToolCall>> {
+    if matches!(provider, ProviderKind::Amp | ProviderKind::Gemini) {
+        let mut raw = String::new();
+        reader
+            .read_to_string(&mut raw)
+            .map_err(|source| XurlError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        return extract_tool_calls(provider, path, &raw);
+    }
+
+    let mut calls = ToolCalls::default();
+    jsonl::parse_thread_jsonl_reader(path, reader, |value| {
+        match provider {
+            ProviderKind::Codex => collect_codex_calls(&value, &mut calls),
+            ProviderKind::Claude => collect_claude_calls(&value, &mut calls),
+            ProviderKind::Pi => collect_pi_calls(&value, &mut calls),
+            ProviderKind::Opencode => collect_opencode_calls(&value, &mut calls),
+            ProviderKind::Amp | ProviderKind::Gemini => {}
+        }
+        Ok(())
+    })?;
+    Ok(calls.calls)
+}
+
+#[derive(Default)]
+struct ToolCalls {
+    calls: Vec<ToolCall>,
+    index_by_id: HashMap<String, usize>,
+}
+
+impl ToolCalls {
+    fn call(&mut self, id: Option<&str>, name: &str, input: Value) {
+        if let Some(id) = id {
+            self.index_by_id.insert(id.to_string(), self.calls.len());
+        }
+        self.calls.push(ToolCall {
+            id: id.map(ToString::to_string),
+            name: name.to_string(),
+            input,
+            output: None,
+        });
+    }
+
+    /// Renames the call with `id`; returns `false` when no call has that id.
+    fn rename(&mut self, id: Option<&str>, name: &str) -> bool {
+        let Some(idx) = id.and_then(|id| self.index_by_id.get(id)) else {
+            return false;
+        };
+        self.calls[*idx].name = name.to_string();
+        true
+    }
+
+    fn output(&mut self, id: Option<&str>, name: Option<&str>, output: String) {
+        if let Some(idx) = id.and_then(|id| self.index_by_id.get(id)) {
+            let call = &mut self.calls[*idx];
+            match &mut call.output {
+                Some(existing) => {
+                    existing.push('\n');
+                    existing.push_str(&output);
+                }
+                None => call.output = Some(output),
+            }
+            return;
+        }
+        self.calls.push(ToolCall {
+            id: id.map(ToString::to_string),
+            name: name.unwrap_or("unknown").to_string(),
+            input: Value::Null,
+            output: Some(output),
+        });
+    }
+
+    /// Answers the latest call named `name` that has no output yet, for providers that pair
+    /// results with calls by name alone.
+    fn output_by_name(&mut self, name: Option<&str>, output: String) {
+        let pending = self
+            .calls
+            .iter_mut()
+            .rev()
+            .find(|call| call.output.is_none() && name.is_none_or(|name| call.name == name));
+        match pending {
+            Some(call) => call.output = Some(output),
+            None => self.output(None, name, output),
+        }
+    }
+}
+
+fn parse_json(path: &Path, raw: &str) -> Result<Value> {
+    serde_json::from_str::<Value>(raw).map_err(|source| XurlError::InvalidJsonLine {
+        path: path.to_path_buf(),
+        line: 1,
+        source,
+    })
+}
+
+/// Name of a tool call made through an MCP server, like `mcp:playwright/click`.
+pub fn mcp_tool_name(server: &str, tool: &str) -> String {
+    format!("mcp:{server}/{tool}")
+}
+
+/// The MCP server a tool call named by [`mcp_tool_name`] went through.
+pub fn mcp_server(name: &str) -> Option<&str> {
+    let (server, _) = name.strip_prefix("mcp:")?.split_once('/')?;
+    Some(server)
+}
+
+/// Calls per MCP server, busiest first, then by server name.
+pub fn mcp_server_usage(calls: &[ToolCall]) -> Vec<McpServerUsage> {
+    let mut usage = Vec::<McpServerUsage>::new();
+    for server in calls.iter().filter_map(|call| mcp_server(&call.name)) {
+        match usage.iter_mut().find(|usage| usage.server == server) {
+            Some(usage) => usage.calls += 1,
+            None => usage.push(McpServerUsage {
+                server: server.to_string(),
+                calls: 1,
+            }),
+        }
+    }
+    usage.sort_by(|a, b| b.calls.cmp(&a.calls).then_with(|| a.server.cmp(&b.server)));
+    usage
+}
+
+/// Codex names MCP tools `mcp__<server>__<tool>` in function calls, and records the server
+/// and tool of each call again in `mcp_tool_call_begin` and `mcp_tool_call_end` events.
+fn collect_codex_calls(value: &Value, calls: &mut ToolCalls) {
+    let Some(payload) = value.get("payload") else {
+        return;
+    };
+    let call_id = payload.get("call_id").and_then(Value::as_str);
+    match value.get("type").and_then(Value::as_str) {
+        Some("response_item") => {}
+        Some("event_msg") => {
+            let event = payload.get("type").and_then(Value::as_str);
+            if !matches!(event, Some("mcp_tool_call_begin" | "mcp_tool_call_end")) {
+                return;
+            }
+            let Some(invocation) = payload.get("invocation") else {
+                return;
+            };
+            let field = |name: &str| invocation.get(name).and_then(Value::as_str);
+            let (Some(server), Some(tool)) = (field("server"), field("tool")) else {
+                return;
+            };
+            let name = mcp_tool_name(server, tool);
+            if !calls.rename(call_id, &name) && event == Some("mcp_tool_call_begin") {
+                let input = invocation.get("arguments").cloned().unwrap_or(Value::Null);
+                calls.call(call_id, &name, input);
+            }
+            return;
+        }
+        _ => return,
+    }
+    match payload.get("type").and_then(Value::as_str) {
+        Some("function_call") => {
+            // Codex stores function arguments as a JSON-encoded string.
+            let input = match payload.get("arguments") {
+                Some(Value::String(arguments)) => serde_json::from_str(arguments)
+                    .unwrap_or_else(|_| Value::String(arguments.clone())),
+                Some(arguments) => arguments.clone(),
+                None => Value::Null,
+            };
+            let name = payload
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown");
+            match name
+                .strip_prefix("mcp__")
+                .and_then(|name| name.split_once("__"))
+            {
+                Some((server, tool)) => calls.call(call_id, &mcp_tool_name(server, tool), input),
+                None => calls.call(call_id, name, input),
+            }
+        }
+        Some("custom_tool_call") => {
+            let name = payload
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown");
+            let input = payload.get("input").cloned().unwrap_or(Value::Null);
+            calls.call(call_id, name, input);
+        }
+        Some("local_shell_call") => {
+            let input = payload.get("action").cloned().unwrap_or(Value::Null);
+            calls.call(call_id, "shell", input);
+        }
+        Some("function_call_output" | "custom_tool_call_output" | "local_shell_call_output") => {
+            let output = match payload.get("output") {
+                Some(Value::Object(output)) => output
+                    .get("content")
+                    .or_else(|| output.get("output"))
+                    .map(output_text)
+                    .unwrap_or_default(),
+                output => output.map(output_text).unwrap_or_default(),
+            };
+            calls.output(call_id, None, output);
+        }
+        _ => {}
+    }
+}
+
+fn collect_claude_calls(value: &Value, calls: &mut ToolCalls) {
+    let record_type = value.get("type").and_then(Value::as_str);
+    if record_type != Some("user") && record_type != Some("assistant") {
+        return;
+    }
+    let items = value
+        .get("message")
+        .and_then(|message| message.get("content"))
+        .and_then(Value::as_array);
+    for item in items.into_iter().flatten() {
+        match item.get("type").and_then(Value::as_str) {
+            Some("tool_use") => {
+                let name = item
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or("unknown");
+                let input = item.get("input").cloned().unwrap_or(Value::Null);
+                calls.call(item.get("id").and_then(Value::as_str), name, input);
+            }
+            Some("tool_result") => {
+                let output = item.get("content").map(output_text).unwrap_or_default();
+                calls.output(
+                    item.get("tool_use_id").and_then(Value::as_str),
+                    None,
+                    output,
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_pi_calls(value: &Value, calls: &mut ToolCalls) {
+    if value.get("type").and_then(Value::as_str) != Some("message") {
+        return;
+    }
+    let Some(message) = value.get("message") else {
+        return;
+    };
+    if message.get("role").and_then(Value::as_str) == Some("toolResult") {
+        let output = message.get("content").map(output_text).unwrap_or_default();
+        calls.output(
+            message.get("toolCallId").and_then(Value::as_str),
+            message.get("toolName").and_then(Value::as_str),
+            output,
+        );
+        return;
+    }
+    let items = message.get("content").and_then(Value::as_array);
+    for item in items.into_iter().flatten() {
+        if item.get("type").and_then(Value::as_str) == Some("toolCall") {
+            let name = item
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown");
+            let input = item.get("arguments").cloned().unwrap_or(Value::Null);
+            calls.call(item.get("id").and_then(Value::as_str), name, input);
+        }
+    }
+}
+
+fn collect_opencode_calls(value: &Value, calls: &mut ToolCalls) {
+    if value.get("type").and_then(Value::as_str) != Some("message") {
+        return;
+    }
+    let parts = value.get("parts").and_then(Value::as_array);
+    for part in parts.into_iter().flatten() {
+        if part.get("type").and_then(Value::as_str) != Some("tool") {
+            continue;
+        }
+        let id = part.get("callID").and_then(Value::as_str);
+        let name = part
+            .get("tool")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown");
+        let state = part.get("state");
+        let input = state
+            .and_then(|state| state.get("input"))
+            .cloned()
+            .unwrap_or(Value::Null);
+        calls.call(id, name, input);
+        if let Some(output) = state.and_then(|state| state.get("output")) {
+            calls.output(id, Some(name), output_text(output));
+        }
+    }
+}
+
+fn collect_amp_calls(value: &Value, calls: &mut ToolCalls) {
+    let messages = value.get("messages").and_then(Value::as_array);
+    for message in messages.into_iter().flatten() {
+        let items = message.get("content").and_then(Value::as_array);
+        for item in items.into_iter().flatten() {
+            match item.get("type").and_then(Value::as_str) {
+                Some("tool_use") => {
+                    let name = item
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .unwrap_or("unknown");
+                    let input = item.get("input").cloned().unwrap_or(Value::Null);
+                    calls.call(item.get("id").and_then(Value::as_str), name, input);
+                }
+                Some("tool_result") => {
+                    let output = item
+                        .get("run")
+                        .and_then(|run| run.get("result"))
+                        .map(output_text)
+                        .unwrap_or_default();
+                    calls.output(item.get("toolUseID").and_then(Value::as_str), None, output);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Gemini chats record a turn's calls in the `toolCalls` array of a `gemini` message, each
+/// with its `functionResponse` results. Calls also come as `functionCall` and
+/// `functionResponse` content parts, the Gemini API format, and as standalone `toolCall` and
+/// `functionResponse` messages; responses without an id answer the latest unanswered call of
+/// the same name.
+fn collect_gemini_calls(value: &Value, calls: &mut ToolCalls) {
+    let messages = value.get("messages").and_then(Value::as_array);
+    for message in messages.into_iter().flatten() {
+        match message.get("type").and_then(Value::as_str) {
+            Some("toolCall") => {
+                gemini_function_call(message.get("toolCall").unwrap_or(message), calls);
+            }
+            Some("functionResponse") => {
+                gemini_function_response(message.get("functionResponse").unwrap_or(message), calls)
+            }
+            _ => {}
+        }
+        let parts = message
+            .get("content")
+            .or_else(|| message.get("parts"))
+            .and_then(Value::as_array);
+        for part in parts.into_iter().flatten() {
+            if let Some(call) = part.get("functionCall") {
+                gemini_function_call(call, calls);
+            } else if let Some(response) = part.get("functionResponse") {
+                gemini_function_response(response, calls);
+            }
+        }
+
+        let tool_calls = message.get("toolCalls").and_then(Value::as_array);
+        for tool_call in tool_calls.into_iter().flatten() {
+            let id = tool_call.get("id").and_then(Value::as_str);
+            let name = tool_call
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown");
+            let input = tool_call.get("args").cloned().unwrap_or(Value::Null);
+            calls.call(id, name, input);
+
+            let responses = tool_call
+                .get("result")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|result| result.pointer("/functionResponse/response"))
+                .map(gemini_response_text)
+                .collect::<Vec<_>>();
+            if !responses.is_empty() {
+                calls.output(id, Some(name), responses.join("\n"));
+            } else if let Some(display) = tool_call.get("resultDisplay").and_then(Value::as_str) {
+                calls.output(id, Some(name), display.to_string());
+            }
+        }
+    }
+}
+
+fn gemini_function_call(call: &Value, calls: &mut ToolCalls) {
+    let name = call
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown");
+    let input = call.get("args").cloned().unwrap_or(Value::Null);
+    calls.call(call.get("id").and_then(Value::as_str), name, input);
+}
+
+fn gemini_function_response(response: &Value, calls: &mut ToolCalls) {
+    let name = response.get("name").and_then(Value::as_str);
+    let output = response
+        .get("response")
+        .map(gemini_response_text)
+        .unwrap_or_default();
+    match response.get("id").and_then(Value::as_str) {
+        Some(id) => calls.output(Some(id), name, output),
+        None => calls.output_by_name(name, output),
+    }
+}
+
+/// A `functionResponse` payload is `{"output": ...}` for Gemini CLI tools and free-form for
+/// others.
+fn gemini_response_text(response: &Value) -> String {
+    output_text(response.get("output").unwrap_or(response))
+}
+
+const PATH_INPUT_KEYS: &[&str] = &[
+    "file_path",
+    "filePath",
+    "path",
+    "notebook_path",
+    "absolute_path",
+    "dir_path",
+];
+
+/// Returns the file paths a tool call's input names, in order and without duplicates.
+///
+/// Paths come from well-known argument keys and from `*** Add/Update/Delete File:` headers
+/// of `apply_patch` payloads anywhere in the input.
+pub(crate) fn input_paths(call: &ToolCall) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut push = |path: &str| {
+        let path = path.trim();
+        if !path.is_empty() && !paths.iter().any(|existing| existing == path) {
+            paths.push(path.to_string());
+        }
+    };
+    if let Some(input) = call.input.as_object() {
+        for key in PATH_INPUT_KEYS {
+            if let Some(path) = input.get(*key).and_then(Value::as_str) {
+                push(path);
+            }
+        }
+    }
+    for_each_string(&call.input, &mut |text| {
+        for (_, path) in patch_file_headers(text) {
+            push(path);
+        }
+    });
+    paths
+}
+
+/// Parses `apply_patch` file headers as `(action, path)` pairs, where action is `Add`,
+/// `Update`, `Delete`, or `Move` for the target of a rename.
+pub(crate) fn patch_file_headers(text: &str) -> Vec<(&str, &str)> {
+    text.lines()
+        .filter_map(|line| {
+            let rest = line.trim_end().strip_prefix("*** ")?;
+            if let Some(path) = rest.strip_prefix("Move to: ") {
+                return Some(("Move", path));
+            }
+            let (action, path) = rest.split_once(" File: ")?;
+            matches!(action, "Add" | "Update" | "Delete").then_some((action, path))
+        })
+        .collect()
+}
+
+pub(crate) fn for_each_string(value: &Value, visit: &mut impl FnMut(&str)) {
+    match value {
+        Value::String(text) => visit(text),
+        Value::Array(items) => items.iter().for_each(|item| for_each_string(item, visit)),
+        Value::Object(map) => map.values().for_each(|item| for_each_string(item, visit)),
+        _ => {}
+    }
+}
+
+const WRITE_TOOLS: &[&str] = &["write", "write_file", "create_file"];
+const EDIT_TOOLS: &[&str] = &[
+    "edit",
+    "multiedit",
+    "edit_file",
+    "replace",
+    "str_replace",
+    "notebookedit",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FileEdit {
+    Create,
+    Modify,
+    Delete,
+    /// A whole-file write whose output does not say whether the file existed.
+    Write,
+}
+
+/// Summarizes the files changed by edit, write, and patch tool calls, in first-touched order.
+///
+/// A file is `deleted` when its last change deletes it, `created` when its first change
+/// creates it, and `modified` otherwise. Writes that do not report whether the file existed
+/// count as creating files the thread had not touched before.
+pub fn files_changed(calls: &[ToolCall]) -> Vec<FileChange> {
+    let mut changes: Vec<(FileChange, FileEdit)> = Vec::new();
+    for call in calls {
+        for (path, edit) in call_file_edits(call) {
+            match changes.iter_mut().find(|(change, _)| change.path == path) {
+                Some((change, first)) => {
+                    change.edits += 1;
+                    change.kind = match edit {
+                        FileEdit::Delete => FileChangeKind::Deleted,
+                        _ if *first == FileEdit::Create => FileChangeKind::Created,
+                        _ => FileChangeKind::Modified,
+                    };
+                }
+                None => {
+                    let kind = match edit {
+                        FileEdit::Create | FileEdit::Write => FileChangeKind::Created,
+                        FileEdit::Modify => FileChangeKind::Modified,
+                        FileEdit::Delete => FileChangeKind::Deleted,
+                    };
+                    let first = if edit == FileEdit::Write {
+                        FileEdit::Create
+                    } else {
+                        edit
+                    };
+                    changes.push((
+                        FileChange {
+                            path,
+                            kind,
+                            edits: 1,
+                        },
+                        first,
+                    ));
+                }
+            }
+        }
+    }
+    changes.into_iter().map(|(change, _)| change).collect()
+}
+
+fn call_file_edits(call: &ToolCall) -> Vec<(String, FileEdit)> {
+    let mut edits = Vec::new();
+    for_each_string(&call.input, &mut |text| {
+        let mut renamed_from = None;
+        for (action, path) in patch_file_headers(text) {
+            let edit = match action {
+                "Add" => FileEdit::Create,
+                "Delete" => FileEdit::Delete,
+                "Move" => {
+                    // `*** Move to:` follows the `*** Update File:` header of the old path.
+                    if let Some(from) = renamed_from.take() {
+                        edits.retain(|(edited, _): &(String, FileEdit)| *edited != from);
+                        edits.push((from, FileEdit::Delete));
+                    }
+                    FileEdit::Create
+                }
+                _ => FileEdit::Modify,
+            };
+            renamed_from = (action == "Update").then(|| path.trim().to_string());
+            edits.push((path.trim().to_string(), edit));
+        }
+    });
+    if !edits.is_empty() {
+        return edits;
+    }
+
+    let name = call.name.to_ascii_lowercase();
+    let edit = if WRITE_TOOLS.contains(&name.as_str()) {
+        let output = call
+            .output
+            .as_deref()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if output.contains("created") || output.contains("new file") {
+            FileEdit::Create
+        } else if output.contains("updated") || output.contains("overwrote") {
+            FileEdit::Modify
+        } else {
+            FileEdit::Write
+        }
+    } else if EDIT_TOOLS.contains(&name.as_str()) {
+        FileEdit::Modify
+    } else {
+        return edits;
+    };
+    let path = call.input.as_object().and_then(|input| {
+        PATH_INPUT_KEYS
+            .iter()
+            .filter(|key| **key != "dir_path")
+            .find_map(|key| input.get(*key).and_then(Value::as_str))
+    });
+    if let Some(path) = path.filter(|path| !path.trim().is_empty()) {
+        edits.push((path.trim().to_string(), edit));
+    }
+    edits
+}
+
+/// Flattens a tool output into text: strings as-is, text blocks joined, anything else as JSON.
+fn output_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item.get("text").and_then(Value::as_str) {
+                Some(text) => text.to_string(),
+                None => output_text(item),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::{Value, json};
+
+    use super::{extract_tool_calls, files_changed, mcp_server_usage};
+    use crate::model::{FileChangeKind, ProviderKind, ToolCall};
+
+    #[test]
+    fn codex_calls_are_paired_with_outputs() {
+        let raw = [
+            json!({"type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"git\",\"commit\",\"-m\",\"fix\"]}","call_id":"call_1"}}),
+            json!({"type":"response_item","payload":{"type":"custom_tool_call","name":"apply_patch","input":"*** Begin Patch","call_id":"call_2"}}),
+            json!({"type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"[main 1a2b3c4] fix"}}),
+            json!({"type":"response_item","payload":{"type":"custom_tool_call_output","call_id":"call_2","output":{"output":"Success"}}}),
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+        let calls = extract_tool_calls(ProviderKind::Codex, Path::new("rollout.jsonl"), &raw)
+            .expect("extract");
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].name, "shell");
+        assert_eq!(calls[0].input["command"][1], "commit");
+        assert_eq!(calls[0].output.as_deref(), Some("[main 1a2b3c4] fix"));
+        assert_eq!(calls[1].name, "apply_patch");
+        assert_eq!(calls[1].output.as_deref(), Some("Success"));
+    }
+
+    #[test]
+    fn codex_mcp_calls_are_attributed_to_their_server() {
+        let raw = [
+            json!({"type":"response_item","payload":{"type":"function_call","name":"mcp__playwright__click","arguments":"{\"ref\":\"e1\"}","call_id":"call_1"}}),
+            json!({"type":"event_msg","payload":{"type":"mcp_tool_call_begin","call_id":"call_1","invocation":{"server":"playwright","tool":"click","arguments":{"ref":"e1"}}}}),
+            json!({"type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"clicked"}}),
+            json!({"type":"response_item","payload":{"type":"function_call","name":"search_docs","arguments":"{}","call_id":"call_2"}}),
+            json!({"type":"event_msg","payload":{"type":"mcp_tool_call_end","call_id":"call_2","invocation":{"server":"context7","tool":"search_docs"}}}),
+            json!({"type":"event_msg","payload":{"type":"mcp_tool_call_begin","call_id":"call_3","invocation":{"server":"playwright","tool":"navigate","arguments":{"url":"https://example.com"}}}}),
+            json!({"type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{}","call_id":"call_4"}}),
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+        let calls = extract_tool_calls(ProviderKind::Codex, Path::new("rollout.jsonl"), &raw)
+            .expect("extract");
+        let names = calls
+            .iter()
+            .map(|call| call.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "mcp:playwright/click",
+                "mcp:context7/search_docs",
+                "mcp:playwright/navigate",
+                "shell"
+            ]
+        );
+        assert_eq!(calls[0].output.as_deref(), Some("clicked"));
+        assert_eq!(calls[2].input["url"], "https://example.com");
+
+        let usage = mcp_server_usage(&calls)
+            .into_iter()
+            .map(|usage| (usage.server, usage.calls))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            usage,
+            vec![("playwright".to_string(), 2), ("context7".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn claude_and_pi_results_match_their_calls() {
+        let claude = [
+            json!({"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"git log -1"}}]}}),
+            json!({"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","content":[{"type":"text","text":"commit 1a2b3c4"}]}]}}),
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+        let calls = extract_tool_calls(ProviderKind::Claude, Path::new("s.jsonl"), &claude)
+            .expect("extract");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].output.as_deref(), Some("commit 1a2b3c4"));
+
+        let pi = [
+            json!({"type":"message","id":"a","message":{"role":"assistant","content":[{"type":"toolCall","id":"tc_1","name":"bash","arguments":{"command":"ls"}}]}}),
+            json!({"type":"message","id":"b","message":{"role":"toolResult","toolCallId":"tc_1","toolName":"bash","content":[{"type":"text","text":"README.md"}]}}),
+            json!({"type":"message","id":"c","message":{"role":"toolResult","toolCallId":"tc_9","toolName":"read","content":"orphan"}}),
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+        let calls =
+            extract_tool_calls(ProviderKind::Pi, Path::new("s.jsonl"), &pi).expect("extract");
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].output.as_deref(), Some("README.md"));
+        assert_eq!(calls[1].name, "read");
+        assert!(calls[1].input.is_null());
+    }
+
+    #[test]
+    fn gemini_calls_come_from_tool_calls_parts_and_messages() {
+        let raw = json!({
+            "sessionId": "s1",
+            "messages": [
+                {"type": "gemini", "content": "Listing", "toolCalls": [{
+                    "id": "list-1",
+                    "name": "list_directory",
+                    "args": {"dir_path": "src"},
+                    "result": [{"functionResponse": {"id": "list-1", "name": "list_directory", "response": {"output": "main.rs"}}}]
+                }]},
+                {"type": "gemini", "content": [
+                    {"text": "Reading"},
+                    {"functionCall": {"name": "read_file", "args": {"absolute_path": "/w/src/main.rs"}}}
+                ]},
+                {"type": "user", "content": [
+                    {"functionResponse": {"name": "read_file", "response": {"output": "fn main() {}"}}}
+                ]},
+                {"type": "toolCall", "id": "edit-1", "name": "replace", "args": {"file_path": "/w/src/main.rs"}},
+                {"type": "functionResponse", "id": "edit-1", "name": "replace", "response": {"error": "no match"}}
+            ]
+        })
+        .to_string();
+
+        let calls = extract_tool_calls(ProviderKind::Gemini, Path::new("chat.json"), &raw)
+            .expect("extract");
+        let summary = calls
+            .iter()
+            .map(|call| (call.name.as_str(), call.output.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("list_directory", Some("main.rs")),
+                ("read_file", Some("fn main() {}")),
+                ("replace", Some(r#"{"error":"no match"}"#)),
+            ]
+        );
+        assert_eq!(calls[1].input["absolute_path"], "/w/src/main.rs");
+        assert_eq!(calls[2].id.as_deref(), Some("edit-1"));
+    }
+
+    #[test]
+    fn files_changed_summarizes_patches_and_edit_tools() {
+        let call = |name: &str, input: Value, output: Option<&str>| ToolCall {
+            id: None,
+            name: name.to_string(),
+            input,
+            output: output.map(ToString::to_string),
+        };
+        let calls = vec![
+            call(
+                "apply_patch",
+                json!(
+                    "*** Begin Patch\n*** Add File: src/new.rs\n+fn main() {}\n*** Update File: src/old.rs\n*** Move to: src/renamed.rs\n*** Delete File: src/gone.rs\n*** End Patch"
+                ),
+                Some("Success"),
+            ),
+            call("Edit", json!({"file_path": "src/new.rs"}), None),
+            call("Edit", json!({"file_path": "README.md"}), None),
+            call(
+                "Write",
+                json!({"file_path": "README.md"}),
+                Some("The file README.md has been updated."),
+            ),
+            call(
+                "write",
+                json!({"path": "notes.md"}),
+                Some("Successfully wrote 12 bytes"),
+            ),
+            call("Read", json!({"file_path": "Cargo.toml"}), None),
+        ];
+
+        let changes = files_changed(&calls)
+            .into_iter()
+            .map(|change| (change.path, change.kind, change.edits))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                ("src/new.rs".to_string(), FileChangeKind::Created, 2),
+                ("src/old.rs".to_string(), FileChangeKind::Deleted, 1),
+                ("src/renamed.rs".to_string(), FileChangeKind::Created, 1),
+                ("src/gone.rs".to_string(), FileChangeKind::Deleted, 1),
+                ("README.md".to_string(), FileChangeKind::Modified, 2),
+                ("notes.md".to_string(), FileChangeKind::Created, 1),
+            ]
+        );
+    }
+}
@@ -1,10 +1,13 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use once_cell::sync::Lazy;
 use regex::Regex;
 
 use crate::error::{Result, XurlError};
-use crate::model::{ProviderKind, ThreadQuery};
+use crate::model::{ProviderKind, QuerySort, ThreadQuery, ThreadStatus};
 
 static SESSION_ID_RE: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"(?i)^[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$")
@@ -16,6 +19,12 @@ static AMP_SESSION_ID_RE: Lazy<Regex> = Lazy::new(|| {
 });
 static OPENCODE_SESSION_ID_RE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"^ses_[0-9A-Za-z]+$").expect("valid regex"));
+static SESSION_ID_PREFIX_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)^[0-9a-f][0-9a-f-]{3,35}$").expect("valid regex"));
+static AMP_SESSION_ID_PREFIX_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)^t-[0-9a-f][0-9a-f-]{3,35}$").expect("valid regex"));
+static ALIAS_NAME_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[A-Za-z0-9][A-Za-z0-9_-]*$").expect("valid regex"));
 static PI_SHORT_ENTRY_ID_RE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"(?i)^[0-9a-f]{8}$").expect("valid regex"));
 
@@ -23,6 +32,66 @@ pub fn is_uuid_session_id(input: &str) -> bool {
     SESSION_ID_RE.is_match(input)
 }
 
+/// Whether `name` can be used as an alias: letters, digits, `-`, and `_`, not starting with a
+/// separator, and not a provider name.
+pub fn is_alias_name(name: &str) -> bool {
+    ALIAS_NAME_RE.is_match(name) && name != "alias" && parse_provider(name).is_err()
+}
+
+/// Rewrites `agents://alias/<name>`, `alias/<name>`, or a bare alias `<name>` to the URI
+/// `aliases` maps it to, appending any query string of `input`. Other input is returned
+/// unchanged, as is a bare name without an alias. [`AgentsUri::parse`] does not expand aliases,
+/// so callers expand user input with this first.
+pub fn expand_alias<'a>(
+    input: &'a str,
+    aliases: &BTreeMap<String, String>,
+) -> Result<Cow<'a, str>> {
+    let (target, raw_query) = split_target_and_query(input);
+    let (name, explicit) = match target
+        .strip_prefix("agents://alias/")
+        .or_else(|| target.strip_prefix("alias/"))
+    {
+        Some(name) => (name, true),
+        None if is_alias_name(target) => (target, false),
+        None => return Ok(Cow::Borrowed(input)),
+    };
+
+    let Some(uri) = aliases.get(name).cloned() else {
+        if explicit {
+            return Err(XurlError::InvalidUri(format!(
+                "{input} (unknown alias `{name}`)"
+            )));
+        }
+        return Ok(Cow::Borrowed(input));
+    };
+    Ok(Cow::Owned(
+        match raw_query.filter(|query| !query.is_empty()) {
+            Some(query) if uri.contains('?') => format!("{uri}&{query}"),
+            Some(query) => format!("{uri}?{query}"),
+            None => uri,
+        },
+    ))
+}
+
+/// Parses `@latest` / `@latest~N` session aliases (and the `latest` / `@last` shortcuts)
+/// into how many sessions back they point.
+pub fn parse_latest_alias(token: &str) -> Option<usize> {
+    if token == "latest" || token == "@last" {
+        return Some(0);
+    }
+
+    let rest = token.strip_prefix("@latest")?;
+    if rest.is_empty() {
+        return Some(0);
+    }
+
+    let offset = rest.strip_prefix('~')?;
+    if offset.is_empty() || !offset.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    offset.parse().ok()
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SkillsUri {
     Local {
@@ -136,6 +205,75 @@ fn validate_skills_segment(segment: &str, input: &str) -> Result<()> {
     Ok(())
 }
 
+/// A content-addressed thread copy taken by `xurl snapshot`: `snapshot://<sha256>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotUri {
+    /// The lowercase hex SHA-256 of the copied thread file.
+    pub hash: String,
+}
+
+impl SnapshotUri {
+    pub fn parse(input: &str) -> Result<Self> {
+        input.parse()
+    }
+
+    pub fn as_string(&self) -> String {
+        format!("snapshot://{}", self.hash)
+    }
+}
+
+impl FromStr for SnapshotUri {
+    type Err = XurlError;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let hash = input
+            .strip_prefix("snapshot://")
+            .filter(|hash| hash.len() == 64 && hash.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .ok_or_else(|| {
+                XurlError::InvalidUri(format!("{input} (expected snapshot://<sha256>)"))
+            })?;
+        Ok(Self {
+            hash: hash.to_ascii_lowercase(),
+        })
+    }
+}
+
+/// A thread file named by its path, like a rollout kept as a CI artifact:
+/// `file:///path/to/rollout.jsonl`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileUri {
+    pub path: PathBuf,
+}
+
+impl FileUri {
+    pub fn parse(input: &str) -> Result<Self> {
+        input.parse()
+    }
+
+    pub fn as_string(&self) -> String {
+        format!("file://{}", self.path.display())
+    }
+}
+
+impl FromStr for FileUri {
+    type Err = XurlError;
+
+    /// Takes absolute paths, with an optional `localhost` host; `%XX` escapes are decoded.
+    fn from_str(input: &str) -> Result<Self> {
+        let invalid =
+            || XurlError::InvalidUri(format!("{input} (expected file:///<absolute path>)"));
+        let target = input.strip_prefix("file://").ok_or_else(invalid)?;
+        let target = target.strip_prefix("localhost").unwrap_or(target);
+        if !target.starts_with('/') {
+            return Err(invalid());
+        }
+        let path = percent_decode(target).ok_or_else(invalid)?;
+        Ok(Self {
+            path: PathBuf::from(path),
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AgentsUri {
     pub provider: ProviderKind,
@@ -149,10 +287,27 @@ impl AgentsUri {
         input.parse()
     }
 
+    /// Like [`AgentsUri::parse`], but also accepts the leading part of a session id (at least
+    /// four characters, like `019c871c`). Expand it with `expand_session_prefix` before
+    /// resolving.
+    pub fn parse_prefix(input: &str) -> Result<Self> {
+        parse_agents_uri(input, true)
+    }
+
+    /// Whether the session id has its provider's full shape rather than being a prefix or an
+    /// alias. OpenCode ids have no fixed length, so any OpenCode id counts as full.
+    pub fn has_full_session_id(&self) -> bool {
+        is_session_id(self.provider, &self.session_id)
+    }
+
     pub fn is_collection(&self) -> bool {
         self.session_id.is_empty() && self.agent_id.is_none()
     }
 
+    pub fn latest_offset(&self) -> Option<usize> {
+        parse_latest_alias(&self.session_id)
+    }
+
     pub fn require_session_id(&self) -> Result<&str> {
         if self.session_id.is_empty() {
             return Err(XurlError::InvalidMode(
@@ -263,94 +418,98 @@ impl FromStr for AgentsUri {
     type Err = XurlError;
 
     fn from_str(input: &str) -> Result<Self> {
-        let (scheme, target_with_query) = input
-            .split_once("://")
-            .map_or((None, input), |(scheme, target)| (Some(scheme), target));
-        let (target, raw_query) = split_target_and_query(target_with_query);
+        parse_agents_uri(input, false)
+    }
+}
 
-        let query = parse_query(raw_query, input)?;
+fn parse_agents_uri(input: &str, allow_prefix: bool) -> Result<AgentsUri> {
+    let (scheme, target_with_query) = input
+        .split_once("://")
+        .map_or((None, input), |(scheme, target)| (Some(scheme), target));
+    let (target, raw_query) = split_target_and_query(target_with_query);
 
-        let (provider, raw_id, raw_agent_id, allows_collection) = match scheme {
-            Some("agents") => parse_agents_target(target, input)?,
-            Some(scheme) => parse_legacy_target(scheme, target, input)?,
-            None => parse_agents_target(target, input)?,
-        };
+    let query = parse_query(raw_query, input)?;
 
-        if raw_id.is_empty() {
-            if !(allows_collection && raw_agent_id.is_none()) {
-                return Err(XurlError::InvalidUri(input.to_string()));
-            }
+    let (provider, raw_id, raw_agent_id, allows_collection) = match scheme {
+        Some("agents") => parse_agents_target(target, input)?,
+        Some(scheme) => parse_legacy_target(scheme, target, input)?,
+        None => parse_agents_target(target, input)?,
+    };
 
-            return Ok(Self {
-                provider,
-                session_id: String::new(),
-                agent_id: None,
-                query,
-            });
+    if raw_id.is_empty() {
+        if !(allows_collection && raw_agent_id.is_none()) {
+            return Err(XurlError::InvalidUri(input.to_string()));
         }
 
-        match provider {
-            ProviderKind::Amp if !AMP_SESSION_ID_RE.is_match(raw_id) => {
-                return Err(XurlError::InvalidSessionId(raw_id.to_string()));
-            }
-            ProviderKind::Codex
-            | ProviderKind::Claude
-            | ProviderKind::Gemini
-            | ProviderKind::Pi
-                if !is_uuid_session_id(raw_id) =>
-            {
-                return Err(XurlError::InvalidSessionId(raw_id.to_string()));
-            }
-            ProviderKind::Opencode if !OPENCODE_SESSION_ID_RE.is_match(raw_id) => {
-                return Err(XurlError::InvalidSessionId(raw_id.to_string()));
-            }
-            _ => {}
-        }
+        return Ok(AgentsUri {
+            provider,
+            session_id: String::new(),
+            agent_id: None,
+            query,
+        });
+    }
 
-        if provider == ProviderKind::Amp
-            && let Some(agent_id) = raw_agent_id.as_deref()
-            && !AMP_SESSION_ID_RE.is_match(agent_id)
+    let is_latest_alias = parse_latest_alias(raw_id).is_some();
+    match provider {
+        _ if is_latest_alias => {}
+        _ if allow_prefix && is_session_id_prefix(provider, raw_id) => {}
+        ProviderKind::Amp if !AMP_SESSION_ID_RE.is_match(raw_id) => {
+            return Err(XurlError::InvalidSessionId(raw_id.to_string()));
+        }
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Gemini | ProviderKind::Pi
+            if !is_uuid_session_id(raw_id) =>
         {
-            return Err(XurlError::InvalidSessionId(agent_id.to_string()));
+            return Err(XurlError::InvalidSessionId(raw_id.to_string()));
         }
+        ProviderKind::Opencode if !OPENCODE_SESSION_ID_RE.is_match(raw_id) => {
+            return Err(XurlError::InvalidSessionId(raw_id.to_string()));
+        }
+        _ => {}
+    }
 
-        let session_id = match provider {
-            ProviderKind::Amp => format!("T-{}", raw_id[2..].to_ascii_lowercase()),
-            ProviderKind::Codex
-            | ProviderKind::Claude
-            | ProviderKind::Gemini
-            | ProviderKind::Pi => raw_id.to_ascii_lowercase(),
-            ProviderKind::Opencode => raw_id.to_string(),
-        };
+    if provider == ProviderKind::Amp
+        && let Some(agent_id) = raw_agent_id.as_deref()
+        && !AMP_SESSION_ID_RE.is_match(agent_id)
+    {
+        return Err(XurlError::InvalidSessionId(agent_id.to_string()));
+    }
 
-        let agent_id = raw_agent_id.map(|agent_id| {
-            if provider == ProviderKind::Amp && AMP_SESSION_ID_RE.is_match(&agent_id) {
-                format!("T-{}", agent_id[2..].to_ascii_lowercase())
-            } else if ((provider == ProviderKind::Codex || provider == ProviderKind::Gemini)
-                && SESSION_ID_RE.is_match(&agent_id))
-                || (provider == ProviderKind::Pi
-                    && (is_uuid_session_id(&agent_id) || PI_SHORT_ENTRY_ID_RE.is_match(&agent_id)))
-            {
-                agent_id.to_ascii_lowercase()
-            } else {
-                agent_id
-            }
-        });
+    let session_id = match provider {
+        _ if is_latest_alias => raw_id.to_string(),
+        ProviderKind::Amp => format!("T-{}", raw_id[2..].to_ascii_lowercase()),
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Gemini | ProviderKind::Pi => {
+            raw_id.to_ascii_lowercase()
+        }
+        ProviderKind::Opencode => raw_id.to_string(),
+    };
 
-        if provider == ProviderKind::Opencode
-            && let Some(child_id) = agent_id.as_deref()
-            && !OPENCODE_SESSION_ID_RE.is_match(child_id)
+    let agent_id = raw_agent_id.map(|agent_id| {
+        if provider == ProviderKind::Amp && AMP_SESSION_ID_RE.is_match(&agent_id) {
+            format!("T-{}", agent_id[2..].to_ascii_lowercase())
+        } else if ((provider == ProviderKind::Codex || provider == ProviderKind::Gemini)
+            && SESSION_ID_RE.is_match(&agent_id))
+            || (provider == ProviderKind::Pi
+                && (is_uuid_session_id(&agent_id) || PI_SHORT_ENTRY_ID_RE.is_match(&agent_id)))
         {
-            return Err(XurlError::InvalidSessionId(child_id.to_string()));
+            agent_id.to_ascii_lowercase()
+        } else {
+            agent_id
         }
+    });
 
-        Ok(Self {
-            provider,
-            session_id,
-            agent_id,
-            query,
-        })
+    if provider == ProviderKind::Opencode
+        && let Some(child_id) = agent_id.as_deref()
+        && !OPENCODE_SESSION_ID_RE.is_match(child_id)
+    {
+        return Err(XurlError::InvalidSessionId(child_id.to_string()));
     }
+
+    Ok(AgentsUri {
+        provider,
+        session_id,
+        agent_id,
+        query,
+    })
 }
 
 fn split_target_and_query(input: &str) -> (&str, Option<&str>) {
@@ -432,7 +591,7 @@ fn hex_value(ch: u8) -> Option<u8> {
     }
 }
 
-fn parse_provider(scheme: &str) -> Result<ProviderKind> {
+pub fn parse_provider(scheme: &str) -> Result<ProviderKind> {
     match scheme {
         "amp" => Ok(ProviderKind::Amp),
         "codex" => Ok(ProviderKind::Codex),
@@ -445,6 +604,20 @@ fn parse_provider(scheme: &str) -> Result<ProviderKind> {
 }
 
 fn looks_like_session_id(provider: ProviderKind, token: &str) -> bool {
+    parse_latest_alias(token).is_some() || is_session_id(provider, token)
+}
+
+fn is_session_id_prefix(provider: ProviderKind, token: &str) -> bool {
+    match provider {
+        ProviderKind::Amp => AMP_SESSION_ID_PREFIX_RE.is_match(token),
+        ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Gemini | ProviderKind::Pi => {
+            SESSION_ID_PREFIX_RE.is_match(token)
+        }
+        ProviderKind::Opencode => OPENCODE_SESSION_ID_RE.is_match(token),
+    }
+}
+
+fn is_session_id(provider: ProviderKind, token: &str) -> bool {
     match provider {
         ProviderKind::Amp => AMP_SESSION_ID_RE.is_match(token),
         ProviderKind::Codex | ProviderKind::Claude | ProviderKind::Gemini | ProviderKind::Pi => {
@@ -478,12 +651,29 @@ pub fn parse_role_uri(input: &str) -> Result<Option<RoleUri>> {
     }))
 }
 
-fn parse_thread_query_pairs(
-    input: &str,
-    query_raw: &str,
-) -> Result<(Option<String>, usize, Vec<String>)> {
+struct ThreadQueryParams {
+    q: Option<String>,
+    limit: usize,
+    sort: QuerySort,
+    offset: Option<usize>,
+    cursor: Option<String>,
+    project: Option<String>,
+    status: Option<ThreadStatus>,
+    workdir: Option<String>,
+    repo: Option<String>,
+    ignored_params: Vec<String>,
+}
+
+fn parse_thread_query_pairs(input: &str, query_raw: &str) -> Result<ThreadQueryParams> {
     let mut q = None::<String>;
     let mut limit = None::<usize>;
+    let mut sort = QuerySort::default();
+    let mut offset = None::<usize>;
+    let mut cursor = None::<String>;
+    let mut project = None::<String>;
+    let mut status = None::<ThreadStatus>;
+    let mut workdir = None::<String>;
+    let mut repo = None::<String>;
     let mut ignored_params = Vec::<String>::new();
 
     for pair in query_raw.split('&').filter(|pair| !pair.is_empty()) {
@@ -503,6 +693,68 @@ fn parse_thread_query_pairs(
                     XurlError::InvalidUri(format!("{input} (invalid limit={value})"))
                 })?);
             }
+            "offset" => {
+                offset = Some(value.parse::<usize>().map_err(|_| {
+                    XurlError::InvalidUri(format!("{input} (invalid offset={value})"))
+                })?);
+            }
+            "cursor" => {
+                let trimmed = value.trim();
+                if !trimmed.is_empty() {
+                    cursor = Some(trimmed.to_string());
+                }
+            }
+            "sort" => {
+                sort = match value.as_str() {
+                    "recency" => QuerySort::Recency,
+                    "relevance" => QuerySort::Relevance,
+                    _ => {
+                        return Err(XurlError::InvalidUri(format!(
+                            "{input} (invalid sort={value}; expected relevance or recency)"
+                        )));
+                    }
+                };
+            }
+            "project" => {
+                let trimmed = value.trim();
+                if trimmed.is_empty() {
+                    return Err(XurlError::InvalidUri(format!(
+                        "{input} (project requires a directory path)"
+                    )));
+                }
+                project = Some(trimmed.to_string());
+            }
+            "workdir" => {
+                let trimmed = value.trim();
+                if trimmed.is_empty() {
+                    return Err(XurlError::InvalidUri(format!(
+                        "{input} (workdir requires a directory path)"
+                    )));
+                }
+                workdir = Some(trimmed.to_string());
+            }
+            "repo" => {
+                let trimmed = value.trim().trim_end_matches('/');
+                if trimmed.is_empty() {
+                    return Err(XurlError::InvalidUri(format!(
+                        "{input} (repo requires owner/name or a remote URL)"
+                    )));
+                }
+                repo = Some(trimmed.to_string());
+            }
+            "status" => {
+                status = Some(match value.as_str() {
+                    "running" => ThreadStatus::Running,
+                    "completed" => ThreadStatus::Completed,
+                    "failed" => ThreadStatus::Failed,
+                    "unknown" => ThreadStatus::Unknown,
+                    _ => {
+                        return Err(XurlError::InvalidUri(format!(
+                            "{input} (invalid status={value}; expected running, completed, failed, or unknown)"
+                        )));
+                    }
+                });
+            }
             _ => {
                 if !ignored_params.iter().any(|existing| existing == &key) {
                     ignored_params.push(key);
@@ -511,7 +763,24 @@ fn parse_thread_query_pairs(
         }
     }
 
-    Ok((q, limit.unwrap_or(10), ignored_params))
+    if offset.is_some() && cursor.is_some() {
+        return Err(XurlError::InvalidUri(format!(
+            "{input} (use either offset or cursor, not both)"
+        )));
+    }
+
+    Ok(ThreadQueryParams {
+        q,
+        limit: limit.unwrap_or(10),
+        sort,
+        offset,
+        cursor,
+        project,
+        status,
+        workdir,
+        repo,
+        ignored_params,
+    })
 }
 
 pub fn parse_collection_query_uri(input: &str) -> Result<Option<ThreadQuery>> {
@@ -529,15 +798,22 @@ pub fn parse_collection_query_uri(input: &str) -> Result<Option<ThreadQuery>> {
     }
 
     let provider = parse_provider(provider_part)?;
-    let (q, limit, ignored_params) = parse_thread_query_pairs(input, query_raw)?;
+    let params = parse_thread_query_pairs(input, query_raw)?;
 
     Ok(Some(ThreadQuery {
         uri: input.to_string(),
         provider,
         role: None,
-        q,
-        limit,
-        ignored_params,
+        q: params.q,
+        limit: params.limit,
+        sort: params.sort,
+        offset: params.offset.unwrap_or(0),
+        cursor: params.cursor,
+        project: params.project,
+        status: params.status,
+        workdir: params.workdir,
+        repo: params.repo,
+        ignored_params: params.ignored_params,
     }))
 }
 
@@ -554,15 +830,22 @@ pub fn parse_role_query_uri(input: &str) -> Result<Option<ThreadQuery>> {
         input
     };
     let (_, query_raw) = target.split_once('?').map_or((target, ""), |parts| parts);
-    let (q, limit, ignored_params) = parse_thread_query_pairs(input, query_raw)?;
+    let params = parse_thread_query_pairs(input, query_raw)?;
 
     Ok(Some(ThreadQuery {
         uri: input.to_string(),
         provider: role_uri.provider,
         role: Some(role_uri.role),
-        q,
-        limit,
-        ignored_params,
+        q: params.q,
+        limit: params.limit,
+        sort: params.sort,
+        offset: params.offset.unwrap_or(0),
+        cursor: params.cursor,
+        project: params.project,
+        status: params.status,
+        workdir: params.workdir,
+        repo: params.repo,
+        ignored_params: params.ignored_params,
     }))
 }
 
@@ -620,10 +903,32 @@ fn hex_nibble(value: u8) -> Option<u8> {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
     use super::{
-        AgentsUri, SkillsUri, parse_collection_query_uri, parse_role_query_uri, parse_role_uri,
+        AgentsUri, FileUri, SkillsUri, SnapshotUri, expand_alias, is_alias_name,
+        parse_collection_query_uri, parse_role_query_uri, parse_role_uri,
     };
-    use crate::model::ProviderKind;
+    use crate::model::{ProviderKind, QuerySort};
+
+    #[test]
+    fn parse_file_and_snapshot_uris() {
+        let uri = FileUri::parse("file:///tmp/ci%20artifacts/rollout.jsonl").expect("parse");
+        assert_eq!(uri.path, Path::new("/tmp/ci artifacts/rollout.jsonl"));
+        assert_eq!(
+            FileUri::parse("file://localhost/tmp/rollout.jsonl")
+                .expect("parse")
+                .path,
+            Path::new("/tmp/rollout.jsonl")
+        );
+        assert!(FileUri::parse("file://rollout.jsonl").is_err());
+
+        let hash = "AB".repeat(32);
+        let uri = SnapshotUri::parse(&format!("snapshot://{hash}")).expect("parse");
+        assert_eq!(uri.as_string(), format!("snapshot://{}", "ab".repeat(32)));
+        assert!(SnapshotUri::parse("snapshot://abc").is_err());
+    }
 
     #[test]
     fn parse_local_skills_uri() {
@@ -919,6 +1224,47 @@ mod tests {
         assert!(format!("{err}").contains("invalid session id"));
     }
 
+    #[test]
+    fn parse_latest_alias_uri() {
+        let uri = AgentsUri::parse("agents://codex/@latest").expect("parse should succeed");
+        assert_eq!(uri.provider, ProviderKind::Codex);
+        assert_eq!(uri.session_id, "@latest");
+        assert_eq!(uri.latest_offset(), Some(0));
+
+        let uri = AgentsUri::parse("opencode/@latest~2").expect("parse should succeed");
+        assert_eq!(uri.provider, ProviderKind::Opencode);
+        assert_eq!(uri.latest_offset(), Some(2));
+    }
+
+    #[test]
+    fn parse_latest_shortcuts() {
+        let uri = AgentsUri::parse("agents://codex/latest").expect("parse should succeed");
+        assert_eq!(uri.session_id, "latest");
+        assert_eq!(uri.latest_offset(), Some(0));
+
+        let uri = AgentsUri::parse("claude/@last?cwd").expect("parse should succeed");
+        assert_eq!(uri.latest_offset(), Some(0));
+        assert_eq!(uri.query, vec![("cwd".to_string(), None)]);
+
+        let role = parse_role_uri("agents://codex/latest").expect("parse should succeed");
+        assert_eq!(role, None);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_latest_alias() {
+        let err = AgentsUri::parse("agents://codex/@latest~").expect_err("must reject alias");
+        assert!(format!("{err}").contains("invalid session id"));
+
+        let err = AgentsUri::parse("agents://codex/@latest~x").expect_err("must reject alias");
+        assert!(format!("{err}").contains("invalid session id"));
+    }
+
+    #[test]
+    fn latest_alias_is_not_a_role() {
+        let role = parse_role_uri("agents://codex/@latest~1").expect("parse should succeed");
+        assert_eq!(role, None);
+    }
+
     #[test]
     fn parse_valid_opencode_uri() {
         let uri = AgentsUri::parse("opencode://ses_43a90e3adffejRgrTdlJa48CtE")
@@ -1016,6 +1362,31 @@ mod tests {
         assert!(query.ignored_params.is_empty());
     }
 
+    #[test]
+    fn parse_collection_query_uri_with_project() {
+        let query = parse_collection_query_uri("claude?project=%2FUsers%2Falice%2Frepo")
+            .expect("collection query parse must work")
+            .expect("query should be present");
+        assert_eq!(query.project.as_deref(), Some("/Users/alice/repo"));
+        assert!(query.ignored_params.is_empty());
+
+        let err = parse_collection_query_uri("claude?project=").expect_err("must fail");
+        assert!(format!("{err}").contains("project requires a directory path"));
+    }
+
+    #[test]
+    fn parse_collection_query_uri_with_workdir_and_repo() {
+        let query = parse_collection_query_uri("codex?workdir=%2Fsrc%2Fwidgets&repo=acme/widgets/")
+            .expect("collection query parse must work")
+            .expect("query should be present");
+        assert_eq!(query.workdir.as_deref(), Some("/src/widgets"));
+        assert_eq!(query.repo.as_deref(), Some("acme/widgets"));
+        assert!(query.ignored_params.is_empty());
+
+        let err = parse_collection_query_uri("codex?repo=").expect_err("must fail");
+        assert!(format!("{err}").contains("repo requires owner/name or a remote URL"));
+    }
+
     #[test]
     fn parse_collection_query_uri_with_q_and_limit() {
         let query = parse_collection_query_uri("agents://claude?q=spawn+agent&limit=7")
@@ -1027,6 +1398,43 @@ mod tests {
         assert_eq!(query.limit, 7);
     }
 
+    #[test]
+    fn parse_collection_query_uri_with_sort() {
+        let query = parse_collection_query_uri("codex?q=deploy&sort=relevance")
+            .expect("collection query parse must work")
+            .expect("query should be present");
+        assert_eq!(query.sort, QuerySort::Relevance);
+        assert_eq!(
+            parse_collection_query_uri("codex")
+                .expect("collection query parse must work")
+                .expect("query should be present")
+                .sort,
+            QuerySort::Recency
+        );
+
+        let err = parse_collection_query_uri("codex?sort=newest").expect_err("must fail");
+        assert!(format!("{err}").contains("invalid sort=newest"));
+    }
+
+    #[test]
+    fn parse_collection_query_uri_with_offset_or_cursor() {
+        let query = parse_collection_query_uri("codex?q=deploy&offset=20")
+            .expect("collection query parse must work")
+            .expect("query should be present");
+        assert_eq!(query.offset, 20);
+        assert_eq!(query.cursor, None);
+
+        let query = parse_collection_query_uri("codex?q=deploy&cursor=10.abc")
+            .expect("collection query parse must work")
+            .expect("query should be present");
+        assert_eq!(query.offset, 0);
+        assert_eq!(query.cursor.as_deref(), Some("10.abc"));
+
+        let err =
+            parse_collection_query_uri("codex?offset=1&cursor=10.abc").expect_err("must fail");
+        assert!(format!("{err}").contains("use either offset or cursor"));
+    }
+
     #[test]
     fn parse_collection_query_uri_without_agents_prefix() {
         let query = parse_collection_query_uri("claude?q=spawn+agent&limit=7")
@@ -1093,6 +1501,55 @@ mod tests {
         assert_eq!(role_uri, None);
     }
 
+    #[test]
+    fn expand_alias_rewrites_alias_forms_and_keeps_query() {
+        let aliases = BTreeMap::from([(
+            "release-debug".to_string(),
+            "agents://codex/reviewer?limit=3".to_string(),
+        )]);
+        for input in [
+            "release-debug",
+            "alias/release-debug",
+            "agents://alias/release-debug",
+        ] {
+            assert_eq!(
+                expand_alias(input, &aliases).expect("expand"),
+                "agents://codex/reviewer?limit=3"
+            );
+        }
+        assert_eq!(
+            expand_alias("release-debug?q=panic", &aliases).expect("expand"),
+            "agents://codex/reviewer?limit=3&q=panic"
+        );
+        assert_eq!(
+            expand_alias("unknown", &aliases).expect("bare names pass through"),
+            "unknown"
+        );
+        assert_eq!(
+            expand_alias("codex/latest", &aliases).expect("uris pass through"),
+            "codex/latest"
+        );
+        let err = expand_alias("agents://alias/unknown", &aliases).expect_err("unknown alias");
+        assert!(err.to_string().contains("unknown alias `unknown`"));
+        // Parsing never expands aliases by itself.
+        assert!(AgentsUri::parse("agents://alias/release-debug").is_err());
+        assert!(!is_alias_name("codex"));
+        assert!(!is_alias_name("-x"));
+    }
+
+    #[test]
+    fn parse_prefix_accepts_session_id_prefixes() {
+        let uri = AgentsUri::parse_prefix("codex://019C871C").expect("parse must succeed");
+        assert_eq!(uri.session_id, "019c871c");
+        assert!(!uri.has_full_session_id());
+
+        let uri = AgentsUri::parse_prefix("agents://amp/t-019c0797").expect("parse must succeed");
+        assert_eq!(uri.session_id, "T-019c0797");
+
+        assert!(AgentsUri::parse_prefix("codex://019").is_err());
+        assert!(AgentsUri::parse("codex://019c871c").is_err());
+    }
+
     #[test]
     fn parse_role_query_uri_with_q_and_limit() {
         let query = parse_role_query_uri("agents://codex/reviewer?q=spawn+agent&limit=3")
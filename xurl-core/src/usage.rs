@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::jsonl;
+use crate::model::{ProviderKind, TokenUsage};
+
+/// Adds up the tokens a thread's provider recorded. Input counts include cached and
+/// cache-creation tokens; output counts include reasoning tokens where they are kept apart.
+/// Returns `None` when the provider recorded no usage.
+pub fn token_usage(provider: ProviderKind, raw: &str) -> Option<TokenUsage> {
+    match provider {
+        ProviderKind::Amp | ProviderKind::Gemini => {
+            let value = serde_json::from_str::<Value>(raw).ok()?;
+            let messages = value.get("messages")?.as_array()?;
+            sum(messages.iter().filter_map(|message| match provider {
+                ProviderKind::Amp => amp_usage(message),
+                _ => gemini_usage(message),
+            }))
+        }
+        ProviderKind::Codex => raw
+            .lines()
+            .rev()
+            .filter_map(jsonl::parse_thread_line)
+            .find_map(|value| codex_usage(&value)),
+        ProviderKind::Claude => {
+            // Claude repeats an assistant message's usage on every content block it streams.
+            let mut by_message = HashMap::new();
+            let mut anonymous = Vec::new();
+            for value in raw.lines().filter_map(jsonl::parse_thread_line) {
+                let Some(usage) = claude_usage(&value) else {
+                    continue;
+                };
+                match value.pointer("/message/id").and_then(Value::as_str) {
+                    Some(id) => {
+                        by_message.insert(id.to_string(), usage);
+                    }
+                    None => anonymous.push(usage),
+                }
+            }
+            sum(by_message.into_values().chain(anonymous))
+        }
+        ProviderKind::Pi => sum(raw
+            .lines()
+            .filter_map(jsonl::parse_thread_line)
+            .filter_map(|value| pi_usage(&value))),
+        ProviderKind::Opencode => sum(raw
+            .lines()
+            .filter_map(jsonl::parse_thread_line)
+            .filter_map(|value| opencode_usage(&value))),
+    }
+}
+
+fn sum(usages: impl Iterator<Item = TokenUsage>) -> Option<TokenUsage> {
+    usages.reduce(|total, usage| TokenUsage {
+        input_tokens: total.input_tokens + usage.input_tokens,
+        output_tokens: total.output_tokens + usage.output_tokens,
+    })
+}
+
+fn count(value: &Value, keys: &[&str]) -> u64 {
+    keys.iter()
+        .filter_map(|key| value.pointer(key).and_then(Value::as_u64))
+        .sum()
+}
+
+/// Codex `token_count` events carry the running total for the session.
+fn codex_usage(value: &Value) -> Option<TokenUsage> {
+    if value.get("type").and_then(Value::as_str) != Some("event_msg")
+        || value.pointer("/payload/type").and_then(Value::as_str) != Some("token_count")
+    {
+        return None;
+    }
+    let total = value.pointer("/payload/info/total_token_usage")?;
+    Some(TokenUsage {
+        input_tokens: count(total, &["/input_tokens"]),
+        output_tokens: count(total, &["/output_tokens"]),
+    })
+}
+
+fn claude_usage(value: &Value) -> Option<TokenUsage> {
+    if value.get("type").and_then(Value::as_str) != Some("assistant") {
+        return None;
+    }
+    let usage = value.pointer("/message/usage")?;
+    Some(TokenUsage {
+        input_tokens: count(
+            usage,
+            &[
+                "/input_tokens",
+                "/cache_creation_input_tokens",
+                "/cache_read_input_tokens",
+            ],
+        ),
+        output_tokens: count(usage, &["/output_tokens"]),
+    })
+}
+
+fn pi_usage(value: &Value) -> Option<TokenUsage> {
+    let usage = value.pointer("/message/usage")?;
+    Some(TokenUsage {
+        input_tokens: count(usage, &["/input", "/cacheRead", "/cacheWrite"]),
+        output_tokens: count(usage, &["/output"]),
+    })
+}
+
+fn opencode_usage(value: &Value) -> Option<TokenUsage> {
+    if value.get("type").and_then(Value::as_str) != Some("message") {
+        return None;
+    }
+    let tokens = value.pointer("/message/tokens")?;
+    Some(TokenUsage {
+        input_tokens: count(tokens, &["/input", "/cache/read", "/cache/write"]),
+        output_tokens: count(tokens, &["/output", "/reasoning"]),
+    })
+}
+
+fn gemini_usage(message: &Value) -> Option<TokenUsage> {
+    let tokens = message.get("tokens")?;
+    Some(TokenUsage {
+        input_tokens: count(tokens, &["/input"]),
+        output_tokens: count(tokens, &["/output", "/thoughts"]),
+    })
+}
+
+fn amp_usage(message: &Value) -> Option<TokenUsage> {
+    let usage = message.get("usage")?;
+    Some(TokenUsage {
+        input_tokens: count(
+            usage,
+            &[
+                "/inputTokens",
+                "/cacheCreationInputTokens",
+                "/cacheReadInputTokens",
+            ],
+        ),
+        output_tokens: count(usage, &["/outputTokens"]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::token_usage;
+    use crate::model::{ProviderKind, TokenUsage};
+
+    #[test]
+    fn token_usage_keeps_codex_totals_and_dedupes_claude_messages() {
+        let codex = concat!(
+            r#"{"type":"event_msg","payload":{"type":"token_count","info":{"total_token_usage":{"input_tokens":100,"output_tokens":10}}}}"#,
+            "\n",
+            r#"{"type":"event_msg","payload":{"type":"token_count","info":{"total_token_usage":{"input_tokens":250,"output_tokens":40}}}}"#,
+            "\n",
+        );
+        assert_eq!(
+            token_usage(ProviderKind::Codex, codex),
+            Some(TokenUsage {
+                input_tokens: 250,
+                output_tokens: 40
+            })
+        );
+
+        let claude = concat!(
+            r#"{"type":"assistant","message":{"id":"msg_1","usage":{"input_tokens":5,"cache_read_input_tokens":100,"output_tokens":7}}}"#,
+            "\n",
+            r#"{"type":"assistant","message":{"id":"msg_1","usage":{"input_tokens":5,"cache_read_input_tokens":100,"output_tokens":20}}}"#,
+            "\n",
+            r#"{"type":"assistant","message":{"id":"msg_2","usage":{"input_tokens":3,"output_tokens":4}}}"#,
+            "\n",
+        );
+        assert_eq!(
+            token_usage(ProviderKind::Claude, claude),
+            Some(TokenUsage {
+                input_tokens: 108,
+                output_tokens: 24
+            })
+        );
+
+        assert_eq!(
+            token_usage(ProviderKind::Pi, "{\"type\":\"session\"}\n"),
+            None
+        );
+    }
+}
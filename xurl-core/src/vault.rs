@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::error::{Result, XurlError};
+use crate::model::{ProviderKind, RenderOptions, SubagentListOptions, VaultSyncReport};
+use crate::provider::ProviderRoots;
+use crate::service::{
+    recent_threads, render_thread_head_markdown, render_thread_markdown, resolve_thread,
+};
+use crate::uri::AgentsUri;
+
+/// Where `xurl sync` keeps the note of a thread: `<vault>/<provider>/<session_id>.md`.
+pub fn vault_note_path(vault: &Path, uri: &AgentsUri) -> PathBuf {
+    vault
+        .join(uri.provider.to_string())
+        .join(format!("{}.md", uri.session_id))
+}
+
+/// Renders the newest `limit` main threads of `provider` (every provider when `None`) into
+/// markdown notes under `vault`, frontmatter included.
+///
+/// A note newer than its thread file is left alone without rendering the thread again, and a
+/// re-rendered note is only rewritten when its content changed. A thread that fails to
+/// resolve or render is reported as a warning and the sync moves on.
+pub fn sync_vault(
+    vault: &Path,
+    provider: Option<ProviderKind>,
+    limit: usize,
+    roots: &ProviderRoots,
+) -> Result<VaultSyncReport> {
+    let recent = recent_threads(provider, usize::MAX, roots)?;
+    let mut report = VaultSyncReport {
+        warnings: recent.warnings,
+        ..VaultSyncReport::default()
+    };
+    let uris = recent
+        .items
+        .iter()
+        .filter_map(|item| AgentsUri::parse(&item.uri).ok())
+        .filter(|uri| uri.agent_id.is_none())
+        .take(limit);
+    for uri in uris {
+        match sync_note(vault, &uri, roots, &mut report) {
+            Ok(()) => {}
+            Err(err @ XurlError::StrictParse { .. }) => return Err(err),
+            Err(err) => report
+                .warnings
+                .push(format!("skipped {}: {err}", uri.as_agents_string())),
+        }
+    }
+    Ok(report)
+}
+
+fn sync_note(
+    vault: &Path,
+    uri: &AgentsUri,
+    roots: &ProviderRoots,
+    report: &mut VaultSyncReport,
+) -> Result<()> {
+    let resolved = resolve_thread(uri, roots)?;
+    let path = vault_note_path(vault, uri);
+    let existing = fs::read_to_string(&path).ok();
+    if existing.is_some() && modified(&path) >= modified(&resolved.path) {
+        report.unchanged += 1;
+        return Ok(());
+    }
+
+    let options = RenderOptions::default();
+    let head = render_thread_head_markdown(
+        uri,
+        roots,
+        &options.timezone,
+        &SubagentListOptions::default(),
+    )?;
+    let body = render_thread_markdown(uri, &resolved, &options)?;
+    let note = format!("{head}\n{body}");
+    if existing.as_deref() == Some(note.as_str()) {
+        report.unchanged += 1;
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|source| XurlError::Io {
+            path: parent.to_path_buf(),
+            source,
+        })?;
+    }
+    fs::write(&path, note).map_err(|source| XurlError::Io {
+        path: path.clone(),
+        source,
+    })?;
+    if existing.is_some() {
+        report.updated.push(path);
+    } else {
+        report.created.push(path);
+    }
+    Ok(())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
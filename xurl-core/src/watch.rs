@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::Serialize;
+
+use crate::error::{Result, XurlError};
+use crate::model::{ProviderKind, ThreadEvent, ThreadEventKind};
+use crate::provider::ProviderRoots;
+use crate::render;
+use crate::service::{self, QueryCandidate, QuerySearchTarget};
+use crate::uri::AgentsUri;
+
+/// How long the provider roots must stay quiet before they are rescanned, so a burst of
+/// appends to one rollout is reported once.
+const SETTLE_DELAY: Duration = Duration::from_millis(200);
+
+/// Longest a busy root can delay a rescan; a thread written to without pause is still
+/// reported about this often.
+const MAX_SETTLE_DELAY: Duration = Duration::from_secs(1);
+
+/// Characters of the last message kept in a `thread_idle` event.
+const LAST_MESSAGE_CHARS: usize = 280;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WatchOptions {
+    /// Report `thread_idle` once a thread created or updated while watching has gone this
+    /// long without another change.
+    pub idle_after: Option<Duration>,
+}
+
+pub trait ThreadEventSink {
+    fn on_warning(&mut self, warning: &str);
+    /// Receives one event; returning `false` stops watching.
+    fn on_event(&mut self, event: &ThreadEvent) -> Result<bool>;
+}
+
+/// What a thread looked like at the last scan. File-backed threads compare size and
+/// modification time; OpenCode threads, which live in sqlite, compare `time_updated`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ThreadStamp {
+    File {
+        len: u64,
+        modified: Option<SystemTime>,
+    },
+    Updated(Option<u64>),
+}
+
+/// Remembers the main threads of some providers and reports the ones that appear or change
+/// between scans.
+#[derive(Debug)]
+pub struct ThreadWatcher {
+    seen: HashMap<String, ThreadStamp>,
+}
+
+impl ThreadWatcher {
+    /// Records every thread that exists now, so only later changes are reported. Watches all
+    /// providers when `provider` is `None`.
+    pub fn new(
+        provider: Option<ProviderKind>,
+        roots: &ProviderRoots,
+        warnings: &mut Vec<String>,
+    ) -> Result<Self> {
+        let mut watcher = Self {
+            seen: HashMap::new(),
+        };
+        for provider in
+            provider.map_or_else(|| ProviderKind::ALL.to_vec(), |provider| vec![provider])
+        {
+            watcher.rescan(provider, roots, warnings)?;
+        }
+        Ok(watcher)
+    }
+
+    /// Scans one provider again and returns events for threads that are new or changed since
+    /// the last scan. Threads that disappeared are forgotten without an event.
+    pub fn rescan(
+        &mut self,
+        provider: ProviderKind,
+        roots: &ProviderRoots,
+        warnings: &mut Vec<String>,
+    ) -> Result<Vec<ThreadEvent>> {
+        let candidates = service::collect_query_candidates(provider, roots, warnings, false)?;
+        let mut events = Vec::new();
+        let mut current = HashMap::new();
+        for candidate in candidates {
+            let stamp = thread_stamp(&candidate);
+            let event = match self.seen.get(&candidate.uri) {
+                None => Some(ThreadEventKind::ThreadCreated),
+                Some(previous) if *previous != stamp => Some(ThreadEventKind::ThreadUpdated),
+                Some(_) => None,
+            };
+            if let Some(event) = event {
+                events.push(ThreadEvent {
+                    event,
+                    provider,
+                    uri: candidate.uri.clone(),
+                    last_message: None,
+                });
+            }
+            current.insert(candidate.uri, stamp);
+        }
+
+        let prefix = format!("agents://{provider}/");
+        self.seen.retain(|uri, _| !uri.starts_with(&prefix));
+        self.seen.extend(current);
+        events.sort_by(|left, right| left.uri.cmp(&right.uri));
+        Ok(events)
+    }
+}
+
+/// Watches provider roots and reports main threads as they are created, updated, and (with
+/// `idle_after`) go quiet, until the sink asks to stop. Roots that do not exist yet are
+/// skipped with a warning.
+pub fn watch_threads(
+    provider: Option<ProviderKind>,
+    roots: &ProviderRoots,
+    options: &WatchOptions,
+    sink: &mut dyn ThreadEventSink,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut notifier = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })
+    .map_err(|err| notify_error(PathBuf::new(), err))?;
+
+    let providers = provider.map_or_else(|| ProviderKind::ALL.to_vec(), |provider| vec![provider]);
+    let mut watched = Vec::new();
+    for provider in providers {
+        let root = service::provider_root(provider, roots).to_path_buf();
+        if !root.is_dir() {
+            sink.on_warning(&format!(
+                "not watching {provider}: {} does not exist",
+                root.display()
+            ));
+            continue;
+        }
+        notifier
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(|err| notify_error(root.clone(), err))?;
+        watched.push((provider, root));
+    }
+    if watched.is_empty() {
+        return Err(XurlError::InvalidMode(
+            "no provider roots to watch; run `xurl doctor` to see where xurl looks".to_string(),
+        ));
+    }
+
+    // Snapshot after the roots are watched, so a write in between still triggers a rescan.
+    let mut warnings = Vec::new();
+    let mut watcher = ThreadWatcher::new(provider, roots, &mut warnings)?;
+    for warning in &warnings {
+        sink.on_warning(warning);
+    }
+
+    // Threads changed while watching that have not been reported idle yet.
+    let mut active = HashMap::<String, (ProviderKind, Instant)>::new();
+    loop {
+        let next_idle = options.idle_after.and_then(|idle_after| {
+            active
+                .values()
+                .map(|(_, changed_at)| *changed_at + idle_after)
+                .min()
+        });
+        let first = match next_idle {
+            Some(deadline) => {
+                match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(event) => Some(event),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                }
+            }
+            None => match rx.recv() {
+                Ok(event) => Some(event),
+                Err(_) => return Ok(()),
+            },
+        };
+        if let Some(first) = first
+            && !report_changes(first, &rx, &watched, &mut watcher, roots, &mut active, sink)?
+        {
+            return Ok(());
+        }
+
+        let Some(idle_after) = options.idle_after else {
+            active.clear();
+            continue;
+        };
+        let now = Instant::now();
+        let mut idle = active
+            .iter()
+            .filter(|(_, (_, changed_at))| now.duration_since(*changed_at) >= idle_after)
+            .map(|(uri, (provider, _))| (uri.clone(), *provider))
+            .collect::<Vec<_>>();
+        idle.sort_by(|left, right| left.0.cmp(&right.0));
+        for (uri, provider) in idle {
+            active.remove(&uri);
+            let event = ThreadEvent {
+                event: ThreadEventKind::ThreadIdle,
+                provider,
+                last_message: last_message(&uri, roots),
+                uri,
+            };
+            if !sink.on_event(&event)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Waits for the burst of file events that starts with `first` to settle, rescans the
+/// providers it touched, and reports their changes. Returns `false` when the sink asks to
+/// stop.
+fn report_changes(
+    first: notify::Result<notify::Event>,
+    rx: &mpsc::Receiver<notify::Result<notify::Event>>,
+    watched: &[(ProviderKind, PathBuf)],
+    watcher: &mut ThreadWatcher,
+    roots: &ProviderRoots,
+    active: &mut HashMap<String, (ProviderKind, Instant)>,
+    sink: &mut dyn ThreadEventSink,
+) -> Result<bool> {
+    let mut changed = Vec::new();
+    let mut note =
+        |event: notify::Result<notify::Event>, sink: &mut dyn ThreadEventSink| match event {
+            Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+            Ok(event) => {
+                for (provider, root) in watched {
+                    if !changed.contains(provider)
+                        && event.paths.iter().any(|path| path.starts_with(root))
+                    {
+                        changed.push(*provider);
+                    }
+                }
+            }
+            Err(err) => sink.on_warning(&format!("watch error: {err}")),
+        };
+    note(first, sink);
+    let deadline = Instant::now() + MAX_SETTLE_DELAY;
+    while let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+        match rx.recv_timeout(wait.min(SETTLE_DELAY)) {
+            Ok(event) => note(event, sink),
+            Err(_) => break,
+        }
+    }
+
+    for provider in changed {
+        // Warnings were reported with the first scan; repeating them on every change
+        // would drown the events.
+        for event in watcher.rescan(provider, roots, &mut Vec::new())? {
+            active.insert(event.uri.clone(), (provider, Instant::now()));
+            if !sink.on_event(&event)? {
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Renders an event as one line of JSON.
+pub fn render_thread_event_json(event: &ThreadEvent) -> Result<String> {
+    let mut line =
+        serde_json::to_string(event).map_err(|err| XurlError::Serialization(err.to_string()))?;
+    line.push('\n');
+    Ok(line)
+}
+
+/// Renders an event as the JSON body of a webhook POST. `text` carries a one-line summary
+/// for chat webhooks such as Slack's, which display that field.
+pub fn render_thread_webhook_json(event: &ThreadEvent) -> Result<String> {
+    #[derive(Serialize)]
+    struct WebhookPayload<'a> {
+        text: String,
+        #[serde(flatten)]
+        event: &'a ThreadEvent,
+    }
+
+    let state = match event.event {
+        ThreadEventKind::ThreadCreated => "started",
+        ThreadEventKind::ThreadUpdated => "updated",
+        ThreadEventKind::ThreadIdle => "went idle",
+    };
+    let mut text = format!("{} thread {} {state}", event.provider, event.uri);
+    if let Some(message) = &event.last_message {
+        text.push_str(": ");
+        text.push_str(message);
+    }
+    serde_json::to_string(&WebhookPayload { text, event })
+        .map_err(|err| XurlError::Serialization(err.to_string()))
+}
+
+/// The last message of a thread, shortened to one line; `None` when it cannot be read.
+fn last_message(uri: &str, roots: &ProviderRoots) -> Option<String> {
+    let uri = AgentsUri::parse(uri).ok()?;
+    let resolved = service::resolve_thread(&uri, roots).ok()?;
+    let bytes = fs::read(&resolved.path).ok()?;
+    let raw = String::from_utf8_lossy(&bytes);
+    let messages = render::extract_messages(uri.provider, &resolved.path, &raw).ok()?;
+    let message = messages.last()?;
+    Some(service::truncate_preview(&message.text, LAST_MESSAGE_CHARS))
+}
+
+fn thread_stamp(candidate: &QueryCandidate) -> ThreadStamp {
+    match &candidate.search_target {
+        QuerySearchTarget::File(path) => {
+            let meta = fs::metadata(path).ok();
+            ThreadStamp::File {
+                len: meta.as_ref().map_or(0, fs::Metadata::len),
+                modified: meta.and_then(|meta| meta.modified().ok()),
+            }
+        }
+        QuerySearchTarget::Text(_) => ThreadStamp::Updated(candidate.updated_epoch),
+    }
+}
+
+fn notify_error(path: PathBuf, err: notify::Error) -> XurlError {
+    XurlError::Io {
+        path,
+        source: io::Error::other(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use super::ThreadWatcher;
+    use crate::model::{ProviderKind, ThreadEventKind};
+    use crate::provider::ProviderRoots;
+    use crate::scan::ScanOptions;
+
+    fn roots(root: &Path) -> ProviderRoots {
+        ProviderRoots {
+            amp_root: root.join("amp"),
+            amp_cache_root: root.join("amp-cache"),
+            codex_root: root.join("codex"),
+            claude_root: root.join("claude"),
+            gemini_root: root.join("gemini"),
+            pi_root: root.join("pi"),
+            opencode_root: root.join("opencode"),
+            skills_root: root.join("skills"),
+            skills_cache_root: root.join("skills-cache"),
+            snapshots_root: root.join("snapshots"),
+            scan: ScanOptions::default(),
+            strict: false,
+        }
+    }
+
+    #[test]
+    fn rescan_reports_created_and_updated_threads_once() {
+        let temp = tempdir().expect("tempdir");
+        let roots = roots(temp.path());
+        let sessions = roots.codex_root.join("sessions/2026/02/23");
+        fs::create_dir_all(&sessions).expect("mkdir");
+        let existing = "019c871c-b1f9-7f60-9c4f-87ed09f13592";
+        let created = "019c871c-b1f9-7f60-9c4f-87ed09f13593";
+        let rollout = |id: &str| sessions.join(format!("rollout-2026-02-23T04-48-50-{id}.jsonl"));
+        let line = |id: &str| {
+            format!(
+                "{{\"timestamp\":\"2026-02-23T04:48:50Z\",\"type\":\"session_meta\",\"payload\":{{\"id\":\"{id}\"}}}}\n"
+            )
+        };
+        fs::write(rollout(existing), line(existing)).expect("write");
+
+        let mut warnings = Vec::new();
+        let mut watcher =
+            ThreadWatcher::new(Some(ProviderKind::Codex), &roots, &mut warnings).expect("watch");
+        assert!(
+            watcher
+                .rescan(ProviderKind::Codex, &roots, &mut warnings)
+                .expect("rescan")
+                .is_empty()
+        );
+
+        fs::write(rollout(existing), line(existing).repeat(2)).expect("append");
+        fs::write(rollout(created), line(created)).expect("create");
+        let events = watcher
+            .rescan(ProviderKind::Codex, &roots, &mut warnings)
+            .expect("rescan")
+            .into_iter()
+            .map(|event| (event.event, event.uri))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                (
+                    ThreadEventKind::ThreadUpdated,
+                    format!("agents://codex/{existing}")
+                ),
+                (
+                    ThreadEventKind::ThreadCreated,
+                    format!("agents://codex/{created}")
+                ),
+            ]
+        );
+        assert!(
+            watcher
+                .rescan(ProviderKind::Codex, &roots, &mut warnings)
+                .expect("rescan")
+                .is_empty()
+        );
+    }
+}
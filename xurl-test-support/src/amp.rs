@@ -0,0 +1,110 @@
+use std::fs;
+use std::path::Path;
+
+use tempfile::{TempDir, tempdir};
+
+use crate::ids::{AMP_SESSION_ID, AMP_SUBAGENT_ID};
+use crate::{Message, Role, json_string, write_file};
+
+/// Builds an `XDG_DATA_HOME` with Amp threads under `amp/threads/`.
+#[derive(Debug, Clone, Default)]
+pub struct AmpTreeBuilder {
+    threads: Vec<(String, Vec<Message>)>,
+}
+
+impl AmpTreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `amp/threads/<id>.json` holding `messages` as text blocks.
+    #[must_use]
+    pub fn with_thread(mut self, id: &str, messages: &[Message]) -> Self {
+        self.threads.push((id.to_string(), messages.to_vec()));
+        self
+    }
+
+    pub fn build(self) -> TempDir {
+        let temp = tempdir().expect("tempdir");
+        self.write_to(temp.path());
+        temp
+    }
+
+    pub fn write_to(&self, root: &Path) {
+        for (id, messages) in &self.threads {
+            let messages = messages
+                .iter()
+                .map(|message| {
+                    let role = match message.role {
+                        Role::User => "user",
+                        Role::Assistant => "assistant",
+                    };
+                    format!(
+                        r#"{{"role":"{role}","content":[{{"type":"text","text":{}}}]}}"#,
+                        json_string(&message.text)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            write_file(
+                &root.join(format!("amp/threads/{id}.json")),
+                &format!(r#"{{"id":"{id}","messages":[{messages}]}}"#),
+            );
+        }
+    }
+}
+
+pub fn setup_amp_tree() -> TempDir {
+    let temp = tempdir().expect("tempdir");
+    let thread_path = temp
+        .path()
+        .join(format!("amp/threads/{AMP_SESSION_ID}.json"));
+    fs::create_dir_all(thread_path.parent().expect("parent")).expect("mkdir");
+    fs::write(
+        &thread_path,
+        r#"{"id":"T-019c0797-c402-7389-bd80-d785c98df295","messages":[{"role":"user","content":[{"type":"text","text":"hello"}]},{"role":"assistant","content":[{"type":"thinking","thinking":"analyze"},{"type":"text","text":"world"}]}]}"#,
+    )
+    .expect("write");
+    temp
+}
+
+/// A main thread with a handoff to `AMP_SUBAGENT_ID`, tagged with `main_role` when given.
+pub fn setup_amp_subagent_tree_with_role(main_role: Option<&str>) -> TempDir {
+    let temp = tempdir().expect("tempdir");
+    let main_path = temp
+        .path()
+        .join(format!("amp/threads/{AMP_SESSION_ID}.json"));
+    fs::create_dir_all(main_path.parent().expect("parent")).expect("mkdir");
+    let role_field = main_role
+        .map(|role| format!(r#","role":"{role}""#))
+        .unwrap_or_default();
+    fs::write(
+        &main_path,
+        format!(
+            r#"{{"id":"{AMP_SESSION_ID}","status":"running","updatedAt":"2026-02-23T00:00:03Z","messages":[{{"role":"user","timestamp":"2026-02-23T00:00:00Z","content":[{{"type":"text","text":"main task"}}]}}],"relationships":[{{"type":"handoff","threadID":"{AMP_SUBAGENT_ID}"{role_field},"timestamp":"2026-02-23T00:00:02Z"}}]}}"#
+        ),
+    )
+    .expect("write main");
+
+    let child_path = temp
+        .path()
+        .join(format!("amp/threads/{AMP_SUBAGENT_ID}.json"));
+    fs::create_dir_all(child_path.parent().expect("parent")).expect("mkdir");
+    fs::write(
+        &child_path,
+        format!(
+            r#"{{"id":"{AMP_SUBAGENT_ID}","status":"completed","lastUpdated":"2026-02-23T00:00:14Z","messages":[{{"role":"user","timestamp":"2026-02-23T00:00:11Z","content":[{{"type":"text","text":"hello child"}}]}},{{"role":"assistant","timestamp":"2026-02-23T00:00:12Z","content":[{{"type":"text","text":"done child"}}]}}],"relationships":[{{"type":"handoff","threadID":"{AMP_SESSION_ID}","role":"child","timestamp":"2026-02-23T00:00:12Z"}}]}}"#
+        ),
+    )
+    .expect("write child");
+
+    temp
+}
+
+pub fn setup_amp_subagent_tree() -> TempDir {
+    setup_amp_subagent_tree_with_role(Some("parent"))
+}
+
+pub fn setup_amp_subagent_tree_missing_role() -> TempDir {
+    setup_amp_subagent_tree_with_role(None)
+}
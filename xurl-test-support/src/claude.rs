@@ -0,0 +1,143 @@
+use std::fs;
+use std::path::Path;
+
+use tempfile::{TempDir, tempdir};
+
+use crate::ids::{CLAUDE_AGENT_ID, CLAUDE_SESSION_ID};
+use crate::{Message, Role, json_string, write_file};
+
+/// Builds a `CLAUDE_CONFIG_DIR` with transcripts under `projects/<project>/`.
+#[derive(Debug, Clone)]
+pub struct ClaudeTreeBuilder {
+    project: String,
+    cwd: Option<String>,
+    threads: Vec<ClaudeThread>,
+}
+
+#[derive(Debug, Clone)]
+struct ClaudeThread {
+    project: String,
+    id: String,
+    records: Vec<String>,
+}
+
+impl Default for ClaudeTreeBuilder {
+    fn default() -> Self {
+        Self {
+            project: "project-xurl".to_string(),
+            cwd: None,
+            threads: Vec::new(),
+        }
+    }
+}
+
+impl ClaudeTreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the `projects/` directory name for threads added afterwards.
+    #[must_use]
+    pub fn with_project(mut self, project: &str) -> Self {
+        project.clone_into(&mut self.project);
+        self
+    }
+
+    /// Records `cwd` as the working directory of messages in threads added afterwards.
+    #[must_use]
+    pub fn with_cwd(mut self, cwd: &str) -> Self {
+        self.cwd = Some(cwd.to_string());
+        self
+    }
+
+    /// Adds `projects/<project>/<id>.jsonl` holding `messages`, one second apart.
+    #[must_use]
+    pub fn with_thread(mut self, id: &str, messages: &[Message]) -> Self {
+        let cwd = self
+            .cwd
+            .as_deref()
+            .map(|cwd| format!("\"cwd\":{},", json_string(cwd)))
+            .unwrap_or_default();
+        let records = messages
+            .iter()
+            .enumerate()
+            .map(|(idx, message)| {
+                let role = match message.role {
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                };
+                format!(
+                    "{{\"timestamp\":\"2026-02-23T00:00:{:02}Z\",\"type\":\"{role}\",\"sessionId\":\"{id}\",{cwd}\"message\":{{\"role\":\"{role}\",\"content\":{}}}}}",
+                    idx % 60,
+                    json_string(&message.text)
+                )
+            })
+            .collect();
+        self.threads.push(ClaudeThread {
+            project: self.project.clone(),
+            id: id.to_string(),
+            records,
+        });
+        self
+    }
+
+    /// Appends `record`, one raw transcript JSON line, to the last added thread.
+    #[must_use]
+    pub fn with_event(mut self, record: impl Into<String>) -> Self {
+        self.threads
+            .last_mut()
+            .expect("add a thread with with_thread first")
+            .records
+            .push(record.into());
+        self
+    }
+
+    pub fn build(self) -> TempDir {
+        let temp = tempdir().expect("tempdir");
+        self.write_to(temp.path());
+        temp
+    }
+
+    pub fn write_to(&self, root: &Path) {
+        for thread in &self.threads {
+            let content = thread
+                .records
+                .iter()
+                .map(|record| format!("{record}\n"))
+                .collect::<String>();
+            write_file(
+                &root.join(format!("projects/{}/{}.jsonl", thread.project, thread.id)),
+                &content,
+            );
+        }
+    }
+}
+
+/// A main thread with sidechain agent `CLAUDE_AGENT_ID` under `<session>/subagents/`.
+pub fn setup_claude_subagent_tree() -> TempDir {
+    let temp = tempdir().expect("tempdir");
+    let project = temp.path().join("projects/project-subagent");
+    fs::create_dir_all(&project).expect("mkdir");
+
+    let main_thread = project.join(format!("{CLAUDE_SESSION_ID}.jsonl"));
+    fs::write(
+        &main_thread,
+        format!(
+            "{{\"timestamp\":\"2026-02-23T00:00:00Z\",\"type\":\"user\",\"sessionId\":\"{CLAUDE_SESSION_ID}\",\"message\":{{\"role\":\"user\",\"content\":\"root thread\"}}}}\n"
+        ),
+    )
+    .expect("write main");
+
+    let subagents_dir = project.join(CLAUDE_SESSION_ID).join("subagents");
+    fs::create_dir_all(&subagents_dir).expect("mkdir");
+    let agent_thread = subagents_dir.join(format!("agent-{CLAUDE_AGENT_ID}.jsonl"));
+    fs::write(
+        &agent_thread,
+        format!(
+            "{{\"timestamp\":\"2026-02-23T00:00:10Z\",\"type\":\"user\",\"sessionId\":\"{CLAUDE_SESSION_ID}\",\"isSidechain\":true,\"agentId\":\"{CLAUDE_AGENT_ID}\",\"message\":{{\"role\":\"user\",\"content\":\"agent task\"}}}}\n{{\"timestamp\":\"2026-02-23T00:00:11Z\",\"type\":\"assistant\",\"sessionId\":\"{CLAUDE_SESSION_ID}\",\"isSidechain\":true,\"agentId\":\"{CLAUDE_AGENT_ID}\",\"message\":{{\"role\":\"assistant\",\"content\":\"agent done\"}}}}\n"
+        ),
+    )
+    .expect("write agent");
+
+    temp
+}
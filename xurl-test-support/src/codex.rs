@@ -0,0 +1,300 @@
+use std::fs;
+use std::path::Path;
+
+use tempfile::{TempDir, tempdir};
+
+use crate::ids::{SESSION_ID, SUBAGENT_ID};
+use crate::{Message, Role, json_string, write_file};
+
+/// Builds a `CODEX_HOME` with rollout files under `sessions/`.
+#[derive(Debug, Clone, Default)]
+pub struct CodexTreeBuilder {
+    threads: Vec<CodexThread>,
+    empty_state_db: bool,
+}
+
+#[derive(Debug, Clone)]
+struct CodexThread {
+    id: String,
+    session_meta: Option<(String, String)>,
+    parent: Option<String>,
+    records: Vec<String>,
+}
+
+/// Rollouts are named after the session start, like Codex does; this is the start of threads
+/// without a `session_meta`.
+const DEFAULT_STARTED_AT: &str = "2026-02-23T04:48:50Z";
+
+impl CodexTreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a rollout for thread `id` holding `messages` as `response_item` messages.
+    #[must_use]
+    pub fn with_thread(mut self, id: &str, messages: &[Message]) -> Self {
+        let records = messages
+            .iter()
+            .map(|message| {
+                let (role, kind) = match message.role {
+                    Role::User => ("user", "input_text"),
+                    Role::Assistant => ("assistant", "output_text"),
+                };
+                format!(
+                    "{{\"type\":\"response_item\",\"payload\":{{\"type\":\"message\",\"role\":\"{role}\",\"content\":[{{\"type\":\"{kind}\",\"text\":{}}}]}}}}",
+                    json_string(&message.text)
+                )
+            })
+            .collect();
+        self.threads.push(CodexThread {
+            id: id.to_string(),
+            session_meta: None,
+            parent: None,
+            records,
+        });
+        self
+    }
+
+    /// Starts the last added thread with a `session_meta` record for `cwd`, started at the
+    /// RFC 3339 `timestamp`, and names its rollout after that time.
+    #[must_use]
+    pub fn with_session_meta(mut self, cwd: &str, timestamp: &str) -> Self {
+        self.last_thread().session_meta = Some((cwd.to_string(), timestamp.to_string()));
+        self
+    }
+
+    /// Marks the last added thread as a subagent spawned by `parent_id`, recorded in its
+    /// `session_meta` source one level below the parent.
+    #[must_use]
+    pub fn with_parent(mut self, parent_id: &str) -> Self {
+        self.last_thread().parent = Some(parent_id.to_string());
+        self
+    }
+
+    /// Appends a `spawn_agent` call to the last added thread that starts subagent `agent_id`.
+    #[must_use]
+    pub fn with_spawned_agent(self, agent_id: &str) -> Self {
+        let output = format!("{{\"agent_id\":{}}}", json_string(agent_id));
+        self.with_function_call(
+            "spawn_agent",
+            "{}",
+            &format!("call_spawn_{agent_id}"),
+            &output,
+        )
+    }
+
+    /// Appends a `close_agent` call to the last added thread that reports subagent `agent_id`
+    /// as `status`, like `completed`, with `message`.
+    #[must_use]
+    pub fn with_closed_agent(self, agent_id: &str, status: &str, message: &str) -> Self {
+        let arguments = format!("{{\"id\":{}}}", json_string(agent_id));
+        let output = format!(
+            "{{\"status\":{{{}:{}}}}}",
+            json_string(status),
+            json_string(message)
+        );
+        self.with_function_call(
+            "close_agent",
+            &arguments,
+            &format!("call_close_{agent_id}"),
+            &output,
+        )
+    }
+
+    /// Appends `record`, one raw rollout JSON line, to the last added thread.
+    #[must_use]
+    pub fn with_event(mut self, record: impl Into<String>) -> Self {
+        self.last_thread().records.push(record.into());
+        self
+    }
+
+    /// Adds an empty `state.sqlite`, so lookups fall back from the unreadable index to a scan.
+    #[must_use]
+    pub fn with_empty_state_db(mut self) -> Self {
+        self.empty_state_db = true;
+        self
+    }
+
+    pub fn build(self) -> TempDir {
+        let temp = tempdir().expect("tempdir");
+        self.write_to(temp.path());
+        temp
+    }
+
+    pub fn write_to(&self, root: &Path) {
+        for thread in &self.threads {
+            let mut content = String::new();
+            let source = thread.parent.as_ref().map(|parent| {
+                format!(
+                    ",\"source\":{{\"subagent\":{{\"thread_spawn\":{{\"parent_thread_id\":{},\"depth\":{}}}}}}}",
+                    json_string(parent),
+                    self.spawn_depth(thread),
+                )
+            });
+            let source = source.as_deref().unwrap_or_default();
+            if let Some((cwd, timestamp)) = &thread.session_meta {
+                content.push_str(&format!(
+                    "{{\"timestamp\":{timestamp},\"type\":\"session_meta\",\"payload\":{{\"id\":{},\"timestamp\":{timestamp},\"cwd\":{}{source}}}}}\n",
+                    json_string(&thread.id),
+                    json_string(cwd),
+                    timestamp = json_string(timestamp),
+                ));
+            } else if thread.parent.is_some() {
+                content.push_str(&format!(
+                    "{{\"type\":\"session_meta\",\"payload\":{{\"id\":{}{source}}}}}\n",
+                    json_string(&thread.id),
+                ));
+            }
+            for record in &thread.records {
+                content.push_str(record);
+                content.push('\n');
+            }
+            write_file(&root.join(Self::rollout_path(thread)), &content);
+        }
+        if self.empty_state_db {
+            write_file(&root.join("state.sqlite"), "");
+        }
+    }
+
+    /// Appends a `function_call` record and its `function_call_output` to the last added thread.
+    fn with_function_call(self, name: &str, arguments: &str, call_id: &str, output: &str) -> Self {
+        let call_id = json_string(call_id);
+        self.with_event(format!(
+            "{{\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call\",\"name\":{},\"arguments\":{},\"call_id\":{call_id}}}}}",
+            json_string(name),
+            json_string(arguments),
+        ))
+        .with_event(format!(
+            "{{\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call_output\",\"call_id\":{call_id},\"output\":{}}}}}",
+            json_string(output),
+        ))
+    }
+
+    /// How many parents separate `thread` from a main thread of this tree.
+    fn spawn_depth(&self, thread: &CodexThread) -> usize {
+        let mut depth = 0;
+        let mut parent = thread.parent.as_deref();
+        while let Some(id) = parent {
+            depth += 1;
+            parent = self
+                .threads
+                .iter()
+                .find(|candidate| candidate.id == id)
+                .and_then(|candidate| candidate.parent.as_deref());
+        }
+        depth
+    }
+
+    fn last_thread(&mut self) -> &mut CodexThread {
+        self.threads
+            .last_mut()
+            .expect("add a thread with with_thread first")
+    }
+
+    /// `sessions/2026/02/23/rollout-2026-02-23T04-48-50-<id>.jsonl` for a thread started at
+    /// `2026-02-23T04:48:50Z`.
+    fn rollout_path(thread: &CodexThread) -> String {
+        let started_at = thread
+            .session_meta
+            .as_ref()
+            .map_or(DEFAULT_STARTED_AT, |(_, timestamp)| timestamp.as_str());
+        let stamp = started_at
+            .get(..19)
+            .expect("RFC 3339 session timestamp")
+            .replace(':', "-");
+        let date = stamp[..10].replace('-', "/");
+        format!("sessions/{date}/rollout-{stamp}-{}.jsonl", thread.id)
+    }
+}
+
+pub fn setup_codex_tree() -> TempDir {
+    CodexTreeBuilder::new()
+        .with_thread(
+            SESSION_ID,
+            &[Message::user("hello"), Message::assistant("world")],
+        )
+        .build()
+}
+
+pub fn setup_codex_tree_with_sqlite_missing_threads() -> TempDir {
+    CodexTreeBuilder::new()
+        .with_thread(
+            SESSION_ID,
+            &[Message::user("hello"), Message::assistant("world")],
+        )
+        .with_empty_state_db()
+        .build()
+}
+
+pub fn setup_codex_role_query_tree() -> TempDir {
+    CodexTreeBuilder::new()
+        .with_thread(
+            SESSION_ID,
+            &[
+                Message::user("run reviewer role"),
+                Message::assistant("reviewer done"),
+            ],
+        )
+        .build()
+}
+
+/// Writes a `reviewer` role to `config.toml`, with its model in `agents/reviewer.toml`.
+pub fn setup_codex_role_configs(root: &Path) {
+    fs::write(
+        root.join("config.toml"),
+        r#"
+[agents.reviewer]
+description = "Find issues."
+config_file = "agents/reviewer.toml"
+model_reasoning_effort = "high"
+developer_instructions = "Focus on high priority issues."
+"#,
+    )
+    .expect("write config");
+
+    let role_dir = root.join("agents");
+    fs::create_dir_all(&role_dir).expect("mkdir");
+    fs::write(
+        role_dir.join("reviewer.toml"),
+        r#"
+model = "gpt-5.3-codex"
+"#,
+    )
+    .expect("write role config");
+}
+
+/// A main thread that spawns, waits on, and closes `SUBAGENT_ID`, plus the subagent rollout.
+pub fn setup_codex_subagent_tree() -> TempDir {
+    let temp = tempdir().expect("tempdir");
+    let main_thread_path = temp.path().join(format!(
+        "sessions/2026/02/23/rollout-2026-02-23T04-48-50-{SESSION_ID}.jsonl"
+    ));
+    fs::create_dir_all(main_thread_path.parent().expect("parent")).expect("mkdir");
+    fs::write(
+        &main_thread_path,
+        format!(
+            "{{\"timestamp\":\"2026-02-23T00:00:00Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call\",\"name\":\"spawn_agent\",\"arguments\":\"{{}}\",\"call_id\":\"call_spawn\"}}}}\n{{\"timestamp\":\"2026-02-23T00:00:01Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call_output\",\"call_id\":\"call_spawn\",\"output\":\"{{\\\"agent_id\\\":\\\"{SUBAGENT_ID}\\\"}}\"}}}}\n{{\"timestamp\":\"2026-02-23T00:00:02Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call\",\"name\":\"wait\",\"arguments\":\"{{\\\"ids\\\":[\\\"{SUBAGENT_ID}\\\"],\\\"timeout_ms\\\":120000}}\",\"call_id\":\"call_wait\"}}}}\n{{\"timestamp\":\"2026-02-23T00:00:03Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call_output\",\"call_id\":\"call_wait\",\"output\":\"{{\\\"status\\\":{{\\\"running\\\":\\\"in progress\\\"}},\\\"timed_out\\\":false}}\"}}}}\n{{\"timestamp\":\"2026-02-23T00:00:04Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call\",\"name\":\"close_agent\",\"arguments\":\"{{\\\"id\\\":\\\"{SUBAGENT_ID}\\\"}}\",\"call_id\":\"call_close\"}}}}\n{{\"timestamp\":\"2026-02-23T00:00:05Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"function_call_output\",\"call_id\":\"call_close\",\"output\":\"{{\\\"status\\\":{{\\\"completed\\\":\\\"done\\\"}}}}\"}}}}\n"
+        ),
+    )
+    .expect("write main");
+
+    let child_thread_path = temp.path().join(format!(
+        "sessions/2026/02/23/rollout-2026-02-23T04-49-10-{SUBAGENT_ID}.jsonl"
+    ));
+    fs::create_dir_all(child_thread_path.parent().expect("parent")).expect("mkdir");
+    fs::write(
+        &child_thread_path,
+        format!(
+            "{{\"timestamp\":\"2026-02-23T00:00:10Z\",\"type\":\"session_meta\",\"payload\":{{\"id\":\"{SUBAGENT_ID}\",\"source\":{{\"subagent\":{{\"thread_spawn\":{{\"parent_thread_id\":\"{SESSION_ID}\",\"depth\":1}}}}}}}}}}\n{{\"timestamp\":\"2026-02-23T00:00:11Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"message\",\"role\":\"user\",\"content\":[{{\"type\":\"input_text\",\"text\":\"hello child\"}}]}}}}\n{{\"timestamp\":\"2026-02-23T00:00:12Z\",\"type\":\"response_item\",\"payload\":{{\"type\":\"message\",\"role\":\"assistant\",\"content\":[{{\"type\":\"output_text\",\"text\":\"done child\"}}]}}}}\n"
+        ),
+    )
+    .expect("write child");
+
+    temp
+}
+
+pub fn setup_codex_subagent_tree_with_sqlite_missing_threads() -> TempDir {
+    let temp = setup_codex_subagent_tree();
+    fs::write(temp.path().join("state.sqlite"), "").expect("write sqlite");
+    temp
+}
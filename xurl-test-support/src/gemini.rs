@@ -0,0 +1,198 @@
+use std::fs;
+use std::path::Path;
+
+use tempfile::{TempDir, tempdir};
+
+use crate::ids::{GEMINI_CHILD_SESSION_ID, GEMINI_MISSING_CHILD_SESSION_ID, GEMINI_SESSION_ID};
+use crate::{Message, Role, json_string, write_file};
+
+/// Project hash the canned Gemini trees store their chats under.
+pub const GEMINI_PROJECT_HASH: &str =
+    "0c0d7b04c22749f3687ea60b66949fd32bcea2551d4349bf72346a9ccc9a9ba4";
+
+/// Builds a `GEMINI_CLI_HOME` with chats under `.gemini/tmp/<project_hash>/chats/`.
+#[derive(Debug, Clone, Default)]
+pub struct GeminiTreeBuilder {
+    threads: Vec<(String, Vec<Message>)>,
+}
+
+impl GeminiTreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a chat file for session `id` holding `messages` as `user` / `gemini` entries.
+    #[must_use]
+    pub fn with_thread(mut self, id: &str, messages: &[Message]) -> Self {
+        self.threads.push((id.to_string(), messages.to_vec()));
+        self
+    }
+
+    pub fn build(self) -> TempDir {
+        let temp = tempdir().expect("tempdir");
+        self.write_to(temp.path());
+        temp
+    }
+
+    pub fn write_to(&self, root: &Path) {
+        for (id, messages) in &self.threads {
+            let messages = messages
+                .iter()
+                .map(|message| {
+                    let kind = match message.role {
+                        Role::User => "user",
+                        Role::Assistant => "gemini",
+                    };
+                    format!(
+                        r#"    {{ "type": "{kind}", "content": {} }}"#,
+                        json_string(&message.text)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",\n");
+            let short_id = id.get(..8).unwrap_or(id);
+            write_file(
+                &root.join(format!(
+                    ".gemini/tmp/{GEMINI_PROJECT_HASH}/chats/session-2026-01-08T11-55-29-{short_id}.json"
+                )),
+                &format!(
+                    r#"{{
+  "sessionId": "{id}",
+  "projectHash": "{GEMINI_PROJECT_HASH}",
+  "startTime": "2026-01-08T11:55:12.379Z",
+  "lastUpdated": "2026-01-08T12:31:14.881Z",
+  "messages": [
+{messages}
+  ]
+}}"#
+                ),
+            );
+        }
+    }
+}
+
+pub fn setup_gemini_tree() -> TempDir {
+    let temp = tempdir().expect("tempdir");
+    let thread_path = temp.path().join(format!(
+        ".gemini/tmp/{GEMINI_PROJECT_HASH}/chats/session-2026-01-08T11-55-29-29d207db.json"
+    ));
+    fs::create_dir_all(thread_path.parent().expect("parent")).expect("mkdir");
+    fs::write(
+        &thread_path,
+        format!(
+            r#"{{
+  "sessionId": "{GEMINI_SESSION_ID}",
+  "projectHash": "{GEMINI_PROJECT_HASH}",
+  "startTime": "2026-01-08T11:55:12.379Z",
+  "lastUpdated": "2026-01-08T12:31:14.881Z",
+  "messages": [
+    {{ "type": "info", "content": "ignored" }},
+    {{ "type": "user", "content": "hello" }},
+    {{ "type": "gemini", "content": "world" }}
+  ]
+}}"#
+        ),
+    )
+    .expect("write");
+    temp
+}
+
+/// A main chat, a child chat resumed from it, and `logs.json` that also names a child
+/// session without a chat file.
+pub fn setup_gemini_subagent_tree() -> TempDir {
+    let temp = tempdir().expect("tempdir");
+    let project_hash = GEMINI_PROJECT_HASH;
+    let project_root = temp.path().join(format!(".gemini/tmp/{project_hash}"));
+    let chats_dir = project_root.join("chats");
+    fs::create_dir_all(&chats_dir).expect("mkdir chats");
+
+    let main_chat_path = chats_dir.join("session-2026-01-08T11-55-main.json");
+    fs::write(
+        &main_chat_path,
+        format!(
+            r#"{{
+  "sessionId": "{GEMINI_SESSION_ID}",
+  "projectHash": "{project_hash}",
+  "startTime": "2026-01-08T11:55:12.379Z",
+  "lastUpdated": "2026-01-08T12:31:14.881Z",
+  "messages": [
+    {{ "type": "user", "content": "hello main" }},
+    {{ "type": "gemini", "content": "main done" }}
+  ]
+}}"#
+        ),
+    )
+    .expect("write main chat");
+
+    let child_chat_path = chats_dir.join("session-2026-01-08T12-12-child.json");
+    fs::write(
+        &child_chat_path,
+        format!(
+            r#"{{
+  "sessionId": "{GEMINI_CHILD_SESSION_ID}",
+  "parentSessionId": "{GEMINI_SESSION_ID}",
+  "projectHash": "{project_hash}",
+  "startTime": "2026-01-08T12:12:00.000Z",
+  "lastUpdated": "2026-01-08T12:20:00.000Z",
+  "messages": [
+    {{ "type": "user", "content": "/resume" }},
+    {{ "type": "gemini", "content": "child done" }}
+  ]
+}}"#
+        ),
+    )
+    .expect("write child chat");
+
+    let logs_path = project_root.join("logs.json");
+    fs::write(
+        &logs_path,
+        format!(
+            r#"[
+  {{
+    "sessionId": "{GEMINI_SESSION_ID}",
+    "messageId": 0,
+    "type": "user",
+    "message": "hello main",
+    "timestamp": "2026-01-08T11:59:09.195Z"
+  }},
+  {{
+    "sessionId": "{GEMINI_MISSING_CHILD_SESSION_ID}",
+    "messageId": 0,
+    "type": "user",
+    "message": "/resume",
+    "timestamp": "2026-01-08T12:00:09.195Z"
+  }},
+  {{
+    "sessionId": "{GEMINI_CHILD_SESSION_ID}",
+    "messageId": 0,
+    "type": "user",
+    "message": "/resume",
+    "timestamp": "2026-01-08T12:11:44.907Z"
+  }}
+]"#
+        ),
+    )
+    .expect("write logs");
+
+    temp
+}
+
+/// Same as [`setup_gemini_subagent_tree`], with `logs.json` written as NDJSON.
+pub fn setup_gemini_subagent_tree_with_ndjson_logs() -> TempDir {
+    let temp = setup_gemini_subagent_tree();
+    let project_hash = GEMINI_PROJECT_HASH;
+    let logs_path = temp
+        .path()
+        .join(format!(".gemini/tmp/{project_hash}/logs.json"));
+    fs::write(
+        &logs_path,
+        format!(
+            r#"{{"sessionId":"{GEMINI_SESSION_ID}","messageId":0,"type":"user","message":"hello main","timestamp":"2026-01-08T11:59:09.195Z"}}
+{{"sessionId":"{GEMINI_MISSING_CHILD_SESSION_ID}","messageId":0,"type":"user","message":"/resume","timestamp":"2026-01-08T12:00:09.195Z"}}
+{{"sessionId":"{GEMINI_CHILD_SESSION_ID}","messageId":0,"type":"user","message":"/resume","timestamp":"2026-01-08T12:11:44.907Z"}}"#
+        ),
+    )
+    .expect("write ndjson logs");
+
+    temp
+}
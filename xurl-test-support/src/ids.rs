@@ -0,0 +1,18 @@
+//! Session ids used by the canned fixture trees.
+
+pub const SESSION_ID: &str = "019c871c-b1f9-7f60-9c4f-87ed09f13592";
+pub const SUBAGENT_ID: &str = "019c87fb-38b9-7843-92b1-832f02598495";
+pub const AMP_SESSION_ID: &str = "T-019c0797-c402-7389-bd80-d785c98df295";
+pub const AMP_SUBAGENT_ID: &str = "T-1abc0797-c402-7389-bd80-d785c98df295";
+pub const GEMINI_SESSION_ID: &str = "29d207db-ca7e-40ba-87f7-e14c9de60613";
+pub const GEMINI_CHILD_SESSION_ID: &str = "2b112c8a-d80a-4cff-9c8a-6f3e6fbaf7fb";
+pub const GEMINI_MISSING_CHILD_SESSION_ID: &str = "62f9f98d-c578-4d3a-b4bf-3aaed19889d6";
+pub const PI_SESSION_ID: &str = "12cb4c19-2774-4de4-a0d0-9fa32fbae29f";
+pub const PI_ENTRY_ID: &str = "d1b2c3d4";
+pub const PI_CHILD_SESSION_ID: &str = "72b3a4a8-4f08-40af-8d7f-8b2c77584e89";
+pub const PI_MISSING_CHILD_SESSION_ID: &str = "b200f2f0-5291-4b89-a1e7-7c6a95f11011";
+pub const CLAUDE_SESSION_ID: &str = "2823d1df-720a-4c31-ac55-ae8ba726721f";
+pub const CLAUDE_AGENT_ID: &str = "acompact-69d537";
+pub const OPENCODE_MAIN_SESSION_ID: &str = "ses_5x7md9kx3c1p";
+pub const OPENCODE_CHILD_SESSION_ID: &str = "ses_5x7md9kx3c2p";
+pub const OPENCODE_CHILD_EMPTY_SESSION_ID: &str = "ses_5x7md9kx3c3p";
@@ -0,0 +1,64 @@
+//! On-disk provider session trees for integration tests against `xurl-core` and the `xurl` CLI.
+//!
+//! Each provider module has a builder that writes threads in that provider's native layout
+//! under a temporary directory, plus the canned fixture trees used by the `xurl` CLI tests.
+//! The doc comment on every builder names the environment variable that points `xurl` at the
+//! built root.
+
+use std::fs;
+use std::path::Path;
+
+pub mod amp;
+pub mod claude;
+pub mod codex;
+pub mod gemini;
+pub mod ids;
+pub mod opencode;
+pub mod pi;
+
+pub use amp::AmpTreeBuilder;
+pub use claude::ClaudeTreeBuilder;
+pub use codex::CodexTreeBuilder;
+pub use gemini::GeminiTreeBuilder;
+pub use opencode::OpencodeTreeBuilder;
+pub use pi::PiTreeBuilder;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// One plain-text turn of a built thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub role: Role,
+    pub text: String,
+}
+
+impl Message {
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            text: text.into(),
+        }
+    }
+
+    pub fn assistant(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::Assistant,
+            text: text.into(),
+        }
+    }
+}
+
+/// Quotes `text` as a JSON string literal.
+fn json_string(text: &str) -> String {
+    serde_json::to_string(text).expect("serialize string")
+}
+
+/// Writes `content` to `path`, creating parent directories first.
+fn write_file(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().expect("parent")).expect("mkdir");
+    fs::write(path, content).expect("write");
+}
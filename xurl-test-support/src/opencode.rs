@@ -0,0 +1,206 @@
+use std::fs;
+use std::path::Path;
+
+use rusqlite::{Connection, params};
+use tempfile::{TempDir, tempdir};
+
+use crate::ids::{
+    OPENCODE_CHILD_EMPTY_SESSION_ID, OPENCODE_CHILD_SESSION_ID, OPENCODE_MAIN_SESSION_ID,
+};
+use crate::{Message, Role, json_string};
+
+const SCHEMA: &str = "
+        CREATE TABLE session (
+            id TEXT PRIMARY KEY,
+            parent_id TEXT
+        );
+        CREATE TABLE message (
+            id TEXT PRIMARY KEY,
+            session_id TEXT NOT NULL,
+            time_created INTEGER NOT NULL,
+            data TEXT NOT NULL
+        );
+        CREATE TABLE part (
+            id TEXT PRIMARY KEY,
+            message_id TEXT NOT NULL,
+            session_id TEXT NOT NULL,
+            time_created INTEGER NOT NULL,
+            data TEXT NOT NULL
+        );
+        ";
+
+/// Builds an `XDG_DATA_HOME` with sessions in `opencode/opencode.db`.
+#[derive(Debug, Clone, Default)]
+pub struct OpencodeTreeBuilder {
+    sessions: Vec<(String, Option<String>, Vec<Message>)>,
+}
+
+impl OpencodeTreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds session `id` holding `messages`, each with a single text part.
+    #[must_use]
+    pub fn with_thread(mut self, id: &str, messages: &[Message]) -> Self {
+        self.sessions
+            .push((id.to_string(), None, messages.to_vec()));
+        self
+    }
+
+    /// Adds session `id` as a child (subagent) session of `parent_id`.
+    #[must_use]
+    pub fn with_child_thread(mut self, parent_id: &str, id: &str, messages: &[Message]) -> Self {
+        self.sessions.push((
+            id.to_string(),
+            Some(parent_id.to_string()),
+            messages.to_vec(),
+        ));
+        self
+    }
+
+    pub fn build(self) -> TempDir {
+        let temp = tempdir().expect("tempdir");
+        self.write_to(temp.path());
+        temp
+    }
+
+    /// Messages get increasing `time_created` values across sessions, in insertion order.
+    pub fn write_to(&self, root: &Path) {
+        let opencode_root = root.join("opencode");
+        fs::create_dir_all(&opencode_root).expect("mkdir");
+        let conn = Connection::open(opencode_root.join("opencode.db")).expect("open sqlite");
+        conn.execute_batch(SCHEMA).expect("create schema");
+
+        let mut time = 0_i64;
+        for (id, parent_id, messages) in &self.sessions {
+            conn.execute(
+                "INSERT INTO session (id, parent_id) VALUES (?1, ?2)",
+                params![id, parent_id],
+            )
+            .expect("insert session");
+            for message in messages {
+                time += 1;
+                let data = match message.role {
+                    Role::User => format!(r#"{{"role":"user","time":{{"created":{time}}}}}"#),
+                    Role::Assistant => format!(
+                        r#"{{"role":"assistant","time":{{"created":{time},"completed":{}}}}}"#,
+                        time + 1
+                    ),
+                };
+                let message_id = format!("msg_{time}");
+                conn.execute(
+                    "INSERT INTO message (id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4)",
+                    params![message_id, id, time, data],
+                )
+                .expect("insert message");
+                conn.execute(
+                    "INSERT INTO part (id, message_id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        format!("part_{time}"),
+                        message_id,
+                        id,
+                        time,
+                        format!(r#"{{"type":"text","text":{}}}"#, json_string(&message.text))
+                    ],
+                )
+                .expect("insert part");
+            }
+        }
+    }
+}
+
+/// A main session with one child session holding a reply and one empty child session.
+pub fn setup_opencode_subagent_tree() -> TempDir {
+    let temp = tempdir().expect("tempdir");
+    let opencode_root = temp.path().join("opencode");
+    fs::create_dir_all(&opencode_root).expect("mkdir");
+    let db_path = opencode_root.join("opencode.db");
+
+    let conn = Connection::open(&db_path).expect("open sqlite");
+    conn.execute_batch(SCHEMA).expect("create schema");
+
+    conn.execute(
+        "INSERT INTO session (id, parent_id) VALUES (?1, NULL)",
+        [OPENCODE_MAIN_SESSION_ID],
+    )
+    .expect("insert main session");
+    conn.execute(
+        "INSERT INTO session (id, parent_id) VALUES (?1, ?2)",
+        params![OPENCODE_CHILD_SESSION_ID, OPENCODE_MAIN_SESSION_ID],
+    )
+    .expect("insert child session");
+    conn.execute(
+        "INSERT INTO session (id, parent_id) VALUES (?1, ?2)",
+        params![OPENCODE_CHILD_EMPTY_SESSION_ID, OPENCODE_MAIN_SESSION_ID],
+    )
+    .expect("insert empty child session");
+
+    conn.execute(
+        "INSERT INTO message (id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            "main_msg_1",
+            OPENCODE_MAIN_SESSION_ID,
+            1_i64,
+            r#"{"role":"user","time":{"created":1}}"#
+        ],
+    )
+    .expect("insert main user");
+    conn.execute(
+        "INSERT INTO part (id, message_id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "main_part_1",
+            "main_msg_1",
+            OPENCODE_MAIN_SESSION_ID,
+            1_i64,
+            r#"{"type":"text","text":"main root prompt"}"#
+        ],
+    )
+    .expect("insert main user part");
+
+    conn.execute(
+        "INSERT INTO message (id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            "child_msg_1",
+            OPENCODE_CHILD_SESSION_ID,
+            2_i64,
+            r#"{"role":"user","time":{"created":2}}"#
+        ],
+    )
+    .expect("insert child user");
+    conn.execute(
+        "INSERT INTO part (id, message_id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "child_part_1",
+            "child_msg_1",
+            OPENCODE_CHILD_SESSION_ID,
+            2_i64,
+            r#"{"type":"text","text":"child asks for help"}"#
+        ],
+    )
+    .expect("insert child user part");
+
+    conn.execute(
+        "INSERT INTO message (id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            "child_msg_2",
+            OPENCODE_CHILD_SESSION_ID,
+            3_i64,
+            r#"{"role":"assistant","time":{"created":3,"completed":4}}"#
+        ],
+    )
+    .expect("insert child assistant");
+    conn.execute(
+        "INSERT INTO part (id, message_id, session_id, time_created, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "child_part_2",
+            "child_msg_2",
+            OPENCODE_CHILD_SESSION_ID,
+            3_i64,
+            r#"{"type":"text","text":"child completed"}"#
+        ],
+    )
+    .expect("insert child assistant part");
+
+    temp
+}
@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::Path;
+
+use tempfile::{TempDir, tempdir};
+
+use crate::ids::{PI_CHILD_SESSION_ID, PI_MISSING_CHILD_SESSION_ID, PI_SESSION_ID};
+use crate::{Message, Role, json_string, write_file};
+
+/// Builds a root whose `agent/` directory is a `PI_CODING_AGENT_DIR`, with sessions under
+/// `agent/sessions/`.
+#[derive(Debug, Clone, Default)]
+pub struct PiTreeBuilder {
+    threads: Vec<(String, Vec<Message>)>,
+}
+
+impl PiTreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a session file for `id` holding `messages` as one linear branch. Entry ids are
+    /// `00000001`, `00000002`, ... in message order.
+    #[must_use]
+    pub fn with_thread(mut self, id: &str, messages: &[Message]) -> Self {
+        self.threads.push((id.to_string(), messages.to_vec()));
+        self
+    }
+
+    pub fn build(self) -> TempDir {
+        let temp = tempdir().expect("tempdir");
+        self.write_to(temp.path());
+        temp
+    }
+
+    pub fn write_to(&self, root: &Path) {
+        for (id, messages) in &self.threads {
+            let mut content = format!(
+                "{{\"type\":\"session\",\"version\":3,\"id\":\"{id}\",\"timestamp\":\"2026-02-23T13:00:12.780Z\",\"cwd\":\"/tmp/project\"}}\n"
+            );
+            for (idx, message) in messages.iter().enumerate() {
+                let role = match message.role {
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                };
+                let parent_id = if idx == 0 {
+                    "null".to_string()
+                } else {
+                    format!("\"{idx:08x}\"")
+                };
+                content.push_str(&format!(
+                    "{{\"type\":\"message\",\"id\":\"{:08x}\",\"parentId\":{parent_id},\"timestamp\":\"2026-02-23T13:00:{:02}.000Z\",\"message\":{{\"role\":\"{role}\",\"content\":[{{\"type\":\"text\",\"text\":{}}}]}}}}\n",
+                    idx + 1,
+                    (idx + 13) % 60,
+                    json_string(&message.text)
+                ));
+            }
+            write_file(
+                &root.join(format!(
+                    "agent/sessions/--tmp-project--/2026-02-23T13-00-12-780Z_{id}.jsonl"
+                )),
+                &content,
+            );
+        }
+    }
+}
+
+/// A session with two branches off the first reply; `PI_ENTRY_ID` is the leaf of the first.
+pub fn setup_pi_tree() -> TempDir {
+    let temp = tempdir().expect("tempdir");
+    let thread_path = temp.path().join(
+        "agent/sessions/--Users-xuanwo-Code-pi-project--/2026-02-23T13-00-12-780Z_12cb4c19-2774-4de4-a0d0-9fa32fbae29f.jsonl",
+    );
+    fs::create_dir_all(thread_path.parent().expect("parent")).expect("mkdir");
+    fs::write(
+        &thread_path,
+        format!(
+            "{{\"type\":\"session\",\"version\":3,\"id\":\"{PI_SESSION_ID}\",\"timestamp\":\"2026-02-23T13:00:12.780Z\",\"cwd\":\"/tmp/project\"}}\n{{\"type\":\"message\",\"id\":\"a1b2c3d4\",\"parentId\":null,\"timestamp\":\"2026-02-23T13:00:13.000Z\",\"message\":{{\"role\":\"user\",\"content\":[{{\"type\":\"text\",\"text\":\"root\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"b1b2c3d4\",\"parentId\":\"a1b2c3d4\",\"timestamp\":\"2026-02-23T13:00:14.000Z\",\"message\":{{\"role\":\"assistant\",\"content\":[{{\"type\":\"text\",\"text\":\"root done\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"c1b2c3d4\",\"parentId\":\"b1b2c3d4\",\"timestamp\":\"2026-02-23T13:00:15.000Z\",\"message\":{{\"role\":\"user\",\"content\":[{{\"type\":\"text\",\"text\":\"branch one\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"d1b2c3d4\",\"parentId\":\"c1b2c3d4\",\"timestamp\":\"2026-02-23T13:00:16.000Z\",\"message\":{{\"role\":\"assistant\",\"content\":[{{\"type\":\"text\",\"text\":\"branch one done\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"e1b2c3d4\",\"parentId\":\"b1b2c3d4\",\"timestamp\":\"2026-02-23T13:00:17.000Z\",\"message\":{{\"role\":\"user\",\"content\":[{{\"type\":\"text\",\"text\":\"branch two\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"f1b2c3d4\",\"parentId\":\"e1b2c3d4\",\"timestamp\":\"2026-02-23T13:00:18.000Z\",\"message\":{{\"role\":\"assistant\",\"content\":[{{\"type\":\"text\",\"text\":\"branch two done\"}}]}}}}\n"
+        ),
+    )
+    .expect("write");
+    temp
+}
+
+/// A main session listing `PI_CHILD_SESSION_ID` (on disk) and `PI_MISSING_CHILD_SESSION_ID`
+/// (absent) as child sessions.
+pub fn setup_pi_tree_with_child_sessions() -> TempDir {
+    let temp = tempdir().expect("tempdir");
+    let main_thread_path = temp.path().join(
+        "agent/sessions/--Users-xuanwo-Code-pi-project--/2026-02-23T13-00-12-780Z_12cb4c19-2774-4de4-a0d0-9fa32fbae29f.jsonl",
+    );
+    fs::create_dir_all(main_thread_path.parent().expect("parent")).expect("mkdir");
+    fs::write(
+        &main_thread_path,
+        format!(
+            "{{\"type\":\"session\",\"version\":3,\"id\":\"{PI_SESSION_ID}\",\"timestamp\":\"2026-02-23T13:00:12.780Z\",\"cwd\":\"/tmp/project\",\"childSessionIds\":[\"{PI_CHILD_SESSION_ID}\",\"{PI_MISSING_CHILD_SESSION_ID}\"]}}\n{{\"type\":\"message\",\"id\":\"a1b2c3d4\",\"parentId\":null,\"timestamp\":\"2026-02-23T13:00:13.000Z\",\"message\":{{\"role\":\"user\",\"content\":[{{\"type\":\"text\",\"text\":\"root\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"b1b2c3d4\",\"parentId\":\"a1b2c3d4\",\"timestamp\":\"2026-02-23T13:00:14.000Z\",\"message\":{{\"role\":\"assistant\",\"content\":[{{\"type\":\"text\",\"text\":\"root done\"}}]}}}}\n"
+        ),
+    )
+    .expect("write main");
+
+    let child_thread_path = temp.path().join(format!(
+        "agent/sessions/--Users-xuanwo-Code-pi-project--/2026-02-23T13-10-12-780Z_{PI_CHILD_SESSION_ID}.jsonl"
+    ));
+    fs::create_dir_all(child_thread_path.parent().expect("parent")).expect("mkdir");
+    fs::write(
+        &child_thread_path,
+        format!(
+            "{{\"type\":\"session\",\"version\":3,\"id\":\"{PI_CHILD_SESSION_ID}\",\"timestamp\":\"2026-02-23T13:10:12.780Z\",\"cwd\":\"/tmp/project\",\"parent_session_id\":\"{PI_SESSION_ID}\"}}\n{{\"type\":\"message\",\"id\":\"b1c2d3e4\",\"parentId\":null,\"timestamp\":\"2026-02-23T13:10:13.000Z\",\"message\":{{\"role\":\"user\",\"content\":[{{\"type\":\"text\",\"text\":\"child prompt\"}}]}}}}\n{{\"type\":\"message\",\"id\":\"c1d2e3f4\",\"parentId\":\"b1c2d3e4\",\"timestamp\":\"2026-02-23T13:10:14.000Z\",\"message\":{{\"role\":\"assistant\",\"content\":[{{\"type\":\"text\",\"text\":\"child done\"}}]}}}}\n"
+        ),
+    )
+    .expect("write child");
+    temp
+}
@@ -0,0 +1,128 @@
+use std::path::Path;
+
+use xurl_core::{
+    AgentsUri, ProviderRoots, RenderOptions, ScanOptions, render_thread_markdown, resolve_thread,
+};
+use xurl_test_support::{
+    AmpTreeBuilder, ClaudeTreeBuilder, CodexTreeBuilder, GeminiTreeBuilder, Message,
+    OpencodeTreeBuilder, PiTreeBuilder,
+};
+
+fn roots(root: &Path) -> ProviderRoots {
+    ProviderRoots {
+        amp_root: root.join("amp"),
+        amp_cache_root: root.join("amp-cache"),
+        codex_root: root.to_path_buf(),
+        claude_root: root.to_path_buf(),
+        gemini_root: root.join(".gemini"),
+        pi_root: root.join("agent"),
+        opencode_root: root.join("opencode"),
+        skills_root: root.join("skills"),
+        skills_cache_root: root.join("skills-cache"),
+        snapshots_root: root.join("snapshots"),
+        scan: ScanOptions::default(),
+        strict: false,
+    }
+}
+
+fn render(root: &Path, uri: &str) -> String {
+    let uri = AgentsUri::parse(uri).expect("parse uri");
+    let resolved = resolve_thread(&uri, &roots(root)).expect("resolve thread");
+    render_thread_markdown(&uri, &resolved, &RenderOptions::default()).expect("render thread")
+}
+
+fn conversation() -> [Message; 2] {
+    [
+        Message::user("ping \"quoted\""),
+        Message::assistant("pong\nsecond line"),
+    ]
+}
+
+fn assert_conversation(markdown: &str) {
+    assert!(markdown.contains("ping \"quoted\""), "{markdown}");
+    assert!(markdown.contains("pong\nsecond line"), "{markdown}");
+}
+
+#[test]
+fn codex_builder_writes_resolvable_thread() {
+    let id = "019c871c-b1f9-7f60-9c4f-87ed09f13592";
+    let temp = CodexTreeBuilder::new()
+        .with_thread(id, &conversation())
+        .build();
+    assert_conversation(&render(temp.path(), &format!("agents://codex/{id}")));
+}
+
+#[test]
+fn codex_builder_names_rollouts_after_session_meta_and_appends_events() {
+    let id = "019c871c-b1f9-7f60-9c4f-87ed09f13592";
+    let temp = CodexTreeBuilder::new()
+        .with_thread(id, &conversation())
+        .with_session_meta("/work/app", "2026-02-22T09:30:00Z")
+        .with_event(r#"{"type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"an event"}]}}"#)
+        .build();
+    assert!(
+        temp.path()
+            .join(format!(
+                "sessions/2026/02/22/rollout-2026-02-22T09-30-00-{id}.jsonl"
+            ))
+            .is_file()
+    );
+    let markdown = render(temp.path(), &format!("agents://codex/{id}"));
+    assert_conversation(&markdown);
+    assert!(markdown.contains("an event"), "{markdown}");
+}
+
+#[test]
+fn claude_builder_writes_resolvable_thread() {
+    let id = "2823d1df-720a-4c31-ac55-ae8ba726721f";
+    let temp = ClaudeTreeBuilder::new()
+        .with_project("project-builder")
+        .with_thread(id, &conversation())
+        .build();
+    assert!(
+        temp.path()
+            .join(format!("projects/project-builder/{id}.jsonl"))
+            .exists()
+    );
+    assert_conversation(&render(temp.path(), &format!("agents://claude/{id}")));
+}
+
+#[test]
+fn gemini_builder_writes_resolvable_thread() {
+    let id = "29d207db-ca7e-40ba-87f7-e14c9de60613";
+    let temp = GeminiTreeBuilder::new()
+        .with_thread(id, &conversation())
+        .build();
+    assert_conversation(&render(temp.path(), &format!("agents://gemini/{id}")));
+}
+
+#[test]
+fn pi_builder_writes_resolvable_thread() {
+    let id = "12cb4c19-2774-4de4-a0d0-9fa32fbae29f";
+    let temp = PiTreeBuilder::new()
+        .with_thread(id, &conversation())
+        .build();
+    assert_conversation(&render(temp.path(), &format!("agents://pi/{id}")));
+    assert_conversation(&render(temp.path(), &format!("agents://pi/{id}/00000002")));
+}
+
+#[test]
+fn amp_builder_writes_resolvable_thread() {
+    let id = "T-019c0797-c402-7389-bd80-d785c98df295";
+    let temp = AmpTreeBuilder::new()
+        .with_thread(id, &conversation())
+        .build();
+    assert_conversation(&render(temp.path(), &format!("agents://amp/{id}")));
+}
+
+#[test]
+fn opencode_builder_writes_resolvable_threads() {
+    let main = "ses_5x7md9kx3c1p";
+    let child = "ses_5x7md9kx3c2p";
+    let temp = OpencodeTreeBuilder::new()
+        .with_thread(main, &[Message::user("main prompt")])
+        .with_child_thread(main, child, &conversation())
+        .build();
+    assert!(render(temp.path(), &format!("agents://opencode/{main}")).contains("main prompt"));
+    assert_conversation(&render(temp.path(), &format!("agents://opencode/{child}")));
+}